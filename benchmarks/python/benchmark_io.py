@@ -148,7 +148,7 @@ class ZDSNativeBenchmark:
         try:
             import orjson
             doc_ids = [r["id"] for r in data]
-            lines = [orjson.dumps(r) for r in data]
+            lines = [orjson.dumps({"_id": r["id"], **r}) for r in data]
             jsonl_blob = b'\n'.join(lines)
             
             start = time.perf_counter()
@@ -0,0 +1,593 @@
+//! `zippy serve --flight`: collections as Arrow Flight record batch streams.
+//!
+//! Flight descriptors and tickets name what to read:
+//!
+//! - a path descriptor `[collection]` or a plain ticket `collection` reads
+//!   the whole collection;
+//! - a command descriptor or ticket holding a JSON request reads a
+//!   projection/selection of it:
+//!
+//! ```json
+//! {"collection": "train", "fields": ["text", "label"],
+//!  "filter": "label = 1", "limit": 1000, "partitions": 4}
+//! ```
+//!
+//! `GetFlightInfo` splits a collection into `partitions` endpoints (one
+//! ticket per document range) so clients can pull them in parallel. The
+//! server is read-only; `DoPut`, `DoExchange` and actions are unimplemented.
+
+use std::{net::SocketAddr, ops::Range, path::Path, pin::Pin, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+use arrow::{datatypes::SchemaRef, ipc::writer::IpcWriteOptions};
+use arrow_flight::{
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
+    flight_service_server::{FlightService, FlightServiceServer},
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use futures::{stream, Stream, TryStreamExt};
+use serde_json::{json, Value};
+use tonic::{Request, Response, Status, Streaming};
+use zippy_data::{
+    arrow::{engine_schema, RecordBatchIter, DEFAULT_BATCH_SIZE},
+    engine::Engine,
+    layout::Layout,
+    ContainerFS,
+};
+
+type BoxStream<T> = Pin<Box<dyn Stream<Item = std::result::Result<T, Status>> + Send + 'static>>;
+
+/// Serve `path` over Arrow Flight on `host:port` until the process is stopped.
+pub fn run(path: &Path, host: &str, port: u16) -> Result<()> {
+    ContainerFS::open(path)?;
+
+    let addr: SocketAddr = format!("{}:{}", host, port)
+        .parse()
+        .with_context(|| format!("Invalid address {}:{}", host, port))?;
+    let service = ZdsFlightService {
+        path: Arc::new(path.to_path_buf()),
+    };
+
+    println!(
+        "Serving {} over Arrow Flight on grpc://{}",
+        path.display(),
+        addr
+    );
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime
+        .block_on(
+            tonic::transport::Server::builder()
+                .add_service(FlightServiceServer::new(service))
+                .serve(addr),
+        )
+        .map_err(|e| anyhow!("Flight server failed: {}", e))
+}
+
+/// A read request decoded from a descriptor or ticket.
+struct FlightRequest {
+    collection: String,
+    fields: Option<Vec<String>>,
+    filter: Option<String>,
+    limit: Option<usize>,
+    partitions: usize,
+    /// Document positions to read (set on tickets issued by `GetFlightInfo`).
+    range: Option<Range<usize>>,
+}
+
+impl FlightRequest {
+    /// Decode a JSON request, or a bare collection name.
+    fn parse(bytes: &[u8]) -> FlightResult<Self> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| RequestError::invalid("Request is not valid UTF-8"))?;
+        if !text.trim_start().starts_with('{') {
+            return Ok(Self::collection(text.trim()));
+        }
+
+        let value: Value = serde_json::from_str(text)
+            .map_err(|e| RequestError::invalid(format!("Invalid JSON request: {}", e)))?;
+        let collection = value
+            .get("collection")
+            .and_then(Value::as_str)
+            .ok_or_else(|| RequestError::invalid("Request is missing \"collection\""))?;
+        let fields = match value.get("fields") {
+            None | Some(Value::Null) => None,
+            Some(Value::Array(items)) => Some(
+                items
+                    .iter()
+                    .map(|f| f.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| RequestError::invalid("\"fields\" must be strings"))?,
+            ),
+            Some(_) => return Err(RequestError::invalid("\"fields\" must be an array")),
+        };
+        let filter = match value.get("filter") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(filter) => Some(filter.to_string()),
+        };
+        let range = match value.get("range") {
+            None | Some(Value::Null) => None,
+            Some(range) => {
+                let bound = |i: usize| range.get(i).and_then(Value::as_u64).map(|n| n as usize);
+                match (bound(0), bound(1)) {
+                    (Some(start), Some(end)) => Some(start..end),
+                    _ => return Err(RequestError::invalid("\"range\" must be [start, end]")),
+                }
+            }
+        };
+
+        Ok(FlightRequest {
+            collection: collection.to_string(),
+            fields,
+            filter,
+            limit: uint(&value, "limit")?,
+            partitions: uint(&value, "partitions")?.unwrap_or(1).max(1),
+            range,
+        })
+    }
+
+    fn collection(name: &str) -> Self {
+        FlightRequest {
+            collection: name.to_string(),
+            fields: None,
+            filter: None,
+            limit: None,
+            partitions: 1,
+            range: None,
+        }
+    }
+
+    fn from_descriptor(descriptor: &FlightDescriptor) -> FlightResult<Self> {
+        match descriptor.path.as_slice() {
+            [] => Self::parse(&descriptor.cmd),
+            [collection] => Ok(Self::collection(collection)),
+            _ => Err(RequestError::invalid(
+                "Descriptor path must be a single collection name",
+            )),
+        }
+    }
+
+    /// Ticket reading `range` of this request.
+    fn ticket(&self, range: Range<usize>) -> Ticket {
+        let request = json!({
+            "collection": self.collection,
+            "fields": self.fields,
+            "filter": self.filter,
+            "limit": self.limit,
+            "range": [range.start, range.end],
+        });
+        Ticket::new(request.to_string())
+    }
+
+    fn field_refs(&self) -> Option<Vec<&str>> {
+        self.fields
+            .as_ref()
+            .map(|f| f.iter().map(|s| s.as_str()).collect())
+    }
+}
+
+fn uint(value: &Value, key: &str) -> FlightResult<Option<usize>> {
+    match value.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .map(|n| Some(n as usize))
+            .ok_or_else(|| RequestError::invalid(format!("\"{}\" must be a number", key))),
+    }
+}
+
+/// A failed Flight request: a malformed descriptor/ticket or a store error.
+enum RequestError {
+    Invalid(String),
+    Store(zippy_data::Error),
+}
+
+impl RequestError {
+    fn invalid(message: impl Into<String>) -> Self {
+        RequestError::Invalid(message.into())
+    }
+}
+
+impl From<zippy_data::Error> for RequestError {
+    fn from(e: zippy_data::Error) -> Self {
+        RequestError::Store(e)
+    }
+}
+
+impl From<RequestError> for Status {
+    fn from(e: RequestError) -> Self {
+        use zippy_data::Error;
+        match e {
+            RequestError::Invalid(message) => Status::invalid_argument(message),
+            RequestError::Store(e) => match &e {
+                Error::CollectionNotFound(_) | Error::DocumentNotFound(_) => {
+                    Status::not_found(e.to_string())
+                }
+                Error::Io(io) if io.kind() == std::io::ErrorKind::NotFound => {
+                    Status::not_found(e.to_string())
+                }
+                Error::Validation(_) | Error::Codec(_) => Status::invalid_argument(e.to_string()),
+                _ => Status::internal(e.to_string()),
+            },
+        }
+    }
+}
+
+type FlightResult<T> = std::result::Result<T, RequestError>;
+
+struct ZdsFlightService {
+    path: Arc<std::path::PathBuf>,
+}
+
+impl ZdsFlightService {
+    /// Open an existing collection; names come from clients, so they are
+    /// checked before they touch the filesystem.
+    fn open(&self, collection: &str) -> FlightResult<Engine> {
+        Layout::validate_collection_name(collection)?;
+        let container = ContainerFS::open(self.path.as_ref())?;
+        if !container
+            .list_collections()?
+            .iter()
+            .any(|c| c == collection)
+        {
+            return Err(zippy_data::Error::CollectionNotFound(collection.to_string()).into());
+        }
+        Ok(Engine::open(self.path.as_ref(), collection)?)
+    }
+
+    fn flight_info(
+        &self,
+        request: &FlightRequest,
+        descriptor: FlightDescriptor,
+    ) -> FlightResult<FlightInfo> {
+        let engine = self.open(&request.collection)?;
+        let schema = engine_schema(&engine, request.field_refs().as_deref())?;
+
+        // A limit spans the whole collection, so it is served as one stream
+        let partitions = if request.limit.is_some() {
+            std::iter::once(0..engine.len()).collect()
+        } else {
+            engine.partitions(request.partitions)
+        };
+        let total_records = match (&request.filter, request.limit) {
+            (None, None) => engine.len() as i64,
+            _ => -1,
+        };
+
+        let mut info = FlightInfo::new()
+            .try_with_schema(&schema)
+            .map_err(|e| RequestError::Store(e.into()))?
+            .with_descriptor(descriptor)
+            .with_total_records(total_records)
+            .with_ordered(true);
+        for range in partitions {
+            info = info.with_endpoint(FlightEndpoint::new().with_ticket(request.ticket(range)));
+        }
+        Ok(info)
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for ZdsFlightService {
+    type HandshakeStream = BoxStream<HandshakeResponse>;
+    type ListFlightsStream = BoxStream<FlightInfo>;
+    type DoGetStream = BoxStream<FlightData>;
+    type DoPutStream = BoxStream<PutResult>;
+    type DoActionStream = BoxStream<arrow_flight::Result>;
+    type ListActionsStream = BoxStream<ActionType>;
+    type DoExchangeStream = BoxStream<FlightData>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> std::result::Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Handshake is not required"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> std::result::Result<Response<Self::ListFlightsStream>, Status> {
+        let container = ContainerFS::open(self.path.as_ref()).map_err(RequestError::from)?;
+        let collections = container.list_collections().map_err(RequestError::from)?;
+
+        let mut infos = Vec::with_capacity(collections.len());
+        for collection in collections {
+            let descriptor = FlightDescriptor::new_path(vec![collection.clone()]);
+            let info = self.flight_info(&FlightRequest::collection(&collection), descriptor);
+            infos.push(info.map_err(Status::from));
+        }
+        Ok(Response::new(Box::pin(stream::iter(infos))))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        let descriptor = request.into_inner();
+        let flight_request = FlightRequest::from_descriptor(&descriptor)?;
+        Ok(Response::new(
+            self.flight_info(&flight_request, descriptor)?,
+        ))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("Use GetFlightInfo"))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<SchemaResult>, Status> {
+        let flight_request = FlightRequest::from_descriptor(request.get_ref())?;
+        let engine = self.open(&flight_request.collection)?;
+        let schema = engine_schema(&engine, flight_request.field_refs().as_deref())
+            .map_err(RequestError::from)?;
+        let options = IpcWriteOptions::default();
+        let result = SchemaResult::try_from(SchemaAsIpc::new(&schema, &options))
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(result))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> std::result::Result<Response<Self::DoGetStream>, Status> {
+        let flight_request = FlightRequest::parse(&request.get_ref().ticket)?;
+        let predicate = flight_request
+            .filter
+            .as_deref()
+            .map(crate::parse_filter)
+            .transpose()
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+
+        let engine = self.open(&flight_request.collection)?;
+        let fields = flight_request.field_refs();
+        let schema = engine_schema(&engine, fields.as_deref()).map_err(RequestError::from)?;
+        let range = flight_request.range.clone().unwrap_or(0..engine.len());
+        let scanner = engine
+            .scan_range(range, predicate.as_ref(), fields.as_deref())
+            .map_err(RequestError::from)?;
+        let limit = flight_request.limit.unwrap_or(usize::MAX);
+
+        // Documents are decoded on a blocking thread and handed over a small
+        // channel, so a slow client applies backpressure to the scan
+        let (tx, rx) = tokio::sync::mpsc::channel(2);
+        let batch_schema: SchemaRef = schema.clone();
+        tokio::task::spawn_blocking(move || {
+            let batches =
+                RecordBatchIter::new(scanner.take(limit), batch_schema, DEFAULT_BATCH_SIZE);
+            for batch in batches {
+                if tx.blocking_send(batch.map_err(FlightError::from)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let batches = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|b| (b, rx)) });
+        let flight_data = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(batches)
+            .map_err(|e| Status::internal(e.to_string()));
+        Ok(Response::new(Box::pin(flight_data)))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("The Flight server is read-only"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> std::result::Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("No actions are supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> std::result::Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(Box::pin(stream::empty())))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("The Flight server is read-only"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::record_batch::RecordBatch;
+    use arrow_flight::decode::FlightRecordBatchStream;
+    use tempfile::TempDir;
+    use zippy_data::{arrow::record_batch_to_docs, FastStore};
+
+    use super::*;
+
+    /// A service over a store with a collection of `n` documents
+    /// `{"n": i, "text": "doc i"}`.
+    fn service(n: usize) -> (TempDir, ZdsFlightService) {
+        let dir = TempDir::new().unwrap();
+        ContainerFS::create_folder(dir.path()).unwrap();
+        let mut store = FastStore::open(dir.path(), "train", 100).unwrap();
+        for i in 0..n {
+            let doc = json!({"n": i, "text": format!("doc {}", i)});
+            store.put(format!("doc{:03}", i), doc).unwrap();
+        }
+        store.flush().unwrap();
+        let service = ZdsFlightService {
+            path: Arc::new(dir.path().to_path_buf()),
+        };
+        (dir, service)
+    }
+
+    /// Read every endpoint of the flight `descriptor` names, in order.
+    async fn read(service: &ZdsFlightService, descriptor: FlightDescriptor) -> Vec<Value> {
+        let info = service
+            .get_flight_info(Request::new(descriptor))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut docs = Vec::new();
+        for endpoint in info.endpoint {
+            let ticket = endpoint.ticket.unwrap();
+            let stream = service
+                .do_get(Request::new(ticket))
+                .await
+                .unwrap()
+                .into_inner();
+            let batches: Vec<RecordBatch> =
+                FlightRecordBatchStream::new_from_flight_data(stream.map_err(FlightError::from))
+                    .try_collect()
+                    .await
+                    .unwrap();
+            for batch in &batches {
+                docs.extend(record_batch_to_docs(batch).unwrap());
+            }
+        }
+        docs
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let request = FlightRequest::parse(b" train ").ok().unwrap();
+        assert_eq!(request.collection, "train");
+        assert_eq!(request.partitions, 1);
+
+        let request = FlightRequest::parse(
+            br#"{"collection": "train", "fields": ["text"], "filter": {"n": 1},
+                 "limit": 5, "partitions": 0, "range": [2, 4]}"#,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(request.fields.unwrap(), ["text"]);
+        assert_eq!(request.filter.unwrap(), r#"{"n":1}"#);
+        assert_eq!(request.limit, Some(5));
+        assert_eq!(request.partitions, 1);
+        assert_eq!(request.range, Some(2..4));
+
+        for bad in [
+            &b"\xff"[..],
+            br#"{"fields": ["text"]}"#,
+            br#"{"collection": "train", "fields": "text"}"#,
+            br#"{"collection": "train", "limit": -1}"#,
+            br#"{"collection": "train", "range": [1]}"#,
+        ] {
+            assert!(matches!(
+                FlightRequest::parse(bad),
+                Err(RequestError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_get_partitions() {
+        let (_dir, service) = service(10);
+        block_on(async {
+            let descriptor = FlightDescriptor::new_cmd(
+                json!({"collection": "train", "partitions": 3}).to_string(),
+            );
+            let info = service
+                .get_flight_info(Request::new(descriptor.clone()))
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(info.endpoint.len(), 3);
+            assert_eq!(info.total_records, 10);
+
+            // The partitions together cover the collection, in order
+            let docs = read(&service, descriptor).await;
+            let ns: Vec<_> = docs.iter().map(|d| d["n"].as_i64().unwrap()).collect();
+            assert_eq!(ns, (0..10).collect::<Vec<_>>());
+
+            let docs = read(&service, FlightDescriptor::new_path(vec!["train".into()])).await;
+            assert_eq!(docs.len(), 10);
+        });
+    }
+
+    #[test]
+    fn test_get_projection_and_selection() {
+        let (_dir, service) = service(10);
+        block_on(async {
+            let descriptor = FlightDescriptor::new_cmd(
+                json!({
+                    "collection": "train",
+                    "fields": ["text"],
+                    "filter": "n >= 4",
+                    "limit": 3,
+                    "partitions": 4,
+                })
+                .to_string(),
+            );
+            let info = service
+                .get_flight_info(Request::new(descriptor.clone()))
+                .await
+                .unwrap()
+                .into_inner();
+            // A limit is served as one stream of unknown length
+            assert_eq!(info.endpoint.len(), 1);
+            assert_eq!(info.total_records, -1);
+
+            let docs = read(&service, descriptor).await;
+            assert_eq!(
+                docs,
+                [
+                    json!({"text": "doc 4"}),
+                    json!({"text": "doc 5"}),
+                    json!({"text": "doc 6"})
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_errors() {
+        let (_dir, service) = service(1);
+        block_on(async {
+            let missing = FlightDescriptor::new_path(vec!["missing".into()]);
+            let status = service
+                .get_flight_info(Request::new(missing))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::NotFound);
+
+            let nested = FlightDescriptor::new_path(vec!["a".into(), "b".into()]);
+            let status = service
+                .get_flight_info(Request::new(nested))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+            let ticket = Ticket::new("../train");
+            let status = service.do_get(Request::new(ticket)).await.err().unwrap();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+            let ticket = Ticket::new(json!({"collection": "train", "filter": "n >"}).to_string());
+            let status = service.do_get(Request::new(ticket)).await.err().unwrap();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+            let status = service
+                .do_action(Request::new(Action::new("drop", "")))
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(status.code(), tonic::Code::Unimplemented);
+        });
+    }
+}
@@ -1,18 +1,54 @@
 //! Zippy CLI - ZDS dataset management tool
 
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::File,
+    hash::Hasher,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use arrow::datatypes::{DataType, Field, Schema};
+use clap::{Parser, Subcommand, ValueEnum};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
 use zippy_data::{
-    container::{pack, unpack},
-    engine::Engine,
+    aggregate::{Aggregate, GroupBy},
+    arrow::{full_engine_schema, record_batch_to_docs, RecordBatchIter, DEFAULT_BATCH_SIZE},
+    codec::{Codec, Predicate},
+    columns::ColumnCache,
+    compression::Compression,
+    config::{CollectionConfig, KeyOrder},
+    container::{
+        pack_incremental_with, pack_with, unpack_with, ArchiveCompression, PackOptions,
+        PackProgress,
+    },
+    encryption,
+    engine::{Engine, StorageMode},
+    ids::IdStrategy,
     index::IndexRegistry,
+    json_schema::JsonSchema,
     layout::Layout,
-    writer::SyncWriter,
-    ContainerFS,
+    store::{open_read_store, open_store, Store},
+    ContainerFS, EncryptionKey, FastStore, FieldIndexes, JournalEntry, OpenMode, ReadLock,
+    Snapshot, SyncOptions, SyncProgress, WriteLock, ZDSRoot,
 };
 
+#[cfg(feature = "flight")]
+mod flight;
+#[cfg(feature = "serve")]
+mod serve;
+
+/// Column holding document IDs in exported files.
+const ID_COLUMN: &str = "_id";
+
+/// Default number of rows buffered per bulk write during imports.
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// Widest cell printed by `query --format table` (longer values are cut).
+const MAX_CELL_WIDTH: usize = 40;
+
 #[derive(Parser)]
 #[command(name = "zippy")]
 #[command(author, version, about = "ZDS (Zippy Data System) CLI", long_about = None)]
@@ -21,6 +57,35 @@ struct Cli {
     command: Commands,
 }
 
+/// External file formats for import/export.
+#[derive(Clone, Copy, ValueEnum)]
+enum DataFormat {
+    /// Apache Parquet (columns typed from the schema registry)
+    Parquet,
+}
+
+/// Output formats for `query` and `agg`.
+#[derive(Clone, Copy, ValueEnum)]
+enum QueryFormat {
+    /// One JSON document per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Aligned text columns
+    Table,
+}
+
+/// How `merge` resolves a document ID found in several sources.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum MergeConflict {
+    /// Keep the newest version (by --version-field, else modification time)
+    Latest,
+    /// Stop with an error
+    Error,
+    /// Keep the first version seen
+    Skip,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new ZDS store
@@ -35,6 +100,28 @@ enum Commands {
         /// Enable strict schema mode
         #[arg(long)]
         strict: bool,
+
+        /// Data file compression (none, zstd)
+        #[arg(long, default_value = "none")]
+        compression: Compression,
+
+        /// Document storage (jsonl, or docs for one file per document);
+        /// set by the first writer if not given
+        #[arg(long)]
+        storage: Option<StorageMode>,
+
+        /// Shard count for parallel ingestion
+        #[arg(long)]
+        shards: Option<usize>,
+
+        /// Dead-byte ratio (0-1] at which fast stores compact on flush
+        #[arg(long)]
+        auto_compact: Option<f64>,
+
+        /// Document ID order kept in memory (hashed, or sorted for
+        /// collections read by ID prefix or range)
+        #[arg(long, default_value = "hashed")]
+        key_order: KeyOrder,
     },
 
     /// Validate a ZDS store
@@ -49,6 +136,15 @@ enum Commands {
         /// Rebuild indexes if invalid
         #[arg(long)]
         fix: bool,
+
+        /// Check documents against this JSON Schema file (draft 2020-12)
+        /// instead of the collection's attached schema
+        #[arg(long)]
+        schema: Option<PathBuf>,
+
+        /// Attach the schema to collections whose documents all match it
+        #[arg(long, requires = "schema")]
+        attach: bool,
     },
 
     /// Show statistics for a ZDS store
@@ -72,6 +168,24 @@ enum Commands {
 
         /// Destination .zds file path
         dest: PathBuf,
+
+        /// File compression (stored, deflate); zstd data segments are
+        /// always stored as they are
+        #[arg(long, default_value = "deflate")]
+        compression: ArchiveCompression,
+
+        /// DEFLATE level (0-9)
+        #[arg(long, value_parser = clap::value_parser!(i32).range(0..=9))]
+        level: Option<i32>,
+
+        /// Show progress on stderr
+        #[arg(long)]
+        progress: bool,
+
+        /// Update an existing archive, re-compressing only files that
+        /// changed since it was packed
+        #[arg(long)]
+        incremental: bool,
     },
 
     /// Unpack a .zds archive into a folder
@@ -81,6 +195,10 @@ enum Commands {
 
         /// Destination folder path
         dest: PathBuf,
+
+        /// Show progress on stderr
+        #[arg(long)]
+        progress: bool,
     },
 
     /// List collections in a store
@@ -116,11 +234,33 @@ enum Commands {
         collection: String,
 
         /// Document ID
-        doc_id: String,
+        #[arg(required_unless_present = "auto_id", conflicts_with = "auto_id")]
+        doc_id: Option<String>,
 
         /// JSON document (reads from stdin if not provided)
         #[arg(long)]
         data: Option<String>,
+
+        /// Generate the document ID (ulid, uuid or seq; default ulid)
+        #[arg(long, value_name = "STRATEGY", num_args = 0..=1, default_missing_value = "ulid")]
+        auto_id: Option<IdStrategy>,
+    },
+
+    /// Patch a document with a JSON merge patch (from stdin or argument)
+    Patch {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Document ID
+        doc_id: String,
+
+        /// JSON merge patch (reads from stdin if not provided)
+        #[arg(long)]
+        data: Option<String>,
     },
 
     /// Delete a document
@@ -136,6 +276,23 @@ enum Commands {
         doc_id: String,
     },
 
+    /// Show the journaled writes and deletes of a document
+    History {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Document ID
+        doc_id: String,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Scan and output documents
     Scan {
         /// Path to the ZDS store
@@ -153,371 +310,3274 @@ enum Commands {
         #[arg(long)]
         fields: Option<String>,
 
+        /// Only output documents matching a filter expression
+        /// (e.g. "age >= 18 AND status = 'active'") or JSON filter
+        /// (e.g. '{"age": {"$gte": 18}}')
+        #[arg(long)]
+        filter: Option<String>,
+
         /// Output as JSON lines
         #[arg(long)]
         jsonl: bool,
     },
 
-    /// Rebuild indexes from disk
-    Reindex {
+    /// Query a collection, printing matching documents as JSONL, CSV or a table
+    Query {
         /// Path to the ZDS store
         path: PathBuf,
 
         /// Collection name
         #[arg(short, long, default_value = "default")]
         collection: String,
-    },
-}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+        /// Filter expression (e.g. "type = 'purchase' AND value > 100")
+        /// or JSON filter (e.g. '{"value": {"$gt": 100}}')
+        #[arg(long)]
+        filter: Option<String>,
 
-    match cli.command {
-        Commands::Init {
-            path,
-            collection,
-            strict,
-        } => {
-            cmd_init(&path, &collection, strict)?;
-        }
-        Commands::Validate {
-            path,
-            collection,
-            fix,
-        } => {
-            cmd_validate(&path, collection.as_deref(), fix)?;
-        }
-        Commands::Stats {
-            path,
-            collection,
-            json,
-        } => {
-            cmd_stats(&path, collection.as_deref(), json)?;
-        }
-        Commands::Pack { source, dest } => {
-            cmd_pack(&source, &dest)?;
-        }
-        Commands::Unpack { source, dest } => {
-            cmd_unpack(&source, &dest)?;
-        }
-        Commands::List { path } => {
-            cmd_list(&path)?;
-        }
-        Commands::Get {
-            path,
-            collection,
-            doc_id,
-            pretty,
-        } => {
-            cmd_get(&path, &collection, &doc_id, pretty)?;
-        }
-        Commands::Put {
-            path,
-            collection,
-            doc_id,
-            data,
-        } => {
-            cmd_put(&path, &collection, &doc_id, data)?;
-        }
-        Commands::Delete {
-            path,
-            collection,
-            doc_id,
-        } => {
-            cmd_delete(&path, &collection, &doc_id)?;
-        }
-        Commands::Scan {
-            path,
-            collection,
-            limit,
-            fields,
-            jsonl,
-        } => {
-            cmd_scan(&path, &collection, limit, fields, jsonl)?;
-        }
-        Commands::Reindex { path, collection } => {
-            cmd_reindex(&path, &collection)?;
-        }
-    }
+        /// Fields to output (comma-separated, dot notation for nested
+        /// fields, `_id` for the document ID)
+        #[arg(long)]
+        fields: Option<String>,
 
-    Ok(())
-}
+        /// Output format
+        #[arg(long, value_enum, default_value_t = QueryFormat::Jsonl)]
+        format: QueryFormat,
 
-fn cmd_init(path: &PathBuf, collection: &str, strict: bool) -> Result<()> {
-    println!("Initializing ZDS store at: {}", path.display());
+        /// Maximum number of documents to output
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
 
-    ContainerFS::create_folder(path).context("Failed to create store")?;
-    Layout::init_collection(path, collection).context("Failed to create collection")?;
+    /// Count documents and sum, min, max or average fields, optionally per group
+    Agg {
+        /// Path to the ZDS store
+        path: PathBuf,
 
-    // Create manifest
-    let manifest = zippy_data::engine::Manifest::new(collection, strict);
-    let manifest_path = Layout::manifest_file(path, collection);
-    let manifest_json = serde_json::to_string_pretty(&manifest)?;
-    std::fs::write(&manifest_path, manifest_json)?;
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
 
-    // Create empty index files
-    let order_path = Layout::order_file(path, collection);
-    std::fs::write(&order_path, "")?;
+        /// Aggregates to compute: `count`, `sum:<field>`, `min:<field>`,
+        /// `max:<field>` or `avg:<field>` (default: count)
+        aggregates: Vec<Aggregate>,
 
-    let index_path = Layout::doc_index(path, collection);
-    std::fs::write(&index_path, "")?;
+        /// Field to group by (dot notation for nested fields)
+        #[arg(long)]
+        group_by: Option<String>,
 
-    let schema_path = Layout::schema_registry(path, collection);
-    std::fs::write(&schema_path, "")?;
+        /// Output format
+        #[arg(long, value_enum, default_value_t = QueryFormat::Table)]
+        format: QueryFormat,
+    },
 
-    println!("✓ Created store with collection '{}'", collection);
-    if strict {
-        println!("  Mode: strict (single schema enforced)");
-    } else {
-        println!("  Mode: flexible (multiple schemas allowed)");
-    }
+    /// Compare a collection between two stores (exits with 1 if they differ)
+    Diff {
+        /// Store to compare from
+        store_a: PathBuf,
 
-    Ok(())
-}
+        /// Store to compare to
+        store_b: PathBuf,
 
-fn cmd_validate(path: &PathBuf, collection: Option<&str>, fix: bool) -> Result<()> {
-    println!("Validating ZDS store at: {}", path.display());
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
 
-    Layout::validate(path).context("Invalid store structure")?;
-    println!("✓ Store structure valid");
+        /// Write a JSONL patch that turns the first collection into the second
+        #[arg(long)]
+        patch: Option<PathBuf>,
+    },
 
-    let container = ContainerFS::open(path)?;
-    let collections = match collection {
-        Some(c) => vec![c.to_string()],
-        None => container.list_collections()?,
-    };
+    /// Merge a collection from several stores into a new fast store collection
+    Merge {
+        /// Stores to read, in order
+        #[arg(required = true)]
+        sources: Vec<PathBuf>,
 
-    for coll in &collections {
-        print!("  Collection '{}': ", coll);
+        /// Store to write the merged collection to
+        #[arg(long)]
+        dest: PathBuf,
 
-        if let Err(e) = Layout::validate_collection(path, coll) {
-            println!("✗ {}", e);
-            continue;
-        }
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
 
-        // Check index consistency
-        let disk_index = IndexRegistry::rebuild(path, coll)?;
-        let stored_index = IndexRegistry::load(path, coll).unwrap_or_default();
+        /// What to do with a document ID found in several sources
+        #[arg(long, value_enum, default_value_t = MergeConflict::Latest)]
+        on_conflict: MergeConflict,
 
-        if disk_index.len() != stored_index.len() {
-            println!(
-                "⚠ Index mismatch (disk: {}, stored: {})",
-                disk_index.len(),
-                stored_index.len()
-            );
-            if fix {
-                disk_index.save(path, coll)?;
-                println!("    ✓ Index rebuilt");
-            }
-        } else {
-            println!("✓ {} documents", disk_index.len());
-        }
-    }
+        /// Field holding document versions for `latest` (dot notation for
+        /// nested fields); modification times are used if not specified
+        #[arg(long)]
+        version_field: Option<String>,
+    },
 
-    Ok(())
-}
+    /// Copy new and changed documents from one store to another (fast
+    /// store collections)
+    Sync {
+        /// Store to copy from (local path, or s3:// or gs:// URL with the
+        /// remote feature)
+        source: PathBuf,
 
-fn cmd_stats(path: &PathBuf, collection: Option<&str>, json_output: bool) -> Result<()> {
-    let container = ContainerFS::open(path)?;
-    let collections = match collection {
-        Some(c) => vec![c.to_string()],
-        None => container.list_collections()?,
-    };
+        /// Local store to copy to (created if missing)
+        dest: PathBuf,
 
-    if json_output {
-        let mut stats = Vec::new();
-        for coll in &collections {
-            let engine = Engine::open(path, coll)?;
-            let s = engine.stats();
-            stats.push(serde_json::json!({
-                "collection": s.collection,
-                "doc_count": s.doc_count,
-                "schema_count": s.schema_count,
-                "total_size": s.total_size,
-                "strict_mode": s.strict_mode,
-            }));
-        }
-        println!("{}", serde_json::to_string_pretty(&stats)?);
-    } else {
-        println!("ZDS Store: {}", path.display());
-        println!();
+        /// Collection to sync (repeatable); every collection if not specified
+        #[arg(short, long)]
+        collection: Vec<String>,
 
-        for coll in &collections {
-            let engine = Engine::open(path, coll)?;
-            let stats = engine.stats();
+        /// Remove documents the source doesn't have
+        #[arg(long)]
+        delete: bool,
 
-            println!("Collection: {}", stats.collection);
-            println!("  Documents:    {}", stats.doc_count);
-            println!("  Schemas:      {}", stats.schema_count);
-            println!("  Total size:   {} bytes", stats.total_size);
-            println!(
-                "  Mode:         {}",
-                if stats.strict_mode {
-                    "strict"
-                } else {
-                    "flexible"
-                }
-            );
-            println!();
-        }
-    }
+        /// Only report what would change
+        #[arg(long)]
+        dry_run: bool,
 
-    Ok(())
-}
+        /// Show progress on stderr
+        #[arg(long)]
+        progress: bool,
+    },
 
-fn cmd_pack(source: &Path, dest: &Path) -> Result<()> {
-    println!("Packing {} → {}", source.display(), dest.display());
+    /// Split a collection into train/test/validation collections by a hash
+    /// of the document IDs
+    Split {
+        /// Path to the ZDS store
+        path: PathBuf,
 
-    Layout::validate(source).context("Invalid source store")?;
-    pack(source, dest).context("Failed to pack archive")?;
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
 
-    let size = std::fs::metadata(dest)?.len();
-    println!("✓ Created archive ({} bytes)", size);
+        /// Fraction of documents for the training split
+        #[arg(long, default_value_t = 0.8)]
+        train: f64,
 
-    Ok(())
-}
+        /// Fraction of documents for the test split
+        #[arg(long, default_value_t = 0.1)]
+        test: f64,
 
-fn cmd_unpack(source: &Path, dest: &Path) -> Result<()> {
-    println!("Unpacking {} → {}", source.display(), dest.display());
+        /// Fraction of documents for the validation split
+        #[arg(long, default_value_t = 0.1)]
+        val: f64,
 
-    unpack(source, dest).context("Failed to unpack archive")?;
-    Layout::validate(dest).context("Unpacked store is invalid")?;
+        /// Seed for the ID hash (the same seed always gives the same split)
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
 
-    println!("✓ Archive unpacked");
+        /// Write `train`, `test` and `val` collections to this store instead
+        /// of `<collection>_train`, ... next to the source
+        #[arg(long)]
+        dest: Option<PathBuf>,
+
+        /// Documents per bulk write
+        #[arg(long, default_value_t = IMPORT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+
+    /// Output a reproducible random sample of documents as JSON lines
+    Sample {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Number of documents to sample
+        #[arg(short = 'n', long, default_value_t = 10)]
+        count: usize,
+
+        /// Seed of the sample (the same seed gives the same documents)
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+
+    /// Export a collection to an external file format
+    Export {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Output file path
+        output: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DataFormat::Parquet)]
+        format: DataFormat,
+    },
+
+    /// Import documents from an external file format
+    Import {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Input file path
+        input: PathBuf,
+
+        /// Input format
+        #[arg(long, value_enum, default_value_t = DataFormat::Parquet)]
+        format: DataFormat,
+
+        /// Column holding document IDs (a ULID is generated when missing)
+        #[arg(long, default_value = ID_COLUMN)]
+        id_column: String,
+    },
+
+    /// Import a CSV file into a collection (fast store)
+    ImportCsv {
+        /// CSV file to import (first row is the header)
+        file: PathBuf,
+
+        /// Path to the ZDS store
+        #[arg(long)]
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Column holding document IDs (ULIDs are generated if not specified)
+        #[arg(long)]
+        id_column: Option<String>,
+
+        /// Rows per flush
+        #[arg(long, default_value_t = IMPORT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+
+    /// Import a JSONL file into a collection
+    ImportJsonl {
+        /// JSONL file to import
+        file: PathBuf,
+
+        /// Path to the ZDS store
+        #[arg(long)]
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Field holding document IDs (uses `_id`, then generated ULIDs, if not specified)
+        #[arg(long)]
+        id_field: Option<String>,
+
+        /// Lines per flush
+        #[arg(long, default_value_t = IMPORT_BATCH_SIZE)]
+        batch_size: usize,
+    },
+
+    /// Export a collection (fast store) as JSONL
+    ExportJsonl {
+        /// Output file (writes to stdout if not specified)
+        output: Option<PathBuf>,
+
+        /// Path to the ZDS store
+        #[arg(long)]
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+    },
+
+    /// Manage secondary field indexes
+    Index {
+        #[command(subcommand)]
+        action: IndexCommand,
+    },
+
+    /// Drop, rename or copy collections
+    Collection {
+        #[command(subcommand)]
+        action: CollectionCommand,
+    },
+
+    /// Create, list, restore or delete collection snapshots (fast store)
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommand,
+    },
+
+    /// Compact a collection (fast store), dropping deleted, overwritten and
+    /// expired lines
+    Compact {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Only report how many bytes compaction would reclaim
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Encrypt or decrypt a collection at rest (fast store)
+    ///
+    /// The key is read from ZDS_ENCRYPTION_KEY (hex) or from the file named
+    /// by ZDS_ENCRYPTION_KEY_FILE.
+    Encrypt {
+        /// Path to the ZDS store (omit with --generate-key)
+        #[arg(required_unless_present = "generate_key")]
+        path: Option<PathBuf>,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Rewrite the collection in the clear
+        #[arg(long, conflicts_with = "generate_key")]
+        decrypt: bool,
+
+        /// Print a new random key as hex and exit
+        #[arg(long)]
+        generate_key: bool,
+    },
+
+    /// Rename or drop fields in every document (file-per-document
+    /// collections), resuming an interrupted run
+    Migrate {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// jq-style script of edits: `.new = .old` and `del(.field)` steps,
+        /// separated by `|` or newlines
+        #[arg(long)]
+        script: Option<PathBuf>,
+
+        /// Rename a field (dot notation for nested fields)
+        #[arg(long, value_name = "OLD=NEW")]
+        rename: Vec<String>,
+
+        /// Drop a field (dot notation for nested fields)
+        #[arg(long, value_name = "FIELD")]
+        drop: Vec<String>,
+    },
+
+    /// Rebuild indexes from disk
+    Reindex {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+    },
+
+    /// Remove a write lock left by a crashed process
+    Unlock {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Remove the lock even if its holder may still be running
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Serve a store over an HTTP JSON API
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Address to bind
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// Reject writes (PUT and DELETE return 403)
+        #[arg(long)]
+        read_only: bool,
+
+        /// Worker threads handling requests
+        #[arg(long, default_value_t = 8)]
+        workers: usize,
+
+        /// Largest request body accepted, in bytes (larger ones get 413)
+        #[arg(long, default_value_t = 16 * 1024 * 1024)]
+        max_body: u64,
+
+        /// Serve Arrow Flight (gRPC) record batch streams instead of the
+        /// HTTP JSON API (always read-only)
+        #[cfg(feature = "flight")]
+        #[arg(long)]
+        flight: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum IndexCommand {
+    /// Build (or rebuild) an index on a field
+    Create {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Field to index (dot notation for nested fields)
+        #[arg(long)]
+        field: String,
+    },
+
+    /// Drop the index on a field
+    Drop {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Indexed field
+        #[arg(long)]
+        field: String,
+    },
+
+    /// Build (or drop) a full-text index on a string field
+    Text {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Field to index (dot notation for nested fields)
+        #[arg(long)]
+        field: String,
+
+        /// Drop the text index instead of building it
+        #[arg(long)]
+        drop: bool,
+    },
+
+    /// Cache fields as columns so scans projecting them skip JSON parsing
+    Columns {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Comma-separated fields to cache (dot notation for nested fields)
+        #[arg(long, required_unless_present = "drop")]
+        fields: Option<String>,
+
+        /// Drop the column cache instead of building it
+        #[arg(long, conflicts_with = "fields")]
+        drop: bool,
+    },
+
+    /// List indexed fields
+    List {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Record the current state of a collection
+    Create {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Snapshot name
+        name: String,
+    },
+
+    /// List the snapshots of a collection
+    List {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+    },
+
+    /// Roll a collection back to a snapshot, discarding later writes
+    Restore {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Snapshot name
+        name: String,
+    },
+
+    /// Delete a snapshot (the collection's data is untouched)
+    Delete {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        #[arg(short, long, default_value = "default")]
+        collection: String,
+
+        /// Snapshot name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CollectionCommand {
+    /// Delete a collection and all its data
+    Drop {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Collection name
+        name: String,
+    },
+
+    /// Rename a collection
+    Rename {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Current collection name
+        from: String,
+
+        /// New collection name
+        to: String,
+    },
+
+    /// Copy a collection (with its indexes) to a new collection
+    Copy {
+        /// Path to the ZDS store
+        path: PathBuf,
+
+        /// Source collection name
+        from: String,
+
+        /// New collection name
+        to: String,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Init {
+            path,
+            collection,
+            strict,
+            compression,
+            storage,
+            shards,
+            auto_compact,
+            key_order,
+        } => {
+            let config = CollectionConfig {
+                storage,
+                compression,
+                strict,
+                shards,
+                auto_compact,
+                key_order,
+            };
+            cmd_init(&path, &collection, &config)?;
+        }
+        Commands::Validate {
+            path,
+            collection,
+            fix,
+            schema,
+            attach,
+        } => {
+            cmd_validate(&path, collection.as_deref(), fix, schema.as_deref(), attach)?;
+        }
+        Commands::Stats {
+            path,
+            collection,
+            json,
+        } => {
+            cmd_stats(&path, collection.as_deref(), json)?;
+        }
+        Commands::Pack {
+            source,
+            dest,
+            compression,
+            level,
+            progress,
+            incremental,
+        } => {
+            let options = PackOptions { compression, level };
+            cmd_pack(&source, &dest, &options, progress, incremental)?;
+        }
+        Commands::Unpack {
+            source,
+            dest,
+            progress,
+        } => {
+            cmd_unpack(&source, &dest, progress)?;
+        }
+        Commands::List { path } => {
+            cmd_list(&path)?;
+        }
+        Commands::Get {
+            path,
+            collection,
+            doc_id,
+            pretty,
+        } => {
+            cmd_get(&path, &collection, &doc_id, pretty)?;
+        }
+        Commands::Put {
+            path,
+            collection,
+            doc_id,
+            data,
+            auto_id,
+        } => {
+            cmd_put(&path, &collection, doc_id.as_deref(), data, auto_id)?;
+        }
+        Commands::Patch {
+            path,
+            collection,
+            doc_id,
+            data,
+        } => {
+            cmd_patch(&path, &collection, &doc_id, data)?;
+        }
+        Commands::Delete {
+            path,
+            collection,
+            doc_id,
+        } => {
+            cmd_delete(&path, &collection, &doc_id)?;
+        }
+        Commands::History {
+            path,
+            collection,
+            doc_id,
+            json,
+        } => {
+            cmd_history(&path, &collection, &doc_id, json)?;
+        }
+        Commands::Scan {
+            path,
+            collection,
+            limit,
+            fields,
+            filter,
+            jsonl,
+        } => {
+            cmd_scan(&path, &collection, limit, fields, filter, jsonl)?;
+        }
+        Commands::Query {
+            path,
+            collection,
+            filter,
+            fields,
+            format,
+            limit,
+        } => {
+            cmd_query(
+                &path,
+                &collection,
+                filter.as_deref(),
+                fields.as_deref(),
+                format,
+                limit,
+            )?;
+        }
+        Commands::Agg {
+            path,
+            collection,
+            aggregates,
+            group_by,
+            format,
+        } => {
+            cmd_agg(
+                &path,
+                &collection,
+                group_by.map(GroupBy).as_ref(),
+                &aggregates,
+                format,
+            )?;
+        }
+        Commands::Diff {
+            store_a,
+            store_b,
+            collection,
+            patch,
+        } => {
+            cmd_diff(&store_a, &store_b, &collection, patch.as_deref())?;
+        }
+        Commands::Merge {
+            sources,
+            dest,
+            collection,
+            on_conflict,
+            version_field,
+        } => {
+            cmd_merge(
+                &sources,
+                &dest,
+                &collection,
+                on_conflict,
+                version_field.as_deref(),
+            )?;
+        }
+        Commands::Sync {
+            source,
+            dest,
+            collection,
+            delete,
+            dry_run,
+            progress,
+        } => {
+            let options = SyncOptions {
+                collections: collection,
+                delete,
+                dry_run,
+            };
+            cmd_sync(&source, &dest, &options, progress)?;
+        }
+        Commands::Split {
+            path,
+            collection,
+            train,
+            test,
+            val,
+            seed,
+            dest,
+            batch_size,
+        } => {
+            let shares = [("train", train), ("test", test), ("val", val)];
+            cmd_split(
+                &path,
+                &collection,
+                &shares,
+                seed,
+                dest.as_deref(),
+                batch_size,
+            )?;
+        }
+        Commands::Sample {
+            path,
+            collection,
+            count,
+            seed,
+        } => {
+            cmd_sample(&path, &collection, count, seed)?;
+        }
+        Commands::Export {
+            path,
+            collection,
+            output,
+            format,
+        } => {
+            cmd_export(&path, &collection, &output, format)?;
+        }
+        Commands::Import {
+            path,
+            collection,
+            input,
+            format,
+            id_column,
+        } => {
+            cmd_import(&path, &collection, &input, format, &id_column)?;
+        }
+        Commands::ImportCsv {
+            file,
+            path,
+            collection,
+            id_column,
+            batch_size,
+        } => {
+            cmd_import_csv(&file, &path, &collection, id_column.as_deref(), batch_size)?;
+        }
+        Commands::ImportJsonl {
+            file,
+            path,
+            collection,
+            id_field,
+            batch_size,
+        } => {
+            cmd_import_jsonl(&file, &path, &collection, id_field.as_deref(), batch_size)?;
+        }
+        Commands::ExportJsonl {
+            output,
+            path,
+            collection,
+        } => {
+            cmd_export_jsonl(output.as_deref(), &path, &collection)?;
+        }
+        Commands::Index { action } => {
+            cmd_index(action)?;
+        }
+        Commands::Collection { action } => {
+            cmd_collection(action)?;
+        }
+        Commands::Snapshot { action } => {
+            cmd_snapshot(action)?;
+        }
+        Commands::Compact {
+            path,
+            collection,
+            dry_run,
+        } => {
+            cmd_compact(&path, &collection, dry_run)?;
+        }
+        Commands::Encrypt {
+            path,
+            collection,
+            decrypt,
+            generate_key,
+        } => {
+            if generate_key {
+                println!("{}", EncryptionKey::generate().to_hex());
+            } else if let Some(path) = path {
+                cmd_encrypt(&path, &collection, decrypt)?;
+            }
+        }
+        Commands::Migrate {
+            path,
+            collection,
+            script,
+            rename,
+            drop,
+        } => {
+            cmd_migrate(&path, &collection, script.as_deref(), &rename, &drop)?;
+        }
+        Commands::Reindex { path, collection } => {
+            cmd_reindex(&path, &collection)?;
+        }
+        Commands::Unlock { path, force } => {
+            cmd_unlock(&path, force)?;
+        }
+        #[cfg(feature = "serve")]
+        Commands::Serve {
+            path,
+            host,
+            port,
+            read_only,
+            workers,
+            max_body,
+            #[cfg(feature = "flight")]
+            flight,
+        } => {
+            #[cfg(feature = "flight")]
+            if flight {
+                flight::run(&path, &host, port)?;
+                return Ok(());
+            }
+            serve::run(&path, &host, port, read_only, workers, max_body)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_init(path: &PathBuf, collection: &str, config: &CollectionConfig) -> Result<()> {
+    config.validate()?;
+    // Record the storage mode, so the first writer doesn't pick it: JSONL
+    // for the fast store options, else files
+    let default_storage =
+        if config.compression != Compression::None || config.auto_compact.is_some() {
+            StorageMode::Jsonl
+        } else {
+            StorageMode::Files
+        };
+    let config = &CollectionConfig {
+        storage: Some(config.storage.unwrap_or(default_storage)),
+        ..config.clone()
+    };
+    println!("Initializing ZDS store at: {}", path.display());
+
+    ContainerFS::create_folder(path).context("Failed to create store")?;
+    Layout::init_collection(path, collection).context("Failed to create collection")?;
+
+    // Create manifest
+    let manifest = zippy_data::engine::Manifest::new(collection, config.strict);
+    let manifest_path = Layout::manifest_file(path, collection);
+    let manifest_json = serde_json::to_string_pretty(&manifest)?;
+    std::fs::write(&manifest_path, manifest_json)?;
+    config.save(path, collection)?;
+
+    // Create empty index files
+    let order_path = Layout::order_file(path, collection);
+    std::fs::write(&order_path, "")?;
+
+    let index_path = Layout::doc_index(path, collection);
+    std::fs::write(&index_path, "")?;
+
+    let schema_path = Layout::schema_registry(path, collection);
+    std::fs::write(&schema_path, "")?;
+
+    println!("✓ Created store with collection '{}'", collection);
+    if config.strict {
+        println!("  Mode: strict (single schema enforced)");
+    } else {
+        println!("  Mode: flexible (multiple schemas allowed)");
+    }
+    if let Some(storage) = config.storage {
+        println!("  Storage: {}", storage.as_str());
+    }
+    if config.compression != Compression::None {
+        println!("  Compression: {}", config.compression);
+    }
+    if config.key_order == KeyOrder::Sorted {
+        println!("  Key order: sorted");
+    }
+
+    Ok(())
+}
+
+fn cmd_validate(
+    path: &PathBuf,
+    collection: Option<&str>,
+    fix: bool,
+    schema_file: Option<&Path>,
+    attach: bool,
+) -> Result<()> {
+    println!("Validating ZDS store at: {}", path.display());
+
+    Layout::validate(path).context("Invalid store structure")?;
+    println!("✓ Store structure valid");
+
+    let schema = match schema_file {
+        Some(file) => {
+            let content = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let schema = serde_json::from_str(&content).context("Schema is not valid JSON")?;
+            Some(JsonSchema::new(schema).context("Invalid JSON Schema")?)
+        }
+        None => None,
+    };
+
+    let container = ContainerFS::open(path)?;
+    let collections = match collection {
+        Some(c) => vec![c.to_string()],
+        None => container.list_collections()?,
+    };
+
+    let mut corrupt_collections = 0;
+    let mut invalid_collections = 0;
+    for coll in &collections {
+        print!("  Collection '{}': ", coll);
+
+        if let Err(e) = Layout::validate_collection(path, coll) {
+            println!("✗ {}", e);
+            continue;
+        }
+
+        // JSONL collections are indexed by index.bin, checked below
+        if StorageMode::detect(path, coll)? == StorageMode::Jsonl {
+            let store = FastStore::open_with_mode(path, coll, IMPORT_BATCH_SIZE, OpenMode::Read)?;
+            println!(
+                "✓ {} documents (JSONL, {} bytes, {} dead)",
+                store.len(),
+                store.data_len(),
+                store.dead_bytes()
+            );
+        } else {
+            check_doc_index(path, coll, fix)?;
+        }
+
+        // Check JSONL data against the line checksums in index.bin
+        if Layout::has_fast_data(path, coll) {
+            let report = FastStore::open_with_mode(path, coll, IMPORT_BATCH_SIZE, OpenMode::Read)?
+                .verify()?;
+            if !report.index_ok {
+                println!("    ⚠ index.bin missing or damaged");
+                if fix {
+                    FastStore::open(path, coll, IMPORT_BATCH_SIZE)?.flush()?;
+                    println!("    ✓ index.bin rebuilt");
+                }
+            }
+            if report.corrupt.is_empty() {
+                println!("    ✓ {} lines match their checksums", report.checked);
+            } else {
+                corrupt_collections += 1;
+                println!("    ✗ {} corrupt lines", report.corrupt.len());
+                for entry in report.corrupt.iter().take(10) {
+                    let segment = match entry.segment {
+                        0 => String::new(),
+                        n => format!(" of segment {}", n),
+                    };
+                    println!(
+                        "      {} at offset {}{}: {}",
+                        entry.doc_id, entry.offset, segment, entry.kind
+                    );
+                }
+                if report.corrupt.len() > 10 {
+                    println!("      ... and {} more", report.corrupt.len() - 10);
+                }
+            }
+        }
+
+        // Check documents against the given or attached JSON Schema
+        let attached = match &schema {
+            Some(_) => None,
+            None => JsonSchema::load(path, coll)?,
+        };
+        if let Some(json_schema) = schema.as_ref().or(attached.as_ref()) {
+            let (checked, violations) = schema_violations(path, coll, json_schema)?;
+            if violations.is_empty() {
+                println!("    ✓ {} documents match the JSON Schema", checked);
+                if attach {
+                    json_schema.save(path, coll)?;
+                    println!("    ✓ Schema attached");
+                }
+            } else {
+                invalid_collections += 1;
+                println!(
+                    "    ✗ {} of {} documents violate the JSON Schema",
+                    violations.len(),
+                    checked
+                );
+                for (doc_id, violation) in violations.iter().take(10) {
+                    println!("      {}: {}", doc_id, violation);
+                }
+                if violations.len() > 10 {
+                    println!("      ... and {} more", violations.len() - 10);
+                }
+            }
+        }
+    }
+
+    if corrupt_collections > 0 {
+        anyhow::bail!("corrupt data in {} collection(s)", corrupt_collections);
+    }
+    if invalid_collections > 0 {
+        anyhow::bail!("schema violations in {} collection(s)", invalid_collections);
+    }
+    Ok(())
+}
+
+/// Compare a file-per-document collection's stored index with its
+/// `docs/` files, rebuilding it with `fix`.
+fn check_doc_index(path: &Path, collection: &str, fix: bool) -> Result<()> {
+    let disk_index = IndexRegistry::rebuild(path, collection)?;
+    let stored_index = IndexRegistry::load(path, collection).unwrap_or_default();
+
+    if disk_index.len() != stored_index.len() {
+        println!(
+            "⚠ Index mismatch (disk: {}, stored: {})",
+            disk_index.len(),
+            stored_index.len()
+        );
+        if fix {
+            disk_index.save(path, collection)?;
+            println!("    ✓ Index rebuilt");
+        }
+    } else {
+        println!("✓ {} documents", disk_index.len());
+    }
+    Ok(())
+}
+
+/// Check every document of a collection against a JSON Schema, returning the
+/// number checked and the failing doc IDs with their first violation.
+fn schema_violations(
+    path: &Path,
+    collection: &str,
+    schema: &JsonSchema,
+) -> Result<(usize, Vec<(String, String)>)> {
+    let mut checked = 0;
+    let mut violations = Vec::new();
+    let mut check = |doc_id: String, doc: &serde_json::Value| -> Result<()> {
+        checked += 1;
+        match schema.validate(doc) {
+            Err(zippy_data::Error::SchemaViolation(violation)) => {
+                violations.push((doc_id, violation))
+            }
+            result => result?,
+        }
+        Ok(())
+    };
+
+    if Layout::has_fast_data(path, collection) {
+        let store = FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, OpenMode::Read)?;
+        let mut scanner = store.iter()?;
+        while let Some((doc_id, doc)) = scanner.next_entry()? {
+            check(doc_id, &doc)?;
+        }
+    } else {
+        let engine = Engine::open(path, collection)?;
+        for doc_id in engine.doc_ids() {
+            check(doc_id.clone(), &engine.get_document(doc_id)?)?;
+        }
+    }
+    Ok((checked, violations))
+}
+
+/// A collection's `zippy stats --json` entry.
+fn stats_entry(engine: &Engine) -> Result<serde_json::Value> {
+    let s = engine.stats();
+    let mut entry = serde_json::json!({
+        "collection": s.collection,
+        "doc_count": s.doc_count,
+        "schema_count": s.schema_count,
+        "total_size": s.total_size,
+        "strict_mode": s.strict_mode,
+        "storage": s.storage.as_str(),
+    });
+    if let Some(recovery) = &s.recovery {
+        entry["recovery"] = serde_json::to_value(recovery)?;
+    }
+    if let (Some(store), Some(fast)) = (engine.fast_store(), s.fast) {
+        entry["data_size"] = fast.data_bytes.into();
+        entry["segments"] = fast.segments.into();
+        entry["compression"] = fast.compression.as_str().into();
+        entry["encrypted"] = store.is_encrypted().into();
+        entry["index_size"] = fast.index_bytes.into();
+        entry["index_memory"] = fast.index_memory_bytes.into();
+        entry["live_bytes"] = fast.live_bytes.into();
+        entry["dead_bytes"] = fast.dead_bytes.into();
+        entry["dead_ratio"] = fast.dead_ratio.into();
+        entry["tombstones"] = fast.tombstones.into();
+        entry["dedup_docs"] = fast.dedup_docs.into();
+        entry["dedup_bytes"] = fast.dedup_bytes.into();
+        entry["min_offset"] = fast.min_offset.into();
+        entry["max_offset"] = fast.max_offset.into();
+        entry["last_flush"] = fast.last_flush.into();
+    }
+    Ok(entry)
+}
+
+fn cmd_stats(path: &PathBuf, collection: Option<&str>, json_output: bool) -> Result<()> {
+    let container = ContainerFS::open(path)?;
+    let collections = match collection {
+        Some(c) => vec![c.to_string()],
+        None => container.list_collections()?,
+    };
+
+    if json_output {
+        let mut stats = Vec::new();
+        for coll in &collections {
+            stats.push(stats_entry(&Engine::open(path, coll)?)?);
+        }
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        println!("ZDS Store: {}", path.display());
+        println!();
+
+        for coll in &collections {
+            let engine = Engine::open(path, coll)?;
+            let stats = engine.stats();
+
+            println!("Collection: {}", stats.collection);
+            println!("  Documents:    {}", stats.doc_count);
+            println!("  Schemas:      {}", stats.schema_count);
+            println!("  Total size:   {} bytes", stats.total_size);
+            println!(
+                "  Mode:         {}",
+                if stats.strict_mode {
+                    "strict"
+                } else {
+                    "flexible"
+                }
+            );
+            println!("  Storage:      {}", stats.storage.as_str());
+            if let Some(recovery) = &stats.recovery {
+                println!(
+                    "  Recovered:    {} put(s) and {} delete(s) replayed, {} rolled back, \
+                     {} temp file(s) removed",
+                    recovery.puts_replayed,
+                    recovery.deletes_replayed,
+                    recovery.rolled_back,
+                    recovery.tmp_files_removed
+                );
+            }
+            if let (Some(store), Some(fast)) = (engine.fast_store(), &stats.fast) {
+                println!(
+                    "  Data size:    {} bytes in {} segment(s), compression {}",
+                    fast.data_bytes,
+                    fast.segments,
+                    fast.compression.as_str()
+                );
+                println!("  Index size:   {} bytes", fast.index_bytes);
+                println!("  Index memory: {} bytes", fast.index_memory_bytes);
+                if store.is_encrypted() {
+                    println!("  Encryption:   {}", encryption::ALGORITHM);
+                }
+                println!("  Live bytes:   {}", fast.live_bytes);
+                println!(
+                    "  Dead bytes:   {} ({:.1}%)",
+                    fast.dead_bytes,
+                    fast.dead_ratio * 100.0
+                );
+                println!("  Tombstones:   {}", fast.tombstones);
+                if fast.dedup_docs > 0 {
+                    println!(
+                        "  Deduplicated: {} document(s), {} bytes saved",
+                        fast.dedup_docs, fast.dedup_bytes
+                    );
+                }
+                if let (Some(min), Some(max)) = (fast.min_offset, fast.max_offset) {
+                    println!("  Offsets:      {}..={}", min, max);
+                }
+                if let Some(last_flush) = fast.last_flush {
+                    println!("  Last flush:   {} ms since epoch", last_flush);
+                }
+            }
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// Progress reporter for `pack`/`unpack --progress`: one stderr line,
+/// redrawn when the percentage changes.
+fn progress_printer(enabled: bool) -> impl FnMut(&PackProgress) {
+    let mut last = None;
+    move |p: &PackProgress| {
+        if !enabled {
+            return;
+        }
+        let percent = (p.bytes_done * 100)
+            .checked_div(p.bytes_total)
+            .unwrap_or(100);
+        let done = p.files_done == p.files_total;
+        if last == Some((percent, done)) {
+            return;
+        }
+        last = Some((percent, done));
+        eprint!(
+            "\r  {:3}% ({}/{} files, {} / {} bytes)",
+            percent, p.files_done, p.files_total, p.bytes_done, p.bytes_total
+        );
+        if done {
+            eprintln!();
+        }
+    }
+}
+
+fn cmd_pack(
+    source: &Path,
+    dest: &Path,
+    options: &PackOptions,
+    progress: bool,
+    incremental: bool,
+) -> Result<()> {
+    println!("Packing {} → {}", source.display(), dest.display());
+
+    Layout::validate(source).context("Invalid source store")?;
+    if incremental {
+        let report = pack_incremental_with(source, dest, options, progress_printer(progress))
+            .context("Failed to pack archive")?;
+        println!(
+            "  {} added, {} updated, {} unchanged, {} removed",
+            report.added, report.updated, report.unchanged, report.removed
+        );
+    } else {
+        pack_with(source, dest, options, progress_printer(progress))
+            .context("Failed to pack archive")?;
+    }
+
+    let size = std::fs::metadata(dest)?.len();
+    println!("✓ Created archive ({} bytes)", size);
+
+    Ok(())
+}
+
+fn cmd_unpack(source: &Path, dest: &Path, progress: bool) -> Result<()> {
+    println!("Unpacking {} → {}", source.display(), dest.display());
+
+    unpack_with(source, dest, progress_printer(progress)).context("Failed to unpack archive")?;
+    Layout::validate(dest).context("Unpacked store is invalid")?;
+
+    println!("✓ Archive unpacked");
+
+    Ok(())
+}
+
+fn cmd_list(path: &PathBuf) -> Result<()> {
+    let container = ContainerFS::open(path)?;
+    let collections = container.list_collections()?;
+
+    if collections.is_empty() {
+        println!("No collections found");
+    } else {
+        println!("Collections:");
+        for coll in collections {
+            let store = open_read_store(path, &coll)?;
+            println!("  {} ({} documents)", coll, store.len());
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_get(path: &PathBuf, collection: &str, doc_id: &str, pretty: bool) -> Result<()> {
+    let doc = open_read_store(path, collection)?.get(doc_id)?;
+
+    if pretty {
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+    } else {
+        println!("{}", serde_json::to_string(&doc)?);
+    }
+
+    Ok(())
+}
+
+fn cmd_put(
+    path: &PathBuf,
+    collection: &str,
+    doc_id: Option<&str>,
+    data: Option<String>,
+    auto_id: Option<IdStrategy>,
+) -> Result<()> {
+    let json_str = match data {
+        Some(d) => d,
+        None => {
+            use std::io::Read;
+            let mut buf = String::new();
+            std::io::stdin().read_to_string(&mut buf)?;
+            buf
+        }
+    };
+
+    let doc: serde_json::Value =
+        serde_json::from_str(&json_str).context("Invalid JSON document")?;
+
+    // Ensure store and collection exist
+    if !path.exists() {
+        ContainerFS::create_folder(path)?;
+    }
+
+    let mut store = open_store(path, collection)?;
+    let doc_id = match (doc_id, auto_id) {
+        (Some(doc_id), _) => {
+            store.put(doc_id, doc)?;
+            doc_id.to_string()
+        }
+        (None, strategy) => store.put_auto(doc, strategy.unwrap_or_default())?,
+    };
+    store.flush()?;
+
+    println!(
+        "✓ Document '{}' written to collection '{}'",
+        doc_id, collection
+    );
+
+    Ok(())
+}
+
+fn cmd_patch(path: &PathBuf, collection: &str, doc_id: &str, data: Option<String>) -> Result<()> {
+    let json_str = match data {
+        Some(d) => d,
+        None => {
+            use std::io::Read;
+            let mut buf = String::new();
+            std::io::stdin().read_to_string(&mut buf)?;
+            buf
+        }
+    };
+
+    let patch: serde_json::Value =
+        serde_json::from_str(&json_str).context("Invalid JSON merge patch")?;
+
+    let mut store = open_store(path, collection)?;
+    store.patch(doc_id, &patch)?;
+    store.flush()?;
+
+    println!(
+        "✓ Document '{}' patched in collection '{}'",
+        doc_id, collection
+    );
+
+    Ok(())
+}
+
+fn cmd_delete(path: &PathBuf, collection: &str, doc_id: &str) -> Result<()> {
+    let mut store = open_store(path, collection)?;
+    store.delete(doc_id)?;
+    store.flush()?;
+
+    println!(
+        "✓ Document '{}' deleted from collection '{}'",
+        doc_id, collection
+    );
+
+    Ok(())
+}
+
+fn cmd_history(path: &PathBuf, collection: &str, doc_id: &str, json_output: bool) -> Result<()> {
+    let engine = Engine::open(path, collection)?;
+    let history = engine.history(doc_id)?;
+
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&history)?);
+        return Ok(());
+    }
+    if history.is_empty() {
+        println!(
+            "No journaled changes of '{}' in collection '{}'",
+            doc_id, collection
+        );
+        return Ok(());
+    }
+    for entry in &history {
+        match entry {
+            JournalEntry::Put {
+                timestamp,
+                schema_id,
+                size,
+                ..
+            } => println!(
+                "{}  PUT     {} bytes, schema {}",
+                timestamp.to_rfc3339(),
+                size,
+                &schema_id[..schema_id.len().min(12)]
+            ),
+            JournalEntry::Delete { timestamp, .. } => {
+                println!("{}  DELETE", timestamp.to_rfc3339())
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a `--filter` argument: a JSON filter object or an expression.
+fn parse_filter(filter: &str) -> Result<Predicate> {
+    if filter.trim_start().starts_with('{') {
+        let value: serde_json::Value =
+            serde_json::from_str(filter).context("Filter is not valid JSON")?;
+        Ok(Predicate::from_json(&value)?)
+    } else {
+        Ok(Predicate::parse(filter)?)
+    }
+}
+
+fn cmd_scan(
+    path: &PathBuf,
+    collection: &str,
+    limit: Option<usize>,
+    fields: Option<String>,
+    filter: Option<String>,
+    jsonl: bool,
+) -> Result<()> {
+    let engine = Engine::open(path, collection)?;
+
+    let predicate = filter.as_deref().map(parse_filter).transpose()?;
+
+    let field_list: Option<Vec<String>> =
+        fields.map(|f| f.split(',').map(|s| s.trim().to_string()).collect());
+    let field_refs: Option<Vec<&str>> = field_list
+        .as_ref()
+        .map(|f| f.iter().map(|s| s.as_str()).collect());
+
+    let mut scanner = engine.scan(predicate.as_ref(), field_refs.as_deref())?;
+
+    let mut count = 0;
+    let max = limit.unwrap_or(usize::MAX);
+
+    if !jsonl {
+        println!("[");
+    }
+
+    while let Some(doc) = scanner.next().transpose()? {
+        if count >= max {
+            break;
+        }
+
+        if jsonl {
+            println!("{}", serde_json::to_string(&doc)?);
+        } else {
+            if count > 0 {
+                println!(",");
+            }
+            print!("  {}", serde_json::to_string(&doc)?);
+        }
+
+        count += 1;
+    }
+
+    if !jsonl {
+        println!();
+        println!("]");
+    }
+
+    eprintln!("({} documents)", count);
+
+    Ok(())
+}
+
+/// Documents of a collection with their IDs, in store order.
+type DocStream = Box<dyn Iterator<Item = zippy_data::Result<(String, serde_json::Value)>>>;
+
+/// Stream the documents of a fast store or file-per-document collection.
+fn collection_docs(path: &Path, collection: &str) -> Result<DocStream> {
+    if Layout::has_fast_data(path, collection) {
+        let store = FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, OpenMode::Read)?;
+        let mut scanner = store.iter()?;
+        Ok(Box::new(std::iter::from_fn(move || {
+            scanner.next_entry().transpose()
+        })))
+    } else {
+        let engine = Engine::open(path, collection)?;
+        let ids = engine.doc_ids().to_vec();
+        Ok(Box::new(ids.into_iter().map(move |id| {
+            engine.get_document(&id).map(|doc| (id, doc))
+        })))
+    }
+}
+
+/// Raw JSONL lines (with `_id`) of a collection and their IDs, in store order.
+type RawLineStream = Box<dyn Iterator<Item = Result<(String, Vec<u8>)>>>;
+
+/// Stream a collection as raw JSONL lines; fast store lines are not parsed.
+fn collection_lines(path: &Path, collection: &str) -> Result<RawLineStream> {
+    if Layout::has_fast_data(path, collection) {
+        let store = FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, OpenMode::Read)?;
+        let mut scanner = store.iter()?;
+        return Ok(Box::new(std::iter::from_fn(move || {
+            scanner.next_raw().transpose().map(|line| {
+                let line = line?;
+                let doc_id = FastStore::extract_id_fast(&line).unwrap_or_default();
+                Ok((doc_id, line))
+            })
+        })));
+    }
+    let docs = collection_docs(path, collection)?;
+    Ok(Box::new(docs.map(|entry| {
+        let (doc_id, doc) = entry?;
+        let mut line = serde_json::Map::new();
+        line.insert(ID_COLUMN.to_string(), doc_id.clone().into());
+        if let serde_json::Value::Object(obj) = doc {
+            line.extend(obj);
+        }
+        Ok((doc_id, serde_json::to_vec(&line)?))
+    })))
+}
+
+fn cmd_query(
+    path: &Path,
+    collection: &str,
+    filter: Option<&str>,
+    fields: Option<&str>,
+    format: QueryFormat,
+    limit: Option<usize>,
+) -> Result<()> {
+    let predicate = filter.map(parse_filter).transpose()?;
+    let fields: Option<Vec<String>> =
+        fields.map(|f| f.split(',').map(|s| s.trim().to_string()).collect());
+
+    let docs = collection_docs(path, collection)?;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let mut rows = Vec::new();
+    let mut count = 0;
+    let max = limit.unwrap_or(usize::MAX);
+    for entry in docs {
+        if count >= max {
+            break;
+        }
+        let (doc_id, doc) = entry?;
+        if let Some(predicate) = &predicate {
+            if !Codec::apply_predicate(&doc, predicate)? {
+                continue;
+            }
+        }
+        let row = query_row(doc_id, doc, fields.as_deref());
+        match format {
+            QueryFormat::Jsonl => writeln!(out, "{}", serde_json::to_string(&row)?)?,
+            QueryFormat::Csv | QueryFormat::Table => rows.push(row),
+        }
+        count += 1;
+    }
+
+    // Columns are the requested fields, or every top-level field seen
+    let columns = fields.unwrap_or_else(|| {
+        let mut columns: Vec<String> = Vec::new();
+        for row in &rows {
+            for key in row.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+        columns
+    });
+    write_rows(&mut out, &rows, &columns, format)?;
+    out.flush()?;
+
+    eprintln!("({} documents)", count);
+
+    Ok(())
+}
+
+fn cmd_agg(
+    path: &Path,
+    collection: &str,
+    group_by: Option<&GroupBy>,
+    aggregates: &[Aggregate],
+    format: QueryFormat,
+) -> Result<()> {
+    let aggregates = if aggregates.is_empty() {
+        &[Aggregate::Count][..]
+    } else {
+        aggregates
+    };
+    let rows = if Layout::has_fast_data(path, collection) {
+        FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, OpenMode::Read)?
+            .aggregate(group_by, aggregates)?
+    } else {
+        Engine::open(path, collection)?.aggregate(group_by, aggregates)?
+    };
+
+    let columns: Vec<String> = group_by
+        .map(|GroupBy(field)| field.clone())
+        .into_iter()
+        .chain(aggregates.iter().map(Aggregate::name))
+        .collect();
+    let rows: Vec<serde_json::Map<String, serde_json::Value>> = rows
+        .into_iter()
+        .filter_map(|row| match row {
+            serde_json::Value::Object(row) => Some(row),
+            _ => None,
+        })
+        .collect();
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    write_rows(&mut out, &rows, &columns, format)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn cmd_diff(store_a: &Path, store_b: &Path, collection: &str, patch: Option<&Path>) -> Result<()> {
+    // Content hashes of the first collection; serde_json maps serialize with
+    // sorted keys, so equal documents hash equally
+    let mut hashes: HashMap<String, u64> = HashMap::new();
+    for entry in collection_docs(store_a, collection)? {
+        let (doc_id, doc) = entry?;
+        hashes.insert(doc_id, content_hash(&doc)?);
+    }
+
+    let mut patch_writer = match patch {
+        Some(patch) => Some(BufWriter::new(
+            File::create(patch).context("Failed to create patch file")?,
+        )),
+        None => None,
+    };
+    let (mut added, mut changed, mut unchanged) = (0, 0, 0);
+    for entry in collection_docs(store_b, collection)? {
+        let (doc_id, doc) = entry?;
+        let op = match hashes.remove(&doc_id) {
+            None => '+',
+            Some(hash) if hash != content_hash(&doc)? => '~',
+            Some(_) => {
+                unchanged += 1;
+                continue;
+            }
+        };
+        if op == '+' {
+            added += 1;
+        } else {
+            changed += 1;
+        }
+        println!("{} {}", op, doc_id);
+        if let Some(writer) = &mut patch_writer {
+            let line = serde_json::json!({"op": "put", ID_COLUMN: doc_id, "doc": doc});
+            writeln!(writer, "{}", line)?;
+        }
+    }
+
+    // IDs left over are only in the first collection
+    let mut removed: Vec<String> = hashes.into_keys().collect();
+    removed.sort_unstable();
+    for doc_id in &removed {
+        println!("- {}", doc_id);
+        if let Some(writer) = &mut patch_writer {
+            let line = serde_json::json!({"op": "delete", ID_COLUMN: doc_id});
+            writeln!(writer, "{}", line)?;
+        }
+    }
+    if let Some(writer) = &mut patch_writer {
+        writer.flush()?;
+    }
+
+    println!(
+        "{} added, {} removed, {} changed, {} unchanged",
+        added,
+        removed.len(),
+        changed,
+        unchanged
+    );
+    if added + removed.len() + changed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn cmd_sync(source: &Path, dest: &Path, options: &SyncOptions, progress: bool) -> Result<()> {
+    println!("Syncing {} → {}", source.display(), dest.display());
+
+    let mut last = None;
+    let report = zippy_data::sync::sync_with(source, dest, options, |p: &SyncProgress| {
+        if !progress {
+            return;
+        }
+        let percent = (p.docs_done * 100).checked_div(p.docs_total).unwrap_or(100);
+        if last == Some(percent) && p.docs_done < p.docs_total {
+            return;
+        }
+        last = Some(percent);
+        eprint!(
+            "\r  {}: {:3}% ({}/{} documents)",
+            p.collection, percent, p.docs_done, p.docs_total
+        );
+        if p.docs_done == p.docs_total {
+            last = None;
+            eprintln!();
+        }
+    })
+    .context("Failed to sync")?;
+
+    println!(
+        "  {} added, {} updated, {} unchanged, {} removed",
+        report.added, report.updated, report.unchanged, report.removed
+    );
+    if options.dry_run {
+        println!("Dry run: {} bytes would be copied", report.bytes);
+    } else {
+        println!("✓ Synced ({} bytes copied)", report.bytes);
+    }
+
+    Ok(())
+}
+
+fn cmd_merge(
+    sources: &[PathBuf],
+    dest: &Path,
+    collection: &str,
+    on_conflict: MergeConflict,
+    version_field: Option<&str>,
+) -> Result<()> {
+    Layout::init_root(dest)?;
+    let mut store = FastStore::open(dest, collection, IMPORT_BATCH_SIZE)?;
+    if !store.is_empty() {
+        anyhow::bail!(
+            "Collection '{}' in {} already has documents",
+            collection,
+            dest.display()
+        );
+    }
+
+    // Version of each merged document (only compared for `latest`)
+    let mut versions: HashMap<String, serde_json::Value> = HashMap::new();
+    let (mut written, mut skipped) = (0, 0);
+    for source in sources {
+        // Fast stores don't record per-document times, so their documents
+        // all take the time the data was last written
+        let fast = Layout::has_fast_data(source, collection);
+        let data_mtime = if fast && version_field.is_none() {
+            fast_data_mtime(source, collection)?
+        } else {
+            0
+        };
+
+        for entry in collection_docs(source, collection)? {
+            let (doc_id, doc) = entry?;
+            let version = match (on_conflict, version_field) {
+                (MergeConflict::Latest, Some(field)) => field_value(&doc, field)
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null),
+                (MergeConflict::Latest, None) if fast => data_mtime.into(),
+                (MergeConflict::Latest, None) => {
+                    mtime_ms(&Layout::doc_file(source, collection, &doc_id))?.into()
+                }
+                _ => serde_json::Value::Null,
+            };
+            if let Some(existing) = versions.get(&doc_id) {
+                let keep_existing = match on_conflict {
+                    MergeConflict::Error => anyhow::bail!(
+                        "Document '{}' in {} is also in an earlier source",
+                        doc_id,
+                        source.display()
+                    ),
+                    MergeConflict::Skip => true,
+                    // Ties go to the later source
+                    MergeConflict::Latest => {
+                        version_cmp(&version, existing) == std::cmp::Ordering::Less
+                    }
+                };
+                if keep_existing {
+                    skipped += 1;
+                    continue;
+                }
+            }
+            versions.insert(doc_id.clone(), version);
+            store.put(doc_id, doc)?;
+            written += 1;
+        }
+    }
+    store.flush()?;
+    // Replaced versions leave dead lines behind
+    if store.dead_bytes() > 0 {
+        store.compact()?;
+    }
+
+    println!(
+        "✓ Merged {} sources into collection '{}' ({} documents, {} writes, {} skipped)",
+        sources.len(),
+        collection,
+        store.len(),
+        written,
+        skipped
+    );
+
+    Ok(())
+}
+
+/// Newest modification time (Unix ms) of a fast store's data segments.
+fn fast_data_mtime(path: &Path, collection: &str) -> Result<u64> {
+    let mut newest = 0;
+    for entry in std::fs::read_dir(Layout::meta_dir(path, collection))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_str().and_then(Layout::parse_data_segment).is_some() {
+            newest = newest.max(mtime_ms(&entry.path())?);
+        }
+    }
+    Ok(newest)
+}
+
+/// Modification time of a file in milliseconds since the Unix epoch.
+fn mtime_ms(path: &Path) -> Result<u64> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    Ok(modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64))
+}
+
+/// Order two document versions: numbers and strings compare by value, and a
+/// missing version is older than any other.
+fn version_cmp(a: &serde_json::Value, b: &serde_json::Value) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    use serde_json::Value;
+
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+/// Value of a (dot notation) field in a document.
+fn field_value<'a>(doc: &'a serde_json::Value, field: &str) -> Option<&'a serde_json::Value> {
+    field.split('.').try_fold(doc, |value, key| value.get(key))
+}
+
+/// One output collection of `split`, buffering a bulk write.
+struct SplitOutput {
+    collection: String,
+    /// Upper bound of the split's hash range
+    bound: f64,
+    store: FastStore,
+    blob: Vec<u8>,
+    doc_ids: Vec<String>,
+    count: usize,
+}
+
+impl SplitOutput {
+    /// Write and flush the buffered lines.
+    fn write(&mut self) -> Result<()> {
+        if self.doc_ids.is_empty() {
+            return Ok(());
+        }
+        self.count += self.store.write_jsonl_blob(&self.blob, &self.doc_ids)?;
+        self.store.flush()?;
+        self.blob.clear();
+        self.doc_ids.clear();
+        Ok(())
+    }
+}
+
+fn cmd_split(
+    path: &Path,
+    collection: &str,
+    shares: &[(&str, f64)],
+    seed: u64,
+    dest: Option<&Path>,
+    batch_size: usize,
+) -> Result<()> {
+    let batch_size = batch_size.max(1);
+    if let Some((name, share)) = shares.iter().find(|(_, s)| !(0.0..=1.0).contains(s)) {
+        anyhow::bail!("--{} must be between 0 and 1 (got {})", name, share);
+    }
+    let total: f64 = shares.iter().map(|(_, share)| share).sum();
+    if (total - 1.0).abs() > 1e-6 {
+        anyhow::bail!("Split fractions must add up to 1 (got {})", total);
+    }
+
+    let dest_root = dest.unwrap_or(path);
+    Layout::init_root(dest_root)?;
+    let mut outputs = Vec::new();
+    let mut bound = 0.0;
+    for &(name, share) in shares {
+        bound += share;
+        if share == 0.0 {
+            continue;
+        }
+        let out = match dest {
+            Some(_) => name.to_string(),
+            None => format!("{}_{}", collection, name),
+        };
+        let store = FastStore::open(dest_root, &out, batch_size)?;
+        if !store.is_empty() {
+            anyhow::bail!(
+                "Collection '{}' in {} already has documents",
+                out,
+                dest_root.display()
+            );
+        }
+        outputs.push(SplitOutput {
+            collection: out,
+            bound,
+            store,
+            blob: Vec::new(),
+            doc_ids: Vec::with_capacity(batch_size),
+            count: 0,
+        });
+    }
+    // Absorb rounding at the top of the range
+    if let Some(last) = outputs.last_mut() {
+        last.bound = f64::INFINITY;
+    }
+
+    for entry in collection_lines(path, collection)? {
+        let (doc_id, line) = entry?;
+        let point = split_point(seed, &doc_id);
+        let Some(out) = outputs.iter_mut().find(|out| point < out.bound) else {
+            continue;
+        };
+        out.blob.extend_from_slice(&line);
+        out.blob.push(b'\n');
+        out.doc_ids.push(doc_id);
+        if out.doc_ids.len() >= batch_size {
+            out.write()?;
+        }
+    }
+
+    let total: usize = outputs
+        .iter()
+        .map(|out| out.doc_ids.len() + out.count)
+        .sum();
+    println!("✓ Split collection '{}' ({} documents)", collection, total);
+    for out in &mut outputs {
+        out.write()?;
+        println!("  {}: {} documents", out.collection, out.count);
+    }
+
+    Ok(())
+}
+
+/// Deterministic position in [0, 1) of a document ID for `split`.
+fn split_point(seed: u64, doc_id: &str) -> f64 {
+    // FNV-1a, then the splitmix64 finalizer so similar IDs spread out
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in seed.to_le_bytes().iter().chain(doc_id.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn cmd_sample(path: &Path, collection: &str, count: usize, seed: u64) -> Result<()> {
+    let docs = if Layout::has_fast_data(path, collection) {
+        FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, OpenMode::Read)?
+            .sample(count, seed)?
+    } else {
+        Engine::open(path, collection)?.sample(count, seed)?
+    };
+
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    for doc in &docs {
+        writeln!(out, "{}", serde_json::to_string(doc)?)?;
+    }
+    out.flush()?;
+    eprintln!("({} documents)", docs.len());
+    Ok(())
+}
+
+/// Hash of a document's canonical JSON text.
+fn content_hash(doc: &serde_json::Value) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(serde_json::to_string(doc)?.as_bytes());
+    Ok(hasher.finish())
+}
+
+/// A query result: the document with its `_id`, projected onto `fields`.
+fn query_row(
+    doc_id: String,
+    doc: serde_json::Value,
+    fields: Option<&[String]>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut row = serde_json::Map::new();
+    row.insert(ID_COLUMN.to_string(), serde_json::Value::String(doc_id));
+    if let serde_json::Value::Object(obj) = doc {
+        row.extend(obj);
+    }
+    let Some(fields) = fields else {
+        return row;
+    };
+
+    let row = serde_json::Value::Object(row);
+    fields
+        .iter()
+        .map(|field| {
+            let value = field_value(&row, field)
+                .cloned()
+                .unwrap_or(serde_json::Value::Null);
+            (field.clone(), value)
+        })
+        .collect()
+}
+
+/// Write query-style result rows in `format`, with `columns` as the CSV and
+/// table columns.
+fn write_rows<W: Write>(
+    out: &mut W,
+    rows: &[serde_json::Map<String, serde_json::Value>],
+    columns: &[String],
+    format: QueryFormat,
+) -> Result<()> {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| query_cell(row.get(c))).collect())
+        .collect();
+
+    match format {
+        QueryFormat::Jsonl => {
+            for row in rows {
+                writeln!(out, "{}", serde_json::to_string(row)?)?;
+            }
+        }
+        QueryFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(&mut *out);
+            writer.write_record(columns)?;
+            for row in &cells {
+                writer.write_record(row)?;
+            }
+            writer.flush()?;
+        }
+        QueryFormat::Table => {
+            let cut = |cell: &str| -> String {
+                if cell.chars().count() <= MAX_CELL_WIDTH {
+                    cell.to_string()
+                } else {
+                    let mut cell: String = cell.chars().take(MAX_CELL_WIDTH - 1).collect();
+                    cell.push('…');
+                    cell
+                }
+            };
+            let header: Vec<String> = columns.iter().map(|c| cut(c)).collect();
+            let cells: Vec<Vec<String>> = cells
+                .iter()
+                .map(|row| row.iter().map(|c| cut(c)).collect())
+                .collect();
+            let widths: Vec<usize> = (0..header.len())
+                .map(|i| {
+                    cells
+                        .iter()
+                        .map(|row| row[i].chars().count())
+                        .chain(std::iter::once(header[i].chars().count()))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            let print_row = |out: &mut W, row: &[String]| -> std::io::Result<()> {
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+                    .collect();
+                writeln!(out, "{}", line.join("  ").trim_end())
+            };
+            print_row(out, &header)?;
+            let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+            writeln!(out, "{}", rule.join("  "))?;
+            for row in &cells {
+                print_row(out, row)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Text of a CSV or table cell (empty for missing and null values).
+fn query_cell(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn cmd_index(action: IndexCommand) -> Result<()> {
+    match action {
+        IndexCommand::Create {
+            path,
+            collection,
+            field,
+        } => {
+            // Fast store collections keep their data in meta/data*.jsonl segments
+            let count = if Layout::has_fast_data(&path, &collection) {
+                FastStore::open(&path, &collection, IMPORT_BATCH_SIZE)?.create_index(&field)?
+            } else {
+                Engine::open(&path, &collection)?.create_index(&field)?
+            };
+            println!(
+                "✓ Indexed field '{}' in collection '{}' ({} documents)",
+                field, collection, count
+            );
+        }
+        IndexCommand::Drop {
+            path,
+            collection,
+            field,
+        } => {
+            FieldIndexes::drop_index(&path, &collection, &field)?;
+            println!(
+                "✓ Dropped index on '{}' in collection '{}'",
+                field, collection
+            );
+        }
+        IndexCommand::Text {
+            path,
+            collection,
+            field,
+            drop,
+        } => {
+            let fast = Layout::has_fast_data(&path, &collection);
+            if drop {
+                FieldIndexes::drop_text_index(&path, &collection, &field)?;
+                println!(
+                    "✓ Dropped text index on '{}' in collection '{}'",
+                    field, collection
+                );
+            } else {
+                let count = if fast {
+                    FastStore::open(&path, &collection, IMPORT_BATCH_SIZE)?
+                        .create_text_index(&field)?
+                } else {
+                    Engine::open(&path, &collection)?.create_text_index(&field)?
+                };
+                println!(
+                    "✓ Text-indexed field '{}' in collection '{}' ({} documents)",
+                    field, collection, count
+                );
+            }
+        }
+        IndexCommand::Columns {
+            path,
+            collection,
+            fields,
+            drop,
+        } => {
+            if !Layout::has_fast_data(&path, &collection) {
+                anyhow::bail!(
+                    "Collection '{}' has no JSONL data; column caches need a fast store collection",
+                    collection
+                );
+            }
+            let mut store = FastStore::open(&path, &collection, IMPORT_BATCH_SIZE)?;
+            if drop {
+                store.drop_column_cache()?;
+                println!("✓ Dropped column cache in collection '{}'", collection);
+            } else {
+                let fields: Vec<&str> = fields
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|f| !f.is_empty())
+                    .collect();
+                let count = store.cache_columns(&fields)?;
+                println!(
+                    "✓ Cached columns {} in collection '{}' ({} documents)",
+                    store
+                        .column_cache()
+                        .map(|c| c.fields().join(", "))
+                        .unwrap_or_default(),
+                    collection,
+                    count
+                );
+            }
+        }
+        IndexCommand::List { path, collection } => {
+            let columns = ColumnCache::load(&path, &collection)?;
+            let fields = FieldIndexes::list(&path, &collection)?;
+            let text_fields = FieldIndexes::list_text(&path, &collection)?;
+            if fields.is_empty() && text_fields.is_empty() && columns.is_none() {
+                println!("No indexes in collection '{}'", collection);
+            } else {
+                println!("Indexes in collection '{}':", collection);
+                for field in fields {
+                    let index = zippy_data::FieldIndex::load(&path, &collection, &field)?;
+                    println!(
+                        "  {} ({} documents, {} distinct values)",
+                        field,
+                        index.len(),
+                        index.distinct_values()
+                    );
+                }
+                for field in text_fields {
+                    let index = zippy_data::TextIndex::load(&path, &collection, &field)?;
+                    println!(
+                        "  {} [text] ({} documents, {} distinct tokens)",
+                        field,
+                        index.len(),
+                        index.distinct_tokens()
+                    );
+                }
+                if let Some(columns) = columns {
+                    println!(
+                        "  {} [columns] ({} documents)",
+                        columns.fields().join(", "),
+                        columns.len()
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_collection(action: CollectionCommand) -> Result<()> {
+    let path = match &action {
+        CollectionCommand::Drop { path, .. }
+        | CollectionCommand::Rename { path, .. }
+        | CollectionCommand::Copy { path, .. } => path,
+    };
+    Layout::validate(path).context("Invalid store structure")?;
+    let root = ZDSRoot::open_rw(path, IMPORT_BATCH_SIZE)?;
+
+    match &action {
+        CollectionCommand::Drop { name, .. } => {
+            root.drop_collection(name)?;
+            println!("✓ Dropped collection '{}'", name);
+        }
+        CollectionCommand::Rename { from, to, .. } => {
+            root.rename_collection(from, to)?;
+            println!("✓ Renamed collection '{}' to '{}'", from, to);
+        }
+        CollectionCommand::Copy { from, to, .. } => {
+            root.copy_collection(from, to)?;
+            println!("✓ Copied collection '{}' to '{}'", from, to);
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_snapshot(action: SnapshotCommand) -> Result<()> {
+    match action {
+        SnapshotCommand::Create {
+            path,
+            collection,
+            name,
+        } => {
+            if !Layout::has_fast_data(&path, &collection) {
+                anyhow::bail!("Collection '{}' has no fast store data", collection);
+            }
+            let mut store = FastStore::open(&path, &collection, IMPORT_BATCH_SIZE)?;
+            let snapshot = store.snapshot(&name)?;
+            println!(
+                "✓ Created snapshot '{}' of collection '{}' ({} documents)",
+                name, collection, snapshot.documents
+            );
+        }
+        SnapshotCommand::List { path, collection } => {
+            let snapshots = Snapshot::list(&path, &collection)?;
+            if snapshots.is_empty() {
+                println!("No snapshots of collection '{}'", collection);
+            }
+            for snapshot in snapshots {
+                let state = if snapshot.is_valid(&path, &collection)? {
+                    ""
+                } else {
+                    "  (stale: compacted since)"
+                };
+                println!(
+                    "  {}  {}  {} documents{}",
+                    snapshot.name,
+                    snapshot.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    snapshot.documents,
+                    state
+                );
+            }
+        }
+        SnapshotCommand::Restore {
+            path,
+            collection,
+            name,
+        } => {
+            let snapshot = Snapshot::restore(&path, &collection, &name)?;
+            println!(
+                "✓ Restored collection '{}' to snapshot '{}' ({} documents)",
+                collection, name, snapshot.documents
+            );
+        }
+        SnapshotCommand::Delete {
+            path,
+            collection,
+            name,
+        } => {
+            Snapshot::delete(&path, &collection, &name)?;
+            println!("✓ Deleted snapshot '{}'", name);
+        }
+    }
 
     Ok(())
 }
 
-fn cmd_list(path: &PathBuf) -> Result<()> {
-    let container = ContainerFS::open(path)?;
-    let collections = container.list_collections()?;
-
-    if collections.is_empty() {
-        println!("No collections found");
+fn cmd_compact(path: &Path, collection: &str, dry_run: bool) -> Result<()> {
+    if !Layout::has_fast_data(path, collection) {
+        anyhow::bail!("Collection '{}' has no fast store data", collection);
+    }
+    let mode = if dry_run {
+        OpenMode::Read
     } else {
-        println!("Collections:");
-        for coll in collections {
-            let engine = Engine::open(path, &coll)?;
-            println!("  {} ({} documents)", coll, engine.len());
-        }
+        OpenMode::ReadWrite
+    };
+    let mut store = FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, mode)?;
+    let total = store.data_len();
+    let reclaimable = store.reclaimable_bytes();
+    let percent = if total == 0 {
+        0.0
+    } else {
+        reclaimable as f64 * 100.0 / total as f64
+    };
+
+    if dry_run {
+        println!(
+            "Collection '{}': {} of {} bytes reclaimable ({:.1}%)",
+            collection, reclaimable, total, percent
+        );
+        return Ok(());
     }
 
+    store.compact()?;
+    println!(
+        "✓ Compacted collection '{}' ({} bytes reclaimed, {} bytes remain)",
+        collection,
+        total - store.data_len(),
+        store.data_len()
+    );
+
     Ok(())
 }
 
-fn cmd_get(path: &PathBuf, collection: &str, doc_id: &str, pretty: bool) -> Result<()> {
-    let engine = Engine::open(path, collection)?;
-    let doc = engine.get_document(doc_id)?;
+fn cmd_encrypt(path: &Path, collection: &str, decrypt: bool) -> Result<()> {
+    if !Layout::has_fast_data(path, collection) {
+        anyhow::bail!("Collection '{}' has no fast store data", collection);
+    }
+    let key = EncryptionKey::from_env()?;
+    if !decrypt && key.is_none() {
+        anyhow::bail!(
+            "No key given; set {} or {} (generate one with --generate-key)",
+            encryption::KEY_ENV,
+            encryption::KEY_FILE_ENV
+        );
+    }
+    let mut store = FastStore::open(path, collection, IMPORT_BATCH_SIZE)?;
+    if decrypt {
+        store.set_encryption(None)?;
+        println!("✓ Decrypted collection '{}'", collection);
+    } else if let Some(key) = key {
+        store.set_encryption(Some(&key))?;
+        println!(
+            "✓ Encrypted collection '{}' with key {}",
+            collection,
+            key.id()
+        );
+    }
 
-    if pretty {
-        println!("{}", serde_json::to_string_pretty(&doc)?);
-    } else {
-        println!("{}", serde_json::to_string(&doc)?);
+    Ok(())
+}
+
+fn cmd_migrate(
+    path: &Path,
+    collection: &str,
+    script: Option<&Path>,
+    renames: &[String],
+    drops: &[String],
+) -> Result<()> {
+    if Layout::has_fast_data(path, collection) {
+        anyhow::bail!(
+            "Collection '{}' uses the fast store; migrate supports file-per-document collections",
+            collection
+        );
+    }
+
+    // Script steps, then renames, then drops
+    let mut edits = match script {
+        Some(script) => parse_migration_script(
+            &std::fs::read_to_string(script)
+                .with_context(|| format!("Failed to read {}", script.display()))?,
+        )?,
+        None => Vec::new(),
+    };
+    for rename in renames {
+        let (from, to) = rename
+            .split_once('=')
+            .with_context(|| format!("Invalid rename '{}' (expected OLD=NEW)", rename))?;
+        edits.push(FieldEdit::Copy {
+            from: from.trim().to_string(),
+            to: to.trim().to_string(),
+        });
+        edits.push(FieldEdit::Drop(from.trim().to_string()));
+    }
+    edits.extend(drops.iter().map(|field| FieldEdit::Drop(field.clone())));
+    if edits.is_empty() {
+        anyhow::bail!("Nothing to migrate: pass --script, --rename or --drop");
+    }
+
+    let report = Engine::open(path, collection)?.migrate(|mut doc| {
+        for edit in &edits {
+            edit.apply(&mut doc);
+        }
+        Ok(doc)
+    })?;
+
+    println!(
+        "✓ Migrated collection '{}' ({} documents)",
+        collection, report.documents
+    );
+    if report.resumed_from > 0 {
+        println!(
+            "  Resumed after {} documents migrated earlier",
+            report.resumed_from
+        );
+    }
+    for mapping in &report.mappings {
+        let short = |id: &str| id.chars().take(12).collect::<String>();
+        println!("  schema {} → {}", short(&mapping.from), short(&mapping.to));
     }
 
     Ok(())
 }
 
-fn cmd_put(path: &PathBuf, collection: &str, doc_id: &str, data: Option<String>) -> Result<()> {
-    let json_str = match data {
-        Some(d) => d,
-        None => {
-            use std::io::Read;
-            let mut buf = String::new();
-            std::io::stdin().read_to_string(&mut buf)?;
-            buf
+/// A document edit of `migrate`.
+#[derive(Debug, Clone, PartialEq)]
+enum FieldEdit {
+    /// Copy a field to another path (skipped if the field is missing)
+    Copy { from: String, to: String },
+    /// Remove a field
+    Drop(String),
+}
+
+impl FieldEdit {
+    fn apply(&self, doc: &mut serde_json::Value) {
+        match self {
+            FieldEdit::Copy { from, to } => {
+                if let Some(value) = field_value(doc, from).cloned() {
+                    set_field(doc, to, value);
+                }
+            }
+            FieldEdit::Drop(field) => {
+                let (parent, key) = match field.rsplit_once('.') {
+                    Some((parent, key)) => (field_value_mut(doc, parent), key),
+                    None => (Some(doc), field.as_str()),
+                };
+                if let Some(serde_json::Value::Object(obj)) = parent {
+                    obj.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// Parse a jq-style migration script: `.new = .old` and `del(.a, .b)`
+/// steps separated by `|` or newlines, with `#` comments.
+fn parse_migration_script(script: &str) -> Result<Vec<FieldEdit>> {
+    let path = |s: &str| -> Result<String> {
+        let field = s.trim().strip_prefix('.').unwrap_or_default();
+        if field.is_empty() || field.split('.').any(str::is_empty) {
+            anyhow::bail!("Invalid field path '{}' in migration script", s.trim());
         }
+        Ok(field.to_string())
     };
 
-    let doc: serde_json::Value =
-        serde_json::from_str(&json_str).context("Invalid JSON document")?;
+    let mut edits = Vec::new();
+    for line in script.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        for step in line.split('|').map(str::trim).filter(|s| !s.is_empty()) {
+            if let Some(fields) = step.strip_prefix("del(").and_then(|s| s.strip_suffix(')')) {
+                for field in fields.split(',') {
+                    edits.push(FieldEdit::Drop(path(field)?));
+                }
+            } else if let Some((to, from)) = step.split_once('=') {
+                edits.push(FieldEdit::Copy {
+                    from: path(from)?,
+                    to: path(to)?,
+                });
+            } else {
+                anyhow::bail!(
+                    "Unsupported migration step '{}' (expected `.new = .old` or `del(.field)`)",
+                    step
+                );
+            }
+        }
+    }
+    Ok(edits)
+}
 
-    // Ensure store and collection exist
-    if !path.exists() {
-        ContainerFS::create_folder(path)?;
+/// Mutable value of a (dot notation) field in a document.
+fn field_value_mut<'a>(
+    doc: &'a mut serde_json::Value,
+    field: &str,
+) -> Option<&'a mut serde_json::Value> {
+    field
+        .split('.')
+        .try_fold(doc, |value, key| value.get_mut(key))
+}
+
+/// Set a (dot notation) field, creating missing parent objects.
+fn set_field(doc: &mut serde_json::Value, field: &str, value: serde_json::Value) {
+    let mut current = doc;
+    for key in field.split('.') {
+        if !current.is_object() {
+            *current = serde_json::Value::Object(serde_json::Map::new());
+        }
+        current = &mut current[key];
     }
+    *current = value;
+}
+
+fn cmd_reindex(path: &Path, collection: &str) -> Result<()> {
+    println!("Rebuilding index for collection '{}'...", collection);
 
-    let mut writer = SyncWriter::new(path, collection)?;
-    writer.put(doc_id, &doc)?;
+    let index = IndexRegistry::rebuild(path, collection)?;
+    index.save(path, collection)?;
 
-    println!(
-        "✓ Document '{}' written to collection '{}'",
-        doc_id, collection
-    );
+    println!("✓ Index rebuilt ({} documents)", index.len());
 
     Ok(())
 }
 
-fn cmd_delete(path: &PathBuf, collection: &str, doc_id: &str) -> Result<()> {
-    let mut writer = SyncWriter::new(path, collection)?;
-    writer.delete(doc_id)?;
+fn cmd_unlock(path: &Path, force: bool) -> Result<()> {
+    let holder = WriteLock::holder(path);
+    if !WriteLock::break_lock(path, force)? {
+        println!("No write lock on {}", path.display());
+        return Ok(());
+    }
+
+    match holder {
+        Some(info) => println!(
+            "✓ Removed write lock (pid={}, host={}, since={})",
+            info.pid, info.hostname, info.timestamp
+        ),
+        None => println!("✓ Removed write lock"),
+    }
+    if ReadLock::readers_active(path) {
+        println!("  Read-only handles are still open");
+    }
+
+    Ok(())
+}
+
+fn cmd_export(path: &Path, collection: &str, output: &Path, format: DataFormat) -> Result<()> {
+    let count = match format {
+        DataFormat::Parquet => export_parquet(path, collection, output)?,
+    };
 
     println!(
-        "✓ Document '{}' deleted from collection '{}'",
-        doc_id, collection
+        "✓ Exported {} documents from '{}' to {}",
+        count,
+        collection,
+        output.display()
     );
 
     Ok(())
 }
 
-fn cmd_scan(
-    path: &PathBuf,
-    collection: &str,
-    limit: Option<usize>,
-    fields: Option<String>,
-    jsonl: bool,
-) -> Result<()> {
+fn export_parquet(path: &Path, collection: &str, output: &Path) -> Result<usize> {
     let engine = Engine::open(path, collection)?;
 
-    let field_list: Option<Vec<String>> =
-        fields.map(|f| f.split(',').map(|s| s.trim().to_string()).collect());
-    let field_refs: Option<Vec<&str>> = field_list
-        .as_ref()
-        .map(|f| f.iter().map(|s| s.as_str()).collect());
+    let doc_schema = full_engine_schema(&engine, None)?;
+    let mut fields = vec![Field::new(ID_COLUMN, DataType::Utf8, false)];
+    fields.extend(
+        doc_schema
+            .fields()
+            .iter()
+            .filter(|f| f.name() != ID_COLUMN)
+            .map(|f| f.as_ref().clone()),
+    );
+    let schema = Arc::new(Schema::new(fields));
 
-    let mut scanner = engine.scan(None, field_refs.as_deref())?;
+    let docs = engine.doc_ids().iter().map(|doc_id| {
+        let mut doc = engine.get_document(doc_id)?;
+        if let Some(obj) = doc.as_object_mut() {
+            obj.insert(ID_COLUMN.to_string(), doc_id.clone().into());
+        }
+        Ok(doc)
+    });
 
+    let file = File::create(output).context("Failed to create output file")?;
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
     let mut count = 0;
-    let max = limit.unwrap_or(usize::MAX);
+    for batch in RecordBatchIter::new(docs, schema, DEFAULT_BATCH_SIZE) {
+        let batch = batch?;
+        count += batch.num_rows();
+        writer.write(&batch)?;
+    }
+    writer.close()?;
 
-    if !jsonl {
-        println!("[");
+    Ok(count)
+}
+
+fn cmd_import(
+    path: &Path,
+    collection: &str,
+    input: &Path,
+    format: DataFormat,
+    id_column: &str,
+) -> Result<()> {
+    if !path.exists() {
+        ContainerFS::create_folder(path)?;
     }
 
-    while let Some(doc) = scanner.next().transpose()? {
-        if count >= max {
-            break;
+    let count = match format {
+        DataFormat::Parquet => import_parquet(path, collection, input, id_column)?,
+    };
+
+    println!(
+        "✓ Imported {} documents into collection '{}'",
+        count, collection
+    );
+
+    Ok(())
+}
+
+fn import_parquet(path: &Path, collection: &str, input: &Path, id_column: &str) -> Result<usize> {
+    let file = File::open(input).context("Failed to open input file")?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?
+        .with_batch_size(DEFAULT_BATCH_SIZE)
+        .build()?;
+
+    // Through the collection's own store, so JSONL collections are written
+    // as JSONL
+    let mut store = open_store(path, collection)?;
+    let mut count = 0;
+    for batch in reader {
+        for mut doc in record_batch_to_docs(&batch?)? {
+            // The internal `_id` column is dropped; user columns are kept
+            let id_value = doc.as_object_mut().and_then(|obj| {
+                if id_column == ID_COLUMN {
+                    obj.remove(id_column)
+                } else {
+                    obj.get(id_column).cloned()
+                }
+            });
+            match id_value {
+                Some(serde_json::Value::String(s)) => store.put(&s, doc)?,
+                Some(v @ serde_json::Value::Number(_)) => store.put(&v.to_string(), doc)?,
+                // ULIDs, so repeated imports add documents instead of
+                // overwriting the last import's
+                _ => {
+                    store.put_auto(doc, IdStrategy::Ulid)?;
+                }
+            }
+            count += 1;
         }
+        store.flush()?;
+    }
 
-        if jsonl {
-            println!("{}", serde_json::to_string(&doc)?);
-        } else {
-            if count > 0 {
-                println!(",");
+    Ok(count)
+}
+
+/// Infer a JSON value from a CSV cell (bool, integer, float, else string).
+fn infer_csv_value(cell: &str) -> serde_json::Value {
+    if cell.eq_ignore_ascii_case("true") {
+        return serde_json::Value::Bool(true);
+    }
+    if cell.eq_ignore_ascii_case("false") {
+        return serde_json::Value::Bool(false);
+    }
+    if let Ok(i) = cell.parse::<i64>() {
+        return i.into();
+    }
+    if let Some(n) = cell
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+    {
+        return serde_json::Value::Number(n);
+    }
+    serde_json::Value::String(cell.to_string())
+}
+
+fn cmd_import_csv(
+    file: &Path,
+    path: &Path,
+    collection: &str,
+    id_column: Option<&str>,
+    batch_size: usize,
+) -> Result<()> {
+    let batch_size = batch_size.max(1);
+    let mut reader = csv::Reader::from_path(file).context("Failed to open CSV file")?;
+    let headers = reader.headers()?.clone();
+    let id_idx = match id_column {
+        Some(name) => Some(
+            headers
+                .iter()
+                .position(|h| h == name)
+                .with_context(|| format!("ID column '{}' not found in CSV header", name))?,
+        ),
+        None => None,
+    };
+
+    // Through the collection's own store, so JSONL collections are written
+    // as JSONL
+    Layout::init_root(path)?;
+    let mut store = open_store(path, collection)?;
+    let mut count = 0;
+
+    for (row, record) in reader.records().enumerate() {
+        let record = record.with_context(|| format!("Invalid CSV row {}", row + 1))?;
+
+        // Empty cells are omitted rather than stored as empty strings
+        let mut doc = serde_json::Map::new();
+        for (name, cell) in headers.iter().zip(record.iter()) {
+            if !cell.is_empty() && name != ID_COLUMN {
+                doc.insert(name.to_string(), infer_csv_value(cell));
             }
-            print!("  {}", serde_json::to_string(&doc)?);
         }
 
+        match id_idx {
+            Some(idx) => {
+                let doc_id = record.get(idx).unwrap_or_default();
+                Layout::validate_doc_id(doc_id)?;
+                store.put(doc_id, doc.into())?;
+            }
+            // ULIDs, so repeated imports add rows instead of overwriting the
+            // last import's
+            None => {
+                store.put_auto(doc.into(), IdStrategy::Ulid)?;
+            }
+        }
         count += 1;
+        if count % batch_size == 0 {
+            store.flush()?;
+        }
     }
+    store.flush()?;
 
-    if !jsonl {
-        println!();
-        println!("]");
+    println!(
+        "✓ Imported {} rows from {} into collection '{}'",
+        count,
+        file.display(),
+        collection
+    );
+
+    Ok(())
+}
+
+/// Write one JSONL line to `store` and return its document ID.
+///
+/// Lines that already have an `_id` (and no `id_field` override) are
+/// written without parsing, and lines without one get a generated ULID, so
+/// repeated imports add documents instead of overwriting the last
+/// import's. An `_id` that is neither a string nor an integer is an error.
+fn import_jsonl_line(
+    store: &mut dyn Store,
+    line: &[u8],
+    id_field: Option<&str>,
+    row: usize,
+) -> Result<String> {
+    if let Some(field) = id_field.filter(|&field| field != ID_COLUMN) {
+        let doc: serde_json::Value = serde_json::from_slice(line)
+            .with_context(|| format!("Invalid JSON on line {}", row + 1))?;
+        let doc_id = match doc.get(field) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(v @ serde_json::Value::Number(_)) => v.to_string(),
+            _ if !doc.is_object() => anyhow::bail!("Line {} is not a JSON object", row + 1),
+            _ => anyhow::bail!("Line {} has no string or number '{}' field", row + 1, field),
+        };
+        Layout::validate_doc_id(&doc_id)?;
+        store.put(&doc_id, doc)?;
+        return Ok(doc_id);
     }
 
-    eprintln!("({} documents)", count);
+    if let Some(doc_id) = FastStore::extract_id_fast(line) {
+        Layout::validate_doc_id(&doc_id)?;
+        store.put_raw_line(&doc_id, line)?;
+        return Ok(doc_id);
+    }
+    // Without a string or integer `_id`, the line is parsed to tell a
+    // missing `_id` from one of another type
+    let doc: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(line)
+        .with_context(|| format!("Line {} is not a JSON object", row + 1))?;
+    if let Some(value) = doc.get(ID_COLUMN) {
+        anyhow::bail!(
+            "Line {} has an {} of type {}; IDs must be strings or integers",
+            row + 1,
+            ID_COLUMN,
+            json_type_name(value)
+        );
+    }
+    Ok(store.put_auto(doc.into(), IdStrategy::Ulid)?)
+}
+
+/// JSON type of a value, for error messages.
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(n) if n.is_f64() => "float",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+fn cmd_import_jsonl(
+    file: &Path,
+    path: &Path,
+    collection: &str,
+    id_field: Option<&str>,
+    batch_size: usize,
+) -> Result<()> {
+    let batch_size = batch_size.max(1);
+    let mut reader = BufReader::with_capacity(
+        1024 * 1024,
+        File::open(file).context("Failed to open JSONL file")?,
+    );
+
+    // Through the collection's own store, so file-per-document collections
+    // are written as files
+    Layout::init_root(path)?;
+    let mut store = open_store(path, collection)?;
+
+    let mut line = Vec::new();
+    let mut count = 0;
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let Some(start) = line.iter().position(|b| !b.is_ascii_whitespace()) else {
+            continue;
+        };
+        let end = line
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .unwrap_or(start)
+            + 1;
+
+        import_jsonl_line(store.as_mut(), &line[start..end], id_field, count)?;
+        count += 1;
+        if count % batch_size == 0 {
+            store.flush()?;
+        }
+    }
+    store.flush()?;
+
+    println!(
+        "✓ Imported {} documents from {} into collection '{}'",
+        count,
+        file.display(),
+        collection
+    );
 
     Ok(())
 }
 
-fn cmd_reindex(path: &Path, collection: &str) -> Result<()> {
-    println!("Rebuilding index for collection '{}'...", collection);
+fn cmd_export_jsonl(output: Option<&Path>, path: &Path, collection: &str) -> Result<()> {
+    let store = FastStore::open_with_mode(path, collection, IMPORT_BATCH_SIZE, OpenMode::Read)?;
 
-    let index = IndexRegistry::rebuild(path, collection)?;
-    index.save(path, collection)?;
+    let count = match output {
+        Some(output) => {
+            let file = File::create(output).context("Failed to create output file")?;
+            let mut writer = BufWriter::with_capacity(1024 * 1024, file);
+            let count = store.export_jsonl(&mut writer)?;
+            writer.flush()?;
+            count
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            let count = store.export_jsonl(&mut stdout)?;
+            stdout.flush()?;
+            count
+        }
+    };
 
-    println!("✓ Index rebuilt ({} documents)", index.len());
+    eprintln!("({} documents)", count);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use arrow::{
+        array::{ArrayRef, Int64Array, StringArray},
+        record_batch::RecordBatch,
+    };
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Write a Parquet file with the given columns.
+    fn write_parquet(path: &Path, columns: Vec<(&str, ArrayRef)>) {
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_import_parquet() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        ContainerFS::create_folder(&root).unwrap();
+
+        // An existing JSONL collection stays JSONL
+        let mut store = FastStore::open(&root, "train", 100).unwrap();
+        store.put("old", json!({"n": 0})).unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let with_ids = dir.path().join("with_ids.parquet");
+        write_parquet(
+            &with_ids,
+            vec![
+                (
+                    "_id",
+                    Arc::new(StringArray::from(vec!["a", "b"])) as ArrayRef,
+                ),
+                ("n", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+            ],
+        );
+        assert_eq!(
+            import_parquet(&root, "train", &with_ids, ID_COLUMN).unwrap(),
+            2
+        );
+        let engine = Engine::open(&root, "train").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Jsonl);
+        assert_eq!(engine.len(), 3);
+        assert_eq!(engine.get_document("b").unwrap(), json!({"n": 2}));
+
+        // Generated IDs don't overwrite an earlier import's
+        let without_ids = dir.path().join("without_ids.parquet");
+        write_parquet(
+            &without_ids,
+            vec![("n", Arc::new(Int64Array::from(vec![3, 4])) as ArrayRef)],
+        );
+        for _ in 0..2 {
+            import_parquet(&root, "train", &without_ids, ID_COLUMN).unwrap();
+        }
+        assert_eq!(Engine::open(&root, "train").unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_export_parquet_fast_store() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        ContainerFS::create_folder(&root).unwrap();
+        let mut store = FastStore::open(&root, "fast", 100).unwrap();
+        store.put("a", json!({"text": "x", "n": 1})).unwrap();
+        store.put("b", json!({"text": "y", "n": 2.5})).unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let output = dir.path().join("fast.parquet");
+        assert_eq!(export_parquet(&root, "fast", &output).unwrap(), 2);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&output).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut docs = Vec::new();
+        for batch in reader {
+            docs.extend(record_batch_to_docs(&batch.unwrap()).unwrap());
+        }
+        docs.sort_by_key(|doc| doc["_id"].as_str().unwrap().to_string());
+        assert_eq!(
+            docs,
+            [
+                json!({"_id": "a", "n": 1.0, "text": "x"}),
+                json!({"_id": "b", "n": 2.5, "text": "y"})
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_jsonl_line() {
+        let mut store = FastStore::open_in_memory("test", 100).unwrap();
+        let mut import =
+            |line: &str, row| import_jsonl_line(&mut store, line.as_bytes(), None, row);
+
+        // Lines with an ID are written under it
+        assert_eq!(import(r#"{"n":1,"_id":"a"}"#, 0).unwrap(), "a");
+        assert_eq!(import(r#"{"_id":42}"#, 1).unwrap(), "42");
+
+        // Lines without one get a generated ID
+        let generated: Vec<_> = [r#"{"n":1}"#, "{ }", r#"{"a":{"_id":null}}"#]
+            .iter()
+            .map(|line| import(line, 2).unwrap())
+            .collect();
+        assert!(generated.iter().all(|id| id.len() == 26));
+        assert_ne!(generated[0], generated[1]);
+
+        // IDs of other types are errors naming the line
+        for (line, kind) in [
+            (r#"{"_id":null}"#, "null"),
+            (r#"{"n":1,"_id":1.5}"#, "float"),
+            (r#"{"_id":{"a":1}}"#, "object"),
+        ] {
+            let err = import(line, 2).unwrap_err().to_string();
+            assert!(err.starts_with("Line 3 ") && err.contains(kind), "{}", err);
+        }
+        assert!(import("[1]", 0).is_err());
+        assert!(import(r#"{"n":"#, 0).is_err());
+
+        // Or IDs come from another field
+        let doc_id = import_jsonl_line(&mut store, br#"{"key":7,"n":2}"#, Some("key"), 0).unwrap();
+        assert_eq!(doc_id, "7");
+        assert!(import_jsonl_line(&mut store, br#"{"n":2}"#, Some("key"), 0).is_err());
+
+        store.flush().unwrap();
+        assert_eq!(store.len(), 6);
+        assert_eq!(store.get("a").unwrap(), json!({"n": 1}));
+        assert_eq!(
+            store.get(&generated[2]).unwrap(),
+            json!({"a": {"_id": null}})
+        );
+        assert_eq!(store.get("7").unwrap(), json!({"key": 7, "n": 2}));
+    }
+
+    #[test]
+    fn test_import_jsonl() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        let file = dir.path().join("docs.jsonl");
+        std::fs::write(
+            &file,
+            "{\"n\":1}\n\n  {\"n\":2}  \n{\"_id\":\"fixed\",\"n\":3}",
+        )
+        .unwrap();
+
+        // Lines without an ID add documents on every import; those with one
+        // replace theirs
+        ContainerFS::create_folder(&root).unwrap();
+        drop(FastStore::open(&root, "jsonl", 100).unwrap());
+        for _ in 0..2 {
+            cmd_import_jsonl(&file, &root, "jsonl", None, 2).unwrap();
+        }
+        let mut store = FastStore::open(&root, "jsonl", 100).unwrap();
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.get("fixed").unwrap(), json!({"n": 3}));
+        assert!(store.verify().unwrap().is_ok());
+
+        // File-per-document collections get files
+        cmd_import_jsonl(&file, &root, "files", None, 100).unwrap();
+        let engine = Engine::open(&root, "files").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Files);
+        assert_eq!(engine.len(), 3);
+        assert!(Layout::doc_file(&root, "files", "fixed").exists());
+    }
+
+    #[test]
+    fn test_init_keeps_file_documents() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        cmd_init(&root, "train", &CollectionConfig::default()).unwrap();
+        assert_eq!(
+            StorageMode::load(&root, "train").unwrap(),
+            Some(StorageMode::Files)
+        );
+        let mut store = open_store(&root, "train").unwrap();
+        for i in 0..5 {
+            store.put(&format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        store.flush().unwrap();
+        drop(store);
+
+        // A JSONL import adds files rather than taking the collection over
+        // and hiding them
+        let file = dir.path().join("more.jsonl");
+        std::fs::write(&file, "{\"_id\":\"x\"}\n").unwrap();
+        cmd_import_jsonl(&file, &root, "train", None, 100).unwrap();
+        let engine = Engine::open(&root, "train").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Files);
+        assert_eq!(engine.len(), 6);
+        assert_eq!(engine.get_document("doc3").unwrap(), json!({"n": 3}));
+
+        let config = CollectionConfig {
+            compression: Compression::Zstd,
+            ..Default::default()
+        };
+        cmd_init(&root, "fast", &config).unwrap();
+        assert_eq!(
+            StorageMode::load(&root, "fast").unwrap(),
+            Some(StorageMode::Jsonl)
+        );
+    }
+
+    #[test]
+    fn test_import_csv() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        let file = dir.path().join("rows.csv");
+        std::fs::write(
+            &file,
+            "key,name,n,score,ok\nk1,alice,1,0.5,true\nk2,bob,,2.0,FALSE\nk3,007,-3,NaN,yes\n",
+        )
+        .unwrap();
+
+        // A JSONL collection stays JSONL
+        ContainerFS::create_folder(&root).unwrap();
+        drop(FastStore::open(&root, "rows", 100).unwrap());
+        cmd_import_csv(&file, &root, "rows", Some("key"), 2).unwrap();
+        let store = FastStore::open(&root, "rows", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["k1", "k2", "k3"]);
+        assert_eq!(
+            store.get("k1").unwrap(),
+            json!({"key": "k1", "name": "alice", "n": 1, "score": 0.5, "ok": true})
+        );
+        // Empty cells are left out; cells that aren't booleans or finite numbers
+        // stay strings
+        assert_eq!(
+            store.get("k2").unwrap(),
+            json!({"key": "k2", "name": "bob", "score": 2.0, "ok": false})
+        );
+        assert_eq!(
+            store.get("k3").unwrap(),
+            json!({"key": "k3", "name": 7, "n": -3, "score": "NaN", "ok": "yes"})
+        );
+
+        // Without an ID column, rows get generated IDs, so a second import
+        // adds to the first
+        for _ in 0..2 {
+            cmd_import_csv(&file, &root, "generated", None, 2).unwrap();
+        }
+        let engine = Engine::open(&root, "generated").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Files);
+        assert_eq!(engine.len(), 6);
+        assert!(engine.doc_ids().iter().all(|id| id.len() == 26));
+
+        let err = cmd_import_csv(&file, &root, "rows", Some("missing"), 100).unwrap_err();
+        assert!(err.to_string().contains("'missing'"), "{}", err);
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        ContainerFS::create_folder(&root).unwrap();
+        let mut store = FastStore::open(&root, "fast", 100).unwrap();
+        for i in 0..10 {
+            store.put(format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        for i in 0..5 {
+            store.delete(&format!("doc{}", i)).unwrap();
+        }
+        store.flush().unwrap();
+        let (total, reclaimable) = (store.data_len(), store.reclaimable_bytes());
+        assert!(reclaimable > 0);
+        drop(store);
+
+        // A dry run leaves the data file alone
+        let data_file = Layout::data_file(&root, "fast");
+        let before = std::fs::read(&data_file).unwrap();
+        cmd_compact(&root, "fast", true).unwrap();
+        assert_eq!(std::fs::read(&data_file).unwrap(), before);
+
+        cmd_compact(&root, "fast", false).unwrap();
+        let store = FastStore::open(&root, "fast", 100).unwrap();
+        assert_eq!(store.data_len(), total - reclaimable);
+        assert_eq!(store.reclaimable_bytes(), 0);
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.get("doc7").unwrap(), json!({"n": 7}));
+
+        // File-per-document collections have nothing to compact
+        Engine::open(&root, "docs").unwrap();
+        assert!(cmd_compact(&root, "docs", true).is_err());
+    }
+
+    #[test]
+    fn test_stats_and_validate_jsonl() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        ContainerFS::create_folder(&root).unwrap();
+        let mut store = FastStore::open(&root, "fast", 100).unwrap();
+        store.put("a", json!({"n": 1})).unwrap();
+        store.put("b", json!({"n": 2})).unwrap();
+        store.put("a", json!({"n": 3})).unwrap();
+        store.delete("b").unwrap();
+        store.flush().unwrap();
+        let (data_len, dead_bytes) = (store.data_len(), store.dead_bytes());
+        drop(store);
+
+        let entry = stats_entry(&Engine::open(&root, "fast").unwrap()).unwrap();
+        assert_eq!(entry["storage"], "jsonl");
+        assert_eq!(entry["doc_count"], 1);
+        assert_eq!(entry["data_size"], data_len);
+        assert_eq!(entry["segments"], 1);
+        assert_eq!(entry["compression"], "none");
+        assert_eq!(entry["encrypted"], false);
+        assert_eq!(entry["dead_bytes"], dead_bytes);
+        assert!(entry["dead_ratio"].as_f64().unwrap() > 0.0);
+        assert_eq!(entry["tombstones"], 1);
+        cmd_stats(&root, None, false).unwrap();
+
+        // Validation checks JSONL collections against index.bin, so a fix
+        // leaves their doc index alone
+        let doc_index = std::fs::read(Layout::doc_index(&root, "fast")).ok();
+        cmd_validate(&root, Some("fast"), true, None, false).unwrap();
+        assert_eq!(
+            std::fs::read(Layout::doc_index(&root, "fast")).ok(),
+            doc_index
+        );
+        assert_eq!(Engine::open(&root, "fast").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,513 @@
+//! `zippy serve`: a small HTTP JSON API over a ZDS store.
+//!
+//! Routes:
+//!
+//! | Method   | Path                          | Body / response                    |
+//! |----------|-------------------------------|------------------------------------|
+//! | `GET`    | `/collections`                | `{"collections": [...]}`           |
+//! | `GET`    | `/collections/{c}/docs/{id}`  | the document                       |
+//! | `PUT`    | `/collections/{c}/docs/{id}`  | JSON document in, `{"ok": true}`   |
+//! | `DELETE` | `/collections/{c}/docs/{id}`  | `{"ok": true}`                     |
+//! | `GET`    | `/collections/{c}/scan`       | chunked JSONL stream               |
+//!
+//! `scan` accepts `filter`, `fields` (comma-separated) and `limit` query
+//! parameters, with the same syntax as `zippy scan`. Errors are returned as
+//! `{"error": "..."}` with a matching status code.
+//!
+//! Requests are handled by a fixed pool of worker threads, and request
+//! bodies are limited in size, so a client can't exhaust either.
+
+use std::{
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use zippy_data::{engine::Engine, engine::Scanner, store::open_store, ContainerFS, Layout};
+
+/// Shared state of the server threads.
+struct ServeState {
+    path: PathBuf,
+    read_only: bool,
+    /// Largest request body accepted, in bytes
+    max_body: u64,
+    /// Serializes writes made through this server.
+    write_lock: Mutex<()>,
+}
+
+/// An HTTP error: status code and message.
+struct HttpError(u16, String);
+
+impl From<zippy_data::Error> for HttpError {
+    fn from(e: zippy_data::Error) -> Self {
+        use zippy_data::Error;
+        let status = match &e {
+            Error::CollectionNotFound(_) | Error::DocumentNotFound(_) => 404,
+            Error::Io(io) if io.kind() == std::io::ErrorKind::NotFound => 404,
+            Error::ReadOnly(_) => 403,
+            Error::WriteLock(_) | Error::DocumentExists(_) => 409,
+            Error::Json(_)
+            | Error::InvalidDocId(_)
+            | Error::Validation(_)
+            | Error::Codec(_)
+            | Error::SchemaMismatch { .. }
+            | Error::SchemaViolation(_)
+            | Error::StrictModeViolation(_) => 400,
+            _ => 500,
+        };
+        HttpError(status, e.to_string())
+    }
+}
+
+impl From<anyhow::Error> for HttpError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<zippy_data::Error>() {
+            Ok(e) => e.into(),
+            Err(e) => HttpError(400, format!("{:#}", e)),
+        }
+    }
+}
+
+type HttpResult<T> = std::result::Result<T, HttpError>;
+
+type HttpResponse = Response<Box<dyn Read + Send>>;
+
+/// Serve `path` on `host:port` until the process is stopped.
+///
+/// Requests are handled by `workers` threads; writes are serialized.
+pub fn run(
+    path: &Path,
+    host: &str,
+    port: u16,
+    read_only: bool,
+    workers: usize,
+    max_body: u64,
+) -> Result<()> {
+    ContainerFS::open(path)?;
+
+    let addr = format!("{}:{}", host, port);
+    let server = Server::http(&addr).map_err(|e| anyhow!("Failed to bind {}: {}", addr, e))?;
+
+    println!("Serving {} on http://{}", path.display(), addr);
+    if read_only {
+        println!("  Mode: read-only");
+    }
+
+    serve(
+        server,
+        ServeState {
+            path: path.to_path_buf(),
+            read_only,
+            max_body,
+            write_lock: Mutex::new(()),
+        },
+        workers,
+    );
+    Ok(())
+}
+
+/// Handle the requests of `server` on `workers` threads until it is
+/// closed.
+fn serve(server: Server, state: ServeState, workers: usize) {
+    let server = Arc::new(server);
+    let state = Arc::new(state);
+    let threads: Vec<_> = (0..workers.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let state = Arc::clone(&state);
+            std::thread::spawn(move || {
+                while let Ok(request) = server.recv() {
+                    handle(&state, request);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
+
+fn handle(state: &ServeState, mut request: Request) {
+    let response = match route(state, &mut request) {
+        Ok(response) => response,
+        Err(HttpError(status, message)) => json_response(status, &json!({ "error": message })),
+    };
+    // The client may have hung up; nothing left to report to
+    let _ = request.respond(response);
+}
+
+fn route(state: &ServeState, request: &mut Request) -> HttpResult<HttpResponse> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_decode)
+        .collect::<HttpResult<Vec<_>>>()?;
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let method = request.method().clone();
+
+    // Decoded segments can hold `/` and `..`: check them before they are
+    // used as paths
+    match segments.as_slice() {
+        ["collections", collection, "docs", doc_id] => {
+            Layout::validate_collection_name(collection)?;
+            Layout::validate_doc_id(doc_id)?;
+        }
+        ["collections", collection, ..] => Layout::validate_collection_name(collection)?,
+        _ => {}
+    }
+
+    match (&method, segments.as_slice()) {
+        (Method::Get, ["collections"]) => list_collections(state),
+        (Method::Get, ["collections", collection, "docs", doc_id]) => {
+            let engine = Engine::open(&state.path, collection)?;
+            Ok(json_response(200, &engine.get_document(doc_id)?))
+        }
+        (Method::Put, ["collections", collection, "docs", doc_id]) => {
+            check_writable(state)?;
+            let body = read_body(state, request)?;
+            let doc: Value = serde_json::from_slice(&body)
+                .map_err(|e| HttpError(400, format!("Invalid JSON document: {}", e)))?;
+
+            let _guard = state.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+            let mut store = open_store(&state.path, collection)?;
+            store.put(doc_id, doc)?;
+            store.flush()?;
+            Ok(json_response(200, &json!({ "ok": true })))
+        }
+        (Method::Delete, ["collections", collection, "docs", doc_id]) => {
+            check_writable(state)?;
+            let _guard = state.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+            let mut store = open_store(&state.path, collection)?;
+            store.delete(doc_id)?;
+            store.flush()?;
+            Ok(json_response(200, &json!({ "ok": true })))
+        }
+        (Method::Get, ["collections", collection, "scan"]) => scan(state, collection, query),
+        (_, ["collections"])
+        | (_, ["collections", _, "docs", _])
+        | (_, ["collections", _, "scan"]) => {
+            Err(HttpError(405, format!("Method {} not allowed", method)))
+        }
+        _ => Err(HttpError(404, format!("No route for {}", path))),
+    }
+}
+
+/// Read a request body of at most `max_body` bytes.
+fn read_body(state: &ServeState, request: &mut Request) -> HttpResult<Vec<u8>> {
+    let too_large = || {
+        HttpError(
+            413,
+            format!("Request body is larger than {} bytes", state.max_body),
+        )
+    };
+    if request
+        .body_length()
+        .is_some_and(|len| len as u64 > state.max_body)
+    {
+        return Err(too_large());
+    }
+    // Chunked bodies have no length up front: read one byte past the limit
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(state.max_body + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| HttpError(400, format!("Failed to read body: {}", e)))?;
+    if body.len() as u64 > state.max_body {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn check_writable(state: &ServeState) -> HttpResult<()> {
+    if state.read_only {
+        return Err(HttpError(403, "Server is read-only".to_string()));
+    }
+    Ok(())
+}
+
+fn list_collections(state: &ServeState) -> HttpResult<HttpResponse> {
+    let container = ContainerFS::open(&state.path)?;
+    let collections = container.list_collections()?;
+    Ok(json_response(200, &json!({ "collections": collections })))
+}
+
+fn scan(state: &ServeState, collection: &str, query: &str) -> HttpResult<HttpResponse> {
+    let mut filter = None;
+    let mut fields = None;
+    let mut limit = None;
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(&value.replace('+', " "))?;
+        match key {
+            "filter" => filter = Some(value),
+            "fields" => fields = Some(value),
+            "limit" => {
+                limit = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| HttpError(400, format!("Invalid limit: {}", value)))?,
+                )
+            }
+            _ => return Err(HttpError(400, format!("Unknown query parameter: {}", key))),
+        }
+    }
+
+    let predicate = filter.as_deref().map(crate::parse_filter).transpose()?;
+    let field_list: Option<Vec<String>> =
+        fields.map(|f| f.split(',').map(|s| s.trim().to_string()).collect());
+    let field_refs: Option<Vec<&str>> = field_list
+        .as_ref()
+        .map(|f| f.iter().map(|s| s.as_str()).collect());
+
+    let engine = Engine::open(&state.path, collection)?;
+    let scanner = engine.scan(predicate.as_ref(), field_refs.as_deref())?;
+
+    let stream = ScanStream {
+        scanner,
+        remaining: limit.unwrap_or(usize::MAX),
+        line: Vec::new(),
+        pos: 0,
+    };
+    // No content length: tiny_http sends the body with chunked encoding
+    Ok(Response::new(
+        StatusCode(200),
+        vec![header("Content-Type", "application/x-ndjson")],
+        Box::new(stream),
+        None,
+        None,
+    ))
+}
+
+/// Scan results rendered as JSONL on demand, one document at a time.
+///
+/// A scan error mid-stream is written as a final `{"error": "..."}` line,
+/// since the status code has already been sent.
+struct ScanStream {
+    scanner: Scanner,
+    remaining: usize,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ScanStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.line.len() {
+            if self.remaining == 0 {
+                return Ok(0);
+            }
+            let doc = match self.scanner.next() {
+                None => return Ok(0),
+                Some(Ok(doc)) => {
+                    self.remaining -= 1;
+                    doc
+                }
+                Some(Err(e)) => {
+                    self.remaining = 0;
+                    json!({ "error": e.to_string() })
+                }
+            };
+            self.line = serde_json::to_vec(&doc)?;
+            self.line.push(b'\n');
+            self.pos = 0;
+        }
+
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn json_response(status: u16, value: &Value) -> HttpResponse {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    let len = body.len();
+    Response::new(
+        StatusCode(status),
+        vec![header("Content-Type", "application/json")],
+        Box::new(Cursor::new(body)),
+        Some(len),
+        None,
+    )
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+/// Decode `%XX` escapes in a URL path segment or query value.
+fn percent_decode(s: &str) -> HttpResult<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes
+                .get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| HttpError(400, format!("Invalid escape in URL: {}", s)))?;
+            out.push(hex);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| HttpError(400, format!("URL is not valid UTF-8: {}", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        net::{SocketAddr, TcpStream},
+    };
+
+    use tempfile::TempDir;
+    use zippy_data::{engine::StorageMode, FastStore};
+
+    use super::*;
+
+    /// Serve `root` on a free port in the background, with two workers
+    /// and bodies of up to 1 KiB.
+    fn start(root: &Path, read_only: bool) -> SocketAddr {
+        ContainerFS::create_folder(root).unwrap();
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        let state = ServeState {
+            path: root.to_path_buf(),
+            read_only,
+            max_body: 1024,
+            write_lock: Mutex::new(()),
+        };
+        std::thread::spawn(move || serve(server, state, 2));
+        addr
+    }
+
+    /// Send a request and return the status and body of the response.
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        // HTTP/1.0, so the response isn't chunked
+        write!(
+            stream,
+            "{} {} HTTP/1.0\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn test_serve_rejects_unsafe_names() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("store");
+        let addr = start(&root, false);
+
+        for path in [
+            "/collections/..%2F..%2Foutside/docs/pwn",
+            "/collections/.hidden/docs/a",
+            "/collections/users/docs/..%2F..%2F..%2Fpwn",
+            "/collections/..%2Foutside/scan",
+        ] {
+            for method in ["PUT", "GET", "DELETE"] {
+                let (status, body) = request(addr, method, path, "{}");
+                assert_eq!(status, 400, "{} {}: {}", method, path, body);
+            }
+        }
+        assert!(!tmp.path().join("outside").exists());
+        assert!(!tmp.path().join("pwn.json").exists());
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_serve_writes_through_the_collections_store() {
+        let tmp = TempDir::new().unwrap();
+        let addr = start(tmp.path(), false);
+        let mut store = FastStore::open(tmp.path(), "jsonl", 100).unwrap();
+        store.put("a", json!({"v": 1})).unwrap();
+        drop(store);
+
+        for collection in ["jsonl", "files"] {
+            let docs = format!("/collections/{}/docs", collection);
+            let (status, _) = request(addr, "PUT", &format!("{}/b", docs), r#"{"v":2}"#);
+            assert_eq!(status, 200);
+            let (status, _) = request(addr, "PUT", &format!("{}/a", docs), r#"{"v":9}"#);
+            assert_eq!(status, 200);
+            let (_, body) = request(addr, "GET", &format!("{}/b", docs), "");
+            assert_eq!(
+                serde_json::from_str::<Value>(&body).unwrap(),
+                json!({"v": 2})
+            );
+            let (_, body) = request(addr, "GET", &format!("{}/a", docs), "");
+            assert_eq!(
+                serde_json::from_str::<Value>(&body).unwrap(),
+                json!({"v": 9})
+            );
+
+            let (status, _) = request(addr, "DELETE", &format!("{}/a", docs), "");
+            assert_eq!(status, 200);
+            let (status, _) = request(addr, "GET", &format!("{}/a", docs), "");
+            assert_eq!(status, 404);
+        }
+        assert_eq!(
+            StorageMode::detect(tmp.path(), "jsonl").unwrap(),
+            StorageMode::Jsonl
+        );
+        assert!(!Layout::docs_dir(tmp.path(), "jsonl")
+            .join("b.json")
+            .exists());
+    }
+
+    #[test]
+    fn test_serve_limits() {
+        let tmp = TempDir::new().unwrap();
+        let addr = start(tmp.path(), false);
+
+        // Bodies over the limit, by length or as they are read
+        let big = format!(r#"{{"text":"{}"}}"#, "x".repeat(2000));
+        let (status, _) = request(addr, "PUT", "/collections/c/docs/big", &big);
+        assert_eq!(status, 413);
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "PUT /collections/c/docs/big HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\
+             Connection: close\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+            big.len(),
+            big
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        let (status, _) = request(addr, "GET", "/collections/c/docs/big", "");
+        assert_eq!(status, 404);
+
+        // More concurrent clients than workers are all served
+        let clients: Vec<_> = (0..16)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let path = format!("/collections/c/docs/d{}", i);
+                    request(addr, "PUT", &path, r#"{"n":1}"#).0
+                })
+            })
+            .collect();
+        for client in clients {
+            assert_eq!(client.join().unwrap(), 200);
+        }
+        let (_, body) = request(addr, "GET", "/collections/c/scan", "");
+        assert_eq!(body.lines().count(), 16);
+    }
+}
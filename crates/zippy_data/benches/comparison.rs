@@ -5,14 +5,16 @@
 //!
 //! Run with: cargo bench -- comparison
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
 use rand::Rng;
 use rusqlite::{params, Connection};
 use serde_json::json;
 use tempfile::TempDir;
-use zippy_data::{FastStore, Layout};
+use zippy_data::{FastStore, Layout, MmapHints};
 
 const RECORD_COUNTS: &[usize] = &[1000, 10000, 100000];
 const RANDOM_LOOKUP_COUNT: usize = 1000;
@@ -346,5 +348,79 @@ fn bench_random_access(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_write, bench_read_all, bench_random_access);
+// =============================================================================
+// Cold Scan Benchmarks
+// =============================================================================
+
+/// Drop a file's pages from the page cache, so the next scan reads it from
+/// disk (Linux only; elsewhere the scans below run warm).
+fn evict_page_cache(path: &Path) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        let file = std::fs::File::open(path).unwrap();
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = path;
+}
+
+/// Full scans with the data file evicted from the page cache first, per
+/// paging hint (see `MmapHints`).
+fn bench_cold_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cold_scan");
+    let count = 100000;
+    group.throughput(Throughput::Elements(count as u64));
+
+    let (zds_tmp, zds_root) = setup_zds(count);
+    let data_file = Layout::data_file(&zds_root, "bench");
+    let variants = [
+        (
+            "no_hints",
+            MmapHints {
+                readahead: 0,
+                ..MmapHints::default()
+            },
+        ),
+        ("readahead", MmapHints::default()),
+        (
+            "sequential",
+            MmapHints {
+                sequential: true,
+                ..MmapHints::default()
+            },
+        ),
+        (
+            "populate",
+            MmapHints {
+                populate: true,
+                ..MmapHints::default()
+            },
+        ),
+    ];
+    for (name, hints) in variants {
+        group.bench_function(BenchmarkId::new("zds", name), |b| {
+            b.iter_batched(
+                || evict_page_cache(&data_file),
+                |_| {
+                    let mut store = FastStore::open(&zds_root, "bench", 1000).unwrap();
+                    store.set_mmap_hints(hints).unwrap();
+                    black_box(store.scan().unwrap().len())
+                },
+                BatchSize::PerIteration,
+            );
+        });
+    }
+    drop(zds_tmp);
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_write,
+    bench_read_all,
+    bench_random_access,
+    bench_cold_scan
+);
 criterion_main!(benches);
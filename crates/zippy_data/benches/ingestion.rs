@@ -62,6 +62,7 @@ fn bench_buffered_write(c: &mut Criterion) {
                             max_pending_ops: 10000,
                             max_pending_bytes: 100 * 1024 * 1024,
                             flush_interval_ms: 60000,
+                            ..Default::default()
                         };
                         let mut writer = BufferedWriter::new(&root, "bench", config).unwrap();
                         for i in 0..count {
@@ -105,6 +106,7 @@ fn bench_different_batch_sizes(c: &mut Criterion) {
                             max_pending_ops: batch_size,
                             max_pending_bytes: 100 * 1024 * 1024,
                             flush_interval_ms: 60000,
+                            ..Default::default()
                         };
                         let mut writer = BufferedWriter::new(&root, "bench", config).unwrap();
                         for i in 0..doc_count {
@@ -14,6 +14,7 @@ fn setup_benchmark_data(doc_count: usize) -> (TempDir, std::path::PathBuf) {
         max_pending_ops: 10000,
         max_pending_bytes: 100 * 1024 * 1024,
         flush_interval_ms: 60000,
+        ..Default::default()
     };
 
     let mut writer = BufferedWriter::new(&root, "bench", config).unwrap();
@@ -0,0 +1,320 @@
+//! Scan regression suite on a large synthetic collection.
+//!
+//! Generates a collection of mixed-schema documents (users, events,
+//! products and long-text notes), then times a cold open, a warm full scan,
+//! a filtered scan and random point lookups. Results are printed as JSON;
+//! given a baseline from an earlier run, any timing that got slower than the
+//! threshold allows fails the run.
+//!
+//! Run with `cargo xtask bench-regression` or, configured through the
+//! environment:
+//!
+//! ```bash
+//! ZDS_BENCH_SIZE_MB=4096 ZDS_BENCH_OUT=new.json ZDS_BENCH_BASELINE=old.json \
+//!     cargo bench --bench regression
+//! ```
+//!
+//! | Variable | Default | Meaning |
+//! |----------|---------|---------|
+//! | `ZDS_BENCH_SIZE_MB` | 256 | Size of the generated data |
+//! | `ZDS_BENCH_DIR` | temp dir | Where to generate it; reused while the size matches |
+//! | `ZDS_BENCH_RUNS` | 3 | Runs per measurement (the median is kept) |
+//! | `ZDS_BENCH_OUT` | | File to write the results to |
+//! | `ZDS_BENCH_BASELINE` | | Results to compare against |
+//! | `ZDS_BENCH_THRESHOLD` | 0.2 | Slowdown that counts as a regression |
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde_json::{json, Map, Value};
+use tempfile::TempDir;
+use zippy_data::{FastStore, Layout, OpenMode, Predicate};
+
+pub const COLLECTION: &str = "regression";
+
+/// Documents written per `write_jsonl_blob` call.
+const CHUNK_DOCS: usize = 50_000;
+
+/// Point lookups per random access run.
+const LOOKUPS: usize = 10_000;
+
+const WORDS: &[&str] = &[
+    "data", "store", "index", "segment", "scan", "query", "vector", "batch", "schema", "field",
+    "value", "record", "stream", "cache", "block", "offset", "commit", "journal", "replay",
+    "shard", "merge", "filter", "project", "sample", "model", "train", "eval", "token",
+];
+
+const EVENT_TYPES: &[&str] = &["view", "click", "scroll", "signup", "purchase"];
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// A document of one of four shapes, picked by `i`.
+fn document(i: usize, rng: &mut StdRng) -> String {
+    let id = format!("doc{:010}", i);
+    match i % 4 {
+        0 => format!(
+            r#"{{"_id":"{}","kind":"user","name":"User {}","email":"user{}@example.com","age":{},"active":{},"tags":["{}","{}"]}}"#,
+            id,
+            i,
+            i,
+            rng.gen_range(18..90),
+            rng.gen_bool(0.5),
+            WORDS.choose(rng).unwrap(),
+            WORDS.choose(rng).unwrap()
+        ),
+        1 => format!(
+            r#"{{"_id":"{}","kind":"event","user":"doc{:010}","type":"{}","ts":{},"props":{{"page":"/{}","ms":{}}}}}"#,
+            id,
+            rng.gen_range(0..i.max(1)) / 4 * 4,
+            EVENT_TYPES.choose(rng).unwrap(),
+            1_700_000_000_000u64 + i as u64 * 1000,
+            WORDS.choose(rng).unwrap(),
+            rng.gen_range(1..5000)
+        ),
+        2 => format!(
+            r#"{{"_id":"{}","kind":"product","sku":"SKU-{}","price":{:.2},"stock":{},"dims":[{},{},{}]}}"#,
+            id,
+            i,
+            rng.gen_range(1.0..500.0),
+            rng.gen_range(0..1000),
+            rng.gen_range(1..100),
+            rng.gen_range(1..100),
+            rng.gen_range(1..100)
+        ),
+        _ => {
+            let words = rng.gen_range(30..300);
+            let body: Vec<&str> = (0..words).map(|_| *WORDS.choose(rng).unwrap()).collect();
+            format!(
+                r#"{{"_id":"{}","kind":"note","title":"Note {}","body":"{}"}}"#,
+                id,
+                i,
+                body.join(" ")
+            )
+        }
+    }
+}
+
+/// Generate about `size_mb` of documents under `root`, unless a collection
+/// of that size is already there. Returns the number of documents.
+pub fn generate(root: &Path, size_mb: u64) -> usize {
+    let marker = root.join("regression.json");
+    if let Ok(text) = std::fs::read_to_string(&marker) {
+        let params: Value = serde_json::from_str(&text).unwrap_or_default();
+        if params["size_mb"] == size_mb {
+            if let Some(docs) = params["docs"].as_u64() {
+                eprintln!("Reusing {} documents in {}", docs, root.display());
+                return docs as usize;
+            }
+        }
+    }
+    if Layout::collection_dir(root, COLLECTION).exists() {
+        std::fs::remove_dir_all(Layout::collection_dir(root, COLLECTION)).unwrap();
+    }
+    Layout::init_root(root).unwrap();
+
+    eprintln!("Generating {} MB in {}", size_mb, root.display());
+    let start = Instant::now();
+    let target = size_mb * 1024 * 1024;
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut store = FastStore::open(root, COLLECTION, 100_000).unwrap();
+    let mut docs = 0;
+    let mut written = 0;
+    while written < target {
+        let mut blob = Vec::with_capacity(CHUNK_DOCS * 300);
+        let mut ids = Vec::with_capacity(CHUNK_DOCS);
+        for i in docs..docs + CHUNK_DOCS {
+            blob.extend_from_slice(document(i, &mut rng).as_bytes());
+            blob.push(b'\n');
+            ids.push(format!("doc{:010}", i));
+        }
+        store.write_jsonl_blob(&blob, &ids).unwrap();
+        docs += CHUNK_DOCS;
+        written += blob.len() as u64;
+    }
+    store.flush().unwrap();
+    drop(store);
+    std::fs::write(
+        &marker,
+        json!({"size_mb": size_mb, "docs": docs}).to_string(),
+    )
+    .unwrap();
+    eprintln!("Generated {} documents in {:.1?}", docs, start.elapsed());
+    docs
+}
+
+/// Drop the collection's files from the page cache (Linux only; elsewhere
+/// the cold open runs warm).
+fn evict_page_cache(root: &Path) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        for entry in std::fs::read_dir(Layout::meta_dir(root, COLLECTION)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                let file = std::fs::File::open(path).unwrap();
+                unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = root;
+}
+
+fn open(root: &Path) -> FastStore {
+    FastStore::open_with_mode(root, COLLECTION, 1000, OpenMode::Read).unwrap()
+}
+
+/// Median duration of `runs` calls of `f`, with the result of the last.
+fn median<T>(runs: usize, mut f: impl FnMut() -> (Duration, T)) -> (Duration, T) {
+    let mut times = Vec::with_capacity(runs);
+    let mut last = None;
+    for _ in 0..runs.max(1) {
+        let (time, value) = f();
+        times.push(time);
+        last = Some(value);
+    }
+    times.sort();
+    (times[times.len() / 2], last.unwrap())
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    let value = f();
+    (start.elapsed(), value)
+}
+
+fn ms(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1e6).round() / 1e3
+}
+
+/// Measurements (all timings, lower is better) and what they covered.
+pub fn measure(root: &Path, docs: usize, runs: usize) -> (Map<String, Value>, Map<String, Value>) {
+    let mut metrics = Map::new();
+    let mut counts = Map::new();
+
+    let (cold_open, _) = median(runs, || {
+        evict_page_cache(root);
+        timed(|| open(root))
+    });
+    metrics.insert("cold_open_ms".into(), json!(ms(cold_open)));
+
+    let store = open(root);
+    assert_eq!(store.len(), docs);
+    let scan = || store.scan_with(|_| Some(())).unwrap().len();
+    scan();
+    let (warm_scan, scanned) = median(runs, || timed(scan));
+    metrics.insert("warm_scan_ms".into(), json!(ms(warm_scan)));
+    counts.insert("scanned".into(), json!(scanned));
+
+    let predicate = Predicate::and(vec![
+        Predicate::eq("kind", "event"),
+        Predicate::eq("type", "purchase"),
+    ]);
+    let (filtered_scan, matched) = median(runs, || {
+        timed(|| store.scan_filtered(Some(&predicate), None).unwrap().len())
+    });
+    metrics.insert("filtered_scan_ms".into(), json!(ms(filtered_scan)));
+    counts.insert("matched".into(), json!(matched));
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let ids: Vec<String> = (0..LOOKUPS)
+        .map(|_| format!("doc{:010}", rng.gen_range(0..docs)))
+        .collect();
+    let (random_access, _) = median(runs, || {
+        timed(|| {
+            for id in &ids {
+                store.get(id).unwrap();
+            }
+        })
+    });
+    metrics.insert(
+        "random_get_us".into(),
+        json!((random_access.as_secs_f64() * 1e9 / LOOKUPS as f64).round() / 1e3),
+    );
+    counts.insert("lookups".into(), json!(LOOKUPS));
+
+    (metrics, counts)
+}
+
+/// Metrics that are slower than in `baseline` by more than `threshold`.
+pub fn regressions(metrics: &Map<String, Value>, baseline: &Value, threshold: f64) -> Vec<String> {
+    metrics
+        .iter()
+        .filter_map(|(name, value)| {
+            let now = value.as_f64()?;
+            let before = baseline["metrics"][name].as_f64()?;
+            (before > 0.0 && now > before * (1.0 + threshold)).then(|| {
+                format!(
+                    "{}: {} -> {} (+{:.0}%)",
+                    name,
+                    before,
+                    now,
+                    (now / before - 1.0) * 100.0
+                )
+            })
+        })
+        .collect()
+}
+
+fn main() {
+    let size_mb: u64 = env_or("ZDS_BENCH_SIZE_MB", 256);
+    let runs: usize = env_or("ZDS_BENCH_RUNS", 3);
+    let threshold: f64 = env_or("ZDS_BENCH_THRESHOLD", 0.2);
+
+    let tmp;
+    let root = match std::env::var_os("ZDS_BENCH_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            tmp = TempDir::new().unwrap();
+            tmp.path().to_path_buf()
+        }
+    };
+    std::fs::create_dir_all(&root).unwrap();
+    let docs = generate(&root, size_mb);
+    let data_bytes = open(&root).data_len();
+
+    let (metrics, counts) = measure(&root, docs, runs);
+    let results = json!({
+        "version": zippy_data::ZDS_VERSION,
+        "size_mb": size_mb,
+        "docs": docs,
+        "data_bytes": data_bytes,
+        "runs": runs,
+        "metrics": metrics,
+        "counts": counts,
+    });
+    let text = serde_json::to_string_pretty(&results).unwrap();
+    println!("{}", text);
+    if let Some(out) = std::env::var_os("ZDS_BENCH_OUT") {
+        std::fs::write(out, format!("{}\n", text)).unwrap();
+    }
+
+    if let Some(path) = std::env::var_os("ZDS_BENCH_BASELINE") {
+        let baseline: Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        if baseline["size_mb"] != results["size_mb"] {
+            eprintln!("Warning: the baseline was measured on a collection of another size");
+        }
+        let slower = regressions(&metrics, &baseline, threshold);
+        if !slower.is_empty() {
+            eprintln!(
+                "Regressions beyond {:.0}% of the baseline:",
+                threshold * 100.0
+            );
+            for line in &slower {
+                eprintln!("  {}", line);
+            }
+            std::process::exit(1);
+        }
+        eprintln!("No regressions beyond {:.0}%", threshold * 100.0);
+    }
+}
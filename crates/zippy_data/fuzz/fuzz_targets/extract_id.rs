@@ -0,0 +1,56 @@
+//! `FastStore::extract_id_fast` against serde_json.
+//!
+//! Whenever a line is a JSON object whose first `_id` key is a string or an
+//! integer, the fast extraction must return exactly that string, or the
+//! integer's digits, or the document would be lost or misfiled by an index
+//! rebuild. Other input must not panic.
+
+#![no_main]
+
+use std::fmt;
+
+use libfuzzer_sys::fuzz_target;
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde_json::Value;
+use zippy_data::FastStore;
+
+/// The value of an object's first `_id` key, as serde_json reads it.
+struct FirstId(Option<Value>);
+
+impl<'de> Deserialize<'de> for FirstId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FirstIdVisitor;
+
+        impl<'de> Visitor<'de> for FirstIdVisitor {
+            type Value = FirstId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FirstId, A::Error> {
+                let mut first = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "_id" && first.is_none() {
+                        first = Some(map.next_value::<Value>()?);
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+                Ok(FirstId(first))
+            }
+        }
+
+        deserializer.deserialize_map(FirstIdVisitor)
+    }
+}
+
+fuzz_target!(|line: &[u8]| {
+    let found = FastStore::extract_id_fast(line);
+    let expected = match serde_json::from_slice(line) {
+        Ok(FirstId(Some(Value::String(s)))) => s,
+        Ok(FirstId(Some(Value::Number(n)))) if n.is_i64() || n.is_u64() => n.to_string(),
+        _ => return,
+    };
+    assert_eq!(found, Some(expected));
+});
@@ -0,0 +1,375 @@
+//! Grouped aggregation over collections.
+//!
+//! An aggregation counts documents and folds numeric fields per group,
+//! without handing documents back to the caller. Stores feed documents in
+//! parallel into per-thread [`Aggregator`]s and merge them at the end, so
+//! memory stays proportional to the number of groups.
+//!
+//! ```ignore
+//! let rows = engine.aggregate(
+//!     Some(&GroupBy::new("type")),
+//!     &[Aggregate::Count, Aggregate::Sum("value".into())],
+//! )?;
+//! // [{"type": "purchase", "count": 2, "sum(value)": 30}, ...]
+//! ```
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use rustc_hash::FxHashMap;
+use serde_json::{Map, Number, Value};
+
+use crate::{codec::Codec, Error, Result};
+
+/// Field whose value splits documents into groups (dot notation for nested
+/// fields). Documents without the field form the `null` group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupBy(pub String);
+
+impl GroupBy {
+    pub fn new(field: impl Into<String>) -> Self {
+        GroupBy(field.into())
+    }
+}
+
+/// A value computed per group.
+///
+/// Fields use dot notation. `Sum` and `Avg` only take numbers into account;
+/// `Min` and `Max` compare numbers numerically and strings lexicographically,
+/// and ignore missing and `null` values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Aggregate {
+    /// Number of documents
+    Count,
+    /// Sum of a numeric field (integers stay exact until they overflow)
+    Sum(String),
+    /// Smallest value of a field
+    Min(String),
+    /// Largest value of a field
+    Max(String),
+    /// Mean of a numeric field
+    Avg(String),
+}
+
+impl Aggregate {
+    /// Column name of the aggregate in result rows, e.g. `sum(value)`.
+    pub fn name(&self) -> String {
+        self.to_string()
+    }
+
+    fn field(&self) -> Option<&str> {
+        match self {
+            Aggregate::Count => None,
+            Aggregate::Sum(f) | Aggregate::Min(f) | Aggregate::Max(f) | Aggregate::Avg(f) => {
+                Some(f)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Aggregate::Count => return f.write_str("count"),
+            Aggregate::Sum(_) => "sum",
+            Aggregate::Min(_) => "min",
+            Aggregate::Max(_) => "max",
+            Aggregate::Avg(_) => "avg",
+        };
+        write!(f, "{}({})", op, self.field().unwrap_or_default())
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = Error;
+
+    /// Parse `count`, `sum(field)` or `sum:field` (likewise `min`, `max`
+    /// and `avg`).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("count") || s.eq_ignore_ascii_case("count()") {
+            return Ok(Aggregate::Count);
+        }
+        let (op, field) = match s.split_once(':') {
+            Some((op, field)) => (op, field),
+            None => s
+                .strip_suffix(')')
+                .and_then(|s| s.split_once('('))
+                .ok_or_else(|| Error::Validation(format!("invalid aggregate '{}'", s)))?,
+        };
+        let field = field.trim();
+        if field.is_empty() {
+            return Err(Error::Validation(format!(
+                "aggregate '{}' needs a field",
+                s
+            )));
+        }
+        let field = field.to_string();
+        match op.trim().to_ascii_lowercase().as_str() {
+            "sum" => Ok(Aggregate::Sum(field)),
+            "min" => Ok(Aggregate::Min(field)),
+            "max" => Ok(Aggregate::Max(field)),
+            "avg" | "mean" => Ok(Aggregate::Avg(field)),
+            _ => Err(Error::Validation(format!(
+                "unknown aggregate '{}' (expected count, sum, min, max or avg)",
+                op
+            ))),
+        }
+    }
+}
+
+/// Running state of one aggregate.
+#[derive(Debug, Clone)]
+enum Acc {
+    Count(u64),
+    Sum {
+        /// Exact integer sum, until a float or an overflow
+        int: Option<i64>,
+        float: f64,
+    },
+    Min(Option<Value>),
+    Max(Option<Value>),
+    Avg {
+        sum: f64,
+        count: u64,
+    },
+}
+
+impl Acc {
+    fn new(aggregate: &Aggregate) -> Self {
+        match aggregate {
+            Aggregate::Count => Acc::Count(0),
+            Aggregate::Sum(_) => Acc::Sum {
+                int: Some(0),
+                float: 0.0,
+            },
+            Aggregate::Min(_) => Acc::Min(None),
+            Aggregate::Max(_) => Acc::Max(None),
+            Aggregate::Avg(_) => Acc::Avg { sum: 0.0, count: 0 },
+        }
+    }
+
+    fn add(&mut self, value: Option<&Value>) {
+        match self {
+            Acc::Count(n) => *n += 1,
+            Acc::Sum { int, float } => {
+                if let Some(Value::Number(n)) = value {
+                    *float += n.as_f64().unwrap_or(0.0);
+                    *int = int.zip(n.as_i64()).and_then(|(a, b)| a.checked_add(b));
+                }
+            }
+            Acc::Min(min) => keep(min, value, Ordering::Less),
+            Acc::Max(max) => keep(max, value, Ordering::Greater),
+            Acc::Avg { sum, count } => {
+                if let Some(x) = value.and_then(Value::as_f64) {
+                    *sum += x;
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Acc) {
+        match (self, other) {
+            (Acc::Count(a), Acc::Count(b)) => *a += b,
+            (Acc::Sum { int, float }, Acc::Sum { int: i, float: f }) => {
+                *float += f;
+                *int = int.zip(i).and_then(|(a, b)| a.checked_add(b));
+            }
+            (Acc::Min(min), Acc::Min(other)) => keep(min, other.as_ref(), Ordering::Less),
+            (Acc::Max(max), Acc::Max(other)) => keep(max, other.as_ref(), Ordering::Greater),
+            (Acc::Avg { sum, count }, Acc::Avg { sum: s, count: c }) => {
+                *sum += s;
+                *count += c;
+            }
+            _ => unreachable!("accumulators of one aggregate have the same kind"),
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self {
+            Acc::Count(n) => n.into(),
+            Acc::Sum { int: Some(int), .. } => int.into(),
+            Acc::Sum { float, .. } => float_value(float),
+            Acc::Min(value) | Acc::Max(value) => value.unwrap_or(Value::Null),
+            Acc::Avg { count: 0, .. } => Value::Null,
+            Acc::Avg { sum, count } => float_value(sum / count as f64),
+        }
+    }
+}
+
+/// Replace `current` with `candidate` if it compares as `wanted` against it.
+fn keep(current: &mut Option<Value>, candidate: Option<&Value>, wanted: Ordering) {
+    let Some(candidate) = candidate.filter(|v| !v.is_null()) else {
+        return;
+    };
+    if current
+        .as_ref()
+        .map_or(true, |c| compare_values(candidate, c) == wanted)
+    {
+        *current = Some(candidate.clone());
+    }
+}
+
+fn float_value(x: f64) -> Value {
+    Number::from_f64(x).map_or(Value::Null, Value::Number)
+}
+
+/// Total order over JSON values: `null` < booleans < numbers < strings <
+/// arrays < objects, numbers numerically and strings lexicographically.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(v: &Value) -> u8 {
+        match v {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a
+                .as_f64()
+                .partial_cmp(&b.as_f64())
+                .unwrap_or(Ordering::Equal),
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.to_string().cmp(&b.to_string())),
+    }
+}
+
+/// Accumulates documents into groups; partial aggregators built on separate
+/// threads are combined with [`Aggregator::merge`].
+#[derive(Debug, Clone)]
+pub(crate) struct Aggregator<'a> {
+    group_by: Option<&'a GroupBy>,
+    aggregates: &'a [Aggregate],
+    /// Group key (JSON text of the group value) -> group value, accumulators
+    groups: FxHashMap<String, (Value, Vec<Acc>)>,
+}
+
+impl<'a> Aggregator<'a> {
+    pub(crate) fn new(group_by: Option<&'a GroupBy>, aggregates: &'a [Aggregate]) -> Self {
+        Aggregator {
+            group_by,
+            aggregates,
+            groups: FxHashMap::default(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, doc: &Value) {
+        let group = match self.group_by {
+            Some(GroupBy(field)) => Codec::get_nested(doc, field)
+                .cloned()
+                .unwrap_or(Value::Null),
+            None => Value::Null,
+        };
+        let aggregates = self.aggregates;
+        let (_, accs) = self
+            .groups
+            .entry(group.to_string())
+            .or_insert_with(|| (group, aggregates.iter().map(Acc::new).collect()));
+        for (acc, aggregate) in accs.iter_mut().zip(aggregates) {
+            acc.add(aggregate.field().and_then(|f| Codec::get_nested(doc, f)));
+        }
+    }
+
+    pub(crate) fn merge(mut self, other: Self) -> Self {
+        for (key, (group, accs)) in other.groups {
+            match self.groups.get_mut(&key) {
+                Some((_, mine)) => {
+                    for (acc, other) in mine.iter_mut().zip(accs) {
+                        acc.merge(other);
+                    }
+                }
+                None => {
+                    self.groups.insert(key, (group, accs));
+                }
+            }
+        }
+        self
+    }
+
+    /// One row per group, sorted by group value. Each row holds the group
+    /// field (when grouping) and every aggregate under its [`Aggregate::name`].
+    ///
+    /// Without a group field there is always exactly one row.
+    pub(crate) fn finish(self) -> Vec<Value> {
+        let mut groups: Vec<(Value, Vec<Acc>)> = self.groups.into_values().collect();
+        if groups.is_empty() && self.group_by.is_none() {
+            groups.push((Value::Null, self.aggregates.iter().map(Acc::new).collect()));
+        }
+        groups.sort_by(|(a, _), (b, _)| compare_values(a, b));
+        groups
+            .into_iter()
+            .map(|(group, accs)| {
+                let mut row = Map::new();
+                if let Some(GroupBy(field)) = self.group_by {
+                    row.insert(field.clone(), group);
+                }
+                for (aggregate, acc) in self.aggregates.iter().zip(accs) {
+                    row.insert(aggregate.name(), acc.finish());
+                }
+                Value::Object(row)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_aggregator() {
+        let group_by = GroupBy::new("type");
+        let aggregates: Vec<Aggregate> = [
+            "count",
+            "sum(value)",
+            "min:value",
+            "max(name)",
+            "avg(value)",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        assert_eq!(aggregates[2], Aggregate::Min("value".into()));
+
+        let docs = [
+            json!({"type": "a", "value": 1, "name": "x"}),
+            json!({"type": "b", "value": 2.5}),
+            json!({"type": "a", "value": 4, "name": "z"}),
+            json!({"value": "n/a"}),
+        ];
+        let mut left = Aggregator::new(Some(&group_by), &aggregates);
+        let mut right = Aggregator::new(Some(&group_by), &aggregates);
+        for (i, doc) in docs.iter().enumerate() {
+            if i % 2 == 0 { &mut left } else { &mut right }.add(doc);
+        }
+        let rows = left.merge(right).finish();
+        assert_eq!(
+            rows,
+            [
+                json!({"type": null, "count": 1, "sum(value)": 0, "min(value)": "n/a",
+                       "max(name)": null, "avg(value)": null}),
+                json!({"type": "a", "count": 2, "sum(value)": 5, "min(value)": 1,
+                       "max(name)": "z", "avg(value)": 2.5}),
+                json!({"type": "b", "count": 1, "sum(value)": 2.5, "min(value)": 2.5,
+                       "max(name)": null, "avg(value)": 2.5}),
+            ]
+        );
+
+        let total = Aggregator::new(None, &[Aggregate::Count]).finish();
+        assert_eq!(total, [json!({"count": 0})]);
+
+        assert!("median(value)".parse::<Aggregate>().is_err());
+        assert!("sum".parse::<Aggregate>().is_err());
+    }
+}
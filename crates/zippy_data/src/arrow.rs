@@ -0,0 +1,433 @@
+//! Arrow interchange: convert collection scans into arrow-rs record batches.
+//!
+//! Schemas come from an [`InferredSchema`] of either a collection's
+//! [`SchemaRegistry`] or a sample of documents. Booleans, integers, floats and strings map to typed
+//! columns, integer/float mixes widen to `Float64`, and anything else
+//! (objects, arrays, conflicting types) becomes a JSON-encoded `Utf8` column.
+//! Missing fields become nulls; a value that doesn't fit its column's type
+//! (possible when the schema came from a sample) is an error.
+//!
+//! Field names may use dot notation (`meta.lang`) to lift nested values into
+//! their own column.
+
+use std::sync::Arc;
+
+use ::arrow::{
+    array::{ArrayRef, BooleanArray, Float64Array, Int64Array, NullArray, StringArray},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    error::ArrowError,
+    record_batch::{RecordBatch, RecordBatchOptions, RecordBatchReader},
+};
+use serde_json::Value;
+
+use crate::{
+    infer::{FieldType, InferredSchema, DEFAULT_SAMPLE_SIZE},
+    Engine, Error, Result, SchemaRegistry,
+};
+
+/// Default number of rows per record batch.
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// Resolve a dot-notation path inside a document.
+fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |current, part| current.get(part))
+}
+
+fn data_type(field_type: &FieldType) -> DataType {
+    match field_type {
+        FieldType::Null => DataType::Null,
+        FieldType::Bool => DataType::Boolean,
+        FieldType::Int => DataType::Int64,
+        FieldType::Float => DataType::Float64,
+        FieldType::Str | FieldType::List(_) | FieldType::Struct(_) | FieldType::Mixed => {
+            DataType::Utf8
+        }
+    }
+}
+
+/// Build an Arrow schema from an inferred schema.
+///
+/// Without `fields`, every top-level field is included in the inferred
+/// schema's order. Columns are always nullable, since documents outside a
+/// sample may lack any field.
+pub fn arrow_schema(schema: &InferredSchema, fields: Option<&[&str]>) -> SchemaRef {
+    let columns: Vec<Field> = match fields {
+        Some(fields) => fields
+            .iter()
+            .map(|name| {
+                let field_type = schema
+                    .field(name)
+                    .map_or(&FieldType::Null, |f| &f.field_type);
+                Field::new(name.to_string(), data_type(field_type), true)
+            })
+            .collect(),
+        None => schema
+            .fields
+            .iter()
+            .map(|f| Field::new(f.name.clone(), data_type(&f.field_type), true))
+            .collect(),
+    };
+    Arc::new(Schema::new(columns))
+}
+
+/// Build an Arrow schema from the structural schemas in a registry.
+///
+/// Without `fields`, every top-level field is included in name order.
+pub fn schema_from_registry(registry: &SchemaRegistry, fields: Option<&[&str]>) -> SchemaRef {
+    arrow_schema(&InferredSchema::from_registry(registry), fields)
+}
+
+/// Infer an Arrow schema from documents.
+///
+/// Without `fields`, every top-level field is included in first-seen order.
+pub fn infer_schema(docs: &[Value], fields: Option<&[&str]>) -> SchemaRef {
+    arrow_schema(&InferredSchema::from_docs(docs), fields)
+}
+
+/// Infer the Arrow schema of a collection from its registry, or from a
+/// sample of [`DEFAULT_SAMPLE_SIZE`] documents when it has none (fast store
+/// collections).
+pub fn engine_schema(engine: &Engine, fields: Option<&[&str]>) -> Result<SchemaRef> {
+    let schema = InferredSchema::for_engine(engine, DEFAULT_SAMPLE_SIZE)?;
+    Ok(arrow_schema(&schema, fields))
+}
+
+/// Like [`engine_schema`], but reading every document of a collection
+/// without a registry, so no field or type outside a sample is missed.
+pub fn full_engine_schema(engine: &Engine, fields: Option<&[&str]>) -> Result<SchemaRef> {
+    let schema = InferredSchema::for_engine(engine, engine.len().max(1))?;
+    Ok(arrow_schema(&schema, fields))
+}
+
+fn build_column(name: &str, values: &[Option<&Value>], data_type: &DataType) -> Result<ArrayRef> {
+    // Nulls and missing values are null; other values must convert
+    fn convert<'a, T>(
+        name: &str,
+        values: &[Option<&'a Value>],
+        data_type: &DataType,
+        f: impl Fn(&'a Value) -> Option<T>,
+    ) -> Result<Vec<Option<T>>> {
+        values
+            .iter()
+            .map(|v| match v {
+                None | Some(Value::Null) => Ok(None),
+                Some(v) => f(v).map(Some).ok_or_else(|| {
+                    Error::Validation(format!(
+                        "field '{}' has value {} in a {} column",
+                        name, v, data_type
+                    ))
+                }),
+            })
+            .collect()
+    }
+
+    Ok(match data_type {
+        DataType::Boolean => Arc::new(BooleanArray::from(convert(
+            name,
+            values,
+            data_type,
+            Value::as_bool,
+        )?)),
+        DataType::Int64 => Arc::new(Int64Array::from(convert(
+            name,
+            values,
+            data_type,
+            Value::as_i64,
+        )?)),
+        DataType::Float64 => Arc::new(Float64Array::from(convert(
+            name,
+            values,
+            data_type,
+            Value::as_f64,
+        )?)),
+        DataType::Utf8 => Arc::new(StringArray::from(convert(name, values, data_type, |v| {
+            Some(match v {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+        })?)),
+        _ => {
+            convert(name, values, data_type, |_| None::<()>)?;
+            Arc::new(NullArray::new(values.len()))
+        }
+    })
+}
+
+/// Convert documents into a record batch with the given schema.
+pub fn to_record_batch(docs: &[Value], schema: SchemaRef) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let values: Vec<Option<&Value>> =
+                docs.iter().map(|doc| get_path(doc, field.name())).collect();
+            build_column(field.name(), &values, field.data_type())
+        })
+        .collect::<Result<_>>()?;
+
+    let options = RecordBatchOptions::new().with_row_count(Some(docs.len()));
+    Ok(RecordBatch::try_new_with_options(
+        schema, columns, &options,
+    )?)
+}
+
+/// Convert a record batch back into JSON documents, one per row.
+///
+/// Null values are omitted from the resulting objects.
+pub fn record_batch_to_docs(batch: &RecordBatch) -> Result<Vec<Value>> {
+    let mut writer = ::arrow::json::LineDelimitedWriter::new(Vec::new());
+    writer.write(batch)?;
+    writer.finish()?;
+    let buf = writer.into_inner();
+
+    buf.split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+/// Chunked record batch reader over a document iterator.
+///
+/// Implements [`RecordBatchReader`], yielding batches of at most
+/// `batch_size` rows.
+pub struct RecordBatchIter<I> {
+    docs: I,
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl<I> RecordBatchIter<I>
+where
+    I: Iterator<Item = Result<Value>>,
+{
+    /// Create a reader over `docs` using a fixed schema.
+    pub fn new(docs: I, schema: SchemaRef, batch_size: usize) -> Self {
+        RecordBatchIter {
+            docs,
+            schema,
+            batch_size: batch_size.max(1),
+        }
+    }
+}
+
+impl<I> Iterator for RecordBatchIter<I>
+where
+    I: Iterator<Item = Result<Value>>,
+{
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::with_capacity(self.batch_size);
+        for doc in self.docs.by_ref() {
+            match doc {
+                Ok(doc) => chunk.push(doc),
+                Err(e) => return Some(Err(ArrowError::ExternalError(Box::new(e)))),
+            }
+            if chunk.len() == self.batch_size {
+                break;
+            }
+        }
+
+        if chunk.is_empty() {
+            return None;
+        }
+        Some(
+            to_record_batch(&chunk, self.schema.clone())
+                .map_err(|e| ArrowError::ExternalError(Box::new(e))),
+        )
+    }
+}
+
+impl<I> RecordBatchReader for RecordBatchIter<I>
+where
+    I: Iterator<Item = Result<Value>>,
+{
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+/// Read a collection as record batches, using [`full_engine_schema`].
+pub fn read_engine(
+    engine: &Engine,
+    fields: Option<&[&str]>,
+    batch_size: usize,
+) -> Result<RecordBatchIter<crate::Scanner>> {
+    let schema = full_engine_schema(engine, fields)?;
+    let scanner = engine.scan(None, None)?;
+    Ok(RecordBatchIter::new(scanner, schema, batch_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::arrow::array::Array;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{writer::SyncWriter, Layout};
+
+    #[test]
+    fn test_infer_schema_and_batch() {
+        let docs = vec![
+            json!({"text": "a", "n": 1, "score": 0.5, "tags": ["x"], "meta": {"lang": "en"}}),
+            json!({"text": "b", "n": 2, "score": 1, "flag": true}),
+        ];
+
+        let schema = infer_schema(&docs, None);
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["meta", "n", "score", "tags", "text", "flag"]);
+        assert_eq!(schema.field(1).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(2).data_type(), &DataType::Float64);
+        assert_eq!(schema.field(3).data_type(), &DataType::Utf8);
+
+        let batch = to_record_batch(&docs, schema).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let flags = batch
+            .column(5)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(flags.is_null(0));
+        assert!(flags.value(1));
+
+        let schema = infer_schema(&docs, Some(&["meta.lang"]));
+        let batch = to_record_batch(&docs, schema).unwrap();
+        let langs = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(langs.value(0), "en");
+        assert!(langs.is_null(1));
+    }
+
+    #[test]
+    fn test_record_batch_type_mismatch() {
+        let schema = infer_schema(&[json!({"n": 1, "flag": true, "text": "a"})], None);
+        let docs = [
+            json!({"n": 2, "flag": null, "text": 3}),
+            json!({"text": {"a": 1}}),
+        ];
+        let batch = to_record_batch(&docs, schema.clone()).unwrap();
+        let texts = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(texts.value(0), "3");
+        assert_eq!(texts.value(1), r#"{"a":1}"#);
+
+        // Values that don't fit are errors, not nulls
+        for doc in [json!({"n": 1.5}), json!({"flag": "yes"}), json!({"n": "2"})] {
+            assert!(matches!(
+                to_record_batch(&[doc], schema.clone()),
+                Err(Error::Validation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_record_batch_roundtrip() {
+        let docs = vec![
+            json!({"text": "a", "n": 1, "meta": {"lang": "en"}}),
+            json!({"text": "b", "n": 2}),
+        ];
+
+        let batch = to_record_batch(&docs, infer_schema(&docs, Some(&["text", "n"]))).unwrap();
+        let back = record_batch_to_docs(&batch).unwrap();
+        assert_eq!(
+            back,
+            vec![json!({"text": "a", "n": 1}), json!({"text": "b", "n": 2})]
+        );
+    }
+
+    #[test]
+    fn test_read_engine_chunked() {
+        let tmp = TempDir::new().unwrap();
+        Layout::init_root(tmp.path()).unwrap();
+
+        let mut writer = SyncWriter::new(tmp.path(), "train").unwrap();
+        for i in 0..5 {
+            writer
+                .put(&format!("doc{}", i), &json!({"i": i, "label": "x"}))
+                .unwrap();
+        }
+        drop(writer);
+
+        let engine = Engine::open(tmp.path(), "train").unwrap();
+        let reader = read_engine(&engine, None, 2).unwrap();
+        let schema = reader.schema();
+        assert_eq!(schema.field(0).name(), "i");
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+
+        let batches: Vec<_> = reader.collect::<std::result::Result<_, _>>().unwrap();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+    }
+
+    #[test]
+    fn test_read_engine_fast_store() {
+        let tmp = TempDir::new().unwrap();
+        Layout::init_root(tmp.path()).unwrap();
+
+        // More documents than a sample holds, with one rare field
+        let mut store = crate::FastStore::open(tmp.path(), "fast", 100).unwrap();
+        let count = DEFAULT_SAMPLE_SIZE * 3;
+        for i in 0..count {
+            let doc = if i == count / 2 {
+                json!({"i": i, "rare": "x"})
+            } else {
+                json!({"i": i})
+            };
+            store.put(format!("doc{:05}", i), doc).unwrap();
+        }
+        drop(store);
+
+        let engine = Engine::open(tmp.path(), "fast").unwrap();
+        let reader = read_engine(&engine, None, 1000).unwrap();
+        let schema = reader.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"rare"));
+        let batches: Vec<_> = reader.collect::<std::result::Result<_, _>>().unwrap();
+        let rare = schema.index_of("rare").unwrap();
+        let values: usize = batches
+            .iter()
+            .map(|b| b.num_rows() - b.column(rare).null_count())
+            .sum();
+        assert_eq!(values, 1);
+    }
+
+    #[test]
+    fn test_engine_schema_samples_fast_store() {
+        let tmp = TempDir::new().unwrap();
+        Layout::init_root(tmp.path()).unwrap();
+
+        let mut store = crate::FastStore::open(tmp.path(), "fast", 100).unwrap();
+        store.put("a", json!({"text": "x", "n": null})).unwrap();
+        store
+            .put("b", json!({"text": "y", "n": 1.5, "extra": true}))
+            .unwrap();
+        drop(store);
+
+        // The registry is empty, so the schema comes from the documents
+        let engine = Engine::open(tmp.path(), "fast").unwrap();
+        let schema = engine_schema(&engine, None).unwrap();
+        let mut columns: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type().clone()))
+            .collect();
+        columns.sort_by_key(|(name, _)| *name);
+        assert_eq!(
+            columns,
+            [
+                ("extra", DataType::Boolean),
+                ("n", DataType::Float64),
+                ("text", DataType::Utf8)
+            ]
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Background writer for a [`SharedStore`].
+//!
+//! An [`AsyncWriter`] queues puts and deletes on a bounded channel and
+//! applies them on a thread of its own, which takes the store's write lock
+//! once per batch of queued writes and flushes (saving the index) on
+//! request and every flush interval. Callers only wait for the queue: when
+//! it is full, `put` and `delete` block until the thread catches up, which
+//! keeps a fast producer from buffering without bound.
+//!
+//! Writes fail on the thread, not in the caller. The first failure stops
+//! the thread, dropping the writes still queued, and is returned by the
+//! next call; later calls fail as if the writer were closed.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde_json::Value;
+
+use crate::{Error, Result, SharedStore};
+
+/// Configuration of an [`AsyncWriter`].
+#[derive(Debug, Clone)]
+pub struct AsyncWriterConfig {
+    /// Writes queued before `put` and `delete` block
+    pub queue_size: usize,
+    /// Most writes applied under one hold of the store's write lock
+    pub max_batch: usize,
+    /// Flush interval in milliseconds, while there are unflushed writes
+    pub flush_interval_ms: u64,
+}
+
+impl Default for AsyncWriterConfig {
+    fn default() -> Self {
+        AsyncWriterConfig {
+            queue_size: 10_000,
+            max_batch: 1000,
+            flush_interval_ms: 1000,
+        }
+    }
+}
+
+/// Work for the writer thread.
+enum Command {
+    Put {
+        doc_id: String,
+        doc: Value,
+    },
+    Delete {
+        doc_id: String,
+    },
+    /// Flush, then drop the sender to tell the caller it's done
+    Flush(mpsc::Sender<()>),
+}
+
+/// Applies writes to a [`SharedStore`] on a background thread.
+///
+/// All methods take `&self`, so one writer can be shared by several
+/// producer threads. Dropping it closes it, discarding any error.
+pub struct AsyncWriter {
+    /// `None` once closed
+    sender: Mutex<Option<SyncSender<Command>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    /// Writes queued and not yet applied
+    pending: Arc<AtomicUsize>,
+    /// First failure of the thread, until it is reported
+    error: Arc<Mutex<Option<Error>>>,
+}
+
+impl AsyncWriter {
+    /// Start a writer thread for `store`.
+    pub fn new(store: SharedStore, config: AsyncWriterConfig) -> Result<Self> {
+        if config.queue_size == 0 || config.max_batch == 0 {
+            return Err(Error::Validation(
+                "queue_size and max_batch must be at least 1".to_string(),
+            ));
+        }
+        let (sender, receiver) = mpsc::sync_channel(config.queue_size);
+        let pending = Arc::new(AtomicUsize::new(0));
+        let error = Arc::new(Mutex::new(None));
+        let worker = Worker {
+            store,
+            receiver,
+            max_batch: config.max_batch,
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            pending: Arc::clone(&pending),
+        };
+        let failed = Arc::clone(&error);
+        let thread = thread::Builder::new()
+            .name("zippy-async-writer".to_string())
+            .spawn(move || {
+                if let Err(e) = worker.run() {
+                    *failed.lock() = Some(e);
+                }
+                // Only now that the error is recorded do callers learn,
+                // from the closed channel, that the thread stopped
+                let pending = Arc::clone(&worker.pending);
+                drop(worker);
+                pending.store(0, Ordering::Relaxed);
+            })?;
+        Ok(AsyncWriter {
+            sender: Mutex::new(Some(sender)),
+            thread: Mutex::new(Some(thread)),
+            pending,
+            error,
+        })
+    }
+
+    /// Queue a document put, waiting while the queue is full.
+    pub fn put(&self, doc_id: impl Into<String>, doc: Value) -> Result<()> {
+        self.send(Command::Put {
+            doc_id: doc_id.into(),
+            doc,
+        })
+    }
+
+    /// Queue a document delete, waiting while the queue is full. Deleting
+    /// a missing document stops the writer like any failed write.
+    pub fn delete(&self, doc_id: impl Into<String>) -> Result<()> {
+        self.send(Command::Delete {
+            doc_id: doc_id.into(),
+        })
+    }
+
+    /// Wait until every write queued so far is applied and flushed.
+    pub fn flush(&self) -> Result<()> {
+        let (done, wait) = mpsc::channel();
+        self.send(Command::Flush(done))?;
+        // The thread drops the sender once flushed, or when it fails
+        let _ = wait.recv();
+        match self.error.lock().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of queued writes not yet applied.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Whether the writer was closed.
+    pub fn is_closed(&self) -> bool {
+        self.sender.lock().is_none()
+    }
+
+    /// Stop taking writes, wait for the thread to apply and flush the
+    /// queued ones, and join it. Closing twice is a no-op.
+    pub fn close(&self) -> Result<()> {
+        self.sender.lock().take();
+        if let Some(thread) = self.thread.lock().take() {
+            thread
+                .join()
+                .map_err(|_| Error::TransactionFailed("async writer panicked".to_string()))?;
+        }
+        match self.error.lock().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn send(&self, command: Command) -> Result<()> {
+        // Clone the sender so a full queue doesn't block other callers
+        let sender = self.sender.lock().clone().ok_or_else(closed)?;
+        let counted = !matches!(command, Command::Flush(_));
+        if counted {
+            self.pending.fetch_add(1, Ordering::Relaxed);
+        }
+        sender.send(command).map_err(|_| {
+            if counted {
+                self.pending.fetch_sub(1, Ordering::Relaxed);
+            }
+            self.error.lock().take().unwrap_or_else(closed)
+        })
+    }
+}
+
+impl Drop for AsyncWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn closed() -> Error {
+    Error::Validation("async writer is closed".to_string())
+}
+
+/// State of the writer thread.
+struct Worker {
+    store: SharedStore,
+    receiver: Receiver<Command>,
+    max_batch: usize,
+    flush_interval: Duration,
+    pending: Arc<AtomicUsize>,
+}
+
+impl Worker {
+    /// Apply batches of queued writes until every sender is gone, then
+    /// flush.
+    fn run(&self) -> Result<()> {
+        let mut dirty = false;
+        let mut last_flush = Instant::now();
+        loop {
+            let wait = if dirty {
+                self.flush_interval.saturating_sub(last_flush.elapsed())
+            } else {
+                Duration::from_secs(3600)
+            };
+            let first = match self.receiver.recv_timeout(wait) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            let mut store = self.store.write()?;
+            let batch = first.into_iter().chain(
+                std::iter::from_fn(|| self.receiver.try_recv().ok()).take(self.max_batch - 1),
+            );
+            for command in batch {
+                match command {
+                    Command::Put { doc_id, doc } => {
+                        store.put(doc_id, doc)?;
+                        self.pending.fetch_sub(1, Ordering::Relaxed);
+                        dirty = true;
+                    }
+                    Command::Delete { doc_id } => {
+                        store.delete(&doc_id)?;
+                        self.pending.fetch_sub(1, Ordering::Relaxed);
+                        dirty = true;
+                    }
+                    Command::Flush(done) => {
+                        store.flush()?;
+                        dirty = false;
+                        last_flush = Instant::now();
+                        drop(done);
+                    }
+                }
+            }
+            if dirty && last_flush.elapsed() >= self.flush_interval {
+                store.flush()?;
+                dirty = false;
+                last_flush = Instant::now();
+            }
+        }
+        if dirty {
+            self.store.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_async_writer() {
+        let tmp = TempDir::new().unwrap();
+        let store = SharedStore::open(tmp.path(), "test", 100_000).unwrap();
+        let config = AsyncWriterConfig {
+            queue_size: 8,
+            max_batch: 4,
+            ..Default::default()
+        };
+        let writer = Arc::new(AsyncWriter::new(store.clone(), config).unwrap());
+
+        // Producers on several threads, held back by the small queue
+        let producers: Vec<_> = (0..4)
+            .map(|t| {
+                let writer = Arc::clone(&writer);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        writer
+                            .put(format!("doc{}-{}", t, i), json!({"t": t, "i": i}))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        writer.delete("doc0-0").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.pending(), 0);
+        assert_eq!(store.len(), 399);
+        assert_eq!(store.get("doc3-99").unwrap(), json!({"t": 3, "i": 99}));
+        let reader =
+            crate::FastStore::open_with_mode(tmp.path(), "test", 100, crate::OpenMode::Read)
+                .unwrap();
+        assert_eq!(reader.len(), 399);
+
+        // A failed write stops the writer and is reported once
+        writer.delete("missing").unwrap();
+        assert!(matches!(
+            writer.flush(),
+            Err(Error::DocumentNotFound(id)) if id == "missing"
+        ));
+        assert!(matches!(
+            writer.put("doc", json!({})),
+            Err(Error::Validation(_))
+        ));
+        writer.close().unwrap();
+        assert!(writer.is_closed());
+
+        // Closing applies and flushes what is still queued
+        let writer = AsyncWriter::new(store.clone(), AsyncWriterConfig::default()).unwrap();
+        writer.put("last", json!({"n": 1})).unwrap();
+        writer.close().unwrap();
+        writer.close().unwrap();
+        assert!(writer.put("late", json!({})).is_err());
+        assert!(store.exists("last"));
+    }
+}
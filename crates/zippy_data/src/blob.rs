@@ -0,0 +1,443 @@
+//! Binary attachments of fast store documents.
+//!
+//! Blobs (images, audio, ...) are named payloads attached to a document,
+//! see [`FastStore::put_blob`](crate::FastStore::put_blob). They are
+//! appended as they are to `blobs/blobs.bin`, next to the collection's JSON
+//! documents, and located through `blobs/index.jsonl`, an append-only log
+//! of the blobs added (with their offset, length and CRC32) and removed.
+//!
+//! Like document lines, payloads are written first and their records on
+//! flush, so a crash loses unflushed blobs and leaves only unreferenced
+//! bytes behind. Compaction copies the live blobs to the next generation of
+//! the data file (`blobs.00001.bin`, ...) and replaces the log with one
+//! record per blob. In an encrypted collection every payload is sealed on
+//! its own; the log, which only holds IDs and names, stays in the clear.
+
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{encryption::Cipher, Error, Layout, OpenMode, Result};
+
+/// Location of a stored blob.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct BlobEntry {
+    /// Generation of the data file holding the payload
+    #[serde(rename = "file", default, skip_serializing_if = "is_zero")]
+    generation: u32,
+    offset: u64,
+    length: u64,
+    /// CRC32 of the stored (sealed, if encrypted) payload
+    checksum: u32,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// Associated data of a sealed blob: its document and name, so sealed
+/// payloads can't be swapped between blobs.
+fn blob_aad(doc_id: &str, name: &str) -> Vec<u8> {
+    let mut aad = format!("zds blob:{}", doc_id).into_bytes();
+    aad.push(0);
+    aad.extend_from_slice(name.as_bytes());
+    aad
+}
+
+/// Line of `blobs/index.jsonl`; a record without a location removes the
+/// blob.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BlobRecord {
+    #[serde(rename = "_id")]
+    id: String,
+    name: String,
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    blob: Option<BlobEntry>,
+}
+
+/// Blobs of one collection.
+pub(crate) struct BlobStore {
+    root: PathBuf,
+    collection: String,
+    /// Doc ID -> blob name -> location
+    blobs: FxHashMap<String, BTreeMap<String, BlobEntry>>,
+    /// Data file generation new payloads are appended to
+    generation: u32,
+    /// Length of the current data file
+    data_len: u64,
+    /// Total length of the live payloads
+    live_bytes: u64,
+    /// Records not yet appended to the log
+    pending: Vec<BlobRecord>,
+    cipher: Option<Arc<Cipher>>,
+}
+
+impl BlobStore {
+    /// Whether a collection has ever stored blobs.
+    pub(crate) fn exists(root: &std::path::Path, collection: &str) -> bool {
+        Layout::blob_index_file(root, collection).exists()
+    }
+
+    /// Load a collection's blob log. In read-write mode, data files of
+    /// other generations, left by an interrupted compaction, are removed.
+    pub(crate) fn open(
+        root: &std::path::Path,
+        collection: &str,
+        mode: OpenMode,
+        cipher: Option<Arc<Cipher>>,
+    ) -> Result<Self> {
+        let index_file = Layout::blob_index_file(root, collection);
+        let content = if index_file.exists() {
+            std::fs::read(&index_file)?
+        } else {
+            Vec::new()
+        };
+        let mut store = BlobStore {
+            root: root.to_path_buf(),
+            collection: collection.to_string(),
+            blobs: FxHashMap::default(),
+            generation: 0,
+            data_len: 0,
+            live_bytes: 0,
+            pending: Vec::new(),
+            cipher,
+        };
+        // An unterminated final line is a torn write: ignored, and cut off
+        // before records are appended again
+        let complete = memchr::memrchr(b'\n', &content).map_or(0, |pos| pos + 1);
+        if complete < content.len() && mode == OpenMode::ReadWrite {
+            OpenOptions::new()
+                .write(true)
+                .open(&index_file)?
+                .set_len(complete as u64)?;
+        }
+        for line in content[..complete].split_inclusive(|&b| b == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let record: BlobRecord = serde_json::from_slice(line)?;
+            if let Some(entry) = record.blob {
+                store.generation = store.generation.max(entry.generation);
+            }
+            store.apply(record);
+        }
+
+        let data_file = Layout::blob_data_file(root, collection, store.generation);
+        store.data_len = match std::fs::metadata(&data_file) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if mode == OpenMode::ReadWrite {
+            store.remove_stale_files()?;
+        }
+        Ok(store)
+    }
+
+    /// Apply a log record to the in-memory map.
+    fn apply(&mut self, record: BlobRecord) {
+        let names = self.blobs.entry(record.id).or_default();
+        let previous = match record.blob {
+            Some(entry) => {
+                self.live_bytes += entry.length;
+                names.insert(record.name, entry)
+            }
+            None => names.remove(&record.name),
+        };
+        if let Some(previous) = previous {
+            self.live_bytes -= previous.length;
+        }
+    }
+
+    /// Remove data files of generations other than the current one.
+    fn remove_stale_files(&self) -> Result<()> {
+        let dir = Layout::blobs_dir(&self.root, &self.collection);
+        if !dir.exists() {
+            return Ok(());
+        }
+        let current = Layout::blob_data_file(&self.root, &self.collection, self.generation);
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let is_data = name == Layout::BLOB_DATA_FILE
+                || (name.starts_with("blobs.") && name.ends_with(".bin"));
+            if is_data && path != current {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append a payload, replacing any blob of the same name.
+    pub(crate) fn put(&mut self, doc_id: &str, name: &str, bytes: &[u8]) -> Result<()> {
+        let sealed;
+        let stored = match &self.cipher {
+            Some(cipher) => {
+                sealed = cipher.seal(bytes, &blob_aad(doc_id, name))?;
+                sealed.as_slice()
+            }
+            None => bytes,
+        };
+        std::fs::create_dir_all(Layout::blobs_dir(&self.root, &self.collection))?;
+        let mut file =
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(Layout::blob_data_file(
+                    &self.root,
+                    &self.collection,
+                    self.generation,
+                ))?;
+        file.write_all(stored)?;
+
+        let entry = BlobEntry {
+            generation: self.generation,
+            offset: self.data_len,
+            length: stored.len() as u64,
+            checksum: crc32fast::hash(stored),
+        };
+        self.data_len += entry.length;
+        self.record(BlobRecord {
+            id: doc_id.to_string(),
+            name: name.to_string(),
+            blob: Some(entry),
+        });
+        Ok(())
+    }
+
+    /// Apply a record and queue it for the log.
+    fn record(&mut self, record: BlobRecord) {
+        self.apply(record.clone());
+        self.pending.push(record);
+    }
+
+    /// Read a blob's payload.
+    pub(crate) fn get(&self, doc_id: &str, name: &str) -> Result<Vec<u8>> {
+        let entry = self
+            .blobs
+            .get(doc_id)
+            .and_then(|names| names.get(name))
+            .ok_or_else(|| Error::DocumentNotFound(format!("{} (blob '{}')", doc_id, name)))?;
+        let mut file = File::open(Layout::blob_data_file(
+            &self.root,
+            &self.collection,
+            entry.generation,
+        ))?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut stored = vec![0u8; entry.length as usize];
+        file.read_exact(&mut stored)?;
+        if crc32fast::hash(&stored) != entry.checksum {
+            return Err(Error::InvalidContainer(format!(
+                "blob '{}' of document '{}' fails its checksum",
+                name, doc_id
+            )));
+        }
+        match &self.cipher {
+            Some(cipher) => cipher.open(&stored, &blob_aad(doc_id, name)),
+            None => Ok(stored),
+        }
+    }
+
+    /// Names of a document's blobs, sorted.
+    pub(crate) fn names(&self, doc_id: &str) -> Vec<String> {
+        self.blobs
+            .get(doc_id)
+            .map(|names| names.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove one blob. Returns whether it existed.
+    pub(crate) fn remove(&mut self, doc_id: &str, name: &str) -> bool {
+        let exists = self
+            .blobs
+            .get(doc_id)
+            .is_some_and(|names| names.contains_key(name));
+        if exists {
+            self.record(BlobRecord {
+                id: doc_id.to_string(),
+                name: name.to_string(),
+                blob: None,
+            });
+        }
+        exists
+    }
+
+    /// Remove every blob of a document.
+    pub(crate) fn remove_doc(&mut self, doc_id: &str) {
+        for name in self.names(doc_id) {
+            self.remove(doc_id, &name);
+        }
+        self.blobs.remove(doc_id);
+    }
+
+    /// Length of payloads no longer referenced.
+    pub(crate) fn dead_bytes(&self) -> u64 {
+        self.data_len.saturating_sub(self.live_bytes)
+    }
+
+    /// Sync written payloads, then append and sync pending records.
+    pub(crate) fn sync(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let data_file = Layout::blob_data_file(&self.root, &self.collection, self.generation);
+        if data_file.exists() {
+            File::open(&data_file)?.sync_data()?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Layout::blob_index_file(&self.root, &self.collection))?;
+        let mut writer = BufWriter::new(file);
+        for record in self.pending.drain(..) {
+            writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    /// Copy the live blobs to a new data file generation, sealed with
+    /// `cipher`, and replace the log with one record per blob.
+    pub(crate) fn rewrite(&mut self, cipher: Option<Arc<Cipher>>) -> Result<()> {
+        self.sync()?;
+        let generation = self.generation + 1;
+        let data_file = Layout::blob_data_file(&self.root, &self.collection, generation);
+        let mut writer = BufWriter::new(File::create(&data_file)?);
+        let mut records = Vec::new();
+        let mut offset = 0;
+        for (doc_id, names) in &self.blobs {
+            for name in names.keys() {
+                let bytes = self.get(doc_id, name)?;
+                let stored = match &cipher {
+                    Some(cipher) => cipher.seal(&bytes, &blob_aad(doc_id, name))?,
+                    None => bytes,
+                };
+                writer.write_all(&stored)?;
+                let entry = BlobEntry {
+                    generation,
+                    offset,
+                    length: stored.len() as u64,
+                    checksum: crc32fast::hash(&stored),
+                };
+                offset += entry.length;
+                records.push(BlobRecord {
+                    id: doc_id.clone(),
+                    name: name.clone(),
+                    blob: Some(entry),
+                });
+            }
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        // The new log takes over atomically; old data files are then unused
+        let index_file = Layout::blob_index_file(&self.root, &self.collection);
+        let tmp = index_file.with_extension("jsonl.tmp");
+        let mut log = BufWriter::new(File::create(&tmp)?);
+        for record in &records {
+            writeln!(log, "{}", serde_json::to_string(record)?)?;
+        }
+        log.flush()?;
+        log.get_ref().sync_all()?;
+        std::fs::rename(&tmp, &index_file)?;
+
+        self.blobs.clear();
+        self.live_bytes = 0;
+        for record in records {
+            self.apply(record);
+        }
+        self.generation = generation;
+        self.data_len = offset;
+        self.cipher = cipher;
+        self.remove_stale_files()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::FastStore;
+
+    #[test]
+    fn test_blobs_survive_reopen_and_compaction() {
+        let tmp = TempDir::new().unwrap();
+        let image = vec![7u8; 4096];
+        let audio: Vec<u8> = (0..=255).collect();
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"label": "cat"})).unwrap();
+        store.put("b", json!({"label": "dog"})).unwrap();
+        assert!(matches!(
+            store.put_blob("missing", "image", &image),
+            Err(Error::DocumentNotFound(_))
+        ));
+        store.put_blob("a", "image", &image).unwrap();
+        store.put_blob("a", "audio", &audio).unwrap();
+        store.put_blob("b", "image", b"old").unwrap();
+        store.put_blob("b", "image", b"new").unwrap();
+        assert_eq!(store.get_blob("a", "audio").unwrap(), audio);
+        store.flush().unwrap();
+        drop(store);
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.blob_names("a"), vec!["audio", "image"]);
+        assert_eq!(store.get_blob("a", "image").unwrap(), image);
+        assert_eq!(store.get_blob("b", "image").unwrap(), b"new");
+        assert!(store.get_blob("b", "audio").is_err());
+
+        // Deleting a document drops its blobs
+        store.delete("a").unwrap();
+        assert!(store.blob_names("a").is_empty());
+        store.delete_blob("b", "image").unwrap();
+        assert!(store.delete_blob("b", "image").is_err());
+        store.put_blob("b", "thumb", b"tiny").unwrap();
+        store.compact().unwrap();
+        let data = Layout::blob_data_file(tmp.path(), "test", 1);
+        assert_eq!(std::fs::metadata(&data).unwrap().len(), 4);
+        assert!(!Layout::blob_data_file(tmp.path(), "test", 0).exists());
+        drop(store);
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.get_blob("b", "thumb").unwrap(), b"tiny");
+        assert!(store.blob_names("a").is_empty());
+    }
+
+    #[test]
+    fn test_torn_blob_log() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({})).unwrap();
+        store.put_blob("a", "kept", b"kept").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        // Payload written, record torn by a crash mid-write
+        let data = Layout::blob_data_file(tmp.path(), "test", 0);
+        let mut file = OpenOptions::new().append(true).open(&data).unwrap();
+        file.write_all(b"lost").unwrap();
+        let index = Layout::blob_index_file(tmp.path(), "test");
+        let mut file = OpenOptions::new().append(true).open(&index).unwrap();
+        file.write_all(br#"{"_id":"a","name":"lost","off"#).unwrap();
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.blob_names("a"), vec!["kept"]);
+        store.put_blob("a", "next", b"next").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.blob_names("a"), vec!["kept", "next"]);
+        assert_eq!(store.get_blob("a", "next").unwrap(), b"next");
+    }
+}
@@ -1,5 +1,7 @@
 //! JSON codec with projection and predicate support.
 
+use std::cmp::Ordering;
+
 use serde_json::{Map, Value};
 
 use crate::{Error, Result};
@@ -13,6 +15,22 @@ pub enum Predicate {
     Exists(String),
     /// Field does not exist
     NotExists(String),
+    /// Field is greater than value
+    Gt(String, Value),
+    /// Field is greater than or equal to value
+    Gte(String, Value),
+    /// Field is less than value
+    Lt(String, Value),
+    /// Field is less than or equal to value
+    Lte(String, Value),
+    /// Field equals one of the values
+    In(String, Vec<Value>),
+    /// String field starts with prefix
+    StartsWith(String, String),
+    /// String field contains substring, or array field contains element
+    Contains(String, Value),
+    /// Text field contains every token of the query (case-insensitive)
+    TextMatch(String, String),
     /// Logical AND of predicates
     And(Vec<Predicate>),
     /// Logical OR of predicates
@@ -30,6 +48,46 @@ impl Predicate {
         Predicate::Exists(field.into())
     }
 
+    /// Create a greater-than predicate.
+    pub fn gt(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Predicate::Gt(field.into(), value.into())
+    }
+
+    /// Create a greater-than-or-equal predicate.
+    pub fn gte(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Predicate::Gte(field.into(), value.into())
+    }
+
+    /// Create a less-than predicate.
+    pub fn lt(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Predicate::Lt(field.into(), value.into())
+    }
+
+    /// Create a less-than-or-equal predicate.
+    pub fn lte(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Predicate::Lte(field.into(), value.into())
+    }
+
+    /// Create a membership predicate (field equals one of `values`).
+    pub fn is_in(field: impl Into<String>, values: Vec<Value>) -> Self {
+        Predicate::In(field.into(), values)
+    }
+
+    /// Create a string prefix predicate.
+    pub fn starts_with(field: impl Into<String>, prefix: impl Into<String>) -> Self {
+        Predicate::StartsWith(field.into(), prefix.into())
+    }
+
+    /// Create a contains predicate (substring or array element).
+    pub fn contains(field: impl Into<String>, value: impl Into<Value>) -> Self {
+        Predicate::Contains(field.into(), value.into())
+    }
+
+    /// Create a full-text match predicate.
+    pub fn text_match(field: impl Into<String>, query: impl Into<String>) -> Self {
+        Predicate::TextMatch(field.into(), query.into())
+    }
+
     /// Combine predicates with AND.
     pub fn and(predicates: Vec<Predicate>) -> Self {
         Predicate::And(predicates)
@@ -39,6 +97,119 @@ impl Predicate {
     pub fn or(predicates: Vec<Predicate>) -> Self {
         Predicate::Or(predicates)
     }
+
+    /// Fields the predicate reads.
+    pub fn fields(&self) -> Vec<&str> {
+        match self {
+            Predicate::And(preds) | Predicate::Or(preds) => {
+                preds.iter().flat_map(Predicate::fields).collect()
+            }
+            Predicate::Eq(field, _)
+            | Predicate::Exists(field)
+            | Predicate::NotExists(field)
+            | Predicate::Gt(field, _)
+            | Predicate::Gte(field, _)
+            | Predicate::Lt(field, _)
+            | Predicate::Lte(field, _)
+            | Predicate::In(field, _)
+            | Predicate::StartsWith(field, _)
+            | Predicate::Contains(field, _)
+            | Predicate::TextMatch(field, _) => vec![field.as_str()],
+        }
+    }
+
+    /// Build a predicate from a JSON filter descriptor.
+    ///
+    /// Accepted forms:
+    /// - `{"field": value, ...}` - equality on each field (ANDed together)
+    /// - `{"field": {"$eq": value}}` / `{"field": {"$exists": bool}}` - field operators,
+    ///   also `$gt`, `$gte`, `$lt`, `$lte`, `$in` (array), `$startswith`, `$contains`
+    ///   and `$text` (full-text match)
+    /// - `{"$and": [filter, ...]}` / `{"$or": [filter, ...]}` - logical combinators
+    ///
+    /// An object value is treated as an operator set only when all of its keys
+    /// start with `$`; otherwise it is compared for equality.
+    pub fn from_json(filter: &Value) -> Result<Self> {
+        let obj = filter
+            .as_object()
+            .ok_or_else(|| Error::Codec(format!("Filter must be an object, got: {}", filter)))?;
+
+        let mut preds = Vec::with_capacity(obj.len());
+        for (key, value) in obj {
+            match key.as_str() {
+                "$and" | "$or" => {
+                    let items = value.as_array().ok_or_else(|| {
+                        Error::Codec(format!("{} expects an array of filters", key))
+                    })?;
+                    let sub = items
+                        .iter()
+                        .map(Predicate::from_json)
+                        .collect::<Result<Vec<_>>>()?;
+                    preds.push(if key == "$and" {
+                        Predicate::And(sub)
+                    } else {
+                        Predicate::Or(sub)
+                    });
+                }
+                op if op.starts_with('$') => {
+                    return Err(Error::Codec(format!("Unknown filter operator: {}", op)));
+                }
+                field => preds.extend(Self::field_from_json(field, value)?),
+            }
+        }
+
+        Ok(if preds.len() == 1 {
+            preds.pop().unwrap()
+        } else {
+            Predicate::And(preds)
+        })
+    }
+
+    /// Parse a filter expression such as `age > 30 AND status = 'active'`.
+    ///
+    /// Supports `=`, `>`, `>=`, `<`, `<=`, `IN (...)`, `STARTSWITH`,
+    /// `CONTAINS`, `MATCH`, `EXISTS` and `NOT EXISTS` conditions combined with
+    /// `AND`, `OR` and parentheses. String values must be quoted.
+    pub fn parse(filter: &str) -> Result<Self> {
+        crate::query::parse(filter)
+    }
+
+    /// Parse the condition(s) applied to a single field.
+    fn field_from_json(field: &str, value: &Value) -> Result<Vec<Self>> {
+        let ops = match value {
+            Value::Object(map) if !map.is_empty() && map.keys().all(|k| k.starts_with('$')) => map,
+            _ => return Ok(vec![Predicate::eq(field, value.clone())]),
+        };
+
+        ops.iter()
+            .map(|(op, arg)| match op.as_str() {
+                "$eq" => Ok(Predicate::eq(field, arg.clone())),
+                "$exists" => match arg.as_bool() {
+                    Some(true) => Ok(Predicate::Exists(field.to_string())),
+                    Some(false) => Ok(Predicate::NotExists(field.to_string())),
+                    None => Err(Error::Codec("$exists expects a boolean".to_string())),
+                },
+                "$gt" => Ok(Predicate::gt(field, arg.clone())),
+                "$gte" => Ok(Predicate::gte(field, arg.clone())),
+                "$lt" => Ok(Predicate::lt(field, arg.clone())),
+                "$lte" => Ok(Predicate::lte(field, arg.clone())),
+                "$in" => match arg {
+                    Value::Array(values) => Ok(Predicate::is_in(field, values.clone())),
+                    _ => Err(Error::Codec("$in expects an array".to_string())),
+                },
+                "$startswith" => match arg {
+                    Value::String(prefix) => Ok(Predicate::starts_with(field, prefix.clone())),
+                    _ => Err(Error::Codec("$startswith expects a string".to_string())),
+                },
+                "$contains" => Ok(Predicate::contains(field, arg.clone())),
+                "$text" => match arg {
+                    Value::String(query) => Ok(Predicate::text_match(field, query.clone())),
+                    _ => Err(Error::Codec("$text expects a string".to_string())),
+                },
+                _ => Err(Error::Codec(format!("Unknown filter operator: {}", op))),
+            })
+            .collect()
+    }
 }
 
 /// JSON codec for ZDS documents.
@@ -80,7 +251,7 @@ impl Codec {
     }
 
     /// Get a nested field value using dot notation.
-    fn get_nested<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
+    pub(crate) fn get_nested<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
         let parts: Vec<&str> = path.split('.').collect();
         let mut current = doc;
 
@@ -91,6 +262,21 @@ impl Codec {
         Some(current)
     }
 
+    /// Compare a document field against a bound.
+    ///
+    /// Numbers compare numerically and strings lexicographically; missing
+    /// fields and mismatched types are incomparable.
+    fn compare_field(doc: &Value, field: &str, bound: &Value) -> Option<Ordering> {
+        match (Self::get_nested(doc, field)?, bound) {
+            (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            },
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
     /// Apply a predicate to a document.
     pub fn apply_predicate(doc: &Value, pred: &Predicate) -> Result<bool> {
         match pred {
@@ -100,6 +286,36 @@ impl Codec {
             }
             Predicate::Exists(field) => Ok(Self::get_nested(doc, field).is_some()),
             Predicate::NotExists(field) => Ok(Self::get_nested(doc, field).is_none()),
+            Predicate::Gt(field, bound) => {
+                Ok(Self::compare_field(doc, field, bound).is_some_and(|o| o == Ordering::Greater))
+            }
+            Predicate::Gte(field, bound) => {
+                Ok(Self::compare_field(doc, field, bound).is_some_and(|o| o != Ordering::Less))
+            }
+            Predicate::Lt(field, bound) => {
+                Ok(Self::compare_field(doc, field, bound).is_some_and(|o| o == Ordering::Less))
+            }
+            Predicate::Lte(field, bound) => {
+                Ok(Self::compare_field(doc, field, bound).is_some_and(|o| o != Ordering::Greater))
+            }
+            Predicate::In(field, values) => {
+                Ok(Self::get_nested(doc, field).is_some_and(|actual| values.contains(actual)))
+            }
+            Predicate::StartsWith(field, prefix) => Ok(Self::get_nested(doc, field)
+                .and_then(Value::as_str)
+                .is_some_and(|s| s.starts_with(prefix.as_str()))),
+            Predicate::Contains(field, needle) => Ok(match Self::get_nested(doc, field) {
+                Some(Value::String(s)) => needle.as_str().is_some_and(|n| s.contains(n)),
+                Some(Value::Array(items)) => items.contains(needle),
+                _ => false,
+            }),
+            Predicate::TextMatch(field, query) => {
+                let terms = crate::text_index::tokenize(query);
+                Ok(!terms.is_empty()
+                    && Self::get_nested(doc, field)
+                        .map(crate::text_index::value_tokens)
+                        .is_some_and(|tokens| terms.iter().all(|t| tokens.contains(t))))
+            }
             Predicate::And(preds) => {
                 for p in preds {
                     if !Self::apply_predicate(doc, p)? {
@@ -119,6 +335,52 @@ impl Codec {
         }
     }
 
+    /// Apply a JSON merge patch (RFC 7386) to a document in place.
+    ///
+    /// Object members are merged recursively and `null` members remove their
+    /// key; any other patch value (arrays included) replaces the target.
+    pub fn merge_patch(target: &mut Value, patch: &Value) {
+        let Value::Object(patch) = patch else {
+            *target = patch.clone();
+            return;
+        };
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        if let Value::Object(target) = target {
+            for (key, value) in patch {
+                if value.is_null() {
+                    target.remove(key);
+                } else {
+                    Self::merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+    }
+
+    /// Estimated size of a value encoded as compact JSON, without encoding
+    /// it. Numbers count as 8 bytes and string escapes are ignored.
+    pub fn estimated_size(v: &Value) -> usize {
+        // Brackets plus the separators between items
+        let enclosing = |items: usize| 2 + items.saturating_sub(1);
+        match v {
+            Value::Null | Value::Bool(true) => 4,
+            Value::Bool(false) => 5,
+            Value::Number(_) => 8,
+            Value::String(s) => s.len() + 2,
+            Value::Array(arr) => {
+                enclosing(arr.len()) + arr.iter().map(Self::estimated_size).sum::<usize>()
+            }
+            Value::Object(map) => {
+                enclosing(map.len())
+                    + map
+                        .iter()
+                        .map(|(k, v)| k.len() + 3 + Self::estimated_size(v))
+                        .sum::<usize>()
+            }
+        }
+    }
+
     /// Canonicalize a JSON value for schema hashing.
     /// Sorts object keys recursively and produces deterministic output.
     pub fn canonicalize(v: &Value) -> String {
@@ -200,10 +462,121 @@ mod tests {
         assert!(Codec::apply_predicate(&doc, &pred).unwrap());
     }
 
+    #[test]
+    fn test_predicate_from_json() {
+        let doc = json!({"status": "active", "meta": {"tier": 2}, "n": 1});
+
+        let pred = Predicate::from_json(&json!({"status": "active", "n": 1})).unwrap();
+        assert!(Codec::apply_predicate(&doc, &pred).unwrap());
+
+        let pred = Predicate::from_json(&json!({"meta.tier": {"$eq": 2}})).unwrap();
+        assert!(Codec::apply_predicate(&doc, &pred).unwrap());
+
+        let pred = Predicate::from_json(&json!({
+            "$or": [{"status": "inactive"}, {"missing": {"$exists": false}}]
+        }))
+        .unwrap();
+        assert!(Codec::apply_predicate(&doc, &pred).unwrap());
+
+        // Plain object values compare for equality
+        let pred = Predicate::from_json(&json!({"meta": {"tier": 2}})).unwrap();
+        assert!(Codec::apply_predicate(&doc, &pred).unwrap());
+
+        assert!(Predicate::from_json(&json!({"n": {"$bogus": 1}})).is_err());
+        assert!(Predicate::from_json(&json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_predicate_comparisons() {
+        let doc = json!({"age": 30, "score": 0.75, "name": "alice", "tags": ["a", "b"]});
+        let check = |pred: Predicate| Codec::apply_predicate(&doc, &pred).unwrap();
+
+        assert!(check(Predicate::gt("age", 29)));
+        assert!(!check(Predicate::gt("age", 30)));
+        assert!(check(Predicate::gte("age", 30)));
+        assert!(check(Predicate::lt("score", 1)));
+        assert!(check(Predicate::lte("score", 0.75)));
+        assert!(check(Predicate::gt("name", "al")));
+        assert!(!check(Predicate::gt("name", 1)));
+        assert!(!check(Predicate::lt("missing", 1)));
+
+        assert!(check(Predicate::is_in("age", vec![json!(1), json!(30)])));
+        assert!(!check(Predicate::is_in("age", vec![json!("30")])));
+        assert!(check(Predicate::starts_with("name", "ali")));
+        assert!(!check(Predicate::starts_with("age", "3")));
+        assert!(check(Predicate::contains("name", "lic")));
+        assert!(check(Predicate::contains("tags", "b")));
+        assert!(!check(Predicate::contains("tags", "c")));
+
+        let pred = Predicate::from_json(&json!({
+            "age": {"$gte": 18, "$lt": 65},
+            "name": {"$startswith": "a"},
+            "tags": {"$contains": "a"},
+            "score": {"$in": [0.5, 0.75]}
+        }))
+        .unwrap();
+        assert!(check(pred));
+        assert!(Predicate::from_json(&json!({"age": {"$in": 1}})).is_err());
+
+        let doc = json!({"body": "The Quick brown fox", "tags": ["Red Panda"]});
+        let check = |pred: Predicate| Codec::apply_predicate(&doc, &pred).unwrap();
+        assert!(check(Predicate::text_match("body", "fox QUICK")));
+        assert!(!check(Predicate::text_match("body", "quick dog")));
+        assert!(!check(Predicate::text_match("body", "")));
+        assert!(check(Predicate::text_match("tags", "panda")));
+        assert!(check(
+            Predicate::from_json(&json!({"body": {"$text": "brown"}})).unwrap()
+        ));
+    }
+
     #[test]
     fn test_canonicalize() {
         let v1 = json!({"b": 2, "a": 1});
         let v2 = json!({"a": 1, "b": 2});
         assert_eq!(Codec::canonicalize(&v1), Codec::canonicalize(&v2));
     }
+
+    #[test]
+    fn test_estimated_size() {
+        let doc = json!({"name": "alice", "tags": ["a", "bc"], "ok": true, "n": null});
+        assert_eq!(
+            Codec::estimated_size(&doc),
+            Codec::encode(&doc).unwrap().len()
+        );
+        assert_eq!(Codec::estimated_size(&json!(12345678)), 8);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut doc = json!({
+            "title": "Goodbye!",
+            "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        });
+        let patch = json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": {"familyName": null},
+            "tags": ["example"]
+        });
+        Codec::merge_patch(&mut doc, &patch);
+        assert_eq!(
+            doc,
+            json!({
+                "title": "Hello!",
+                "author": {"givenName": "John"},
+                "tags": ["example"],
+                "content": "This will be unchanged",
+                "phoneNumber": "+01-123-456-7890"
+            })
+        );
+
+        // Nulls in a new nested object are dropped, non-objects replace
+        let mut doc = json!({"a": 1});
+        Codec::merge_patch(&mut doc, &json!({"a": {"b": null, "c": 2}}));
+        assert_eq!(doc, json!({"a": {"c": 2}}));
+        Codec::merge_patch(&mut doc, &json!([1, 2]));
+        assert_eq!(doc, json!([1, 2]));
+    }
 }
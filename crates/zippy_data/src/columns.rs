@@ -0,0 +1,345 @@
+//! Columnar projection cache for hot fields.
+//!
+//! Scans that project a few scalar fields still have to parse every JSON
+//! line. A [`ColumnCache`] keeps the values of chosen fields as one array
+//! per field, so [`Scanner`](crate::Scanner) can answer projections and
+//! predicates that only read cached fields without touching the documents.
+//!
+//! The cache lives in `meta/columns.json` and is maintained by
+//! [`FastStore`](crate::FastStore) once built: written documents are cached
+//! as they are put and the file is saved on flush. Every row records the
+//! checksum of the line it was read from, so rows gone stale through any
+//! other write are ignored and the scan falls back to the JSON line.
+
+use std::path::Path;
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{encryption, Codec, Error, FieldIndexes, Layout, Result};
+
+/// On-disk form of one column.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredColumn {
+    /// One value per row, `null` where the document lacks the field
+    values: Vec<Value>,
+    /// Rows whose document lacks the field
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    missing: Vec<usize>,
+}
+
+/// On-disk form of the cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCache {
+    fields: Vec<String>,
+    ids: Vec<String>,
+    checksums: Vec<u32>,
+    columns: Vec<StoredColumn>,
+}
+
+/// Cached values of a set of fields, one array per field.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnCache {
+    /// Cached fields, sorted
+    fields: Vec<String>,
+    /// Doc ID -> row
+    rows: FxHashMap<String, usize>,
+    ids: Vec<String>,
+    /// Checksum of the line each row was read from
+    checksums: Vec<u32>,
+    /// One value array per field, `None` where a document lacks the field
+    columns: Vec<Vec<Option<Value>>>,
+}
+
+impl ColumnCache {
+    /// Create an empty cache for `fields` (dot notation for nested fields).
+    pub fn new(fields: impl IntoIterator<Item = impl Into<String>>) -> Result<Self> {
+        let mut fields: Vec<String> = fields.into_iter().map(Into::into).collect();
+        fields.sort();
+        fields.dedup();
+        for field in &fields {
+            FieldIndexes::validate_field(field)?;
+            if field == "_id" || field.starts_with("_id.") {
+                return Err(Error::Validation(
+                    "the document ID can't be cached as a column".to_string(),
+                ));
+            }
+        }
+        Ok(ColumnCache {
+            columns: vec![Vec::new(); fields.len()],
+            fields,
+            ..Default::default()
+        })
+    }
+
+    /// The cached fields, sorted.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Number of cached documents.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if no documents are cached.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Whether every one of `fields` is cached.
+    pub fn covers<'a>(&self, fields: impl IntoIterator<Item = &'a str>) -> bool {
+        fields.into_iter().all(|field| {
+            self.fields
+                .binary_search_by(|f| f.as_str().cmp(field))
+                .is_ok()
+        })
+    }
+
+    /// Cache (or re-cache) a document read from a line with `checksum`.
+    pub fn insert(&mut self, doc_id: &str, checksum: u32, doc: &Value) {
+        let row = match self.rows.get(doc_id) {
+            Some(&row) => row,
+            None => {
+                let row = self.ids.len();
+                self.rows.insert(doc_id.to_string(), row);
+                self.ids.push(doc_id.to_string());
+                self.checksums.push(0);
+                for column in &mut self.columns {
+                    column.push(None);
+                }
+                row
+            }
+        };
+        self.checksums[row] = checksum;
+        for (field, column) in self.fields.iter().zip(&mut self.columns) {
+            column[row] = Codec::get_nested(doc, field).cloned();
+        }
+    }
+
+    /// Remove a document from the cache.
+    pub fn remove(&mut self, doc_id: &str) -> bool {
+        let Some(row) = self.rows.remove(doc_id) else {
+            return false;
+        };
+        self.ids.swap_remove(row);
+        self.checksums.swap_remove(row);
+        for column in &mut self.columns {
+            column.swap_remove(row);
+        }
+        if let Some(moved) = self.ids.get(row) {
+            self.rows.insert(moved.clone(), row);
+        }
+        true
+    }
+
+    /// Keep only the documents for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        let gone: Vec<String> = self.ids.iter().filter(|id| !keep(id)).cloned().collect();
+        for doc_id in gone {
+            self.remove(&doc_id);
+        }
+    }
+
+    /// The cached values of `fields` as a partial document, or `None` if a
+    /// field isn't cached or the document isn't cached from a line with
+    /// `checksum`.
+    ///
+    /// Fields the document lacks are left out, so projections and
+    /// predicates read the partial document like the full one.
+    pub fn get(&self, doc_id: &str, checksum: u32, fields: &[&str]) -> Option<Value> {
+        let row = *self.rows.get(doc_id)?;
+        if self.checksums[row] != checksum {
+            return None;
+        }
+        let mut doc = Value::Object(Map::new());
+        for field in fields {
+            let col = self
+                .fields
+                .binary_search_by(|f| f.as_str().cmp(field))
+                .ok()?;
+            if let Some(value) = &self.columns[col][row] {
+                set_nested(&mut doc, field, value.clone());
+            }
+        }
+        Some(doc)
+    }
+
+    /// Load a collection's cache, or `None` if it has none.
+    pub fn load(root: &Path, collection: &str) -> Result<Option<Self>> {
+        let path = Layout::column_cache_file(root, collection);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let stored: StoredCache =
+            serde_json::from_slice(&encryption::read_file(root, collection, &path)?)?;
+        let corrupt = || Error::InvalidContainer("column cache is corrupted".to_string());
+        let rows = stored.ids.len();
+        if stored.checksums.len() != rows || stored.columns.len() != stored.fields.len() {
+            return Err(corrupt());
+        }
+
+        let mut cache = ColumnCache::new(stored.fields.iter().cloned())?;
+        if cache.fields != stored.fields {
+            return Err(corrupt());
+        }
+        cache.columns.clear();
+        for stored_column in stored.columns {
+            if stored_column.values.len() != rows {
+                return Err(corrupt());
+            }
+            let mut column: Vec<Option<Value>> =
+                stored_column.values.into_iter().map(Some).collect();
+            for row in stored_column.missing {
+                *column.get_mut(row).ok_or_else(corrupt)? = None;
+            }
+            cache.columns.push(column);
+        }
+        cache.rows = stored
+            .ids
+            .iter()
+            .enumerate()
+            .map(|(row, id)| (id.clone(), row))
+            .collect();
+        cache.ids = stored.ids;
+        cache.checksums = stored.checksums;
+        Ok(Some(cache))
+    }
+
+    /// Save the cache to disk, replacing the file atomically.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                let mut stored = StoredColumn::default();
+                for (row, value) in column.iter().enumerate() {
+                    match value {
+                        Some(value) => stored.values.push(value.clone()),
+                        None => {
+                            stored.values.push(Value::Null);
+                            stored.missing.push(row);
+                        }
+                    }
+                }
+                stored
+            })
+            .collect();
+        let stored = StoredCache {
+            fields: self.fields.clone(),
+            ids: self.ids.clone(),
+            checksums: self.checksums.clone(),
+            columns,
+        };
+
+        std::fs::create_dir_all(Layout::meta_dir(root, collection))?;
+        let path = Layout::column_cache_file(root, collection);
+        encryption::write_file(root, collection, &path, serde_json::to_vec(&stored)?)
+    }
+
+    /// Delete a collection's stored cache.
+    pub fn drop_cache(root: &Path, collection: &str) -> Result<()> {
+        let path = Layout::column_cache_file(root, collection);
+        if !path.exists() {
+            return Err(Error::Validation(format!(
+                "No column cache in collection '{}'",
+                collection
+            )));
+        }
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+/// Set the value at a dot-notation path, creating objects along the way.
+/// A path through a non-object value is left alone.
+fn set_nested(doc: &mut Value, path: &str, value: Value) {
+    let mut current = doc;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let Value::Object(obj) = current else {
+            return;
+        };
+        if parts.peek().is_none() {
+            obj.insert(part.to_string(), value);
+            return;
+        }
+        current = obj
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_column_cache() {
+        let mut cache = ColumnCache::new(["label", "meta.score", "label"]).unwrap();
+        assert_eq!(cache.fields(), ["label", "meta.score"]);
+        assert!(cache.covers(["meta.score"]));
+        assert!(!cache.covers(["label", "meta"]));
+        assert!(ColumnCache::new(["_id"]).is_err());
+
+        let a = json!({"label": "cat", "meta": {"score": 0.9, "src": "web"}});
+        let b = json!({"label": null});
+        cache.insert("a", 1, &a);
+        cache.insert("b", 2, &b);
+        assert_eq!(
+            cache.get("a", 1, &["label", "meta.score"]),
+            Some(json!({"label": "cat", "meta": {"score": 0.9}}))
+        );
+        assert_eq!(
+            cache.get("b", 2, &["label", "meta.score"]),
+            Some(json!({"label": null}))
+        );
+        // Stale rows and uncached fields fall back
+        assert_eq!(cache.get("a", 7, &["label"]), None);
+        assert_eq!(cache.get("a", 1, &["meta.src"]), None);
+
+        // Projections of the partial document match the full one
+        let fields = ["label", "meta.score"];
+        let partial = cache.get("a", 1, &fields).unwrap();
+        assert_eq!(
+            Codec::extract_fields(&partial, &fields).unwrap(),
+            Codec::extract_fields(&a, &fields).unwrap()
+        );
+
+        assert!(cache.remove("a"));
+        assert!(!cache.remove("a"));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("b", 2, &["label"]), Some(json!({"label": null})));
+    }
+
+    #[test]
+    fn test_column_cache_save_load() {
+        let tmp = TempDir::new().unwrap();
+        assert!(ColumnCache::load(tmp.path(), "test").unwrap().is_none());
+
+        let mut cache = ColumnCache::new(["n", "tags"]).unwrap();
+        cache.insert("x", 10, &json!({"n": 1, "tags": ["a"]}));
+        cache.insert("y", 11, &json!({"tags": null}));
+        cache.insert("z", 12, &json!({"n": 3}));
+        cache.remove("x");
+        cache.save(tmp.path(), "test").unwrap();
+
+        let loaded = ColumnCache::load(tmp.path(), "test").unwrap().unwrap();
+        assert_eq!(loaded.fields(), ["n", "tags"]);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded.get("y", 11, &["n", "tags"]),
+            Some(json!({"tags": null}))
+        );
+        assert_eq!(loaded.get("z", 12, &["n", "tags"]), Some(json!({"n": 3})));
+        assert_eq!(loaded.get("x", 10, &["n"]), None);
+
+        ColumnCache::drop_cache(tmp.path(), "test").unwrap();
+        assert!(ColumnCache::load(tmp.path(), "test").unwrap().is_none());
+        assert!(ColumnCache::drop_cache(tmp.path(), "test").is_err());
+    }
+}
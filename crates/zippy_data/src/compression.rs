@@ -0,0 +1,823 @@
+//! Block-compressed data files for the fast store.
+//!
+//! A compressed data file (`meta/data.jsonl.zst`) is a sequence of blocks.
+//! Each block is a zstd frame holding whole JSONL lines, preceded by a
+//! skippable frame that records its uncompressed and compressed sizes. The
+//! file is therefore still a valid zstd stream (`zstd -d` yields the JSONL),
+//! and walking the headers rebuilds the block offset index without
+//! decompressing anything.
+//!
+//! Index entries keep offsets into the uncompressed ("logical") stream, so
+//! point lookups decompress a single block and everything else in the store
+//! works the same in both modes.
+//!
+//! In encrypted collections (see [`encryption`](crate::encryption)) each
+//! zstd frame is sealed, and the header, payload and all, becomes one
+//! skippable frame with its own magic.
+
+use std::{
+    borrow::Cow,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+use memmap2::{Mmap, MmapOptions};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(all(target_os = "linux", feature = "vectored-io"))]
+use crate::vectored::VectoredWriter;
+use crate::{
+    config::CollectionConfig, encryption::Cipher, fast_writer::IndexEntry, segment::MmapHints,
+    Error, Layout, OpenMode, Result,
+};
+
+/// Uncompressed size at which a block is cut.
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+/// zstd compression level for data blocks.
+pub const ZSTD_LEVEL: i32 = 3;
+
+/// Skippable frame magic used for block headers.
+const HEADER_MAGIC: u32 = 0x184D2A5A;
+
+/// Skippable frame magic of encrypted blocks, whose frame covers the
+/// sealed payload too.
+const ENCRYPTED_MAGIC: u32 = 0x184D2A5B;
+
+/// Header length: magic, frame size, raw length, compressed length.
+const HEADER_LEN: usize = 16;
+
+/// Associated data of an encrypted block: its logical offset, so blocks
+/// can't be reordered or moved between offsets.
+fn block_aad(logical: u64) -> [u8; 17] {
+    let mut aad = [0u8; 17];
+    aad[..9].copy_from_slice(b"zds block");
+    aad[9..].copy_from_slice(&logical.to_le_bytes());
+    aad
+}
+
+/// Storage format of a fast store data file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Plain JSONL (`meta/data.jsonl`)
+    #[default]
+    None,
+    /// zstd-compressed blocks of JSONL (`meta/data.jsonl.zst`)
+    Zstd,
+}
+
+impl Compression {
+    /// Name as stored in the manifest.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Read a collection's configured compression from its manifest.
+    ///
+    /// Missing manifests or fields mean no compression.
+    pub fn load(root: &Path, collection: &str) -> Result<Self> {
+        let path = Layout::manifest_file(root, collection);
+        if !path.exists() {
+            return Ok(Compression::None);
+        }
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        match manifest.get("compression").and_then(Value::as_str) {
+            Some(name) => name.parse(),
+            None => Ok(Compression::None),
+        }
+    }
+
+    /// Store this compression in a collection's manifest, keeping other fields.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        CollectionConfig::update(root, collection, |obj| {
+            obj.insert("compression".to_string(), Value::from(self.as_str()));
+        })
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "zstd" => Ok(Compression::Zstd),
+            other => Err(Error::Validation(format!(
+                "unknown compression '{}' (expected none or zstd)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Location of one compressed block.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Block {
+    /// Offset of the block's first line in the logical stream
+    logical: u64,
+    /// Offset of the zstd frame in the file
+    physical: usize,
+    raw_len: u32,
+    compressed_len: u32,
+    /// Whether the frame is sealed (see [`Cipher`])
+    encrypted: bool,
+}
+
+impl Block {
+    fn logical_end(&self) -> u64 {
+        self.logical + self.raw_len as u64
+    }
+}
+
+/// Walk block headers, returning the blocks and the length of the file
+/// prefix they cover (anything after it is a torn write).
+fn read_blocks(data: &[u8]) -> (Vec<Block>, usize) {
+    let mut blocks = Vec::new();
+    let mut pos = 0;
+    let mut logical = 0;
+    while let Some(header) = data.get(pos..pos + HEADER_LEN) {
+        let word = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let encrypted = match word(0) {
+            HEADER_MAGIC if word(4) == 8 => false,
+            ENCRYPTED_MAGIC if word(4) as u64 == 8 + word(12) as u64 => true,
+            _ => break,
+        };
+        let block = Block {
+            logical,
+            physical: pos + HEADER_LEN,
+            raw_len: word(8),
+            compressed_len: word(12),
+            encrypted,
+        };
+        let end = block.physical + block.compressed_len as usize;
+        if end > data.len() {
+            break;
+        }
+        logical = block.logical_end();
+        blocks.push(block);
+        pos = end;
+    }
+    (blocks, pos)
+}
+
+/// Drop a torn trailing block left by a crash mid-write.
+///
+/// Returns the logical (uncompressed) length of the complete blocks and
+/// whether they are all encrypted (`None` if there are none); the file is
+/// only truncated in read-write mode.
+pub(crate) fn recover(path: &Path, mode: OpenMode) -> Result<(u64, Option<bool>)> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok((0, None));
+    }
+    let mmap = unsafe { Mmap::map(&file)? };
+    let (blocks, complete) = read_blocks(&mmap);
+    drop(mmap);
+
+    if (complete as u64) < len && mode == OpenMode::ReadWrite {
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(complete as u64)?;
+    }
+    let encrypted = (!blocks.is_empty()).then(|| blocks.iter().all(|b| b.encrypted));
+    Ok((blocks.last().map_or(0, Block::logical_end), encrypted))
+}
+
+/// Most recently decoded block, reused by sequential reads.
+#[derive(Default)]
+pub(crate) struct BlockCache {
+    block: Option<usize>,
+    data: Vec<u8>,
+}
+
+/// Read-only view of a data file in logical (uncompressed) offsets.
+pub(crate) enum DataView {
+    Plain(Mmap),
+    Blocks {
+        mmap: Mmap,
+        blocks: Vec<Block>,
+        /// Opens encrypted blocks
+        cipher: Option<Arc<Cipher>>,
+    },
+}
+
+impl DataView {
+    /// Map a data file (`None` if it is missing or empty), applying the
+    /// mapping-time `hints`.
+    pub(crate) fn open(
+        path: &Path,
+        compression: Compression,
+        cipher: Option<&Arc<Cipher>>,
+        hints: &MmapHints,
+    ) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(None);
+        }
+        let mut options = MmapOptions::new();
+        if hints.populate {
+            options.populate();
+        }
+        let mmap = unsafe { options.map(&file)? };
+        #[cfg(unix)]
+        if hints.sequential {
+            // Only a hint: failures are ignored
+            let _ = mmap.advise(memmap2::Advice::Sequential);
+        }
+        Ok(Some(match compression {
+            Compression::None => DataView::Plain(mmap),
+            Compression::Zstd => {
+                let (blocks, _) = read_blocks(&mmap);
+                DataView::Blocks {
+                    mmap,
+                    blocks,
+                    cipher: cipher.cloned(),
+                }
+            }
+        }))
+    }
+
+    /// Format of the mapped file.
+    pub(crate) fn compression(&self) -> Compression {
+        match self {
+            DataView::Plain(_) => Compression::None,
+            DataView::Blocks { .. } => Compression::Zstd,
+        }
+    }
+
+    /// Logical length of the data.
+    pub(crate) fn len(&self) -> u64 {
+        match self {
+            DataView::Plain(mmap) => mmap.len() as u64,
+            DataView::Blocks { blocks, .. } => blocks.last().map_or(0, Block::logical_end),
+        }
+    }
+
+    /// Whether every block is encrypted (`false` for plain or empty files).
+    pub(crate) fn encrypted(&self) -> bool {
+        match self {
+            DataView::Plain(_) => false,
+            DataView::Blocks { blocks, .. } => {
+                !blocks.is_empty() && blocks.iter().all(|b| b.encrypted)
+            }
+        }
+    }
+
+    fn decode(mmap: &Mmap, block: &Block, cipher: Option<&Arc<Cipher>>) -> Result<Vec<u8>> {
+        let frame = &mmap[block.physical..block.physical + block.compressed_len as usize];
+        let opened;
+        let frame = match (block.encrypted, cipher) {
+            (true, Some(cipher)) => {
+                opened = cipher.open(frame, &block_aad(block.logical))?;
+                &opened[..]
+            }
+            (true, None) => {
+                return Err(Error::Encryption(
+                    "data is encrypted and no key was given".to_string(),
+                ))
+            }
+            // A block in the clear may have been put in place of a sealed one
+            (false, Some(_)) => {
+                return Err(Error::Encryption(
+                    "data block of an encrypted collection is not sealed".to_string(),
+                ))
+            }
+            (false, None) => frame,
+        };
+        zstd::bulk::decompress(frame, block.raw_len as usize).map_err(Error::Io)
+    }
+
+    /// Index of the block holding logical `offset`.
+    fn block_of(blocks: &[Block], offset: u64) -> Option<usize> {
+        let idx = blocks
+            .partition_point(|b| b.logical <= offset)
+            .checked_sub(1)?;
+        (offset < blocks[idx].logical_end()).then_some(idx)
+    }
+
+    /// Read `length` bytes at logical `offset` (`None` if out of bounds).
+    ///
+    /// Lines never span blocks, so compressed reads decode one block, which
+    /// stays in `cache` for the next read.
+    pub(crate) fn read<'a>(
+        &'a self,
+        offset: u64,
+        length: u32,
+        cache: &'a mut BlockCache,
+    ) -> Result<Option<&'a [u8]>> {
+        let start = offset as usize;
+        let end = start + length as usize;
+        match self {
+            DataView::Plain(mmap) => Ok(mmap.get(start..end)),
+            DataView::Blocks {
+                mmap,
+                blocks,
+                cipher,
+            } => {
+                let Some(idx) = Self::block_of(blocks, offset) else {
+                    return Ok(None);
+                };
+                if cache.block != Some(idx) {
+                    cache.data = Self::decode(mmap, &blocks[idx], cipher.as_ref())?;
+                    cache.block = Some(idx);
+                }
+                let base = blocks[idx].logical as usize;
+                Ok(cache.data.get(start - base..end - base))
+            }
+        }
+    }
+
+    /// Ask the OS to read the file data holding logical offsets
+    /// `start..end` into the page cache, without waiting for it.
+    pub(crate) fn prefetch(&self, start: u64, end: u64) {
+        let (mmap, range) = match self {
+            DataView::Plain(mmap) => (mmap, start as usize..(end as usize).min(mmap.len())),
+            DataView::Blocks { mmap, blocks, .. } => {
+                let first = Self::block_of(blocks, start).unwrap_or(blocks.len());
+                let last = blocks.partition_point(|b| b.logical < end);
+                let covered = blocks.get(first..last).unwrap_or_default();
+                let range = match (covered.first(), covered.last()) {
+                    (Some(head), Some(tail)) => {
+                        head.physical..tail.physical + tail.compressed_len as usize
+                    }
+                    _ => 0..0,
+                };
+                (mmap, range)
+            }
+        };
+        #[cfg(unix)]
+        if !range.is_empty() {
+            // Only a hint: failures are ignored
+            let _ = mmap.advise_range(memmap2::Advice::WillNeed, range.start, range.len());
+        }
+        #[cfg(not(unix))]
+        let _ = (mmap, range);
+    }
+
+    /// Map the lines of `entries` (sorted by offset) in parallel, keeping order.
+    ///
+    /// Compressed data is decoded once per block. Lines that are out of
+    /// bounds or in undecodable blocks are skipped, as are `None` results.
+    pub(crate) fn par_map_lines<T, F>(&self, entries: &[IndexEntry], f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&[u8]) -> Option<T> + Sync + Send,
+    {
+        match self {
+            DataView::Plain(mmap) => entries
+                .par_iter()
+                .filter_map(|e| {
+                    let start = e.offset as usize;
+                    mmap.get(start..start + e.length as usize).and_then(&f)
+                })
+                .collect(),
+            DataView::Blocks {
+                mmap,
+                blocks,
+                cipher,
+            } => {
+                // Runs of consecutive entries that live in the same block
+                let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+                for (i, entry) in entries.iter().enumerate() {
+                    let Some(idx) = Self::block_of(blocks, entry.offset) else {
+                        continue;
+                    };
+                    match runs.last_mut() {
+                        Some((block, _, end)) if *block == idx && *end == i => *end = i + 1,
+                        _ => runs.push((idx, i, i + 1)),
+                    }
+                }
+                runs.par_iter()
+                    .flat_map_iter(|&(idx, start, end)| {
+                        let block = &blocks[idx];
+                        let data = Self::decode(mmap, block, cipher.as_ref()).unwrap_or_default();
+                        let base = block.logical as usize;
+                        entries[start..end]
+                            .iter()
+                            .filter_map(|e| {
+                                let offset = e.offset as usize - base;
+                                data.get(offset..offset + e.length as usize).and_then(&f)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Visit the data in `start..end` as `(logical offset, bytes)` chunks
+    /// that begin and end on line boundaries (given line-aligned bounds).
+    pub(crate) fn for_each_chunk(
+        &self,
+        start: u64,
+        end: u64,
+        mut f: impl FnMut(u64, &[u8]),
+    ) -> Result<()> {
+        match self {
+            DataView::Plain(mmap) => {
+                let end = (end as usize).min(mmap.len());
+                if (start as usize) < end {
+                    f(start, &mmap[start as usize..end]);
+                }
+            }
+            DataView::Blocks {
+                mmap,
+                blocks,
+                cipher,
+            } => {
+                for block in blocks
+                    .iter()
+                    .filter(|b| b.logical_end() > start && b.logical < end)
+                {
+                    let data = Self::decode(mmap, block, cipher.as_ref())?;
+                    let from = start.saturating_sub(block.logical) as usize;
+                    let to = (end.min(block.logical_end()) - block.logical) as usize;
+                    f(block.logical + from as u64, &data[from..to]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The whole logical data (decompressed for compressed files).
+    pub(crate) fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+        match self {
+            DataView::Plain(mmap) => Ok(Cow::Borrowed(&mmap[..])),
+            DataView::Blocks { .. } => {
+                let mut data = Vec::with_capacity(self.len() as usize);
+                self.for_each_chunk(0, self.len(), |_, chunk| data.extend_from_slice(chunk))?;
+                Ok(Cow::Owned(data))
+            }
+        }
+    }
+}
+
+/// Appends whole JSONL lines to a data file, compressing them in blocks.
+pub(crate) struct BlockWriter {
+    file: BufWriter<File>,
+    /// Uncompressed lines not yet written as a block
+    buf: Vec<u8>,
+    /// Seals every block when set
+    cipher: Option<Arc<Cipher>>,
+    /// Logical offset of the next block
+    logical: u64,
+}
+
+impl BlockWriter {
+    /// Write the complete lines in the buffer (all of it when `all`) as a block.
+    fn write_block(&mut self, all: bool) -> std::io::Result<()> {
+        let cut = if all {
+            self.buf.len()
+        } else {
+            memchr::memrchr(b'\n', &self.buf).map_or(0, |pos| pos + 1)
+        };
+        if cut == 0 {
+            return Ok(());
+        }
+        let mut compressed = zstd::bulk::compress(&self.buf[..cut], ZSTD_LEVEL)?;
+        let (magic, frame_size) = match &self.cipher {
+            Some(cipher) => {
+                compressed = cipher
+                    .seal(&compressed, &block_aad(self.logical))
+                    .map_err(|e| std::io::Error::other(e.to_string()))?;
+                (ENCRYPTED_MAGIC, 8 + compressed.len() as u32)
+            }
+            None => (HEADER_MAGIC, 8),
+        };
+        self.file.write_all(&magic.to_le_bytes())?;
+        self.file.write_all(&frame_size.to_le_bytes())?;
+        self.file.write_all(&(cut as u32).to_le_bytes())?;
+        self.file
+            .write_all(&(compressed.len() as u32).to_le_bytes())?;
+        self.file.write_all(&compressed)?;
+        self.buf.drain(..cut);
+        self.logical += cut as u64;
+        Ok(())
+    }
+}
+
+impl Write for BlockWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= BLOCK_SIZE {
+            self.write_block(false)?;
+        }
+        Ok(data.len())
+    }
+
+    /// Compress everything buffered into a final (possibly short) block.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_block(true)?;
+        self.file.flush()
+    }
+}
+
+/// Appending writer for a data file in either format.
+pub(crate) enum DataWriter {
+    Plain(BufWriter<File>),
+    /// Plain JSONL written with large vectored writes
+    #[cfg(all(target_os = "linux", feature = "vectored-io"))]
+    Vectored(VectoredWriter),
+    Blocks(BlockWriter),
+}
+
+impl DataWriter {
+    /// Open `path` for appending (created if missing).
+    ///
+    /// Blocks are sealed with `cipher` when given; plain JSONL files can't
+    /// be encrypted.
+    pub(crate) fn append(
+        path: &Path,
+        compression: Compression,
+        cipher: Option<&Arc<Cipher>>,
+    ) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Sealed blocks are bound to their offset, so it must be known
+        let logical = match compression {
+            Compression::Zstd if file.metadata()?.len() > 0 => {
+                let mmap = unsafe { Mmap::map(&File::open(path)?)? };
+                read_blocks(&mmap).0.last().map_or(0, Block::logical_end)
+            }
+            _ => 0,
+        };
+        Ok(Self::new(file, compression, cipher, logical))
+    }
+
+    /// Create (or truncate) `path`.
+    pub(crate) fn create(
+        path: &Path,
+        compression: Compression,
+        cipher: Option<&Arc<Cipher>>,
+    ) -> Result<Self> {
+        Ok(Self::new(File::create(path)?, compression, cipher, 0))
+    }
+
+    fn new(
+        file: File,
+        compression: Compression,
+        cipher: Option<&Arc<Cipher>>,
+        logical: u64,
+    ) -> Self {
+        #[cfg(all(target_os = "linux", feature = "vectored-io"))]
+        if compression == Compression::None {
+            return DataWriter::Vectored(VectoredWriter::new(file));
+        }
+        let file = BufWriter::with_capacity(256 * 1024, file); // 256KB buffer
+        match compression {
+            Compression::None => DataWriter::Plain(file),
+            Compression::Zstd => DataWriter::Blocks(BlockWriter {
+                file,
+                buf: Vec::with_capacity(BLOCK_SIZE * 2),
+                cipher: cipher.cloned(),
+                logical,
+            }),
+        }
+    }
+
+    /// fsync written data (call after `flush`).
+    pub(crate) fn sync_data(&self) -> std::io::Result<()> {
+        match self {
+            DataWriter::Plain(file) => file.get_ref().sync_data(),
+            #[cfg(all(target_os = "linux", feature = "vectored-io"))]
+            DataWriter::Vectored(writer) => writer.get_ref().sync_data(),
+            DataWriter::Blocks(writer) => writer.file.get_ref().sync_data(),
+        }
+    }
+}
+
+impl Write for DataWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        match self {
+            DataWriter::Plain(file) => file.write(data),
+            #[cfg(all(target_os = "linux", feature = "vectored-io"))]
+            DataWriter::Vectored(writer) => writer.write(data),
+            DataWriter::Blocks(writer) => writer.write(data),
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            DataWriter::Plain(file) => file.write_all(data),
+            #[cfg(all(target_os = "linux", feature = "vectored-io"))]
+            DataWriter::Vectored(writer) => writer.write_all(data),
+            DataWriter::Blocks(writer) => writer.write_all(data),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            DataWriter::Plain(file) => file.flush(),
+            #[cfg(all(target_os = "linux", feature = "vectored-io"))]
+            DataWriter::Vectored(writer) => writer.flush(),
+            DataWriter::Blocks(writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_block_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("data.jsonl.zst");
+        let lines: Vec<String> = (0..5000)
+            .map(|i| format!(r#"{{"_id":"doc{}","text":"line number {}"}}"#, i, i))
+            .collect();
+
+        let mut writer = DataWriter::create(&path, Compression::Zstd, None).unwrap();
+        for line in &lines {
+            writer.write_all(line.as_bytes()).unwrap();
+            writer.write_all(b"\n").unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let expected = lines.join("\n") + "\n";
+        let view = DataView::open(&path, Compression::Zstd, None, &MmapHints::default())
+            .unwrap()
+            .unwrap();
+        let DataView::Blocks { blocks, .. } = &view else {
+            panic!("expected a block view");
+        };
+        assert!(blocks.len() > 1);
+        assert!(std::fs::metadata(&path).unwrap().len() < expected.len() as u64 / 4);
+        assert_eq!(view.len(), expected.len() as u64);
+        assert_eq!(view.bytes().unwrap(), expected.as_bytes());
+
+        // The file is a plain zstd stream
+        let decoded = zstd::stream::decode_all(File::open(&path).unwrap()).unwrap();
+        assert_eq!(decoded, expected.as_bytes());
+
+        // Point reads decode only the block holding the line
+        let offset = expected.find(r#"{"_id":"doc4321""#).unwrap() as u64;
+        let mut cache = BlockCache::default();
+        let line = view
+            .read(offset, lines[4321].len() as u32, &mut cache)
+            .unwrap();
+        assert_eq!(line, Some(lines[4321].as_bytes()));
+
+        // A torn trailing block is dropped
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+        let (recovered, encrypted) = recover(&path, OpenMode::ReadWrite).unwrap();
+        assert_eq!(encrypted, Some(false));
+        assert!(recovered < expected.len() as u64);
+        assert_eq!(
+            DataView::open(&path, Compression::Zstd, None, &MmapHints::default())
+                .unwrap()
+                .unwrap()
+                .len(),
+            recovered
+        );
+    }
+
+    #[test]
+    fn test_encrypted_blocks() {
+        use crate::encryption::EncryptionKey;
+
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("data.jsonl.zst");
+        let cipher = Arc::new(Cipher::new(&EncryptionKey::generate()));
+        let lines: Vec<String> = (0..3000)
+            .map(|i| format!(r#"{{"_id":"doc{}","secret":"value {}"}}"#, i, i))
+            .collect();
+
+        let mut writer = DataWriter::create(&path, Compression::Zstd, Some(&cipher)).unwrap();
+        for line in &lines {
+            writer.write_all(line.as_bytes()).unwrap();
+            writer.write_all(b"\n").unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        // Still a zstd stream, of skippable frames only
+        let decoded = zstd::stream::decode_all(File::open(&path).unwrap()).unwrap();
+        assert!(decoded.is_empty());
+
+        let expected = lines.join("\n") + "\n";
+        let view = DataView::open(
+            &path,
+            Compression::Zstd,
+            Some(&cipher),
+            &MmapHints::default(),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(view.encrypted());
+        assert_eq!(view.bytes().unwrap(), expected.as_bytes());
+        assert_eq!(
+            recover(&path, OpenMode::Read).unwrap(),
+            (expected.len() as u64, Some(true))
+        );
+
+        // Without the key, the layout is readable but the data isn't
+        let locked = DataView::open(&path, Compression::Zstd, None, &MmapHints::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(locked.len(), expected.len() as u64);
+        assert!(matches!(locked.bytes(), Err(Error::Encryption(_))));
+    }
+
+    #[test]
+    fn test_encrypted_block_tampering() {
+        use crate::encryption::EncryptionKey;
+
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("data.jsonl.zst");
+        let cipher = Arc::new(Cipher::new(&EncryptionKey::generate()));
+        let open = |path: &Path, cipher: Option<&Arc<Cipher>>| {
+            DataView::open(path, Compression::Zstd, cipher, &MmapHints::default())
+                .unwrap()
+                .unwrap()
+                .bytes()
+                .map(|bytes| bytes.into_owned())
+        };
+        let line = |i: usize| format!("{{\"_id\":\"doc{}\",\"pad\":\"{:0>200}\"}}\n", i, i);
+
+        // Appends continue the offsets sealed into the blocks
+        let mut expected = String::new();
+        for part in 0..2 {
+            let mut writer = DataWriter::append(&path, Compression::Zstd, Some(&cipher)).unwrap();
+            for i in part * 1000..(part + 1) * 1000 {
+                expected.push_str(&line(i));
+                writer.write_all(line(i).as_bytes()).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        assert_eq!(open(&path, Some(&cipher)).unwrap(), expected.as_bytes());
+
+        // Swapping two sealed blocks fails to authenticate
+        let data = std::fs::read(&path).unwrap();
+        let (blocks, _) = read_blocks(&data);
+        assert!(blocks.len() > 2);
+        let frame =
+            |b: &Block| &data[b.physical - HEADER_LEN..b.physical + b.compressed_len as usize];
+        let mut swapped = frame(&blocks[1]).to_vec();
+        swapped.extend_from_slice(frame(&blocks[0]));
+        for block in &blocks[2..] {
+            swapped.extend_from_slice(frame(block));
+        }
+        let swapped_path = tmp.path().join("swapped.jsonl.zst");
+        std::fs::write(&swapped_path, swapped).unwrap();
+        assert!(matches!(
+            open(&swapped_path, Some(&cipher)),
+            Err(Error::Encryption(_))
+        ));
+
+        // Blocks in the clear aren't read with a key
+        let plain_path = tmp.path().join("plain.jsonl.zst");
+        let mut writer = DataWriter::create(&plain_path, Compression::Zstd, None).unwrap();
+        writer.write_all(expected.as_bytes()).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert!(matches!(
+            open(&plain_path, Some(&cipher)),
+            Err(Error::Encryption(_))
+        ));
+        assert_eq!(open(&plain_path, None).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_compression_manifest() {
+        let tmp = TempDir::new().unwrap();
+        assert_eq!(
+            Compression::load(tmp.path(), "c").unwrap(),
+            Compression::None
+        );
+        Compression::Zstd.save(tmp.path(), "c").unwrap();
+        assert_eq!(
+            Compression::load(tmp.path(), "c").unwrap(),
+            Compression::Zstd
+        );
+        assert!("gzip".parse::<Compression>().is_err());
+    }
+}
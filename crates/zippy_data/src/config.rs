@@ -0,0 +1,302 @@
+//! Collection options stored in the manifest.
+//!
+//! [`CollectionConfig`] gathers the manifest fields that change how a
+//! collection is opened: where documents live, the data segment format,
+//! strict schemas, the shard count of parallel ingestion, the dead-byte
+//! ratio at which fast stores compact themselves and whether document IDs
+//! are kept sorted. Fields a manifest lacks take their defaults.
+
+use std::{path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    engine::StorageMode, fast_writer::CompactionPolicy, Compression, Error, Layout, Result,
+};
+
+/// How stores keep a collection's document IDs in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyOrder {
+    /// In a hash index only; the sorted IDs of prefix and range scans are
+    /// built on the first one
+    #[default]
+    Hashed,
+    /// Also sorted, from the moment the collection is opened; for
+    /// collections read mostly by ID prefix or range
+    Sorted,
+}
+
+impl KeyOrder {
+    /// Name as stored in the manifest.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyOrder::Hashed => "hashed",
+            KeyOrder::Sorted => "sorted",
+        }
+    }
+}
+
+impl FromStr for KeyOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hashed" => Ok(KeyOrder::Hashed),
+            "sorted" => Ok(KeyOrder::Sorted),
+            other => Err(Error::Validation(format!(
+                "unknown key order '{}' (expected hashed or sorted)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Options of a collection, as stored in its `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CollectionConfig {
+    /// Document storage; `None` until set or a store writes (see
+    /// [`StorageMode::detect`])
+    pub storage: Option<StorageMode>,
+    /// Data segment format of fast stores
+    pub compression: Compression,
+    /// Whether every document must have the first document's schema
+    pub strict: bool,
+    /// Shards used for parallel ingestion when none are given
+    pub shards: Option<usize>,
+    /// Dead-byte ratio at which fast stores compact on flush (`None`
+    /// leaves automatic compaction off)
+    pub auto_compact: Option<f64>,
+    /// Whether stores keep the document IDs sorted
+    pub key_order: KeyOrder,
+}
+
+impl CollectionConfig {
+    /// Read a collection's options from its manifest.
+    ///
+    /// A missing manifest means the defaults.
+    pub fn load(root: &Path, collection: &str) -> Result<Self> {
+        let path = Layout::manifest_file(root, collection);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let config = CollectionConfig {
+            storage: manifest
+                .get("storage")
+                .and_then(Value::as_str)
+                .map(str::parse)
+                .transpose()?,
+            compression: manifest
+                .get("compression")
+                .and_then(Value::as_str)
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            strict: manifest
+                .get("strict")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            shards: manifest
+                .get("shards")
+                .and_then(Value::as_u64)
+                .map(|n| n as usize),
+            auto_compact: manifest.get("auto_compact").and_then(Value::as_f64),
+            key_order: manifest
+                .get("key_order")
+                .and_then(Value::as_str)
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Store these options in a collection's manifest, keeping other
+    /// fields.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        self.validate()?;
+        Self::update(root, collection, |obj| self.apply(obj))
+    }
+
+    /// Apply `change` to the fields of a collection's manifest, keeping
+    /// the others, and save it.
+    ///
+    /// The manifest goes to a temp file renamed over it, so readers never
+    /// see a partial one.
+    pub(crate) fn update(
+        root: &Path,
+        collection: &str,
+        change: impl FnOnce(&mut Map<String, Value>),
+    ) -> Result<()> {
+        let path = Layout::manifest_file(root, collection);
+        let mut manifest = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            std::fs::create_dir_all(Layout::meta_dir(root, collection))?;
+            Map::new()
+        };
+        change(&mut manifest);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&manifest)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Set the manifest fields of these options.
+    fn apply(&self, obj: &mut Map<String, Value>) {
+        match self.storage {
+            Some(storage) => obj.insert("storage".to_string(), Value::from(storage.as_str())),
+            None => obj.remove("storage"),
+        };
+        obj.insert(
+            "compression".to_string(),
+            Value::from(self.compression.as_str()),
+        );
+        obj.insert("strict".to_string(), Value::from(self.strict));
+        match self.shards {
+            Some(shards) => obj.insert("shards".to_string(), Value::from(shards)),
+            None => obj.remove("shards"),
+        };
+        match self.auto_compact {
+            Some(ratio) => obj.insert("auto_compact".to_string(), Value::from(ratio)),
+            None => obj.remove("auto_compact"),
+        };
+        obj.insert(
+            "key_order".to_string(),
+            Value::from(self.key_order.as_str()),
+        );
+    }
+
+    /// Check the options are usable.
+    pub fn validate(&self) -> Result<()> {
+        if self.shards == Some(0) {
+            return Err(Error::Validation(
+                "shard count must be at least 1".to_string(),
+            ));
+        }
+        if let Some(ratio) = self.auto_compact {
+            if !(ratio > 0.0 && ratio <= 1.0) {
+                return Err(Error::Validation(format!(
+                    "auto-compact threshold must be in (0, 1], got {}",
+                    ratio
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The automatic compaction policy of fast stores, if enabled.
+    pub fn compaction_policy(&self) -> Option<CompactionPolicy> {
+        self.auto_compact.map(CompactionPolicy::with_ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{FastStore, OpenMode};
+
+    #[test]
+    fn test_collection_config() {
+        let tmp = TempDir::new().unwrap();
+        assert_eq!(
+            CollectionConfig::load(tmp.path(), "test").unwrap(),
+            CollectionConfig::default()
+        );
+
+        let config = CollectionConfig {
+            storage: Some(StorageMode::Jsonl),
+            compression: Compression::Zstd,
+            strict: true,
+            shards: Some(4),
+            auto_compact: Some(0.5),
+            key_order: KeyOrder::Sorted,
+        };
+        config.save(tmp.path(), "test").unwrap();
+        assert_eq!(CollectionConfig::load(tmp.path(), "test").unwrap(), config);
+        assert_eq!(
+            config.compaction_policy(),
+            Some(CompactionPolicy::with_ratio(0.5))
+        );
+
+        // Other manifest fields are kept; unset options are removed
+        Compression::None.save(tmp.path(), "test").unwrap();
+        let config = CollectionConfig {
+            shards: None,
+            ..CollectionConfig::load(tmp.path(), "test").unwrap()
+        };
+        config.save(tmp.path(), "test").unwrap();
+        let loaded = CollectionConfig::load(tmp.path(), "test").unwrap();
+        assert_eq!(loaded.compression, Compression::None);
+        assert_eq!(loaded.shards, None);
+        assert!(loaded.strict);
+
+        let invalid = CollectionConfig {
+            auto_compact: Some(1.5),
+            ..Default::default()
+        };
+        assert!(invalid.save(tmp.path(), "test").is_err());
+        assert!(CollectionConfig {
+            shards: Some(0),
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_manifest_update() {
+        let tmp = TempDir::new().unwrap();
+        CollectionConfig::update(tmp.path(), "test", |obj| {
+            obj.insert("note".to_string(), Value::from("kept"));
+        })
+        .unwrap();
+        StorageMode::Jsonl.save(tmp.path(), "test").unwrap();
+        crate::dedup::Dedup::save_enabled(tmp.path(), "test", true).unwrap();
+
+        let path = Layout::manifest_file(tmp.path(), "test");
+        let manifest: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(manifest["note"], "kept");
+        assert_eq!(manifest["storage"], "jsonl");
+        assert_eq!(manifest["dedup"], true);
+        // Written through a temp file renamed over the manifest
+        assert!(!path.with_extension("json.tmp").exists());
+
+        // A manifest that isn't an object is left alone
+        std::fs::write(&path, "[]").unwrap();
+        assert!(Compression::Zstd.save(tmp.path(), "test").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_config_respected_on_open() {
+        let tmp = TempDir::new().unwrap();
+        let config = CollectionConfig {
+            compression: Compression::Zstd,
+            auto_compact: Some(0.4),
+            ..Default::default()
+        };
+        config.save(tmp.path(), "fast").unwrap();
+        let store = FastStore::open(tmp.path(), "fast", 100).unwrap();
+        assert_eq!(store.compression(), Compression::Zstd);
+        assert_eq!(
+            store.compaction_policy(),
+            Some(CompactionPolicy::with_ratio(0.4))
+        );
+        assert_eq!(store.config().unwrap().storage, Some(StorageMode::Jsonl));
+
+        let files = CollectionConfig {
+            storage: Some(StorageMode::Files),
+            ..Default::default()
+        };
+        files.save(tmp.path(), "files").unwrap();
+        assert!(FastStore::open(tmp.path(), "files", 100).is_err());
+        assert!(FastStore::open_with_mode(tmp.path(), "files", 100, OpenMode::Read).is_ok());
+    }
+}
@@ -1,6 +1,11 @@
 //! Container abstraction for folder and archive access.
 
-use std::path::{Path, PathBuf};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use parking_lot::Mutex;
 
 use crate::{Error, Layout, Result};
 
@@ -9,15 +14,58 @@ use crate::{Error, Layout, Result};
 pub enum ContainerFS {
     /// Folder-based container (read/write)
     Folder(PathBuf),
-    /// ZIP archive container (read-only in v0.1)
+    /// ZIP archive container (read-only in v0.1), local or, with the
+    /// `remote` feature, at a URL
     Zip(PathBuf),
+    /// Folder container in S3 or GCS (read-only)
+    #[cfg(feature = "remote")]
+    Remote(crate::remote::RemoteStore),
+}
+
+/// URL schemes of remote containers.
+const REMOTE_SCHEMES: [&str; 4] = ["s3://", "gs://", "http://", "https://"];
+
+/// Check if a path is a URL (`s3://`, `gs://`, `http://` or `https://`)
+/// rather than a local path.
+pub fn is_remote_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|p| REMOTE_SCHEMES.iter().any(|scheme| p.starts_with(scheme)))
+}
+
+fn is_archive_path(path: &Path) -> bool {
+    path.extension().map(|e| e == "zds").unwrap_or(false)
 }
 
 impl ContainerFS {
     /// Open a container from a path.
+    ///
+    /// With the `remote` feature, the path can also be the URL of a folder
+    /// container in S3 or GCS, or of a `.zds` archive in S3, GCS or on an
+    /// HTTP server (read with range requests).
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
 
+        if is_remote_url(path) {
+            #[cfg(feature = "remote")]
+            return if is_archive_path(path) {
+                Ok(ContainerFS::Zip(path.to_path_buf()))
+            } else if path.to_string_lossy().starts_with("http") {
+                Err(Error::InvalidContainer(format!(
+                    "HTTP URLs must point to a .zds archive: {}",
+                    path.display()
+                )))
+            } else {
+                Ok(ContainerFS::Remote(crate::remote::RemoteStore::open(
+                    &path.to_string_lossy(),
+                )?))
+            };
+            #[cfg(not(feature = "remote"))]
+            return Err(Error::InvalidContainer(format!(
+                "{} is a remote URL; remote containers need the `remote` feature",
+                path.display()
+            )));
+        }
+
         if !path.exists() {
             return Err(Error::InvalidContainer(format!(
                 "Path does not exist: {}",
@@ -27,7 +75,7 @@ impl ContainerFS {
 
         if path.is_dir() {
             Ok(ContainerFS::Folder(path.to_path_buf()))
-        } else if path.is_file() && path.extension().map(|e| e == "zds").unwrap_or(false) {
+        } else if path.is_file() && is_archive_path(path) {
             Ok(ContainerFS::Zip(path.to_path_buf()))
         } else {
             Err(Error::InvalidContainer(format!(
@@ -49,6 +97,8 @@ impl ContainerFS {
         match self {
             ContainerFS::Folder(p) => p,
             ContainerFS::Zip(p) => p,
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(store) => store.url(),
         }
     }
 
@@ -74,15 +124,11 @@ impl ContainerFS {
                 Ok(collections)
             }
             ContainerFS::Zip(path) => {
-                let file = std::fs::File::open(path)?;
-                let archive = zip::ZipArchive::new(file)
-                    .map_err(|e| Error::Archive(format!("Failed to open archive: {}", e)))?;
-
                 let mut collections = std::collections::HashSet::new();
-                for name in archive.file_names() {
+                for name in ZipReader::open(path)?.file_names() {
                     // Parse paths like "collections/train/docs/..."
                     let parts: Vec<&str> = name.split('/').collect();
-                    if parts.len() >= 2 && parts[0] == "collections" {
+                    if parts.len() >= 2 && parts[0] == "collections" && !parts[1].is_empty() {
                         collections.insert(parts[1].to_string());
                     }
                 }
@@ -91,6 +137,13 @@ impl ContainerFS {
                 result.sort();
                 Ok(result)
             }
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(store) => {
+                let mut collections =
+                    store.list_subdirs(&Layout::collections_dir(Path::new("")))?;
+                collections.sort();
+                Ok(collections)
+            }
         }
     }
 
@@ -104,6 +157,15 @@ impl ContainerFS {
         matches!(self, ContainerFS::Zip(_))
     }
 
+    /// Check if container is in object storage.
+    pub fn is_remote(&self) -> bool {
+        match self {
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(_) => true,
+            _ => false,
+        }
+    }
+
     /// Check if container is writable.
     pub fn is_writable(&self) -> bool {
         self.is_folder()
@@ -117,19 +179,10 @@ impl ContainerFS {
                 Ok(std::fs::read(&path)?)
             }
             ContainerFS::Zip(archive_path) => {
-                let file = std::fs::File::open(archive_path)?;
-                let mut archive = zip::ZipArchive::new(file)
-                    .map_err(|e| Error::Archive(format!("Failed to open archive: {}", e)))?;
-
-                let path_str = relative_path.to_string_lossy();
-                let mut entry = archive.by_name(&path_str).map_err(|e| {
-                    Error::Archive(format!("File not found in archive: {} ({})", path_str, e))
-                })?;
-
-                let mut buffer = Vec::new();
-                std::io::Read::read_to_end(&mut entry, &mut buffer)?;
-                Ok(buffer)
+                ZipReader::open(archive_path)?.read_file(relative_path)
             }
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(store) => store.read_file(relative_path),
         }
     }
 
@@ -153,6 +206,10 @@ impl ContainerFS {
             ContainerFS::Zip(_) => Err(Error::InvalidContainer(
                 "Cannot write to archive container".to_string(),
             )),
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(_) => Err(Error::InvalidContainer(
+                "Cannot write to remote container".to_string(),
+            )),
         }
     }
 
@@ -164,75 +221,566 @@ impl ContainerFS {
                 Ok(path.exists())
             }
             ContainerFS::Zip(archive_path) => {
-                let file = std::fs::File::open(archive_path)?;
-                let archive = zip::ZipArchive::new(file)
-                    .map_err(|e| Error::Archive(format!("Failed to open archive: {}", e)))?;
-                let path_str = relative_path.to_string_lossy();
-                let exists = archive.file_names().any(|n| n == path_str.as_ref());
-                Ok(exists)
+                Ok(ZipReader::open(archive_path)?.contains(relative_path))
             }
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(store) => store.exists(relative_path),
+        }
+    }
+}
+
+/// A file listed in a ZIP archive or remote container.
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    /// File name within its directory
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Modification time (Unix seconds, taking the archive's local time as
+    /// UTC)
+    pub mtime: u64,
+}
+
+/// Bytes of an archive: a local file or a remote one read with range
+/// requests.
+trait ArchiveSource: std::io::Read + std::io::Seek + Send {}
+
+impl<T: std::io::Read + std::io::Seek + Send> ArchiveSource for T {}
+
+/// An open ZIP archive for repeated reads.
+///
+/// The central directory is read once on open, so reading many files
+/// doesn't re-open the archive; file contents are only decompressed when
+/// read.
+pub struct ZipReader {
+    archive: Mutex<zip::ZipArchive<Box<dyn ArchiveSource>>>,
+}
+
+impl ZipReader {
+    /// Open an archive and read its file listing.
+    ///
+    /// With the `remote` feature, `path` can be a URL: only the central
+    /// directory is fetched on open, and each file with range requests
+    /// when it is read.
+    pub fn open(path: &Path) -> Result<Self> {
+        #[cfg(feature = "remote")]
+        if is_remote_url(path) {
+            let reader = crate::remote::RangeReader::open(&path.to_string_lossy())?;
+            return Self::from_source(Box::new(reader));
+        }
+        Self::from_source(Box::new(File::open(path)?))
+    }
+
+    /// Open an archive in a remote container.
+    #[cfg(feature = "remote")]
+    pub fn from_range_reader(reader: crate::remote::RangeReader) -> Result<Self> {
+        Self::from_source(Box::new(reader))
+    }
+
+    fn from_source(source: Box<dyn ArchiveSource>) -> Result<Self> {
+        let archive = zip::ZipArchive::new(source)
+            .map_err(|e| Error::Archive(format!("Failed to open archive: {}", e)))?;
+        Ok(ZipReader {
+            archive: Mutex::new(archive),
+        })
+    }
+
+    /// Names of all entries, directories included.
+    pub fn file_names(&self) -> Vec<String> {
+        let archive = self.archive.lock();
+        archive.file_names().map(str::to_string).collect()
+    }
+
+    /// Check if the archive holds a file.
+    pub fn contains(&self, relative_path: &Path) -> bool {
+        let path_str = relative_path.to_string_lossy();
+        let archive = self.archive.lock();
+        let exists = archive.file_names().any(|n| n == path_str.as_ref());
+        exists
+    }
+
+    /// Read a file, or `None` if the archive doesn't hold it.
+    pub fn read(&self, relative_path: &Path) -> Result<Option<Vec<u8>>> {
+        let path_str = relative_path.to_string_lossy();
+        let mut archive = self.archive.lock();
+        let mut entry = match archive.by_name(&path_str) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => {
+                return Err(Error::Archive(format!(
+                    "Failed to read {} from archive: {}",
+                    path_str, e
+                )))
+            }
+        };
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        std::io::Read::read_to_end(&mut entry, &mut buffer)?;
+        Ok(Some(buffer))
+    }
+
+    /// Read a file, failing if the archive doesn't hold it.
+    pub fn read_file(&self, relative_path: &Path) -> Result<Vec<u8>> {
+        self.read(relative_path)?.ok_or_else(|| {
+            Error::Archive(format!(
+                "File not found in archive: {}",
+                relative_path.to_string_lossy()
+            ))
+        })
+    }
+
+    /// Files directly inside a directory, without reading them.
+    pub fn list_dir(&self, dir: &Path) -> Result<Vec<ZipEntry>> {
+        let prefix = format!("{}/", dir.to_string_lossy().trim_end_matches('/'));
+        let mut archive = self.archive.lock();
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive
+                .by_index_raw(i)
+                .map_err(|e| Error::Archive(format!("Failed to read entry: {}", e)))?;
+            let Some(name) = entry.name().strip_prefix(&prefix) else {
+                continue;
+            };
+            if entry.is_dir() || name.is_empty() || name.contains('/') {
+                continue;
+            }
+            let modified = entry.last_modified();
+            let mtime = chrono::NaiveDate::from_ymd_opt(
+                modified.year().into(),
+                modified.month().into(),
+                modified.day().into(),
+            )
+            .and_then(|date| {
+                date.and_hms_opt(
+                    modified.hour().into(),
+                    modified.minute().into(),
+                    modified.second().into(),
+                )
+            })
+            .map_or(0, |time| time.and_utc().timestamp().max(0) as u64);
+            entries.push(ZipEntry {
+                name: name.to_string(),
+                size: entry.size(),
+                mtime,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Compression of the files in a `.zds` archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchiveCompression {
+    /// No compression, for the fastest reads
+    Stored,
+    /// DEFLATE, readable by any ZIP tool
+    #[default]
+    Deflate,
+}
+
+impl ArchiveCompression {
+    /// Name as accepted by [`FromStr`](std::str::FromStr).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveCompression::Stored => "stored",
+            ArchiveCompression::Deflate => "deflate",
+        }
+    }
+}
+
+impl std::str::FromStr for ArchiveCompression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stored" | "none" => Ok(ArchiveCompression::Stored),
+            "deflate" => Ok(ArchiveCompression::Deflate),
+            other => Err(Error::Validation(format!(
+                "unknown archive compression '{}' (expected stored or deflate)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Options for [`pack_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PackOptions {
+    /// How files are compressed; zstd data segments are always stored as
+    /// they are
+    pub compression: ArchiveCompression,
+    /// DEFLATE level (0-9), or `None` for the default (6)
+    pub level: Option<i32>,
+}
+
+/// Progress of [`pack_with`] or [`unpack_with`], reported after every chunk
+/// copied and every file finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackProgress {
+    /// Path of the current file, relative to the store root
+    pub path: String,
+    /// Files finished so far
+    pub files_done: usize,
+    pub files_total: usize,
+    /// Uncompressed bytes copied so far
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+/// Size of the chunks files are copied in.
+const COPY_CHUNK: usize = 1024 * 1024;
+
+/// Whether a store file is left out of archives: journals, lock files and
+/// temporary files only matter to a running writer.
+fn skip_when_packing(name: &str) -> bool {
+    name == Layout::JOURNAL_FILE || name.ends_with(".lock") || name.ends_with(".tmp")
+}
+
+/// Copy `reader` into `writer` in chunks, reporting each one.
+fn copy_with_progress(
+    reader: &mut impl std::io::Read,
+    writer: &mut impl std::io::Write,
+    progress: &mut PackProgress,
+    report: &mut impl FnMut(&PackProgress),
+) -> Result<()> {
+    let mut buffer = vec![0u8; COPY_CHUNK];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        writer.write_all(&buffer[..read])?;
+        progress.bytes_done += read as u64;
+        report(progress);
+    }
+}
+
+/// What [`pack_incremental`] did with each file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IncrementalReport {
+    /// Files not in the previous archive
+    pub added: usize,
+    /// Files that changed since the previous archive
+    pub updated: usize,
+    /// Files copied over from the previous archive without recompressing
+    pub unchanged: usize,
+    /// Files in the previous archive that are gone from the store
+    pub removed: usize,
+}
+
+/// Archive timestamp for a file's modification time, taken as UTC like
+/// [`ZipReader::list_dir`] reads it back.
+fn zip_mtime(metadata: &std::fs::Metadata) -> zip::DateTime {
+    use chrono::{Datelike, Timelike};
+
+    let Ok(modified) = metadata.modified() else {
+        return zip::DateTime::default();
+    };
+    let time = chrono::DateTime::<chrono::Utc>::from(modified);
+    zip::DateTime::from_date_and_time(
+        time.year().clamp(0, u16::MAX as i32) as u16,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second().min(59) as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// CRC-32 of a file, as ZIP entries record it.
+fn file_crc32(path: &Path) -> Result<u32> {
+    let mut file = File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0u8; COPY_CHUNK];
+    loop {
+        match std::io::Read::read(&mut file, &mut buffer) {
+            Ok(0) => return Ok(hasher.finalize()),
+            Ok(read) => hasher.update(&buffer[..read]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
         }
     }
 }
 
 /// Pack a folder container into a .zds archive.
 pub fn pack(source: &Path, dest: &Path) -> Result<()> {
-    use std::io::Write;
+    pack_with(source, dest, &PackOptions::default(), |_| {})
+}
 
-    use zip::write::FileOptions;
+/// Pack a folder container into a .zds archive, streaming each file and
+/// calling `progress` as it goes.
+///
+/// Files are added in path order. Journals, lock files and temporary files
+/// are skipped, as is the archive itself if it lies inside `source`.
+pub fn pack_with(
+    source: &Path,
+    dest: &Path,
+    options: &PackOptions,
+    progress: impl FnMut(&PackProgress),
+) -> Result<()> {
+    let file = File::create(dest)?;
+    let skip = [dest.canonicalize()?];
+    write_archive(source, file, &skip, options, None, progress)?;
+    Ok(())
+}
 
-    let file = std::fs::File::create(dest)?;
-    let mut archive = zip::ZipWriter::new(file);
-    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+/// Repack a folder container over an existing .zds archive, reusing the
+/// entries of files that haven't changed.
+///
+/// A file is unchanged if its size matches the archived entry and either
+/// its modification time or its CRC-32 does too; its compressed bytes are
+/// then copied over as they are. Everything else is compressed afresh, and
+/// entries for files that are gone are dropped. The new archive replaces
+/// the old one only once it's complete. If `archive` doesn't exist yet,
+/// every file is added.
+pub fn pack_incremental(source: &Path, archive: &Path) -> Result<IncrementalReport> {
+    pack_incremental_with(source, archive, &PackOptions::default(), |_| {})
+}
+
+/// [`pack_incremental`] with compression options and progress reports.
+///
+/// Reused entries keep the compression they were written with, unless
+/// `options` asks for a different method.
+pub fn pack_incremental_with(
+    source: &Path,
+    archive: &Path,
+    options: &PackOptions,
+    progress: impl FnMut(&PackProgress),
+) -> Result<IncrementalReport> {
+    let mut previous = if archive.exists() {
+        Some(
+            zip::ZipArchive::new(File::open(archive)?)
+                .map_err(|e| Error::Archive(format!("Failed to open archive: {}", e)))?,
+        )
+    } else {
+        None
+    };
+
+    let tmp_path = archive.with_extension("zds.tmp");
+    let file = File::create(&tmp_path)?;
+    let mut skip = vec![tmp_path.canonicalize()?];
+    if previous.is_some() {
+        skip.push(archive.canonicalize()?);
+    }
+    let report = write_archive(source, file, &skip, options, previous.as_mut(), progress);
+    match report {
+        Ok(report) => {
+            std::fs::rename(&tmp_path, archive)?;
+            Ok(report)
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
 
-    fn add_dir(
-        archive: &mut zip::ZipWriter<std::fs::File>,
-        base: &Path,
-        current: &Path,
-        options: FileOptions,
-    ) -> Result<()> {
-        for entry in std::fs::read_dir(current)? {
+/// Write the files under `source` into a new archive, copying unchanged
+/// entries from `previous` when given.
+fn write_archive(
+    source: &Path,
+    file: File,
+    skip: &[PathBuf],
+    options: &PackOptions,
+    mut previous: Option<&mut zip::ZipArchive<File>>,
+    mut progress: impl FnMut(&PackProgress),
+) -> Result<IncrementalReport> {
+    use zip::{write::FileOptions, CompressionMethod};
+
+    // Collect the tree first so progress has totals
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    let mut pending = vec![source.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
             let entry = entry?;
             let path = entry.path();
-            let relative = path.strip_prefix(base).unwrap();
-            let name = relative.to_string_lossy();
-
-            if path.is_dir() {
-                archive
-                    .add_directory(format!("{}/", name), options)
-                    .map_err(|e| Error::Archive(format!("Failed to add directory: {}", e)))?;
-                add_dir(archive, base, &path, options)?;
-            } else {
-                archive
-                    .start_file(name.to_string(), options)
-                    .map_err(|e| Error::Archive(format!("Failed to start file: {}", e)))?;
-                let data = std::fs::read(&path)?;
-                archive.write_all(&data)?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_dir() {
+                dirs.push(path.clone());
+                pending.push(path);
+            } else if !name.to_str().is_some_and(skip_when_packing)
+                && !skip.contains(&path.canonicalize()?)
+            {
+                let metadata = entry.metadata()?;
+                files.push((path, metadata));
+            }
+        }
+    }
+    dirs.sort();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let relative = |path: &Path| {
+        path.strip_prefix(source)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+
+    let mut archive = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    let method = match options.compression {
+        ArchiveCompression::Stored => CompressionMethod::Stored,
+        ArchiveCompression::Deflate => CompressionMethod::Deflated,
+    };
+    let deflate = FileOptions::default()
+        .compression_method(method)
+        .compression_level(options.level)
+        .large_file(true);
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    for dir in &dirs {
+        archive
+            .add_directory(format!("{}/", relative(dir)), stored)
+            .map_err(|e| Error::Archive(format!("Failed to add directory: {}", e)))?;
+    }
+
+    let mut report = IncrementalReport::default();
+    let mut state = PackProgress {
+        path: String::new(),
+        files_done: 0,
+        files_total: files.len(),
+        bytes_done: 0,
+        bytes_total: files.iter().map(|(_, metadata)| metadata.len()).sum(),
+    };
+    for (path, metadata) in &files {
+        let name = relative(path);
+        let mtime = zip_mtime(metadata);
+        // Compressing zstd data again gains nothing
+        let file_options = if name.ends_with(".zst") {
+            stored
+        } else {
+            deflate
+        }
+        .last_modified_time(mtime);
+        state.path = name.clone();
+
+        if let Some(previous) = previous.as_deref_mut() {
+            match previous.by_name(&name) {
+                Ok(entry) => {
+                    let old = entry.last_modified();
+                    let expected = if name.ends_with(".zst") {
+                        CompressionMethod::Stored
+                    } else {
+                        method
+                    };
+                    let unchanged = entry.is_file()
+                        && entry.size() == metadata.len()
+                        && entry.compression() == expected
+                        && ((old.datepart(), old.timepart())
+                            == (mtime.datepart(), mtime.timepart())
+                            || entry.crc32() == file_crc32(path)?);
+                    if unchanged {
+                        archive
+                            .raw_copy_file(entry)
+                            .map_err(|e| Error::Archive(format!("Failed to copy entry: {}", e)))?;
+                        report.unchanged += 1;
+                        state.bytes_done += metadata.len();
+                        state.files_done += 1;
+                        progress(&state);
+                        continue;
+                    }
+                    report.updated += 1;
+                }
+                Err(zip::result::ZipError::FileNotFound) => report.added += 1,
+                Err(e) => {
+                    return Err(Error::Archive(format!(
+                        "Failed to read {} from archive: {}",
+                        name, e
+                    )))
+                }
             }
+        } else {
+            report.added += 1;
         }
-        Ok(())
+
+        archive
+            .start_file(name, file_options)
+            .map_err(|e| Error::Archive(format!("Failed to start file: {}", e)))?;
+        copy_with_progress(
+            &mut File::open(path)?,
+            &mut archive,
+            &mut state,
+            &mut progress,
+        )?;
+        state.files_done += 1;
+        progress(&state);
+    }
+
+    if let Some(previous) = previous {
+        let kept = report.unchanged + report.updated;
+        let archived = previous
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .count();
+        report.removed = archived - kept;
     }
 
-    add_dir(&mut archive, source, source, options)?;
     archive
         .finish()
-        .map_err(|e| Error::Archive(format!("Failed to finish archive: {}", e)))?;
+        .map_err(|e| Error::Archive(format!("Failed to finish archive: {}", e)))?
+        .into_inner()
+        .map_err(|e| Error::Io(e.into_error()))?
+        .sync_all()?;
 
-    Ok(())
+    Ok(report)
 }
 
 /// Unpack a .zds archive into a folder.
 pub fn unpack(source: &Path, dest: &Path) -> Result<()> {
-    let file = std::fs::File::open(source)?;
+    unpack_with(source, dest, |_| {})
+}
+
+/// Unpack a .zds archive into a folder, streaming each file and calling
+/// `progress` as it goes.
+///
+/// Entries whose names would land outside `dest` are rejected.
+pub fn unpack_with(
+    source: &Path,
+    dest: &Path,
+    mut progress: impl FnMut(&PackProgress),
+) -> Result<()> {
+    let file = File::open(source)?;
     let mut archive = zip::ZipArchive::new(file)
         .map_err(|e| Error::Archive(format!("Failed to open archive: {}", e)))?;
 
+    let mut state = PackProgress {
+        path: String::new(),
+        files_done: 0,
+        files_total: 0,
+        bytes_done: 0,
+        bytes_total: 0,
+    };
+    for i in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(i)
+            .map_err(|e| Error::Archive(format!("Failed to read entry: {}", e)))?;
+        if !entry.is_dir() {
+            state.files_total += 1;
+            state.bytes_total += entry.size();
+        }
+    }
+
     for i in 0..archive.len() {
         let mut entry = archive
             .by_index(i)
             .map_err(|e| Error::Archive(format!("Failed to read entry: {}", e)))?;
-
-        let outpath = dest.join(entry.name());
+        let relative = entry
+            .enclosed_name()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| Error::Archive(format!("Unsafe path in archive: {}", entry.name())))?;
+        let outpath = dest.join(relative);
 
         if entry.is_dir() {
             std::fs::create_dir_all(&outpath)?;
@@ -240,8 +788,12 @@ pub fn unpack(source: &Path, dest: &Path) -> Result<()> {
             if let Some(parent) = outpath.parent() {
                 std::fs::create_dir_all(parent)?;
             }
-            let mut outfile = std::fs::File::create(&outpath)?;
-            std::io::copy(&mut entry, &mut outfile)?;
+            let mut outfile = std::io::BufWriter::new(File::create(&outpath)?);
+            state.path = entry.name().to_string();
+            copy_with_progress(&mut entry, &mut outfile, &mut state, &mut progress)?;
+            std::io::Write::flush(&mut outfile)?;
+            state.files_done += 1;
+            progress(&state);
         }
     }
 
@@ -288,4 +840,94 @@ mod tests {
         unpack(&archive, &dest).unwrap();
         assert!(Layout::doc_file(&dest, "train", "doc001").exists());
     }
+
+    #[test]
+    fn test_pack_with_options() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("source");
+        let dest = tmp.path().join("dest");
+        ContainerFS::create_folder(&source).unwrap();
+        Layout::init_collection(&source, "train").unwrap();
+        std::fs::write(Layout::doc_file(&source, "train", "doc001"), "{}").unwrap();
+        std::fs::write(Layout::journal_file(&source, "train"), "{}\n").unwrap();
+        std::fs::write(source.join("index.tmp"), "").unwrap();
+
+        // An archive inside the source isn't packed into itself
+        let archive = source.join("test.zds");
+        let options = PackOptions {
+            compression: ArchiveCompression::Stored,
+            level: None,
+        };
+        let mut reports = Vec::new();
+        pack_with(&source, &archive, &options, |p| reports.push(p.clone())).unwrap();
+        let last = reports.last().unwrap();
+        assert_eq!(last.files_done, last.files_total);
+        assert_eq!(last.bytes_done, last.bytes_total);
+
+        let names = ZipReader::open(&archive).unwrap().file_names();
+        assert!(names.contains(&"collections/train/docs/doc001.json".to_string()));
+        assert!(!names
+            .iter()
+            .any(|n| n.ends_with("journal.log") || n.ends_with(".tmp") || n.ends_with(".zds")));
+
+        let mut done = 0;
+        unpack_with(&archive, &dest, |p| done = p.files_done).unwrap();
+        assert_eq!(done, last.files_total);
+        assert!(Layout::doc_file(&dest, "train", "doc001").exists());
+        assert_eq!(
+            "deflate".parse::<ArchiveCompression>().unwrap(),
+            ArchiveCompression::Deflate
+        );
+    }
+
+    #[test]
+    fn test_pack_incremental() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("source");
+        ContainerFS::create_folder(&source).unwrap();
+        Layout::init_collection(&source, "train").unwrap();
+        std::fs::write(Layout::doc_file(&source, "train", "doc001"), "{\"v\":1}").unwrap();
+        std::fs::write(Layout::doc_file(&source, "train", "doc002"), "{\"v\":2}").unwrap();
+
+        let archive = tmp.path().join("test.zds");
+        let first = pack_incremental(&source, &archive).unwrap();
+        assert_eq!(first.unchanged, 0);
+        assert_eq!(first.removed, 0);
+        let added = first.added;
+
+        std::fs::write(Layout::doc_file(&source, "train", "doc001"), "{\"v\":10}").unwrap();
+        std::fs::remove_file(Layout::doc_file(&source, "train", "doc002")).unwrap();
+        std::fs::write(Layout::doc_file(&source, "train", "doc003"), "{\"v\":3}").unwrap();
+
+        let second = pack_incremental(&source, &archive).unwrap();
+        assert_eq!(second.added, 1);
+        assert_eq!(second.updated, 1);
+        assert_eq!(second.removed, 1);
+        assert_eq!(second.unchanged, added - 2);
+        assert!(!tmp.path().join("test.zds.tmp").exists());
+
+        let reader = ZipReader::open(&archive).unwrap();
+        let doc = |id| reader.read(&Layout::doc_file(Path::new(""), "train", id));
+        assert_eq!(doc("doc001").unwrap().unwrap(), b"{\"v\":10}");
+        assert!(doc("doc002").unwrap().is_none());
+        assert_eq!(doc("doc003").unwrap().unwrap(), b"{\"v\":3}");
+    }
+
+    #[test]
+    fn test_unpack_rejects_unsafe_paths() {
+        use std::io::Write;
+
+        let tmp = TempDir::new().unwrap();
+        let archive = tmp.path().join("evil.zds");
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        writer
+            .start_file("../escaped.json", zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.finish().unwrap();
+
+        let dest = tmp.path().join("dest");
+        assert!(matches!(unpack(&archive, &dest), Err(Error::Archive(_))));
+        assert!(!tmp.path().join("escaped.json").exists());
+    }
 }
@@ -0,0 +1,409 @@
+//! Content-addressed deduplication of identical documents.
+//!
+//! With `"dedup": true` in the manifest, a [`FastStore`](crate::FastStore)
+//! hashes the content of every document it puts (its line without the
+//! `_id`) with blake3. A document whose content matches a line already in
+//! the data segments isn't written again: its index entry points at that
+//! line instead, and reads put the document's own `_id` back.
+//!
+//! Such a reference has no line of its own to be replayed from, so it is
+//! also appended to `meta/dedup.jsonl` on flush, with the index offset at
+//! the time of the write. On open the last record of each document is
+//! applied unless a later line or tombstone superseded it; deleting a
+//! shared document records that it no longer points at the line.
+//! Compaction copies a shared line once and rewrites the file.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::CollectionConfig, fast_writer::IndexEntry, id_map::IdMap, Layout, Result};
+
+/// A line of `dedup.jsonl`.
+#[derive(Debug, Serialize, Deserialize)]
+struct DedupRecord {
+    id: String,
+    /// Index offset at the time of the write
+    at: u64,
+    /// Offset, length and checksum of the shared line; `None` once the
+    /// document no longer points at it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line: Option<(u64, u32, u32)>,
+}
+
+/// Line sharing state of a collection.
+#[derive(Debug, Default)]
+pub(crate) struct Dedup {
+    /// Whether new documents are deduplicated (shared lines are resolved
+    /// either way)
+    enabled: bool,
+    /// Content hash -> a line with that content (built on the first write)
+    lines: Option<FxHashMap<[u8; 32], IndexEntry>>,
+    /// Offset -> number of documents pointing at the line
+    owners: FxHashMap<u64, u32>,
+    /// Documents pointing at a line written for another document
+    refs: FxHashSet<String>,
+    /// Documents beyond the first pointing at a line, and their bytes
+    shared_docs: usize,
+    shared_bytes: u64,
+    /// Records not yet appended to the file
+    pending: Vec<DedupRecord>,
+}
+
+/// Start of a line whose `_id` is its first member.
+const ID_PREFIX: &[u8] = br#"{"_id":"#;
+
+/// The part of a line after its leading `_id` member, if it starts with
+/// one.
+fn after_id(line: &[u8]) -> Option<&[u8]> {
+    let rest = line.strip_prefix(ID_PREFIX)?;
+    let mut stream = serde_json::Deserializer::from_slice(rest).into_iter::<IgnoredAny>();
+    stream.next()?.ok()?;
+    Some(&rest[stream.byte_offset()..])
+}
+
+/// blake3 hash of a line's content: the line after its leading `_id`
+/// member, or the whole line if it doesn't start with one.
+pub(crate) fn content_hash(line: &[u8]) -> [u8; 32] {
+    *blake3::hash(after_id(line).unwrap_or(line)).as_bytes()
+}
+
+/// A line with its `_id` replaced by `doc_id`, keeping the other members
+/// as they are.
+pub(crate) fn with_id(line: &[u8], doc_id: &str) -> Result<Vec<u8>> {
+    let id = serde_json::to_vec(doc_id)?;
+    if let Some(rest) = after_id(line) {
+        return Ok([ID_PREFIX, &id, rest].concat());
+    }
+    let mut doc: Value = serde_json::from_slice(line)?;
+    if let Value::Object(obj) = &mut doc {
+        obj.insert("_id".to_string(), Value::String(doc_id.to_string()));
+    }
+    Ok(serde_json::to_vec(&doc)?)
+}
+
+impl Dedup {
+    /// Read whether a collection deduplicates documents from its manifest.
+    pub(crate) fn load_enabled(root: &Path, collection: &str) -> Result<bool> {
+        let path = Layout::manifest_file(root, collection);
+        if !path.exists() {
+            return Ok(false);
+        }
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        Ok(manifest
+            .get("dedup")
+            .and_then(Value::as_bool)
+            .unwrap_or(false))
+    }
+
+    /// Store whether a collection deduplicates documents in its manifest,
+    /// keeping other fields.
+    pub(crate) fn save_enabled(root: &Path, collection: &str, enabled: bool) -> Result<()> {
+        CollectionConfig::update(root, collection, |obj| {
+            obj.insert("dedup".to_string(), Value::from(enabled));
+        })
+    }
+
+    /// Load the sharing state of a collection and point `index` at the
+    /// shared lines it doesn't have yet, if `holds_line` finds them intact.
+    /// `None` if the collection neither deduplicates nor has shared lines.
+    pub(crate) fn load(
+        root: &Path,
+        collection: &str,
+        index: &mut IdMap<IndexEntry>,
+        tombstones: &FxHashMap<String, u64>,
+        holds_line: impl Fn(&IndexEntry) -> bool,
+    ) -> Result<Option<Self>> {
+        let enabled = Self::load_enabled(root, collection)?;
+        let path = Layout::dedup_file(root, collection);
+        if !enabled && !path.exists() {
+            return Ok(None);
+        }
+        let mut dedup = Dedup {
+            enabled,
+            ..Default::default()
+        };
+
+        // The last record of a document wins
+        let mut last: FxHashMap<String, DedupRecord> = FxHashMap::default();
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            for line in content.split_inclusive('\n') {
+                // A torn last line is ignored
+                if let Ok(record) = serde_json::from_str::<DedupRecord>(line) {
+                    last.insert(record.id.clone(), record);
+                }
+            }
+        }
+        for (id, record) in last {
+            let Some((offset, length, checksum)) = record.line else {
+                continue;
+            };
+            let entry = IndexEntry {
+                offset,
+                length,
+                checksum,
+            };
+            let deleted = tombstones.get(&id).is_some_and(|&t| t > record.at);
+            let written = index.get(&id).is_some_and(|e| e.offset >= record.at);
+            if deleted || written || !holds_line(&entry) {
+                continue;
+            }
+            index.insert(id.clone(), entry);
+            dedup.refs.insert(id);
+        }
+        dedup.recount(index);
+        Ok(Some(dedup))
+    }
+
+    /// Whether new documents are deduplicated.
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Whether any document points at another's line.
+    pub(crate) fn has_refs(&self) -> bool {
+        !self.refs.is_empty()
+    }
+
+    /// Whether a document points at another's line.
+    pub(crate) fn is_ref(&self, doc_id: &str) -> bool {
+        self.refs.contains(doc_id)
+    }
+
+    /// Documents sharing a line with another, and the bytes they save.
+    pub(crate) fn savings(&self) -> (usize, u64) {
+        (self.shared_docs, self.shared_bytes)
+    }
+
+    /// Count the documents pointing at each line of `index`, and forget
+    /// the content hashes (after a compaction moved the lines).
+    pub(crate) fn recount(&mut self, index: &IdMap<IndexEntry>) {
+        self.lines = None;
+        self.owners.clear();
+        self.shared_docs = 0;
+        self.shared_bytes = 0;
+        for entry in index.values() {
+            self.add_owner(entry);
+        }
+        self.refs.retain(|id| index.contains_key(id));
+    }
+
+    fn add_owner(&mut self, entry: &IndexEntry) {
+        let owners = self.owners.entry(entry.offset).or_insert(0);
+        *owners += 1;
+        if *owners > 1 {
+            self.shared_docs += 1;
+            self.shared_bytes += entry.length as u64;
+        }
+    }
+
+    fn remove_owner(&mut self, entry: &IndexEntry) {
+        if let Some(owners) = self.owners.get_mut(&entry.offset) {
+            *owners -= 1;
+            if *owners == 0 {
+                self.owners.remove(&entry.offset);
+            } else {
+                self.shared_docs -= 1;
+                self.shared_bytes -= entry.length as u64;
+            }
+        }
+    }
+
+    /// Whether the content hashes need building (see [`build`](Self::build)).
+    pub(crate) fn needs_lines(&self) -> bool {
+        self.lines.is_none()
+    }
+
+    /// Set the content hashes of the lines documents point at.
+    pub(crate) fn build(&mut self, hashes: Vec<([u8; 32], IndexEntry)>) {
+        let mut lines = FxHashMap::default();
+        for (hash, entry) in hashes {
+            lines.entry(hash).or_insert(entry);
+        }
+        self.lines = Some(lines);
+    }
+
+    /// The line holding content with `hash`, if any.
+    pub(crate) fn find(&self, hash: &[u8; 32]) -> Option<IndexEntry> {
+        self.lines.as_ref()?.get(hash).copied()
+    }
+
+    /// Remember a newly written line's content hash.
+    pub(crate) fn add_line(&mut self, hash: [u8; 32], entry: IndexEntry) {
+        if let Some(lines) = &mut self.lines {
+            lines.insert(hash, entry);
+        }
+    }
+
+    /// A document now points at `new` instead of `old`, with its own line.
+    pub(crate) fn replace(&mut self, doc_id: &str, old: Option<IndexEntry>, new: &IndexEntry) {
+        if let Some(old) = old {
+            self.remove_owner(&old);
+        }
+        self.add_owner(new);
+        self.refs.remove(doc_id);
+    }
+
+    /// A document that points at `entry` was deleted at index offset `at`.
+    pub(crate) fn remove(&mut self, doc_id: &str, entry: Option<IndexEntry>, at: u64) {
+        if let Some(entry) = entry {
+            self.remove_owner(&entry);
+        }
+        if self.refs.remove(doc_id) {
+            self.pending.push(DedupRecord {
+                id: doc_id.to_string(),
+                at,
+                line: None,
+            });
+        }
+    }
+
+    /// Record that a document (already indexed) points at another's line,
+    /// written at index offset `at`.
+    pub(crate) fn share(&mut self, doc_id: &str, entry: &IndexEntry, at: u64) {
+        self.refs.insert(doc_id.to_string());
+        self.pending.push(DedupRecord {
+            id: doc_id.to_string(),
+            at,
+            line: Some((entry.offset, entry.length, entry.checksum)),
+        });
+    }
+
+    /// Append the records written since the last sync.
+    pub(crate) fn sync(&mut self, root: &Path, collection: &str) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Layout::dedup_file(root, collection))?;
+        let mut writer = BufWriter::new(file);
+        for record in self.pending.drain(..) {
+            writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    /// Replace the file with a record per shared document, as of index
+    /// offset `at` (or remove it if none are left).
+    pub(crate) fn save(
+        &mut self,
+        root: &Path,
+        collection: &str,
+        index: &IdMap<IndexEntry>,
+        at: u64,
+    ) -> Result<()> {
+        self.pending.clear();
+        let path = Layout::dedup_file(root, collection);
+        if self.refs.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+        let tmp_file = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_file)?);
+        for id in &self.refs {
+            let Some(entry) = index.get(id) else {
+                continue;
+            };
+            let record = DedupRecord {
+                id: id.clone(),
+                at,
+                line: Some((entry.offset, entry.length, entry.checksum)),
+            };
+            writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        drop(writer);
+        std::fs::rename(&tmp_file, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::{FastStore, Layout};
+
+    #[test]
+    fn test_dedup() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.set_dedup(true).unwrap();
+        store.put("a", json!({"text": "same", "n": 1})).unwrap();
+        store.put("b", json!({"text": "same", "n": 1})).unwrap();
+        store.put("c", json!({"text": "other"})).unwrap();
+        store.put("d", json!({"text": "same", "n": 1})).unwrap();
+        store.flush().unwrap();
+
+        let stats = store.stats();
+        assert_eq!((stats.dedup_docs, stats.doc_count), (2, 4));
+        assert_eq!(stats.dead_bytes, 0);
+        assert_eq!(store.get("b").unwrap(), json!({"text": "same", "n": 1}));
+        assert_eq!(
+            store.raw_line("d").unwrap(),
+            br#"{"_id":"d","n":1,"text":"same"}"#
+        );
+        let mut exported = Vec::new();
+        assert_eq!(store.export_jsonl(&mut exported).unwrap(), 4);
+        let ids: Vec<String> = exported
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| FastStore::extract_id_fast(line).unwrap())
+            .collect();
+        assert_eq!(ids, ["a", "b", "d", "c"]);
+        assert!(store.verify().unwrap().is_ok());
+
+        // Deleting the document that wrote the line keeps the others
+        store.delete("a").unwrap();
+        store.put("b", json!({"text": "changed"})).unwrap();
+        drop(store);
+
+        // References survive an index rebuilt from the data files
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 3);
+        assert!(!store.exists("a"));
+        assert_eq!(store.get("b").unwrap()["text"], "changed");
+        assert_eq!(store.get("d").unwrap()["text"], "same");
+
+        store.compact().unwrap();
+        assert_eq!(store.dead_bytes(), 0);
+        store.put("e", json!({"text": "changed"})).unwrap();
+        store.flush().unwrap();
+        assert_eq!(store.dedup_savings().0, 1);
+        drop(store);
+
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        let mut ids = store.doc_ids();
+        ids.sort();
+        assert_eq!(ids, ["b", "c", "d", "e"]);
+        assert_eq!(store.get("e").unwrap()["text"], "changed");
+        let raw = store.scan_raw().unwrap();
+        assert_eq!(raw[0], br#"{"_id":"d","n":1,"text":"same"}"#);
+        store.delete("d").unwrap();
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert!(!store.exists("d"));
+        assert!(store.dedup_enabled());
+    }
+}
@@ -0,0 +1,234 @@
+//! Borrowed documents for the read path.
+//!
+//! Scans used to copy every line into a fresh buffer and build a full
+//! `serde_json::Value` tree before looking at it. [`DocRef`] instead parses
+//! a line into simd-json's borrowed representation, whose strings point into
+//! the line, using a line buffer and parser buffers reused by each thread.
+//! Predicates are evaluated against an owned copy of only the fields they
+//! reference, and projections copy only the projected fields, so documents
+//! a scan rejects are never materialized.
+
+use std::{borrow::Cow, cell::RefCell};
+
+use serde_json::{Map, Number, Value};
+use simd_json::{BorrowedValue, Buffers, StaticNode};
+
+use crate::{Codec, Error, Predicate, Result};
+
+thread_local! {
+    /// Line buffer and parser buffers of the thread's scans
+    static SCRATCH: RefCell<(Vec<u8>, Buffers)> = RefCell::new((Vec::new(), Buffers::default()));
+}
+
+/// A parsed document borrowing its strings from the line it was parsed
+/// from.
+pub struct DocRef<'a> {
+    value: BorrowedValue<'a>,
+}
+
+impl<'a> DocRef<'a> {
+    /// Parse a JSON line (a trailing newline is ignored) and call `f` with
+    /// the document.
+    ///
+    /// The line is parsed in a buffer reused by the calling thread, so the
+    /// document can't outlive `f`.
+    pub fn with_line<R>(line: &[u8], f: impl FnOnce(&DocRef<'_>) -> R) -> Result<R> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+            Ok(mut scratch) => {
+                let (buf, buffers) = &mut *scratch;
+                Self::parse_in(buf, buffers, line, f)
+            }
+            // Called from inside `f`: parse with buffers of its own
+            Err(_) => Self::parse_in(&mut Vec::new(), &mut Buffers::default(), line, f),
+        })
+    }
+
+    fn parse_in<R>(
+        buf: &mut Vec<u8>,
+        buffers: &mut Buffers,
+        line: &[u8],
+        f: impl FnOnce(&DocRef<'_>) -> R,
+    ) -> Result<R> {
+        buf.clear();
+        buf.extend_from_slice(line);
+        let value = simd_json::to_borrowed_value_with_buffers(buf, buffers)
+            .map_err(|e| Error::Codec(format!("Invalid JSON line: {}", e)))?;
+        Ok(f(&DocRef { value }))
+    }
+
+    /// The document's `_id`, if it has a string one.
+    pub fn id(&self) -> Option<&str> {
+        match self.field("_id")? {
+            BorrowedValue::String(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// An owned copy of a field, by dot path (`"meta.author"`).
+    pub fn get(&self, path: &str) -> Option<Value> {
+        self.field(path).map(to_owned)
+    }
+
+    /// A string field, by dot path, without copying it.
+    pub fn get_str(&self, path: &str) -> Option<&str> {
+        match self.field(path)? {
+            BorrowedValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Whether the document matches `predicate` (see
+    /// [`Codec::apply_predicate`]).
+    ///
+    /// Only the fields the predicate references are copied.
+    pub fn matches(&self, predicate: &Predicate) -> bool {
+        let doc = self.sparse(&predicate.fields());
+        Codec::apply_predicate(&doc, predicate).unwrap_or(false)
+    }
+
+    /// The document projected onto `fields`, like
+    /// [`Codec::extract_fields`], copying only those fields.
+    pub fn project(&self, fields: &[&str]) -> Result<Value> {
+        if !matches!(self.value, BorrowedValue::Object(_)) {
+            return Err(Error::Codec(
+                "Cannot extract fields from non-object".to_string(),
+            ));
+        }
+        let mut result = Map::new();
+        for field in fields {
+            if let Some(value) = self.field(field) {
+                let key = field.rsplit('.').next().unwrap_or(field);
+                result.insert(key.to_string(), to_owned(value));
+            }
+        }
+        Ok(Value::Object(result))
+    }
+
+    /// An owned copy of the document without its `_id`.
+    pub fn to_value(&self) -> Value {
+        match &self.value {
+            BorrowedValue::Object(obj) => Value::Object(
+                obj.iter()
+                    .filter(|(key, _)| key.as_ref() != "_id")
+                    .map(|(key, value)| (key.to_string(), to_owned(value)))
+                    .collect(),
+            ),
+            value => to_owned(value),
+        }
+    }
+
+    /// A field by dot path.
+    fn field(&self, path: &str) -> Option<&BorrowedValue<'a>> {
+        path.split('.')
+            .try_fold(&self.value, |current, part| match current {
+                BorrowedValue::Object(obj) => obj.get(part),
+                _ => None,
+            })
+    }
+
+    /// An owned document holding only the fields at `paths`, each with the
+    /// value it has in this document, so predicates on those paths give the
+    /// same result on both.
+    fn sparse(&self, paths: &[&str]) -> Value {
+        if !matches!(self.value, BorrowedValue::Object(_)) {
+            return to_owned(&self.value);
+        }
+        let mut doc = Value::Object(Map::new());
+        for path in paths {
+            let Some(value) = self.field(path) else {
+                continue;
+            };
+            let mut parts = path.split('.').peekable();
+            let mut current = &mut doc;
+            while let Some(part) = parts.next() {
+                let Value::Object(obj) = current else {
+                    break;
+                };
+                if parts.peek().is_none() {
+                    obj.insert(part.to_string(), to_owned(value));
+                    break;
+                }
+                current = obj.entry(part).or_insert_with(|| Value::Object(Map::new()));
+            }
+        }
+        doc
+    }
+}
+
+/// An owned copy of a borrowed value.
+fn to_owned(value: &BorrowedValue<'_>) -> Value {
+    match value {
+        BorrowedValue::Static(node) => match *node {
+            StaticNode::I64(n) => Value::from(n),
+            StaticNode::U64(n) => Value::from(n),
+            StaticNode::F64(n) => Number::from_f64(n).map_or(Value::Null, Value::Number),
+            StaticNode::Bool(b) => Value::Bool(b),
+            StaticNode::Null => Value::Null,
+        },
+        BorrowedValue::String(s) => Value::String(Cow::as_ref(s).to_string()),
+        BorrowedValue::Array(items) => Value::Array(items.iter().map(to_owned).collect()),
+        BorrowedValue::Object(obj) => Value::Object(
+            obj.iter()
+                .map(|(key, value)| (key.to_string(), to_owned(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_doc_ref() {
+        let line = br#"{"_id":"d1","name":"cafe","n":3,"meta":{"tags":["a","b"],"score":0.5}}
+"#;
+        let full: Value = serde_json::from_slice(line).unwrap();
+        DocRef::with_line(line, |doc| {
+            assert_eq!(doc.id(), Some("d1"));
+            assert_eq!(doc.get_str("name"), Some("cafe"));
+            assert_eq!(doc.get("meta.score"), Some(json!(0.5)));
+            assert_eq!(doc.get("meta.missing"), None);
+
+            let mut expected = full.clone();
+            expected.as_object_mut().unwrap().remove("_id");
+            assert_eq!(doc.to_value(), expected);
+            assert_eq!(
+                doc.project(&["n", "meta.tags"]).unwrap(),
+                Codec::extract_fields(&full, &["n", "meta.tags"]).unwrap()
+            );
+
+            for predicate in [
+                Predicate::eq("meta.score", 0.5),
+                Predicate::gt("n", 2),
+                Predicate::contains("meta.tags", "b"),
+                Predicate::NotExists("meta.author".to_string()),
+                Predicate::and(vec![
+                    Predicate::eq("meta", full["meta"].clone()),
+                    Predicate::lt("meta.score", 1),
+                ]),
+                Predicate::or(vec![
+                    Predicate::eq("n", 4),
+                    Predicate::starts_with("name", "x"),
+                ]),
+            ] {
+                assert_eq!(
+                    doc.matches(&predicate),
+                    Codec::apply_predicate(&full, &predicate).unwrap(),
+                    "{:?}",
+                    predicate
+                );
+            }
+
+            // Nested parses get buffers of their own
+            let inner = DocRef::with_line(b"[1,2]", |inner| inner.to_value()).unwrap();
+            assert_eq!(inner, json!([1, 2]));
+        })
+        .unwrap();
+
+        assert!(DocRef::with_line(b"{not json", |_| ()).is_err());
+    }
+}
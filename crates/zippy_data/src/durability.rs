@@ -0,0 +1,81 @@
+//! When writes are synced to disk.
+//!
+//! Writers hand their bytes to the operating system, which can lose them in
+//! a power failure or kernel crash until they are synced with `fsync`. A
+//! [`Durability`] trades that window for write throughput. The default
+//! syncs once per flush or commit (group commit): every write of a batch
+//! shares one sync of the data and one of the journal.
+//!
+//! It governs the files documents are written through: the data segments
+//! and tombstones of a [`FastStore`](crate::FastStore), the documents of a
+//! file-per-document writer, and the journal of either.
+
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+/// When writes are synced to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Durability {
+    /// Never sync, and buffer journal entries until the batch commits. A
+    /// process crash can lose the current batch, and leave the documents
+    /// it already wrote unindexed.
+    None,
+    /// Never sync, but write every journal entry and every flushed batch
+    /// through to the operating system, so only a system crash or power
+    /// failure loses writes.
+    Flush,
+    /// Sync once per flush or commit: the data and tombstones, then the
+    /// journal's COMMIT. A system crash loses at most the batch in flight,
+    /// whose documents may then be on disk without being indexed.
+    #[default]
+    FsyncBatch,
+    /// Sync after every write: each line or tombstone of a fast store,
+    /// each document file and each journal entry.
+    FsyncOp,
+}
+
+impl Durability {
+    /// Short machine-readable name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Durability::None => "none",
+            Durability::Flush => "flush",
+            Durability::FsyncBatch => "fsync_batch",
+            Durability::FsyncOp => "fsync_op",
+        }
+    }
+
+    /// Whether flushes and commits are synced.
+    pub fn syncs_batches(&self) -> bool {
+        matches!(self, Durability::FsyncBatch | Durability::FsyncOp)
+    }
+
+    /// Whether every write is synced on its own.
+    pub fn syncs_ops(&self) -> bool {
+        *self == Durability::FsyncOp
+    }
+}
+
+impl FromStr for Durability {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Durability::None),
+            "flush" => Ok(Durability::Flush),
+            "fsync_batch" => Ok(Durability::FsyncBatch),
+            "fsync_op" => Ok(Durability::FsyncOp),
+            other => Err(Error::Validation(format!(
+                "unknown durability '{}' (expected none, flush, fsync_batch or fsync_op)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Durability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
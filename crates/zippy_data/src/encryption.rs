@@ -0,0 +1,380 @@
+//! Encryption at rest for fast store collections.
+//!
+//! An encrypted collection stores its data segments as zstd blocks whose
+//! frames are sealed with AES-256-GCM, each under a fresh random nonce. An
+//! encrypted block is written as a zstd skippable frame, so the file is
+//! still a valid zstd stream and block headers can be walked without the
+//! key. `index.bin` and the field and text index files are sealed whole.
+//! Document order, tombstone and manifest files only hold document IDs and
+//! settings, and stay in the clear.
+//!
+//! Sealed data is bound to where it belongs through the associated data of
+//! AES-GCM: a whole file to its path within the collection (see
+//! [`file_aad`]), and a data block to its offset in the segment, so sealed
+//! files and blocks can't be swapped or moved undetected. Once a collection
+//! is encrypted, files and blocks that aren't sealed are rejected rather
+//! than read as they are.
+//!
+//! The manifest records that a collection is encrypted and the ID of its
+//! key, never the key itself. Keys are 32 bytes, supplied through
+//! [`KEY_ENV`] (hex) or [`KEY_FILE_ENV`] (path to a file holding the key as
+//! hex or raw bytes) whenever an encrypted collection is opened.
+
+use std::{path::Path, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use serde_json::Value;
+
+use crate::{config::CollectionConfig, Error, Layout, Result};
+
+/// Environment variable holding the key as 64 hex digits.
+pub const KEY_ENV: &str = "ZDS_ENCRYPTION_KEY";
+
+/// Environment variable holding the path of a key file.
+pub const KEY_FILE_ENV: &str = "ZDS_ENCRYPTION_KEY_FILE";
+
+/// Key length in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// Algorithm name as stored in the manifest.
+pub const ALGORITHM: &str = "aes-256-gcm";
+
+/// Nonce length of sealed data.
+const NONCE_LEN: usize = 12;
+
+/// Magic at the start of a sealed whole file ("ZDSE").
+const FILE_MAGIC: &[u8; 4] = b"ZDSE";
+
+/// A 256-bit collection key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EncryptionKey").field(&self.id()).finish()
+    }
+}
+
+impl EncryptionKey {
+    /// Wrap raw key bytes.
+    pub fn new(bytes: [u8; KEY_LEN]) -> Self {
+        EncryptionKey(bytes)
+    }
+
+    /// Generate a random key.
+    pub fn generate() -> Self {
+        EncryptionKey(Aes256Gcm::generate_key(OsRng).into())
+    }
+
+    /// Parse a key from 64 hex digits.
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        let invalid = || {
+            Error::Encryption(format!(
+                "key must be {} hex digits, got {} characters",
+                KEY_LEN * 2,
+                hex.len()
+            ))
+        };
+        if hex.len() != KEY_LEN * 2 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let mut bytes = [0u8; KEY_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(EncryptionKey(bytes))
+    }
+
+    /// The key as 64 hex digits.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Read a key file holding either the raw 32 bytes or hex digits.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = std::fs::read(path.as_ref())?;
+        if let Ok(raw) = <[u8; KEY_LEN]>::try_from(bytes.as_slice()) {
+            return Ok(EncryptionKey(raw));
+        }
+        let text = String::from_utf8(bytes).map_err(|_| {
+            Error::Encryption(format!(
+                "key file {} is neither {} raw bytes nor hex",
+                path.as_ref().display(),
+                KEY_LEN
+            ))
+        })?;
+        Self::from_hex(&text)
+    }
+
+    /// The key from [`KEY_ENV`], else from the file named by
+    /// [`KEY_FILE_ENV`], or `None` if neither is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        if let Ok(hex) = std::env::var(KEY_ENV) {
+            return Self::from_hex(&hex).map(Some);
+        }
+        match std::env::var_os(KEY_FILE_ENV) {
+            Some(path) => Self::from_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Short fingerprint recorded in the manifest to recognize the key.
+    pub fn id(&self) -> String {
+        let hash = blake3::keyed_hash(&self.0, b"zds key id");
+        hash.to_hex()[..16].to_string()
+    }
+}
+
+/// ID of the key a collection is encrypted with, or `None` if it isn't
+/// encrypted.
+pub fn key_id(root: &Path, collection: &str) -> Result<Option<String>> {
+    let path = Layout::manifest_file(root, collection);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    let Some(settings) = manifest.get("encryption") else {
+        return Ok(None);
+    };
+    match settings.get("algorithm").and_then(Value::as_str) {
+        Some(ALGORITHM) => {}
+        other => {
+            return Err(Error::Encryption(format!(
+                "unsupported encryption algorithm {:?} in collection '{}'",
+                other.unwrap_or("none"),
+                collection
+            )))
+        }
+    }
+    Ok(Some(
+        settings
+            .get("key_id")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+    ))
+}
+
+/// Record `key` in a collection's manifest (or remove the encryption
+/// settings with `None`), keeping other fields.
+pub(crate) fn save(root: &Path, collection: &str, key: Option<&EncryptionKey>) -> Result<()> {
+    CollectionConfig::update(root, collection, |obj| match key {
+        Some(key) => {
+            let settings = serde_json::json!({ "algorithm": ALGORITHM, "key_id": key.id() });
+            obj.insert("encryption".to_string(), settings);
+        }
+        None => {
+            obj.remove("encryption");
+        }
+    })
+}
+
+/// The cipher of an encrypted collection, with the key from the
+/// environment, or `None` if the collection isn't encrypted.
+pub(crate) fn load_cipher(root: &Path, collection: &str) -> Result<Option<Arc<Cipher>>> {
+    let Some(key_id) = key_id(root, collection)? else {
+        return Ok(None);
+    };
+    let key = EncryptionKey::from_env()?.ok_or_else(|| {
+        Error::Encryption(format!(
+            "collection '{}' is encrypted; set {} or {}",
+            collection, KEY_ENV, KEY_FILE_ENV
+        ))
+    })?;
+    if key.id() != key_id {
+        return Err(Error::Encryption(format!(
+            "wrong key for collection '{}' (expected key {}, got {})",
+            collection,
+            key_id,
+            key.id()
+        )));
+    }
+    Ok(Some(Arc::new(Cipher::new(&key))))
+}
+
+/// AES-256-GCM sealing with random nonces.
+pub(crate) struct Cipher(Aes256Gcm);
+
+impl Cipher {
+    pub(crate) fn new(key: &EncryptionKey) -> Self {
+        Cipher(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0)))
+    }
+
+    /// Encrypt `plain` as nonce followed by ciphertext and tag. `aad` is
+    /// authenticated along with it, and opening needs the same `aad`.
+    pub(crate) fn seal(&self, plain: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = self
+            .0
+            .encrypt(&nonce, Payload { msg: plain, aad })
+            .map_err(|_| Error::Encryption("encryption failed".to_string()))?;
+        let mut out = Vec::with_capacity(NONCE_LEN + sealed.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Decrypt and authenticate the output of [`seal`](Self::seal).
+    pub(crate) fn open(&self, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let fail =
+            || Error::Encryption("decryption failed (wrong key or damaged data)".to_string());
+        if sealed.len() < NONCE_LEN {
+            return Err(fail());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        self.0
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| fail())
+    }
+}
+
+/// Associated data of a sealed collection file: its path relative to the
+/// collection directory, which survives renaming or copying the collection.
+pub(crate) fn file_aad(root: &Path, collection: &str, path: &Path) -> Vec<u8> {
+    let dir = Layout::collection_dir(root, collection);
+    let relative = path.strip_prefix(&dir).unwrap_or(path);
+    let mut aad = b"zds file:".to_vec();
+    for (i, component) in relative.components().enumerate() {
+        if i > 0 {
+            aad.push(b'/');
+        }
+        aad.extend_from_slice(component.as_os_str().to_string_lossy().as_bytes());
+    }
+    aad
+}
+
+/// Seal a whole file's contents (unchanged without a cipher), bound to
+/// `aad`.
+pub(crate) fn seal_file(cipher: Option<&Cipher>, contents: Vec<u8>, aad: &[u8]) -> Result<Vec<u8>> {
+    let Some(cipher) = cipher else {
+        return Ok(contents);
+    };
+    let mut out = FILE_MAGIC.to_vec();
+    out.extend(cipher.seal(&contents, aad)?);
+    Ok(out)
+}
+
+/// Contents of a file written by [`seal_file`] with the same `aad`.
+///
+/// Without a cipher, files that aren't sealed are returned as they are;
+/// with one, they are rejected, as the file may have been replaced.
+pub(crate) fn open_file(cipher: Option<&Cipher>, contents: Vec<u8>, aad: &[u8]) -> Result<Vec<u8>> {
+    match (cipher, contents.strip_prefix(FILE_MAGIC)) {
+        (Some(cipher), Some(sealed)) => cipher.open(sealed, aad),
+        (Some(_), None) => Err(Error::Encryption(
+            "file of an encrypted collection is not sealed".to_string(),
+        )),
+        (None, Some(_)) => Err(Error::Encryption(
+            "file is encrypted and no key was given".to_string(),
+        )),
+        (None, None) => Ok(contents),
+    }
+}
+
+/// Read a collection file, decrypting it if the collection is encrypted.
+pub(crate) fn read_file(root: &Path, collection: &str, path: &Path) -> Result<Vec<u8>> {
+    let contents = std::fs::read(path)?;
+    let cipher = load_cipher(root, collection)?;
+    open_file(
+        cipher.as_deref(),
+        contents,
+        &file_aad(root, collection, path),
+    )
+}
+
+/// Write a collection file, encrypting it if the collection is encrypted.
+///
+/// The contents go to a temp file renamed over `path`, so readers never see
+/// a partial file.
+pub(crate) fn write_file(
+    root: &Path,
+    collection: &str,
+    path: &Path,
+    contents: Vec<u8>,
+) -> Result<()> {
+    let cipher = load_cipher(root, collection)?;
+    let aad = file_aad(root, collection, path);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, seal_file(cipher.as_deref(), contents, &aad)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_key_formats() {
+        let key = EncryptionKey::generate();
+        assert_eq!(EncryptionKey::from_hex(&key.to_hex()).unwrap(), key);
+        assert!(EncryptionKey::from_hex("abcd").is_err());
+        assert!(!format!("{:?}", key).contains(&key.to_hex()));
+
+        let tmp = TempDir::new().unwrap();
+        let raw = tmp.path().join("raw.key");
+        std::fs::write(&raw, key.0).unwrap();
+        assert_eq!(EncryptionKey::from_file(&raw).unwrap(), key);
+        let hex = tmp.path().join("hex.key");
+        std::fs::write(&hex, format!("{}\n", key.to_hex())).unwrap();
+        assert_eq!(EncryptionKey::from_file(&hex).unwrap(), key);
+    }
+
+    #[test]
+    fn test_seal_file() {
+        let cipher = Cipher::new(&EncryptionKey::generate());
+        let sealed = seal_file(Some(&cipher), b"secret".to_vec(), b"a").unwrap();
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(
+            open_file(Some(&cipher), sealed.clone(), b"a").unwrap(),
+            b"secret"
+        );
+
+        let other = Cipher::new(&EncryptionKey::generate());
+        assert!(matches!(
+            open_file(Some(&other), sealed.clone(), b"a"),
+            Err(Error::Encryption(_))
+        ));
+        assert!(open_file(None, sealed, b"a").is_err());
+        assert_eq!(open_file(None, b"plain".to_vec(), b"a").unwrap(), b"plain");
+    }
+
+    #[test]
+    fn test_sealed_file_tampering() {
+        let cipher = Cipher::new(&EncryptionKey::generate());
+        let sealed = seal_file(Some(&cipher), b"secret".to_vec(), b"a").unwrap();
+
+        // Moved to another file
+        assert!(matches!(
+            open_file(Some(&cipher), sealed.clone(), b"b"),
+            Err(Error::Encryption(_))
+        ));
+        // Any flipped bit
+        for i in FILE_MAGIC.len()..sealed.len() {
+            let mut damaged = sealed.clone();
+            damaged[i] ^= 1;
+            assert!(open_file(Some(&cipher), damaged, b"a").is_err());
+        }
+        // Replaced by a file in the clear
+        assert!(matches!(
+            open_file(Some(&cipher), b"secret".to_vec(), b"a"),
+            Err(Error::Encryption(_))
+        ));
+
+        let root = Path::new("/store");
+        let aad = |path: &str| file_aad(root, "c", &Layout::collection_dir(root, "c").join(path));
+        assert_eq!(aad("meta/index.bin"), b"zds file:meta/index.bin");
+        assert_ne!(aad("meta/indexes/a.jsonl"), aad("meta/indexes/b.jsonl"));
+    }
+}
@@ -1,18 +1,115 @@
 //! Main engine for ZDS operations.
 
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::{Range, RangeBounds},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
 
+use once_cell::sync::OnceCell;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+#[cfg(feature = "remote")]
+use crate::remote::{RemoteJsonl, RemoteStore};
 use crate::{
+    aggregate::{Aggregate, Aggregator, GroupBy},
     codec::{Codec, Predicate},
-    container::ContainerFS,
-    index::IndexRegistry,
+    compression::Compression,
+    config::{CollectionConfig, KeyOrder},
+    container::{ContainerFS, ZipEntry, ZipReader},
+    fast_writer::{ChunkSize, FastStore, FastStoreStats, OpenMode},
+    field_index::{FieldIndex, FieldIndexes},
+    index::{DocIndexEntry, IndexRegistry},
+    keys::{id_bounds, SortedIds},
+    lock::WriteLock,
+    migrate::{self, MigrationRecord, MigrationReport},
+    sample,
     schema::SchemaRegistry,
-    Error, Result,
+    store::ReadStore,
+    text_index::TextIndex,
+    txlog::{JournalEntry, TransactionLog},
+    vectors::{Metric, Neighbor, VectorIndex},
+    writer::{self, RecoveryReport},
+    Error, Layout, Result,
 };
 
+/// How a collection stores its documents, as recorded in its manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageMode {
+    /// One JSON file per document (`docs/{doc_id}.json`), written by the
+    /// writers
+    #[default]
+    #[serde(alias = "docs")]
+    Files,
+    /// JSONL data segments (`meta/data.jsonl`, ...), written by [`FastStore`]
+    Jsonl,
+}
+
+impl StorageMode {
+    /// Name as stored in the manifest.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageMode::Files => "files",
+            StorageMode::Jsonl => "jsonl",
+        }
+    }
+
+    /// Read the storage mode recorded in a collection's manifest, if any.
+    pub fn load(root: &Path, collection: &str) -> Result<Option<Self>> {
+        let path = Layout::manifest_file(root, collection);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        manifest
+            .get("storage")
+            .and_then(Value::as_str)
+            .map(str::parse)
+            .transpose()
+    }
+
+    /// The storage mode of a collection: the recorded one, or for
+    /// collections written before it was recorded, `Jsonl` if the
+    /// collection holds fast store data.
+    pub fn detect(root: &Path, collection: &str) -> Result<Self> {
+        Ok(Self::load(root, collection)?.unwrap_or_else(|| {
+            if Layout::has_fast_data(root, collection) {
+                StorageMode::Jsonl
+            } else {
+                StorageMode::Files
+            }
+        }))
+    }
+
+    /// Record this storage mode in a collection's manifest, keeping other
+    /// fields.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        CollectionConfig::update(root, collection, |obj| {
+            obj.insert("storage".to_string(), Value::from(self.as_str()));
+        })
+    }
+}
+
+impl FromStr for StorageMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "files" | "docs" => Ok(StorageMode::Files),
+            "jsonl" => Ok(StorageMode::Jsonl),
+            other => Err(Error::Validation(format!(
+                "unknown storage mode '{}' (expected files, docs or jsonl)",
+                other
+            ))),
+        }
+    }
+}
+
 /// Manifest for a collection.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Manifest {
@@ -22,6 +119,28 @@ pub struct Manifest {
     pub created_at: String,
     pub doc_count: u64,
     pub schema_count: u64,
+    /// Time the counts were last recorded by a writer's flush
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// Fast store data file format
+    #[serde(default)]
+    pub compression: Compression,
+    /// Document storage; set by the first store that writes, and detected
+    /// from the data when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageMode>,
+    /// Shards used for parallel ingestion when none are given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shards: Option<usize>,
+    /// Dead-byte ratio at which fast stores compact on flush
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_compact: Option<f64>,
+    /// Whether stores keep the document IDs sorted
+    #[serde(default)]
+    pub key_order: KeyOrder,
+    /// Finished migrations, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub migrations: Vec<MigrationRecord>,
 }
 
 impl Manifest {
@@ -33,16 +152,45 @@ impl Manifest {
             created_at: chrono::Utc::now().to_rfc3339(),
             doc_count: 0,
             schema_count: 0,
+            updated_at: None,
+            compression: Compression::None,
+            storage: None,
+            shards: None,
+            auto_compact: None,
+            key_order: KeyOrder::Hashed,
+            migrations: Vec::new(),
         }
     }
 }
 
 /// Main ZDS engine.
+///
+/// Reads both storage modes: file-per-document collections directly from
+/// the container, and [JSONL](StorageMode::Jsonl) collections through a
+/// read-only [`FastStore`]. ZIP archives are read without unpacking:
+/// file-per-document collections in place, JSONL collections from a copy
+/// in memory.
 pub struct Engine {
     container: ContainerFS,
     collection: String,
     index: IndexRegistry,
     schema_registry: SchemaRegistry,
+    field_indexes: FieldIndexes,
+    /// Vector indexes of a file-per-document collection (a JSONL
+    /// collection's are held by its store)
+    vectors: BTreeMap<String, VectorIndex>,
+    /// Store holding the documents of a JSONL collection
+    fast: Option<Arc<FastStore>>,
+    /// Open archive of a ZIP container
+    archive: Option<Arc<ZipReader>>,
+    /// Reader of a JSONL collection in a remote container
+    #[cfg(feature = "remote")]
+    remote: Option<Arc<RemoteJsonl>>,
+    /// Writes of a crashed writer recovered on open
+    recovery: Option<RecoveryReport>,
+    /// Document IDs in sorted order, for prefix scans (built on the first
+    /// one)
+    sorted_ids: OnceCell<SortedIds>,
 }
 
 impl Engine {
@@ -50,20 +198,88 @@ impl Engine {
     pub fn open(container_path: impl AsRef<Path>, collection: impl AsRef<str>) -> Result<Self> {
         let container = ContainerFS::open(container_path)?;
         let collection = collection.as_ref().to_string();
+        if container.is_zip() {
+            return Self::open_archive(container, collection);
+        }
+        #[cfg(feature = "remote")]
+        if let ContainerFS::Remote(store) = container {
+            return Self::open_remote(store, collection);
+        }
 
-        // Load indexes
-        let index = if container.is_folder() {
-            IndexRegistry::load(container.root_path(), &collection).unwrap_or_default()
+        let mode = StorageMode::detect(container.root_path(), &collection)?;
+        let recovery = match mode {
+            StorageMode::Files => recover_files(container.root_path(), &collection)?,
+            StorageMode::Jsonl => None,
+        };
+        let fast = if mode == StorageMode::Jsonl {
+            // The batch size only matters for writes
+            let store =
+                FastStore::open_with_mode(container.root_path(), &collection, 1, OpenMode::Read)?;
+            Some(Arc::new(store))
         } else {
-            // For zip archives, rebuild index from contents
-            IndexRegistry::new() // TODO: implement zip index loading
+            None
         };
 
-        let schema_registry = if container.is_folder() {
-            SchemaRegistry::load(container.root_path(), &collection)
-                .unwrap_or_else(|_| SchemaRegistry::new(false))
-        } else {
-            SchemaRegistry::new(false)
+        // Load indexes
+        let index = match &fast {
+            Some(store) => store.doc_index(),
+            None => IndexRegistry::load(container.root_path(), &collection).unwrap_or_default(),
+        };
+        let schema_registry = SchemaRegistry::load(container.root_path(), &collection)
+            .unwrap_or_else(|_| SchemaRegistry::new(false));
+        let field_indexes =
+            FieldIndexes::load(container.root_path(), &collection).unwrap_or_default();
+        let vectors = match &fast {
+            Some(_) => BTreeMap::new(),
+            None => VectorIndex::load_all(container.root_path(), &collection)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|index| (index.name().to_string(), index))
+                .collect(),
+        };
+        let sorted_ids = OnceCell::new();
+        if CollectionConfig::load(container.root_path(), &collection)?.key_order == KeyOrder::Sorted
+        {
+            let _ = sorted_ids.set(sorted_doc_ids(&index));
+        }
+
+        Ok(Engine {
+            container,
+            collection,
+            index,
+            schema_registry,
+            field_indexes,
+            vectors,
+            fast,
+            archive: None,
+            #[cfg(feature = "remote")]
+            remote: None,
+            recovery,
+            sorted_ids,
+        })
+    }
+
+    /// Open a collection inside a ZIP archive, reading its index and
+    /// schemas from the archive.
+    ///
+    /// Without a saved index the documents are listed from the archive's
+    /// directory, in ID order, without reading them. Field indexes aren't
+    /// loaded, so queries scan every document. A JSONL collection is
+    /// copied out of the archive into memory (see
+    /// [`FastStore::load_from_archive`]).
+    fn open_archive(container: ContainerFS, collection: String) -> Result<Self> {
+        let archive = ZipReader::open(container.root_path())?;
+        let read = |path: &Path| utf8(archive.read(path)?);
+        let list_dir = |dir: &Path| archive.list_dir(dir);
+
+        let (storage, schema_registry) = listed_metadata(&collection, &read, &list_dir)?;
+        let (fast, index) = match storage {
+            StorageMode::Jsonl => {
+                let store = FastStore::load_from_archive(&archive, &collection)?;
+                let index = store.doc_index();
+                (Some(Arc::new(store)), index)
+            }
+            StorageMode::Files => (None, listed_index(&collection, &read, &list_dir)?),
         };
 
         Ok(Engine {
@@ -71,16 +287,103 @@ impl Engine {
             collection,
             index,
             schema_registry,
+            field_indexes: FieldIndexes::new(),
+            vectors: BTreeMap::new(),
+            fast,
+            archive: Some(Arc::new(archive)),
+            #[cfg(feature = "remote")]
+            remote: None,
+            recovery: None,
+            sorted_ids: OnceCell::new(),
+        })
+    }
+
+    /// Open a collection in a remote container, fetching its index and
+    /// schemas.
+    ///
+    /// File-per-document collections are read like archives. JSONL
+    /// collections are read through their saved `index.bin`, with one range
+    /// request per document. Field indexes aren't loaded.
+    #[cfg(feature = "remote")]
+    pub fn open_remote(store: RemoteStore, collection: impl AsRef<str>) -> Result<Self> {
+        let collection = collection.as_ref().to_string();
+        let read = |path: &Path| utf8(store.read(path)?);
+        let list_dir = |dir: &Path| store.list_dir(dir);
+
+        let (storage, schema_registry) = listed_metadata(&collection, &read, &list_dir)?;
+        let (remote, index) = match storage {
+            StorageMode::Jsonl => {
+                let (reader, index) = RemoteJsonl::open(&store, &collection)?;
+                (Some(Arc::new(reader)), index)
+            }
+            StorageMode::Files => (None, listed_index(&collection, &read, &list_dir)?),
+        };
+
+        Ok(Engine {
+            container: ContainerFS::Remote(store),
+            collection,
+            index,
+            schema_registry,
+            field_indexes: FieldIndexes::new(),
+            vectors: BTreeMap::new(),
+            fast: None,
+            archive: None,
+            remote,
+            recovery: None,
+            sorted_ids: OnceCell::new(),
         })
     }
 
+    /// The storage mode the collection is read in.
+    pub fn storage_mode(&self) -> StorageMode {
+        #[cfg(feature = "remote")]
+        if self.remote.is_some() {
+            return StorageMode::Jsonl;
+        }
+        match self.fast {
+            Some(_) => StorageMode::Jsonl,
+            None => StorageMode::Files,
+        }
+    }
+
+    /// The read-only store holding a JSONL collection's documents.
+    pub fn fast_store(&self) -> Option<&FastStore> {
+        self.fast.as_deref()
+    }
+
     /// Get a single document by ID.
     pub fn get_document(&self, doc_id: &str) -> Result<Value> {
-        let relative_path = format!("collections/{}/docs/{}.json", self.collection, doc_id);
-        let content = self.container.read_file_string(Path::new(&relative_path))?;
+        if let Some(store) = &self.fast {
+            return store.get(doc_id);
+        }
+        #[cfg(feature = "remote")]
+        if let Some(reader) = &self.remote {
+            return reader.get(doc_id);
+        }
+        let content = read_doc_file(
+            &self.container,
+            self.archive.as_deref(),
+            &self.collection,
+            doc_id,
+        )?;
         Codec::decode(&content)
     }
 
+    /// The committed PUT and DELETE entries of a document in the
+    /// collection's journal, oldest first.
+    ///
+    /// Only file-per-document writers journal every write, and archives
+    /// don't include journals, so other collections have no history.
+    pub fn history(&self, doc_id: &str) -> Result<Vec<JournalEntry>> {
+        if !self.container.is_folder() {
+            return Ok(Vec::new());
+        }
+        let mut history =
+            TransactionLog::load_history(self.container.root_path(), &self.collection)?;
+        history.retain(|entry| entry.doc_id() == Some(doc_id));
+        Ok(history)
+    }
+
     /// Get document at index position (based on order.ids).
     pub fn get_document_at(&self, index: usize) -> Result<Value> {
         let doc_id = self
@@ -90,17 +393,204 @@ impl Engine {
         self.get_document(doc_id)
     }
 
+    /// Read a reproducible random sample of `n` documents (all of them if
+    /// the collection holds fewer), in `order.ids` order.
+    ///
+    /// Only the chosen documents are read; the same `seed` gives the same
+    /// sample as long as the collection is unchanged.
+    pub fn sample(&self, n: usize, seed: u64) -> Result<Vec<Value>> {
+        sample::sample_positions(self.index.len(), n, seed)
+            .into_iter()
+            .map(|i| self.get_document_at(i))
+            .collect()
+    }
+
+    /// Aggregate the collection, optionally per group, reading documents in
+    /// parallel.
+    ///
+    /// Returns one row per group, sorted by group value, holding the group
+    /// field and each aggregate under its [`Aggregate::name`]. Without a
+    /// group field there is a single row.
+    pub fn aggregate(
+        &self,
+        group_by: Option<&GroupBy>,
+        aggregates: &[Aggregate],
+    ) -> Result<Vec<Value>> {
+        let new = || Aggregator::new(group_by, aggregates);
+        let aggregator = (0..self.index.len())
+            .into_par_iter()
+            .try_fold(new, |mut aggregator, i| {
+                aggregator.add(&self.get_document_at(i)?);
+                Ok::<_, Error>(aggregator)
+            })
+            .try_reduce(new, |a, b| Ok(a.merge(b)))?;
+        Ok(aggregator.finish())
+    }
+
+    /// Rewrite every document of the collection through `transform`.
+    ///
+    /// Runs in batches of [`MIGRATION_BATCH_SIZE`](migrate::MIGRATION_BATCH_SIZE)
+    /// under the store's write lock, and resumes where it stopped if an
+    /// earlier run was interrupted (re-run it with the same transform). A
+    /// transform error stops the migration, leaving it resumable. In strict
+    /// mode all migrated documents must share one schema.
+    ///
+    /// The old → new schema ID mappings are returned and recorded in the
+    /// manifest. Only folder stores with file-per-document data can be migrated.
+    pub fn migrate<F>(&mut self, transform: F) -> Result<MigrationReport>
+    where
+        F: FnMut(Value) -> Result<Value>,
+    {
+        if !self.container.is_folder() {
+            return Err(Error::ReadOnly(
+                "cannot migrate a collection in a ZIP archive".to_string(),
+            ));
+        }
+        if self.fast.is_some() {
+            return Err(Error::Validation(
+                "cannot migrate a JSONL collection; migrations rewrite file-per-document data"
+                    .to_string(),
+            ));
+        }
+        let root = self.container.root_path().to_path_buf();
+        let report = migrate::run(
+            &root,
+            &self.collection,
+            migrate::MIGRATION_BATCH_SIZE,
+            transform,
+        )?;
+        *self = Engine::open(&root, &self.collection)?;
+        Ok(report)
+    }
+
     /// Create a scanner for iterating documents.
     pub fn scan(&self, predicate: Option<&Predicate>, fields: Option<&[&str]>) -> Result<Scanner> {
+        self.scan_range(0..self.index.len(), predicate, fields)
+    }
+
+    /// Scan the documents whose IDs start with `prefix`, in ID order.
+    ///
+    /// With hierarchical IDs (`"user/123/event/456"`), a prefix ending in
+    /// the separator scans a subtree.
+    pub fn scan_prefix(
+        &self,
+        prefix: &str,
+        predicate: Option<&Predicate>,
+        fields: Option<&[&str]>,
+    ) -> Result<Scanner> {
+        Scanner::new(
+            self,
+            self.sorted_ids()
+                .with_prefix(prefix)
+                .map(str::to_string)
+                .collect(),
+            predicate.cloned(),
+            fields.map(|f| f.iter().map(|s| s.to_string()).collect()),
+        )
+    }
+
+    /// IDs within `range` (e.g. `"a".."m"`), sorted.
+    pub fn id_range<'a>(&self, range: impl RangeBounds<&'a str>) -> Vec<String> {
+        self.sorted_ids()
+            .range(id_bounds(&range))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Scan the documents whose IDs are within `range`, in ID order (see
+    /// [`id_range`](Self::id_range)).
+    pub fn scan_id_range<'a>(
+        &self,
+        range: impl RangeBounds<&'a str>,
+        predicate: Option<&Predicate>,
+        fields: Option<&[&str]>,
+    ) -> Result<Scanner> {
+        Scanner::new(
+            self,
+            self.id_range(range),
+            predicate.cloned(),
+            fields.map(|f| f.iter().map(|s| s.to_string()).collect()),
+        )
+    }
+
+    /// The sorted document IDs, built on first use unless the collection's
+    /// key order is sorted.
+    fn sorted_ids(&self) -> &SortedIds {
+        self.sorted_ids.get_or_init(|| sorted_doc_ids(&self.index))
+    }
+
+    /// Scan documents in chunks bounded by both a document count and a byte
+    /// size, see [`ChunkSize`].
+    ///
+    /// Byte sizes are estimated from the (projected) documents with
+    /// [`Codec::estimated_size`].
+    pub fn scan_chunks(
+        &self,
+        predicate: Option<&Predicate>,
+        fields: Option<&[&str]>,
+        size: ChunkSize,
+    ) -> Result<ScannerChunks> {
+        Ok(self.scan(predicate, fields)?.chunks(size))
+    }
+
+    /// Create a scanner over a contiguous range of document positions (order.ids).
+    ///
+    /// The range is clamped to the collection size, so out-of-range partitions
+    /// simply yield no documents.
+    ///
+    /// When the predicate references indexed fields, only the candidate
+    /// documents from the field indexes are read.
+    pub fn scan_range(
+        &self,
+        range: Range<usize>,
+        predicate: Option<&Predicate>,
+        fields: Option<&[&str]>,
+    ) -> Result<Scanner> {
+        let ids = self.index.all_doc_ids();
+        let end = range.end.min(ids.len());
+        let start = range.start.min(end);
+        let ids = &ids[start..end];
+
+        let doc_ids = match predicate.and_then(|p| self.field_indexes.candidates(p)) {
+            Some(candidates) => {
+                let candidates: HashSet<String> = candidates.into_iter().collect();
+                ids.iter()
+                    .filter(|id| candidates.contains(*id))
+                    .cloned()
+                    .collect()
+            }
+            None => ids.to_vec(),
+        };
+
         Scanner::new(
-            self.container.clone(),
-            self.collection.clone(),
-            self.index.clone(),
+            self,
+            doc_ids,
             predicate.cloned(),
             fields.map(|f| f.iter().map(|s| s.to_string()).collect()),
         )
     }
 
+    /// Split the collection into at most `count` contiguous, non-empty partitions
+    /// of roughly equal size, suitable for driving one scanner per thread.
+    pub fn partitions(&self, count: usize) -> Vec<Range<usize>> {
+        let total = self.index.len();
+        if total == 0 {
+            return Vec::new();
+        }
+        let count = count.clamp(1, total);
+        let base = total / count;
+        let extra = total % count;
+
+        let mut partitions = Vec::with_capacity(count);
+        let mut start = 0;
+        for i in 0..count {
+            let len = base + usize::from(i < extra);
+            partitions.push(start..start + len);
+            start += len;
+        }
+        partitions
+    }
+
     /// Get collection statistics.
     pub fn stats(&self) -> CollectionStats {
         CollectionStats {
@@ -109,6 +599,9 @@ impl Engine {
             schema_count: self.schema_registry.schema_count(),
             total_size: self.index.total_size(),
             strict_mode: self.schema_registry.is_strict(),
+            storage: self.storage_mode(),
+            fast: self.fast.as_ref().map(|store| store.stats()),
+            recovery: self.recovery.clone(),
         }
     }
 
@@ -142,15 +635,288 @@ impl Engine {
         &self.container
     }
 
+    /// Get the secondary field indexes.
+    pub fn field_indexes(&self) -> &FieldIndexes {
+        &self.field_indexes
+    }
+
+    /// Build (or rebuild) a secondary index on `field` and save it.
+    ///
+    /// Returns the number of indexed documents.
+    pub fn create_index(&mut self, field: &str) -> Result<usize> {
+        FieldIndexes::validate_field(field)?;
+        if !self.container.is_folder() {
+            return Err(Error::ReadOnly(
+                "cannot create indexes in archives".to_string(),
+            ));
+        }
+
+        let mut index = FieldIndex::new(field);
+        for doc_id in self.index.all_doc_ids() {
+            if let Ok(doc) = self.get_document(doc_id) {
+                index.insert(doc_id, &doc);
+            }
+        }
+        index.save(self.container.root_path(), &self.collection)?;
+
+        let count = index.len();
+        self.field_indexes.add(index);
+        Ok(count)
+    }
+
+    /// Drop the secondary index on `field`.
+    pub fn drop_index(&mut self, field: &str) -> Result<()> {
+        if self.container.is_folder() {
+            FieldIndexes::drop_index(self.container.root_path(), &self.collection, field)?;
+        }
+        self.field_indexes.remove_index(field);
+        Ok(())
+    }
+
+    /// Build (or rebuild) a full-text index on a string `field`.
+    ///
+    /// Returns the number of documents with indexed text.
+    pub fn create_text_index(&mut self, field: &str) -> Result<usize> {
+        FieldIndexes::validate_field(field)?;
+        if !self.container.is_folder() {
+            return Err(Error::ReadOnly(
+                "cannot create indexes in archives".to_string(),
+            ));
+        }
+
+        let mut index = TextIndex::new(field);
+        for doc_id in self.index.all_doc_ids() {
+            if let Ok(doc) = self.get_document(doc_id) {
+                index.insert(doc_id, &doc);
+            }
+        }
+        index.save(self.container.root_path(), &self.collection)?;
+
+        let count = index.len();
+        self.field_indexes.add_text(index);
+        Ok(count)
+    }
+
+    /// Drop the full-text index on `field`.
+    pub fn drop_text_index(&mut self, field: &str) -> Result<()> {
+        if self.container.is_folder() {
+            FieldIndexes::drop_text_index(self.container.root_path(), &self.collection, field)?;
+        }
+        self.field_indexes.remove_text_index(field);
+        Ok(())
+    }
+
+    /// Scan documents whose text-indexed fields contain every token of `query`.
+    pub fn search(&self, query: &str) -> Result<Scanner> {
+        let predicate = self.field_indexes.search_predicate(query).ok_or_else(|| {
+            Error::Validation(format!("No text index in collection '{}'", self.collection))
+        })?;
+        self.scan(Some(&predicate), None)
+    }
+
+    /// The `k` documents whose embeddings in the vector index `name` are
+    /// nearest to `query`, nearest first.
+    ///
+    /// Vector indexes are only read from folder stores.
+    pub fn knn(
+        &self,
+        name: &str,
+        query: &[f32],
+        k: usize,
+        metric: Metric,
+    ) -> Result<Vec<Neighbor>> {
+        if let Some(store) = &self.fast {
+            return store.knn(name, query, k, metric);
+        }
+        let index = self.vectors.get(name).ok_or_else(|| {
+            Error::Validation(format!(
+                "No vector index '{}' in collection '{}'",
+                name, self.collection
+            ))
+        })?;
+        index.search(query, k, metric)
+    }
+
     /// Rebuild indexes from disk.
     pub fn rebuild_index(&mut self) -> Result<()> {
-        if self.container.is_folder() {
+        if let Some(store) = &self.fast {
+            self.index = store.doc_index();
+        } else if self.container.is_folder() {
             self.index = IndexRegistry::rebuild(self.container.root_path(), &self.collection)?;
         }
+        if self.sorted_ids.take().is_some() {
+            let _ = self.sorted_ids.set(sorted_doc_ids(&self.index));
+        }
         Ok(())
     }
 }
 
+/// Storage mode and schemas of a collection read through `read` and
+/// `list_dir`, for containers that aren't local folders.
+///
+/// Without a recorded storage mode, the collection is JSONL if its `meta/`
+/// directory holds data segments.
+fn listed_metadata(
+    collection: &str,
+    read: &dyn Fn(&Path) -> Result<Option<String>>,
+    list_dir: &dyn Fn(&Path) -> Result<Vec<ZipEntry>>,
+) -> Result<(StorageMode, SchemaRegistry)> {
+    // Layout paths relative to the container root
+    let root = Path::new("");
+    let manifest: Option<Value> = read(&Layout::manifest_file(root, collection))?
+        .map(|content| serde_json::from_str(&content))
+        .transpose()?;
+    let recorded = manifest
+        .as_ref()
+        .and_then(|m| m.get("storage"))
+        .and_then(Value::as_str)
+        .map(str::parse)
+        .transpose()?;
+    let storage = match recorded {
+        Some(storage) => storage,
+        None => {
+            let has_fast_data =
+                list_dir(&Layout::meta_dir(root, collection)).is_ok_and(|entries| {
+                    entries
+                        .iter()
+                        .any(|e| Layout::parse_data_segment(&e.name).is_some())
+                });
+            if has_fast_data {
+                StorageMode::Jsonl
+            } else {
+                StorageMode::Files
+            }
+        }
+    };
+
+    let strict = manifest
+        .as_ref()
+        .and_then(|m| m.get("strict"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let schemas = read(&Layout::schema_registry(root, collection))?.unwrap_or_default();
+    let schema_registry =
+        SchemaRegistry::parse(strict, &schemas).unwrap_or_else(|_| SchemaRegistry::new(false));
+    Ok((storage, schema_registry))
+}
+
+/// Recover the writes a crashed writer left in a file-per-document
+/// collection's journal, unless there are none or a writer holds the
+/// store's lock.
+fn recover_files(root: &Path, collection: &str) -> Result<Option<RecoveryReport>> {
+    if TransactionLog::load_uncommitted(root, collection)?.is_empty() {
+        return Ok(None);
+    }
+    let Ok(lock) = WriteLock::acquire(root) else {
+        return Ok(None);
+    };
+    let report = writer::recover(root, collection)?;
+    lock.release();
+    Ok(Some(report))
+}
+
+/// Document index of a file-per-document collection read through `read`
+/// and `list_dir`: the saved index, or without one the `docs/` listing in
+/// ID order, without reading the documents.
+fn listed_index(
+    collection: &str,
+    read: &dyn Fn(&Path) -> Result<Option<String>>,
+    list_dir: &dyn Fn(&Path) -> Result<Vec<ZipEntry>>,
+) -> Result<IndexRegistry> {
+    let root = Path::new("");
+    let doc_index = read(&Layout::doc_index(root, collection))?;
+    let order = read(&Layout::order_file(root, collection))?;
+    let delta = read(&Layout::doc_index_delta(root, collection))?;
+    if doc_index.is_some() || order.is_some() || delta.is_some() {
+        let mut index =
+            IndexRegistry::parse(doc_index.as_deref(), order.as_deref()).unwrap_or_default();
+        if let Some(delta) = delta {
+            let _ = index.apply_delta(&delta);
+        }
+        return Ok(index);
+    }
+
+    let mut entries = list_dir(&Layout::docs_dir(root, collection))?;
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut index = IndexRegistry::new();
+    for entry in entries {
+        let Some(doc_id) = entry.name.strip_suffix(".json") else {
+            continue;
+        };
+        index.put(DocIndexEntry {
+            doc_id: Layout::doc_id_from_stem(doc_id),
+            schema_id: String::new(),
+            size: entry.size,
+            mtime: entry.mtime,
+        });
+    }
+    Ok(index)
+}
+
+/// The IDs of a document index, sorted.
+fn sorted_doc_ids(index: &IndexRegistry) -> SortedIds {
+    SortedIds::new(index.all_doc_ids().iter().map(String::as_str))
+}
+
+/// File contents as UTF-8.
+fn utf8(bytes: Option<Vec<u8>>) -> Result<Option<String>> {
+    bytes
+        .map(|bytes| {
+            String::from_utf8(bytes)
+                .map_err(|e| Error::Codec(format!("Invalid UTF-8 in file: {}", e)))
+        })
+        .transpose()
+}
+
+impl ReadStore for Engine {
+    fn storage(&self) -> StorageMode {
+        self.storage_mode()
+    }
+
+    fn get(&self, doc_id: &str) -> Result<Value> {
+        self.get_document(doc_id)
+    }
+
+    fn exists(&self, doc_id: &str) -> bool {
+        self.index.get(doc_id).is_some()
+    }
+
+    fn doc_ids(&self) -> Vec<String> {
+        Engine::doc_ids(self).to_vec()
+    }
+
+    fn scan(&self) -> Result<Vec<Value>> {
+        Scanner::collect(&mut Engine::scan(self, None, None)?)
+    }
+
+    fn len(&self) -> usize {
+        Engine::len(self)
+    }
+
+    fn stats(&self) -> CollectionStats {
+        Engine::stats(self)
+    }
+}
+
+/// Read a document's JSON file from a folder or an open archive.
+fn read_doc_file(
+    container: &ContainerFS,
+    archive: Option<&ZipReader>,
+    collection: &str,
+    doc_id: &str,
+) -> Result<String> {
+    let relative_path = format!(
+        "collections/{}/docs/{}.json",
+        collection,
+        Layout::doc_file_stem(doc_id)
+    );
+    match archive {
+        Some(archive) => String::from_utf8(archive.read_file(Path::new(&relative_path))?)
+            .map_err(|e| Error::Codec(format!("Invalid UTF-8 in file: {}", e))),
+        None => container.read_file_string(Path::new(&relative_path)),
+    }
+}
+
 /// Collection statistics.
 #[derive(Debug, Clone)]
 pub struct CollectionStats {
@@ -159,33 +925,63 @@ pub struct CollectionStats {
     pub schema_count: usize,
     pub total_size: u64,
     pub strict_mode: bool,
+    pub storage: StorageMode,
+    /// Storage statistics of a local JSONL collection
+    pub fast: Option<FastStoreStats>,
+    /// Writes of a crashed writer that opening the collection recovered
+    pub recovery: Option<RecoveryReport>,
 }
 
 /// Scanner for iterating over documents with optional filtering.
 pub struct Scanner {
     container: ContainerFS,
     collection: String,
+    fast: Option<Arc<FastStore>>,
+    archive: Option<Arc<ZipReader>>,
+    #[cfg(feature = "remote")]
+    remote: Option<Arc<RemoteJsonl>>,
     doc_ids: Vec<String>,
     predicate: Option<Predicate>,
     fields: Option<Vec<String>>,
+    /// Fields read from the fast store's column cache instead of the
+    /// documents, when it covers the projection and the predicate
+    cached_fields: Option<Vec<String>>,
     current_idx: usize,
 }
 
 impl Scanner {
+    /// Scan `doc_ids` of the engine's collection, reading from the same
+    /// source.
     fn new(
-        container: ContainerFS,
-        collection: String,
-        index: IndexRegistry,
+        engine: &Engine,
+        doc_ids: Vec<String>,
         predicate: Option<Predicate>,
         fields: Option<Vec<String>>,
     ) -> Result<Self> {
-        let doc_ids = index.all_doc_ids().to_vec();
+        let columns = engine.fast.as_ref().and_then(|store| store.column_cache());
+        let cached_fields = match (columns, &fields) {
+            (Some(columns), Some(fields)) => {
+                let mut read: Vec<&str> = fields.iter().map(String::as_str).collect();
+                read.extend(predicate.iter().flat_map(Predicate::fields));
+                read.sort_unstable();
+                read.dedup();
+                columns
+                    .covers(read.iter().copied())
+                    .then(|| read.into_iter().map(str::to_string).collect())
+            }
+            _ => None,
+        };
         Ok(Scanner {
-            container,
-            collection,
+            container: engine.container.clone(),
+            collection: engine.collection.clone(),
+            fast: engine.fast.clone(),
+            archive: engine.archive.clone(),
+            #[cfg(feature = "remote")]
+            remote: engine.remote.clone(),
             doc_ids,
             predicate,
             fields,
+            cached_fields,
             current_idx: 0,
         })
     }
@@ -196,32 +992,96 @@ impl Scanner {
             let doc_id = &self.doc_ids[self.current_idx].clone();
             self.current_idx += 1;
 
-            let relative_path = format!("collections/{}/docs/{}.json", self.collection, doc_id);
-            let content = match self.container.read_file_string(Path::new(&relative_path)) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+            if let Some(doc) = self.cached_doc(doc_id) {
+                if let Some(result) = self.filter_project(doc)? {
+                    return Ok(Some(result));
+                }
+                continue;
+            }
 
-            let doc = Codec::decode(&content)?;
+            #[cfg(feature = "remote")]
+            let fast_get = self
+                .fast
+                .as_ref()
+                .map(|store| store.get(doc_id))
+                .or_else(|| self.remote.as_ref().map(|reader| reader.get(doc_id)));
+            #[cfg(not(feature = "remote"))]
+            let fast_get = self.fast.as_ref().map(|store| store.get(doc_id));
 
-            // Apply predicate
-            if let Some(ref pred) = self.predicate {
-                if !Codec::apply_predicate(&doc, pred)? {
-                    continue;
+            let doc = match fast_get {
+                Some(read) => match read {
+                    Ok(doc) => doc,
+                    Err(Error::DocumentNotFound(_)) => continue,
+                    Err(e) => return Err(e),
+                },
+                None => {
+                    let read = read_doc_file(
+                        &self.container,
+                        self.archive.as_deref(),
+                        &self.collection,
+                        doc_id,
+                    );
+                    match read {
+                        Ok(content) => Codec::decode(&content)?,
+                        Err(_) => continue,
+                    }
                 }
+            };
+
+            if let Some(result) = self.filter_project(doc)? {
+                return Ok(Some(result));
             }
+        }
+        Ok(None)
+    }
 
-            // Apply projection
-            let result = if let Some(ref fields) = self.fields {
-                let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
-                Codec::extract_fields(&doc, &field_refs)?
-            } else {
-                doc
+    /// The cached fields of a document from the column cache, if the scan
+    /// reads only cached fields and the cache holds the document.
+    fn cached_doc(&self, doc_id: &str) -> Option<Value> {
+        let fields = self.cached_fields.as_ref()?;
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        self.fast.as_ref()?.cached_fields(doc_id, &fields)
+    }
+
+    /// Apply the predicate and the projection to a document; `None` if the
+    /// predicate rejects it.
+    fn filter_project(&self, doc: Value) -> Result<Option<Value>> {
+        if let Some(ref pred) = self.predicate {
+            if !Codec::apply_predicate(&doc, pred)? {
+                return Ok(None);
+            }
+        }
+
+        let result = if let Some(ref fields) = self.fields {
+            let field_refs: Vec<&str> = fields.iter().map(|s| s.as_str()).collect();
+            Codec::extract_fields(&doc, &field_refs)?
+        } else {
+            doc
+        };
+        Ok(Some(result))
+    }
+
+    /// Get the next chunk of matching documents within `size` (empty when
+    /// exhausted).
+    pub fn next_chunk(&mut self, size: ChunkSize) -> Result<Vec<Value>> {
+        let mut docs = Vec::new();
+        let mut bytes = 0;
+        while !size.is_full(docs.len(), bytes) {
+            let Some(doc) = self.next_doc()? else {
+                break;
             };
+            bytes += Codec::estimated_size(&doc);
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
 
-            return Ok(Some(result));
+    /// Turn this scanner into an iterator over chunks within `size`.
+    pub fn chunks(self, size: ChunkSize) -> ScannerChunks {
+        ScannerChunks {
+            scanner: self,
+            size,
         }
-        Ok(None)
     }
 
     /// Collect all remaining documents.
@@ -256,6 +1116,23 @@ impl Iterator for Scanner {
     }
 }
 
+/// Chunked document iterator, see [`Engine::scan_chunks`].
+pub struct ScannerChunks {
+    scanner: Scanner,
+    size: ChunkSize,
+}
+
+impl Iterator for ScannerChunks {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.scanner.next_chunk(self.size) {
+            Ok(docs) if docs.is_empty() => None,
+            result => Some(result),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde_json::json;
@@ -294,6 +1171,36 @@ mod tests {
         assert_eq!(doc["name"], "alice");
     }
 
+    #[test]
+    fn test_engine_knn() {
+        let (_tmp, root) = setup_test_collection();
+        let mut index = VectorIndex::new("emb", 2).unwrap();
+        index.insert("doc1", &[1.0, 0.0]).unwrap();
+        index.insert("doc2", &[0.0, 1.0]).unwrap();
+        index.save(&root, "test").unwrap();
+
+        let engine = Engine::open(&root, "test").unwrap();
+        let hits = engine.knn("emb", &[0.1, 1.0], 1, Metric::Cosine).unwrap();
+        assert_eq!(hits[0].doc_id, "doc2");
+        assert!(engine
+            .knn("missing", &[0.1, 1.0], 1, Metric::Cosine)
+            .is_err());
+
+        let mut store = FastStore::open(&root, "fast", 100).unwrap();
+        store.put("x", json!({})).unwrap();
+        store.put_vector("emb", "x", &[3.0, 4.0]).unwrap();
+        drop(store);
+        let engine = Engine::open(&root, "fast").unwrap();
+        let hits = engine.knn("emb", &[3.0, 4.0], 5, Metric::Dot).unwrap();
+        assert_eq!(
+            hits,
+            [Neighbor {
+                doc_id: "x".to_string(),
+                score: 25.0
+            }]
+        );
+    }
+
     #[test]
     fn test_engine_scan() {
         let (_tmp, root) = setup_test_collection();
@@ -305,6 +1212,59 @@ mod tests {
         assert_eq!(docs.len(), 3);
     }
 
+    #[test]
+    fn test_engine_scan_chunks() {
+        let (_tmp, root) = setup_test_collection();
+
+        let engine = Engine::open(&root, "test").unwrap();
+        let chunks: Vec<Vec<Value>> = engine
+            .scan_chunks(None, None, ChunkSize::docs(2))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+
+        let pred = Predicate::eq("name", "alice");
+        let chunks: Vec<Vec<Value>> = engine
+            .scan_chunks(Some(&pred), Some(&["name"]), ChunkSize::bytes(1))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(chunks, [vec![json!({"name": "alice"})]]);
+    }
+
+    #[test]
+    fn test_engine_aggregate() {
+        let (_tmp, root) = setup_test_collection();
+
+        let engine = Engine::open(&root, "test").unwrap();
+        let rows = engine
+            .aggregate(
+                None,
+                &[
+                    Aggregate::Count,
+                    Aggregate::Avg("age".into()),
+                    Aggregate::Min("name".into()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            rows,
+            [json!({"count": 3, "avg(age)": 30.0, "min(name)": "alice"})]
+        );
+    }
+
+    #[test]
+    fn test_engine_sample() {
+        let (_tmp, root) = setup_test_collection();
+
+        let engine = Engine::open(&root, "test").unwrap();
+        let sample = engine.sample(2, 1).unwrap();
+        assert_eq!(sample.len(), 2);
+        assert_eq!(engine.sample(2, 1).unwrap(), sample);
+        assert_eq!(engine.sample(10, 1).unwrap().len(), 3);
+    }
+
     #[test]
     fn test_engine_scan_with_predicate() {
         let (_tmp, root) = setup_test_collection();
@@ -343,5 +1303,268 @@ mod tests {
         assert_eq!(stats.collection, "test");
         assert_eq!(stats.doc_count, 3);
         assert_eq!(stats.schema_count, 1); // All docs have same schema
+        assert_eq!(stats.storage, StorageMode::Files);
+        assert!(stats.fast.is_none());
+    }
+
+    #[test]
+    fn test_engine_scan_uses_field_index() {
+        let (_tmp, root) = setup_test_collection();
+
+        let mut engine = Engine::open(&root, "test").unwrap();
+        assert_eq!(engine.create_index("name").unwrap(), 3);
+
+        // Writers keep the index up to date, saving it at checkpoints
+        let mut writer = SyncWriter::new(&root, "test").unwrap();
+        writer
+            .put("doc4", &json!({"name": "alice", "age": 41}))
+            .unwrap();
+        writer.delete("doc1").unwrap();
+        writer.checkpoint().unwrap();
+
+        let engine = Engine::open(&root, "test").unwrap();
+        let pred = Predicate::eq("name", "alice");
+        assert_eq!(
+            engine.field_indexes().candidates(&pred).unwrap(),
+            vec!["doc4".to_string()]
+        );
+
+        let mut scanner = engine.scan(Some(&pred), None).unwrap();
+        assert_eq!(scanner.remaining(), 1);
+        let docs = Scanner::collect(&mut scanner).unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0]["age"], 41);
+    }
+
+    #[test]
+    fn test_engine_text_search() {
+        let (_tmp, root) = setup_test_collection();
+
+        let mut engine = Engine::open(&root, "test").unwrap();
+        assert!(engine.search("fox").is_err());
+        let mut writer = SyncWriter::new(&root, "test").unwrap();
+        writer
+            .put("doc4", &json!({"body": "The quick brown fox"}))
+            .unwrap();
+        drop(writer);
+        engine = Engine::open(&root, "test").unwrap();
+        assert_eq!(engine.create_text_index("body").unwrap(), 1);
+
+        let mut writer = SyncWriter::new(&root, "test").unwrap();
+        writer
+            .put("doc5", &json!({"body": "A lazy brown dog"}))
+            .unwrap();
+        drop(writer);
+
+        let engine = Engine::open(&root, "test").unwrap();
+        assert_eq!(engine.search("BROWN").unwrap().remaining(), 2);
+        let mut scanner = engine.search("brown fox").unwrap();
+        let docs = Scanner::collect(&mut scanner).unwrap();
+        assert_eq!(docs, vec![json!({"body": "The quick brown fox"})]);
+
+        let pred = Predicate::parse("body MATCH 'dog'").unwrap();
+        assert_eq!(engine.scan(Some(&pred), None).unwrap().remaining(), 1);
+    }
+
+    #[test]
+    fn test_engine_reads_jsonl_collection() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        let mut store = FastStore::open(root, "fast", 100).unwrap();
+        for (id, name) in [("b", "bob"), ("a", "alice"), ("c", "carol")] {
+            store.put(id, json!({"name": name})).unwrap();
+        }
+        store.delete("c").unwrap();
+        // Overwriting keeps a document's place
+        store.put("b", json!({"name": "bobby"})).unwrap();
+        store.flush().unwrap();
+        drop(store);
+        assert_eq!(
+            StorageMode::load(root, "fast").unwrap(),
+            Some(StorageMode::Jsonl)
+        );
+
+        let engine = Engine::open(root, "fast").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Jsonl);
+        assert_eq!(engine.doc_ids(), ["b", "a"]);
+        assert_eq!(engine.get_document("a").unwrap(), json!({"name": "alice"}));
+        assert!(matches!(
+            engine.get_document("c"),
+            Err(Error::DocumentNotFound(_))
+        ));
+        assert_eq!(engine.get_document_at(0).unwrap()["name"], "bobby");
+
+        let pred = Predicate::eq("name", "alice");
+        let docs = Scanner::collect(&mut engine.scan(Some(&pred), None).unwrap()).unwrap();
+        assert_eq!(docs, [json!({"name": "alice"})]);
+        let stats = engine.stats();
+        assert_eq!(stats.doc_count, 2);
+        assert!(stats.total_size > 0);
+        assert_eq!(stats.storage, StorageMode::Jsonl);
+        let fast = stats.fast.unwrap();
+        assert_eq!(fast.doc_count, 2);
+        assert!(fast.last_flush.is_some());
+
+        let mut engine = engine;
+        assert!(engine.migrate(Ok).is_err());
+    }
+
+    #[test]
+    fn test_engine_scan_prefix() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        let mut writer = SyncWriter::new(root, "events").unwrap();
+        for id in [
+            "user/2/event/1",
+            "user/1/event/2",
+            "user/10/event/1",
+            "user/1/event/1",
+        ] {
+            writer.put(id, &json!({"id": id})).unwrap();
+        }
+        drop(writer);
+        assert!(Layout::doc_file(root, "events", "user/1/event/1").exists());
+
+        let mut engine = Engine::open(root, "events").unwrap();
+        let scan = |engine: &Engine| {
+            Scanner::collect(&mut engine.scan_prefix("user/1/", None, None).unwrap()).unwrap()
+        };
+        assert_eq!(
+            scan(&engine),
+            [
+                json!({"id": "user/1/event/1"}),
+                json!({"id": "user/1/event/2"})
+            ]
+        );
+        let pred = Predicate::eq("id", "user/1/event/2");
+        let docs =
+            Scanner::collect(&mut engine.scan_prefix("user/", Some(&pred), None).unwrap()).unwrap();
+        assert_eq!(docs.len(), 1);
+
+        // IDs are read back from the file names
+        engine.rebuild_index().unwrap();
+        assert_eq!(scan(&engine).len(), 2);
+        assert!(engine.get_document("user/10/event/1").is_ok());
+
+        assert_eq!(
+            engine.id_range("user/1/event/2".."user/2"),
+            ["user/1/event/2", "user/10/event/1"]
+        );
+        let docs =
+            Scanner::collect(&mut engine.scan_id_range("user/2".., None, None).unwrap()).unwrap();
+        assert_eq!(docs, [json!({"id": "user/2/event/1"})]);
+    }
+
+    #[test]
+    fn test_engine_recovers_crashed_writer() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        writer.put("doc1", &json!({"name": "alice"})).unwrap();
+        drop(writer);
+
+        // A put whose temp file was never renamed
+        let docs_dir = Layout::docs_dir(root, "test");
+        std::fs::write(docs_dir.join(".doc2.tmp"), r#"{"name": "bob"}"#).unwrap();
+        let mut journal = TransactionLog::open(root, "test").unwrap();
+        journal
+            .append(&JournalEntry::put("doc2", "schema", 15))
+            .unwrap();
+
+        let engine = Engine::open(root, "test").unwrap();
+        let recovery = engine.stats().recovery.unwrap();
+        assert_eq!(recovery.puts_replayed, 1);
+        assert_eq!(engine.doc_ids(), ["doc1", "doc2"]);
+        assert_eq!(engine.get_document("doc2").unwrap()["name"], "bob");
+
+        let engine = Engine::open(root, "test").unwrap();
+        assert!(engine.stats().recovery.is_none());
+    }
+
+    #[test]
+    fn test_engine_history() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        writer.put("doc1", &json!({"v": 1})).unwrap();
+        writer.put("doc2", &json!({"v": 1})).unwrap();
+        writer.patch("doc1", &json!({"v": 2})).unwrap();
+        writer.delete("doc1").unwrap();
+        drop(writer);
+
+        let engine = Engine::open(root, "test").unwrap();
+        let history = engine.history("doc1").unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(matches!(history[0], JournalEntry::Put { .. }));
+        assert!(matches!(history[2], JournalEntry::Delete { .. }));
+        assert!(history[0].timestamp() <= history[2].timestamp());
+        assert_eq!(engine.history("doc2").unwrap().len(), 1);
+        assert!(engine.history("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_engine_reads_zip_archive() {
+        let (_tmp, root) = setup_test_collection();
+        let out = TempDir::new().unwrap();
+        let archive = out.path().join("data.zds");
+        crate::container::pack(&root, &archive).unwrap();
+
+        let engine = Engine::open(&archive, "test").unwrap();
+        assert!(engine.container().is_zip());
+        assert_eq!(engine.doc_ids(), ["doc1", "doc2", "doc3"]);
+        assert_eq!(engine.get_document_at(1).unwrap()["name"], "bob");
+        assert_eq!(engine.stats().schema_count, 1);
+        let pred = Predicate::gt("age", 28);
+        let docs =
+            Scanner::collect(&mut engine.scan(Some(&pred), Some(&["name"])).unwrap()).unwrap();
+        assert_eq!(docs, [json!({"name": "alice"}), json!({"name": "charlie"})]);
+
+        // Without a saved index the documents are listed from the archive
+        std::fs::remove_file(Layout::doc_index(&root, "test")).unwrap();
+        std::fs::remove_file(Layout::order_file(&root, "test")).unwrap();
+        std::fs::remove_file(&archive).unwrap();
+        crate::container::pack(&root, &archive).unwrap();
+        let engine = Engine::open(&archive, "test").unwrap();
+        assert_eq!(engine.doc_ids(), ["doc1", "doc2", "doc3"]);
+        assert!(engine.index().get("doc2").unwrap().size > 0);
+        assert_eq!(engine.get_document("doc3").unwrap()["age"], 35);
+
+        // JSONL collections are read from a copy in memory
+        let mut store = FastStore::open(&root, "fast", 100).unwrap();
+        store.put("a", json!({"x": 1})).unwrap();
+        store.put("b", json!({"x": 2})).unwrap();
+        store.delete("a").unwrap();
+        store.put("c", json!({"x": 3})).unwrap();
+        drop(store);
+        std::fs::remove_file(&archive).unwrap();
+        crate::container::pack(&root, &archive).unwrap();
+        let engine = Engine::open(&archive, "fast").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Jsonl);
+        assert_eq!(engine.doc_ids(), ["b", "c"]);
+        assert_eq!(engine.get_document("c").unwrap()["x"], 3);
+        assert!(engine.get_document("a").is_err());
+        let pred = Predicate::gt("x", 2);
+        let docs = Scanner::collect(&mut engine.scan(Some(&pred), None).unwrap()).unwrap();
+        assert_eq!(docs, [json!({"x": 3})]);
+    }
+
+    #[test]
+    fn test_engine_partitions() {
+        let (_tmp, root) = setup_test_collection();
+
+        let engine = Engine::open(&root, "test").unwrap();
+        assert_eq!(engine.partitions(2), vec![0..2, 2..3]);
+        assert_eq!(engine.partitions(10).len(), 3);
+
+        let mut total = 0;
+        for range in engine.partitions(2) {
+            total += engine.scan_range(range, None, None).unwrap().count();
+        }
+        assert_eq!(total, 3);
+
+        // Out-of-range partitions are empty rather than an error
+        assert_eq!(engine.scan_range(5..9, None, None).unwrap().count(), 0);
     }
 }
@@ -23,9 +23,15 @@ pub enum Error {
     #[error("Document not found: {0}")]
     DocumentNotFound(String),
 
+    #[error("Document already exists: {0}")]
+    DocumentExists(String),
+
     #[error("Schema mismatch: expected {expected}, got {actual}")]
     SchemaMismatch { expected: String, actual: String },
 
+    #[error("Schema violation: {0}")]
+    SchemaViolation(String),
+
     #[error("Strict mode violation: {0}")]
     StrictModeViolation(String),
 
@@ -52,6 +58,13 @@ pub enum Error {
 
     #[error("Read-only mode: {0}")]
     ReadOnly(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[cfg(feature = "arrow")]
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] ::arrow::error::ArrowError),
 }
 
 impl Error {
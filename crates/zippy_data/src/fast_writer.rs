@@ -6,24 +6,78 @@
 //! - Binary index format for fast load/save
 //! - Sorted offset iteration for cache-friendly reads
 //! - mmap + parallel SIMD JSON parsing
+//!
+//! Crash safety: the append-only data file doubles as the write-ahead log.
+//! Flushes fsync the data before writing tombstones and the index, so on open
+//! a torn trailing line is truncated and lines written after the last saved
+//! index are replayed into it. The store's [`Durability`] can relax those
+//! syncs, or add one after every write.
+//!
+//! Data is split into [segments](crate::segment) of bounded size, so
+//! compaction only rewrites the segments that hold dead lines.
+//!
+//! Transactions ([`FastStore::begin`]) buffer their writes in memory. A commit
+//! logs a BEGIN record with the current file lengths to the collection's
+//! [journal](crate::TransactionLog), appends the lines and tombstones, then
+//! logs COMMIT; a BEGIN without a COMMIT is rolled back on open.
+//!
+//! Documents written with [`FastStore::put_with_ttl`] carry an expiry time in
+//! the index. Expired documents are hidden from reads and physically removed
+//! by the next compaction.
 
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{File, OpenOptions},
     io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::RangeBounds,
     path::{Path, PathBuf},
+    str::FromStr,
     sync::{Arc, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use memchr::memchr_iter;
 use memmap2::Mmap;
-use once_cell::sync::Lazy;
-use parking_lot::RwLock;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::{Mutex, RwLock};
 use rayon::prelude::*;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-
-use crate::{lock::WriteLock, Error, Layout, Result};
+use tempfile::TempDir;
+
+use crate::{
+    aggregate::{Aggregate, Aggregator, GroupBy},
+    blob::BlobStore,
+    columns::ColumnCache,
+    compression::{self, Compression, DataWriter},
+    config::{CollectionConfig, KeyOrder},
+    container::ZipReader,
+    dedup::{self, Dedup},
+    docref::DocRef,
+    encryption::{self, Cipher, EncryptionKey},
+    engine::{CollectionStats, StorageMode},
+    field_index::FieldIndexes,
+    id_map::IdMap,
+    ids::{IdSequence, IdStrategy},
+    index::{DocIndexEntry, IndexRegistry},
+    json_schema::JsonSchema,
+    keys::{id_bounds, SortedIds},
+    lock::{ReadLock, WriteLock},
+    manifest::{self, RootManifest},
+    sample,
+    schema::SchemaRegistry,
+    segment::{self, MmapHints, ReadCache, Segment, SegmentView, DEFAULT_SEGMENT_SIZE},
+    sharded::ShardedWriter,
+    snapshot::Snapshot,
+    store::{ReadStore, Store},
+    txlog::{JournalEntry, TransactionLog},
+    vectors::{Metric, Neighbor, VectorIndex},
+    versions::{DocVersion, Versions},
+    watch::{ChangeEvent, Watcher},
+    Codec, Durability, Error, Layout, Predicate, Result,
+};
 
 /// Open mode for ZDS stores.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
@@ -46,14 +100,327 @@ static ROOT_CACHE: Lazy<RwLock<RootCache>> = Lazy::new(|| RwLock::new(HashMap::n
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct IndexEntry {
+    /// Segment-qualified offset (see [`segment`](crate::segment))
     pub offset: u64,
     pub length: u32,
-    _padding: u32, // Explicit padding for alignment
+    /// CRC32 of the line, excluding its newline
+    pub checksum: u32,
+}
+
+/// CRC32 of a data file line, excluding its newline.
+pub(crate) fn line_checksum(line: &[u8]) -> u32 {
+    crc32fast::hash(line.strip_suffix(b"\n").unwrap_or(line))
+}
+
+/// Current time in milliseconds since the Unix epoch.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Shared copy of a schema ID.
+fn intern(ids: &mut FxHashSet<Arc<str>>, id: &str) -> Arc<str> {
+    if let Some(id) = ids.get(id) {
+        return id.clone();
+    }
+    let id: Arc<str> = Arc::from(id);
+    ids.insert(id.clone());
+    id
+}
+
+/// Parse a raw data line into its document, without `_id`.
+pub(crate) fn parse_raw_doc(line: &[u8]) -> Result<Value> {
+    let mut doc: Value = serde_json::from_slice(line)?;
+    if let Value::Object(obj) = &mut doc {
+        obj.remove("_id");
+    }
+    Ok(doc)
+}
+
+/// Check that a raw line's `_id` is the ID it's written under, which is the
+/// one an index rebuild will find it by.
+pub(crate) fn check_line_id(doc_id: &str, line: &[u8]) -> Result<()> {
+    match FastStore::extract_id_fast(line) {
+        Some(id) if id == doc_id => Ok(()),
+        Some(id) => Err(Error::Validation(format!(
+            "line has _id '{}' but is written as '{}'",
+            id, doc_id
+        ))),
+        None => Err(Error::Validation(format!(
+            "line written as '{}' has no string or integer _id",
+            doc_id
+        ))),
+    }
+}
+
+/// Kind of damage found by [`FastStore::verify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptionKind {
+    /// The line extends past the end of the data file
+    Truncated,
+    /// The line doesn't match the checksum recorded in the index
+    ChecksumMismatch,
+    /// The line is not valid JSON
+    InvalidJson,
+    /// The line's `_id` differs from the indexed ID
+    IdMismatch,
+}
+
+impl CorruptionKind {
+    /// Short machine-readable name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CorruptionKind::Truncated => "truncated",
+            CorruptionKind::ChecksumMismatch => "checksum_mismatch",
+            CorruptionKind::InvalidJson => "invalid_json",
+            CorruptionKind::IdMismatch => "id_mismatch",
+        }
+    }
+}
+
+impl std::fmt::Display for CorruptionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A damaged document line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptEntry {
+    pub doc_id: String,
+    /// Data segment holding the line (0 for `data.jsonl`)
+    pub segment: u32,
+    /// Offset of the line within its (uncompressed) segment
+    pub offset: u64,
+    pub kind: CorruptionKind,
+}
+
+/// Corruption report from [`FastStore::verify`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of live lines checked
+    pub checked: usize,
+    /// Damaged lines, in file order
+    pub corrupt: Vec<CorruptEntry>,
+    /// Whether `index.bin` exists and passes its checksum
+    pub index_ok: bool,
+}
+
+impl VerifyReport {
+    /// Check that no corruption was found.
+    pub fn is_ok(&self) -> bool {
+        self.index_ok && self.corrupt.is_empty()
+    }
+}
+
+/// Deletion record appended to `meta/tombstones.jsonl`.
+///
+/// `offset` is the end of the data when the delete happened, so the
+/// tombstone only applies to lines written before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Tombstone {
+    #[serde(rename = "_id")]
+    pub(crate) id: String,
+    pub(crate) offset: u64,
+}
+
+/// Insertion position and metadata of an indexed document, saved as the
+/// collection's `order.ids` and `doc_index.jsonl`.
+#[derive(Debug, Clone)]
+struct DocMeta {
+    /// Insertion sequence number, kept when the document is overwritten
+    seq: u64,
+    /// Time of the last write (Unix seconds, 0 if unknown)
+    mtime: u64,
+    /// Schema of the last write (`None` for raw lines)
+    schema_id: Option<Arc<str>>,
+}
+
+/// A write buffered by an open transaction.
+enum TxnOp {
+    Put {
+        doc_id: String,
+        line: Vec<u8>,
+        expires_at: Option<u64>,
+        schema_id: Option<Arc<str>>,
+    },
+    Delete {
+        doc_id: String,
+    },
+}
+
+impl TxnOp {
+    fn doc_id(&self) -> &str {
+        match self {
+            TxnOp::Put { doc_id, .. } | TxnOp::Delete { doc_id } => doc_id,
+        }
+    }
+}
+
+/// Where a transaction without a COMMIT record started.
+#[derive(Debug, Clone, Copy)]
+struct TxnStart {
+    segment: u32,
+    segment_len: u64,
+    offset: u64,
+    tombstones_len: u64,
+}
+
+/// The saved `index.bin` and the changes appended to `index.delta` on top
+/// of it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SavedIndex {
+    /// Checksum of `index.bin`, which the delta's header repeats
+    checksum: u32,
+    /// Length of the delta up to its last complete batch, 0 if it has none
+    delta_len: u64,
+    /// Changes in those batches
+    delta_changes: usize,
+}
+
+impl SavedIndex {
+    fn new(checksum: u32) -> Self {
+        SavedIndex {
+            checksum,
+            delta_len: 0,
+            delta_changes: 0,
+        }
+    }
+}
+
+/// Storage statistics of a collection, from [`FastStore::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FastStoreStats {
+    pub doc_count: usize,
+    /// Logical (uncompressed) size of the data segments
+    pub data_bytes: u64,
+    /// Bytes of the lines documents currently point to
+    pub live_bytes: u64,
+    /// Bytes of deleted or overwritten lines
+    pub dead_bytes: u64,
+    pub dead_ratio: f64,
+    /// Size of `index.bin` and `index.delta` as of the last flush
+    pub index_bytes: u64,
+    /// Memory held by the in-memory document index
+    pub index_memory_bytes: u64,
+    /// Lowest and highest index offset of a live line (segment-qualified,
+    /// see [`segment`](crate::segment)), `None` for an empty store
+    pub min_offset: Option<u64>,
+    pub max_offset: Option<u64>,
+    /// Time of the last flush by any handle (dropping a read-write handle
+    /// flushes), in milliseconds since the Unix epoch, `None` if the
+    /// collection was never flushed
+    pub last_flush: Option<u64>,
+    pub segments: usize,
+    pub tombstones: usize,
+    pub compression: Compression,
+    /// Documents stored as a reference to an identical document's line
+    pub dedup_docs: usize,
+    /// Bytes those documents would take as lines of their own
+    pub dedup_bytes: u64,
+}
+
+/// When to compact the data file automatically on flush/close.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompactionPolicy {
+    /// Minimum fraction of dead (deleted or overwritten) bytes in the data file
+    pub min_dead_ratio: f64,
+    /// Minimum number of dead bytes, so small files aren't rewritten constantly
+    pub min_dead_bytes: u64,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        CompactionPolicy {
+            min_dead_ratio: 0.3,
+            min_dead_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl CompactionPolicy {
+    /// Compact once `min_dead_ratio` of the file is garbage.
+    pub fn with_ratio(min_dead_ratio: f64) -> Self {
+        CompactionPolicy {
+            min_dead_ratio,
+            ..Default::default()
+        }
+    }
+
+    /// Check whether a file with `dead_bytes` of `total_bytes` needs compaction.
+    pub fn should_compact(&self, dead_bytes: u64, total_bytes: u64) -> bool {
+        total_bytes > 0
+            && dead_bytes >= self.min_dead_bytes
+            && dead_bytes as f64 / total_bytes as f64 >= self.min_dead_ratio
+    }
+}
+
+/// How [`FastStore::put_batch_with_policy`] handles IDs that already exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Replace existing documents (same as [`FastStore::put`])
+    #[default]
+    Overwrite,
+    /// Keep existing documents and skip the incoming ones
+    SkipExisting,
+    /// Reject the whole batch if any ID already exists
+    FailOnConflict,
+}
+
+impl ConflictPolicy {
+    /// Short machine-readable name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::SkipExisting => "skip_existing",
+            ConflictPolicy::FailOnConflict => "fail_on_conflict",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip_existing" => Ok(ConflictPolicy::SkipExisting),
+            "fail_on_conflict" => Ok(ConflictPolicy::FailOnConflict),
+            other => Err(Error::Validation(format!(
+                "unknown conflict policy '{}' (expected overwrite, skip_existing or fail_on_conflict)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Binary index header (magic + version + count).
 const INDEX_MAGIC: u32 = 0x5A445349; // "ZDSI"
-const INDEX_VERSION: u32 = 1;
+const INDEX_VERSION: u32 = 3;
+
+/// Index delta header (magic + version + checksum of the index it extends).
+const INDEX_DELTA_MAGIC: u32 = 0x5A445344; // "ZDSD"
+const INDEX_DELTA_VERSION: u32 = 1;
+const INDEX_DELTA_HEADER: usize = 12;
+
+/// Index delta record kinds.
+const DELTA_PUT: u8 = 0;
+const DELTA_PUT_EXPIRING: u8 = 1;
+const DELTA_REMOVE: u8 = 2;
+
+/// Smallest number of index changes folded into `index.bin`, however small
+/// the index.
+const INDEX_DELTA_MIN: usize = 1024;
+
+/// Documents parsed at once by [`FastStore::aggregate`].
+const AGGREGATE_CHUNK: usize = 64 * 1024;
 
 /// High-performance JSONL-based store.
 pub struct FastStore {
@@ -61,17 +428,107 @@ pub struct FastStore {
     root: PathBuf,
     #[allow(dead_code)]
     collection: String,
-    data_file: PathBuf,
     index_file: PathBuf,
-    index: FxHashMap<String, IndexEntry>, // FxHashMap for faster string hashing
-    writer: Option<BufWriter<File>>,
+    index_delta_file: PathBuf,
+    /// What `index.bin` and `index.delta` hold, `None` if the index is to
+    /// be saved in full on the next flush
+    saved_index: Option<SavedIndex>,
+    /// IDs whose entry or expiry time changed since the index was saved
+    /// (tracked while `saved_index` is set)
+    index_changes: FxHashSet<String>,
+    /// Document ID -> location of its line
+    index: IdMap<IndexEntry>,
+    /// Writer for the active (last) segment
+    writer: Option<DataWriter>,
+    /// Data segments by number; the last one is appended to
+    segments: Vec<Segment>,
+    /// Format of new segments (offsets are always uncompressed)
+    compression: Compression,
+    /// Seals data blocks and the index of an encrypted collection
+    cipher: Option<Arc<Cipher>>,
+    /// Size at which the active segment is sealed and a new one started
+    segment_size: u64,
+    /// Index offset of the end of the active segment
     current_offset: u64,
     pending_count: usize,
     batch_size: usize,
     /// Memory-mapped view for fast reads (lazily initialized)
-    mmap: Option<Arc<Mmap>>,
+    mmap: Option<Arc<SegmentView>>,
+    /// Paging hints of the memory maps
+    mmap_hints: MmapHints,
     /// Open mode (read-only or read-write)
     mode: OpenMode,
+    /// Secondary field indexes (saved on flush when dirty)
+    field_indexes: FieldIndexes,
+    field_indexes_dirty: bool,
+    /// Vector indexes by name (saved on flush when dirty)
+    vectors: BTreeMap<String, VectorIndex>,
+    vectors_dirty: bool,
+    /// Columnar projection cache, once built (saved on flush when dirty)
+    columns: Option<ColumnCache>,
+    columns_dirty: bool,
+    /// Unexpired index entries sorted by offset, and the time (Unix ms) the
+    /// first of them expires (built lazily, reset on writes)
+    ordered: OnceCell<(Arc<Vec<IndexEntry>>, u64)>,
+    /// Indexed IDs in sorted order, for prefix and range scans (built on
+    /// open with [`KeyOrder::Sorted`], else on the first scan, then kept up
+    /// to date)
+    sorted_ids: OnceCell<SortedIds>,
+    /// Total length of the live lines in the data segments
+    live_bytes: u64,
+    /// Doc ID -> expiry time (Unix ms) of indexed documents written with a TTL
+    expires: FxHashMap<String, u64>,
+    /// Deleted doc ID -> index offset at deletion (while dead lines remain)
+    tombstones: FxHashMap<String, u64>,
+    /// Length of the tombstone file read so far (see [`refresh`](Self::refresh))
+    tombstones_read: u64,
+    /// Deletions not yet appended to the tombstone file
+    pending_tombstones: Vec<Tombstone>,
+    /// Automatic compaction policy (disabled when `None`)
+    compaction: Option<CompactionPolicy>,
+    /// When writes are synced
+    durability: Durability,
+    /// Writes of the open transaction, if any
+    txn: Option<Vec<TxnOp>>,
+    /// Collection journal (opened on the first commit)
+    journal: Option<TransactionLog>,
+    /// Marks the collection open in the [`ZDSRoot`] it was opened from
+    root_handle: Option<Arc<()>>,
+    /// Schema registry of a strict-mode collection; writes of another
+    /// schema fail with [`Error::SchemaMismatch`]
+    schema: Option<SchemaRegistry>,
+    /// JSON Schema attached to the collection, checked on every write
+    json_schema: Option<JsonSchema>,
+    /// Insertion order and metadata of the indexed documents
+    doc_meta: FxHashMap<String, DocMeta>,
+    /// Sequence number of the next new document
+    next_seq: u64,
+    /// Interned schema IDs of `doc_meta`
+    schema_ids: FxHashSet<Arc<str>>,
+    /// Whether `doc_meta` is saved on flush (only for collections stored
+    /// as JSONL, so a `docs/` index is never overwritten)
+    saves_doc_meta: bool,
+    /// Receivers of this handle's changes (see [`subscribe`](Self::subscribe))
+    subscribers: Vec<std::sync::mpsc::Sender<ChangeEvent>>,
+    /// Marker of the live [`ShardedWriter`], if any (see
+    /// [`sharded_writer`](Self::sharded_writer))
+    shard_handle: Option<Weak<()>>,
+    doc_meta_dirty: bool,
+    /// Binary attachments (loaded on open if the collection has any,
+    /// otherwise on the first [`put_blob`](Self::put_blob))
+    blobs: Option<BlobStore>,
+    /// Previous versions of overwritten documents (see
+    /// [`set_version_retention`](Self::set_version_retention))
+    versions: Versions,
+    /// Documents sharing identical lines, if the collection deduplicates
+    /// or did (see [`set_dedup`](Self::set_dedup))
+    dedup: Option<Dedup>,
+    /// Counter of sequential generated IDs (loaded on first use, see
+    /// [`put_auto`](Self::put_auto))
+    id_sequence: Option<IdSequence>,
+    /// Directory holding an in-memory store, removed when the store is
+    /// dropped (see [`open_in_memory`](Self::open_in_memory))
+    scratch: Option<TempDir>,
 }
 
 impl FastStore {
@@ -84,6 +541,95 @@ impl FastStore {
         Self::open_with_mode(root, collection, batch_size, OpenMode::ReadWrite)
     }
 
+    /// Create an empty store kept in memory, for tests and caches.
+    ///
+    /// The store lives in a temporary directory on a RAM-backed file
+    /// system (`/dev/shm` on Linux; the system temp directory elsewhere)
+    /// and is deleted when the store is dropped. It has the full API of a
+    /// store on disk.
+    pub fn open_in_memory(collection: impl AsRef<str>, batch_size: usize) -> Result<Self> {
+        let scratch = memory_dir()?;
+        let mut store = Self::open(scratch.path(), collection, batch_size)?;
+        store.scratch = Some(scratch);
+        Ok(store)
+    }
+
+    /// Copy a collection into memory (see
+    /// [`open_in_memory`](Self::open_in_memory)), pinning a hot dataset in
+    /// RAM.
+    ///
+    /// The copy holds the collection's flushed documents. Writes go to the
+    /// copy only and are lost when the store is dropped.
+    pub fn load_in_memory(
+        root: impl AsRef<Path>,
+        collection: impl AsRef<str>,
+        batch_size: usize,
+    ) -> Result<Self> {
+        let collection = collection.as_ref();
+        let source = Layout::collection_dir(root.as_ref(), collection);
+        if !source.is_dir() {
+            return Err(Error::CollectionNotFound(collection.to_string()));
+        }
+        let scratch = memory_dir()?;
+        let dest = Layout::collection_dir(scratch.path(), collection);
+        std::fs::create_dir_all(&dest)?;
+        copy_dir(&source, &dest)?;
+        let mut store = Self::open(scratch.path(), collection, batch_size)?;
+        store.scratch = Some(scratch);
+        Ok(store)
+    }
+
+    /// Copy a collection out of a ZIP archive into memory (see
+    /// [`open_in_memory`](Self::open_in_memory)) and open it read-only.
+    pub fn load_from_archive(archive: &ZipReader, collection: impl AsRef<str>) -> Result<Self> {
+        let collection = collection.as_ref();
+        let prefix = format!("{}/{}/", Layout::COLLECTIONS_DIR, collection);
+        let scratch = memory_dir()?;
+        let mut found = false;
+        for name in archive.file_names() {
+            let Some(relative) = name.strip_prefix(&prefix) else {
+                continue;
+            };
+            found = true;
+            let relative = Path::new(relative);
+            // Entry names come from the archive; keep them inside the copy
+            if relative
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)))
+            {
+                continue;
+            }
+            let dest = Layout::collection_dir(scratch.path(), collection).join(relative);
+            if name.ends_with('/') {
+                std::fs::create_dir_all(&dest)?;
+                continue;
+            }
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&dest, archive.read_file(Path::new(&name))?)?;
+        }
+        if !found {
+            return Err(Error::CollectionNotFound(collection.to_string()));
+        }
+        let mut store = Self::open_with_mode(scratch.path(), collection, 1, OpenMode::Read)?;
+        store.scratch = Some(scratch);
+        Ok(store)
+    }
+
+    /// Whether the store was created by
+    /// [`open_in_memory`](Self::open_in_memory),
+    /// [`load_in_memory`](Self::load_in_memory) or
+    /// [`load_from_archive`](Self::load_from_archive).
+    pub fn is_in_memory(&self) -> bool {
+        self.scratch.is_some()
+    }
+
+    /// Path of the root directory holding the store.
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
     /// Open a fast store with explicit mode.
     pub fn open_with_mode(
         root: impl AsRef<Path>,
@@ -94,51 +640,185 @@ impl FastStore {
         let root = root.as_ref().to_path_buf();
         let collection = collection.as_ref().to_string();
 
+        let config = CollectionConfig::load(&root, &collection)?;
         // Create directory structure (only in ReadWrite mode)
         let meta_dir = Layout::meta_dir(&root, &collection);
         if mode == OpenMode::ReadWrite {
-            std::fs::create_dir_all(&meta_dir)?;
+            match config.storage {
+                Some(StorageMode::Files) => {
+                    return Err(Error::Validation(format!(
+                        "collection '{}' is configured to store documents as files",
+                        collection
+                    )));
+                }
+                Some(StorageMode::Jsonl) => std::fs::create_dir_all(&meta_dir)?,
+                // Documents already stored as files would be hidden from
+                // readers once the collection is recorded as JSONL
+                None if Layout::has_file_docs(&root, &collection) => {
+                    return Err(Error::Validation(format!(
+                        "collection '{}' stores documents as files",
+                        collection
+                    )));
+                }
+                // Lets the Engine find the documents
+                None => {
+                    std::fs::create_dir_all(&meta_dir)?;
+                    StorageMode::Jsonl.save(&root, &collection)?;
+                }
+            }
         }
 
-        let data_file = meta_dir.join("data.jsonl");
+        let cipher = encryption::load_cipher(&root, &collection)?;
+        let compression = Self::configured_compression(&root, &collection, cipher.as_ref())?;
         let index_file = meta_dir.join("index.bin");
+        let index_delta_file = Layout::index_delta(&root, &collection);
+
+        // A transaction that didn't commit is rolled back (or, read-only,
+        // ignored)
+        let interrupted = Self::interrupted_transaction(&root, &collection)?;
+        if let (Some(start), OpenMode::ReadWrite) = (interrupted, mode) {
+            Self::undo_transaction(&root, &collection, start)?;
+        }
+        let interrupted = interrupted.filter(|_| mode == OpenMode::Read);
+
+        let mut segments =
+            Self::load_segments(&root, &collection, compression, cipher.is_some(), mode)?;
+        if let Some(start) = interrupted {
+            segments.retain(|s| s.no <= start.segment);
+        }
+        if segments.is_empty() {
+            segments.push(Segment {
+                no: 0,
+                compression,
+                len: 0,
+                encrypted: cipher.is_some(),
+            });
+        }
+        let view = SegmentView::open(
+            &root,
+            &collection,
+            &segments,
+            None,
+            cipher.as_ref(),
+            MmapHints::default(),
+        )?;
 
         // Load index (try binary first, fall back to text, then rebuild)
-        let mut index = FxHashMap::default();
-        let current_offset = if data_file.exists() {
-            if index_file.exists() {
-                // Try binary format first
-                if Self::load_index_binary(&index_file, &mut index).is_err() {
+        let mut index = IdMap::default();
+        let mut expires = FxHashMap::default();
+        let mut saved_index = None;
+        if index_file.exists() {
+            // Try binary format first
+            match Self::load_index_binary(
+                &index_file,
+                &index_delta_file,
+                cipher.as_deref(),
+                &encryption::file_aad(&root, &collection, &index_file),
+                &mut index,
+                &mut expires,
+            ) {
+                Ok(saved) => saved_index = Some(saved),
+                Err(e @ Error::Encryption(_)) => return Err(e),
+                Err(_) => {
                     // Fall back to text format
                     index.clear();
-                    let _ = Self::load_index_text(&index_file, &mut index);
+                    expires.clear();
+                    if Self::load_index_text(&index_file, &mut index).is_ok() {
+                        Self::fill_checksums(view.as_ref(), &mut index)?;
+                    }
                 }
             }
-            if index.is_empty() {
-                // Rebuild index from data file
-                Self::rebuild_index(&data_file, &mut index)?;
+        }
+        let mut indexed_end = 0;
+        let repaired;
+        if let Some(view) = &view {
+            let stale = Self::reindex_stale_segments(view, &segments, &mut index)?;
+            // Replay lines written after the index was last saved
+            indexed_end = index
+                .values()
+                .map(|e| e.offset + e.length as u64)
+                .max()
+                .unwrap_or(0);
+            let end = interrupted.map_or(view.end(), |start| view.end().min(start.offset));
+            Self::rebuild_index(view, indexed_end, end, &mut index)?;
+            repaired = stale || indexed_end < end;
+        } else {
+            repaired = !index.is_empty();
+            index.clear();
+        }
+        // A delta would only hold later writes, which move the indexed end
+        // past the lines replayed here: the index is saved in full instead
+        if repaired {
+            saved_index = None;
+        }
+        let current_offset = segments.last().map_or(0, Segment::end);
+
+        // Re-apply deletions: a rebuilt or replayed index would otherwise resurrect them
+        let tombstone_file = Layout::tombstone_file(&root, &collection);
+        if tombstone_file.exists() {
+            Self::recover_file(&tombstone_file, mode)?;
+        }
+        let mut tombstones = FxHashMap::default();
+        let tombstones_len = interrupted.map(|start| start.tombstones_len);
+        let (loaded, tombstones_read) = Self::load_tombstones(&tombstone_file, 0, tombstones_len)?;
+        for tombstone in loaded {
+            if index
+                .get(&tombstone.id)
+                .is_some_and(|e| e.offset < tombstone.offset)
+            {
+                index.remove(&tombstone.id);
             }
-            std::fs::metadata(&data_file)?.len()
+            let offset = tombstones.entry(tombstone.id).or_insert(0);
+            *offset = tombstone.offset.max(*offset);
+        }
+        // Replayed lines were written after the saved expiry times
+        expires.retain(|id, _| index.get(id).is_some_and(|e| e.offset < indexed_end));
+        let dedup = Dedup::load(&root, &collection, &mut index, &tombstones, |entry| {
+            Self::holds_line(view.as_ref(), entry)
+        })?;
+        let versions = Versions::load(&root, &collection, &index, &tombstones, |id| {
+            dedup.as_ref().is_some_and(|d| d.is_ref(id))
+        })?;
+
+        let saves_doc_meta = StorageMode::detect(&root, &collection)? == StorageMode::Jsonl;
+        let mut schema_ids = FxHashSet::default();
+        let (doc_meta, doc_meta_dirty) = if saves_doc_meta {
+            Self::load_doc_meta(&root, &collection, &index, &mut schema_ids)
         } else {
-            0
+            Self::order_doc_meta(FxHashMap::default(), &index)
         };
+        let next_seq = doc_meta.len() as u64;
 
         // Open writer in append mode with larger buffer (only in ReadWrite mode)
-        let writer = if mode == OpenMode::ReadWrite {
-            let file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&data_file)?;
-            Some(BufWriter::with_capacity(256 * 1024, file)) // 256KB buffer
-        } else {
-            None
+        let writer = match (mode, segments.last()) {
+            (OpenMode::ReadWrite, Some(active)) => Some(DataWriter::append(
+                &active.path(&root, &collection),
+                active.compression,
+                cipher.as_ref(),
+            )?),
+            _ => None,
         };
 
-        // Create mmap if data file exists and has content
-        let mmap = if data_file.exists() && current_offset > 0 {
-            let file = File::open(&data_file)?;
-            let mmap = unsafe { Mmap::map(&file)? };
-            Some(Arc::new(mmap))
+        // Keep the mmap if the segments have content
+        let mmap = view.map(Arc::new);
+
+        let field_indexes = FieldIndexes::load(&root, &collection)?;
+        let vectors = VectorIndex::load_all(&root, &collection)?
+            .into_iter()
+            .map(|index| (index.name().to_string(), index))
+            .collect();
+        let columns = ColumnCache::load(&root, &collection).unwrap_or_default();
+        let live_bytes = index.values().map(|e| e.length as u64).sum();
+        let sorted_ids = OnceCell::new();
+        if config.key_order == KeyOrder::Sorted {
+            let _ = sorted_ids.set(SortedIds::new(index.keys()));
+        }
+        let schema = SchemaRegistry::load(&root, &collection)
+            .ok()
+            .filter(SchemaRegistry::is_strict);
+        let json_schema = JsonSchema::load(&root, &collection)?;
+        let blobs = if BlobStore::exists(&root, &collection) {
+            Some(BlobStore::open(&root, &collection, mode, cipher.clone())?)
         } else {
             None
         };
@@ -146,18 +826,134 @@ impl FastStore {
         Ok(FastStore {
             root,
             collection,
-            data_file,
             index_file,
+            index_delta_file,
+            saved_index,
+            index_changes: FxHashSet::default(),
             index,
             writer,
+            segments,
+            compression,
+            cipher,
+            segment_size: DEFAULT_SEGMENT_SIZE,
             current_offset,
             pending_count: 0,
             batch_size,
             mmap,
+            mmap_hints: MmapHints::default(),
             mode,
+            field_indexes,
+            field_indexes_dirty: false,
+            vectors,
+            vectors_dirty: false,
+            columns,
+            columns_dirty: false,
+            ordered: OnceCell::new(),
+            sorted_ids,
+            live_bytes,
+            expires,
+            tombstones,
+            tombstones_read,
+            pending_tombstones: Vec::new(),
+            compaction: config.compaction_policy(),
+            durability: Durability::default(),
+            txn: None,
+            journal: None,
+            root_handle: None,
+            schema,
+            json_schema,
+            doc_meta,
+            next_seq,
+            schema_ids,
+            saves_doc_meta,
+            doc_meta_dirty,
+            subscribers: Vec::new(),
+            shard_handle: None,
+            blobs,
+            versions,
+            dedup,
+            id_sequence: None,
+            scratch: None,
         })
     }
 
+    /// Insertion order and metadata of the indexed documents, from the
+    /// collection's `order.ids` and `doc_index.jsonl`. Returns whether they
+    /// need saving again.
+    fn load_doc_meta(
+        root: &Path,
+        collection: &str,
+        index: &IdMap<IndexEntry>,
+        schema_ids: &mut FxHashSet<Arc<str>>,
+    ) -> (FxHashMap<String, DocMeta>, bool) {
+        let registry = IndexRegistry::load(root, collection).unwrap_or_default();
+        let mut doc_meta = FxHashMap::default();
+        doc_meta.reserve(index.len());
+        for entry in registry.iter() {
+            if !index.contains_key(&entry.doc_id) || doc_meta.contains_key(&entry.doc_id) {
+                continue;
+            }
+            let meta = DocMeta {
+                seq: doc_meta.len() as u64,
+                mtime: entry.mtime,
+                schema_id: (!entry.schema_id.is_empty())
+                    .then(|| intern(schema_ids, &entry.schema_id)),
+            };
+            doc_meta.insert(entry.doc_id.clone(), meta);
+        }
+        let stale = doc_meta.len() != registry.len();
+        let (doc_meta, missing) = Self::order_doc_meta(doc_meta, index);
+        (doc_meta, stale || missing)
+    }
+
+    /// Append the indexed documents missing from `doc_meta` in file order,
+    /// with an unknown mtime. These were written after the order was last
+    /// saved (or by a version that didn't save it). Returns whether any
+    /// were missing.
+    fn order_doc_meta(
+        mut doc_meta: FxHashMap<String, DocMeta>,
+        index: &IdMap<IndexEntry>,
+    ) -> (FxHashMap<String, DocMeta>, bool) {
+        let mut missing: Vec<(&str, u64)> = index
+            .iter()
+            .filter(|(id, _)| !doc_meta.contains_key(*id))
+            .map(|(id, e)| (id, e.offset))
+            .collect();
+        missing.sort_unstable_by_key(|&(_, offset)| offset);
+        let found = !missing.is_empty();
+        for (doc_id, _) in missing {
+            let meta = DocMeta {
+                seq: doc_meta.len() as u64,
+                mtime: 0,
+                schema_id: None,
+            };
+            doc_meta.insert(doc_id.to_string(), meta);
+        }
+        (doc_meta, found)
+    }
+
+    /// JSON Schema attached to the collection, if any.
+    pub fn json_schema(&self) -> Option<&JsonSchema> {
+        self.json_schema.as_ref()
+    }
+
+    /// Attach a JSON Schema that every later write must match, or detach
+    /// it with `None`. Documents already stored are not checked; see
+    /// `zippy validate --schema`.
+    pub fn set_json_schema(&mut self, schema: Option<JsonSchema>) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot set a schema in read-only mode".to_string(),
+            ));
+        }
+        match &schema {
+            Some(schema) => schema.save(&self.root, &self.collection)?,
+            None => JsonSchema::remove(&self.root, &self.collection)?,
+        }
+        self.json_schema = schema;
+        Ok(())
+    }
+
     /// Get the open mode.
     pub fn mode(&self) -> OpenMode {
         self.mode
@@ -170,860 +966,5976 @@ impl FastStore {
 
     /// Refresh mmap after writes (call after flush for read consistency)
     pub fn refresh_mmap(&mut self) -> Result<()> {
-        if self.data_file.exists() && self.current_offset > 0 {
-            let file = File::open(&self.data_file)?;
-            let mmap = unsafe { Mmap::map(&file)? };
-            self.mmap = Some(Arc::new(mmap));
+        if self.data_len() > 0 {
+            let previous = self.mmap.as_deref();
+            if let Some(view) = SegmentView::open(
+                &self.root,
+                &self.collection,
+                &self.segments,
+                previous,
+                self.cipher.as_ref(),
+                self.mmap_hints,
+            )? {
+                self.mmap = Some(Arc::new(view));
+            }
         }
         Ok(())
     }
 
-    /// Load binary index format (fast path).
-    /// Format: [magic:u32][version:u32][count:u64] + [id_len:u16, id_bytes, entry:12bytes]...
-    fn load_index_binary(path: &Path, index: &mut FxHashMap<String, IndexEntry>) -> Result<()> {
-        let mut file = File::open(path)?;
-
-        // Read header
-        let mut header = [0u8; 16];
-        file.read_exact(&mut header)?;
-
-        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
-        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
-        let count = u64::from_le_bytes([
-            header[8], header[9], header[10], header[11], header[12], header[13], header[14],
-            header[15],
-        ]);
-
-        if magic != INDEX_MAGIC || version != INDEX_VERSION {
-            return Err(Error::Io(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "Invalid index format",
-            )));
-        }
-
-        index.reserve(count as usize);
+    /// Paging hints of the store's memory maps.
+    pub fn mmap_hints(&self) -> MmapHints {
+        self.mmap_hints
+    }
 
-        // Read entries
-        for _ in 0..count {
-            // Read doc_id length
-            let mut len_buf = [0u8; 2];
-            file.read_exact(&mut len_buf)?;
-            let id_len = u16::from_le_bytes(len_buf) as usize;
-
-            // Read doc_id
-            let mut id_buf = vec![0u8; id_len];
-            file.read_exact(&mut id_buf)?;
-            let doc_id = String::from_utf8_lossy(&id_buf).into_owned();
-
-            // Read entry (12 bytes)
-            let mut entry_buf = [0u8; 12];
-            file.read_exact(&mut entry_buf)?;
-            let offset = u64::from_le_bytes([
-                entry_buf[0],
-                entry_buf[1],
-                entry_buf[2],
-                entry_buf[3],
-                entry_buf[4],
-                entry_buf[5],
-                entry_buf[6],
-                entry_buf[7],
-            ]);
-            let length =
-                u32::from_le_bytes([entry_buf[8], entry_buf[9], entry_buf[10], entry_buf[11]]);
+    /// Set the paging hints of the store's memory maps, remapping the data
+    /// if `populate` or `sequential` changed (so `populate` reads it in
+    /// right away).
+    pub fn set_mmap_hints(&mut self, hints: MmapHints) -> Result<()> {
+        self.mmap_hints = hints;
+        self.refresh_mmap()
+    }
 
-            index.insert(
-                doc_id,
-                IndexEntry {
-                    offset,
-                    length,
-                    _padding: 0,
-                },
-            );
+    /// Ask the OS to read the store's flushed data into the page cache in
+    /// the background, so a following scan doesn't wait on page faults.
+    ///
+    /// Returns without waiting for the reads; a no-op where the platform
+    /// has no such hint.
+    pub fn prefetch(&self) -> Result<()> {
+        if let Some(view) = self.current_mmap()? {
+            view.prefetch(0, view.end());
         }
-
         Ok(())
     }
 
-    /// Load text-based index format (legacy fallback).
-    fn load_index_text(path: &Path, index: &mut FxHashMap<String, IndexEntry>) -> Result<()> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split('\t').collect();
-            if parts.len() == 3 {
-                let doc_id = parts[0].to_string();
-                let offset: u64 = parts[1].parse().unwrap_or(0);
-                let length: u32 = parts[2].parse().unwrap_or(0);
-                index.insert(
-                    doc_id,
-                    IndexEntry {
-                        offset,
-                        length,
-                        _padding: 0,
-                    },
-                );
+    /// Pick up writes made by other processes since this read-only store
+    /// was opened or last refreshed. Returns whether anything changed.
+    ///
+    /// Lines appended to the data segments are indexed incrementally: only
+    /// the region past the end already seen is parsed, and deletions
+    /// appended to the tombstone file are applied. A collection rewritten in
+    /// the meantime (compacted, restored from a snapshot) or with a
+    /// transaction in progress is reopened instead. Expiry times of
+    /// documents appended since the store was opened are only known after a
+    /// reopen.
+    pub fn refresh(&mut self) -> Result<bool> {
+        if self.mode == OpenMode::ReadWrite {
+            return Err(Error::Validation(
+                "refresh is for read-only stores; a read-write store sees its own writes"
+                    .to_string(),
+            ));
+        }
+        match self.refresh_appended()? {
+            Some(changed) => Ok(changed),
+            None => {
+                self.reopen()?;
+                Ok(true)
             }
         }
-        Ok(())
     }
 
-    /// Rebuild index from data file using SIMD newline search.
-    fn rebuild_index(path: &Path, index: &mut FxHashMap<String, IndexEntry>) -> Result<()> {
-        let file = File::open(path)?;
-        let mmap = unsafe { Mmap::map(&file)? };
-        let data = &mmap[..];
-
-        let mut offset: u64 = 0;
-        let mut line_start = 0;
-
-        // Use memchr for SIMD newline search
-        for newline_pos in memchr_iter(b'\n', data) {
-            let line = &data[line_start..newline_pos];
-            let length = (newline_pos - line_start + 1) as u32;
-
-            // Fast _id extraction without full JSON parse
-            if let Some(doc_id) = Self::extract_id_fast(line) {
-                index.insert(
-                    doc_id,
-                    IndexEntry {
-                        offset,
-                        length,
-                        _padding: 0,
-                    },
-                );
+    /// Index lines and deletions appended since the last look, or `None` if
+    /// the collection changed in another way.
+    fn refresh_appended(&mut self) -> Result<Option<bool>> {
+        if Self::interrupted_transaction(&self.root, &self.collection)?.is_some() {
+            return Ok(None);
+        }
+        let mut segments = Self::load_segments(
+            &self.root,
+            &self.collection,
+            self.compression,
+            self.cipher.is_some(),
+            OpenMode::Read,
+        )?;
+        if segments.is_empty() {
+            segments.clone_from(&self.segments);
+        }
+        let active = *self.active();
+        // The writer may be mid-line: only complete lines are taken, so the
+        // rest is indexed by a later refresh
+        for segment in segments.iter_mut() {
+            if segment.no >= active.no && segment.compression == Compression::None {
+                let path = segment.path(&self.root, &self.collection);
+                segment.len = segment.len.min(Self::complete_len(&path)?);
             }
+        }
 
-            offset += length as u64;
-            line_start = newline_pos + 1;
+        // Sealed segments must be unchanged and the active one only grown;
+        // new segments may follow it
+        let appended = self.segments.iter().all(|old| {
+            segments.iter().any(|s| {
+                s.no == old.no
+                    && s.compression == old.compression
+                    && (s.len == old.len || (s.no == active.no && s.len > old.len))
+            })
+        }) && segments
+            .iter()
+            .all(|s| s.no > active.no || self.segments.iter().any(|old| old.no == s.no));
+        let tombstone_file = Layout::tombstone_file(&self.root, &self.collection);
+        let tombstones_len = std::fs::metadata(&tombstone_file).map_or(0, |m| m.len());
+        if !appended || tombstones_len < self.tombstones_read {
+            return Ok(None);
+        }
+        let old_end = self.current_offset;
+        let grown = segments.last().map_or(old_end, Segment::end) != old_end
+            || segments.len() != self.segments.len();
+        if !grown && tombstones_len == self.tombstones_read {
+            return Ok(Some(false));
         }
 
-        // Handle last line without trailing newline
-        if line_start < data.len() {
-            let line = &data[line_start..];
-            let length = (data.len() - line_start) as u32;
-            if let Some(doc_id) = Self::extract_id_fast(line) {
-                index.insert(
-                    doc_id,
-                    IndexEntry {
-                        offset,
-                        length,
-                        _padding: 0,
-                    },
-                );
+        self.segments = segments;
+        self.current_offset = self.active().end();
+        self.refresh_mmap()?;
+        if let Some(view) = self.mmap.clone() {
+            // A segment rewritten to the same length or longer
+            if !Self::stale_segments(&view, &self.segments, &self.index)?.is_empty() {
+                return Ok(None);
+            }
+            Self::rebuild_index(&view, old_end, view.end(), &mut self.index)?;
+        }
+        let (tombstones, read) =
+            Self::load_tombstones(&tombstone_file, self.tombstones_read, None)?;
+        self.tombstones_read = read;
+        for tombstone in tombstones {
+            if self
+                .index
+                .get(&tombstone.id)
+                .is_some_and(|e| e.offset < tombstone.offset)
+            {
+                self.index.remove(&tombstone.id);
+                self.expires.remove(&tombstone.id);
             }
+            let offset = self.tombstones.entry(tombstone.id).or_insert(0);
+            *offset = tombstone.offset.max(*offset);
         }
 
+        let view = self.mmap.clone();
+        self.dedup = Dedup::load(
+            &self.root,
+            &self.collection,
+            &mut self.index,
+            &self.tombstones,
+            |entry| Self::holds_line(view.as_deref(), entry),
+        )?;
+        let index = &self.index;
+        self.doc_meta.retain(|id, _| index.contains_key(id));
+        let (doc_meta, _) = Self::order_doc_meta(std::mem::take(&mut self.doc_meta), index);
+        self.doc_meta = doc_meta;
+        self.live_bytes = self.index.values().map(|e| e.length as u64).sum();
+        self.ordered.take();
+        self.reset_sorted_ids();
+        let dedup = &self.dedup;
+        self.versions = Versions::load(
+            &self.root,
+            &self.collection,
+            &self.index,
+            &self.tombstones,
+            |id| dedup.as_ref().is_some_and(|d| d.is_ref(id)),
+        )?;
+        self.field_indexes = FieldIndexes::load(&self.root, &self.collection)?;
+        self.columns = ColumnCache::load(&self.root, &self.collection).unwrap_or_default();
+        Ok(Some(true))
+    }
+
+    /// Replace this handle's state with a freshly opened store.
+    fn reopen(&mut self) -> Result<()> {
+        let mut store =
+            Self::open_with_mode(&self.root, &self.collection, self.batch_size, self.mode)?;
+        store.segment_size = self.segment_size;
+        store.compaction = self.compaction;
+        store.durability = self.durability;
+        store.id_sequence = self.id_sequence.take();
+        store.root_handle = self.root_handle.take();
+        store.subscribers = std::mem::take(&mut self.subscribers);
+        *self = store;
         Ok(())
     }
 
-    /// Fast _id extraction using pattern matching (avoids full JSON parse).
-    fn extract_id_fast(line: &[u8]) -> Option<String> {
-        // Look for "_id":" pattern
-        const PATTERN: &[u8] = b"\"_id\":\"";
+    /// The active (last) segment.
+    fn active(&self) -> &Segment {
+        self.segments.last().expect("store has an active segment")
+    }
 
-        if let Some(start) = line.windows(PATTERN.len()).position(|w| w == PATTERN) {
-            let id_start = start + PATTERN.len();
-            // Find closing quote
-            if let Some(end_offset) = memchr::memchr(b'"', &line[id_start..]) {
-                let id_bytes = &line[id_start..id_start + end_offset];
-                return Some(String::from_utf8_lossy(id_bytes).into_owned());
-            }
-        }
-        None
+    /// Total logical (uncompressed) length of all segments.
+    pub fn data_len(&self) -> u64 {
+        self.segments.iter().map(|s| s.len).sum()
     }
 
-    /// Save index in binary format (fast).
-    fn save_index(&self) -> Result<()> {
-        let file = File::create(&self.index_file)?;
-        let mut writer = BufWriter::with_capacity(256 * 1024, file);
+    /// Number of data segments.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
 
-        // Write header
-        writer.write_all(&INDEX_MAGIC.to_le_bytes())?;
-        writer.write_all(&INDEX_VERSION.to_le_bytes())?;
-        writer.write_all(&(self.index.len() as u64).to_le_bytes())?;
+    /// Bytes of live data: the lines documents currently point to.
+    pub fn live_bytes(&self) -> u64 {
+        self.live_bytes
+    }
 
-        // Write entries
-        for (doc_id, entry) in &self.index {
-            let id_bytes = doc_id.as_bytes();
-            writer.write_all(&(id_bytes.len() as u16).to_le_bytes())?;
-            writer.write_all(id_bytes)?;
-            writer.write_all(&entry.offset.to_le_bytes())?;
-            writer.write_all(&entry.length.to_le_bytes())?;
-        }
-
-        writer.flush()?;
-        Ok(())
+    /// Size on disk of the document index (`index.bin` and the changes
+    /// appended to `index.delta` since it was saved) as of the last flush,
+    /// 0 before the first one.
+    pub fn index_bytes(&self) -> u64 {
+        [&self.index_file, &self.index_delta_file]
+            .into_iter()
+            .map(|path| std::fs::metadata(path).map_or(0, |m| m.len()))
+            .sum()
     }
 
-    /// Put a document.
-    pub fn put(&mut self, doc_id: impl Into<String>, doc: Value) -> Result<()> {
-        if self.mode == OpenMode::Read {
-            return Err(Error::ReadOnly("cannot put in read-only mode".to_string()));
-        }
-        let doc_id = doc_id.into();
-        Layout::validate_doc_id(&doc_id)?;
+    /// Memory held by the in-memory document index.
+    ///
+    /// The IDs are kept back to back in one buffer rather than as a string
+    /// each, so this is the IDs' total length plus some 30 to 50 bytes per
+    /// document, depending on how full the index's buffers are.
+    pub fn index_memory_bytes(&self) -> u64 {
+        self.index.memory_bytes() as u64
+    }
 
-        // Create document with _id field
-        let mut doc_with_id = serde_json::Map::new();
-        doc_with_id.insert("_id".to_string(), Value::String(doc_id.clone()));
+    /// Size (uncompressed) at which the active segment is sealed.
+    pub fn segment_size(&self) -> u64 {
+        self.segment_size
+    }
 
-        if let Value::Object(obj) = doc {
-            for (k, v) in obj {
-                doc_with_id.insert(k, v);
-            }
-        }
+    /// Set the segment size for new writes (at least 1 byte; a line longer
+    /// than it gets a segment of its own).
+    pub fn set_segment_size(&mut self, bytes: u64) {
+        self.segment_size = bytes.max(1);
+    }
 
-        // Serialize to compact JSON
-        let line = serde_json::to_string(&Value::Object(doc_with_id))?;
-        self.put_raw_line(doc_id, line.as_bytes())
+    /// Format of the active data segment.
+    pub fn compression(&self) -> Compression {
+        self.active().compression
     }
 
-    /// Put a document as raw JSON bytes (fastest path).
-    /// The line should be valid JSON with "_id" field already included.
-    pub fn put_raw_line(&mut self, doc_id: impl Into<String>, line_bytes: &[u8]) -> Result<()> {
+    /// Set the collection's compression (stored in its manifest).
+    ///
+    /// New segments use it right away, as does an empty active segment;
+    /// existing data is converted on the next [`compact`](Self::compact).
+    pub fn set_compression(&mut self, compression: Compression) -> Result<()> {
         if self.mode == OpenMode::Read {
-            return Err(Error::ReadOnly("cannot put in read-only mode".to_string()));
+            return Err(Error::ReadOnly(
+                "cannot change compression in read-only mode".to_string(),
+            ));
         }
-        let doc_id = doc_id.into();
-        let length = line_bytes.len() as u32 + 1; // +1 for newline
-
-        // Write to buffer
-        if let Some(writer) = &mut self.writer {
-            writer.write_all(line_bytes)?;
-            writer.write_all(b"\n")?;
+        if self.cipher.is_some() && compression != Compression::Zstd {
+            return Err(Error::Validation(
+                "encrypted collections are stored in zstd blocks".to_string(),
+            ));
         }
+        compression.save(&self.root, &self.collection)?;
+        self.compression = compression;
+        let active = *self.active();
+        if active.len == 0 && active.compression != compression {
+            self.writer = None;
+            let old = active.path(&self.root, &self.collection);
+            if old.exists() {
+                std::fs::remove_file(old)?;
+            }
+            let active = Segment {
+                compression,
+                ..active
+            };
+            *self.segments.last_mut().unwrap() = active;
+            self.writer = Some(DataWriter::append(
+                &active.path(&self.root, &self.collection),
+                compression,
+                self.cipher.as_ref(),
+            )?);
+        }
+        Ok(())
+    }
 
-        // Update index
-        self.index.insert(
-            doc_id,
-            IndexEntry {
-                offset: self.current_offset,
-                length,
-                _padding: 0,
-            },
-        );
-        self.current_offset += length as u64;
-        self.pending_count += 1;
-
-        // Auto-flush if batch size reached
-        if self.pending_count >= self.batch_size {
-            self.flush()?;
+    /// The manifest's compression; encrypted collections always use zstd
+    /// blocks, which is where the encryption happens.
+    fn configured_compression(
+        root: &Path,
+        collection: &str,
+        cipher: Option<&Arc<Cipher>>,
+    ) -> Result<Compression> {
+        match cipher {
+            Some(_) => Ok(Compression::Zstd),
+            None => Compression::load(root, collection),
         }
+    }
 
-        Ok(())
+    /// Whether the collection is encrypted at rest.
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
     }
 
-    /// Write a complete JSONL blob with doc_ids (fastest bulk path).
-    /// Uses SIMD newline search and single write for maximum throughput.
-    pub fn write_jsonl_blob(&mut self, jsonl_data: &[u8], doc_ids: &[String]) -> Result<usize> {
+    /// Encrypt the collection with `key`, or decrypt it with `None`.
+    ///
+    /// The setting is stored in the manifest (with the key's ID, not the
+    /// key), and all data segments, `index.bin` and the field and text
+    /// indexes are rewritten right away. Encrypted collections are stored
+    /// in zstd blocks. Opening the collection later needs the key in the
+    /// environment (see [`EncryptionKey::from_env`]).
+    pub fn set_encryption(&mut self, key: Option<&EncryptionKey>) -> Result<()> {
         if self.mode == OpenMode::Read {
             return Err(Error::ReadOnly(
-                "cannot write in read-only mode".to_string(),
+                "cannot change encryption in read-only mode".to_string(),
             ));
         }
-        let writer = self.writer.as_mut().ok_or_else(|| {
-            Error::Io(std::io::Error::new(
-                std::io::ErrorKind::NotConnected,
-                "Writer not available",
-            ))
-        })?;
-
-        // Write entire blob at once (single syscall)
-        writer.write_all(jsonl_data)?;
-
-        // Ensure trailing newline
-        if !jsonl_data.is_empty() && jsonl_data.last() != Some(&b'\n') {
-            writer.write_all(b"\n")?;
-        }
-
-        // Build index using SIMD newline search
-        let mut count = 0;
-        let mut line_start = 0;
-        let mut doc_idx = 0;
-
-        for newline_pos in memchr_iter(b'\n', jsonl_data) {
-            if doc_idx < doc_ids.len() && line_start < newline_pos {
-                let length = (newline_pos - line_start + 1) as u32;
-
-                // Use reference to avoid clone when possible
-                self.index.insert(
-                    doc_ids[doc_idx].clone(),
-                    IndexEntry {
-                        offset: self.current_offset,
-                        length,
-                        _padding: 0,
-                    },
-                );
-                self.current_offset += length as u64;
-                count += 1;
-                doc_idx += 1;
-            }
-            line_start = newline_pos + 1;
+        if self.txn.is_some() {
+            return Err(Error::TransactionFailed(
+                "cannot change encryption inside a transaction".to_string(),
+            ));
         }
+        // The current view keeps reading with the old key during the rewrite
+        self.flush_writes()?;
+        self.refresh_mmap()?;
 
-        // Handle last line without trailing newline
-        if line_start < jsonl_data.len() && doc_idx < doc_ids.len() {
-            let length = (jsonl_data.len() - line_start + 1) as u32; // +1 for added newline
-
-            self.index.insert(
-                doc_ids[doc_idx].clone(),
-                IndexEntry {
-                    offset: self.current_offset,
-                    length,
-                    _padding: 0,
-                },
-            );
-            self.current_offset += length as u64;
-            count += 1;
+        encryption::save(&self.root, &self.collection, key)?;
+        if key.is_some() {
+            Compression::Zstd.save(&self.root, &self.collection)?;
         }
-
-        self.pending_count += count;
-        Ok(count)
-    }
-
-    /// Get a document by ID (uses mmap if available).
-    pub fn get(&self, doc_id: &str) -> Result<Value> {
-        let entry = self
-            .index
-            .get(doc_id)
-            .ok_or_else(|| Error::DocumentNotFound(doc_id.to_string()))?;
-
-        // Use mmap for zero-copy access if available
-        if let Some(mmap) = &self.mmap {
-            let start = entry.offset as usize;
-            let end = start + entry.length as usize;
-
-            if end <= mmap.len() {
-                let mut buffer = mmap[start..end].to_vec();
-                if buffer.last() == Some(&b'\n') {
-                    buffer.pop();
-                }
-
-                // Use simd-json for faster parsing
-                let mut doc: Value = simd_json::from_slice(&mut buffer).map_err(|e| {
-                    Error::Json(serde_json::Error::io(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        e.to_string(),
-                    )))
-                })?;
-
-                if let Value::Object(ref mut obj) = doc {
-                    obj.remove("_id");
-                }
-
-                return Ok(doc);
-            }
+        self.cipher = key.map(|key| Arc::new(Cipher::new(key)));
+        self.writer = None;
+        self.rewrite_data(None)?;
+        if let Some(blobs) = &mut self.blobs {
+            blobs.rewrite(self.cipher.clone())?;
         }
-
-        // Fallback to regular file I/O
-        let mut file = File::open(&self.data_file)?;
-        file.seek(SeekFrom::Start(entry.offset))?;
-
-        let mut buffer = vec![0u8; entry.length as usize];
-        std::io::Read::read_exact(&mut file, &mut buffer)?;
-
-        if buffer.last() == Some(&b'\n') {
-            buffer.pop();
+        for index in self.vectors.values() {
+            index.save(&self.root, &self.collection)?;
         }
-
-        let mut doc: Value = serde_json::from_slice(&buffer)?;
-
-        if let Value::Object(ref mut obj) = doc {
-            obj.remove("_id");
+        if let Some(columns) = &self.columns {
+            columns.save(&self.root, &self.collection)?;
         }
-
-        Ok(doc)
+        self.field_indexes.save(&self.root, &self.collection)
     }
 
-    /// Delete a document.
-    pub fn delete(&mut self, doc_id: &str) -> Result<()> {
-        if !self.index.contains_key(doc_id) {
-            return Err(Error::DocumentNotFound(doc_id.to_string()));
+    /// Start a new segment if `incoming` bytes would overflow the active one.
+    ///
+    /// The sealed segment is synced first, as flushes only sync the active one.
+    fn roll_segment(&mut self, incoming: u64) -> Result<()> {
+        let active = *self.active();
+        if active.len == 0 || active.len + incoming <= self.segment_size {
+            return Ok(());
+        }
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+            writer.sync_data()?;
         }
-        self.index.remove(doc_id);
+        let segment = Segment {
+            no: active.no + 1,
+            compression: self.compression,
+            len: 0,
+            encrypted: self.cipher.is_some(),
+        };
+        self.writer = Some(DataWriter::append(
+            &segment.path(&self.root, &self.collection),
+            segment.compression,
+            self.cipher.as_ref(),
+        )?);
+        self.segments.push(segment);
+        self.current_offset = segment.end();
         Ok(())
     }
 
-    /// Check if document exists.
-    pub fn exists(&self, doc_id: &str) -> bool {
-        self.index.contains_key(doc_id)
-    }
-
-    /// Get document count.
-    pub fn len(&self) -> usize {
-        self.index.len()
+    /// Account for `len` bytes appended to the active segment.
+    fn advance(&mut self, len: u64) {
+        self.current_offset += len;
+        if let Some(active) = self.segments.last_mut() {
+            active.len += len;
+        }
     }
 
-    /// Check if empty.
-    pub fn is_empty(&self) -> bool {
-        self.index.is_empty()
+    /// Read deletion records (missing file means none) from byte `start`
+    /// up to the first `limit` bytes of the file, or all of it. Returns them
+    /// with the file length read, up to the last complete line.
+    ///
+    /// An unterminated final line is a torn write and is ignored.
+    fn load_tombstones(
+        path: &Path,
+        start: u64,
+        limit: Option<u64>,
+    ) -> Result<(Vec<Tombstone>, u64)> {
+        if !path.exists() {
+            return Ok((Vec::new(), 0));
+        }
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut content = Vec::new();
+        match limit {
+            Some(limit) => file
+                .take(limit.saturating_sub(start))
+                .read_to_end(&mut content)?,
+            None => file.read_to_end(&mut content)?,
+        };
+        let complete = memchr::memrchr(b'\n', &content).map_or(0, |pos| pos + 1);
+        Ok((Self::parse_tombstones(&content)?, start + complete as u64))
     }
 
-    /// Get all document IDs.
-    pub fn doc_ids(&self) -> Vec<String> {
-        self.index.keys().cloned().collect()
+    /// Load the data segments of a collection, dropping torn trailing lines
+    /// (see [`recover_file`](Self::recover_file)).
+    fn load_segments(
+        root: &Path,
+        collection: &str,
+        compression: Compression,
+        encrypted: bool,
+        mode: OpenMode,
+    ) -> Result<Vec<Segment>> {
+        let mut segments = Vec::new();
+        for (no, format) in segment::list(root, collection, compression, mode)? {
+            let path = Layout::data_segment_file(root, collection, no, format == Compression::Zstd);
+            let (len, sealed) = match format {
+                Compression::None => (Self::recover_file(&path, mode)?, Some(false)),
+                Compression::Zstd => compression::recover(&path, mode)?,
+            };
+            segments.push(Segment {
+                no,
+                compression: format,
+                len,
+                encrypted: sealed.unwrap_or(encrypted),
+            });
+        }
+        Ok(segments)
     }
 
-    /// Flush pending writes to disk.
-    pub fn flush(&mut self) -> Result<()> {
-        if let Some(writer) = &mut self.writer {
-            writer.flush()?;
+    /// Parse the contents of a tombstone file, ignoring a torn last line.
+    pub(crate) fn parse_tombstones(content: &[u8]) -> Result<Vec<Tombstone>> {
+        let mut tombstones = Vec::new();
+        for line in content.split_inclusive(|&b| b == b'\n') {
+            if line.last() != Some(&b'\n') || line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            tombstones.push(serde_json::from_slice(line)?);
         }
-        self.pending_count = 0;
-        self.save_index()?;
-        Ok(())
+        Ok(tombstones)
     }
 
-    /// Scan all documents using mmap + parallel SIMD parsing.
-    pub fn scan(&self) -> Result<Vec<Value>> {
-        if self.index.is_empty() {
-            return Ok(Vec::new());
+    /// Drop a torn trailing line left by a crash mid-write.
+    ///
+    /// A final line that is valid JSON but lacks its newline is kept (and
+    /// terminated in read-write mode). Returns the usable file length; the
+    /// file itself is only modified in read-write mode.
+    fn recover_file(path: &Path, mode: OpenMode) -> Result<u64> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len == 0 {
+            return Ok(0);
         }
-
-        // Use mmap for zero-copy access
-        if let Some(mmap) = &self.mmap {
-            return self.scan_mmap_parallel(mmap);
+        let mmap = unsafe { Mmap::map(&file)? };
+        let complete = memchr::memrchr(b'\n', &mmap).map_or(0, |pos| pos + 1);
+        if complete == mmap.len() {
+            return Ok(len);
         }
+        let valid = serde_json::from_slice::<serde::de::IgnoredAny>(&mmap[complete..]).is_ok();
+        drop(mmap);
 
-        // Fallback to regular file reading if mmap not available
-        self.scan_file()
+        if mode == OpenMode::ReadWrite {
+            let mut file = OpenOptions::new().append(true).open(path)?;
+            if valid {
+                file.write_all(b"\n")?;
+                return Ok(len + 1);
+            }
+            file.set_len(complete as u64)?;
+        }
+        Ok(if valid { len } else { complete as u64 })
     }
 
-    /// Scan using memory-mapped file with parallel SIMD parsing.
-    fn scan_mmap_parallel(&self, mmap: &Mmap) -> Result<Vec<Value>> {
-        let entries: Vec<_> = self.index.values().collect();
-
-        // Direct parallel iteration - simpler and faster
-        let docs: Vec<Value> = entries
-            .par_iter()
-            .filter_map(|entry| {
-                let start = entry.offset as usize;
-                let end = start + entry.length as usize;
-
-                if end <= mmap.len() {
-                    let mut slice = mmap[start..end].to_vec();
-                    if slice.last() == Some(&b'\n') {
-                        slice.pop();
-                    }
-
-                    if let Ok(mut doc) = simd_json::from_slice::<Value>(&mut slice) {
-                        if let Value::Object(ref mut obj) = doc {
-                            obj.remove("_id");
-                        }
-                        return Some(doc);
-                    }
-                }
-                None
-            })
-            .collect();
-
-        Ok(docs)
+    /// Length of a file up to the end of its last complete line.
+    fn complete_len(path: &Path) -> Result<u64> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(0);
+        }
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(memchr::memrchr(b'\n', &mmap).map_or(0, |pos| pos + 1) as u64)
     }
 
-    /// Fallback scan using regular file I/O.
-    fn scan_file(&self) -> Result<Vec<Value>> {
-        if !self.data_file.exists() {
-            return Ok(Vec::new());
+    /// Re-index segments whose index entries don't describe their data.
+    ///
+    /// The last indexed line of each segment must lie within it and carry its
+    /// ID; a mismatch means the segment was rewritten without saving the
+    /// index, so its entries are dropped and its lines indexed again.
+    fn reindex_stale_segments(
+        view: &SegmentView,
+        segments: &[Segment],
+        index: &mut IdMap<IndexEntry>,
+    ) -> Result<bool> {
+        let stale = Self::stale_segments(view, segments, index)?;
+        if stale.is_empty() {
+            return Ok(false);
         }
 
-        let file = File::open(&self.data_file)?;
-        let reader = BufReader::new(file);
-        let mut docs = Vec::with_capacity(self.index.len());
+        index.retain(|_, e| !stale.contains(&segment::split(e.offset).0));
+        for segment in segments.iter().filter(|s| stale.contains(&s.no)) {
+            Self::rebuild_index(view, segment::pack(segment.no, 0), segment.end(), index)?;
+        }
+        Ok(true)
+    }
 
-        for line in reader.lines() {
-            let line = line?;
-            if let Ok(mut doc) = serde_json::from_str::<Value>(&line) {
-                if let Value::Object(ref mut obj) = doc {
-                    let doc_id = obj.remove("_id");
-                    if let Some(Value::String(id)) = doc_id {
-                        if self.index.contains_key(&id) {
-                            docs.push(doc);
-                        }
-                    }
-                }
+    /// Segments whose last indexed line doesn't carry its ID (see
+    /// [`reindex_stale_segments`](Self::reindex_stale_segments)).
+    fn stale_segments(
+        view: &SegmentView,
+        segments: &[Segment],
+        index: &IdMap<IndexEntry>,
+    ) -> Result<Vec<u32>> {
+        let mut last: BTreeMap<u32, (&str, &IndexEntry)> = BTreeMap::new();
+        for (doc_id, entry) in index.iter() {
+            let (no, _) = segment::split(entry.offset);
+            let slot = last.entry(no).or_insert((doc_id, entry));
+            if entry.offset > slot.1.offset {
+                *slot = (doc_id, entry);
             }
         }
 
-        Ok(docs)
+        let mut cache = ReadCache::default();
+        let mut stale = Vec::new();
+        for (no, (doc_id, entry)) in last {
+            let (_, offset) = segment::split(entry.offset);
+            let in_bounds = segments
+                .iter()
+                .any(|s| s.no == no && offset + entry.length as u64 <= s.len);
+            let matches = in_bounds
+                && view.line(entry, &mut cache)?.is_some_and(|line| {
+                    let line = line.strip_suffix(b"\n").unwrap_or(line);
+                    Self::extract_id_fast(line).as_deref() == Some(doc_id)
+                });
+            if !matches {
+                stale.push(no);
+            }
+        }
+        Ok(stale)
     }
 
-    /// Scan and return raw JSON bytes (fastest - zero parsing).
-    pub fn scan_raw(&self) -> Result<Vec<Vec<u8>>> {
-        if self.index.is_empty() {
-            return Ok(Vec::new());
+    /// Load binary index format (fast path).
+    /// Format: [magic:u32][version:u32][count:u64]
+    ///   + [id_len:u16, id_bytes, offset:u64, length:u32, checksum:u32]...
+    ///   + [expiry_count:u64] + [id_len:u16, id_bytes, expires_at:u64]...
+    ///   + [crc32 of everything before:u32]
+    ///
+    /// Version 2 indexes have no expiry section. Encrypted collections seal
+    /// the whole file. The changes of the index delta at `delta_path` are
+    /// applied on top (see [`parse_index_delta`](Self::parse_index_delta)).
+    fn load_index_binary(
+        path: &Path,
+        delta_path: &Path,
+        cipher: Option<&Cipher>,
+        aad: &[u8],
+        index: &mut IdMap<IndexEntry>,
+        expires: &mut FxHashMap<String, u64>,
+    ) -> Result<SavedIndex> {
+        let buf = encryption::open_file(cipher, std::fs::read(path)?, aad)?;
+        let checksum = Self::parse_index_binary(&buf, index, expires)?;
+        match std::fs::read(delta_path) {
+            Ok(delta) => Self::parse_index_delta(&delta, checksum, cipher, index, expires),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SavedIndex::new(checksum)),
+            Err(e) => Err(e.into()),
         }
+    }
 
-        if let Some(mmap) = &self.mmap {
-            let entries: Vec<_> = self.index.values().collect();
-
-            let raw: Vec<Vec<u8>> = entries
-                .par_iter()
-                .filter_map(|entry| {
-                    let start = entry.offset as usize;
-                    let end = start + entry.length as usize;
+    /// Parse the contents of a binary index (see
+    /// [`load_index_binary`](Self::load_index_binary)), returning its
+    /// checksum.
+    pub(crate) fn parse_index_binary(
+        buf: &[u8],
+        index: &mut IdMap<IndexEntry>,
+        expires: &mut FxHashMap<String, u64>,
+    ) -> Result<u32> {
+        let invalid = || {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid index format",
+            ))
+        };
+        if buf.len() < 20 {
+            return Err(invalid());
+        }
 
-                    if end <= mmap.len() {
-                        let mut slice = mmap[start..end].to_vec();
-                        if slice.last() == Some(&b'\n') {
-                            slice.pop();
-                        }
-                        return Some(slice);
-                    }
-                    None
-                })
-                .collect();
+        let (body, trailer) = buf.split_at(buf.len() - 4);
+        let magic = u32::from_le_bytes(body[0..4].try_into().unwrap());
+        let version = u32::from_le_bytes(body[4..8].try_into().unwrap());
+        let count = u64::from_le_bytes(body[8..16].try_into().unwrap());
 
-            return Ok(raw);
+        if magic != INDEX_MAGIC || !(2..=INDEX_VERSION).contains(&version) {
+            return Err(invalid());
+        }
+        let checksum = crc32fast::hash(body);
+        if checksum != u32::from_le_bytes(trailer.try_into().unwrap()) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Index checksum mismatch",
+            )));
         }
 
-        // Fallback: create mmap on demand
-        if self.data_file.exists() {
-            let file = File::open(&self.data_file)?;
-            let mmap = unsafe { Mmap::map(&file)? };
-            let entries: Vec<_> = self.index.values().collect();
-
-            let raw: Vec<Vec<u8>> = entries
-                .par_iter()
-                .filter_map(|entry| {
-                    let start = entry.offset as usize;
-                    let end = start + entry.length as usize;
+        index.reserve(count as usize);
 
-                    if end <= mmap.len() {
-                        let mut slice = mmap[start..end].to_vec();
-                        if slice.last() == Some(&b'\n') {
-                            slice.pop();
-                        }
-                        return Some(slice);
-                    }
-                    None
-                })
-                .collect();
+        // Read entries
+        let mut pos = 16;
+        for _ in 0..count {
+            let id_len = u16::from_le_bytes(
+                body.get(pos..pos + 2)
+                    .ok_or_else(invalid)?
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            pos += 2;
+            let entry = body.get(pos..pos + id_len + 16).ok_or_else(invalid)?;
+            pos += id_len + 16;
+
+            let (id_bytes, entry) = entry.split_at(id_len);
+            index.insert(
+                String::from_utf8_lossy(id_bytes),
+                IndexEntry {
+                    offset: u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+                    length: u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+                    checksum: u32::from_le_bytes(entry[12..16].try_into().unwrap()),
+                },
+            );
+        }
+        if version < 3 {
+            return Ok(checksum);
+        }
 
-            return Ok(raw);
+        // Read expiry times
+        let count = u64::from_le_bytes(
+            body.get(pos..pos + 8)
+                .ok_or_else(invalid)?
+                .try_into()
+                .unwrap(),
+        );
+        pos += 8;
+        for _ in 0..count {
+            let id_len = u16::from_le_bytes(
+                body.get(pos..pos + 2)
+                    .ok_or_else(invalid)?
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            pos += 2;
+            let record = body.get(pos..pos + id_len + 8).ok_or_else(invalid)?;
+            pos += id_len + 8;
+
+            let (id_bytes, expires_at) = record.split_at(id_len);
+            expires.insert(
+                String::from_utf8_lossy(id_bytes).into_owned(),
+                u64::from_le_bytes(expires_at.try_into().unwrap()),
+            );
         }
 
-        Ok(Vec::new())
+        Ok(checksum)
     }
 
-    /// Get the raw JSONL data as bytes (zero-copy from mmap).
-    /// This is the fastest way to get all data for bulk processing.
-    pub fn get_raw_data(&self) -> Option<&[u8]> {
-        self.mmap.as_ref().map(|m| &**m as &[u8])
+    /// Associated data of a sealed index delta batch: the checksum of the
+    /// index it extends and the batch's position in the delta, so batches
+    /// can't be replayed onto another index or reordered.
+    fn index_delta_aad(checksum: u32, pos: u64) -> Vec<u8> {
+        let mut aad = b"zds index delta".to_vec();
+        aad.extend_from_slice(&checksum.to_le_bytes());
+        aad.extend_from_slice(&pos.to_le_bytes());
+        aad
     }
 
-    /// Compact the data file by removing deleted entries.
-    pub fn compact(&mut self) -> Result<()> {
-        self.flush()?;
-
-        let tmp_file = self.data_file.with_extension("tmp");
-        let mut new_index = FxHashMap::default();
-        let mut offset: u64 = 0;
+    /// Apply the contents of an index delta to the binary index it extends,
+    /// whose checksum is `checksum`.
+    ///
+    /// Delta format (appended to by [`flush`](Self::flush), see
+    /// [`save_index_changes`](Self::save_index_changes)):
+    ///   [magic:u32][version:u32][checksum of the index:u32]
+    ///   + batches of [len:u32][crc32 of payload:u32][payload]
+    ///
+    /// Payload: [count:u64] + [op:u8, id_len:u16, id_bytes, fields]..., with
+    /// `[offset:u64][length:u32][checksum:u32]` for a put and the same plus
+    /// `[expires_at:u64]` for a put with a TTL. Encrypted collections seal
+    /// each payload (see [`index_delta_aad`](Self::index_delta_aad)).
+    ///
+    /// A delta left over from an index saved since is ignored, and so is a
+    /// torn last batch.
+    pub(crate) fn parse_index_delta(
+        buf: &[u8],
+        checksum: u32,
+        cipher: Option<&Cipher>,
+        index: &mut IdMap<IndexEntry>,
+        expires: &mut FxHashMap<String, u64>,
+    ) -> Result<SavedIndex> {
+        let mut saved = SavedIndex::new(checksum);
+        if buf.get(..INDEX_DELTA_HEADER) != Some(&Self::index_delta_header(checksum)) {
+            return Ok(saved);
+        }
 
-        {
-            let src = File::open(&self.data_file)?;
-            let reader = BufReader::new(src);
-            let dst = File::create(&tmp_file)?;
-            let mut writer = BufWriter::new(dst);
-
-            for line in reader.lines() {
-                let line = line?;
-                if let Ok(doc) = serde_json::from_str::<Value>(&line) {
-                    if let Some(doc_id) = doc.get("_id").and_then(|v| v.as_str()) {
-                        if self.index.contains_key(doc_id) {
-                            let length = line.len() as u32 + 1;
-                            writeln!(writer, "{}", line)?;
-                            new_index.insert(
-                                doc_id.to_string(),
-                                IndexEntry {
-                                    offset,
-                                    length,
-                                    _padding: 0,
-                                },
-                            );
-                            offset += length as u64;
-                        }
-                    }
-                }
+        let mut pos = INDEX_DELTA_HEADER;
+        while let Some(frame) = buf.get(pos..pos + 8) {
+            let len = u32::from_le_bytes(frame[0..4].try_into().unwrap()) as usize;
+            let crc = u32::from_le_bytes(frame[4..8].try_into().unwrap());
+            let Some(payload) = buf.get(pos + 8..pos + 8 + len) else {
+                break;
+            };
+            if crc32fast::hash(payload) != crc {
+                break;
             }
-            writer.flush()?;
+            let aad = Self::index_delta_aad(checksum, pos as u64);
+            let payload = encryption::open_file(cipher, payload.to_vec(), &aad)?;
+            saved.delta_changes += Self::apply_index_changes(&payload, index, expires)?;
+            pos += 8 + len;
+            saved.delta_len = pos as u64;
         }
+        Ok(saved)
+    }
 
-        // Atomic replace
-        std::fs::rename(&tmp_file, &self.data_file)?;
-        self.index = new_index;
-        self.current_offset = offset;
-        self.save_index()?;
-
-        // Reopen writer
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.data_file)?;
-        self.writer = Some(BufWriter::with_capacity(64 * 1024, file));
-
-        Ok(())
+    fn index_delta_header(checksum: u32) -> [u8; INDEX_DELTA_HEADER] {
+        let mut header = [0; INDEX_DELTA_HEADER];
+        header[0..4].copy_from_slice(&INDEX_DELTA_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&INDEX_DELTA_VERSION.to_le_bytes());
+        header[8..12].copy_from_slice(&checksum.to_le_bytes());
+        header
     }
-}
 
-impl Drop for FastStore {
-    fn drop(&mut self) {
-        let _ = self.flush();
+    /// Apply the changes of one delta batch, returning how many it holds.
+    fn apply_index_changes(
+        payload: &[u8],
+        index: &mut IdMap<IndexEntry>,
+        expires: &mut FxHashMap<String, u64>,
+    ) -> Result<usize> {
+        let invalid = || {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Invalid index delta",
+            ))
+        };
+        let count = u64::from_le_bytes(payload.get(0..8).ok_or_else(invalid)?.try_into().unwrap());
+
+        let mut pos = 8;
+        for _ in 0..count {
+            let head = payload.get(pos..pos + 3).ok_or_else(invalid)?;
+            let op = head[0];
+            let id_len = u16::from_le_bytes(head[1..3].try_into().unwrap()) as usize;
+            pos += 3;
+            let fields_len = match op {
+                DELTA_PUT => 16,
+                DELTA_PUT_EXPIRING => 24,
+                DELTA_REMOVE => 0,
+                _ => return Err(invalid()),
+            };
+            let record = payload
+                .get(pos..pos + id_len + fields_len)
+                .ok_or_else(invalid)?;
+            pos += id_len + fields_len;
+
+            let (id_bytes, fields) = record.split_at(id_len);
+            let doc_id = String::from_utf8_lossy(id_bytes);
+            if op == DELTA_REMOVE {
+                index.remove(&doc_id);
+                expires.remove(doc_id.as_ref());
+                continue;
+            }
+            index.insert(
+                &doc_id,
+                IndexEntry {
+                    offset: u64::from_le_bytes(fields[0..8].try_into().unwrap()),
+                    length: u32::from_le_bytes(fields[8..12].try_into().unwrap()),
+                    checksum: u32::from_le_bytes(fields[12..16].try_into().unwrap()),
+                },
+            );
+            if op == DELTA_PUT_EXPIRING {
+                let expires_at = u64::from_le_bytes(fields[16..24].try_into().unwrap());
+                expires.insert(doc_id.into_owned(), expires_at);
+            } else {
+                expires.remove(doc_id.as_ref());
+            }
+        }
+        Ok(count as usize)
     }
-}
 
-/// Inner state for ZDSRoot, shared via Arc.
-struct ZDSRootInner {
-    root: PathBuf,
-    batch_size: usize,
-    mode: OpenMode,
-    /// Write lock (only held in ReadWrite mode)
-    write_lock: Option<WriteLock>,
-}
+    /// Load text-based index format (legacy fallback).
+    fn load_index_text(path: &Path, index: &mut IdMap<IndexEntry>) -> Result<()> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
 
-impl std::fmt::Debug for ZDSRootInner {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ZDSRootInner")
-            .field("root", &self.root)
-            .field("batch_size", &self.batch_size)
-            .field("mode", &self.mode)
-            .field("write_lock", &self.write_lock.is_some())
-            .finish()
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() == 3 {
+                let doc_id = parts[0].to_string();
+                let offset: u64 = parts[1].parse().unwrap_or(0);
+                let length: u32 = parts[2].parse().unwrap_or(0);
+                index.insert(
+                    doc_id,
+                    IndexEntry {
+                        offset,
+                        length,
+                        checksum: 0,
+                    },
+                );
+            }
+        }
+        Ok(())
     }
-}
 
-/// Root handle for a ZDS store directory.
-///
-/// This struct represents a ZDS root directory without binding to a specific collection.
-/// It allows opening multiple collections from the same root safely, avoiding corruption
-/// when writing to multiple collections simultaneously.
-///
-/// Roots are **memoized** by (path, mode): opening the same path twice returns the same
-/// shared instance, ensuring a single write lock protects the entire store.
-///
-/// # Example
-///
-/// ```ignore
-/// use zippy_data::{ZDSRoot, OpenMode};
-///
-/// // Open in read-write mode (default)
-/// let root = ZDSRoot::open("./data", 1000, OpenMode::ReadWrite)?;
-/// let train = root.collection("train")?;
-/// let test = root.collection("test")?;
-///
-/// train.put("doc1", json!({"split": "train"}))?;
-/// test.put("doc1", json!({"split": "test"}))?;
-///
-/// // Open read-only (no lock, parallel readers allowed)
-/// let reader = ZDSRoot::open("./data", 1000, OpenMode::Read)?;
-/// ```
-#[derive(Clone)]
-pub struct ZDSRoot {
-    inner: Arc<ZDSRootInner>,
-}
+    /// Compute line checksums for an index loaded without them.
+    fn fill_checksums(view: Option<&SegmentView>, index: &mut IdMap<IndexEntry>) -> Result<()> {
+        let Some(view) = view else {
+            return Ok(());
+        };
+        let mut entries: Vec<&mut IndexEntry> = index.values_mut().collect();
+        entries.sort_unstable_by_key(|e| e.offset);
+        let mut cache = ReadCache::default();
+        for entry in entries {
+            if let Some(line) = view.line(entry, &mut cache)? {
+                entry.checksum = line_checksum(line);
+            }
+        }
+        Ok(())
+    }
 
-impl std::fmt::Debug for ZDSRoot {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("ZDSRoot")
-            .field("root", &self.inner.root)
-            .field("batch_size", &self.inner.batch_size)
-            .field("mode", &self.inner.mode)
-            .finish()
+    /// Index the lines between index offsets `start..end` using SIMD newline
+    /// search.
+    fn rebuild_index(
+        view: &SegmentView,
+        start: u64,
+        end: u64,
+        index: &mut IdMap<IndexEntry>,
+    ) -> Result<()> {
+        view.for_each_chunk(start, end, |base, data| {
+            Self::index_lines(data, base, index)
+        })
     }
-}
 
-impl ZDSRoot {
-    /// Open or create a ZDS root directory.
-    ///
-    /// This initializes the root directory structure but does not open any collection.
-    /// Use `collection()` to get a handle to a specific collection.
+    /// Index the lines of a chunk of data starting at index offset `base`.
     ///
-    /// # Memoization
+    /// An ID keeps its entry if that points at a later line.
+    fn index_lines(data: &[u8], base: u64, index: &mut IdMap<IndexEntry>) {
+        let mut offset = base;
+        let mut line_start = 0;
+
+        // Use memchr for SIMD newline search
+        for newline_pos in memchr_iter(b'\n', data) {
+            let line = &data[line_start..newline_pos];
+            let length = (newline_pos - line_start + 1) as u32;
+
+            if let Some(doc_id) = Self::extract_id_fast(line) {
+                Self::index_line(index, doc_id, offset, length, line);
+            }
+
+            offset += length as u64;
+            line_start = newline_pos + 1;
+        }
+
+        // Handle last line without trailing newline
+        if line_start < data.len() {
+            let line = &data[line_start..];
+            let length = (data.len() - line_start) as u32;
+            if let Some(doc_id) = Self::extract_id_fast(line) {
+                Self::index_line(index, doc_id, offset, length, line);
+            }
+        }
+    }
+
+    fn index_line(
+        index: &mut IdMap<IndexEntry>,
+        doc_id: String,
+        offset: u64,
+        length: u32,
+        line: &[u8],
+    ) {
+        let entry = IndexEntry {
+            offset,
+            length,
+            checksum: line_checksum(line),
+        };
+        match index.get_mut(&doc_id) {
+            Some(slot) => {
+                if slot.offset < offset {
+                    *slot = entry;
+                }
+            }
+            None => {
+                index.insert(doc_id, entry);
+            }
+        }
+    }
+
+    /// `_id` of a data line, or `None` if it isn't a JSON object with a
+    /// string or integer `_id`. Integers are read as written: `42` is
+    /// `"42"`.
     ///
-    /// Roots are cached by (canonical_path, mode). Opening the same path multiple times
-    /// returns the same shared instance, ensuring consistent locking.
+    /// Lines the store writes start with their ID, which is read straight
+    /// off the prefix. Any other line is walked key by key at the top level,
+    /// skipping values without parsing them, so whitespace, escapes, nested
+    /// objects with an `_id` of their own and `"_id":"` inside strings are
+    /// all handled. A repeated `_id` key resolves to the first.
+    pub fn extract_id_fast(line: &[u8]) -> Option<String> {
+        const PREFIX: &[u8] = b"{\"_id\":\"";
+
+        if let Some(rest) = line.strip_prefix(PREFIX) {
+            if let Some(end) = memchr::memchr2(b'"', b'\\', rest) {
+                if rest[end] == b'"' {
+                    return Some(String::from_utf8_lossy(&rest[..end]).into_owned());
+                }
+            }
+        }
+        crate::json_scan::top_level_id(line)
+    }
+
+    /// Save index in binary format (fast), replacing the old one atomically
+    /// and starting a new, empty delta.
+    fn save_index(&mut self) -> Result<()> {
+        let tmp_file = self.index_file.with_extension("tmp");
+        let mut buf = Vec::with_capacity(20 + self.index.len() * 32);
+
+        // Write header
+        buf.extend_from_slice(&INDEX_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+        buf.extend_from_slice(&(self.index.len() as u64).to_le_bytes());
+
+        // Write entries
+        for (doc_id, entry) in &self.index {
+            let id_bytes = doc_id.as_bytes();
+            buf.extend_from_slice(&(id_bytes.len() as u16).to_le_bytes());
+            buf.extend_from_slice(id_bytes);
+            buf.extend_from_slice(&entry.offset.to_le_bytes());
+            buf.extend_from_slice(&entry.length.to_le_bytes());
+            buf.extend_from_slice(&entry.checksum.to_le_bytes());
+        }
+
+        // Write expiry times
+        buf.extend_from_slice(&(self.expires.len() as u64).to_le_bytes());
+        for (doc_id, expires_at) in &self.expires {
+            let id_bytes = doc_id.as_bytes();
+            buf.extend_from_slice(&(id_bytes.len() as u16).to_le_bytes());
+            buf.extend_from_slice(id_bytes);
+            buf.extend_from_slice(&expires_at.to_le_bytes());
+        }
+
+        // Whole-file checksum trailer
+        let checksum = crc32fast::hash(&buf);
+        buf.extend_from_slice(&checksum.to_le_bytes());
+
+        let aad = encryption::file_aad(&self.root, &self.collection, &self.index_file);
+        std::fs::write(
+            &tmp_file,
+            encryption::seal_file(self.cipher.as_deref(), buf, &aad)?,
+        )?;
+        std::fs::rename(&tmp_file, &self.index_file)?;
+        if self.index_delta_file.exists() {
+            std::fs::remove_file(&self.index_delta_file)?;
+        }
+        self.saved_index = Some(SavedIndex::new(checksum));
+        self.index_changes.clear();
+        Ok(())
+    }
+
+    /// Persist the index changes made since it was last saved: they are
+    /// appended to `index.delta` as one batch, so a flush costs what was
+    /// written rather than the size of the index. The whole index is saved
+    /// instead if it never was, or once the delta would outgrow it.
+    fn save_index_changes(&mut self) -> Result<()> {
+        let Some(saved) = self.saved_index else {
+            return self.save_index();
+        };
+        if self.index_changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut payload = Vec::with_capacity(8 + self.index_changes.len() * 48);
+        payload.extend_from_slice(&(self.index_changes.len() as u64).to_le_bytes());
+        for doc_id in &self.index_changes {
+            let entry = self.index.get(doc_id);
+            let expires_at = entry.and(self.expires.get(doc_id));
+            payload.push(match (entry, expires_at) {
+                (None, _) => DELTA_REMOVE,
+                (Some(_), None) => DELTA_PUT,
+                (Some(_), Some(_)) => DELTA_PUT_EXPIRING,
+            });
+            let id_bytes = doc_id.as_bytes();
+            payload.extend_from_slice(&(id_bytes.len() as u16).to_le_bytes());
+            payload.extend_from_slice(id_bytes);
+            if let Some(entry) = entry {
+                payload.extend_from_slice(&entry.offset.to_le_bytes());
+                payload.extend_from_slice(&entry.length.to_le_bytes());
+                payload.extend_from_slice(&entry.checksum.to_le_bytes());
+            }
+            if let Some(expires_at) = expires_at {
+                payload.extend_from_slice(&expires_at.to_le_bytes());
+            }
+        }
+        let mut buf = Vec::with_capacity(INDEX_DELTA_HEADER + 8 + payload.len());
+        if saved.delta_len == 0 {
+            buf.extend_from_slice(&Self::index_delta_header(saved.checksum));
+        }
+        let aad = Self::index_delta_aad(saved.checksum, saved.delta_len + buf.len() as u64);
+        let payload = encryption::seal_file(self.cipher.as_deref(), payload, &aad)?;
+
+        buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        buf.extend_from_slice(&payload);
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&self.index_delta_file)?;
+        // Drop whatever a failed append left after the last complete batch
+        file.set_len(saved.delta_len)?;
+        file.seek(SeekFrom::Start(saved.delta_len))?;
+        file.write_all(&buf)?;
+        self.saved_index = Some(SavedIndex {
+            delta_len: saved.delta_len + buf.len() as u64,
+            delta_changes: saved.delta_changes + self.index_changes.len(),
+            ..saved
+        });
+        self.index_changes.clear();
+        Ok(())
+    }
+
+    /// Record that the index entry or expiry time of `doc_id` changed, for
+    /// the next [`save_index_changes`](Self::save_index_changes).
+    fn index_changed(&mut self, doc_id: &str) {
+        let Some(saved) = self.saved_index else {
+            return;
+        };
+        if saved.delta_changes + self.index_changes.len() >= self.index.len().max(INDEX_DELTA_MIN) {
+            // The delta would outgrow the index: it is saved in full
+            self.saved_index = None;
+            self.index_changes.clear();
+        } else if !self.index_changes.contains(doc_id) {
+            self.index_changes.insert(doc_id.to_string());
+        }
+    }
+
+    /// Fold `index.delta` into `index.bin`.
+    fn merge_index_delta(&mut self) -> Result<()> {
+        if self.mode == OpenMode::ReadWrite && self.saved_index.is_some_and(|s| s.delta_len > 0) {
+            self.save_index()?;
+        }
+        Ok(())
+    }
+
+    /// Put a document.
+    pub fn put(&mut self, doc_id: impl Into<String>, doc: Value) -> Result<()> {
+        self.put_expiring(doc_id.into(), doc, None)
+    }
+
+    /// Put a document under a generated ID, and return the ID.
     ///
-    /// # Locking
+    /// Sequence numbers already used by documents are skipped; the
+    /// sequence is saved on flush.
+    pub fn put_auto(&mut self, doc: Value, strategy: IdStrategy) -> Result<String> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly("cannot put in read-only mode".to_string()));
+        }
+        let doc_id = match strategy.generate() {
+            Some(doc_id) => doc_id,
+            None => {
+                if self.id_sequence.is_none() {
+                    self.id_sequence = Some(IdSequence::load(&self.root, &self.collection)?);
+                }
+                let index = &self.index;
+                self.id_sequence
+                    .as_mut()
+                    .unwrap()
+                    .allocate(|id| index.contains_key(id))
+            }
+        };
+        self.put(doc_id.clone(), doc)?;
+        Ok(doc_id)
+    }
+
+    /// Put a document that expires after `ttl`.
     ///
-    /// - `OpenMode::ReadWrite`: Acquires an exclusive write lock. Only one writer allowed.
-    /// - `OpenMode::Read`: No lock acquired. Multiple readers allowed.
-    pub fn open(root: impl AsRef<Path>, batch_size: usize, mode: OpenMode) -> Result<Self> {
-        let root_path = root.as_ref();
+    /// The expiry time is kept in the index: once it passes, the document
+    /// is hidden from reads, and the next compaction deletes it. Writing the
+    /// document again without a TTL clears it. An index rebuilt from the data
+    /// files has no expiry times.
+    pub fn put_with_ttl(
+        &mut self,
+        doc_id: impl Into<String>,
+        doc: Value,
+        ttl: Duration,
+    ) -> Result<()> {
+        let ttl_ms = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+        self.put_expiring(doc_id.into(), doc, Some(now_ms().saturating_add(ttl_ms)))
+    }
 
-        // Initialize root directory structure first (needed for canonicalize)
-        if mode == OpenMode::ReadWrite {
-            Layout::init_root(root_path)?;
+    /// Put a document with an optional expiry time (Unix ms).
+    fn put_expiring(
+        &mut self,
+        doc_id: String,
+        mut doc: Value,
+        expires_at: Option<u64>,
+    ) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly("cannot put in read-only mode".to_string()));
+        }
+        self.check_unsharded()?;
+        Layout::validate_doc_id(&doc_id)?;
+        // The document is stored under `doc_id`; an `_id` member of its own
+        // would disagree with the index
+        if let Value::Object(obj) = &mut doc {
+            obj.remove("_id");
         }
+        self.check_schemas(std::slice::from_ref(&doc))?;
+        let schema_id = intern(
+            &mut self.schema_ids,
+            &SchemaRegistry::compute_schema_id(&doc),
+        );
 
-        // Canonicalize path for consistent caching (after directory exists)
-        let canonical =
-            std::fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
-        let cache_key = (canonical.clone(), mode);
+        // Transactions index the fields of their lines on commit
+        if self.txn.is_none() && !self.field_indexes.is_empty() {
+            self.field_indexes.insert(&doc_id, &doc);
+            self.field_indexes_dirty = true;
+        }
 
-        // Check cache first
-        {
-            let cache = ROOT_CACHE.read();
-            if let Some(weak) = cache.get(&cache_key) {
-                if let Some(inner) = weak.upgrade() {
-                    return Ok(ZDSRoot { inner });
+        // Create document with _id field
+        let mut doc_with_id = serde_json::Map::new();
+        doc_with_id.insert("_id".to_string(), Value::String(doc_id.clone()));
+
+        if let Value::Object(obj) = doc {
+            for (k, v) in obj {
+                doc_with_id.insert(k, v);
+            }
+        }
+
+        // Serialize to compact JSON
+        let doc = Value::Object(doc_with_id);
+        let line = serde_json::to_string(&doc)?;
+        if self.txn.is_none() {
+            if let Some(columns) = &mut self.columns {
+                columns.insert(&doc_id, line_checksum(line.as_bytes()), &doc);
+                self.columns_dirty = true;
+            }
+        }
+        if let Some(ops) = &mut self.txn {
+            ops.push(TxnOp::Put {
+                doc_id,
+                line: line.into_bytes(),
+                expires_at,
+                schema_id: Some(schema_id),
+            });
+            return Ok(());
+        }
+        self.append_line(doc_id, line.as_bytes(), expires_at, Some(schema_id))
+    }
+
+    /// Apply a JSON merge patch (RFC 7386) to a document and return the
+    /// patched document.
+    ///
+    /// The patch must be an object without `_id`; `null` members remove
+    /// fields. The
+    /// patched document is written like a [`put`](Self::put), keeping its
+    /// expiry time if it has one.
+    pub fn patch(&mut self, doc_id: &str, patch: &Value) -> Result<Value> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot patch in read-only mode".to_string(),
+            ));
+        }
+        if !patch.is_object() {
+            return Err(Error::Validation(
+                "merge patch must be a JSON object".to_string(),
+            ));
+        }
+        if patch.get("_id").is_some() {
+            return Err(Error::Validation(
+                "merge patch can't change _id".to_string(),
+            ));
+        }
+        let mut doc = match self.get(doc_id) {
+            // The document may still sit in the write buffer
+            Err(Error::Io(_)) if self.writer.is_some() => {
+                if let Some(writer) = &mut self.writer {
+                    writer.flush()?;
+                }
+                self.get(doc_id)?
+            }
+            result => result?,
+        };
+        Codec::merge_patch(&mut doc, patch);
+        let expires_at = self.expires.get(doc_id).copied();
+        self.put_expiring(doc_id.to_string(), doc.clone(), expires_at)?;
+        Ok(doc)
+    }
+
+    /// Put a batch of documents, resolving IDs that already exist with
+    /// `policy`. Returns the number of documents written.
+    ///
+    /// IDs are checked against the index, so a duplicate ID later in the
+    /// batch conflicts with its first occurrence. With
+    /// [`ConflictPolicy::FailOnConflict`] nothing is written if any ID
+    /// conflicts, and the error names the first one.
+    pub fn put_batch_with_policy(
+        &mut self,
+        items: Vec<(String, Value)>,
+        policy: ConflictPolicy,
+    ) -> Result<usize> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly("cannot put in read-only mode".to_string()));
+        }
+        if policy == ConflictPolicy::FailOnConflict {
+            let mut seen = HashSet::with_capacity(items.len());
+            for (doc_id, _) in &items {
+                if self.exists(doc_id) || !seen.insert(doc_id.as_str()) {
+                    return Err(Error::DocumentExists(doc_id.clone()));
                 }
             }
         }
 
-        // Not in cache or expired - create new
-        let mut cache = ROOT_CACHE.write();
+        let mut written = 0;
+        for (doc_id, doc) in items {
+            if policy == ConflictPolicy::SkipExisting && self.exists(&doc_id) {
+                continue;
+            }
+            self.put(doc_id, doc)?;
+            written += 1;
+        }
+        Ok(written)
+    }
 
-        // Double-check after acquiring write lock
-        if let Some(weak) = cache.get(&cache_key) {
+    /// Put a document as raw JSON bytes (fastest path).
+    ///
+    /// The line must be a JSON object whose `_id` is `doc_id`: a string, or
+    /// an integer written with the same digits (`{"_id":42}` for `"42"`), so
+    /// that an index rebuilt from the data finds it under the same ID.
+    pub fn put_raw_line(&mut self, doc_id: impl Into<String>, line_bytes: &[u8]) -> Result<()> {
+        self.put_raw_expiring(doc_id.into(), line_bytes, None)
+    }
+
+    /// Put a raw line with an optional expiry time (Unix ms).
+    pub(crate) fn put_raw_expiring(
+        &mut self,
+        doc_id: String,
+        line_bytes: &[u8],
+        expires_at: Option<u64>,
+    ) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly("cannot put in read-only mode".to_string()));
+        }
+        self.check_unsharded()?;
+        check_line_id(&doc_id, line_bytes)?;
+        if self.validates() {
+            self.check_schemas(&[parse_raw_doc(line_bytes)?])?;
+        }
+        if let Some(ops) = &mut self.txn {
+            ops.push(TxnOp::Put {
+                doc_id,
+                line: line_bytes.to_vec(),
+                expires_at,
+                schema_id: None,
+            });
+            return Ok(());
+        }
+        self.index_raw_line(&doc_id, line_bytes);
+        self.append_line(doc_id, line_bytes, expires_at, None)
+    }
+
+    /// Whether writes are checked against a schema (so raw lines must be
+    /// parsed).
+    fn validates(&self) -> bool {
+        self.schema.is_some() || self.json_schema.is_some()
+    }
+
+    /// Check every document against the attached JSON Schema and, in strict
+    /// mode, that it has the collection's schema. The first documents
+    /// written set the strict schema; nothing is registered if any document
+    /// fails.
+    fn check_schemas(&mut self, docs: &[Value]) -> Result<()> {
+        if let Some(json_schema) = &self.json_schema {
+            docs.iter().try_for_each(|doc| json_schema.validate(doc))?;
+        }
+        let Some(registry) = &mut self.schema else {
+            return Ok(());
+        };
+        let unset = registry.strict_schema_id().is_none();
+        for doc in docs {
+            if let Err(e) = registry.register(doc) {
+                if unset {
+                    *registry = SchemaRegistry::new(true);
+                }
+                return Err(e);
+            }
+        }
+        if unset && registry.strict_schema_id().is_some() {
+            registry.save(&self.root, &self.collection)?;
+        }
+        Ok(())
+    }
+
+    /// Update field indexes and the column cache from a raw line (parsed
+    /// only when either exists).
+    fn index_raw_line(&mut self, doc_id: &str, line_bytes: &[u8]) {
+        if self.field_indexes.is_empty() && self.columns.is_none() {
+            return;
+        }
+        let doc = serde_json::from_slice::<Value>(line_bytes).ok();
+        if !self.field_indexes.is_empty() {
+            self.field_indexes.remove(doc_id);
+            if let Some(doc) = &doc {
+                self.field_indexes.insert(doc_id, doc);
+            }
+            self.field_indexes_dirty = true;
+        }
+        if let Some(columns) = &mut self.columns {
+            match &doc {
+                Some(doc) => columns.insert(doc_id, line_checksum(line_bytes), doc),
+                None => {
+                    columns.remove(doc_id);
+                }
+            }
+            self.columns_dirty = true;
+        }
+    }
+
+    /// Point `doc_id` at a newly written line, tracking live bytes and its
+    /// metadata. A new document goes last in insertion order; an
+    /// overwritten one keeps its place.
+    fn insert_entry(
+        &mut self,
+        doc_id: String,
+        offset: u64,
+        line: &[u8],
+        schema_id: Option<Arc<str>>,
+    ) {
+        let entry = IndexEntry {
+            offset,
+            length: line.len() as u32 + 1, // +1 for newline
+            checksum: line_checksum(line),
+        };
+        self.insert_indexed(doc_id, entry, schema_id);
+    }
+
+    /// Point `doc_id` at an index entry (see [`insert_entry`](Self::insert_entry)).
+    fn insert_indexed(&mut self, doc_id: String, entry: IndexEntry, schema_id: Option<Arc<str>>) {
+        let length = entry.length;
+        if !self.expires.is_empty() {
+            self.expires.remove(&doc_id);
+        }
+        let mtime = now_ms() / 1000;
+        let mut written = 0;
+        match self.doc_meta.get_mut(&doc_id) {
+            Some(meta) => {
+                written = meta.mtime;
+                meta.mtime = mtime;
+                meta.schema_id = schema_id;
+            }
+            None => {
+                let meta = DocMeta {
+                    seq: self.next_seq,
+                    mtime,
+                    schema_id,
+                };
+                self.next_seq += 1;
+                self.doc_meta.insert(doc_id.clone(), meta);
+            }
+        }
+        self.doc_meta_dirty = true;
+        if !self.subscribers.is_empty() {
+            self.notify(ChangeEvent::Put {
+                doc_id: doc_id.clone(),
+            });
+        }
+        if self.versions.retention() > 0 {
+            if let Some(&old) = self.index.get(&doc_id) {
+                self.versions.retain(&doc_id, old, written);
+            }
+        }
+        if let Some(dedup) = &mut self.dedup {
+            dedup.replace(&doc_id, self.index.get(&doc_id).copied(), &entry);
+        }
+        if let Some(ids) = self.sorted_ids.get_mut() {
+            ids.insert(&doc_id);
+        }
+        self.index_changed(&doc_id);
+        if let Some(old) = self.index.insert(doc_id, entry) {
+            self.live_bytes -= old.length as u64;
+        }
+        self.live_bytes += length as u64;
+        self.ordered.take();
+    }
+
+    /// Append a line to the data file and index it.
+    fn append_line(
+        &mut self,
+        doc_id: String,
+        line_bytes: &[u8],
+        expires_at: Option<u64>,
+        schema_id: Option<Arc<str>>,
+    ) -> Result<()> {
+        if self.dedup.as_ref().is_some_and(Dedup::enabled) {
+            self.write_dedup(doc_id, line_bytes, expires_at, schema_id)?;
+        } else {
+            self.write_line(doc_id, line_bytes, expires_at, schema_id)?;
+        }
+        self.sync_op(true)?;
+
+        // Auto-flush if batch size reached
+        if self.pending_count >= self.batch_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether `view` has the line an index entry points to.
+    fn holds_line(view: Option<&SegmentView>, entry: &IndexEntry) -> bool {
+        let Some(view) = view else {
+            return false;
+        };
+        let mut cache = ReadCache::default();
+        view.line(entry, &mut cache)
+            .ok()
+            .flatten()
+            .is_some_and(|line| line_checksum(line) == entry.checksum)
+    }
+
+    /// Point a document at an existing line with the same content, or
+    /// write it like [`write_line`](Self::write_line) if there is none.
+    fn write_dedup(
+        &mut self,
+        doc_id: String,
+        line_bytes: &[u8],
+        expires_at: Option<u64>,
+        schema_id: Option<Arc<str>>,
+    ) -> Result<()> {
+        if self.dedup.as_ref().is_some_and(Dedup::needs_lines) {
+            // Hash the lines documents point at, buffered ones included
+            if let Some(writer) = &mut self.writer {
+                writer.flush()?;
+            }
+            let view = self.current_mmap()?;
+            let mut entries: Vec<IndexEntry> = self.index.values().copied().collect();
+            entries.sort_unstable_by_key(|e| e.offset);
+            entries.dedup_by_key(|e| e.offset);
+            let mut cache = ReadCache::default();
+            let mut hashes = Vec::with_capacity(entries.len());
+            if let Some(view) = &view {
+                for entry in entries {
+                    if let Some(line) = view.line(&entry, &mut cache)? {
+                        let line = line.strip_suffix(b"\n").unwrap_or(line);
+                        hashes.push((dedup::content_hash(line), entry));
+                    }
+                }
+            }
+            if let Some(dedup) = &mut self.dedup {
+                dedup.build(hashes);
+            }
+        }
+
+        let hash = dedup::content_hash(line_bytes);
+        let Some(target) = self.dedup.as_ref().and_then(|d| d.find(&hash)) else {
+            self.write_line(doc_id.clone(), line_bytes, expires_at, schema_id)?;
+            let entry = self.index.get(&doc_id).copied().unwrap();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.add_line(hash, entry);
+            }
+            return Ok(());
+        };
+        self.insert_indexed(doc_id.clone(), target, schema_id);
+        if let Some(expires_at) = expires_at {
+            self.expires.insert(doc_id.clone(), expires_at);
+        }
+        if let Some(dedup) = &mut self.dedup {
+            dedup.share(&doc_id, &target, self.current_offset);
+        }
+        self.pending_count += 1;
+        Ok(())
+    }
+
+    /// Write a line to the active segment and index it, without flushing.
+    fn write_line(
+        &mut self,
+        doc_id: String,
+        line_bytes: &[u8],
+        expires_at: Option<u64>,
+        schema_id: Option<Arc<str>>,
+    ) -> Result<()> {
+        self.roll_segment(line_bytes.len() as u64 + 1)?;
+
+        // Write to buffer
+        if let Some(writer) = &mut self.writer {
+            writer.write_all(line_bytes)?;
+            writer.write_all(b"\n")?;
+        }
+
+        // Update index
+        let offset = self.current_offset;
+        match expires_at {
+            Some(expires_at) => {
+                self.insert_entry(doc_id.clone(), offset, line_bytes, schema_id);
+                self.expires.insert(doc_id, expires_at);
+            }
+            None => self.insert_entry(doc_id, offset, line_bytes, schema_id),
+        }
+        self.advance(line_bytes.len() as u64 + 1);
+        self.pending_count += 1;
+        Ok(())
+    }
+
+    /// Write a complete JSONL blob with doc_ids (fastest bulk path).
+    /// Uses SIMD newline search and single write for maximum throughput.
+    ///
+    /// Each non-empty line is written under the ID at its position, which
+    /// must be its `_id` as for [`put_raw_line`](Self::put_raw_line). Every
+    /// line is checked before any is written, and a blob whose line count
+    /// differs from the number of IDs is rejected with
+    /// [`Error::Validation`].
+    pub fn write_jsonl_blob(&mut self, jsonl_data: &[u8], doc_ids: &[String]) -> Result<usize> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot write in read-only mode".to_string(),
+            ));
+        }
+        if self.txn.is_some() {
+            return Err(Error::TransactionFailed(
+                "cannot write a JSONL blob inside a transaction".to_string(),
+            ));
+        }
+        self.check_unsharded()?;
+        // Check the whole blob before writing any of it
+        let mut line_count = 0;
+        for line in jsonl_data.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            if let Some(doc_id) = doc_ids.get(line_count) {
+                check_line_id(doc_id, line)?;
+            }
+            line_count += 1;
+        }
+        if line_count != doc_ids.len() {
+            return Err(Error::Validation(format!(
+                "JSONL blob has {} lines but {} document IDs",
+                line_count,
+                doc_ids.len()
+            )));
+        }
+        if self.validates() {
+            let docs = jsonl_data
+                .split(|&b| b == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(parse_raw_doc)
+                .collect::<Result<Vec<_>>>()?;
+            self.check_schemas(&docs)?;
+        }
+        // The whole blob goes into one segment
+        self.roll_segment(jsonl_data.len() as u64 + 1)?;
+        let writer = self.writer.as_mut().ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "Writer not available",
+            ))
+        })?;
+
+        // Write entire blob at once (single syscall)
+        writer.write_all(jsonl_data)?;
+
+        // Ensure trailing newline
+        if !jsonl_data.is_empty() && jsonl_data.last() != Some(&b'\n') {
+            writer.write_all(b"\n")?;
+        }
+
+        // Build index using SIMD newline search
+        let mut count = 0;
+        let mut line_start = 0;
+        let mut doc_idx = 0;
+
+        for newline_pos in memchr_iter(b'\n', jsonl_data) {
+            if doc_idx < doc_ids.len() && line_start < newline_pos {
+                let line = &jsonl_data[line_start..newline_pos];
+                self.index_raw_line(&doc_ids[doc_idx], line);
+
+                self.insert_entry(doc_ids[doc_idx].clone(), self.current_offset, line, None);
+                self.advance(line.len() as u64 + 1);
+                count += 1;
+                doc_idx += 1;
+            } else {
+                // Blank lines are written too, and later lines sit after them
+                self.advance((newline_pos - line_start) as u64 + 1);
+            }
+            line_start = newline_pos + 1;
+        }
+
+        // Handle last line without trailing newline
+        if line_start < jsonl_data.len() && doc_idx < doc_ids.len() {
+            let line = &jsonl_data[line_start..]; // newline added above
+            self.index_raw_line(&doc_ids[doc_idx], line);
+            self.insert_entry(doc_ids[doc_idx].clone(), self.current_offset, line, None);
+            self.advance(line.len() as u64 + 1);
+            count += 1;
+        }
+
+        self.pending_count += count;
+        self.sync_op(true)?;
+        Ok(count)
+    }
+
+    /// Start parallel ingestion into `shards` new data segments.
+    ///
+    /// The returned writer takes `&self`, so any number of threads can put
+    /// documents through it at once; each goes to the shard picked by a
+    /// hash of its ID. Its writes become visible once passed to
+    /// [`merge_shards`](Self::merge_shards). Until then the store stays
+    /// readable and deletes work, but puts, transactions and compaction
+    /// fail. Dropping the writer instead discards its writes.
+    ///
+    /// Documents are written without TTLs, and strict-mode collections
+    /// are not supported; a JSON Schema is checked by the writer.
+    pub fn sharded_writer(&mut self, shards: usize) -> Result<ShardedWriter> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot write shards in read-only mode".to_string(),
+            ));
+        }
+        if self.txn.is_some() {
+            return Err(Error::TransactionFailed(
+                "cannot start sharded writes inside a transaction".to_string(),
+            ));
+        }
+        if shards == 0 {
+            return Err(Error::Validation(
+                "a sharded writer needs at least one shard".to_string(),
+            ));
+        }
+        if self.schema.is_some() {
+            return Err(Error::StrictModeViolation(
+                "sharded writes are not supported in strict mode".to_string(),
+            ));
+        }
+        self.check_unsharded()?;
+        self.flush_writes()?;
+
+        let writer = ShardedWriter::create(
+            &self.root,
+            &self.collection,
+            self.active().no + 1,
+            shards,
+            self.compression,
+            self.cipher.as_ref(),
+            self.json_schema.clone(),
+        )?;
+        self.shard_handle = Some(writer.handle());
+        Ok(writer)
+    }
+
+    /// Add the segments of a [`ShardedWriter`] of this store and index
+    /// their lines, in shard order. Returns the number of lines merged.
+    ///
+    /// The last non-empty shard becomes the active segment, and the index
+    /// is saved as by [`flush`](Self::flush).
+    pub fn merge_shards(&mut self, writer: ShardedWriter) -> Result<usize> {
+        if !self
+            .shard_handle
+            .as_ref()
+            .is_some_and(|handle| handle.ptr_eq(&writer.handle()))
+        {
+            return Err(Error::Validation(
+                "the sharded writer belongs to another store".to_string(),
+            ));
+        }
+        let shards = writer.finish()?;
+        self.shard_handle = None;
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+            writer.sync_data()?;
+        }
+
+        let mut merged = 0;
+        let mut doc_ids = FxHashSet::default();
+        for shard in shards {
+            if shard.segment.len == 0 {
+                std::fs::remove_file(shard.segment.path(&self.root, &self.collection))?;
+                continue;
+            }
+            let no = shard.segment.no;
+            self.segments.push(shard.segment);
+            merged += shard.lines.len();
+            for line in shard.lines {
+                let schema_id = line.schema_id.map(|id| intern(&mut self.schema_ids, &id));
+                let entry = IndexEntry {
+                    offset: segment::pack(no, line.offset),
+                    length: line.length,
+                    checksum: line.checksum,
+                };
+                if !self.field_indexes.is_empty() || self.columns.is_some() {
+                    doc_ids.insert(line.doc_id.clone());
+                }
+                self.insert_indexed(line.doc_id, entry, schema_id);
+            }
+        }
+
+        let active = *self.active();
+        self.writer = Some(DataWriter::append(
+            &active.path(&self.root, &self.collection),
+            active.compression,
+            self.cipher.as_ref(),
+        )?);
+        self.current_offset = active.end();
+        for doc_id in doc_ids {
+            let line = self.raw_line(&doc_id)?;
+            self.index_raw_line(&doc_id, &line);
+        }
+        self.pending_count += merged;
+        self.flush()?;
+        Ok(merged)
+    }
+
+    /// Whether a [`ShardedWriter`] of this store is alive.
+    fn sharding(&self) -> bool {
+        self.shard_handle
+            .as_ref()
+            .is_some_and(|handle| handle.strong_count() > 0)
+    }
+
+    /// Fail while a [`ShardedWriter`] owns the next segments.
+    fn check_unsharded(&self) -> Result<()> {
+        if self.sharding() {
+            return Err(Error::Validation(
+                "sharded writes in progress; merge or drop the ShardedWriter first".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get a document by ID (uses mmap if available).
+    pub fn get(&self, doc_id: &str) -> Result<Value> {
+        let mut buffer = self.raw_line(doc_id)?;
+
+        // Use simd-json for faster parsing
+        let mut doc: Value = simd_json::from_slice(&mut buffer).map_err(|e| {
+            Error::Json(serde_json::Error::io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e.to_string(),
+            )))
+        })?;
+
+        if let Value::Object(ref mut obj) = doc {
+            obj.remove("_id");
+        }
+
+        Ok(doc)
+    }
+
+    /// Get several documents at once, in the order of `doc_ids`, with
+    /// `None` for missing ones.
+    ///
+    /// The requested lines are read in offset order in one pass over the
+    /// mmap and parsed in parallel, which beats calling [`get`](Self::get)
+    /// per document for batches of more than a few.
+    pub fn get_many<S: AsRef<str>>(&self, doc_ids: &[S]) -> Result<Vec<Option<Value>>> {
+        let mut entries: Vec<IndexEntry> = doc_ids
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|doc_id| !self.is_expired(doc_id))
+            .filter_map(|doc_id| self.index.get(doc_id).copied())
+            .collect();
+        entries.sort_unstable_by_key(|e| e.offset);
+        entries.dedup_by_key(|e| e.offset);
+
+        let mut found: FxHashMap<String, Value> = match self.current_mmap()? {
+            Some(view) => view
+                .par_map_lines(&entries, |line| {
+                    DocRef::with_line(line, |doc| Some((doc.id()?.to_string(), doc.to_value())))
+                        .ok()
+                        .flatten()
+                })
+                .into_iter()
+                .collect(),
+            None => FxHashMap::default(),
+        };
+
+        // Lines the pass didn't yield (and repeated IDs) are read one by one
+        doc_ids
+            .iter()
+            .map(|doc_id| match found.remove(doc_id.as_ref()) {
+                Some(doc) => Ok(Some(doc)),
+                None => match self.get(doc_id.as_ref()) {
+                    Ok(doc) => Ok(Some(doc)),
+                    Err(Error::DocumentNotFound(_)) => Ok(None),
+                    Err(e) => Err(e),
+                },
+            })
+            .collect()
+    }
+
+    /// The stored line of a document, `_id` included and without its
+    /// newline.
+    pub(crate) fn raw_line(&self, doc_id: &str) -> Result<Vec<u8>> {
+        let entry = self
+            .index
+            .get(doc_id)
+            .filter(|_| !self.is_expired(doc_id))
+            .ok_or_else(|| Error::DocumentNotFound(doc_id.to_string()))?;
+        let line = self.read_entry(entry)?;
+        match &self.dedup {
+            Some(dedup) if dedup.is_ref(doc_id) => dedup::with_id(&line, doc_id),
+            _ => Ok(line),
+        }
+    }
+
+    /// The line an index entry points to, without its newline.
+    fn read_entry(&self, entry: &IndexEntry) -> Result<Vec<u8>> {
+        // Use mmap for zero-copy access if available (compressed stores
+        // decode only the block holding the line)
+        let (no, offset) = segment::split(entry.offset);
+        let compression = self
+            .segments
+            .iter()
+            .find(|s| s.no == no)
+            .map_or(Compression::None, |s| s.compression);
+        let fresh = match &self.mmap {
+            Some(view) if view.end() >= entry.offset + entry.length as u64 => None,
+            _ if compression != Compression::None => self.current_mmap()?,
+            _ => None,
+        };
+        if let Some(view) = fresh.as_ref().or(self.mmap.as_ref()) {
+            let mut cache = ReadCache::default();
+            if let Some(line) = view.line(entry, &mut cache)? {
+                return Ok(line.strip_suffix(b"\n").unwrap_or(line).to_vec());
+            }
+        }
+
+        // Fallback to regular file I/O (compressed offsets are logical, so
+        // an unflushed line can only be read after a flush)
+        if compression != Compression::None {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let path = Layout::data_segment_file(&self.root, &self.collection, no, false);
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut buffer = vec![0u8; entry.length as usize];
+        std::io::Read::read_exact(&mut file, &mut buffer)?;
+
+        if buffer.last() == Some(&b'\n') {
+            buffer.pop();
+        }
+        Ok(buffer)
+    }
+
+    /// Number of previous versions kept per document (0, the default,
+    /// keeps none).
+    pub fn version_retention(&self) -> usize {
+        self.versions.retention()
+    }
+
+    /// Keep the last `keep` previous versions of every document when it is
+    /// overwritten, until it is deleted. The setting is saved in the
+    /// collection manifest; lowering it drops the oldest versions, and 0
+    /// drops them all. Compaction keeps retained versions like current
+    /// documents.
+    pub fn set_version_retention(&mut self, keep: usize) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot change version retention in read-only mode".to_string(),
+            ));
+        }
+        Versions::save_retention(&self.root, &self.collection, keep)?;
+        if self.versions.set_retention(keep) || keep == 0 {
+            self.flush_writes()?;
+            self.versions.save(&self.root, &self.collection)?;
+        }
+        Ok(())
+    }
+
+    /// Versions of a document, oldest first, ending with the current one.
+    /// Empty if the document doesn't exist.
+    pub fn list_versions(&self, doc_id: &str) -> Vec<DocVersion> {
+        let Some(entry) = self.index.get(doc_id).filter(|_| !self.is_expired(doc_id)) else {
+            return Vec::new();
+        };
+        let versions = self.versions.get(doc_id);
+        let mut list: Vec<DocVersion> = versions
+            .iter()
+            .map(|v| DocVersion {
+                version: v.version,
+                mtime: v.mtime,
+                size: v.entry.length as u64,
+                current: false,
+            })
+            .collect();
+        list.push(DocVersion {
+            version: self.versions.current(doc_id),
+            mtime: self.doc_meta.get(doc_id).map_or(0, |meta| meta.mtime),
+            size: entry.length as u64,
+            current: true,
+        });
+        list
+    }
+
+    /// Get a version of a document by its number (see
+    /// [`list_versions`](Self::list_versions)); the current version is the
+    /// same as [`get`](Self::get).
+    pub fn get_version(&self, doc_id: &str, version: u64) -> Result<Value> {
+        if version == self.versions.current(doc_id) {
+            return self.get(doc_id);
+        }
+        let entry = self
+            .versions
+            .get(doc_id)
+            .iter()
+            .find(|v| v.version == version)
+            .map(|v| v.entry)
+            .ok_or_else(|| Error::DocumentNotFound(format!("{} (version {})", doc_id, version)))?;
+        let mut line = self.read_entry(&entry)?;
+        if line_checksum(&line) != entry.checksum {
+            return Err(Error::Validation(format!(
+                "version {} of '{}' failed its checksum",
+                version, doc_id
+            )));
+        }
+        let mut doc: Value = simd_json::from_slice(&mut line).map_err(|e| {
+            Error::Json(serde_json::Error::io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                e.to_string(),
+            )))
+        })?;
+        if let Value::Object(ref mut obj) = doc {
+            obj.remove("_id");
+        }
+        Ok(doc)
+    }
+
+    /// Whether documents identical to one already stored share its line.
+    pub fn dedup_enabled(&self) -> bool {
+        self.dedup.as_ref().is_some_and(Dedup::enabled)
+    }
+
+    /// Store documents whose content (everything but `_id`) matches a
+    /// line already in the data segments as a reference to that line
+    /// instead of a copy. The setting is saved in the collection manifest.
+    /// Disabling it keeps the lines already shared; only puts outside
+    /// transactions are deduplicated.
+    pub fn set_dedup(&mut self, enabled: bool) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot change deduplication in read-only mode".to_string(),
+            ));
+        }
+        Dedup::save_enabled(&self.root, &self.collection, enabled)?;
+        match &mut self.dedup {
+            Some(dedup) => dedup.set_enabled(enabled),
+            None if enabled => {
+                let mut dedup = Dedup::default();
+                dedup.set_enabled(true);
+                dedup.recount(&self.index);
+                self.dedup = Some(dedup);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Documents stored as a reference to another document's line, and
+    /// the bytes that saves.
+    pub fn dedup_savings(&self) -> (usize, u64) {
+        self.dedup.as_ref().map_or((0, 0), Dedup::savings)
+    }
+
+    /// Delete a document.
+    pub fn delete(&mut self, doc_id: &str) -> Result<()> {
+        if self.txn.is_some() {
+            if !self.exists_in_txn(doc_id) {
+                return Err(Error::DocumentNotFound(doc_id.to_string()));
+            }
+            if let Some(ops) = &mut self.txn {
+                ops.push(TxnOp::Delete {
+                    doc_id: doc_id.to_string(),
+                });
+            }
+            return Ok(());
+        }
+        if !self.exists(doc_id) {
+            return Err(Error::DocumentNotFound(doc_id.to_string()));
+        }
+        self.remove_entry(doc_id);
+        self.sync_op(false)
+    }
+
+    /// Whether a document exists once the open transaction's writes apply.
+    fn exists_in_txn(&self, doc_id: &str) -> bool {
+        let last = self
+            .txn
+            .as_ref()
+            .and_then(|ops| ops.iter().rev().find(|op| op.doc_id() == doc_id));
+        match last {
+            Some(TxnOp::Put { .. }) => true,
+            Some(TxnOp::Delete { .. }) => false,
+            None => self.exists(doc_id),
+        }
+    }
+
+    /// Attach a binary payload (an image, audio clip, ...) to a document
+    /// under `name`, replacing any blob of that name.
+    ///
+    /// Blobs are stored apart from the JSON documents, become durable on
+    /// [`flush`](Self::flush) and are removed with their document.
+    pub fn put_blob(&mut self, doc_id: &str, name: &str, bytes: &[u8]) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot write blobs in read-only mode".to_string(),
+            ));
+        }
+        if name.is_empty() {
+            return Err(Error::Validation("blob name must not be empty".to_string()));
+        }
+        if !self.exists(doc_id) {
+            return Err(Error::DocumentNotFound(doc_id.to_string()));
+        }
+        let blobs = match &mut self.blobs {
+            Some(blobs) => blobs,
+            None => self.blobs.insert(BlobStore::open(
+                &self.root,
+                &self.collection,
+                self.mode,
+                self.cipher.clone(),
+            )?),
+        };
+        blobs.put(doc_id, name, bytes)
+    }
+
+    /// Read a document's blob.
+    pub fn get_blob(&self, doc_id: &str, name: &str) -> Result<Vec<u8>> {
+        match &self.blobs {
+            Some(blobs) => blobs.get(doc_id, name),
+            None => Err(Error::DocumentNotFound(format!(
+                "{} (blob '{}')",
+                doc_id, name
+            ))),
+        }
+    }
+
+    /// Names of a document's blobs, sorted.
+    pub fn blob_names(&self, doc_id: &str) -> Vec<String> {
+        self.blobs
+            .as_ref()
+            .map(|blobs| blobs.names(doc_id))
+            .unwrap_or_default()
+    }
+
+    /// Remove a document's blob.
+    pub fn delete_blob(&mut self, doc_id: &str, name: &str) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot delete blobs in read-only mode".to_string(),
+            ));
+        }
+        let removed = self
+            .blobs
+            .as_mut()
+            .is_some_and(|blobs| blobs.remove(doc_id, name));
+        if !removed {
+            return Err(Error::DocumentNotFound(format!(
+                "{} (blob '{}')",
+                doc_id, name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Drop an indexed document and record its tombstone.
+    fn remove_entry(&mut self, doc_id: &str) {
+        self.index_changed(doc_id);
+        let entry = self.index.remove(doc_id);
+        if let Some(entry) = entry {
+            self.live_bytes -= entry.length as u64;
+        }
+        if let Some(ids) = self.sorted_ids.get_mut() {
+            ids.remove(doc_id);
+        }
+        if let Some(dedup) = &mut self.dedup {
+            dedup.remove(doc_id, entry, self.current_offset);
+        }
+        self.versions.remove(doc_id);
+        self.doc_meta.remove(doc_id);
+        self.doc_meta_dirty = true;
+        self.expires.remove(doc_id);
+        self.ordered.take();
+        self.pending_tombstones.push(Tombstone {
+            id: doc_id.to_string(),
+            offset: self.current_offset,
+        });
+        self.tombstones
+            .insert(doc_id.to_string(), self.current_offset);
+        if let Some(blobs) = &mut self.blobs {
+            blobs.remove_doc(doc_id);
+        }
+        for index in self.vectors.values_mut() {
+            self.vectors_dirty |= index.remove(doc_id);
+        }
+        if !self.field_indexes.is_empty() {
+            self.field_indexes.remove(doc_id);
+            self.field_indexes_dirty = true;
+        }
+        if let Some(columns) = &mut self.columns {
+            self.columns_dirty |= columns.remove(doc_id);
+        }
+        if !self.subscribers.is_empty() {
+            self.notify(ChangeEvent::Delete {
+                doc_id: doc_id.to_string(),
+            });
+        }
+    }
+
+    /// Receive the changes made through this handle.
+    ///
+    /// Every put and delete is sent as it is applied: writes of a
+    /// transaction on commit, and documents whose TTL ran out when
+    /// compaction deletes them. Changes made by other handles or processes
+    /// are not seen; use a [`Watcher`] for those. Dropping the receiver
+    /// unsubscribes.
+    pub fn subscribe(&mut self) -> std::sync::mpsc::Receiver<ChangeEvent> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Send an event to the subscribers, forgetting those that hung up.
+    fn notify(&mut self, event: ChangeEvent) {
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Check if document exists.
+    pub fn exists(&self, doc_id: &str) -> bool {
+        self.index.contains_key(doc_id) && !self.is_expired(doc_id)
+    }
+
+    /// Get document count.
+    pub fn len(&self) -> usize {
+        if self.expires.is_empty() {
+            return self.index.len();
+        }
+        let now = now_ms();
+        let expired = self.expires.values().filter(|&&t| t <= now).count();
+        self.index.len() - expired
+    }
+
+    /// Check if empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// When a document written with [`put_with_ttl`](Self::put_with_ttl)
+    /// expires (`None` for documents without a TTL).
+    pub fn expires_at(&self, doc_id: &str) -> Option<SystemTime> {
+        self.expires
+            .get(doc_id)
+            .map(|&ms| UNIX_EPOCH + Duration::from_millis(ms))
+    }
+
+    /// Unexpired documents with their index entries and expiry times
+    /// (Unix ms), in no particular order.
+    pub(crate) fn indexed(&self) -> impl Iterator<Item = (&str, &IndexEntry, Option<u64>)> {
+        let now = now_ms();
+        self.index.iter().filter_map(move |(id, entry)| {
+            let expires_at = self.expires.get(id).copied();
+            match expires_at {
+                Some(t) if t <= now => None,
+                _ => Some((id, entry, expires_at)),
+            }
+        })
+    }
+
+    /// Whether a document's TTL has run out.
+    fn is_expired(&self, doc_id: &str) -> bool {
+        self.expires
+            .get(doc_id)
+            .is_some_and(|&expires_at| expires_at <= now_ms())
+    }
+
+    /// Get all document IDs in file order (see [`FastStore::iter`]).
+    pub fn doc_ids(&self) -> Vec<String> {
+        let now = now_ms();
+        let mut ids: Vec<(&str, u64)> = self
+            .index
+            .iter()
+            .filter(|(id, _)| self.expires.get(*id).map_or(true, |&t| t > now))
+            .map(|(id, e)| (id, e.offset))
+            .collect();
+        ids.sort_unstable_by_key(|&(_, offset)| offset);
+        ids.into_iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    /// IDs starting with `prefix`, sorted.
+    ///
+    /// With hierarchical IDs (`"user/123/event/456"`), a prefix ending in
+    /// the separator lists a subtree. The sorted IDs are built on the first
+    /// prefix or range scan (or when the store is opened, with
+    /// [`KeyOrder::Sorted`]) and kept up to date by writes.
+    pub fn ids_with_prefix(&self, prefix: &str) -> Vec<String> {
+        let now = now_ms();
+        self.sorted_ids()
+            .with_prefix(prefix)
+            .filter(|id| self.expires.get(*id).map_or(true, |&t| t > now))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// IDs within `range` (e.g. `"a".."m"`), sorted.
+    pub fn id_range<'a>(&self, range: impl RangeBounds<&'a str>) -> Vec<String> {
+        let now = now_ms();
+        self.sorted_ids()
+            .range(id_bounds(&range))
+            .filter(|id| self.expires.get(*id).map_or(true, |&t| t > now))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Documents whose IDs are within `range`, in ID order (see
+    /// [`id_range`](Self::id_range)).
+    pub fn scan_id_range<'a>(&self, range: impl RangeBounds<&'a str>) -> Result<Vec<Value>> {
+        let ids = self.id_range(range);
+        Ok(self.get_many(&ids)?.into_iter().flatten().collect())
+    }
+
+    /// The sorted IDs of prefix and range scans.
+    fn sorted_ids(&self) -> &SortedIds {
+        self.sorted_ids
+            .get_or_init(|| SortedIds::new(self.index.keys()))
+    }
+
+    /// Rebuild the sorted IDs after the index was reloaded, if they were
+    /// built.
+    fn reset_sorted_ids(&mut self) {
+        if self.sorted_ids.take().is_some() {
+            let _ = self.sorted_ids.set(SortedIds::new(self.index.keys()));
+        }
+    }
+
+    /// Documents whose IDs start with `prefix`, in ID order (see
+    /// [`ids_with_prefix`](Self::ids_with_prefix)).
+    pub fn scan_prefix(&self, prefix: &str) -> Result<Vec<Value>> {
+        let ids = self.ids_with_prefix(prefix);
+        Ok(self.get_many(&ids)?.into_iter().flatten().collect())
+    }
+
+    /// Document index of the live documents in insertion order, for
+    /// reading the collection through the [`Engine`](crate::Engine).
+    ///
+    /// Sizes are line lengths; the schema ID is empty for documents written
+    /// as raw lines, and the mtime 0 for documents written before the order
+    /// was saved.
+    pub(crate) fn doc_index(&self) -> IndexRegistry {
+        let now = now_ms();
+        let mut docs: Vec<(&String, &DocMeta)> = self
+            .doc_meta
+            .iter()
+            .filter(|(id, _)| self.expires.get(*id).map_or(true, |&t| t > now))
+            .collect();
+        docs.sort_unstable_by_key(|(_, meta)| meta.seq);
+        let mut index = IndexRegistry::new();
+        for (doc_id, meta) in docs {
+            index.put(DocIndexEntry {
+                doc_id: doc_id.clone(),
+                schema_id: meta.schema_id.as_deref().unwrap_or_default().to_string(),
+                size: self.index.get(doc_id).map_or(0, |e| e.length as u64),
+                mtime: meta.mtime,
+            });
+        }
+        index
+    }
+
+    /// Unexpired index entries sorted by offset, cached until the next write
+    /// or until one of them expires.
+    fn ordered_entries(&self) -> Arc<Vec<IndexEntry>> {
+        let now = now_ms();
+        match self.ordered.get() {
+            Some((entries, valid_until)) if now < *valid_until => entries.clone(),
+            // Stale: rebuilt on every call until the next write resets it
+            Some(_) => self.live_entries(now).0,
+            None => self
+                .ordered
+                .get_or_init(|| self.live_entries(now))
+                .0
+                .clone(),
+        }
+    }
+
+    /// Whether some documents point at another document's line, so raw
+    /// lines need their `_id` put back.
+    fn shares_lines(&self) -> bool {
+        self.dedup.as_ref().is_some_and(Dedup::has_refs)
+    }
+
+    /// Unexpired index entries sorted by offset, with the IDs of their
+    /// documents if some share a line (not cached).
+    fn ordered_with_ids(&self) -> (Arc<Vec<IndexEntry>>, Option<Arc<Vec<String>>>) {
+        if !self.shares_lines() {
+            return (self.ordered_entries(), None);
+        }
+        let now = now_ms();
+        let mut docs: Vec<(&str, &IndexEntry)> = self
+            .index
+            .iter()
+            .filter(|(id, _)| self.expires.get(*id).map_or(true, |&t| t > now))
+            .collect();
+        docs.sort_unstable_by_key(|&(id, e)| (e.offset, id));
+        let (ids, entries) = docs.into_iter().map(|(id, e)| (id.to_string(), *e)).unzip();
+        (Arc::new(entries), Some(Arc::new(ids)))
+    }
+
+    /// Index entries unexpired at `now` sorted by offset, and the time the
+    /// first of them expires.
+    fn live_entries(&self, now: u64) -> (Arc<Vec<IndexEntry>>, u64) {
+        let mut valid_until = u64::MAX;
+        let mut entries: Vec<IndexEntry> = self
+            .index
+            .iter()
+            .filter(|(id, _)| match self.expires.get(*id) {
+                Some(&expires_at) if expires_at <= now => false,
+                Some(&expires_at) => {
+                    valid_until = valid_until.min(expires_at);
+                    true
+                }
+                None => true,
+            })
+            .map(|(_, e)| *e)
+            .collect();
+        entries.sort_unstable_by_key(|e| e.offset);
+        (Arc::new(entries), valid_until)
+    }
+
+    /// Delete the documents whose TTL has run out, returning how many.
+    fn purge_expired(&mut self) -> usize {
+        let now = now_ms();
+        let expired: Vec<String> = self
+            .expires
+            .iter()
+            .filter(|(_, &expires_at)| expires_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for doc_id in &expired {
+            self.remove_entry(doc_id);
+        }
+        expired.len()
+    }
+
+    /// Flush pending writes to disk.
+    ///
+    /// Compacts the data file afterwards if the compaction policy says so.
+    pub fn flush(&mut self) -> Result<()> {
+        // A reader has nothing to write, and saving its index would undo
+        // the writer's
+        if self.mode == OpenMode::Read {
+            return Ok(());
+        }
+        self.flush_writes()?;
+        if self.needs_compaction() {
+            self.rewrite_data(self.compaction)?;
+        }
+        Ok(())
+    }
+
+    /// Make pending writes durable: data first, then tombstones, then the index.
+    fn flush_writes(&mut self) -> Result<()> {
+        self.sync_writes()?;
+        self.save_index_changes()?;
+        if self.field_indexes_dirty {
+            self.field_indexes.save(&self.root, &self.collection)?;
+            self.field_indexes_dirty = false;
+        }
+        if self.doc_meta_dirty && self.saves_doc_meta {
+            self.doc_index().save(&self.root, &self.collection)?;
+            manifest::record_counts(
+                &self.root,
+                &self.collection,
+                self.len(),
+                self.schema_count(),
+            )?;
+            self.doc_meta_dirty = false;
+        }
+        if self.vectors_dirty {
+            for index in self.vectors.values() {
+                index.save(&self.root, &self.collection)?;
+            }
+            self.vectors_dirty = false;
+        }
+        if self.columns_dirty {
+            if let Some(columns) = &self.columns {
+                columns.save(&self.root, &self.collection)?;
+            }
+            self.columns_dirty = false;
+        }
+        if let Some(blobs) = &mut self.blobs {
+            blobs.sync()?;
+        }
+        self.versions.sync(&self.root, &self.collection)?;
+        if let Some(dedup) = &mut self.dedup {
+            dedup.sync(&self.root, &self.collection)?;
+        }
+        if let Some(sequence) = &mut self.id_sequence {
+            sequence.save(&self.root, &self.collection)?;
+        }
+        Ok(())
+    }
+
+    /// Number of distinct schemas among the documents.
+    fn schema_count(&self) -> usize {
+        self.doc_meta
+            .values()
+            .filter_map(|meta| meta.schema_id.as_ref())
+            .collect::<FxHashSet<_>>()
+            .len()
+    }
+
+    /// Sync pending data lines, then append and sync pending tombstones
+    /// (each written through without a sync if the durability doesn't sync
+    /// batches).
+    fn sync_writes(&mut self) -> Result<()> {
+        let lines = std::mem::take(&mut self.pending_count) > 0;
+        self.write_through(lines, self.durability.syncs_batches())
+    }
+
+    /// Sync the write just made outside a transaction, with
+    /// [`Durability::FsyncOp`]. It stays pending for the next flush, which
+    /// saves the index.
+    fn sync_op(&mut self, lines: bool) -> Result<()> {
+        if self.durability.syncs_ops() && self.txn.is_none() {
+            self.write_through(lines, true)?;
+        }
+        Ok(())
+    }
+
+    /// Write buffered data lines and pending tombstones through to the
+    /// operating system, syncing the data if `lines` were written and
+    /// `sync`, and the tombstones if `sync`.
+    fn write_through(&mut self, lines: bool, sync: bool) -> Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+            if lines && sync {
+                writer.sync_data()?;
+            }
+        }
+        if !self.pending_tombstones.is_empty() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(Layout::tombstone_file(&self.root, &self.collection))?;
+            let mut writer = BufWriter::new(file);
+            for tombstone in self.pending_tombstones.drain(..) {
+                writeln!(writer, "{}", serde_json::to_string(&tombstone)?)?;
+            }
+            writer.flush()?;
+            if sync {
+                writer.get_ref().sync_data()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a transaction.
+    ///
+    /// Until [`commit`](Self::commit), puts and deletes are buffered in
+    /// memory and reads see only committed documents. A transaction still
+    /// open when the store is dropped is discarded.
+    pub fn begin(&mut self) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot begin a transaction in read-only mode".to_string(),
+            ));
+        }
+        if self.txn.is_some() {
+            return Err(Error::TransactionFailed(
+                "a transaction is already in progress".to_string(),
+            ));
+        }
+        self.check_unsharded()?;
+        self.txn = Some(Vec::new());
+        Ok(())
+    }
+
+    /// Check whether a transaction is open.
+    pub fn in_transaction(&self) -> bool {
+        self.txn.is_some()
+    }
+
+    /// Apply the open transaction's writes atomically and make them durable.
+    ///
+    /// If the process dies before the COMMIT record is synced, the next open
+    /// rolls the data and tombstone files back to where the transaction
+    /// started. After an I/O error the store should be reopened, which
+    /// rolls back the partial commit the same way.
+    pub fn commit(&mut self) -> Result<()> {
+        let ops = self
+            .txn
+            .take()
+            .ok_or_else(|| Error::TransactionFailed("no transaction in progress".to_string()))?;
+        if ops.is_empty() {
+            return Ok(());
+        }
+
+        // Earlier writes go first, so everything past BEGIN is the transaction's
+        self.flush_writes()?;
+        let active = *self.active();
+        let segment_len =
+            std::fs::metadata(active.path(&self.root, &self.collection)).map_or(0, |m| m.len());
+        let tombstones_len =
+            std::fs::metadata(Layout::tombstone_file(&self.root, &self.collection))
+                .map_or(0, |m| m.len());
+        let begin =
+            JournalEntry::begin(active.no, segment_len, self.current_offset, tombstones_len);
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            journal => journal.insert(TransactionLog::open(&self.root, &self.collection)?),
+        };
+        journal.set_durability(self.durability)?;
+        journal.append(&begin)?;
+        // BEGIN must be on disk before any of the transaction's data
+        journal.sync()?;
+
+        for op in ops {
+            match op {
+                TxnOp::Put {
+                    doc_id,
+                    line,
+                    expires_at,
+                    schema_id,
+                } => {
+                    self.index_raw_line(&doc_id, &line);
+                    self.write_line(doc_id, &line, expires_at, schema_id)?;
+                }
+                TxnOp::Delete { doc_id } => {
+                    if self.index.contains_key(&doc_id) {
+                        self.remove_entry(&doc_id);
+                    }
+                }
+            }
+        }
+        self.sync_writes()?;
+        if let Some(journal) = &mut self.journal {
+            journal.commit()?;
+        }
+        self.flush()
+    }
+
+    /// Discard the open transaction's writes.
+    pub fn rollback(&mut self) -> Result<()> {
+        self.txn
+            .take()
+            .ok_or_else(|| Error::TransactionFailed("no transaction in progress".to_string()))?;
+        Ok(())
+    }
+
+    /// The start of the last transaction in the journal, if it never committed.
+    fn interrupted_transaction(root: &Path, collection: &str) -> Result<Option<TxnStart>> {
+        let uncommitted = TransactionLog::load_uncommitted(root, collection)?;
+        Ok(uncommitted.into_iter().rev().find_map(|entry| match entry {
+            JournalEntry::Begin {
+                segment,
+                segment_len,
+                offset,
+                tombstones_len,
+                ..
+            } => Some(TxnStart {
+                segment,
+                segment_len,
+                offset,
+                tombstones_len,
+            }),
+            _ => None,
+        }))
+    }
+
+    /// Cut the data and tombstone files back to a transaction's start, then
+    /// checkpoint the journal so the rollback isn't repeated.
+    fn undo_transaction(root: &Path, collection: &str, start: TxnStart) -> Result<()> {
+        for entry in std::fs::read_dir(Layout::meta_dir(root, collection))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some((no, _)) = name.to_str().and_then(Layout::parse_data_segment) else {
+                continue;
+            };
+            if no > start.segment {
+                std::fs::remove_file(entry.path())?;
+            } else if no == start.segment && entry.metadata()?.len() > start.segment_len {
+                OpenOptions::new()
+                    .write(true)
+                    .open(entry.path())?
+                    .set_len(start.segment_len)?;
+            }
+        }
+        let tombstone_file = Layout::tombstone_file(root, collection);
+        if std::fs::metadata(&tombstone_file).is_ok_and(|m| m.len() > start.tombstones_len) {
+            OpenOptions::new()
+                .write(true)
+                .open(&tombstone_file)?
+                .set_len(start.tombstones_len)?;
+        }
+        TransactionLog::open(root, collection)?.checkpoint()
+    }
+
+    /// The collection's options, as currently stored in its manifest.
+    pub fn config(&self) -> Result<CollectionConfig> {
+        CollectionConfig::load(&self.root, &self.collection)
+    }
+
+    /// Get the automatic compaction policy (on open, the one of the
+    /// manifest's `auto_compact` threshold).
+    pub fn compaction_policy(&self) -> Option<CompactionPolicy> {
+        self.compaction
+    }
+
+    /// Set the automatic compaction policy (`None` disables it).
+    pub fn set_compaction_policy(&mut self, policy: Option<CompactionPolicy>) {
+        self.compaction = policy;
+    }
+
+    /// Get when writes are synced ([`Durability::FsyncBatch`] on open).
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Set when writes are synced, from the next write on. It isn't saved
+    /// with the collection.
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    /// Bytes in the data segments that belong to deleted or overwritten
+    /// lines, other than retained versions.
+    pub fn dead_bytes(&self) -> u64 {
+        let (_, shared) = self.dedup_savings();
+        self.data_len()
+            .saturating_sub(self.live_bytes - shared + self.versions.bytes())
+    }
+
+    /// Fraction of the data that is garbage (0.0 for an empty store).
+    pub fn dead_ratio(&self) -> f64 {
+        let total = self.data_len();
+        if total == 0 {
+            0.0
+        } else {
+            self.dead_bytes() as f64 / total as f64
+        }
+    }
+
+    /// Storage statistics: sizes, live offset range and last flush time.
+    ///
+    /// Flushing saves `index.bin` or appends to `index.delta`, so the later
+    /// of their modification times is the time of the last flush (of the
+    /// last one that changed documents, as a flush without changes writes
+    /// neither).
+    pub fn stats(&self) -> FastStoreStats {
+        let offsets = self.index.values().map(|e| e.offset);
+        let last_flush = [&self.index_file, &self.index_delta_file]
+            .into_iter()
+            .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .max()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64);
+        FastStoreStats {
+            doc_count: self.len(),
+            data_bytes: self.data_len(),
+            live_bytes: self.live_bytes,
+            dead_bytes: self.dead_bytes(),
+            dead_ratio: self.dead_ratio(),
+            index_bytes: self.index_bytes(),
+            index_memory_bytes: self.index_memory_bytes(),
+            min_offset: offsets.clone().min(),
+            max_offset: offsets.max(),
+            last_flush,
+            segments: self.segment_count(),
+            tombstones: self.tombstone_count(),
+            compression: self.compression(),
+            dedup_docs: self.dedup_savings().0,
+            dedup_bytes: self.dedup_savings().1,
+        }
+    }
+
+    /// Bytes that [`compact`](Self::compact) would free: the dead lines plus
+    /// the lines of expired documents.
+    pub fn reclaimable_bytes(&self) -> u64 {
+        let now = now_ms();
+        let expired: u64 = self
+            .expires
+            .iter()
+            .filter(|(_, &expires_at)| expires_at <= now)
+            .filter_map(|(id, _)| self.index.get(id))
+            .map(|e| e.length as u64)
+            .sum();
+        self.dead_bytes() + expired
+    }
+
+    /// Live bytes per segment number, retained versions included. A line
+    /// shared by several documents counts once.
+    fn live_bytes_by_segment(&self) -> FxHashMap<u32, u64> {
+        let mut live = FxHashMap::default();
+        let mut seen = FxHashSet::default();
+        let versions = self.versions.entries().map(|(_, v)| &v.entry);
+        for entry in self.index.values().chain(versions) {
+            if self.dedup.is_some() && !seen.insert(entry.offset) {
+                continue;
+            }
+            *live.entry(segment::split(entry.offset).0).or_insert(0) += entry.length as u64;
+        }
+        live
+    }
+
+    /// Number of deleted IDs recorded since the last compaction.
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// IDs deleted since the last full compaction and not written again, sorted.
+    pub fn deleted_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .tombstones
+            .keys()
+            .filter(|id| !self.index.contains_key(id))
+            .cloned()
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Check the data file against the line checksums in the index.
+    ///
+    /// Pending writes are flushed first. `index.bin` is checked against its
+    /// own checksum, then every line it references with the changes of
+    /// `index.delta` applied (the in-memory index if it is damaged) is checked for truncation, checksum mismatch, invalid
+    /// JSON and a wrong `_id`. Opening a store repairs what it can, so this
+    /// reads the on-disk index rather than the recovered one.
+    pub fn verify(&mut self) -> Result<VerifyReport> {
+        if self.mode == OpenMode::ReadWrite {
+            self.flush_writes()?;
+        }
+        let mut disk_index = IdMap::default();
+        let index_ok = Self::load_index_binary(
+            &self.index_file,
+            &self.index_delta_file,
+            self.cipher.as_deref(),
+            &encryption::file_aad(&self.root, &self.collection, &self.index_file),
+            &mut disk_index,
+            &mut FxHashMap::default(),
+        )
+        .is_ok();
+        let index = if index_ok { &disk_index } else { &self.index };
+
+        let view = self.current_mmap()?;
+        let mut entries: Vec<(&str, &IndexEntry)> = index.iter().collect();
+        entries.sort_unstable_by_key(|(_, e)| e.offset);
+
+        let mut report = VerifyReport {
+            checked: entries.len(),
+            corrupt: Vec::new(),
+            index_ok,
+        };
+        let mut cache = ReadCache::default();
+        for (doc_id, entry) in entries {
+            let line = match &view {
+                Some(view) => view.line(entry, &mut cache).unwrap_or(None),
+                None => None,
+            };
+            let kind = match line {
+                None => Some(CorruptionKind::Truncated),
+                Some(line) if line_checksum(line) != entry.checksum => {
+                    Some(CorruptionKind::ChecksumMismatch)
+                }
+                Some(line) => {
+                    let line = line.strip_suffix(b"\n").unwrap_or(line);
+                    // IDs are compared like the write path reads them, so
+                    // integer IDs match their digits
+                    match serde_json::from_slice::<serde::de::IgnoredAny>(line) {
+                        Err(_) => Some(CorruptionKind::InvalidJson),
+                        Ok(_)
+                            if Self::extract_id_fast(line).as_deref() != Some(doc_id)
+                                && !self.dedup.as_ref().is_some_and(|d| d.is_ref(doc_id)) =>
+                        {
+                            Some(CorruptionKind::IdMismatch)
+                        }
+                        Ok(_) => None,
+                    }
+                }
+            };
+            if let Some(kind) = kind {
+                let (segment, offset) = segment::split(entry.offset);
+                report.corrupt.push(CorruptEntry {
+                    doc_id: doc_id.to_string(),
+                    segment,
+                    offset,
+                    kind,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Check whether the compaction policy selects any segment.
+    fn needs_compaction(&self) -> bool {
+        let Some(policy) = self.compaction else {
+            return false;
+        };
+        if self.mode == OpenMode::Read
+            || self.sharding()
+            || self.dead_bytes() < policy.min_dead_bytes
+        {
+            return false;
+        }
+        let live = self.live_bytes_by_segment();
+        self.segments.iter().any(|s| {
+            let dead = s.len.saturating_sub(live.get(&s.no).copied().unwrap_or(0));
+            policy.should_compact(dead, s.len)
+        })
+    }
+
+    /// Scan all documents using mmap + parallel SIMD parsing.
+    pub fn scan(&self) -> Result<Vec<Value>> {
+        if self.index.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Use mmap for zero-copy access (refreshed if writes were flushed)
+        match self.current_mmap()? {
+            Some(view) => self.scan_mmap_parallel(&view),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Scan using memory-mapped file with parallel SIMD parsing.
+    fn scan_mmap_parallel(&self, view: &SegmentView) -> Result<Vec<Value>> {
+        Ok(Self::parse_lines(view, &self.ordered_entries()))
+    }
+
+    /// Parse the lines of `entries` (sorted by offset) in parallel, without
+    /// their `_id`.
+    fn parse_lines(view: &SegmentView, entries: &[IndexEntry]) -> Vec<Value> {
+        view.par_map_lines(entries, |line| {
+            DocRef::with_line(line, |doc| doc.to_value()).ok()
+        })
+    }
+
+    /// Scan the documents matching `predicate` (all of them with `None`),
+    /// projected onto `fields` (whole documents with `None`), in file order.
+    ///
+    /// Each line is parsed, filtered and projected inside the parallel scan,
+    /// so only matching documents are materialized. When the predicate
+    /// references indexed fields, only the candidate documents are read.
+    pub fn scan_filtered(
+        &self,
+        predicate: Option<&Predicate>,
+        fields: Option<&[&str]>,
+    ) -> Result<Vec<Value>> {
+        let Some(view) = self.current_mmap()? else {
+            return Ok(Vec::new());
+        };
+        let entries = self.ordered_entries();
+        let candidates = predicate.and_then(|p| self.field_indexes.candidates(p));
+        let entries: Cow<'_, [IndexEntry]> = match candidates {
+            Some(candidates) => {
+                let mut picked: Vec<IndexEntry> = candidates
+                    .iter()
+                    .filter(|doc_id| !self.is_expired(doc_id))
+                    .filter_map(|doc_id| self.index.get(doc_id).copied())
+                    .collect();
+                picked.sort_unstable_by_key(|e| e.offset);
+                Cow::Owned(picked)
+            }
+            None => Cow::Borrowed(&entries),
+        };
+
+        Ok(view.par_map_lines(&entries, |line| {
+            DocRef::with_line(line, |doc| {
+                if predicate.is_some_and(|predicate| !doc.matches(predicate)) {
+                    return None;
+                }
+                match fields {
+                    Some(fields) => doc.project(fields).ok(),
+                    None => Some(doc.to_value()),
+                }
+            })
+            .ok()
+            .flatten()
+        }))
+    }
+
+    /// Map `f` over every document, in parallel, keeping what it returns,
+    /// in file order.
+    ///
+    /// Documents are handed to `f` as [`DocRef`]s borrowing from the
+    /// segment, so fields `f` doesn't look at are never copied, and
+    /// documents it skips are never materialized. Lines that fail to parse
+    /// are skipped. Pending writes must be flushed first to be scanned.
+    pub fn scan_with<T, F>(&self, f: F) -> Result<Vec<T>>
+    where
+        T: Send,
+        F: Fn(&DocRef<'_>) -> Option<T> + Sync,
+    {
+        let Some(view) = self.current_mmap()? else {
+            return Ok(Vec::new());
+        };
+        Ok(view.par_map_lines(&self.ordered_entries(), |line| {
+            DocRef::with_line(line, &f).ok().flatten()
+        }))
+    }
+
+    /// Aggregate the store, optionally per group (see [`Engine::aggregate`]).
+    ///
+    /// Documents are parsed and folded in parallel, a chunk at a time, so
+    /// memory stays bounded by the chunk size and the number of groups.
+    ///
+    /// [`Engine::aggregate`]: crate::Engine::aggregate
+    pub fn aggregate(
+        &self,
+        group_by: Option<&GroupBy>,
+        aggregates: &[Aggregate],
+    ) -> Result<Vec<Value>> {
+        let new = || Aggregator::new(group_by, aggregates);
+        let mut aggregator = new();
+        if let Some(view) = self.current_mmap()? {
+            for chunk in self.ordered_entries().chunks(AGGREGATE_CHUNK) {
+                let partial = Self::parse_lines(&view, chunk)
+                    .par_iter()
+                    .fold(new, |mut aggregator, doc| {
+                        aggregator.add(doc);
+                        aggregator
+                    })
+                    .reduce(new, Aggregator::merge);
+                aggregator = aggregator.merge(partial);
+            }
+        }
+        Ok(aggregator.finish())
+    }
+
+    /// Read a reproducible random sample of `n` documents (all of them if
+    /// the store holds fewer), in file order.
+    ///
+    /// The sample is drawn from the index, so only the chosen documents are
+    /// read. The same `seed` gives the same sample as long as the store is
+    /// unchanged. Pending writes must be flushed first to be sampled.
+    pub fn sample(&self, n: usize, seed: u64) -> Result<Vec<Value>> {
+        let Some(view) = self.current_mmap()? else {
+            return Ok(Vec::new());
+        };
+        let entries = self.ordered_entries();
+        let picked: Vec<IndexEntry> = sample::sample_positions(entries.len(), n, seed)
+            .into_iter()
+            .map(|i| entries[i])
+            .collect();
+        Ok(Self::parse_lines(&view, &picked))
+    }
+
+    /// Scan and return raw JSON bytes (fastest - zero parsing).
+    pub fn scan_raw(&self) -> Result<Vec<Vec<u8>>> {
+        if self.index.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.shares_lines() {
+            let mut scanner = self.iter()?;
+            let mut lines = Vec::with_capacity(scanner.remaining());
+            while let Some(line) = scanner.next_raw()? {
+                lines.push(line);
+            }
+            return Ok(lines);
+        }
+
+        let Some(view) = self.current_mmap()? else {
+            return Ok(Vec::new());
+        };
+        let entries = self.ordered_entries();
+        Ok(view.par_map_lines(&entries, |line| {
+            Some(line.strip_suffix(b"\n").unwrap_or(line).to_vec())
+        }))
+    }
+
+    /// Scan raw JSON lines (including `_id`) into one contiguous buffer.
+    ///
+    /// Returns the concatenated lines, without newlines, and `len + 1`
+    /// offsets such that line `i` is `data[offsets[i]..offsets[i + 1]]`, in
+    /// file order. This avoids an allocation per document for callers that
+    /// slice views out of the buffer. With a `predicate`, lines are parsed
+    /// in parallel to evaluate it and only matching ones are kept.
+    pub fn scan_raw_packed(&self, predicate: Option<&Predicate>) -> Result<(Vec<u8>, Vec<u64>)> {
+        let mut data = Vec::new();
+        let mut offsets = vec![0];
+        let Some(view) = self.current_mmap()? else {
+            return Ok((data, offsets));
+        };
+        let entries = self.ordered_entries();
+
+        let mut push = |line: &[u8]| {
+            data.extend_from_slice(line.strip_suffix(b"\n").unwrap_or(line));
+            offsets.push(data.len() as u64);
+        };
+        if self.shares_lines() {
+            let mut scanner = self.iter()?;
+            while let Some(line) = scanner.next_raw()? {
+                let keep = predicate.map_or(true, |predicate| {
+                    DocRef::with_line(&line, |doc| doc.matches(predicate)).unwrap_or(false)
+                });
+                if keep {
+                    push(&line);
+                }
+            }
+            return Ok((data, offsets));
+        }
+        match predicate {
+            Some(predicate) => {
+                let lines = view.par_map_lines(&entries, |line| {
+                    DocRef::with_line(line, |doc| doc.matches(predicate))
+                        .unwrap_or(false)
+                        .then(|| line.to_vec())
+                });
+                for line in &lines {
+                    push(line);
+                }
+            }
+            None => {
+                let mut cache = ReadCache::default();
+                for entry in entries.iter() {
+                    // Lines written after the view was taken are skipped
+                    if let Some(line) = view.line(entry, &mut cache)? {
+                        push(line);
+                    }
+                }
+            }
+        }
+        Ok((data, offsets))
+    }
+
+    /// Get the raw JSONL data as bytes (zero-copy from mmap, decompressed
+    /// for compressed stores).
+    /// This is the fastest way to get all data for bulk processing.
+    pub fn get_raw_data(&self) -> Option<Cow<'_, [u8]>> {
+        self.mmap.as_ref().and_then(|view| view.bytes().ok())
+    }
+
+    /// Get the secondary field indexes.
+    pub fn field_indexes(&self) -> &FieldIndexes {
+        &self.field_indexes
+    }
+
+    /// Build (or rebuild) a secondary index on `field` and save it.
+    ///
+    /// Returns the number of indexed documents.
+    pub fn create_index(&mut self, field: &str) -> Result<usize> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot create index in read-only mode".to_string(),
+            ));
+        }
+        FieldIndexes::validate_field(field)?;
+        self.flush()?;
+
+        let mut index = crate::FieldIndex::new(field);
+        for doc_id in self.index.keys() {
+            if let Ok(doc) = self.get(doc_id) {
+                index.insert(doc_id, &doc);
+            }
+        }
+        index.save(&self.root, &self.collection)?;
+
+        let count = index.len();
+        self.field_indexes.add(index);
+        Ok(count)
+    }
+
+    /// Drop the secondary index on `field`.
+    pub fn drop_index(&mut self, field: &str) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot drop index in read-only mode".to_string(),
+            ));
+        }
+        FieldIndexes::drop_index(&self.root, &self.collection, field)?;
+        self.field_indexes.remove_index(field);
+        Ok(())
+    }
+
+    /// Build (or rebuild) a full-text index on a string `field`.
+    ///
+    /// Returns the number of documents with indexed text.
+    pub fn create_text_index(&mut self, field: &str) -> Result<usize> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot create index in read-only mode".to_string(),
+            ));
+        }
+        FieldIndexes::validate_field(field)?;
+        self.flush()?;
+
+        let mut index = crate::TextIndex::new(field);
+        for doc_id in self.index.keys() {
+            if let Ok(doc) = self.get(doc_id) {
+                index.insert(doc_id, &doc);
+            }
+        }
+        index.save(&self.root, &self.collection)?;
+
+        let count = index.len();
+        self.field_indexes.add_text(index);
+        Ok(count)
+    }
+
+    /// Drop the full-text index on `field`.
+    pub fn drop_text_index(&mut self, field: &str) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot drop index in read-only mode".to_string(),
+            ));
+        }
+        FieldIndexes::drop_text_index(&self.root, &self.collection, field)?;
+        self.field_indexes.remove_text_index(field);
+        Ok(())
+    }
+
+    /// The columnar projection cache, if one was built.
+    pub fn column_cache(&self) -> Option<&ColumnCache> {
+        self.columns.as_ref()
+    }
+
+    /// Cache the values of `fields` (dot notation for nested fields) as
+    /// columns, in addition to the fields already cached, and save the
+    /// cache.
+    ///
+    /// Scans through the [`Engine`](crate::Engine) that only project and
+    /// filter on cached fields then read the columns instead of parsing the
+    /// documents. Once built, the cache is kept up to date by writes and
+    /// saved on flush. Returns the number of cached documents.
+    pub fn cache_columns(&mut self, fields: &[&str]) -> Result<usize> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot cache columns in read-only mode".to_string(),
+            ));
+        }
+        let cached = self.columns.iter().flat_map(|c| c.fields().iter());
+        let mut columns = ColumnCache::new(
+            cached
+                .map(String::as_str)
+                .chain(fields.iter().copied())
+                .collect::<Vec<_>>(),
+        )?;
+        self.flush()?;
+
+        for (doc_id, entry) in &self.index {
+            if let Ok(doc) = self.get(doc_id) {
+                columns.insert(doc_id, entry.checksum, &doc);
+            }
+        }
+        columns.save(&self.root, &self.collection)?;
+
+        let count = columns.len();
+        self.columns = Some(columns);
+        self.columns_dirty = false;
+        Ok(count)
+    }
+
+    /// Drop the columnar projection cache.
+    pub fn drop_column_cache(&mut self) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot drop column cache in read-only mode".to_string(),
+            ));
+        }
+        ColumnCache::drop_cache(&self.root, &self.collection)?;
+        self.columns = None;
+        self.columns_dirty = false;
+        Ok(())
+    }
+
+    /// A document's cached values of `fields` as a partial document (see
+    /// [`ColumnCache::get`]), or `None` if the cache can't answer and the
+    /// document must be read.
+    pub(crate) fn cached_fields(&self, doc_id: &str, fields: &[&str]) -> Option<Value> {
+        let entry = self.index.get(doc_id)?;
+        if self.is_expired(doc_id) {
+            return None;
+        }
+        self.columns.as_ref()?.get(doc_id, entry.checksum, fields)
+    }
+
+    /// Set a document's embedding in the vector index `name`, replacing any
+    /// previous one.
+    ///
+    /// The index is created with the dimension of the first vector put into
+    /// it; later vectors must match it. Indexes are saved on flush, and a
+    /// deleted document's vectors are removed.
+    pub fn put_vector(&mut self, name: &str, doc_id: &str, vector: &[f32]) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot write vectors in read-only mode".to_string(),
+            ));
+        }
+        if !self.exists(doc_id) {
+            return Err(Error::DocumentNotFound(doc_id.to_string()));
+        }
+        if !self.vectors.contains_key(name) {
+            let index = VectorIndex::new(name, vector.len())?;
+            self.vectors.insert(name.to_string(), index);
+        }
+        if let Some(index) = self.vectors.get_mut(name) {
+            index.insert(doc_id, vector)?;
+        }
+        self.vectors_dirty = true;
+        Ok(())
+    }
+
+    /// A document's embedding in the vector index `name`.
+    pub fn get_vector(&self, name: &str, doc_id: &str) -> Option<&[f32]> {
+        self.vectors.get(name)?.get(doc_id)
+    }
+
+    /// The vector index `name`, if any vector was put into it.
+    pub fn vector_index(&self, name: &str) -> Option<&VectorIndex> {
+        self.vectors.get(name)
+    }
+
+    /// Names of the vector indexes.
+    pub fn vector_indexes(&self) -> impl Iterator<Item = &str> {
+        self.vectors.keys().map(String::as_str)
+    }
+
+    /// Drop the vector index `name`.
+    pub fn drop_vector_index(&mut self, name: &str) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot drop index in read-only mode".to_string(),
+            ));
+        }
+        VectorIndex::drop_index(&self.root, &self.collection, name)?;
+        self.vectors.remove(name);
+        Ok(())
+    }
+
+    /// The `k` documents whose embeddings in the vector index `name` are
+    /// nearest to `query`, nearest first.
+    pub fn knn(
+        &self,
+        name: &str,
+        query: &[f32],
+        k: usize,
+        metric: Metric,
+    ) -> Result<Vec<Neighbor>> {
+        let index = self.vectors.get(name).ok_or_else(|| {
+            Error::Validation(format!(
+                "No vector index '{}' in collection '{}'",
+                name, self.collection
+            ))
+        })?;
+        if self.expires.is_empty() {
+            return index.search(query, k, metric);
+        }
+        // Expired documents keep their vectors until they are purged, so
+        // they are skipped while searching rather than taking up places
+        let now = now_ms();
+        index.search_where(query, k, metric, |doc_id| {
+            self.expires
+                .get(doc_id)
+                .map_or(true, |&expires_at| expires_at > now)
+        })
+    }
+
+    /// Find documents whose text-indexed fields contain every token of `query`.
+    pub fn search(&self, query: &str) -> Result<Vec<Value>> {
+        let predicate = self.field_indexes.search_predicate(query).ok_or_else(|| {
+            Error::Validation(format!("No text index in collection '{}'", self.collection))
+        })?;
+        self.find(&predicate)
+    }
+
+    /// Find documents matching a predicate, in file order.
+    ///
+    /// Uses the field indexes to read only candidate documents when the
+    /// predicate references indexed fields, otherwise scans everything
+    /// (see [`scan_filtered`](Self::scan_filtered)).
+    pub fn find(&self, predicate: &Predicate) -> Result<Vec<Value>> {
+        self.scan_filtered(Some(predicate), None)
+    }
+
+    /// Current mmap if it covers all flushed data, otherwise a fresh one.
+    fn current_mmap(&self) -> Result<Option<Arc<SegmentView>>> {
+        match &self.mmap {
+            Some(view) if view.end() >= self.current_offset => Ok(Some(view.clone())),
+            _ if self.data_len() > 0 => Ok(SegmentView::open(
+                &self.root,
+                &self.collection,
+                &self.segments,
+                self.mmap.as_deref(),
+                self.cipher.as_ref(),
+                self.mmap_hints,
+            )?
+            .map(Arc::new)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Lazily iterate over all documents in file order.
+    ///
+    /// File order is stable: documents appear in the order they were last
+    /// written, so overwriting a document moves it to the end.
+    ///
+    /// The scanner holds a snapshot of the index and a shared mmap, so only
+    /// one document is decoded at a time and the store may be modified while
+    /// iterating. Pending writes must be flushed first to be visible.
+    pub fn iter(&self) -> Result<FastScanner> {
+        self.iter_range(0, None)
+    }
+
+    /// Lazily iterate over `limit` documents (all by default) starting at
+    /// position `offset` in file order.
+    pub fn iter_range(&self, offset: usize, limit: Option<usize>) -> Result<FastScanner> {
+        let (entries, ids) = self.ordered_with_ids();
+        let pos = offset.min(entries.len());
+        let end = limit.map_or(entries.len(), |n| pos.saturating_add(n).min(entries.len()));
+        Ok(FastScanner {
+            mmap: self.current_mmap()?,
+            cache: ReadCache::default(),
+            entries,
+            ids,
+            pos,
+            start: pos,
+            end,
+        })
+    }
+
+    /// Read `limit` documents starting at position `offset` in file order.
+    ///
+    /// Useful for paging and for deterministically sharding a collection.
+    pub fn scan_range(&self, offset: usize, limit: usize) -> Result<Vec<Value>> {
+        self.iter_range(offset, Some(limit))?.collect()
+    }
+
+    /// Lazily iterate over all documents in batches of `batch_size`.
+    pub fn scan_iter(&self, batch_size: usize) -> Result<ScanBatches> {
+        Ok(self.iter()?.batches(batch_size))
+    }
+
+    /// Lazily iterate over all documents in chunks bounded by both a
+    /// document count and a byte size, see [`ChunkSize`].
+    ///
+    /// Chunk sizes are the lengths of the stored lines, so a caller can
+    /// cap how much memory each chunk takes however large the collection.
+    pub fn scan_chunks(&self, size: ChunkSize) -> Result<ScanChunks> {
+        Ok(self.iter()?.chunks(size))
+    }
+
+    /// Write all live documents as JSONL (including `_id`) in file order.
+    ///
+    /// Overwritten, deleted and expired lines are skipped; when the data has no dead
+    /// lines it is copied in bulk. Pending writes must be
+    /// flushed first. Returns the number of documents written.
+    pub fn export_jsonl<W: Write>(&self, out: &mut W) -> Result<usize> {
+        let Some(view) = self.current_mmap()? else {
+            return Ok(0);
+        };
+        if self.shares_lines() {
+            let mut scanner = self.iter()?;
+            let mut count = 0;
+            while let Some(line) = scanner.next_raw()? {
+                out.write_all(&line)?;
+                out.write_all(b"\n")?;
+                count += 1;
+            }
+            return Ok(count);
+        }
+        let entries = self.ordered_entries();
+
+        if self.live_bytes == view.len() && entries.len() == self.index.len() {
+            let (mut last, mut written) = (None, Ok(()));
+            view.for_each_chunk(0, view.end(), |_, chunk| {
+                if written.is_ok() {
+                    last = chunk.last().copied();
+                    written = out.write_all(chunk);
+                }
+            })?;
+            written?;
+            if last.is_some_and(|b| b != b'\n') {
+                out.write_all(b"\n")?;
+            }
+            return Ok(entries.len());
+        }
+
+        let mut count = 0;
+        let mut cache = ReadCache::default();
+        for entry in entries.iter() {
+            let Some(line) = view.line(entry, &mut cache)? else {
+                continue;
+            };
+            out.write_all(line)?;
+            if line.last() != Some(&b'\n') {
+                out.write_all(b"\n")?;
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Flush pending writes and record the collection as snapshot `name`
+    /// (see [`Snapshot`]).
+    ///
+    /// A later compaction of the segments it points into (by
+    /// [`compact`](Self::compact), automatically on
+    /// [`flush`](Self::flush), or re-encryption) marks the snapshot stale
+    /// and unrestorable.
+    pub fn snapshot(&mut self, name: &str) -> Result<Snapshot> {
+        if self.txn.is_some() {
+            return Err(Error::TransactionFailed(
+                "cannot snapshot inside a transaction".to_string(),
+            ));
+        }
+        self.flush_writes()?;
+        Snapshot::create(&self.root, &self.collection, name, self.len())
+    }
+
+    /// Compact the data by removing deleted, overwritten and expired lines.
+    ///
+    /// Only segments holding dead lines (or stored in another format than
+    /// the manifest's) are rewritten. Live documents keep their relative
+    /// order. Clears the tombstones.
+    pub fn compact(&mut self) -> Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(
+                "cannot compact in read-only mode".to_string(),
+            ));
+        }
+        self.flush_writes()?;
+        self.rewrite_data(None)?;
+        match &mut self.blobs {
+            Some(blobs) if blobs.dead_bytes() > 0 => blobs.rewrite(self.cipher.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rewrite segments with only their live lines (writes must be flushed).
+    ///
+    /// With a `policy`, only the segments it selects are rewritten; otherwise
+    /// every segment with dead lines or in a stale format is.
+    fn rewrite_data(&mut self, policy: Option<CompactionPolicy>) -> Result<()> {
+        self.check_unsharded()?;
+        // Expired documents are deleted first, so their lines count as dead
+        if self.purge_expired() > 0 {
+            self.sync_writes()?;
+        }
+        // Compaction is also where segments switch to the manifest's format
+        let target =
+            Self::configured_compression(&self.root, &self.collection, self.cipher.as_ref())?;
+        let encrypted = self.cipher.is_some();
+        self.compression = target;
+        let live = self.live_bytes_by_segment();
+        let selected: Vec<Segment> = self
+            .segments
+            .iter()
+            .copied()
+            .filter(|s| {
+                let dead = s.len.saturating_sub(live.get(&s.no).copied().unwrap_or(0));
+                match policy {
+                    Some(policy) => policy.should_compact(dead, s.len),
+                    None => dead > 0 || s.compression != target || s.encrypted != encrypted,
+                }
+            })
+            .collect();
+        // Snapshots pointing into the rewritten segments (or the tombstones,
+        // which move with them) can't be restored afterwards
+        if !selected.is_empty() {
+            let mut rewritten: Vec<String> = selected
+                .iter()
+                .filter_map(|s| {
+                    let path = s.path(&self.root, &self.collection);
+                    Some(path.file_name()?.to_string_lossy().into_owned())
+                })
+                .collect();
+            rewritten.push(Layout::TOMBSTONE_FILE.to_string());
+            Snapshot::invalidate(&self.root, &self.collection, &rewritten)?;
+        }
+
+        // Lines to copy per segment, with their version (`None` for the
+        // current one)
+        let mut groups: FxHashMap<u32, Vec<(String, Option<u64>, IndexEntry)>> =
+            selected.iter().map(|s| (s.no, Vec::new())).collect();
+        let versions = self
+            .versions
+            .entries()
+            .map(|(doc_id, v)| (doc_id, Some(v.version), &v.entry));
+        let current = self.index.iter().map(|(id, e)| (id, None, e));
+        for (doc_id, version, entry) in current.chain(versions) {
+            if let Some(group) = groups.get_mut(&segment::split(entry.offset).0) {
+                group.push((doc_id.to_string(), version, *entry));
+            }
+        }
+
+        let view = self.current_mmap()?;
+        let active_no = self.active().no;
+        self.mmap = None;
+        for old in selected {
+            let mut entries = groups.remove(&old.no).unwrap_or_default();
+            entries.sort_unstable_by_key(|(_, _, e)| e.offset);
+            let mut new = Segment {
+                no: old.no,
+                compression: target,
+                len: 0,
+                encrypted,
+            };
+            let path = new.path(&self.root, &self.collection);
+            let tmp_file = path.with_extension("tmp");
+
+            // Old and new offsets of the copied lines, for the tombstones
+            let mut moves: Vec<(u64, u64)> = Vec::with_capacity(entries.len());
+            let mut moved = Vec::with_capacity(entries.len());
+            {
+                let mut cache = ReadCache::default();
+                let mut writer = DataWriter::create(&tmp_file, target, self.cipher.as_ref())?;
+                // A line shared by several documents is copied once
+                let mut last: Option<(u64, IndexEntry)> = None;
+                for (doc_id, version, entry) in &entries {
+                    if let Some((offset, copied)) = last.filter(|&(o, _)| o == entry.offset) {
+                        moved.push((doc_id.clone(), *version, copied));
+                        last = Some((offset, copied));
+                        continue;
+                    }
+                    let Some(line) = (match &view {
+                        Some(view) => view.line(entry, &mut cache)?,
+                        None => None,
+                    }) else {
+                        continue;
+                    };
+                    let line = line.strip_suffix(b"\n").unwrap_or(line);
+                    writer.write_all(line)?;
+                    writer.write_all(b"\n")?;
+
+                    let length = line.len() as u32 + 1;
+                    moves.push((segment::split(entry.offset).1, new.len));
+                    let copied = IndexEntry {
+                        offset: new.end(),
+                        length,
+                        checksum: entry.checksum,
+                    };
+                    moved.push((doc_id.clone(), *version, copied));
+                    last = Some((entry.offset, copied));
+                    new.len += length as u64;
+                }
+                writer.flush()?;
+                writer.sync_data()?;
+            }
+
+            // Tombstones inside the segment move with the lines that follow
+            // them, and are saved before the swap so a rebuilt index of the
+            // new segment still honours them.
+            let mut remapped = false;
+            for offset in self.tombstones.values_mut() {
+                let (no, local) = segment::split(*offset);
+                if no == old.no {
+                    let next = moves.partition_point(|&(from, _)| from < local);
+                    let local = moves.get(next).map_or(new.len, |&(_, to)| to);
+                    *offset = segment::pack(no, local);
+                    remapped = true;
+                }
+            }
+            if remapped {
+                self.save_tombstones()?;
+            }
+
+            // Atomic replace (a crash before the index is saved is caught on open)
+            if old.no == active_no {
+                self.writer = None;
+            }
+            std::fs::rename(&tmp_file, &path)?;
+            let old_path = old.path(&self.root, &self.collection);
+            if old_path != path && old_path.exists() {
+                std::fs::remove_file(old_path)?;
+            }
+            for (doc_id, version, _) in &entries {
+                if version.is_none() {
+                    self.index.remove(doc_id);
+                }
+            }
+            for (doc_id, version, entry) in moved {
+                match version {
+                    Some(version) => self.versions.relocate(&doc_id, version, entry),
+                    None => {
+                        self.index.insert(doc_id, entry);
+                    }
+                }
+            }
+            if new.len == 0 && old.no != active_no {
+                std::fs::remove_file(&path)?;
+                self.segments.retain(|s| s.no != old.no);
+            } else if let Some(segment) = self.segments.iter_mut().find(|s| s.no == old.no) {
+                *segment = new;
+            }
+        }
+        self.ordered.take();
+        self.reset_sorted_ids();
+        self.live_bytes = self.index.values().map(|e| e.length as u64).sum();
+        if let Some(dedup) = &mut self.dedup {
+            dedup.recount(&self.index);
+        }
+
+        // Without dead lines left, the tombstones are no longer needed
+        // (unless a deleted document's line lives on, shared by another)
+        if self.dead_bytes() == 0 && !self.shares_lines() {
+            let tombstone_file = Layout::tombstone_file(&self.root, &self.collection);
+            if tombstone_file.exists() {
+                std::fs::remove_file(tombstone_file)?;
+            }
+            self.tombstones.clear();
+        }
+        let active = *self.active();
+        self.current_offset = active.end();
+        self.save_index()?;
+        self.versions.save(&self.root, &self.collection)?;
+        if let Some(dedup) = &mut self.dedup {
+            dedup.save(
+                &self.root,
+                &self.collection,
+                &self.index,
+                self.current_offset,
+            )?;
+        }
+
+        // Reopen writer
+        if self.writer.is_none() {
+            self.writer = Some(DataWriter::append(
+                &active.path(&self.root, &self.collection),
+                active.compression,
+                self.cipher.as_ref(),
+            )?);
+        }
+        self.refresh_mmap()
+    }
+
+    /// Replace the tombstone file with the current deletion records.
+    fn save_tombstones(&self) -> Result<()> {
+        let path = Layout::tombstone_file(&self.root, &self.collection);
+        let tmp_file = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_file)?);
+        for (id, &offset) in &self.tombstones {
+            let tombstone = Tombstone {
+                id: id.clone(),
+                offset,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&tombstone)?)?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        drop(writer);
+        std::fs::rename(&tmp_file, &path)?;
+        Ok(())
+    }
+}
+
+/// A JSONL collection as a [`Store`].
+impl Store for FastStore {
+    fn put(&mut self, doc_id: &str, doc: Value) -> Result<()> {
+        FastStore::put(self, doc_id, doc)
+    }
+
+    fn put_raw_line(&mut self, doc_id: &str, line: &[u8]) -> Result<()> {
+        FastStore::put_raw_line(self, doc_id, line)
+    }
+
+    fn put_auto(&mut self, doc: Value, strategy: IdStrategy) -> Result<String> {
+        FastStore::put_auto(self, doc, strategy)
+    }
+
+    fn patch(&mut self, doc_id: &str, patch: &Value) -> Result<Value> {
+        FastStore::patch(self, doc_id, patch)
+    }
+
+    fn delete(&mut self, doc_id: &str) -> Result<()> {
+        FastStore::delete(self, doc_id)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        FastStore::flush(self)
+    }
+}
+
+impl ReadStore for FastStore {
+    fn storage(&self) -> StorageMode {
+        StorageMode::Jsonl
+    }
+
+    fn get(&self, doc_id: &str) -> Result<Value> {
+        FastStore::get(self, doc_id)
+    }
+
+    fn exists(&self, doc_id: &str) -> bool {
+        FastStore::exists(self, doc_id)
+    }
+
+    fn doc_ids(&self) -> Vec<String> {
+        FastStore::doc_ids(self)
+    }
+
+    fn scan(&self) -> Result<Vec<Value>> {
+        FastStore::scan(self)
+    }
+
+    fn len(&self) -> usize {
+        FastStore::len(self)
+    }
+
+    fn stats(&self) -> CollectionStats {
+        CollectionStats {
+            collection: self.collection.clone(),
+            doc_count: FastStore::len(self),
+            schema_count: self.schema_count(),
+            total_size: self.live_bytes,
+            strict_mode: self.schema.is_some(),
+            storage: StorageMode::Jsonl,
+            fast: Some(FastStore::stats(self)),
+            recovery: None,
+        }
+    }
+}
+
+impl Drop for FastStore {
+    fn drop(&mut self) {
+        if self.scratch.is_none() {
+            let _ = self.flush().and_then(|()| self.merge_index_delta());
+        }
+    }
+}
+
+/// A temporary directory for an in-memory store, on a RAM-backed file
+/// system where there is one.
+fn memory_dir() -> Result<TempDir> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("zippy-mem-");
+    let shm = Path::new("/dev/shm");
+    if cfg!(target_os = "linux") && shm.is_dir() {
+        if let Ok(dir) = builder.tempdir_in(shm) {
+            return Ok(dir);
+        }
+    }
+    Ok(builder.tempdir()?)
+}
+
+/// Lazy document iterator over a [`FastStore`], see [`FastStore::iter`].
+///
+/// Yields documents (without `_id`) in file order, mirroring the Engine
+/// [`Scanner`](crate::Scanner).
+pub struct FastScanner {
+    mmap: Option<Arc<SegmentView>>,
+    /// Last decompressed block (compressed segments)
+    cache: ReadCache,
+    /// Index snapshot sorted by offset
+    entries: Arc<Vec<IndexEntry>>,
+    /// IDs of the entries, when some documents share a line
+    ids: Option<Arc<Vec<String>>>,
+    pos: usize,
+    start: usize,
+    end: usize,
+}
+
+impl FastScanner {
+    /// Get the next document.
+    pub fn next_doc(&mut self) -> Result<Option<Value>> {
+        Ok(self.next_entry()?.map(|(_, doc)| doc))
+    }
+
+    /// Get the next document together with its ID.
+    pub fn next_entry(&mut self) -> Result<Option<(String, Value)>> {
+        let Some(mut line) = self.next_raw()? else {
+            return Ok(None);
+        };
+        parse_doc(&mut line).map(Some)
+    }
+
+    /// Get the next raw JSON line (including `_id`, without the newline),
+    /// skipping the parse.
+    pub fn next_raw(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(mmap) = &self.mmap else {
+            return Ok(None);
+        };
+
+        while self.pos < self.end {
+            let entry = self.entries[self.pos];
+            self.pos += 1;
+            // Lines written after the snapshot was taken are skipped
+            let Some(line) = mmap.line(&entry, &mut self.cache)? else {
+                continue;
+            };
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            return match &self.ids {
+                Some(ids) => dedup::with_id(line, &ids[self.pos - 1]).map(Some),
+                None => Ok(Some(line.to_vec())),
+            };
+        }
+
+        Ok(None)
+    }
+
+    /// Get up to `max` next documents (empty when exhausted).
+    pub fn next_batch(&mut self, max: usize) -> Result<Vec<Value>> {
+        let mut docs = Vec::with_capacity(max.min(self.remaining()));
+        while docs.len() < max {
+            match self.next_doc()? {
+                Some(doc) => docs.push(doc),
+                None => break,
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Get the next chunk of documents within `size` (empty when
+    /// exhausted).
+    pub fn next_chunk(&mut self, size: ChunkSize) -> Result<Vec<Value>> {
+        let mut docs = Vec::new();
+        let mut bytes = 0;
+        while !size.is_full(docs.len(), bytes) {
+            let Some(mut line) = self.next_raw()? else {
+                break;
+            };
+            bytes += line.len();
+            docs.push(parse_doc(&mut line)?.1);
+        }
+        Ok(docs)
+    }
+
+    /// Turn this scanner into an iterator over chunks within `size`.
+    pub fn chunks(self, size: ChunkSize) -> ScanChunks {
+        ScanChunks {
+            scanner: self,
+            size,
+        }
+    }
+
+    /// Turn this scanner into an iterator over batches of `batch_size`.
+    pub fn batches(self, batch_size: usize) -> ScanBatches {
+        ScanBatches {
+            scanner: self,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Reset scanner to beginning.
+    pub fn reset(&mut self) {
+        self.pos = self.start;
+    }
+
+    /// Get remaining document count (upper bound).
+    pub fn remaining(&self) -> usize {
+        self.end - self.pos
+    }
+}
+
+impl Iterator for FastScanner {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_doc().transpose()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining()))
+    }
+}
+
+/// Batched document iterator, see [`FastStore::scan_iter`].
+pub struct ScanBatches {
+    scanner: FastScanner,
+    batch_size: usize,
+}
+
+impl Iterator for ScanBatches {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.scanner.next_batch(self.batch_size) {
+            Ok(docs) if docs.is_empty() => None,
+            result => Some(result),
+        }
+    }
+}
+
+/// Size limits of the chunks from [`FastStore::scan_chunks`] and
+/// [`Engine::scan_chunks`](crate::Engine::scan_chunks).
+///
+/// A chunk ends once it holds `max_docs` documents or its documents take
+/// `max_bytes` or more. Every chunk holds at least one document, so a single
+/// document larger than `max_bytes` makes a chunk of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSize {
+    pub max_docs: usize,
+    pub max_bytes: usize,
+}
+
+impl ChunkSize {
+    /// Chunks of at most `max_docs` documents and about `max_bytes` bytes.
+    pub fn new(max_docs: usize, max_bytes: usize) -> Self {
+        ChunkSize {
+            max_docs: max_docs.max(1),
+            max_bytes: max_bytes.max(1),
+        }
+    }
+
+    /// Chunks of at most `max_docs` documents, whatever their size.
+    pub fn docs(max_docs: usize) -> Self {
+        Self::new(max_docs, usize::MAX)
+    }
+
+    /// Chunks of about `max_bytes` bytes, however many documents.
+    pub fn bytes(max_bytes: usize) -> Self {
+        Self::new(usize::MAX, max_bytes)
+    }
+
+    /// Whether a chunk of `docs` documents taking `bytes` is complete.
+    pub(crate) fn is_full(&self, docs: usize, bytes: usize) -> bool {
+        docs >= self.max_docs || (docs > 0 && bytes >= self.max_bytes)
+    }
+}
+
+/// Chunked document iterator, see [`FastStore::scan_chunks`].
+pub struct ScanChunks {
+    scanner: FastScanner,
+    size: ChunkSize,
+}
+
+impl Iterator for ScanChunks {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.scanner.next_chunk(self.size) {
+            Ok(docs) if docs.is_empty() => None,
+            result => Some(result),
+        }
+    }
+}
+
+/// Parse a stored line into its document ID and the document without
+/// `_id`.
+fn parse_doc(line: &mut [u8]) -> Result<(String, Value)> {
+    let mut doc: Value = simd_json::from_slice(line).map_err(|e| {
+        Error::Json(serde_json::Error::io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            e.to_string(),
+        )))
+    })?;
+    let doc_id = match doc {
+        Value::Object(ref mut obj) => match obj.remove("_id") {
+            Some(Value::String(id)) => id,
+            _ => String::new(),
+        },
+        _ => String::new(),
+    };
+    Ok((doc_id, doc))
+}
+
+/// Inner state for ZDSRoot, shared via Arc.
+struct ZDSRootInner {
+    root: PathBuf,
+    batch_size: usize,
+    mode: OpenMode,
+    /// Write lock (only held in ReadWrite mode)
+    write_lock: Option<WriteLock>,
+    /// Shared reader lock (Read mode, best effort)
+    read_lock: Option<ReadLock>,
+    /// Collections with live handles opened through this root
+    open_collections: Mutex<FxHashMap<String, Weak<()>>>,
+}
+
+impl std::fmt::Debug for ZDSRootInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZDSRootInner")
+            .field("root", &self.root)
+            .field("batch_size", &self.batch_size)
+            .field("mode", &self.mode)
+            .field("write_lock", &self.write_lock.is_some())
+            .field("read_lock", &self.read_lock.is_some())
+            .finish()
+    }
+}
+
+/// Root handle for a ZDS store directory.
+///
+/// This struct represents a ZDS root directory without binding to a specific collection.
+/// It allows opening multiple collections from the same root safely, avoiding corruption
+/// when writing to multiple collections simultaneously.
+///
+/// Roots are **memoized** by (path, mode): opening the same path twice returns the same
+/// shared instance, ensuring a single write lock protects the entire store.
+///
+/// # Example
+///
+/// ```ignore
+/// use zippy_data::{ZDSRoot, OpenMode};
+///
+/// // Open in read-write mode (default)
+/// let root = ZDSRoot::open("./data", 1000, OpenMode::ReadWrite)?;
+/// let train = root.collection("train")?;
+/// let test = root.collection("test")?;
+///
+/// train.put("doc1", json!({"split": "train"}))?;
+/// test.put("doc1", json!({"split": "test"}))?;
+///
+/// // Open read-only (shared lock, parallel readers allowed)
+/// let reader = ZDSRoot::open("./data", 1000, OpenMode::Read)?;
+/// ```
+#[derive(Clone)]
+pub struct ZDSRoot {
+    inner: Arc<ZDSRootInner>,
+}
+
+impl std::fmt::Debug for ZDSRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZDSRoot")
+            .field("root", &self.inner.root)
+            .field("batch_size", &self.inner.batch_size)
+            .field("mode", &self.inner.mode)
+            .finish()
+    }
+}
+
+impl ZDSRoot {
+    /// Open or create a ZDS root directory.
+    ///
+    /// This initializes the root directory structure but does not open any collection.
+    /// Use `collection()` to get a handle to a specific collection.
+    ///
+    /// # Memoization
+    ///
+    /// Roots are cached by (canonical_path, mode). Opening the same path multiple times
+    /// returns the same shared instance, ensuring consistent locking.
+    ///
+    /// # Locking
+    ///
+    /// - `OpenMode::ReadWrite`: Acquires an exclusive write lock. Only one writer allowed.
+    /// - `OpenMode::Read`: Takes a shared [`ReadLock`] when the root has lock
+    ///   metadata and is writable. Multiple readers allowed, alongside the writer.
+    pub fn open(root: impl AsRef<Path>, batch_size: usize, mode: OpenMode) -> Result<Self> {
+        let root_path = root.as_ref();
+
+        // Initialize root directory structure first (needed for canonicalize)
+        if mode == OpenMode::ReadWrite {
+            Layout::init_root(root_path)?;
+        }
+
+        // Canonicalize path for consistent caching (after directory exists)
+        let canonical =
+            std::fs::canonicalize(root_path).unwrap_or_else(|_| root_path.to_path_buf());
+        let cache_key = (canonical.clone(), mode);
+
+        // Check cache first
+        {
+            let cache = ROOT_CACHE.read();
+            if let Some(weak) = cache.get(&cache_key) {
+                if let Some(inner) = weak.upgrade() {
+                    return Ok(ZDSRoot { inner });
+                }
+            }
+        }
+
+        // Not in cache or expired - create new
+        let mut cache = ROOT_CACHE.write();
+
+        // Double-check after acquiring write lock
+        if let Some(weak) = cache.get(&cache_key) {
             if let Some(inner) = weak.upgrade() {
                 return Ok(ZDSRoot { inner });
             }
         }
 
-        // Acquire write lock if in ReadWrite mode
-        let write_lock = if mode == OpenMode::ReadWrite {
-            Some(WriteLock::acquire(root_path)?)
-        } else {
-            None
-        };
+        // Acquire write lock if in ReadWrite mode
+        let write_lock = if mode == OpenMode::ReadWrite {
+            Some(WriteLock::acquire(root_path)?)
+        } else {
+            None
+        };
+        let read_lock = if mode == OpenMode::Read {
+            ReadLock::acquire(root_path).ok()
+        } else {
+            None
+        };
+
+        let inner = Arc::new(ZDSRootInner {
+            root: root_path.to_path_buf(),
+            batch_size,
+            mode,
+            write_lock,
+            read_lock,
+            open_collections: Mutex::default(),
+        });
+
+        // Store weak reference in cache
+        cache.insert(cache_key, Arc::downgrade(&inner));
+
+        Ok(ZDSRoot { inner })
+    }
+
+    /// Open in read-write mode (convenience method).
+    pub fn open_rw(root: impl AsRef<Path>, batch_size: usize) -> Result<Self> {
+        Self::open(root, batch_size, OpenMode::ReadWrite)
+    }
+
+    /// Open in read-only mode (convenience method).
+    pub fn open_readonly(root: impl AsRef<Path>, batch_size: usize) -> Result<Self> {
+        Self::open(root, batch_size, OpenMode::Read)
+    }
+
+    /// Get the root path.
+    pub fn root_path(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Get the default batch size.
+    pub fn batch_size(&self) -> usize {
+        self.inner.batch_size
+    }
+
+    /// Get the open mode.
+    pub fn mode(&self) -> OpenMode {
+        self.inner.mode
+    }
+
+    /// Check if this root is writable.
+    pub fn is_writable(&self) -> bool {
+        self.inner.mode == OpenMode::ReadWrite
+    }
+
+    /// Open a collection within this ZDS root.
+    ///
+    /// Creates the collection if it doesn't exist (in ReadWrite mode).
+    /// Returns an error if attempting to create in Read mode.
+    pub fn collection(&self, name: impl AsRef<str>) -> Result<FastStore> {
+        self.collection_with_batch_size(name, self.inner.batch_size)
+    }
+
+    /// Open a collection with a custom batch size.
+    pub fn collection_with_batch_size(
+        &self,
+        name: impl AsRef<str>,
+        batch_size: usize,
+    ) -> Result<FastStore> {
+        let name = name.as_ref();
+
+        // Check if collection exists
+        let exists = self.collection_exists(name);
+
+        // In read mode, collection must exist
+        if self.inner.mode == OpenMode::Read && !exists {
+            return Err(Error::CollectionNotFound(name.to_string()));
+        }
+
+        let mut open = self.inner.open_collections.lock();
+        let mut store =
+            FastStore::open_with_mode(&self.inner.root, name, batch_size, self.inner.mode)?;
+        let handle = match open.get(name).and_then(Weak::upgrade) {
+            Some(handle) => handle,
+            None => {
+                let handle = Arc::new(());
+                open.insert(name.to_string(), Arc::downgrade(&handle));
+                handle
+            }
+        };
+        store.root_handle = Some(handle);
+        Ok(store)
+    }
+
+    /// Watch a collection for changes made by any process (see
+    /// [`Watcher`]).
+    pub fn watch(&self, name: impl AsRef<str>) -> Result<Watcher> {
+        Watcher::new(&self.inner.root, name)
+    }
+
+    /// List all collections in this ZDS root.
+    pub fn list_collections(&self) -> Result<Vec<String>> {
+        let collections_dir = Layout::collections_dir(&self.inner.root);
+        if !collections_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut collections = Vec::new();
+        for entry in std::fs::read_dir(collections_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    collections.push(name.to_string());
+                }
+            }
+        }
+        collections.sort();
+        Ok(collections)
+    }
+
+    /// Check if a collection exists.
+    pub fn collection_exists(&self, name: &str) -> bool {
+        Layout::collection_dir(&self.inner.root, name).exists()
+    }
+
+    /// Delete a collection and all its data.
+    ///
+    /// Requires read-write mode, and fails while a store opened through this
+    /// root still holds the collection.
+    pub fn drop_collection(&self, name: &str) -> Result<()> {
+        let open = self.lock_collections("drop")?;
+        self.check_closed(&open, name)?;
+        std::fs::remove_dir_all(Layout::collection_dir(&self.inner.root, name))?;
+        RootManifest::update(&self.inner.root, |manifest| {
+            manifest.collections.remove(name);
+        })
+    }
+
+    /// Rename a collection.
+    ///
+    /// `to` must be a valid collection name that isn't taken. Requires
+    /// read-write mode, and fails while `from` is open through this root.
+    pub fn rename_collection(&self, from: &str, to: &str) -> Result<()> {
+        let open = self.lock_collections("rename")?;
+        self.check_closed(&open, from)?;
+        self.check_new_name(to)?;
+        std::fs::rename(
+            Layout::collection_dir(&self.inner.root, from),
+            Layout::collection_dir(&self.inner.root, to),
+        )?;
+        RootManifest::update(&self.inner.root, |manifest| {
+            if let Some(summary) = manifest.collections.remove(from) {
+                manifest.collections.insert(to.to_string(), summary);
+            }
+        })
+    }
+
+    /// Copy a collection, with its indexes, to a new collection `to`.
+    ///
+    /// Requires read-write mode, and fails while `from` is open through this
+    /// root (its unflushed writes would be missing from the copy). A failed
+    /// copy is removed.
+    pub fn copy_collection(&self, from: &str, to: &str) -> Result<()> {
+        let open = self.lock_collections("copy")?;
+        self.check_closed(&open, from)?;
+        self.check_new_name(to)?;
+        let dest = Layout::collection_dir(&self.inner.root, to);
+        std::fs::create_dir(&dest)?;
+        if let Err(e) = copy_dir(&Layout::collection_dir(&self.inner.root, from), &dest) {
+            let _ = std::fs::remove_dir_all(&dest);
+            return Err(e.into());
+        }
+        RootManifest::update(&self.inner.root, |manifest| {
+            if let Some(summary) = manifest.collections.get(from).cloned() {
+                manifest.collections.insert(to.to_string(), summary);
+            }
+        })
+    }
+
+    /// Lock the open collection handles for a collection-level operation.
+    ///
+    /// The write lock held in read-write mode keeps other processes out; the
+    /// handles lock keeps this process from opening a collection meanwhile.
+    fn lock_collections(
+        &self,
+        operation: &str,
+    ) -> Result<parking_lot::MutexGuard<'_, FxHashMap<String, Weak<()>>>> {
+        if self.inner.mode == OpenMode::Read {
+            return Err(Error::ReadOnly(format!(
+                "cannot {} collection in read-only mode",
+                operation
+            )));
+        }
+        Ok(self.inner.open_collections.lock())
+    }
+
+    /// Fail unless `name` exists and has no open handles.
+    fn check_closed(&self, open: &FxHashMap<String, Weak<()>>, name: &str) -> Result<()> {
+        if !self.collection_exists(name) {
+            return Err(Error::CollectionNotFound(name.to_string()));
+        }
+        if open
+            .get(name)
+            .is_some_and(|handle| handle.strong_count() > 0)
+        {
+            return Err(Error::WriteLock(format!(
+                "collection '{}' is still open",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fail unless `name` can be used for a new collection.
+    fn check_new_name(&self, name: &str) -> Result<()> {
+        Layout::validate_collection_name(name)?;
+        if self.collection_exists(name) {
+            return Err(Error::Validation(format!(
+                "collection '{}' already exists",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Close the root explicitly, releasing any locks.
+    ///
+    /// This removes the root from the cache and drops the write lock if held.
+    /// After calling this, the root handle is still valid but will need to
+    /// reacquire the lock if opened again.
+    pub fn close(&self) {
+        let canonical =
+            std::fs::canonicalize(&self.inner.root).unwrap_or_else(|_| self.inner.root.clone());
+        let cache_key = (canonical, self.inner.mode);
+
+        let mut cache = ROOT_CACHE.write();
+        cache.remove(&cache_key);
+        // The write lock will be released when the last Arc reference is dropped
+    }
+
+    /// Clear all cached roots (useful for testing).
+    #[doc(hidden)]
+    pub fn clear_cache() {
+        let mut cache = ROOT_CACHE.write();
+        cache.clear();
+    }
+}
+
+/// Recursively copy the contents of directory `src` into `dest`.
+fn copy_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            std::fs::create_dir(&target)?;
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_fast_store_basic() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+
+        store.put("doc1", json!({"name": "alice"})).unwrap();
+        store.flush().unwrap();
+
+        let doc = store.get("doc1").unwrap();
+        assert_eq!(doc["name"], "alice");
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_fast_store_export_jsonl() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+
+        store.put("a", json!({"v": 1})).unwrap();
+        store.put("b", json!({"v": 2})).unwrap();
+        store.flush().unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(store.export_jsonl(&mut out).unwrap(), 2);
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 2);
+
+        // Overwrites and deletes leave dead lines that must be skipped
+        store.put("a", json!({"v": 3})).unwrap();
+        store.delete("b").unwrap();
+        store.flush().unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(store.export_jsonl(&mut out).unwrap(), 1);
+        let line: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line, json!({"_id": "a", "v": 3}));
+    }
+
+    #[test]
+    fn test_fast_store_field_index() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"cat": "A", "n": 1})).unwrap();
+        store.put("b", json!({"cat": "B", "n": 2})).unwrap();
+        assert_eq!(store.create_index("cat").unwrap(), 2);
+
+        // Index is maintained across put, raw writes and delete
+        store.put("c", json!({"cat": "A", "n": 3})).unwrap();
+        store
+            .write_jsonl_blob(
+                b"{\"_id\":\"d\",\"cat\":\"A\",\"n\":4}\n",
+                &["d".to_string()],
+            )
+            .unwrap();
+        store.delete("a").unwrap();
+        store.put("b", json!({"cat": "A", "n": 5})).unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        let pred = Predicate::eq("cat", "A");
+        let mut candidates = store.field_indexes().candidates(&pred).unwrap();
+        candidates.sort();
+        assert_eq!(candidates, ["b", "c", "d"]);
+
+        let mut found: Vec<_> = store
+            .find(&pred)
+            .unwrap()
+            .iter()
+            .map(|d| d["n"].as_i64().unwrap())
+            .collect();
+        found.sort();
+        assert_eq!(found, [3, 4, 5]);
+        assert_eq!(store.find(&Predicate::eq("n", 3)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_fast_store_scan_filtered() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..200 {
+            let cat = if i % 3 == 0 { "A" } else { "B" };
+            let doc = json!({"i": i, "cat": cat, "meta": {"even": i % 2 == 0}});
+            store.put(format!("doc{}", i), doc).unwrap();
+        }
+        store.delete("doc3").unwrap();
+        store.put("doc0", json!({"i": 1000, "cat": "A"})).unwrap();
+        store.flush().unwrap();
+
+        let pred = Predicate::and(vec![
+            Predicate::eq("cat", "A"),
+            Predicate::eq("meta.even", true),
+        ]);
+        let docs = store
+            .scan_filtered(Some(&pred), Some(&["i", "meta.even"]))
+            .unwrap();
+        // File order, projected
+        assert_eq!(docs.len(), 33);
+        assert_eq!(docs[0], json!({"i": 6, "even": true}));
+        assert_eq!(docs[32], json!({"i": 198, "even": true}));
+
+        // Index candidates give the same documents in the same order
+        let unindexed = store.scan_filtered(Some(&pred), None).unwrap();
+        store.create_index("cat").unwrap();
+        assert_eq!(store.scan_filtered(Some(&pred), None).unwrap(), unindexed);
+        assert_eq!(store.find(&pred).unwrap(), unindexed);
+
+        let all = store.scan_filtered(None, Some(&["i"])).unwrap();
+        assert_eq!(all.len(), 199);
+        assert_eq!(all.last().unwrap(), &json!({"i": 1000}));
+    }
+
+    #[test]
+    fn test_fast_store_get_many() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 1000).unwrap();
+        for i in 0..100 {
+            store.put(format!("doc{}", i), json!({"i": i})).unwrap();
+        }
+        store.delete("doc7").unwrap();
+        store.put("doc9", json!({"i": 900})).unwrap();
+        store.flush().unwrap();
+
+        let docs = store
+            .get_many(&["doc50", "missing", "doc9", "doc7", "doc1", "doc50"])
+            .unwrap();
+        assert_eq!(
+            docs,
+            [
+                Some(json!({"i": 50})),
+                None,
+                Some(json!({"i": 900})),
+                None,
+                Some(json!({"i": 1})),
+                Some(json!({"i": 50})),
+            ]
+        );
+        assert!(store.get_many::<&str>(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fast_store_scan_raw_packed() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.scan_raw_packed(None).unwrap(), (Vec::new(), vec![0]));
+        for i in 0..5 {
+            store.put(format!("d{}", i), json!({"i": i})).unwrap();
+        }
+        store.delete("d1").unwrap();
+        store.flush().unwrap();
+
+        let (data, offsets) = store.scan_raw_packed(None).unwrap();
+        assert_eq!(offsets.len(), 5);
+        let lines: Vec<&[u8]> = offsets
+            .windows(2)
+            .map(|w| &data[w[0] as usize..w[1] as usize])
+            .collect();
+        assert_eq!(lines, store.scan_raw().unwrap());
+        assert_eq!(lines[0], br#"{"_id":"d0","i":0}"#);
+
+        let pred = Predicate::gte("i", 3);
+        let (data, offsets) = store.scan_raw_packed(Some(&pred)).unwrap();
+        assert_eq!(offsets, [0, 18, 36]);
+        assert_eq!(&data[18..], br#"{"_id":"d4","i":4}"#);
+    }
+
+    #[test]
+    fn test_fast_store_iter() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..10 {
+            store.put(format!("doc{}", i), json!({"i": i})).unwrap();
+        }
+        store.put("doc3", json!({"i": 30})).unwrap();
+        store.delete("doc5").unwrap();
+        store.flush().unwrap();
+
+        let mut scanner = store.iter().unwrap();
+        assert_eq!(scanner.remaining(), 9);
+        let first = scanner.next_entry().unwrap().unwrap();
+        assert_eq!(first, ("doc0".to_string(), json!({"i": 0})));
+
+        // Unflushed writes after the snapshot don't disturb iteration
+        store.put("late", json!({"i": 99})).unwrap();
+        let rest: Vec<i64> = scanner
+            .map(|doc| doc.unwrap()["i"].as_i64().unwrap())
+            .collect();
+        assert_eq!(rest, [1, 2, 4, 6, 7, 8, 9, 30]);
+
+        store.flush().unwrap();
+        let sizes: Vec<usize> = store
+            .scan_iter(4)
+            .unwrap()
+            .map(|batch| batch.unwrap().len())
+            .collect();
+        assert_eq!(sizes, [4, 4, 2]);
+
+        // Chunks end at whichever limit is reached first
+        let chunk_sizes = |size| -> Vec<usize> {
+            store
+                .scan_chunks(size)
+                .unwrap()
+                .map(|chunk| chunk.unwrap().len())
+                .collect()
+        };
+        assert_eq!(chunk_sizes(ChunkSize::new(3, 1000)), [3, 3, 3, 1]);
+        assert_eq!(chunk_sizes(ChunkSize::new(3, 40)), [2, 2, 2, 2, 2]);
+        assert_eq!(chunk_sizes(ChunkSize::bytes(1)), [1; 10]);
+        assert_eq!(chunk_sizes(ChunkSize::docs(0)), [1; 10]);
+
+        let mut raw = store.iter_range(0, Some(1)).unwrap();
+        assert_eq!(raw.next_raw().unwrap().unwrap(), br#"{"_id":"doc0","i":0}"#);
+        assert!(raw.next_raw().unwrap().is_none());
+
+        let empty = FastStore::open(tmp.path(), "empty", 100).unwrap();
+        assert_eq!(empty.iter().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_fast_store_aggregate() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..10 {
+            let kind = if i % 2 == 0 { "even" } else { "odd" };
+            store
+                .put(format!("doc{}", i), json!({"type": kind, "value": i}))
+                .unwrap();
+        }
+        store.delete("doc9").unwrap();
+        store.flush().unwrap();
+
+        let rows = store
+            .aggregate(
+                Some(&GroupBy::new("type")),
+                &[Aggregate::Count, Aggregate::Sum("value".into())],
+            )
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                json!({"type": "even", "count": 5, "sum(value)": 20}),
+                json!({"type": "odd", "count": 4, "sum(value)": 16}),
+            ]
+        );
+
+        let total = store
+            .aggregate(None, &[Aggregate::Max("value".into())])
+            .unwrap();
+        assert_eq!(total, [json!({"max(value)": 8})]);
+    }
+
+    #[test]
+    fn test_fast_store_sample() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..100 {
+            store.put(format!("doc{}", i), json!({"i": i})).unwrap();
+        }
+        store.delete("doc7").unwrap();
+        store.flush().unwrap();
+
+        let sample = store.sample(10, 42).unwrap();
+        assert_eq!(sample.len(), 10);
+        let values: Vec<i64> = sample.iter().map(|d| d["i"].as_i64().unwrap()).collect();
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+        assert!(!values.contains(&7));
+        assert!(sample.iter().all(|d| d.get("_id").is_none()));
+        assert_eq!(store.sample(10, 42).unwrap(), sample);
+
+        assert_eq!(store.sample(1000, 0).unwrap().len(), 99);
+        let empty = FastStore::open(tmp.path(), "empty", 100).unwrap();
+        assert!(empty.sample(5, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fast_store_strict_schema() {
+        let tmp = TempDir::new().unwrap();
+        Layout::init_collection(tmp.path(), "test").unwrap();
+        let manifest = crate::engine::Manifest::new("test", true);
+        std::fs::write(
+            Layout::manifest_file(tmp.path(), "test"),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        // A rejected blob does not set the schema
+        let blob = b"{\"_id\":\"a\",\"x\":1}\n{\"_id\":\"b\",\"y\":1}\n";
+        let err = store
+            .write_jsonl_blob(blob, &["a".into(), "b".into()])
+            .unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }));
+        assert!(store.is_empty());
+
+        store
+            .put("doc1", json!({"name": "alice", "age": 30}))
+            .unwrap();
+        store
+            .put("doc2", json!({"name": "bob", "age": 25}))
+            .unwrap();
+        let err = store.put("doc3", json!({"name": "carol"})).unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }));
+        let err = store
+            .put_raw_line("doc3", br#"{"_id":"doc3","name":1,"age":2}"#)
+            .unwrap_err();
+        assert!(matches!(err, Error::SchemaMismatch { .. }));
+        store
+            .put_raw_line("doc3", br#"{"_id":"doc3","name":"carol","age":41}"#)
+            .unwrap();
+        assert_eq!(store.len(), 3);
+        store.flush().unwrap();
+        drop(store);
+
+        // The schema set by the first write is kept across opens
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert!(store.put("doc4", json!({"other": true})).is_err());
+        store.put("doc4", json!({"name": "dan", "age": 5})).unwrap();
+
+        // Flexible collections accept any shape
+        let mut flexible = FastStore::open(tmp.path(), "flexible", 100).unwrap();
+        flexible.put("a", json!({"x": 1})).unwrap();
+        flexible.put("b", json!({"y": "z"})).unwrap();
+    }
+
+    #[test]
+    fn test_fast_store_insertion_order() {
+        let tmp = TempDir::new().unwrap();
+        {
+            let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+            for id in ["c", "a", "b"] {
+                store.put(id, json!({"n": 1})).unwrap();
+            }
+            // Overwrites and compaction keep the order; a deleted ID is new
+            store.put("c", json!({"n": "two"})).unwrap();
+            store.put_raw_line("d", br#"{"_id":"d","n":3}"#).unwrap();
+            store.delete("a").unwrap();
+            store.put("a", json!({"n": 4})).unwrap();
+            store.compact().unwrap();
+        }
+        let registry = IndexRegistry::load(tmp.path(), "test").unwrap();
+        assert_eq!(registry.all_doc_ids(), ["c", "b", "d", "a"]);
+        let c = registry.get("c").unwrap();
+        assert_eq!(
+            c.schema_id,
+            SchemaRegistry::compute_schema_id(&json!({"n": "two"}))
+        );
+        assert!(c.mtime > 0);
+        assert_eq!(registry.get("d").unwrap().schema_id, "");
+
+        let store = FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        assert_eq!(store.doc_index().all_doc_ids(), ["c", "b", "d", "a"]);
+        drop(store);
+
+        // Without the saved order, documents follow in file order
+        std::fs::remove_file(Layout::order_file(tmp.path(), "test")).unwrap();
+        std::fs::remove_file(Layout::doc_index(tmp.path(), "test")).unwrap();
+        drop(FastStore::open(tmp.path(), "test", 100).unwrap());
+        let registry = IndexRegistry::load(tmp.path(), "test").unwrap();
+        assert_eq!(registry.all_doc_ids(), ["b", "c", "d", "a"]);
+        assert_eq!(registry.get("b").unwrap().mtime, 0);
+    }
+
+    #[test]
+    fn test_fast_store_json_schema() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("old", json!({"score": -5})).unwrap();
+        let schema = JsonSchema::new(json!({
+            "properties": {"score": {"type": "integer", "minimum": 0}},
+            "required": ["score"]
+        }))
+        .unwrap();
+        store.set_json_schema(Some(schema)).unwrap();
+
+        store.put("a", json!({"score": 3})).unwrap();
+        let err = store.put("b", json!({"score": -1})).unwrap_err();
+        assert!(matches!(err, Error::SchemaViolation(_)));
+        assert!(store.put_raw_line("b", br#"{"_id":"b"}"#).is_err());
+        let blob = b"{\"_id\":\"c\",\"score\":1}\n{\"_id\":\"d\",\"score\":\"x\"}\n";
+        assert!(store
+            .write_jsonl_blob(blob, &["c".into(), "d".into()])
+            .is_err());
+        assert!(!store.exists("c"));
+        store.flush().unwrap();
+        drop(store);
+
+        // The schema is loaded on open and can be detached
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert!(store.json_schema().is_some());
+        assert!(store.patch("a", &json!({"score": null})).is_err());
+        store.set_json_schema(None).unwrap();
+        store.put("b", json!({"score": -1})).unwrap();
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert!(store.json_schema().is_none());
+    }
+
+    #[test]
+    fn test_fast_store_ordered_paging() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..6 {
+            store.put(format!("doc{}", i), json!({"i": i})).unwrap();
+        }
+        store.put("doc1", json!({"i": 10})).unwrap();
+        store.delete("doc4").unwrap();
+        store.flush().unwrap();
+
+        assert_eq!(store.doc_ids(), ["doc0", "doc2", "doc3", "doc5", "doc1"]);
+        let page = |store: &FastStore, offset, limit| -> Vec<i64> {
+            store
+                .scan_range(offset, limit)
+                .unwrap()
+                .iter()
+                .map(|d| d["i"].as_i64().unwrap())
+                .collect()
+        };
+        assert_eq!(page(&store, 0, 2), [0, 2]);
+        assert_eq!(page(&store, 2, 2), [3, 5]);
+        assert_eq!(page(&store, 4, 2), [10]);
+        assert!(page(&store, 9, 2).is_empty());
+
+        let all: Vec<i64> = store
+            .scan()
+            .unwrap()
+            .iter()
+            .map(|d| d["i"].as_i64().unwrap())
+            .collect();
+        assert_eq!(all, [0, 2, 3, 5, 10]);
+
+        // Order survives reopening
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(page(&store, 1, 3), [2, 3, 5]);
+        let mut scanner = store.iter_range(3, None).unwrap();
+        assert_eq!(scanner.remaining(), 2);
+        assert_eq!(scanner.next_doc().unwrap().unwrap(), json!({"i": 5}));
+        scanner.reset();
+        assert_eq!(scanner.count(), 2);
+    }
+
+    #[test]
+    fn test_fast_store_scan_prefix() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for id in ["user/2/a", "user/1/b", "user/10/a", "user/1/a"] {
+            store.put(id, json!({"id": id})).unwrap();
+        }
+        store.flush().unwrap();
+        assert_eq!(store.ids_with_prefix("user/1/"), ["user/1/a", "user/1/b"]);
+
+        // Writes after the first prefix scan are kept in order
+        store.put("user/1/0", json!({"id": "user/1/0"})).unwrap();
+        store.delete("user/1/b").unwrap();
+        store.flush().unwrap();
+        let docs = store.scan_prefix("user/1/").unwrap();
+        assert_eq!(docs, [json!({"id": "user/1/0"}), json!({"id": "user/1/a"})]);
+        assert_eq!(store.ids_with_prefix("user/1").len(), 3);
+
+        store.compact().unwrap();
+        assert_eq!(store.ids_with_prefix("user/").len(), 4);
+        assert!(store.put("user//x", json!({})).is_err());
+    }
+
+    #[test]
+    fn test_fast_store_in_memory() {
+        let mut store = FastStore::open_in_memory("test", 100).unwrap();
+        assert!(store.is_in_memory());
+        store.put("a", json!({"n": 1})).unwrap();
+        store.flush().unwrap();
+        assert_eq!(store.get("a").unwrap(), json!({"n": 1}));
+        let dir = store.root_path().to_path_buf();
+        assert!(dir.exists());
+        drop(store);
+        assert!(!dir.exists());
+
+        // A loaded copy leaves the collection on disk as it was
+        let tmp = TempDir::new().unwrap();
+        let mut disk = FastStore::open(tmp.path(), "test", 100).unwrap();
+        disk.put("a", json!({"n": 1})).unwrap();
+        disk.flush().unwrap();
+        let mut copy = FastStore::load_in_memory(tmp.path(), "test", 100).unwrap();
+        assert_eq!(copy.get("a").unwrap(), json!({"n": 1}));
+        copy.put("b", json!({"n": 2})).unwrap();
+        copy.flush().unwrap();
+        drop(copy);
+        disk.refresh_mmap().unwrap();
+        assert!(!disk.exists("b"));
+        assert!(matches!(
+            FastStore::load_in_memory(tmp.path(), "missing", 100),
+            Err(Error::CollectionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_fast_store_id_range() {
+        let tmp = TempDir::new().unwrap();
+        let config = CollectionConfig {
+            key_order: KeyOrder::Sorted,
+            ..Default::default()
+        };
+        config.save(tmp.path(), "test").unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for id in ["c", "a", "e", "b", "d"] {
+            store.put(id, json!({"id": id})).unwrap();
+        }
+        store.flush().unwrap();
+        assert_eq!(store.config().unwrap().key_order, KeyOrder::Sorted);
+        assert_eq!(store.id_range("b".."d"), ["b", "c"]);
+        assert_eq!(store.id_range("b"..="d"), ["b", "c", "d"]);
+        assert_eq!(store.id_range(.."b"), ["a"]);
+        assert!(store.id_range("d".."b").is_empty());
+        drop(store);
+
+        // Sorted from the start after a reopen
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert!(store.sorted_ids.get().is_some());
+        let docs = store.scan_id_range("d"..).unwrap();
+        assert_eq!(docs, [json!({"id": "d"}), json!({"id": "e"})]);
+    }
+
+    #[test]
+    fn test_fast_store_scan_with() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..10 {
+            store
+                .put(
+                    format!("doc{}", i),
+                    json!({"i": i, "tag": if i % 2 == 0 { "even" } else { "odd" }}),
+                )
+                .unwrap();
+        }
+        store.flush().unwrap();
+
+        let odd = store
+            .scan_with(|doc| {
+                (doc.get_str("tag") == Some("odd")).then(|| doc.id().unwrap().to_string())
+            })
+            .unwrap();
+        assert_eq!(odd, ["doc1", "doc3", "doc5", "doc7", "doc9"]);
+
+        let filtered = store
+            .scan_filtered(Some(&Predicate::gte("i", 8)), Some(&["i"]))
+            .unwrap();
+        assert_eq!(filtered, [json!({"i": 8}), json!({"i": 9})]);
+        let (data, offsets) = store
+            .scan_raw_packed(Some(&Predicate::eq("tag", "even")))
+            .unwrap();
+        assert_eq!(offsets.len(), 6);
+        assert!(data.starts_with(br#"{"_id":"doc0""#));
+    }
+
+    #[test]
+    fn test_fast_store_mmap_hints() {
+        let tmp = TempDir::new().unwrap();
+        for (collection, compression) in [("plain", Compression::None), ("zstd", Compression::Zstd)]
+        {
+            let mut store = FastStore::open(tmp.path(), collection, 100).unwrap();
+            store.set_compression(compression).unwrap();
+            for i in 0..500 {
+                store.put(format!("doc{:03}", i), json!({"i": i})).unwrap();
+            }
+            store.flush().unwrap();
+            let expected = store.scan().unwrap();
+            assert_eq!(store.mmap_hints(), MmapHints::default());
+
+            store
+                .set_mmap_hints(MmapHints {
+                    populate: true,
+                    sequential: true,
+                    readahead: 1024,
+                })
+                .unwrap();
+            store.prefetch().unwrap();
+            assert_eq!(store.scan().unwrap(), expected);
+            let filtered = store
+                .scan_filtered(Some(&Predicate::gte("i", 498)), None)
+                .unwrap();
+            assert_eq!(filtered, [json!({"i": 498}), json!({"i": 499})]);
+
+            store
+                .set_mmap_hints(MmapHints {
+                    readahead: 0,
+                    ..MmapHints::default()
+                })
+                .unwrap();
+            assert_eq!(store.scan().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_fast_store_stats() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        let stats = store.stats();
+        assert_eq!(stats.doc_count, 0);
+        assert_eq!(stats.data_bytes, 0);
+        assert_eq!(stats.dead_ratio, 0.0);
+        assert_eq!((stats.min_offset, stats.max_offset), (None, None));
+        assert_eq!(stats.segments, 1);
+        assert_eq!(stats.compression, Compression::None);
+
+        store.set_compression(Compression::Zstd).unwrap();
+        store.set_segment_size(200);
+        for i in 0..10 {
+            store
+                .put(format!("doc{}", i), json!({"text": "x".repeat(50)}))
+                .unwrap();
+        }
+        store.put("doc0", json!({"text": "y"})).unwrap();
+        store.delete("doc9").unwrap();
+        let before_flush = now_ms();
+        store.flush().unwrap();
+
+        let stats = store.stats();
+        assert_eq!(stats.doc_count, 9);
+        assert_eq!(stats.compression, Compression::Zstd);
+        assert!(stats.segments > 1);
+        assert_eq!(stats.live_bytes + stats.dead_bytes, stats.data_bytes);
+        assert_eq!(stats.tombstones, 1);
+        // The rewritten doc0 holds the highest offset, in the last segment
+        assert_eq!(stats.max_offset, Some(store.index["doc0"].offset));
+        assert!(stats.min_offset < stats.max_offset);
+        let last_flush = stats.last_flush.unwrap();
+        // File times may be coarser than a millisecond
+        assert!(last_flush + 1000 >= before_flush && last_flush <= now_ms());
+
+        // A reader sees the same numbers
+        let reader = FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        let read_stats = reader.stats();
+        assert_eq!(read_stats.doc_count, stats.doc_count);
+        assert_eq!(read_stats.data_bytes, stats.data_bytes);
+        assert_eq!(read_stats.dead_bytes, stats.dead_bytes);
+        assert_eq!(read_stats.segments, stats.segments);
+        assert_eq!(read_stats.last_flush, stats.last_flush);
+    }
+
+    #[test]
+    fn test_fast_store_compaction() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..10 {
+            store.put(format!("doc{}", i), json!({"i": i})).unwrap();
+        }
+        store.flush().unwrap();
+        assert_eq!(store.dead_bytes(), 0);
+        assert_eq!(store.live_bytes(), store.data_len());
+        assert!(store.index_bytes() > 0);
+
+        store.put("doc0", json!({"i": 100})).unwrap();
+        store.delete("doc1").unwrap();
+        store.delete("doc2").unwrap();
+        store.flush().unwrap();
+        assert_eq!(store.tombstone_count(), 2);
+        assert!(store.dead_ratio() > 0.2 && store.dead_ratio() < 0.4);
+        assert_eq!(store.live_bytes() + store.dead_bytes(), store.data_len());
+        let stats = store.stats();
+        assert_eq!(stats.doc_count, 8);
+        assert_eq!(stats.data_bytes, store.data_len());
+        assert_eq!(stats.dead_bytes, store.dead_bytes());
+        assert_eq!(stats.tombstones, 2);
+        assert!(stats.index_memory_bytes > 0);
+        assert_eq!(stats.min_offset, Some(store.index["doc3"].offset));
+        assert_eq!(stats.max_offset, Some(store.index["doc0"].offset));
+        assert!(stats.last_flush.unwrap() <= now_ms());
+        assert!(Layout::tombstone_file(tmp.path(), "test").exists());
+
+        // Stats survive reopening
+        drop(store);
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.tombstone_count(), 2);
+        let dead = store.dead_bytes();
+        assert!(dead > 0);
+
+        // Below the threshold nothing happens
+        store.set_compaction_policy(Some(CompactionPolicy {
+            min_dead_ratio: 0.5,
+            min_dead_bytes: 0,
+        }));
+        store.flush().unwrap();
+        assert_eq!(store.dead_bytes(), dead);
+
+        store.set_compaction_policy(Some(CompactionPolicy {
+            min_dead_ratio: 0.3,
+            min_dead_bytes: 0,
+        }));
+        store.delete("doc3").unwrap();
+        store.flush().unwrap();
+        assert_eq!(store.dead_bytes(), 0);
+        assert_eq!(store.tombstone_count(), 0);
+        assert!(!Layout::tombstone_file(tmp.path(), "test").exists());
+
+        let values: Vec<i64> = store
+            .scan()
+            .unwrap()
+            .iter()
+            .map(|d| d["i"].as_i64().unwrap())
+            .collect();
+        assert_eq!(values, [4, 5, 6, 7, 8, 9, 100]);
+        assert_eq!(
+            std::fs::metadata(Layout::data_file(tmp.path(), "test"))
+                .unwrap()
+                .len(),
+            store.live_bytes
+        );
+
+        // Writes keep working after the rewrite
+        store.put("doc11", json!({"i": 11})).unwrap();
+        store.flush().unwrap();
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 8);
+        assert_eq!(store.get("doc0").unwrap(), json!({"i": 100}));
+    }
+
+    #[test]
+    fn test_fast_store_deletes_survive_index_rebuild() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for id in ["a", "b", "c"] {
+            store.put(id, json!({"id": id})).unwrap();
+        }
+        store.delete("a").unwrap();
+        store.delete("b").unwrap();
+        store.put("b", json!({"id": "b", "v": 2})).unwrap();
+        store.flush().unwrap();
+        assert_eq!(store.deleted_ids(), ["a"]);
+        drop(store);
+
+        // Lose the binary index so it is rebuilt from data.jsonl
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["c", "b"]);
+        assert!(!store.exists("a"));
+        assert_eq!(store.get("b").unwrap(), json!({"id": "b", "v": 2}));
+        assert_eq!(store.deleted_ids(), ["a"]);
+
+        // Re-creating a deleted doc clears it from the deleted list
+        store.put("a", json!({"id": "a"})).unwrap();
+        assert!(store.deleted_ids().is_empty());
+        store.flush().unwrap();
+        drop(store);
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn test_fast_store_raw_line_ids() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put_raw_line("42", br#"{"_id":42,"n":1}"#).unwrap();
+        store
+            .put_raw_line(r#"q"\u"#, br#"{ "n" : 2, "_id" : "q\"\\\u0075" }"#)
+            .unwrap();
+        let blob = b"{\"_id\":-7,\"n\":3}\n\n{\"a\":{\"_id\":\"x\"},\"_id\":\"\\u00e9\",\"n\":4}\n";
+        assert_eq!(
+            store
+                .write_jsonl_blob(blob, &["-7".into(), "\u{e9}".into()])
+                .unwrap(),
+            2
+        );
+
+        // Lines whose _id isn't the ID they're written as are rejected
+        for line in [
+            &br#"{"_id":"other"}"#[..],
+            br#"{"n":1}"#,
+            br#"{"_id":1.5}"#,
+            b"not json",
+        ] {
+            assert!(matches!(
+                store.put_raw_line("doc", line),
+                Err(Error::Validation(_))
+            ));
+        }
+        let blob = b"{\"_id\":\"a\"}\n{\"_id\":\"c\"}\n";
+        assert!(matches!(
+            store.write_jsonl_blob(blob, &["a".into(), "b".into()]),
+            Err(Error::Validation(_))
+        ));
+        assert!(!store.exists("a"));
+        // One ID per line: neither fewer nor more
+        let blob = b"{\"_id\":\"a\"}\n\n{\"_id\":\"b\"}\n";
+        for doc_ids in [
+            &["a".to_string()][..],
+            &["a".into(), "b".into(), "c".into()],
+        ] {
+            assert!(matches!(
+                store.write_jsonl_blob(blob, doc_ids),
+                Err(Error::Validation(_))
+            ));
+        }
+        assert!(!store.exists("a"));
+        store.flush().unwrap();
+        // Integer and escaped IDs match the IDs they're indexed under
+        let report = store.verify().unwrap();
+        assert_eq!(report.checked, 4);
+        assert!(report.is_ok(), "{:?}", report.corrupt);
+        drop(store);
+
+        // A rebuilt index finds every document under the same ID
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 4);
+        assert_eq!(store.get("42").unwrap(), json!({"n": 1}));
+        assert_eq!(store.get(r#"q"\u"#).unwrap(), json!({"n": 2}));
+        assert_eq!(store.get("-7").unwrap(), json!({"n": 3}));
+        assert_eq!(
+            store.get("\u{e9}").unwrap(),
+            json!({"a": {"_id": "x"}, "n": 4})
+        );
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_index_rebuild_keeps_every_document(
+            docs in proptest::collection::btree_map(
+                proptest::prelude::any::<String>(),
+                proptest::prelude::any::<String>(),
+                1..16,
+            ),
+        ) {
+            let tmp = TempDir::new().unwrap();
+            let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+            // Documents with valid IDs are put every other time, the rest
+            // written as raw lines with the ID last and lookalikes before it
+            let mut blob = Vec::new();
+            let mut blob_ids = Vec::new();
+            for (i, (doc_id, text)) in docs.iter().enumerate() {
+                if i % 2 == 0 && Layout::validate_doc_id(doc_id).is_ok() {
+                    store.put(doc_id, json!({"text": text})).unwrap();
+                } else {
+                    let line = format!(
+                        r#"{{ "A" : {{"_id":"nested"}}, "text" : {}, "_id" : {} }}"#,
+                        json!(text),
+                        json!(doc_id)
+                    );
+                    blob.extend_from_slice(line.as_bytes());
+                    blob.push(b'\n');
+                    blob_ids.push(doc_id.clone());
+                }
+            }
+            store.write_jsonl_blob(&blob, &blob_ids).unwrap();
+            store.flush().unwrap();
+            drop(store);
+
+            std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+            let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+            proptest::prop_assert_eq!(store.len(), docs.len());
+            for (doc_id, text) in &docs {
+                let doc = store.get(doc_id).unwrap();
+                proptest::prop_assert_eq!(&doc["text"], &json!(text));
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_store_crash_recovery() {
+        let tmp = TempDir::new().unwrap();
+        let data_file = Layout::data_file(tmp.path(), "test");
+        let index_file = Layout::meta_dir(tmp.path(), "test").join("index.bin");
+        let saved_index = tmp.path().join("index.bak");
 
-        let inner = Arc::new(ZDSRootInner {
-            root: root_path.to_path_buf(),
-            batch_size,
-            mode,
-            write_lock,
-        });
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"v": 1})).unwrap();
+        store.put("b", json!({"v": 2})).unwrap();
+        store.flush().unwrap();
+        std::fs::copy(&index_file, &saved_index).unwrap();
 
-        // Store weak reference in cache
-        cache.insert(cache_key, Arc::downgrade(&inner));
+        // Writes after the saved index: replayed from the data file on open
+        store.put("c", json!({"v": 3})).unwrap();
+        store.delete("a").unwrap();
+        store.flush().unwrap();
+        drop(store);
+        std::fs::copy(&saved_index, &index_file).unwrap();
 
-        Ok(ZDSRoot { inner })
+        // Torn final line from a crash mid-write
+        let clean_len = std::fs::metadata(&data_file).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&data_file).unwrap();
+        file.write_all(br#"{"_id":"d","v":"#).unwrap();
+        drop(file);
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(std::fs::metadata(&data_file).unwrap().len(), clean_len);
+        assert_eq!(store.doc_ids(), ["b", "c"]);
+        assert_eq!(store.get("c").unwrap(), json!({"v": 3}));
+
+        // Compaction interrupted before the new index was saved
+        std::fs::copy(&index_file, &saved_index).unwrap();
+        store.compact().unwrap();
+        drop(store);
+        std::fs::copy(&saved_index, &index_file).unwrap();
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["b", "c"]);
+        assert_eq!(store.get("b").unwrap(), json!({"v": 2}));
     }
 
-    /// Open in read-write mode (convenience method).
-    pub fn open_rw(root: impl AsRef<Path>, batch_size: usize) -> Result<Self> {
-        Self::open(root, batch_size, OpenMode::ReadWrite)
+    #[test]
+    fn test_fast_store_durability() {
+        let tmp = TempDir::new().unwrap();
+        let tombstone_file = Layout::tombstone_file(tmp.path(), "test");
+        let reader = || FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.durability(), Durability::FsyncBatch);
+        store.put("doc1", json!({"i": 1})).unwrap();
+        assert_eq!(reader().len(), 0);
+
+        // Every write reaches the files before the flush saves the index
+        store.set_durability(Durability::FsyncOp);
+        store.put("doc2", json!({"i": 2})).unwrap();
+        assert_eq!(reader().len(), 2);
+        store.delete("doc1").unwrap();
+        assert!(tombstone_file.exists());
+        assert!(!reader().exists("doc1"));
+
+        // Transactions still commit atomically without syncs
+        store.set_durability(Durability::None);
+        store.begin().unwrap();
+        store.put("doc3", json!({"i": 3})).unwrap();
+        store.delete("doc2").unwrap();
+        store.commit().unwrap();
+        assert!(TransactionLog::load_uncommitted(tmp.path(), "test")
+            .unwrap()
+            .is_empty());
+        drop(store);
+        let store = reader();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get("doc3").unwrap(), json!({"i": 3}));
     }
 
-    /// Open in read-only mode (convenience method).
-    pub fn open_readonly(root: impl AsRef<Path>, batch_size: usize) -> Result<Self> {
-        Self::open(root, batch_size, OpenMode::Read)
+    #[test]
+    fn test_fast_store_index_delta() {
+        let tmp = TempDir::new().unwrap();
+        let index_file = Layout::meta_dir(tmp.path(), "test").join("index.bin");
+        let delta_file = Layout::index_delta(tmp.path(), "test");
+        let reader = || FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..10 {
+            store.put(format!("doc{}", i), json!({"i": i})).unwrap();
+        }
+        store.flush().unwrap();
+        assert!(index_file.exists() && !delta_file.exists());
+        let saved = std::fs::read(&index_file).unwrap();
+
+        // Later flushes append their changes, leaving index.bin alone
+        store.put("doc0", json!({"i": 100})).unwrap();
+        store.delete("doc1").unwrap();
+        store
+            .put_with_ttl("doc2", json!({"i": 2}), Duration::from_secs(3600))
+            .unwrap();
+        store.flush().unwrap();
+        store.put("doc10", json!({"i": 10})).unwrap();
+        store.flush().unwrap();
+        assert_eq!(std::fs::read(&index_file).unwrap(), saved);
+        let delta_len = std::fs::metadata(&delta_file).unwrap().len();
+        assert_eq!(store.index_bytes(), saved.len() as u64 + delta_len);
+
+        let check = |store: &FastStore| {
+            assert_eq!(store.len(), 10);
+            assert_eq!(store.get("doc0").unwrap(), json!({"i": 100}));
+            assert!(!store.exists("doc1") && store.exists("doc10"));
+            assert!(store.expires_at("doc2").is_some());
+        };
+        let loaded = reader();
+        check(&loaded);
+        assert!(loaded.saved_index.is_some_and(|s| s.delta_changes == 4));
+        assert!(reader().verify().unwrap().is_ok());
+
+        // A torn batch is ignored, then cut off by the next append
+        let mut file = OpenOptions::new().append(true).open(&delta_file).unwrap();
+        file.write_all(&[200, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+        check(&reader());
+        store.delete("doc3").unwrap();
+        store.flush().unwrap();
+        let loaded = reader();
+        assert!(!loaded.exists("doc3") && loaded.exists("doc10"));
+
+        // Closing folds the delta into index.bin
+        drop(store);
+        assert!(!delta_file.exists());
+        assert_ne!(std::fs::read(&index_file).unwrap(), saved);
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 9);
+        assert!(store.expires_at("doc2").is_some());
+
+        // A delta left over from a replaced index.bin is ignored
+        store.put("doc11", json!({"i": 11})).unwrap();
+        store.flush().unwrap();
+        let delta = std::fs::read(&delta_file).unwrap();
+        store.compact().unwrap();
+        assert!(!delta_file.exists());
+        drop(store);
+        std::fs::write(&delta_file, delta).unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 10);
+        assert_eq!(store.get("doc11").unwrap(), json!({"i": 11}));
+        assert!(store.verify().unwrap().is_ok());
     }
 
-    /// Get the root path.
-    pub fn root_path(&self) -> &Path {
-        &self.inner.root
+    #[test]
+    fn test_fast_store_transaction() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"v": 1})).unwrap();
+        assert!(store.commit().is_err());
+
+        // Uncommitted writes are invisible and can be discarded
+        store.begin().unwrap();
+        assert!(store.begin().is_err());
+        store.put("b", json!({"v": 2})).unwrap();
+        store.delete("a").unwrap();
+        assert!(store.delete("a").is_err());
+        assert!(!store.exists("b") && store.exists("a"));
+        store.rollback().unwrap();
+        assert!(!store.in_transaction());
+        assert_eq!(store.doc_ids(), ["a"]);
+
+        store.begin().unwrap();
+        store.put("b", json!({"v": 2})).unwrap();
+        store.put("c", json!({"v": 3})).unwrap();
+        store.delete("c").unwrap();
+        store.delete("a").unwrap();
+        store.commit().unwrap();
+        assert_eq!(store.doc_ids(), ["b"]);
+        assert_eq!(store.get("b").unwrap(), json!({"v": 2}));
+        drop(store);
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["b"]);
+        assert_eq!(store.deleted_ids(), ["a", "c"]);
     }
 
-    /// Get the default batch size.
-    pub fn batch_size(&self) -> usize {
-        self.inner.batch_size
+    #[test]
+    fn test_fast_store_interrupted_transaction() {
+        let tmp = TempDir::new().unwrap();
+        let data_file = Layout::data_file(tmp.path(), "test");
+        let tombstone_file = Layout::tombstone_file(tmp.path(), "test");
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"v": 1})).unwrap();
+        store.put("b", json!({"v": 2})).unwrap();
+        store.delete("b").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        // Crash after the lines of a transaction were written, before COMMIT
+        let data_len = std::fs::metadata(&data_file).unwrap().len();
+        let tombstones_len = std::fs::metadata(&tombstone_file).unwrap().len();
+        let mut journal = TransactionLog::open(tmp.path(), "test").unwrap();
+        journal
+            .append(&JournalEntry::begin(0, data_len, data_len, tombstones_len))
+            .unwrap();
+        drop(journal);
+        let mut file = OpenOptions::new().append(true).open(&data_file).unwrap();
+        file.write_all(b"{\"_id\":\"x\",\"v\":3}\n").unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&tombstone_file)
+            .unwrap();
+        writeln!(file, r#"{{"_id":"a","offset":{}}}"#, data_len + 15).unwrap();
+
+        let store = FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        assert_eq!(store.doc_ids(), ["a"]);
+        drop(store);
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(std::fs::metadata(&data_file).unwrap().len(), data_len);
+        assert_eq!(
+            std::fs::metadata(&tombstone_file).unwrap().len(),
+            tombstones_len
+        );
+        assert_eq!(store.doc_ids(), ["a"]);
+
+        // The rollback is recorded, so later writes are kept
+        store.put("y", json!({"v": 4})).unwrap();
+        store.flush().unwrap();
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["a", "y"]);
     }
 
-    /// Get the open mode.
-    pub fn mode(&self) -> OpenMode {
-        self.inner.mode
+    #[test]
+    fn test_fast_store_verify() {
+        let tmp = TempDir::new().unwrap();
+        let data_file = Layout::data_file(tmp.path(), "test");
+        let index_file = Layout::meta_dir(tmp.path(), "test").join("index.bin");
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..4 {
+            store.put(format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        let report = store.verify().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.checked, 4);
+        drop(store);
+
+        // Flip a digit in doc1 and cut off doc3
+        let data = std::fs::read_to_string(&data_file).unwrap();
+        let data = data.replace(r#""n":1"#, r#""n":7"#);
+        std::fs::write(&data_file, &data[..data.len() - 3]).unwrap();
+
+        let mut store = FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        let report = store.verify().unwrap();
+        assert!(report.index_ok);
+        let kinds: Vec<_> = report
+            .corrupt
+            .iter()
+            .map(|c| (c.doc_id.as_str(), c.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("doc1", CorruptionKind::ChecksumMismatch),
+                ("doc3", CorruptionKind::Truncated)
+            ]
+        );
+
+        // A damaged index fails its own checksum
+        let mut index = std::fs::read(&index_file).unwrap();
+        *index.last_mut().unwrap() ^= 0xFF;
+        std::fs::write(&index_file, index).unwrap();
+        let mut store = FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        assert!(!store.verify().unwrap().index_ok);
     }
 
-    /// Check if this root is writable.
-    pub fn is_writable(&self) -> bool {
-        self.inner.mode == OpenMode::ReadWrite
+    #[test]
+    fn test_fast_store_refresh() {
+        let tmp = TempDir::new().unwrap();
+        let data_file = Layout::data_file(tmp.path(), "test");
+        let index_file = Layout::meta_dir(tmp.path(), "test").join("index.bin");
+
+        let mut writer = FastStore::open(tmp.path(), "test", 100).unwrap();
+        writer.put("a", json!({"v": 1})).unwrap();
+        writer.put("b", json!({"v": 2})).unwrap();
+        writer.flush().unwrap();
+
+        let mut reader =
+            FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        assert_eq!(reader.len(), 2);
+        assert!(!reader.refresh().unwrap());
+
+        // Appends, overwrites and deletes are picked up incrementally
+        writer.put("c", json!({"v": 3})).unwrap();
+        writer.put("a", json!({"v": 10})).unwrap();
+        writer.delete("b").unwrap();
+        writer.flush().unwrap();
+        assert!(reader.refresh().unwrap());
+        assert_eq!(reader.doc_ids(), ["c", "a"]);
+        assert_eq!(reader.get("a").unwrap()["v"], 10);
+        assert!(!reader.exists("b"));
+        assert!(!reader.refresh().unwrap());
+
+        // A half-written line stays invisible until it is complete
+        let mut file = OpenOptions::new().append(true).open(&data_file).unwrap();
+        file.write_all(br#"{"_id":"d","#).unwrap();
+        assert!(!reader.refresh().unwrap());
+        file.write_all(b"\"v\":4}\n").unwrap();
+        drop(file);
+        assert!(reader.refresh().unwrap());
+        assert_eq!(reader.get("d").unwrap()["v"], 4);
+
+        // Compaction rewrites the data, so the reader reopens
+        drop(writer);
+        let mut writer = FastStore::open(tmp.path(), "test", 100).unwrap();
+        writer.compact().unwrap();
+        writer.put("e", json!({"v": 5})).unwrap();
+        writer.flush().unwrap();
+        assert!(reader.refresh().unwrap());
+        assert_eq!(reader.len(), 4);
+        assert_eq!(reader.get("e").unwrap()["v"], 5);
+
+        // Dropping a reader leaves the writer's index alone
+        let index = std::fs::read(&index_file).unwrap();
+        drop(reader);
+        assert_eq!(std::fs::read(&index_file).unwrap(), index);
+
+        assert!(writer.refresh().is_err());
     }
 
-    /// Open a collection within this ZDS root.
-    ///
-    /// Creates the collection if it doesn't exist (in ReadWrite mode).
-    /// Returns an error if attempting to create in Read mode.
-    pub fn collection(&self, name: impl AsRef<str>) -> Result<FastStore> {
-        self.collection_with_batch_size(name, self.inner.batch_size)
+    #[test]
+    fn test_fast_store_compression() {
+        let tmp = TempDir::new().unwrap();
+        let plain = Layout::data_file(tmp.path(), "test");
+        let compressed = Layout::compressed_data_file(tmp.path(), "test");
+        let text = "lorem ipsum dolor sit amet ".repeat(20);
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.set_compression(Compression::Zstd).unwrap();
+        for i in 0..2000 {
+            store
+                .put(format!("doc{:04}", i), json!({"n": i, "text": text}))
+                .unwrap();
+        }
+        store.delete("doc0001").unwrap();
+        store.flush().unwrap();
+        assert!(compressed.exists() && !plain.exists());
+        assert!(std::fs::metadata(&compressed).unwrap().len() < store.current_offset / 10);
+
+        assert_eq!(store.get("doc1234").unwrap()["n"], 1234);
+        assert_eq!(store.scan().unwrap().len(), 1999);
+        assert_eq!(store.scan_raw().unwrap().len(), 1999);
+        assert_eq!(store.iter().unwrap().count(), 1999);
+        drop(store);
+
+        // The index is rebuilt from the blocks
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 1999);
+        assert_eq!(store.get("doc1999").unwrap()["n"], 1999);
+        assert!(store.verify().unwrap().is_ok());
+        let raw = store.get_raw_data().unwrap();
+        assert_eq!(raw.len() as u64, store.current_offset);
+        drop(raw);
+
+        // Compaction switches formats
+        store.set_compression(Compression::None).unwrap();
+        store.compact().unwrap();
+        assert!(plain.exists() && !compressed.exists());
+        assert_eq!(store.compression(), Compression::None);
+        assert_eq!(store.get("doc0002").unwrap()["n"], 2);
+        drop(store);
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 1999);
+        assert!(store.get("doc0001").is_err());
     }
 
-    /// Open a collection with a custom batch size.
-    pub fn collection_with_batch_size(
-        &self,
-        name: impl AsRef<str>,
-        batch_size: usize,
-    ) -> Result<FastStore> {
-        let name = name.as_ref();
+    #[test]
+    fn test_fast_store_encryption() {
+        use crate::encryption::{EncryptionKey, KEY_ENV};
 
-        // Check if collection exists
-        let exists = self.collection_exists(name);
+        let tmp = TempDir::new().unwrap();
+        let key = EncryptionKey::generate();
+        std::env::set_var(KEY_ENV, key.to_hex());
+        let data = Layout::compressed_data_file(tmp.path(), "test");
+        let index_file = Layout::meta_dir(tmp.path(), "test").join("index.bin");
+        let contains = |path: &Path, text: &str| {
+            let bytes = std::fs::read(path).unwrap();
+            bytes.windows(text.len()).any(|w| w == text.as_bytes())
+        };
 
-        // In read mode, collection must exist
-        if self.inner.mode == OpenMode::Read && !exists {
-            return Err(Error::CollectionNotFound(name.to_string()));
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..500 {
+            store
+                .put(format!("doc{:03}", i), json!({"n": i, "secret": "hunter2"}))
+                .unwrap();
         }
+        store.create_index("secret").unwrap();
+        store.delete("doc007").unwrap();
+        store
+            .put_blob("doc001", "raw", b"hunter2 as bytes")
+            .unwrap();
+        store.set_encryption(Some(&key)).unwrap();
+        assert!(store.is_encrypted());
+        store
+            .put("doc500", json!({"n": 500, "secret": "hunter2"}))
+            .unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        assert!(!Layout::data_file(tmp.path(), "test").exists());
+        assert!(!contains(&data, "hunter2") && !contains(&index_file, "doc001"));
+        let field_index = Layout::field_index_file(tmp.path(), "test", "secret");
+        assert!(!contains(&field_index, "hunter2"));
+        let blobs = Layout::blob_data_file(tmp.path(), "test", 1);
+        assert!(!contains(&blobs, "hunter2"));
+        assert_eq!(
+            crate::encryption::key_id(tmp.path(), "test").unwrap(),
+            Some(key.id())
+        );
 
-        FastStore::open_with_mode(&self.inner.root, name, batch_size, self.inner.mode)
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 500);
+        assert_eq!(store.get("doc500").unwrap()["secret"], "hunter2");
+        assert!(store.get("doc007").is_err());
+        assert_eq!(
+            store.get_blob("doc001", "raw").unwrap(),
+            b"hunter2 as bytes"
+        );
+        let index = store.field_indexes().get("secret").unwrap();
+        assert_eq!(index.lookup(&json!("hunter2")).len(), 500);
+        assert!(store.verify().unwrap().is_ok());
+
+        // Neither a file in the clear nor another sealed file is read in
+        // place of a sealed file
+        let sealed = std::fs::read(&field_index).unwrap();
+        for swapped in [b"{}\n".to_vec(), std::fs::read(&index_file).unwrap()] {
+            std::fs::write(&field_index, swapped).unwrap();
+            assert!(matches!(
+                FieldIndexes::load(tmp.path(), "test"),
+                Err(Error::Encryption(_))
+            ));
+        }
+        std::fs::write(&field_index, sealed).unwrap();
+
+        // Encrypted collections stay compressed
+        assert!(store.set_compression(Compression::None).is_err());
+
+        // Decrypting rewrites everything in the clear again
+        store.set_encryption(None).unwrap();
+        drop(store);
+        std::env::remove_var(KEY_ENV);
+        assert!(contains(&index_file, "doc001"));
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.get("doc042").unwrap()["n"], 42);
+        assert!(!store.is_encrypted());
     }
 
-    /// List all collections in this ZDS root.
-    pub fn list_collections(&self) -> Result<Vec<String>> {
-        let collections_dir = Layout::collections_dir(&self.inner.root);
-        if !collections_dir.exists() {
-            return Ok(Vec::new());
-        }
+    #[test]
+    fn test_fast_store_column_cache() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store
+            .put("a", json!({"label": "cat", "n": 1, "text": "long"}))
+            .unwrap();
+        store.put("b", json!({"label": "dog", "n": 2})).unwrap();
+        assert_eq!(store.cache_columns(&["label"]).unwrap(), 2);
+        assert_eq!(store.cache_columns(&["n"]).unwrap(), 2);
+        assert_eq!(store.column_cache().unwrap().fields(), ["label", "n"]);
+
+        // Writes through every path keep the cache current
+        store.put("c", json!({"label": "cow"})).unwrap();
+        store
+            .put_raw_line("d", br#"{"_id":"d","label":"eel","n":4}"#)
+            .unwrap();
+        store.delete("b").unwrap();
+        store.flush().unwrap();
+        drop(store);
 
-        let mut collections = Vec::new();
-        for entry in std::fs::read_dir(collections_dir)? {
-            let entry = entry?;
-            if entry.file_type()?.is_dir() {
-                if let Some(name) = entry.file_name().to_str() {
-                    collections.push(name.to_string());
-                }
-            }
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        let columns = store.column_cache().unwrap();
+        assert_eq!(columns.len(), 3);
+        assert_eq!(
+            store.cached_fields("c", &["label", "n"]),
+            Some(json!({"label": "cow"}))
+        );
+        assert_eq!(store.cached_fields("d", &["n"]), Some(json!({"n": 4})));
+        assert_eq!(store.cached_fields("a", &["text"]), None);
+        assert_eq!(store.cached_fields("b", &["label"]), None);
+
+        // Scans reading only cached fields are answered from the columns
+        let pred = Predicate::lt("n", 2);
+        let scan = |fields: &[&str]| {
+            let engine = crate::Engine::open(tmp.path(), "test").unwrap();
+            let scanner = engine.scan(Some(&pred), Some(fields)).unwrap();
+            scanner.collect::<Result<Vec<_>>>().unwrap()
+        };
+        let checksum = store.index["a"].checksum;
+        let columns = store.columns.as_mut().unwrap();
+        columns.insert("a", checksum, &json!({"label": "cached", "n": 1}));
+        store.columns_dirty = true;
+        store.flush().unwrap();
+        assert_eq!(scan(&["label"]), [json!({"label": "cached"})]);
+        // ... and from the documents otherwise
+        assert_eq!(
+            scan(&["label", "text"]),
+            [json!({"label": "cat", "text": "long"})]
+        );
+
+        // A row left stale by a write the cache missed is ignored
+        let columns = store.columns.as_mut().unwrap();
+        columns.insert("a", checksum ^ 1, &json!({}));
+        store.columns_dirty = true;
+        store.flush().unwrap();
+        assert_eq!(scan(&["label"]), [json!({"label": "cat"})]);
+
+        store.drop_column_cache().unwrap();
+        assert!(store.column_cache().is_none());
+        assert!(!Layout::column_cache_file(tmp.path(), "test").exists());
+    }
+
+    #[test]
+    fn test_fast_store_vectors() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for (id, v) in [("a", [1.0, 0.0]), ("b", [0.0, 1.0]), ("c", [0.7, 0.7])] {
+            store.put(id, json!({"id": id})).unwrap();
+            store.put_vector("emb", id, &v).unwrap();
         }
-        collections.sort();
-        Ok(collections)
+        assert!(store.put_vector("emb", "missing", &[1.0, 0.0]).is_err());
+        assert!(store.put_vector("emb", "a", &[1.0, 0.0, 0.0]).is_err());
+        let hits = store.knn("emb", &[1.0, 0.1], 2, Metric::Cosine).unwrap();
+        assert_eq!(hits[0].doc_id, "a");
+        assert_eq!(hits[1].doc_id, "c");
+        assert!(store.knn("other", &[1.0, 0.0], 2, Metric::Cosine).is_err());
+
+        // Deleting a document drops its vector
+        store.delete("a").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.vector_indexes().collect::<Vec<_>>(), ["emb"]);
+        assert_eq!(store.vector_index("emb").unwrap().len(), 2);
+        assert!(store.get_vector("emb", "a").is_none());
+        assert_eq!(store.get_vector("emb", "b"), Some(&[0.0, 1.0][..]));
+        let hits = store.knn("emb", &[1.0, 0.1], 1, Metric::Dot).unwrap();
+        assert_eq!(hits[0].doc_id, "c");
     }
 
-    /// Check if a collection exists.
-    pub fn collection_exists(&self, name: &str) -> bool {
-        Layout::collection_dir(&self.inner.root, name).exists()
+    #[test]
+    fn test_fast_store_body_id() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        // The key wins over an `_id` in the document
+        store.put("a", json!({"_id": "z", "n": 1})).unwrap();
+        store
+            .put_with_ttl("b", json!({"_id": 7, "n": 2}), Duration::from_secs(3600))
+            .unwrap();
+        store.put("c", json!({"n": 3})).unwrap();
+        // A patch can't move a document to another ID
+        let err = store.patch("c", &json!({"_id": "a"})).unwrap_err();
+        assert!(matches!(err, Error::Validation(_)));
+        assert!(store.patch("c", &json!({"_id": null})).is_err());
+        store.flush().unwrap();
+
+        assert_eq!(store.get("a").unwrap(), json!({"n": 1}));
+        assert!(!store.exists("z"));
+        assert!(store.verify().unwrap().is_ok());
+        drop(store);
+
+        // An index rebuilt from the data finds the same IDs
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["a", "b", "c"]);
+        assert_eq!(store.get("b").unwrap(), json!({"n": 2}));
+        assert_eq!(store.get("c").unwrap(), json!({"n": 3}));
     }
 
-    /// Close the root explicitly, releasing any locks.
-    ///
-    /// This removes the root from the cache and drops the write lock if held.
-    /// After calling this, the root handle is still valid but will need to
-    /// reacquire the lock if opened again.
-    pub fn close(&self) {
-        let canonical =
-            std::fs::canonicalize(&self.inner.root).unwrap_or_else(|_| self.inner.root.clone());
-        let cache_key = (canonical, self.inner.mode);
+    #[test]
+    fn test_fast_store_patch() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store
+            .put(
+                "a",
+                json!({"name": "alice", "tags": ["x"], "meta": {"age": 30, "city": "Oslo"}}),
+            )
+            .unwrap();
+        store.put("b", json!({"name": "bob"})).unwrap();
 
-        let mut cache = ROOT_CACHE.write();
-        cache.remove(&cache_key);
-        // The write lock will be released when the last Arc reference is dropped
+        let patched = store
+            .patch(
+                "a",
+                &json!({"tags": ["y"], "meta": {"city": null, "zip": "0150"}}),
+            )
+            .unwrap();
+        let expected = json!({"name": "alice", "tags": ["y"], "meta": {"age": 30, "zip": "0150"}});
+        assert_eq!(patched, expected);
+        assert!(store.patch("a", &json!(["not", "an", "object"])).is_err());
+        assert!(matches!(
+            store.patch("missing", &json!({"x": 1})),
+            Err(Error::DocumentNotFound(_))
+        ));
+        store.flush().unwrap();
+        assert_eq!(store.get("a").unwrap(), expected);
+        drop(store);
+
+        // A patch rewrites the document, so it moves to the end
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["b", "a"]);
+        assert_eq!(store.get("a").unwrap(), expected);
     }
 
-    /// Clear all cached roots (useful for testing).
-    #[doc(hidden)]
-    pub fn clear_cache() {
-        let mut cache = ROOT_CACHE.write();
-        cache.clear();
+    #[test]
+    fn test_fast_store_ttl() {
+        let tmp = TempDir::new().unwrap();
+        let hour = Duration::from_secs(3600);
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"v": 1})).unwrap();
+        store
+            .put_with_ttl("b", json!({"v": 2}), Duration::ZERO)
+            .unwrap();
+        store.put_with_ttl("c", json!({"v": 3}), hour).unwrap();
+        store.flush().unwrap();
+
+        // Expired documents are hidden from reads
+        assert!(matches!(store.get("b"), Err(Error::DocumentNotFound(_))));
+        assert!(!store.exists("b"));
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.doc_ids(), ["a", "c"]);
+        assert_eq!(store.scan().unwrap().len(), 2);
+        assert_eq!(store.export_jsonl(&mut Vec::new()).unwrap(), 2);
+        assert!(store.expires_at("c").unwrap() > SystemTime::now());
+        assert!(store.expires_at("a").is_none());
+
+        // Expiry times survive a reopen, and patches keep them
+        drop(store);
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["a", "c"]);
+        store.patch("c", &json!({"w": 4})).unwrap();
+        assert!(store.expires_at("c").is_some());
+
+        // Compaction deletes expired lines
+        assert!(store.reclaimable_bytes() > store.dead_bytes());
+        store.compact().unwrap();
+        assert_eq!(store.reclaimable_bytes(), 0);
+        let data = std::fs::read_to_string(Layout::data_file(tmp.path(), "test")).unwrap();
+        assert!(!data.contains("\"b\""));
+
+        // Writing without a TTL clears the expiry
+        store.put("c", json!({"v": 5})).unwrap();
+        assert!(store.expires_at("c").is_none());
+        store.flush().unwrap();
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["a", "c"]);
+        assert!(store.expires_at("c").is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use serde_json::json;
-    use tempfile::TempDir;
+    #[test]
+    fn test_fast_store_knn_skips_expired() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        for (id, v) in [("a", [1.0, 0.0]), ("b", [0.9, 0.1]), ("c", [0.0, 1.0])] {
+            store.put(id, json!({"id": id})).unwrap();
+            store.put_vector("emb", id, &v).unwrap();
+        }
+        let d = [1.0, 0.05];
+        store.put("d", json!({"id": "d"})).unwrap();
+        store.put_vector("emb", "d", &d).unwrap();
+        store
+            .put_with_ttl("d", json!({"id": "d"}), Duration::ZERO)
+            .unwrap();
+        assert!(store.get_vector("emb", "d").is_some());
 
-    use super::*;
+        // The expired nearest neighbor doesn't take up one of the k places
+        let hits = store.knn("emb", &d, 3, Metric::Cosine).unwrap();
+        let ids: Vec<_> = hits.iter().map(|n| n.doc_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+    }
 
     #[test]
-    fn test_fast_store_basic() {
+    fn test_fast_store_put_batch_with_policy() {
         let tmp = TempDir::new().unwrap();
         let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"v": 1})).unwrap();
+        store.put("b", json!({"v": 1})).unwrap();
+        store.delete("b").unwrap();
+        let batch = |v: i32| -> Vec<(String, Value)> {
+            ["a", "b", "c"]
+                .iter()
+                .map(|id| (id.to_string(), json!({"v": v})))
+                .collect()
+        };
 
-        store.put("doc1", json!({"name": "alice"})).unwrap();
+        // Deleted IDs don't conflict
+        let err = store
+            .put_batch_with_policy(batch(2), ConflictPolicy::FailOnConflict)
+            .unwrap_err();
+        assert!(matches!(err, Error::DocumentExists(id) if id == "a"));
+        assert_eq!(store.len(), 1);
+        let dup = vec![("x".to_string(), json!({})), ("x".to_string(), json!({}))];
+        assert!(store
+            .put_batch_with_policy(dup, ConflictPolicy::FailOnConflict)
+            .is_err());
+
+        assert_eq!(
+            store
+                .put_batch_with_policy(batch(2), ConflictPolicy::SkipExisting)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            store
+                .put_batch_with_policy(batch(3), ConflictPolicy::Overwrite)
+                .unwrap(),
+            3
+        );
         store.flush().unwrap();
+        assert_eq!(store.doc_ids(), ["a", "b", "c"]);
+        assert_eq!(store.get("a").unwrap(), json!({"v": 3}));
 
-        let doc = store.get("doc1").unwrap();
-        assert_eq!(doc["name"], "alice");
-        assert_eq!(store.len(), 1);
+        assert_eq!(
+            "skip_existing".parse::<ConflictPolicy>().unwrap(),
+            ConflictPolicy::SkipExisting
+        );
+        assert!("ignore".parse::<ConflictPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_fast_store_segments() {
+        let tmp = TempDir::new().unwrap();
+        let segment_file = |no| Layout::data_segment_file(tmp.path(), "test", no, false);
+
+        let mut store = FastStore::open(tmp.path(), "test", 10).unwrap();
+        store.set_segment_size(200);
+        for i in 0..30 {
+            store.put(format!("doc{:02}", i), json!({"n": i})).unwrap();
+        }
+        store.flush().unwrap();
+        let segments = store.segment_count();
+        assert!(segments > 2);
+        assert!(segment_file(0).exists() && segment_file(1).exists());
+        assert!(std::fs::metadata(segment_file(0)).unwrap().len() <= 200);
+
+        let ids: Vec<String> = (0..30).map(|i| format!("doc{:02}", i)).collect();
+        assert_eq!(store.doc_ids(), ids);
+        assert_eq!(store.get("doc29").unwrap(), json!({"n": 29}));
+        assert_eq!(store.scan().unwrap().len(), 30);
+        assert_eq!(store.iter().unwrap().count(), 30);
+
+        // Only the segment holding dead lines is rewritten
+        let clean = std::fs::read(segment_file(1)).unwrap();
+        store.delete("doc00").unwrap();
+        store.delete("doc01").unwrap();
+        store.put("doc01", json!({"n": 101})).unwrap();
+        store.compact().unwrap();
+        assert_eq!(store.dead_bytes(), 0);
+        assert_eq!(std::fs::read(segment_file(1)).unwrap(), clean);
+        assert!(!std::fs::read_to_string(segment_file(0))
+            .unwrap()
+            .contains("doc00"));
+        assert_eq!(store.len(), 29);
+        assert!(store.verify().unwrap().is_ok());
+        drop(store);
+
+        // The index is rebuilt across all segments
+        std::fs::remove_file(Layout::meta_dir(tmp.path(), "test").join("index.bin")).unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 10).unwrap();
+        assert_eq!(store.segment_count(), segments);
+        assert_eq!(store.len(), 29);
+        assert!(!store.exists("doc00"));
+        assert_eq!(store.get("doc01").unwrap(), json!({"n": 101}));
+        assert_eq!(store.doc_ids().last().unwrap(), "doc01");
+        assert!(store.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_fast_store_text_search() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"body": "Rust is fast"})).unwrap();
+        assert!(store.search("rust").is_err());
+        assert_eq!(store.create_text_index("body").unwrap(), 1);
+
+        store.put("b", json!({"body": "rust and python"})).unwrap();
+        store.put("c", json!({"body": "python only"})).unwrap();
+        store.delete("a").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.search("RUST").unwrap().len(), 1);
+        assert_eq!(store.search("python").unwrap().len(), 2);
+        assert!(store.search("fast").unwrap().is_empty());
+
+        store.drop_text_index("body").unwrap();
+        assert!(store.search("python").is_err());
     }
 
     #[test]
@@ -1180,7 +7092,13 @@ mod tests {
             // Cannot open non-existent collection in read mode
             let result = root.collection("nonexistent");
             assert!(result.is_err());
+
+            // Readers hold a shared lock that doesn't block the writer
+            assert!(ReadLock::readers_active(tmp.path()));
+            let writer = ZDSRoot::open_rw(tmp.path(), 100).unwrap();
+            drop(writer);
         }
+        assert!(!ReadLock::readers_active(tmp.path()));
     }
 
     #[test]
@@ -1243,4 +7161,49 @@ mod tests {
         let root2 = ZDSRoot::open_rw(tmp.path(), 100).unwrap();
         assert!(root2.list_collections().unwrap().is_empty());
     }
+
+    #[test]
+    fn test_zds_root_collection_operations() {
+        ZDSRoot::clear_cache();
+        let tmp = TempDir::new().unwrap();
+        let root = ZDSRoot::open_rw(tmp.path(), 100).unwrap();
+
+        let mut train = root.collection("train").unwrap();
+        train.put("doc1", json!({"n": 1})).unwrap();
+        train.flush().unwrap();
+
+        // Open collections can't be touched
+        assert!(matches!(
+            root.rename_collection("train", "old"),
+            Err(Error::WriteLock(_))
+        ));
+        drop(train);
+
+        root.copy_collection("train", "backup").unwrap();
+        root.rename_collection("train", "old").unwrap();
+        assert_eq!(root.list_collections().unwrap(), ["backup", "old"]);
+        assert_eq!(
+            root.collection("backup").unwrap().get("doc1").unwrap(),
+            json!({"n": 1})
+        );
+        assert_eq!(root.collection("old").unwrap().len(), 1);
+
+        assert!(root.copy_collection("old", "backup").is_err());
+        assert!(root.rename_collection("old", "../escape").is_err());
+        assert!(matches!(
+            root.drop_collection("missing"),
+            Err(Error::CollectionNotFound(_))
+        ));
+        root.drop_collection("old").unwrap();
+        assert_eq!(root.list_collections().unwrap(), ["backup"]);
+
+        // Read-only roots can't change collections
+        drop(root);
+        ZDSRoot::clear_cache();
+        let reader = ZDSRoot::open_readonly(tmp.path(), 100).unwrap();
+        assert!(matches!(
+            reader.drop_collection("backup"),
+            Err(Error::ReadOnly(_))
+        ));
+    }
 }
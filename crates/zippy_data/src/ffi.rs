@@ -1,20 +1,31 @@
 //! C FFI for libzippy_data.
 //!
-//! Provides a stable C ABI for bindings (Python, Node, DuckDB).
+//! Provides a stable C ABI for bindings (Python, Node, DuckDB). Reads go
+//! through `zds_open` engine handles and writes through `zds_open_rw`
+//! fast store handles.
+//!
+//! Every call resets the calling thread's last error, and a failing call
+//! records its code and message there: after a null or non-`Ok` return,
+//! `zds_last_error_code` and `zds_last_error_message` say what went wrong.
 
 use std::{
+    cell::RefCell,
     ffi::{c_char, CStr, CString},
     ptr,
 };
 
-use crate::{Engine, Error};
+use crate::{Engine, Error, FastStore, Predicate};
 
 /// Opaque handle to a ZDS engine.
 pub struct ZdsEngine(Engine);
 
+/// Opaque handle to a read-write fast store.
+pub struct ZdsWriter(FastStore);
+
 /// Error codes returned by FFI functions.
 #[repr(C)]
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ZdsError {
     Ok = 0,
     InvalidPath = 1,
@@ -23,6 +34,9 @@ pub enum ZdsError {
     IoError = 4,
     JsonError = 5,
     SchemaMismatch = 6,
+    InvalidArgument = 7,
+    ReadOnly = 8,
+    WriteLocked = 9,
     Unknown = 99,
 }
 
@@ -35,11 +49,102 @@ impl From<&Error> for ZdsError {
             Error::Io(_) => ZdsError::IoError,
             Error::Json(_) => ZdsError::JsonError,
             Error::SchemaMismatch { .. } => ZdsError::SchemaMismatch,
+            Error::InvalidDocId(_) => ZdsError::InvalidArgument,
+            Error::ReadOnly(_) => ZdsError::ReadOnly,
+            Error::WriteLock(_) => ZdsError::WriteLocked,
             _ => ZdsError::Unknown,
         }
     }
 }
 
+thread_local! {
+    /// The calling thread's last error: code and message.
+    static LAST_ERROR: RefCell<Option<(ZdsError, CString)>> = const { RefCell::new(None) };
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+/// Record an error for `zds_last_error_*` and return its code.
+fn set_last_error(code: ZdsError, message: impl Into<String>) -> ZdsError {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((code, message)));
+    code
+}
+
+fn record(e: &Error) -> ZdsError {
+    set_last_error(ZdsError::from(e), e.to_string())
+}
+
+/// Read a null-terminated UTF-8 argument named `name`.
+unsafe fn str_arg<'a>(name: &str, s: *const c_char) -> std::result::Result<&'a str, ZdsError> {
+    if s.is_null() {
+        return Err(set_last_error(
+            ZdsError::InvalidArgument,
+            format!("{} is null", name),
+        ));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| {
+        set_last_error(
+            ZdsError::InvalidArgument,
+            format!("{} is not valid UTF-8", name),
+        )
+    })
+}
+
+/// Check that a handle argument is non-null.
+fn handle_arg<T>(name: &str, handle: *const T) -> std::result::Result<(), ZdsError> {
+    if handle.is_null() {
+        return Err(set_last_error(
+            ZdsError::InvalidArgument,
+            format!("{} is null", name),
+        ));
+    }
+    Ok(())
+}
+
+fn error_code(result: crate::Result<()>) -> ZdsError {
+    match result {
+        Ok(()) => ZdsError::Ok,
+        Err(e) => record(&e),
+    }
+}
+
+/// Serialize a document into a newly allocated C string.
+fn doc_to_c(doc: &serde_json::Value) -> *mut c_char {
+    let json = serde_json::to_string(doc).unwrap_or_default();
+    CString::new(json)
+        .map(|s| s.into_raw())
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Code of the calling thread's last error, or `Ok` if the last call
+/// succeeded.
+#[no_mangle]
+pub extern "C" fn zds_last_error_code() -> ZdsError {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ZdsError::Ok, |(code, _)| *code)
+    })
+}
+
+/// Message of the calling thread's last error, or null if the last call
+/// succeeded.
+///
+/// # Safety
+/// - The string is owned by the library: do not free it
+/// - It stays valid until the next ZDS call on the same thread
+#[no_mangle]
+pub extern "C" fn zds_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |(_, message)| message.as_ptr())
+    })
+}
+
 /// Open a ZDS container and collection.
 ///
 /// # Safety
@@ -51,23 +156,18 @@ pub unsafe extern "C" fn zds_open(
     path: *const c_char,
     collection: *const c_char,
 ) -> *mut ZdsEngine {
-    if path.is_null() || collection.is_null() {
+    clear_last_error();
+    let (Ok(path), Ok(collection)) = (str_arg("path", path), str_arg("collection", collection))
+    else {
         return ptr::null_mut();
-    }
-
-    let path = match CStr::from_ptr(path).to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
-    };
-
-    let collection = match CStr::from_ptr(collection).to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
     };
 
     match Engine::open(path, collection) {
         Ok(engine) => Box::into_raw(Box::new(ZdsEngine(engine))),
-        Err(_) => ptr::null_mut(),
+        Err(e) => {
+            record(&e);
+            ptr::null_mut()
+        }
     }
 }
 
@@ -78,6 +178,7 @@ pub unsafe extern "C" fn zds_open(
 /// - `engine` must not be used after this call
 #[no_mangle]
 pub unsafe extern "C" fn zds_close(engine: *mut ZdsEngine) {
+    clear_last_error();
     if !engine.is_null() {
         drop(Box::from_raw(engine));
     }
@@ -89,7 +190,8 @@ pub unsafe extern "C" fn zds_close(engine: *mut ZdsEngine) {
 /// - `engine` must be a valid pointer returned by `zds_open`
 #[no_mangle]
 pub unsafe extern "C" fn zds_count(engine: *const ZdsEngine) -> usize {
-    if engine.is_null() {
+    clear_last_error();
+    if handle_arg("engine", engine).is_err() {
         return 0;
     }
     (*engine).0.len()
@@ -104,23 +206,20 @@ pub unsafe extern "C" fn zds_count(engine: *const ZdsEngine) -> usize {
 /// - Returns null on error
 #[no_mangle]
 pub unsafe extern "C" fn zds_get(engine: *const ZdsEngine, doc_id: *const c_char) -> *mut c_char {
-    if engine.is_null() || doc_id.is_null() {
+    clear_last_error();
+    if handle_arg("engine", engine).is_err() {
         return ptr::null_mut();
     }
-
-    let doc_id = match CStr::from_ptr(doc_id).to_str() {
-        Ok(s) => s,
-        Err(_) => return ptr::null_mut(),
+    let Ok(doc_id) = str_arg("doc_id", doc_id) else {
+        return ptr::null_mut();
     };
 
     match (*engine).0.get_document(doc_id) {
-        Ok(doc) => {
-            let json = serde_json::to_string(&doc).unwrap_or_default();
-            CString::new(json)
-                .map(|s| s.into_raw())
-                .unwrap_or(ptr::null_mut())
+        Ok(doc) => doc_to_c(&doc),
+        Err(e) => {
+            record(&e);
+            ptr::null_mut()
         }
-        Err(_) => ptr::null_mut(),
     }
 }
 
@@ -132,18 +231,17 @@ pub unsafe extern "C" fn zds_get(engine: *const ZdsEngine, doc_id: *const c_char
 /// - Returns null on error
 #[no_mangle]
 pub unsafe extern "C" fn zds_get_at(engine: *const ZdsEngine, index: usize) -> *mut c_char {
-    if engine.is_null() {
+    clear_last_error();
+    if handle_arg("engine", engine).is_err() {
         return ptr::null_mut();
     }
 
     match (*engine).0.get_document_at(index) {
-        Ok(doc) => {
-            let json = serde_json::to_string(&doc).unwrap_or_default();
-            CString::new(json)
-                .map(|s| s.into_raw())
-                .unwrap_or(ptr::null_mut())
+        Ok(doc) => doc_to_c(&doc),
+        Err(e) => {
+            record(&e);
+            ptr::null_mut()
         }
-        Err(_) => ptr::null_mut(),
     }
 }
 
@@ -165,7 +263,8 @@ pub unsafe extern "C" fn zds_free_string(s: *mut c_char) {
 /// - Returns a newly allocated JSON array string (caller must free with `zds_free_string`)
 #[no_mangle]
 pub unsafe extern "C" fn zds_doc_ids(engine: *const ZdsEngine) -> *mut c_char {
-    if engine.is_null() {
+    clear_last_error();
+    if handle_arg("engine", engine).is_err() {
         return ptr::null_mut();
     }
 
@@ -186,13 +285,90 @@ pub struct ZdsScanner(crate::Scanner);
 /// - Returns null on error
 #[no_mangle]
 pub unsafe extern "C" fn zds_scan(engine: *const ZdsEngine) -> *mut ZdsScanner {
-    if engine.is_null() {
+    clear_last_error();
+    if handle_arg("engine", engine).is_err() {
         return ptr::null_mut();
     }
 
     match (*engine).0.scan(None, None) {
         Ok(scanner) => Box::into_raw(Box::new(ZdsScanner(scanner))),
-        Err(_) => ptr::null_mut(),
+        Err(e) => {
+            record(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Create a scanner with a projection and a filter.
+///
+/// `fields_json` is a JSON array of field names (dot notation for nested
+/// fields) and `predicate_json` a filter descriptor as accepted by
+/// [`Predicate::from_json`], e.g. `{"age": {"$gt": 30}}`. Either may be
+/// null to read every field or every document.
+///
+/// # Safety
+/// - `engine` must be a valid pointer returned by `zds_open`
+/// - `fields_json` and `predicate_json` must be null or valid
+///   null-terminated C strings
+/// - Returns null on error, including malformed descriptors
+#[no_mangle]
+pub unsafe extern "C" fn zds_scan_ex(
+    engine: *const ZdsEngine,
+    fields_json: *const c_char,
+    predicate_json: *const c_char,
+) -> *mut ZdsScanner {
+    clear_last_error();
+    if handle_arg("engine", engine).is_err() {
+        return ptr::null_mut();
+    }
+
+    let fields: Option<Vec<String>> = if fields_json.is_null() {
+        None
+    } else {
+        let Ok(json) = str_arg("fields_json", fields_json) else {
+            return ptr::null_mut();
+        };
+        match serde_json::from_str(json) {
+            Ok(fields) => Some(fields),
+            Err(e) => {
+                set_last_error(
+                    ZdsError::InvalidArgument,
+                    format!("fields_json must be an array of field names: {}", e),
+                );
+                return ptr::null_mut();
+            }
+        }
+    };
+    let predicate = if predicate_json.is_null() {
+        None
+    } else {
+        let Ok(json) = str_arg("predicate_json", predicate_json) else {
+            return ptr::null_mut();
+        };
+        match serde_json::from_str(json)
+            .map_err(Error::from)
+            .and_then(|filter| Predicate::from_json(&filter))
+        {
+            Ok(predicate) => Some(predicate),
+            Err(e) => {
+                set_last_error(
+                    ZdsError::InvalidArgument,
+                    format!("invalid predicate_json: {}", e),
+                );
+                return ptr::null_mut();
+            }
+        }
+    };
+
+    let fields: Option<Vec<&str>> = fields
+        .as_ref()
+        .map(|fields| fields.iter().map(String::as_str).collect());
+    match (*engine).0.scan(predicate.as_ref(), fields.as_deref()) {
+        Ok(scanner) => Box::into_raw(Box::new(ZdsScanner(scanner))),
+        Err(e) => {
+            record(&e);
+            ptr::null_mut()
+        }
     }
 }
 
@@ -201,22 +377,108 @@ pub unsafe extern "C" fn zds_scan(engine: *const ZdsEngine) -> *mut ZdsScanner {
 /// # Safety
 /// - `scanner` must be a valid pointer returned by `zds_scan`
 /// - Returns a newly allocated JSON string (caller must free with `zds_free_string`)
-/// - Returns null when no more documents
+/// - Returns null when no more documents, or on error (with the last
+///   error set)
 #[no_mangle]
 pub unsafe extern "C" fn zds_scan_next(scanner: *mut ZdsScanner) -> *mut c_char {
-    if scanner.is_null() {
+    clear_last_error();
+    if handle_arg("scanner", scanner).is_err() {
         return ptr::null_mut();
     }
 
     match (*scanner).0.next_doc() {
-        Ok(Some(doc)) => {
-            let json = serde_json::to_string(&doc).unwrap_or_default();
-            CString::new(json)
-                .map(|s| s.into_raw())
-                .unwrap_or(ptr::null_mut())
-        }
+        Ok(Some(doc)) => doc_to_c(&doc),
         Ok(None) => ptr::null_mut(),
-        _ => ptr::null_mut(),
+        Err(e) => {
+            record(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// A batch of documents from `zds_scan_next_batch`.
+#[repr(C)]
+pub struct ZdsBatch {
+    /// Null-terminated JSONL buffer, one document per `\n`-terminated line
+    /// (null when the batch is empty)
+    pub data: *mut c_char,
+    /// Length of `data` in bytes, without the terminator
+    pub len: usize,
+    /// Number of documents
+    pub count: usize,
+}
+
+impl ZdsBatch {
+    fn empty() -> Self {
+        ZdsBatch {
+            data: ptr::null_mut(),
+            len: 0,
+            count: 0,
+        }
+    }
+}
+
+/// Get up to `max_n` documents from a scanner as one JSONL buffer.
+///
+/// Much cheaper than one `zds_scan_next` call per document for consumers
+/// that cross the FFI boundary often. An empty batch means the scan is
+/// done, or failed if the last error is set; a failure mid-batch returns
+/// the documents read before it and sets the last error.
+///
+/// # Safety
+/// - `scanner` must be a valid pointer returned by `zds_scan` or
+///   `zds_scan_ex`
+/// - The batch must be freed with `zds_free_batch`
+#[no_mangle]
+pub unsafe extern "C" fn zds_scan_next_batch(scanner: *mut ZdsScanner, max_n: usize) -> ZdsBatch {
+    clear_last_error();
+    if handle_arg("scanner", scanner).is_err() {
+        return ZdsBatch::empty();
+    }
+
+    let mut buf = Vec::new();
+    let mut count = 0;
+    while count < max_n {
+        match (*scanner).0.next_doc() {
+            Ok(Some(doc)) => {
+                if let Err(e) = serde_json::to_writer(&mut buf, &doc) {
+                    record(&e.into());
+                    break;
+                }
+                buf.push(b'\n');
+                count += 1;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                record(&e);
+                break;
+            }
+        }
+    }
+
+    if count == 0 {
+        return ZdsBatch::empty();
+    }
+    let len = buf.len();
+    // Serialized JSON never contains a raw NUL byte
+    match CString::new(buf) {
+        Ok(data) => ZdsBatch {
+            data: data.into_raw(),
+            len,
+            count,
+        },
+        Err(_) => ZdsBatch::empty(),
+    }
+}
+
+/// Free a batch returned by `zds_scan_next_batch`.
+///
+/// # Safety
+/// - `batch` must have been returned by `zds_scan_next_batch` and not freed
+#[no_mangle]
+pub unsafe extern "C" fn zds_free_batch(batch: ZdsBatch) {
+    if !batch.data.is_null() {
+        drop(CString::from_raw(batch.data));
     }
 }
 
@@ -226,11 +488,140 @@ pub unsafe extern "C" fn zds_scan_next(scanner: *mut ZdsScanner) -> *mut c_char
 /// - `scanner` must be a valid pointer returned by `zds_scan`
 #[no_mangle]
 pub unsafe extern "C" fn zds_scan_close(scanner: *mut ZdsScanner) {
+    clear_last_error();
     if !scanner.is_null() {
         drop(Box::from_raw(scanner));
     }
 }
 
+/// Open (or create) a collection for writing.
+///
+/// Documents are buffered and written in batches of `batch_size`; call
+/// `zds_flush` to make them durable. Takes the collection's write lock.
+///
+/// # Safety
+/// - `path` and `collection` must be valid null-terminated C strings
+/// - `error` must be null or point to writable memory; it receives the
+///   error code
+/// - Returns null on error
+#[no_mangle]
+pub unsafe extern "C" fn zds_open_rw(
+    path: *const c_char,
+    collection: *const c_char,
+    batch_size: usize,
+    error: *mut ZdsError,
+) -> *mut ZdsWriter {
+    clear_last_error();
+    let result = match (str_arg("path", path), str_arg("collection", collection)) {
+        (Ok(path), Ok(collection)) => {
+            FastStore::open(path, collection, batch_size.max(1)).map_err(|e| record(&e))
+        }
+        (Err(e), _) | (_, Err(e)) => Err(e),
+    };
+    let (writer, code) = match result {
+        Ok(store) => (Box::into_raw(Box::new(ZdsWriter(store))), ZdsError::Ok),
+        Err(code) => (ptr::null_mut(), code),
+    };
+    if !error.is_null() {
+        *error = code;
+    }
+    writer
+}
+
+/// Put a document, replacing any document with the same ID.
+///
+/// # Safety
+/// - `writer` must be a valid pointer returned by `zds_open_rw`
+/// - `doc_id` and `doc_json` must be valid null-terminated C strings;
+///   `doc_json` must be a JSON object
+#[no_mangle]
+pub unsafe extern "C" fn zds_put(
+    writer: *mut ZdsWriter,
+    doc_id: *const c_char,
+    doc_json: *const c_char,
+) -> ZdsError {
+    clear_last_error();
+    if let Err(e) = handle_arg("writer", writer) {
+        return e;
+    }
+    let (doc_id, doc_json) = match (str_arg("doc_id", doc_id), str_arg("doc_json", doc_json)) {
+        (Ok(doc_id), Ok(doc_json)) => (doc_id, doc_json),
+        (Err(e), _) | (_, Err(e)) => return e,
+    };
+    let doc = match serde_json::from_str(doc_json) {
+        Ok(doc) => doc,
+        Err(e) => return record(&e.into()),
+    };
+    error_code((*writer).0.put(doc_id, doc))
+}
+
+/// Put a document as a raw JSON line, skipping re-serialization.
+///
+/// # Safety
+/// - `writer` must be a valid pointer returned by `zds_open_rw`
+/// - `doc_id` and `line` must be valid null-terminated C strings; `line`
+///   must be a single-line JSON object whose `_id` is `doc_id`
+#[no_mangle]
+pub unsafe extern "C" fn zds_put_raw(
+    writer: *mut ZdsWriter,
+    doc_id: *const c_char,
+    line: *const c_char,
+) -> ZdsError {
+    clear_last_error();
+    if let Err(e) = handle_arg("writer", writer) {
+        return e;
+    }
+    match (str_arg("doc_id", doc_id), str_arg("line", line)) {
+        (Ok(doc_id), Ok(line)) => error_code((*writer).0.put_raw_line(doc_id, line.as_bytes())),
+        (Err(e), _) | (_, Err(e)) => e,
+    }
+}
+
+/// Delete a document.
+///
+/// # Safety
+/// - `writer` must be a valid pointer returned by `zds_open_rw`
+/// - `doc_id` must be a valid null-terminated C string
+#[no_mangle]
+pub unsafe extern "C" fn zds_delete(writer: *mut ZdsWriter, doc_id: *const c_char) -> ZdsError {
+    clear_last_error();
+    if let Err(e) = handle_arg("writer", writer) {
+        return e;
+    }
+    match str_arg("doc_id", doc_id) {
+        Ok(doc_id) => error_code((*writer).0.delete(doc_id)),
+        Err(e) => e,
+    }
+}
+
+/// Write buffered documents and the index to disk.
+///
+/// # Safety
+/// - `writer` must be a valid pointer returned by `zds_open_rw`
+#[no_mangle]
+pub unsafe extern "C" fn zds_flush(writer: *mut ZdsWriter) -> ZdsError {
+    clear_last_error();
+    if let Err(e) = handle_arg("writer", writer) {
+        return e;
+    }
+    error_code((*writer).0.flush())
+}
+
+/// Flush and close a writer handle, releasing the write lock.
+///
+/// # Safety
+/// - `writer` must be a valid pointer returned by `zds_open_rw`
+/// - `writer` must not be used after this call
+#[no_mangle]
+pub unsafe extern "C" fn zds_close_rw(writer: *mut ZdsWriter) -> ZdsError {
+    clear_last_error();
+    if writer.is_null() {
+        return ZdsError::Ok;
+    }
+    let mut writer = Box::from_raw(writer);
+    error_code(writer.0.flush())
+}
+
 /// Get ZDS library version.
 ///
 /// # Safety
@@ -277,4 +668,198 @@ mod tests {
             zds_close(engine);
         }
     }
+
+    #[test]
+    fn test_ffi_writer() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+
+        unsafe {
+            let path = CString::new(root.to_str().unwrap()).unwrap();
+            let collection = CString::new("test").unwrap();
+            let mut code = ZdsError::Unknown;
+            let writer = zds_open_rw(path.as_ptr(), collection.as_ptr(), 100, &mut code);
+            assert!(!writer.is_null());
+            assert!(matches!(code, ZdsError::Ok));
+
+            let a = CString::new("a").unwrap();
+            let b = CString::new("b").unwrap();
+            let doc = CString::new(r#"{"name": "alice"}"#).unwrap();
+            let line = CString::new(r#"{"_id":"b","name":"bob"}"#).unwrap();
+            let bad = CString::new("{not json").unwrap();
+            assert!(matches!(
+                zds_put(writer, a.as_ptr(), doc.as_ptr()),
+                ZdsError::Ok
+            ));
+            assert!(matches!(
+                zds_put(writer, a.as_ptr(), bad.as_ptr()),
+                ZdsError::JsonError
+            ));
+            assert!(matches!(
+                zds_put(writer, a.as_ptr(), ptr::null()),
+                ZdsError::InvalidArgument
+            ));
+            assert!(matches!(
+                zds_put_raw(writer, b.as_ptr(), line.as_ptr()),
+                ZdsError::Ok
+            ));
+            assert!(matches!(zds_delete(writer, a.as_ptr()), ZdsError::Ok));
+            assert!(matches!(
+                zds_delete(writer, a.as_ptr()),
+                ZdsError::DocumentNotFound
+            ));
+            assert!(matches!(zds_flush(writer), ZdsError::Ok));
+            assert!(matches!(zds_close_rw(writer), ZdsError::Ok));
+
+            let engine = zds_open(path.as_ptr(), collection.as_ptr());
+            assert_eq!(zds_count(engine), 1);
+            let json = zds_get(engine, b.as_ptr());
+            assert!(CStr::from_ptr(json).to_str().unwrap().contains("bob"));
+            zds_free_string(json);
+            zds_close(engine);
+        }
+    }
+
+    #[test]
+    fn test_ffi_last_error() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        writer.put("doc1", &json!({"name": "alice"})).unwrap();
+
+        unsafe {
+            let path = CString::new(root.to_str().unwrap()).unwrap();
+            let collection = CString::new("test").unwrap();
+            let engine = zds_open(path.as_ptr(), collection.as_ptr());
+            assert_eq!(zds_last_error_code(), ZdsError::Ok);
+            assert!(zds_last_error_message().is_null());
+
+            assert!(zds_get_at(engine, 5).is_null());
+            assert_eq!(zds_last_error_code(), ZdsError::DocumentNotFound);
+            let message = CStr::from_ptr(zds_last_error_message()).to_str().unwrap();
+            assert!(message.contains("index 5"), "{}", message);
+
+            // The next call resets it
+            assert_eq!(zds_count(engine), 1);
+            assert_eq!(zds_last_error_code(), ZdsError::Ok);
+
+            assert!(zds_get(engine, ptr::null()).is_null());
+            assert_eq!(zds_last_error_code(), ZdsError::InvalidArgument);
+            let message = CStr::from_ptr(zds_last_error_message()).to_str().unwrap();
+            assert_eq!(message, "doc_id is null");
+
+            // Errors are per thread
+            std::thread::spawn(|| assert_eq!(zds_last_error_code(), ZdsError::Ok))
+                .join()
+                .unwrap();
+            zds_close(engine);
+        }
+    }
+
+    #[test]
+    fn test_ffi_scan_ex() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        for (id, age) in [("a", 20), ("b", 35), ("c", 40)] {
+            writer
+                .put(id, &json!({"name": id, "age": age, "meta": {"x": 1}}))
+                .unwrap();
+        }
+
+        unsafe {
+            let path = CString::new(root.to_str().unwrap()).unwrap();
+            let collection = CString::new("test").unwrap();
+            let engine = zds_open(path.as_ptr(), collection.as_ptr());
+
+            let fields = CString::new(r#"["name"]"#).unwrap();
+            let predicate = CString::new(r#"{"age": {"$gt": 30}}"#).unwrap();
+            let scanner = zds_scan_ex(engine, fields.as_ptr(), predicate.as_ptr());
+            assert!(!scanner.is_null());
+            let mut docs = Vec::new();
+            loop {
+                let json = zds_scan_next(scanner);
+                if json.is_null() {
+                    break;
+                }
+                let doc: serde_json::Value =
+                    serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+                docs.push(doc);
+                zds_free_string(json);
+            }
+            assert_eq!(zds_last_error_code(), ZdsError::Ok);
+            docs.sort_by_key(|doc| doc["name"].as_str().map(String::from));
+            assert_eq!(docs, [json!({"name": "b"}), json!({"name": "c"})]);
+            zds_scan_close(scanner);
+
+            // Null descriptors read everything
+            let scanner = zds_scan_ex(engine, ptr::null(), ptr::null());
+            let mut count = 0;
+            loop {
+                let json = zds_scan_next(scanner);
+                if json.is_null() {
+                    break;
+                }
+                zds_free_string(json);
+                count += 1;
+            }
+            assert_eq!(count, 3);
+            zds_scan_close(scanner);
+
+            let bad = CString::new(r#"{"$nope": 1}"#).unwrap();
+            assert!(zds_scan_ex(engine, ptr::null(), bad.as_ptr()).is_null());
+            assert_eq!(zds_last_error_code(), ZdsError::InvalidArgument);
+            let bad = CString::new(r#"{"name": 1}"#).unwrap();
+            assert!(zds_scan_ex(engine, bad.as_ptr(), ptr::null()).is_null());
+            assert_eq!(zds_last_error_code(), ZdsError::InvalidArgument);
+
+            zds_close(engine);
+        }
+    }
+
+    #[test]
+    fn test_ffi_scan_next_batch() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        for i in 0..5 {
+            writer.put(&format!("doc{}", i), &json!({"i": i})).unwrap();
+        }
+
+        unsafe {
+            let path = CString::new(root.to_str().unwrap()).unwrap();
+            let collection = CString::new("test").unwrap();
+            let engine = zds_open(path.as_ptr(), collection.as_ptr());
+            let scanner = zds_scan(engine);
+
+            let mut counts = Vec::new();
+            let mut seen = Vec::new();
+            loop {
+                let batch = zds_scan_next_batch(scanner, 2);
+                if batch.count == 0 {
+                    assert!(batch.data.is_null());
+                    break;
+                }
+                let text = CStr::from_ptr(batch.data).to_str().unwrap();
+                assert_eq!(text.len(), batch.len);
+                for line in text.lines() {
+                    let doc: serde_json::Value = serde_json::from_str(line).unwrap();
+                    seen.push(doc["i"].as_i64().unwrap());
+                }
+                counts.push(batch.count);
+                zds_free_batch(batch);
+            }
+            assert_eq!(zds_last_error_code(), ZdsError::Ok);
+            assert_eq!(counts, [2, 2, 1]);
+            seen.sort();
+            assert_eq!(seen, [0, 1, 2, 3, 4]);
+
+            zds_scan_close(scanner);
+            zds_close(engine);
+        }
+    }
 }
@@ -0,0 +1,477 @@
+//! Secondary field indexes for fast predicate scans.
+//!
+//! A field index maps the values of one (possibly nested) field to the IDs
+//! of the documents holding them. Indexes live under
+//! `meta/indexes/<field>.jsonl` and are maintained by the writers and
+//! [`FastStore`](crate::FastStore) once created.
+//!
+//! [`FieldIndexes`] also carries the collection's full-text indexes (see
+//! [`TextIndex`]) so writers maintain both kinds together.
+//!
+//! Index lookups only narrow the set of candidate documents; scans still
+//! evaluate the full predicate against every candidate.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{encryption, Codec, Error, Layout, Predicate, Result, TextIndex};
+
+/// On-disk posting list for one field value.
+#[derive(Debug, Serialize, Deserialize)]
+struct Posting {
+    /// Canonical JSON encoding of the value
+    key: String,
+    ids: Vec<String>,
+}
+
+/// Index over the values of a single field.
+#[derive(Debug, Clone)]
+pub struct FieldIndex {
+    field: String,
+    /// Canonical value -> doc IDs holding it
+    postings: HashMap<String, BTreeSet<String>>,
+    /// Doc ID -> canonical value (reverse map for updates)
+    values: HashMap<String, String>,
+}
+
+impl FieldIndex {
+    /// Create an empty index for `field` (dot notation for nested fields).
+    pub fn new(field: impl Into<String>) -> Self {
+        FieldIndex {
+            field: field.into(),
+            postings: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    /// Build an index from `(doc_id, doc)` pairs.
+    pub fn build(
+        field: impl Into<String>,
+        docs: impl IntoIterator<Item = (String, Value)>,
+    ) -> Self {
+        let mut index = FieldIndex::new(field);
+        for (doc_id, doc) in docs {
+            index.insert(&doc_id, &doc);
+        }
+        index
+    }
+
+    /// The indexed field.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Number of indexed documents.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Check if no documents are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Number of distinct indexed values.
+    pub fn distinct_values(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Index (or re-index) a document. Documents without the field are skipped.
+    pub fn insert(&mut self, doc_id: &str, doc: &Value) {
+        self.remove(doc_id);
+        if let Some(value) = Codec::get_nested(doc, &self.field) {
+            let key = Codec::canonicalize(value);
+            self.postings
+                .entry(key.clone())
+                .or_default()
+                .insert(doc_id.to_string());
+            self.values.insert(doc_id.to_string(), key);
+        }
+    }
+
+    /// Remove a document from the index.
+    pub fn remove(&mut self, doc_id: &str) {
+        if let Some(key) = self.values.remove(doc_id) {
+            if let Some(ids) = self.postings.get_mut(&key) {
+                ids.remove(doc_id);
+                if ids.is_empty() {
+                    self.postings.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// IDs of documents whose field equals `value`.
+    pub fn lookup(&self, value: &Value) -> Vec<String> {
+        self.postings
+            .get(&Codec::canonicalize(value))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Load the index for `field` from disk.
+    pub fn load(root: &Path, collection: &str, field: &str) -> Result<Self> {
+        let path = Layout::field_index_file(root, collection, field);
+        let content = encryption::read_file(root, collection, &path)?;
+
+        let mut index = FieldIndex::new(field);
+        for line in content.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let posting: Posting = serde_json::from_str(&line)?;
+            for doc_id in &posting.ids {
+                index.values.insert(doc_id.clone(), posting.key.clone());
+            }
+            index
+                .postings
+                .insert(posting.key, posting.ids.into_iter().collect());
+        }
+
+        Ok(index)
+    }
+
+    /// Save the index to disk.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        std::fs::create_dir_all(Layout::field_index_dir(root, collection))?;
+        let path = Layout::field_index_file(root, collection, &self.field);
+        let mut file = Vec::new();
+
+        let mut keys: Vec<_> = self.postings.keys().collect();
+        keys.sort();
+        for key in keys {
+            let posting = Posting {
+                key: key.clone(),
+                ids: self.postings[key].iter().cloned().collect(),
+            };
+            writeln!(file, "{}", serde_json::to_string(&posting)?)?;
+        }
+
+        encryption::write_file(root, collection, &path, file)
+    }
+}
+
+/// All field and text indexes of a collection.
+#[derive(Debug, Clone, Default)]
+pub struct FieldIndexes {
+    indexes: HashMap<String, FieldIndex>,
+    text: HashMap<String, TextIndex>,
+}
+
+impl FieldIndexes {
+    /// Create an empty index set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every index stored for a collection.
+    pub fn load(root: &Path, collection: &str) -> Result<Self> {
+        let mut indexes = FieldIndexes::new();
+        for field in Self::list(root, collection)? {
+            let index = FieldIndex::load(root, collection, &field)?;
+            indexes.indexes.insert(field, index);
+        }
+        for field in Self::list_text(root, collection)? {
+            let index = TextIndex::load(root, collection, &field)?;
+            indexes.text.insert(field, index);
+        }
+        Ok(indexes)
+    }
+
+    /// List the indexed fields stored for a collection.
+    pub fn list(root: &Path, collection: &str) -> Result<Vec<String>> {
+        Self::list_dir(&Layout::field_index_dir(root, collection))
+    }
+
+    /// List the text-indexed fields stored for a collection.
+    pub fn list_text(root: &Path, collection: &str) -> Result<Vec<String>> {
+        Self::list_dir(&Layout::text_index_dir(root, collection))
+    }
+
+    fn list_dir(dir: &Path) -> Result<Vec<String>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut fields = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("jsonl") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    fields.push(stem.to_string());
+                }
+            }
+        }
+        fields.sort();
+        Ok(fields)
+    }
+
+    /// Validate a field name for use as an index.
+    pub fn validate_field(field: &str) -> Result<()> {
+        if field.is_empty()
+            || field.starts_with('.')
+            || field.contains(|c: char| c == '/' || c == '\\' || c.is_control())
+        {
+            return Err(Error::Validation(format!(
+                "Invalid index field name: {:?}",
+                field
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check if no fields are indexed (by value or text).
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty() && self.text.is_empty()
+    }
+
+    /// Indexed field names.
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.indexes.keys().map(String::as_str)
+    }
+
+    /// Get the index for a field.
+    pub fn get(&self, field: &str) -> Option<&FieldIndex> {
+        self.indexes.get(field)
+    }
+
+    /// Add (or replace) an index.
+    pub fn add(&mut self, index: FieldIndex) {
+        self.indexes.insert(index.field.clone(), index);
+    }
+
+    /// Remove an index from the set (does not touch disk).
+    pub fn remove_index(&mut self, field: &str) -> Option<FieldIndex> {
+        self.indexes.remove(field)
+    }
+
+    /// Delete a stored index from disk.
+    pub fn drop_index(root: &Path, collection: &str, field: &str) -> Result<()> {
+        let path = Layout::field_index_file(root, collection, field);
+        if !path.exists() {
+            return Err(Error::Validation(format!("No index on field '{}'", field)));
+        }
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Text-indexed field names.
+    pub fn text_fields(&self) -> impl Iterator<Item = &str> {
+        self.text.keys().map(String::as_str)
+    }
+
+    /// Get the text index for a field.
+    pub fn get_text(&self, field: &str) -> Option<&TextIndex> {
+        self.text.get(field)
+    }
+
+    /// Add (or replace) a text index.
+    pub fn add_text(&mut self, index: TextIndex) {
+        self.text.insert(index.field().to_string(), index);
+    }
+
+    /// Remove a text index from the set (does not touch disk).
+    pub fn remove_text_index(&mut self, field: &str) -> Option<TextIndex> {
+        self.text.remove(field)
+    }
+
+    /// Delete a stored text index from disk.
+    pub fn drop_text_index(root: &Path, collection: &str, field: &str) -> Result<()> {
+        let path = Layout::text_index_file(root, collection, field);
+        if !path.exists() {
+            return Err(Error::Validation(format!(
+                "No text index on field '{}'",
+                field
+            )));
+        }
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Predicate matching `query` against every text-indexed field.
+    ///
+    /// Returns `None` when the collection has no text index.
+    pub fn search_predicate(&self, query: &str) -> Option<Predicate> {
+        let mut fields: Vec<&str> = self.text_fields().collect();
+        fields.sort_unstable();
+        match fields.as_slice() {
+            [] => None,
+            [field] => Some(Predicate::text_match(*field, query)),
+            _ => Some(Predicate::or(
+                fields
+                    .into_iter()
+                    .map(|field| Predicate::text_match(field, query))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Index (or re-index) a document in every index.
+    pub fn insert(&mut self, doc_id: &str, doc: &Value) {
+        for index in self.indexes.values_mut() {
+            index.insert(doc_id, doc);
+        }
+        for index in self.text.values_mut() {
+            index.insert(doc_id, doc);
+        }
+    }
+
+    /// Remove a document from every index.
+    pub fn remove(&mut self, doc_id: &str) {
+        for index in self.indexes.values_mut() {
+            index.remove(doc_id);
+        }
+        for index in self.text.values_mut() {
+            index.remove(doc_id);
+        }
+    }
+
+    /// Save every index to disk.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        for index in self.indexes.values() {
+            index.save(root, collection)?;
+        }
+        for index in self.text.values() {
+            index.save(root, collection)?;
+        }
+        Ok(())
+    }
+
+    /// Candidate doc IDs for a predicate, if the indexes can narrow it.
+    ///
+    /// Returns `None` when the predicate doesn't reference an indexed field
+    /// in a way that bounds the result (a full scan is needed).
+    pub fn candidates(&self, predicate: &Predicate) -> Option<Vec<String>> {
+        self.candidate_set(predicate)
+            .map(|ids| ids.into_iter().collect())
+    }
+
+    fn candidate_set(&self, predicate: &Predicate) -> Option<HashSet<String>> {
+        match predicate {
+            Predicate::Eq(field, value) => self
+                .indexes
+                .get(field)
+                .map(|index| index.lookup(value).into_iter().collect()),
+            Predicate::In(field, values) => self.indexes.get(field).map(|index| {
+                values
+                    .iter()
+                    .flat_map(|value| index.lookup(value))
+                    .collect()
+            }),
+            Predicate::TextMatch(field, query) => {
+                self.text.get(field).map(|index| index.search_set(query))
+            }
+            Predicate::And(preds) => preds
+                .iter()
+                .filter_map(|p| self.candidate_set(p))
+                .reduce(|acc, ids| acc.intersection(&ids).cloned().collect()),
+            Predicate::Or(preds) if !preds.is_empty() => {
+                let mut union = HashSet::new();
+                for p in preds {
+                    union.extend(self.candidate_set(p)?);
+                }
+                Some(union)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn sorted(mut ids: Vec<String>) -> Vec<String> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_field_index_updates() {
+        let mut index = FieldIndex::new("meta.cat");
+        index.insert("a", &json!({"meta": {"cat": "x"}}));
+        index.insert("b", &json!({"meta": {"cat": "x"}}));
+        index.insert("c", &json!({"other": 1}));
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.lookup(&json!("x")), vec!["a", "b"]);
+
+        index.insert("a", &json!({"meta": {"cat": "y"}}));
+        index.remove("b");
+        assert!(index.lookup(&json!("x")).is_empty());
+        assert_eq!(index.lookup(&json!("y")), vec!["a"]);
+        assert_eq!(index.distinct_values(), 1);
+    }
+
+    #[test]
+    fn test_field_indexes_candidates_and_persistence() {
+        let tmp = TempDir::new().unwrap();
+        let docs = vec![
+            ("a".to_string(), json!({"cat": "A", "n": 1})),
+            ("b".to_string(), json!({"cat": "B", "n": 1})),
+            ("c".to_string(), json!({"cat": "A", "n": 2})),
+        ];
+        FieldIndex::build("cat", docs.clone())
+            .save(tmp.path(), "test")
+            .unwrap();
+        FieldIndex::build("n", docs)
+            .save(tmp.path(), "test")
+            .unwrap();
+
+        let indexes = FieldIndexes::load(tmp.path(), "test").unwrap();
+        assert_eq!(
+            FieldIndexes::list(tmp.path(), "test").unwrap(),
+            ["cat", "n"]
+        );
+
+        let eq = Predicate::eq("cat", "A");
+        assert_eq!(sorted(indexes.candidates(&eq).unwrap()), ["a", "c"]);
+
+        let and = Predicate::and(vec![eq.clone(), Predicate::eq("n", 2)]);
+        assert_eq!(indexes.candidates(&and).unwrap(), ["c"]);
+
+        let and = Predicate::and(vec![eq.clone(), Predicate::exists("other")]);
+        assert_eq!(sorted(indexes.candidates(&and).unwrap()), ["a", "c"]);
+
+        let or = Predicate::or(vec![eq.clone(), Predicate::eq("cat", "B")]);
+        assert_eq!(indexes.candidates(&or).unwrap().len(), 3);
+
+        let is_in = Predicate::is_in("cat", vec![json!("B"), json!("C")]);
+        assert_eq!(indexes.candidates(&is_in).unwrap(), ["b"]);
+        assert!(indexes.candidates(&Predicate::gt("n", 1)).is_none());
+
+        let or = Predicate::or(vec![eq, Predicate::eq("unindexed", 1)]);
+        assert!(indexes.candidates(&or).is_none());
+
+        FieldIndexes::drop_index(tmp.path(), "test", "n").unwrap();
+        assert_eq!(FieldIndexes::list(tmp.path(), "test").unwrap(), ["cat"]);
+
+        let mut text = TextIndex::new("body");
+        text.insert("a", &json!({"body": "red fox"}));
+        text.insert("b", &json!({"body": "red panda"}));
+        text.save(tmp.path(), "test").unwrap();
+
+        let mut indexes = FieldIndexes::load(tmp.path(), "test").unwrap();
+        assert_eq!(
+            FieldIndexes::list_text(tmp.path(), "test").unwrap(),
+            ["body"]
+        );
+        let search = indexes.search_predicate("Fox").unwrap();
+        assert_eq!(indexes.candidates(&search).unwrap(), ["a"]);
+        let and = Predicate::and(vec![search, Predicate::eq("cat", "A")]);
+        assert_eq!(indexes.candidates(&and).unwrap(), ["a"]);
+
+        indexes.remove("a");
+        let search = indexes.search_predicate("fox").unwrap();
+        assert!(indexes.candidates(&search).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+//! Compact maps keyed by document ID.
+//!
+//! A `FxHashMap<String, V>` spends a 24-byte `String` and a heap allocation
+//! per document on top of the ID itself, which dominates the memory of a
+//! store's index once it holds millions of documents. [`IdMap`] keeps the
+//! IDs back to back in one arena, the values in a dense vector, and hashes
+//! `u32` slot handles that point into both.
+
+use std::{hash::BuildHasher, mem, ops::Index};
+
+use hashbrown::HashTable;
+use rustc_hash::FxBuildHasher;
+
+/// Dead arena bytes below which removed IDs aren't compacted away.
+const MIN_COMPACT_BYTES: usize = 64 * 1024;
+
+fn hash(id: &str) -> u64 {
+    FxBuildHasher.hash_one(id)
+}
+
+/// A map from document ID to `V` with the IDs stored in an arena.
+///
+/// Lookups, inserts and removals are O(1) like a hash map's. Removing an
+/// entry moves the last one into its slot, so iteration order is arbitrary
+/// and changes as the map is modified. The arena is compacted once most of
+/// it holds removed IDs; live IDs may total at most 4 GiB.
+#[derive(Debug, Clone)]
+pub(crate) struct IdMap<V> {
+    /// IDs of the slots, back to back, and of removed entries until the
+    /// next compaction
+    arena: String,
+    /// Arena offset and length of each slot's ID
+    spans: Vec<(u32, u32)>,
+    /// Value of each slot
+    values: Vec<V>,
+    /// Slot handles, hashed by their IDs
+    table: HashTable<u32>,
+    /// Arena bytes of removed IDs
+    dead: usize,
+}
+
+impl<V> Default for IdMap<V> {
+    fn default() -> Self {
+        IdMap {
+            arena: String::new(),
+            spans: Vec::new(),
+            values: Vec::new(),
+            table: HashTable::new(),
+            dead: 0,
+        }
+    }
+}
+
+/// ID of `slot`.
+fn id_at<'a>(arena: &'a str, spans: &[(u32, u32)], slot: u32) -> &'a str {
+    let (start, len) = spans[slot as usize];
+    &arena[start as usize..(start + len) as usize]
+}
+
+impl<V> IdMap<V> {
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = IdMap::default();
+    }
+
+    /// Reserve room for `additional` more entries.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let Self {
+            arena,
+            spans,
+            values,
+            table,
+            ..
+        } = self;
+        table.reserve(additional, |&slot| hash(id_at(arena, spans, slot)));
+        spans.reserve(additional);
+        values.reserve(additional);
+    }
+
+    fn slot(&self, id: &str) -> Option<usize> {
+        self.table
+            .find(hash(id), |&slot| {
+                id_at(&self.arena, &self.spans, slot) == id
+            })
+            .map(|&slot| slot as usize)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<&V> {
+        self.slot(id).map(|slot| &self.values[slot])
+    }
+
+    pub(crate) fn get_mut(&mut self, id: &str) -> Option<&mut V> {
+        self.slot(id).map(|slot| &mut self.values[slot])
+    }
+
+    pub(crate) fn contains_key(&self, id: &str) -> bool {
+        self.slot(id).is_some()
+    }
+
+    /// Insert or replace the value of `id`, returning the replaced one.
+    pub(crate) fn insert(&mut self, id: impl AsRef<str>, value: V) -> Option<V> {
+        let id = id.as_ref();
+        if let Some(slot) = self.slot(id) {
+            return Some(mem::replace(&mut self.values[slot], value));
+        }
+
+        let slot = u32::try_from(self.values.len()).expect("IdMap holds at most u32::MAX IDs");
+        if self.arena.len() + id.len() > u32::MAX as usize {
+            self.compact();
+            assert!(
+                self.arena.len() + id.len() <= u32::MAX as usize,
+                "IdMap IDs exceed 4 GiB"
+            );
+        }
+        self.spans.push((self.arena.len() as u32, id.len() as u32));
+        self.arena.push_str(id);
+        self.values.push(value);
+        let Self {
+            arena,
+            spans,
+            table,
+            ..
+        } = self;
+        table.insert_unique(hash(id), slot, |&slot| hash(id_at(arena, spans, slot)));
+        None
+    }
+
+    /// Remove `id`, returning its value.
+    pub(crate) fn remove(&mut self, id: &str) -> Option<V> {
+        let Self {
+            arena,
+            spans,
+            table,
+            ..
+        } = self;
+        let (slot, _) = table
+            .find_entry(hash(id), |&slot| id_at(arena, spans, slot) == id)
+            .ok()?
+            .remove();
+        self.dead += id.len();
+
+        // The last slot moves into the freed one
+        let last = (self.values.len() - 1) as u32;
+        if slot != last {
+            let moved = hash(id_at(&self.arena, &self.spans, last));
+            if let Some(handle) = self.table.find_mut(moved, |&s| s == last) {
+                *handle = slot;
+            }
+        }
+        self.spans.swap_remove(slot as usize);
+        let value = self.values.swap_remove(slot as usize);
+
+        if self.dead >= MIN_COMPACT_BYTES && self.dead * 2 > self.arena.len() {
+            self.compact();
+        }
+        Some(value)
+    }
+
+    /// Keep only the entries for which `f` returns `true`.
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&str, &mut V) -> bool) {
+        let mut slot = 0;
+        while slot < self.values.len() {
+            let id = id_at(&self.arena, &self.spans, slot as u32);
+            if f(id, &mut self.values[slot]) {
+                slot += 1;
+            } else {
+                // The slot now holds the entry that was last
+                let id = id.to_string();
+                self.remove(&id);
+            }
+        }
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            arena: &self.arena,
+            slots: self.spans.iter().zip(&self.values),
+        }
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub(crate) fn values(&self) -> std::slice::Iter<'_, V> {
+        self.values.iter()
+    }
+
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut V> + '_ {
+        self.values.iter_mut()
+    }
+
+    /// Bytes allocated by the map.
+    pub(crate) fn memory_bytes(&self) -> usize {
+        self.arena.capacity()
+            + self.spans.capacity() * mem::size_of::<(u32, u32)>()
+            + self.values.capacity() * mem::size_of::<V>()
+            + self.table.allocation_size()
+    }
+
+    /// Drop the IDs of removed entries from the arena.
+    fn compact(&mut self) {
+        let mut arena = String::with_capacity(self.arena.len() - self.dead);
+        for span in &mut self.spans {
+            let (start, len) = *span;
+            *span = (arena.len() as u32, len);
+            arena.push_str(&self.arena[start as usize..(start + len) as usize]);
+        }
+        self.arena = arena;
+        self.dead = 0;
+    }
+}
+
+/// Iterator over the `(id, value)` pairs of an [`IdMap`].
+pub(crate) struct Iter<'a, V> {
+    arena: &'a str,
+    slots: std::iter::Zip<std::slice::Iter<'a, (u32, u32)>, std::slice::Iter<'a, V>>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a str, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&(start, len), value) = self.slots.next()?;
+        Some((&self.arena[start as usize..(start + len) as usize], value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a IdMap<V> {
+    type Item = (&'a str, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
+
+impl<V> Index<&str> for IdMap<V> {
+    type Output = V;
+
+    fn index(&self, id: &str) -> &V {
+        self.get(id).expect("ID not in map")
+    }
+}
+
+impl<K: AsRef<str>, V> FromIterator<(K, V)> for IdMap<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = IdMap::default();
+        for (id, value) in iter {
+            map.insert(id, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_id_map() {
+        let mut map = IdMap::default();
+        let mut expected = HashMap::new();
+        for i in 0..20_000u32 {
+            let id = format!("user/{}/event-{}", i % 97, i);
+            assert_eq!(map.insert(&id, i), None);
+            expected.insert(id, i);
+        }
+        assert_eq!(map.insert("user/1/event-1", 7), Some(1));
+        expected.insert("user/1/event-1".to_string(), 7);
+
+        // Enough removals to compact the arena
+        for i in (0..20_000u32).filter(|i| i % 3 != 0) {
+            let id = format!("user/{}/event-{}", i % 97, i);
+            assert_eq!(map.remove(&id), expected.remove(&id));
+        }
+        assert_eq!(map.remove("missing"), None);
+        assert!(map.dead < map.arena.len());
+        map.retain(|id, value| {
+            *value += 1;
+            !id.ends_with('0')
+        });
+        expected.retain(|id, value| {
+            *value += 1;
+            !id.ends_with('0')
+        });
+
+        assert_eq!(map.len(), expected.len());
+        for (id, value) in &expected {
+            assert_eq!(map.get(id), Some(value), "{}", id);
+        }
+        assert!(map
+            .iter()
+            .all(|(id, value)| expected.get(id) == Some(value)));
+        assert!(!map.contains_key("user/0/event-0"));
+        assert!(map.memory_bytes() > 0);
+
+        map.clear();
+        assert!(map.is_empty() && map.get("user/0/event-3").is_none());
+    }
+}
@@ -0,0 +1,205 @@
+//! Generated document IDs.
+//!
+//! [`IdStrategy`] picks how `put_auto` names a document: a ULID (sortable
+//! by creation time), a random UUIDv4, or the next number of a per-collection
+//! sequence. The sequence is kept in `meta/id_sequence.json` and saved when
+//! the writer flushes; numbers already taken by documents are skipped, so a
+//! sequence that was not saved before a crash never reuses a live ID.
+
+use std::{path::Path, str::FromStr};
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Layout, Result};
+
+/// Crockford base32, the ULID alphabet.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// How generated document IDs are made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdStrategy {
+    /// 26-character ULID; IDs sort by creation time (to the millisecond)
+    #[default]
+    Ulid,
+    /// Random UUID (version 4), in hyphenated lowercase hex
+    Uuid,
+    /// Decimal numbers counting up from 1, per collection
+    Sequence,
+}
+
+impl IdStrategy {
+    /// Short machine-readable name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdStrategy::Ulid => "ulid",
+            IdStrategy::Uuid => "uuid",
+            IdStrategy::Sequence => "seq",
+        }
+    }
+
+    /// A new ID of a strategy that needs no state (`None` for
+    /// [`Sequence`](IdStrategy::Sequence)).
+    pub(crate) fn generate(&self) -> Option<String> {
+        match self {
+            IdStrategy::Ulid => Some(ulid()),
+            IdStrategy::Uuid => Some(uuid_v4()),
+            IdStrategy::Sequence => None,
+        }
+    }
+}
+
+impl FromStr for IdStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "ulid" => Ok(IdStrategy::Ulid),
+            "uuid" | "uuid4" => Ok(IdStrategy::Uuid),
+            "seq" | "sequence" => Ok(IdStrategy::Sequence),
+            other => Err(Error::Validation(format!(
+                "unknown ID strategy '{}' (expected ulid, uuid or seq)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for IdStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A new ULID: 48 bits of Unix milliseconds, then 80 random bits.
+pub fn ulid() -> String {
+    let millis = chrono::Utc::now().timestamp_millis().max(0) as u128;
+    let mut random = [0u8; 16];
+    OsRng.fill_bytes(&mut random[6..]);
+    let value = (millis << 80) | (u128::from_be_bytes(random) & ((1 << 80) - 1));
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// A new random UUID (version 4).
+pub fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The counter of [`IdStrategy::Sequence`] IDs.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct IdSequence {
+    /// Next number to hand out
+    next: u64,
+    /// Whether `next` moved since the last save
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl IdSequence {
+    /// Read a collection's sequence (starting at 1 if it has none).
+    pub(crate) fn load(root: &Path, collection: &str) -> Result<Self> {
+        let path = Layout::id_sequence_file(root, collection);
+        if !path.exists() {
+            return Ok(IdSequence {
+                next: 1,
+                dirty: false,
+            });
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(&path)?)?)
+    }
+
+    /// The next number whose ID `taken` doesn't claim.
+    pub(crate) fn allocate(&mut self, taken: impl Fn(&str) -> bool) -> String {
+        loop {
+            let id = self.next.to_string();
+            self.next += 1;
+            self.dirty = true;
+            if !taken(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// Save the counter if it moved.
+    pub(crate) fn save(&mut self, root: &Path, collection: &str) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let path = Layout::id_sequence_file(root, collection);
+        let tmp_file = path.with_extension("tmp");
+        std::fs::write(&tmp_file, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp_file, &path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::FastStore;
+
+    #[test]
+    fn test_generated_ids() {
+        let a = ulid();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let b = ulid();
+        assert_eq!(a.len(), 26);
+        assert!(a < b);
+        assert!(a.bytes().all(|c| CROCKFORD.contains(&c)));
+
+        let uuid = uuid_v4();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!("89ab".contains(&uuid[19..20]));
+        assert_ne!(uuid, uuid_v4());
+
+        assert_eq!("SEQ".parse::<IdStrategy>().unwrap(), IdStrategy::Sequence);
+        assert!("snowflake".parse::<IdStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_put_auto_sequence() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("2", json!({"n": 0})).unwrap();
+        let first = store
+            .put_auto(json!({"n": 1}), IdStrategy::Sequence)
+            .unwrap();
+        let second = store
+            .put_auto(json!({"n": 2}), IdStrategy::Sequence)
+            .unwrap();
+        // "2" is taken
+        assert_eq!((first.as_str(), second.as_str()), ("1", "3"));
+        let ulid = store.put_auto(json!({"n": 3}), IdStrategy::Ulid).unwrap();
+        store.flush().unwrap();
+        assert_eq!(store.get(&ulid).unwrap()["n"], 3);
+        drop(store);
+
+        // The sequence continues after a reopen
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        let next = store
+            .put_auto(json!({"n": 4}), IdStrategy::Sequence)
+            .unwrap();
+        assert_eq!(next, "4");
+        assert_eq!(store.len(), 5);
+    }
+}
@@ -1,10 +1,11 @@
 //! Document index and ordering.
+//!
+//! The index is saved as `doc_index.jsonl` and `order.ids`. Writers that
+//! commit a few documents at a time append their changes to
+//! `doc_index.delta.jsonl` instead of rewriting both files, and fold the
+//! delta back in once it grows as large as the index itself.
 
-use std::{
-    collections::HashMap,
-    io::{BufRead, BufReader, Write},
-    path::Path,
-};
+use std::{collections::HashMap, io::Write, path::Path};
 
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +20,17 @@ pub struct DocIndexEntry {
     pub mtime: u64,
 }
 
+/// A change to the index, as appended to `doc_index.delta.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum IndexChange {
+    Put(DocIndexEntry),
+    Remove { doc_id: String },
+}
+
+/// Smallest delta that is compacted, however small the index.
+const DELTA_CHECKPOINT_MIN: usize = 1024;
+
 /// Index registry for a collection.
 #[derive(Debug, Clone)]
 pub struct IndexRegistry {
@@ -26,6 +38,8 @@ pub struct IndexRegistry {
     doc_index: HashMap<String, DocIndexEntry>,
     /// Ordered list of document IDs (stable iteration order)
     order: Vec<String>,
+    /// Changes in the delta file on disk
+    delta_len: usize,
 }
 
 impl IndexRegistry {
@@ -34,34 +48,44 @@ impl IndexRegistry {
         IndexRegistry {
             doc_index: HashMap::new(),
             order: Vec::new(),
+            delta_len: 0,
         }
     }
 
-    /// Load index from disk.
+    /// Load index from disk, with the changes of its delta file applied.
     pub fn load(root: &Path, collection: &str) -> Result<Self> {
-        let mut registry = IndexRegistry::new();
+        let read = |path: &Path| -> Result<Option<String>> {
+            if path.exists() {
+                Ok(Some(std::fs::read_to_string(path)?))
+            } else {
+                Ok(None)
+            }
+        };
+        let mut registry = Self::parse(
+            read(&Layout::doc_index(root, collection))?.as_deref(),
+            read(&Layout::order_file(root, collection))?.as_deref(),
+        )?;
+        if let Some(delta) = read(&Layout::doc_index_delta(root, collection))? {
+            registry.apply_delta(&delta)?;
+        }
+        Ok(registry)
+    }
 
-        // Load doc_index.jsonl
-        let index_path = Layout::doc_index(root, collection);
-        if index_path.exists() {
-            let file = std::fs::File::open(&index_path)?;
-            let reader = BufReader::new(file);
+    /// Build an index from the contents of `doc_index.jsonl` and
+    /// `order.ids` (either may be missing).
+    pub fn parse(doc_index: Option<&str>, order: Option<&str>) -> Result<Self> {
+        let mut registry = IndexRegistry::new();
 
-            for line in reader.lines() {
-                let line = line?;
-                if line.trim().is_empty() {
-                    continue;
-                }
-                let entry: DocIndexEntry = serde_json::from_str(&line)?;
-                registry.doc_index.insert(entry.doc_id.clone(), entry);
+        for line in doc_index.unwrap_or_default().lines() {
+            if line.trim().is_empty() {
+                continue;
             }
+            let entry: DocIndexEntry = serde_json::from_str(line)?;
+            registry.doc_index.insert(entry.doc_id.clone(), entry);
         }
 
-        // Load order.ids
-        let order_path = Layout::order_file(root, collection);
-        if order_path.exists() {
-            let content = std::fs::read_to_string(&order_path)?;
-            for line in content.lines() {
+        if let Some(order) = order {
+            for line in order.lines() {
                 let doc_id = line.trim();
                 if !doc_id.is_empty() {
                     registry.order.push(doc_id.to_string());
@@ -75,7 +99,70 @@ impl IndexRegistry {
         Ok(registry)
     }
 
-    /// Save index to disk.
+    /// Apply the contents of `doc_index.delta.jsonl`. A torn last line,
+    /// left by a crash while appending, is ignored.
+    pub fn apply_delta(&mut self, delta: &str) -> Result<()> {
+        let mut lines = delta.lines().filter(|line| !line.trim().is_empty());
+        while let Some(line) = lines.next() {
+            let change = match serde_json::from_str(line) {
+                Ok(change) => change,
+                Err(_) if lines.clone().next().is_none() => break,
+                Err(e) => return Err(e.into()),
+            };
+            match change {
+                IndexChange::Put(entry) => self.put(entry),
+                IndexChange::Remove { doc_id } => {
+                    self.remove(&doc_id);
+                }
+            }
+            self.delta_len += 1;
+        }
+        Ok(())
+    }
+
+    /// Persist changes already applied to this index: they are appended to
+    /// the delta file, or the whole index is saved once the delta would
+    /// outgrow it.
+    pub fn save_changes(
+        &mut self,
+        root: &Path,
+        collection: &str,
+        changes: &[IndexChange],
+    ) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if self.delta_len + changes.len() > self.len().max(DELTA_CHECKPOINT_MIN) {
+            return self.checkpoint(root, collection);
+        }
+
+        let mut buf = Vec::new();
+        for change in changes {
+            serde_json::to_writer(&mut buf, change)?;
+            buf.push(b'\n');
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Layout::doc_index_delta(root, collection))?
+            .write_all(&buf)?;
+        self.delta_len += changes.len();
+        Ok(())
+    }
+
+    /// Number of changes in the delta file.
+    pub fn delta_len(&self) -> usize {
+        self.delta_len
+    }
+
+    /// Save the index in full and start a new, empty delta.
+    pub fn checkpoint(&mut self, root: &Path, collection: &str) -> Result<()> {
+        self.save(root, collection)?;
+        self.delta_len = 0;
+        Ok(())
+    }
+
+    /// Save index to disk, replacing its delta file.
     pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
         // Save doc_index.jsonl
         let index_path = Layout::doc_index(root, collection);
@@ -92,6 +179,11 @@ impl IndexRegistry {
             writeln!(file, "{}", doc_id)?;
         }
 
+        let delta_path = Layout::doc_index_delta(root, collection);
+        if delta_path.exists() {
+            std::fs::remove_file(&delta_path)?;
+        }
+
         Ok(())
     }
 
@@ -167,11 +259,9 @@ impl IndexRegistry {
             let path = entry.path();
 
             if path.extension().map(|e| e == "json").unwrap_or(false) {
-                let doc_id = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("")
-                    .to_string();
+                let doc_id = Layout::doc_id_from_stem(
+                    path.file_stem().and_then(|s| s.to_str()).unwrap_or(""),
+                );
 
                 if doc_id.is_empty() {
                     continue;
@@ -258,4 +348,51 @@ mod tests {
         let ids: Vec<_> = registry.all_doc_ids().to_vec();
         assert_eq!(ids, vec!["doc000", "doc001", "doc002", "doc003", "doc004"]);
     }
+
+    #[test]
+    fn test_index_delta() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_collection(root, "test").unwrap();
+        let entry = |doc_id: &str| DocIndexEntry {
+            doc_id: doc_id.to_string(),
+            schema_id: "schema".to_string(),
+            size: 100,
+            mtime: 0,
+        };
+
+        let mut registry = IndexRegistry::new();
+        registry.put(entry("a"));
+        registry.put(entry("b"));
+        registry.save(root, "test").unwrap();
+
+        registry.put(entry("c"));
+        registry.remove("a");
+        let changes = [
+            IndexChange::Put(entry("c")),
+            IndexChange::Remove {
+                doc_id: "a".to_string(),
+            },
+        ];
+        registry.save_changes(root, "test", &changes).unwrap();
+        assert_eq!(registry.delta_len(), 2);
+        assert!(Layout::doc_index_delta(root, "test").exists());
+
+        // A torn last line is skipped
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(Layout::doc_index_delta(root, "test"))
+            .unwrap();
+        write!(file, "{{\"op\":\"put\",\"doc_id\":\"d").unwrap();
+        let mut loaded = IndexRegistry::load(root, "test").unwrap();
+        assert_eq!(loaded.all_doc_ids(), ["b", "c"]);
+        assert_eq!(loaded.delta_len(), 2);
+
+        // A checkpoint folds the delta into the index
+        loaded.checkpoint(root, "test").unwrap();
+        assert!(!Layout::doc_index_delta(root, "test").exists());
+        let loaded = IndexRegistry::load(root, "test").unwrap();
+        assert_eq!(loaded.all_doc_ids(), ["b", "c"]);
+        assert_eq!(loaded.delta_len(), 0);
+    }
 }
@@ -0,0 +1,369 @@
+//! Schema inference over many documents.
+//!
+//! An [`InferredSchema`] is the union of the fields of a set of documents:
+//! each field gets the narrowest [`FieldType`] that holds every value seen
+//! and is marked nullable if some document lacks it or holds `null`. Nested
+//! objects and arrays keep their structure, so exporters can decide how
+//! deep to map them (Arrow flattens them to JSON text, DuckDB maps them to
+//! STRUCT and LIST types).
+//!
+//! Collections are inferred from their [`SchemaRegistry`] when it has
+//! schemas (it describes every document), and otherwise from a sample of
+//! their documents.
+
+use serde_json::Value;
+
+use crate::{Engine, Result, SchemaRegistry};
+
+/// Default number of documents sampled by [`InferredSchema::for_engine`].
+pub const DEFAULT_SAMPLE_SIZE: usize = 1000;
+
+/// Seed of the document sample, so inference is reproducible.
+const SAMPLE_SEED: u64 = 0;
+
+/// Inferred type of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+    /// Only `null` was seen
+    Null,
+    Bool,
+    Int,
+    Float,
+    Str,
+    /// Arrays, with the merged type of their elements (`Null` if all were
+    /// empty)
+    List(Box<FieldType>),
+    /// Objects, with their fields in first-seen order
+    Struct(Vec<InferredField>),
+    /// Values of conflicting types
+    Mixed,
+}
+
+impl FieldType {
+    /// Type of a single value.
+    pub fn of_value(value: &Value) -> Self {
+        let mut field_type = FieldType::Null;
+        field_type.merge_value(value);
+        field_type
+    }
+
+    /// Type of a structural schema node (see
+    /// [`SchemaRegistry::extract_schema`]).
+    pub fn of_schema(node: &Value) -> Self {
+        match node {
+            Value::String(kind) => match kind.as_str() {
+                "null" => FieldType::Null,
+                "boolean" => FieldType::Bool,
+                "integer" => FieldType::Int,
+                "number" => FieldType::Float,
+                "string" => FieldType::Str,
+                _ => FieldType::Mixed,
+            },
+            Value::Array(items) => FieldType::List(Box::new(
+                items.first().map_or(FieldType::Null, Self::of_schema),
+            )),
+            Value::Object(obj) => {
+                let mut fields: Vec<InferredField> = obj
+                    .iter()
+                    .map(|(name, node)| {
+                        let field_type = Self::of_schema(node);
+                        InferredField {
+                            name: name.clone(),
+                            nullable: field_type == FieldType::Null,
+                            field_type,
+                        }
+                    })
+                    .collect();
+                fields.sort_by(|a, b| a.name.cmp(&b.name));
+                FieldType::Struct(fields)
+            }
+            _ => FieldType::Mixed,
+        }
+    }
+
+    /// Widen this type to also hold `other`: nulls take the other type,
+    /// integers and floats become floats, lists and structs merge element-
+    /// and field-wise, and anything else is mixed.
+    pub fn merge(&mut self, other: FieldType) {
+        match (&mut *self, other) {
+            (a, b) if *a == b => {}
+            (_, FieldType::Null) => {}
+            (FieldType::Null, b) => *self = b,
+            (FieldType::Int, FieldType::Float) => *self = FieldType::Float,
+            (FieldType::Float, FieldType::Int) => {}
+            (FieldType::List(a), FieldType::List(b)) => a.merge(*b),
+            (FieldType::Struct(a), FieldType::Struct(b)) => merge_fields(a, b),
+            _ => *self = FieldType::Mixed,
+        }
+    }
+
+    fn merge_value(&mut self, value: &Value) {
+        match value {
+            Value::Null => {}
+            Value::Bool(_) => self.merge(FieldType::Bool),
+            Value::Number(n) if n.is_i64() => self.merge(FieldType::Int),
+            Value::Number(_) => self.merge(FieldType::Float),
+            Value::String(_) => self.merge(FieldType::Str),
+            Value::Array(items) => {
+                if *self == FieldType::Null {
+                    *self = FieldType::List(Box::new(FieldType::Null));
+                }
+                match self {
+                    FieldType::List(item_type) => {
+                        for item in items {
+                            item_type.merge_value(item);
+                        }
+                    }
+                    _ => *self = FieldType::Mixed,
+                }
+            }
+            Value::Object(obj) => {
+                let seen = match self {
+                    FieldType::Null => {
+                        *self = FieldType::Struct(Vec::new());
+                        false
+                    }
+                    _ => true,
+                };
+                match self {
+                    FieldType::Struct(fields) => add_object(fields, obj, seen),
+                    _ => *self = FieldType::Mixed,
+                }
+            }
+        }
+    }
+}
+
+/// A field of an [`InferredSchema`] or of a struct.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredField {
+    pub name: String,
+    pub field_type: FieldType,
+    /// Whether some document lacks the field or holds `null` in it
+    pub nullable: bool,
+}
+
+/// The union of the fields of a set of documents.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InferredSchema {
+    /// Top-level fields in first-seen order
+    pub fields: Vec<InferredField>,
+    /// Number of documents the schema was inferred from
+    pub sampled: usize,
+}
+
+impl InferredSchema {
+    /// Infer the schema of `docs`.
+    pub fn from_docs<'a>(docs: impl IntoIterator<Item = &'a Value>) -> Self {
+        let mut schema = InferredSchema::default();
+        for doc in docs {
+            schema.add(doc);
+        }
+        schema
+    }
+
+    /// Widen the schema to cover one more document. Non-object documents
+    /// only count towards [`sampled`](Self::sampled).
+    pub fn add(&mut self, doc: &Value) {
+        if let Value::Object(obj) = doc {
+            add_object(&mut self.fields, obj, self.sampled > 0);
+        }
+        self.sampled += 1;
+    }
+
+    /// Infer the schema of a registry's structural schemas, with fields in
+    /// name order.
+    pub fn from_registry(registry: &SchemaRegistry) -> Self {
+        let mut schema = InferredSchema::default();
+        let mut entries: Vec<_> = registry.schemas().filter(|e| e.count > 0).collect();
+        entries.sort_by(|a, b| a.schema_id.cmp(&b.schema_id));
+        for entry in entries {
+            if let FieldType::Struct(fields) = FieldType::of_schema(&entry.schema) {
+                if schema.sampled > 0 {
+                    merge_fields(&mut schema.fields, fields);
+                } else {
+                    schema.fields = fields;
+                }
+            }
+            schema.sampled += entry.count as usize;
+        }
+        schema.sort();
+        schema
+    }
+
+    /// Infer the schema of a random sample of up to `sample_size` documents
+    /// of a collection.
+    pub fn sample(engine: &Engine, sample_size: usize) -> Result<Self> {
+        Ok(Self::from_docs(&engine.sample(sample_size, SAMPLE_SEED)?))
+    }
+
+    /// Infer a collection's schema from its registry, or from a sample of up
+    /// to `sample_size` documents when the registry is empty (fast store
+    /// collections don't record schemas).
+    pub fn for_engine(engine: &Engine, sample_size: usize) -> Result<Self> {
+        let schema = Self::from_registry(engine.schema_registry());
+        if schema.sampled > 0 {
+            return Ok(schema);
+        }
+        Self::sample(engine, sample_size)
+    }
+
+    /// Sort the fields, nested ones included, by name.
+    pub fn sort(&mut self) {
+        sort_fields(&mut self.fields);
+    }
+
+    /// Look up a field by dot-notation path (`meta.lang`).
+    pub fn field(&self, path: &str) -> Option<&InferredField> {
+        let mut fields = &self.fields;
+        let mut parts = path.split('.').peekable();
+        while let Some(part) = parts.next() {
+            let field = fields.iter().find(|f| f.name == part)?;
+            if parts.peek().is_none() {
+                return Some(field);
+            }
+            match &field.field_type {
+                FieldType::Struct(children) => fields = children,
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+/// Add an object's fields to `fields`; `seen` is whether objects were
+/// added before, so fields new to this one were missing from those.
+fn add_object(fields: &mut Vec<InferredField>, obj: &serde_json::Map<String, Value>, seen: bool) {
+    for field in fields.iter_mut() {
+        if !obj.contains_key(&field.name) {
+            field.nullable = true;
+        }
+    }
+    for (name, value) in obj {
+        let pos = match fields.iter().position(|f| &f.name == name) {
+            Some(pos) => pos,
+            None => {
+                fields.push(InferredField {
+                    name: name.clone(),
+                    field_type: FieldType::Null,
+                    nullable: seen,
+                });
+                fields.len() - 1
+            }
+        };
+        let field = &mut fields[pos];
+        if value.is_null() {
+            field.nullable = true;
+        }
+        field.field_type.merge_value(value);
+    }
+}
+
+/// Merge two field lists; fields missing from either side become nullable.
+fn merge_fields(fields: &mut Vec<InferredField>, other: Vec<InferredField>) {
+    for field in fields.iter_mut() {
+        if !other.iter().any(|f| f.name == field.name) {
+            field.nullable = true;
+        }
+    }
+    for theirs in other {
+        match fields.iter_mut().find(|f| f.name == theirs.name) {
+            Some(ours) => {
+                ours.nullable |= theirs.nullable;
+                ours.field_type.merge(theirs.field_type);
+            }
+            None => fields.push(InferredField {
+                nullable: true,
+                ..theirs
+            }),
+        }
+    }
+}
+
+fn sort_fields(fields: &mut [InferredField]) {
+    fields.sort_by(|a, b| a.name.cmp(&b.name));
+    for field in fields {
+        let mut field_type = &mut field.field_type;
+        while let FieldType::List(item_type) = field_type {
+            field_type = item_type;
+        }
+        if let FieldType::Struct(children) = field_type {
+            sort_fields(children);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_infer_from_docs() {
+        let docs = [
+            json!({"text": "a", "n": null, "meta": {"lang": "en"}}),
+            json!({"text": "b", "n": 1, "tags": ["x"], "meta": {"lang": "fr", "score": 0.5}}),
+            json!({"text": "c", "n": 2.5, "tags": [], "meta": "none"}),
+        ];
+        let schema = InferredSchema::from_docs(&docs);
+        assert_eq!(schema.sampled, 3);
+        let names: Vec<&str> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["meta", "n", "text", "tags"]);
+
+        let text = schema.field("text").unwrap();
+        assert_eq!((&text.field_type, text.nullable), (&FieldType::Str, false));
+        // Null in the first document doesn't hide the type
+        let n = schema.field("n").unwrap();
+        assert_eq!((&n.field_type, n.nullable), (&FieldType::Float, true));
+        let tags = schema.field("tags").unwrap();
+        assert_eq!(tags.field_type, FieldType::List(Box::new(FieldType::Str)));
+        assert!(tags.nullable);
+        assert_eq!(schema.field("meta").unwrap().field_type, FieldType::Mixed);
+        assert!(schema.field("meta.lang").is_none());
+    }
+
+    #[test]
+    fn test_infer_nested_nullability() {
+        let docs = [
+            json!({"meta": {"lang": "en"}}),
+            json!({"meta": {"lang": "fr", "score": 1}}),
+        ];
+        let schema = InferredSchema::from_docs(&docs);
+        let meta = schema.field("meta").unwrap();
+        assert!(!meta.nullable);
+        assert!(!schema.field("meta.lang").unwrap().nullable);
+        let score = schema.field("meta.score").unwrap();
+        assert_eq!((&score.field_type, score.nullable), (&FieldType::Int, true));
+
+        assert_eq!(FieldType::of_value(&json!([[1], [2.5]])), {
+            FieldType::List(Box::new(FieldType::List(Box::new(FieldType::Float))))
+        });
+        assert_eq!(FieldType::of_value(&json!([1, "a"])), {
+            FieldType::List(Box::new(FieldType::Mixed))
+        });
+    }
+
+    #[test]
+    fn test_infer_from_registry() {
+        let mut registry = SchemaRegistry::new(false);
+        registry
+            .register(&json!({"b": 1, "a": {"y": "s"}}))
+            .unwrap();
+        registry
+            .register(&json!({"b": 1.5, "a": {"x": true, "y": "t"}}))
+            .unwrap();
+        let schema = InferredSchema::from_registry(&registry);
+        assert_eq!(schema.sampled, 2);
+        let names: Vec<&str> = schema.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(schema.field("b").unwrap().field_type, FieldType::Float);
+        assert!(!schema.field("b").unwrap().nullable);
+        assert!(schema.field("a.x").unwrap().nullable);
+        assert!(!schema.field("a.y").unwrap().nullable);
+        match &schema.field("a").unwrap().field_type {
+            FieldType::Struct(fields) => assert_eq!(fields[0].name, "x"),
+            other => panic!("unexpected type {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Finding a top-level field of a JSON line without parsing it.
+//!
+//! Index rebuilds only need each line's `_id`. Searching the bytes for
+//! `"_id":"` is fast but wrong whenever the ID isn't the first key: a nested
+//! object or a string value can contain the same bytes, and an escaped quote
+//! ends the ID early. These functions walk the object's top-level keys
+//! instead, skipping over values by matching brackets and quotes, so only
+//! the `_id` value itself is decoded.
+
+/// Value of the top-level `_id` key of a JSON object: a string, unescaped,
+/// or an integer, as written (`42` is read as `"42"`).
+///
+/// Returns `None` for anything but an object, for an `_id` of another type
+/// (including fractional numbers), and when the line is malformed before
+/// the `_id`. A repeated `_id` resolves to the first.
+pub(crate) fn top_level_id(line: &[u8]) -> Option<String> {
+    let mut pos = skip_ws(line, 0);
+    if line.get(pos) != Some(&b'{') {
+        return None;
+    }
+    pos += 1;
+    loop {
+        pos = skip_ws(line, pos);
+        if line.get(pos) != Some(&b'"') {
+            return None;
+        }
+        let key_end = string_end(line, pos)?;
+        let key = &line[pos..key_end];
+        pos = skip_ws(line, key_end);
+        if line.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_ws(line, pos + 1);
+        if is_id_key(key) {
+            return match line.get(pos)? {
+                b'"' => decode_string(&line[pos..string_end(line, pos)?]),
+                b'-' | b'0'..=b'9' => integer(&line[pos..value_end(line, pos)?]),
+                _ => None,
+            };
+        }
+        pos = skip_ws(line, value_end(line, pos)?);
+        if line.get(pos) != Some(&b',') {
+            return None;
+        }
+        pos += 1;
+    }
+}
+
+/// Whether a quoted key is `_id`, spelled plainly or with escapes.
+fn is_id_key(key: &[u8]) -> bool {
+    key == b"\"_id\"" || (key.contains(&b'\\') && decode_string(key).as_deref() == Some("_id"))
+}
+
+/// Contents of a quoted JSON string, unescaped.
+fn decode_string(quoted: &[u8]) -> Option<String> {
+    if quoted.contains(&b'\\') {
+        serde_json::from_slice(quoted).ok()
+    } else {
+        Some(String::from_utf8_lossy(&quoted[1..quoted.len() - 1]).into_owned())
+    }
+}
+
+/// A JSON integer's text, if that is what `token` is.
+fn integer(token: &[u8]) -> Option<String> {
+    // `-0` is a float to serde_json
+    let valid = match token {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] | [b'-', b'1'..=b'9', rest @ ..] => {
+            rest.iter().all(u8::is_ascii_digit)
+        }
+        _ => false,
+    };
+    valid.then(|| String::from_utf8_lossy(token).into_owned())
+}
+
+fn skip_ws(line: &[u8], mut pos: usize) -> usize {
+    while matches!(line.get(pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Position just past the string whose opening quote is at `start`.
+fn string_end(line: &[u8], start: usize) -> Option<usize> {
+    let mut pos = start + 1;
+    loop {
+        let found = pos + memchr::memchr2(b'"', b'\\', &line[pos..])?;
+        if line[found] == b'"' {
+            return Some(found + 1);
+        }
+        // Skip the escaped byte; `\uXXXX` has no quotes or backslashes
+        pos = found + 2;
+        if pos > line.len() {
+            return None;
+        }
+    }
+}
+
+/// Position just past the value starting at `start`.
+fn value_end(line: &[u8], start: usize) -> Option<usize> {
+    match *line.get(start)? {
+        b'"' => string_end(line, start),
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut pos = start;
+            while pos < line.len() {
+                match line[pos] {
+                    b'"' => {
+                        pos = string_end(line, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+            None
+        }
+        // Numbers, booleans and null run to the next delimiter
+        _ => {
+            let len = line[start..]
+                .iter()
+                .position(|b| matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r'))
+                .unwrap_or(line.len() - start);
+            (len > 0).then_some(start + len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde_json::{json, Map, Value};
+
+    use super::*;
+
+    fn id(line: &str) -> Option<String> {
+        top_level_id(line.as_bytes())
+    }
+
+    #[test]
+    fn test_top_level_id() {
+        assert_eq!(id(r#"{"_id":"a","n":1}"#).as_deref(), Some("a"));
+        assert_eq!(id(r#" { "_id" : "a" } "#).as_deref(), Some("a"));
+        assert_eq!(id(r#"{"_id":"a\"b\\"}"#).as_deref(), Some(r#"a"b\"#));
+        assert_eq!(id(r#"{"_id":"é😀"}"#).as_deref(), Some("é😀"));
+
+        // Lookalikes before the real key are skipped
+        let line =
+            r#"{"A":{"_id":"nested"},"B":"\"_id\":\"str\"","C":[1,{"_id":"x"}],"_id":"real"}"#;
+        assert_eq!(id(line).as_deref(), Some("real"));
+        let line = r#"{"n":-1.5e3,"t":true,"z":null,"e":{},"l":[],"_id":"real"}"#;
+        assert_eq!(id(line).as_deref(), Some("real"));
+        assert_eq!(
+            id(r#"{"_id":"first","_id":"second"}"#).as_deref(),
+            Some("first")
+        );
+
+        // Integer IDs are read as written
+        assert_eq!(id(r#"{"_id":42}"#).as_deref(), Some("42"));
+        assert_eq!(id(r#"{"n":1, "_id" : -7 }"#).as_deref(), Some("-7"));
+        assert_eq!(id(r#"{"_id":0,"n":1}"#).as_deref(), Some("0"));
+        assert_eq!(id(r#"{"_id":"A\n"}"#).as_deref(), Some("A\n"));
+
+        for line in [
+            "",
+            "[]",
+            "{}",
+            r#"{"_id":1.5}"#,
+            r#"{"_id":1e3}"#,
+            r#"{"_id":007}"#,
+            r#"{"_id":-0}"#,
+            r#"{"_id":-}"#,
+            r#"{"_id":null}"#,
+            r#"{"_id":["a"]}"#,
+            r#"{"a":{"_id":"nested"}}"#,
+            r#"{"a":"unterminated,"_id":"x"}"#,
+            r#"{"a" 1,"_id":"x"}"#,
+            r#"{"_id":"x"#,
+            r#"{"a":"\"#,
+        ] {
+            assert_eq!(id(line), None, "{}", line);
+        }
+    }
+
+    /// Arbitrary JSON values, nested a few levels.
+    fn value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            Just(Value::Null),
+            any::<bool>().prop_map(Value::Bool),
+            any::<i64>().prop_map(Value::from),
+            any::<f64>()
+                .prop_filter("finite", |f| f.is_finite())
+                .prop_map(Value::from),
+            any::<String>().prop_map(Value::String),
+            Just(Value::String(r#""_id":"fake""#.to_string())),
+        ];
+        leaf.prop_recursive(3, 24, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+                prop::collection::vec((any::<String>(), inner.clone()), 0..4)
+                    .prop_map(|fields| Value::Object(fields.into_iter().collect())),
+                inner.prop_map(|v| json!({"_id": v})),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_top_level_id_matches_serde(
+            doc_id in prop_oneof![
+                any::<String>().prop_map(Value::String),
+                any::<i64>().prop_map(Value::from),
+            ],
+            fields in prop::collection::vec((any::<String>(), value()), 0..6),
+            pretty in any::<bool>(),
+        ) {
+            let expected = match &doc_id {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            let mut doc: Map<String, Value> = fields.into_iter().collect();
+            doc.insert("_id".to_string(), doc_id);
+            let doc = Value::Object(doc);
+            // Pretty output on one line: newlines only separate tokens
+            let line = if pretty {
+                serde_json::to_string_pretty(&doc).unwrap().replace('\n', " ")
+            } else {
+                serde_json::to_string(&doc).unwrap()
+            };
+            prop_assert_eq!(top_level_id(line.as_bytes()), Some(expected));
+        }
+
+        #[test]
+        fn prop_top_level_id_never_panics(line in prop::collection::vec(any::<u8>(), 0..64)) {
+            let _ = top_level_id(&line);
+        }
+    }
+}
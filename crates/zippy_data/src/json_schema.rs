@@ -0,0 +1,677 @@
+//! JSON Schema (draft 2020-12) validation of documents.
+//!
+//! A collection can carry a schema in `meta/schema.json`; writers and the
+//! fast store validate every document against it before writing. Unlike the
+//! structural [schema registry](crate::schema), a JSON Schema can express
+//! constraints on values such as ranges, enums and patterns.
+//!
+//! Supported keywords: `type`, `enum`, `const`, the numeric, length, item
+//! and property bounds, `pattern`, `required`, `properties`,
+//! `patternProperties`, `additionalProperties`, `propertyNames`,
+//! `dependentRequired`, `dependentSchemas`, `prefixItems`, `items`,
+//! `contains`, `uniqueItems`, `allOf`, `anyOf`, `oneOf`, `not`,
+//! `if`/`then`/`else` and `$ref` to a JSON pointer within the schema
+//! (`#/$defs/...`). Annotations such as `title` and `format` are ignored,
+//! as are the `unevaluated*` keywords.
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{Error, Layout, Result};
+
+/// `$ref`s followed without descending into the document before the schema
+/// is taken to recurse without end.
+const MAX_REF_DEPTH: usize = 64;
+
+/// A compiled JSON Schema.
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+    schema: Value,
+    /// Compiled `pattern` and `patternProperties` regexes by source
+    patterns: HashMap<String, Regex>,
+}
+
+/// The first way a document fails its schema.
+#[derive(Debug)]
+struct Violation {
+    /// JSON pointer to the failing value (empty for the document)
+    path: String,
+    message: String,
+}
+
+impl Violation {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        Violation {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+type Check = std::result::Result<(), Violation>;
+
+impl JsonSchema {
+    /// Compile a schema, checking its patterns and `$ref`s.
+    pub fn new(schema: Value) -> Result<Self> {
+        if !schema.is_object() && !schema.is_boolean() {
+            return Err(Error::Validation(
+                "JSON Schema must be an object or a boolean".to_string(),
+            ));
+        }
+        let mut compiled = JsonSchema {
+            schema,
+            patterns: HashMap::new(),
+        };
+        let mut patterns = HashMap::new();
+        compiled.compile(&compiled.schema, &mut patterns)?;
+        compiled.patterns = patterns;
+        Ok(compiled)
+    }
+
+    /// Load the schema attached to a collection, if any.
+    pub fn load(root: &Path, collection: &str) -> Result<Option<Self>> {
+        let path = Layout::json_schema_file(root, collection);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Self::new(serde_json::from_str(&content)?).map(Some)
+    }
+
+    /// Attach this schema to a collection.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        let path = Layout::json_schema_file(root, collection);
+        std::fs::write(&path, serde_json::to_string_pretty(&self.schema)?)?;
+        Ok(())
+    }
+
+    /// Detach the schema from a collection.
+    pub fn remove(root: &Path, collection: &str) -> Result<()> {
+        match std::fs::remove_file(Layout::json_schema_file(root, collection)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The schema document.
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// Validate a document, failing with [`Error::SchemaViolation`] on the
+    /// first value that does not match.
+    pub fn validate(&self, doc: &Value) -> Result<()> {
+        self.check(&self.schema, doc, "", 0)
+            .map_err(|v| Error::SchemaViolation(v.to_string()))
+    }
+
+    /// Compile the regexes of `schema` and check that its `$ref`s resolve.
+    fn compile(&self, schema: &Value, patterns: &mut HashMap<String, Regex>) -> Result<()> {
+        let obj = match schema {
+            Value::Object(obj) => obj,
+            Value::Array(items) => {
+                return items.iter().try_for_each(|s| self.compile(s, patterns));
+            }
+            _ => return Ok(()),
+        };
+        for (key, value) in obj {
+            match (key.as_str(), value) {
+                // Instance values, not schemas
+                ("enum" | "const" | "default" | "examples", _) => {}
+                ("pattern", Value::String(pattern)) => compile_pattern(pattern, patterns)?,
+                ("patternProperties", Value::Object(props)) => {
+                    for (pattern, s) in props {
+                        compile_pattern(pattern, patterns)?;
+                        self.compile(s, patterns)?;
+                    }
+                }
+                ("$ref", Value::String(reference)) => {
+                    if self.resolve(reference).is_none() {
+                        return Err(Error::Validation(format!(
+                            "unsupported $ref {:?}: only JSON pointers within the schema resolve",
+                            reference
+                        )));
+                    }
+                }
+                _ => self.compile(value, patterns)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve a `#`-relative JSON pointer in the root schema.
+    fn resolve(&self, reference: &str) -> Option<&Value> {
+        reference
+            .strip_prefix('#')
+            .and_then(|pointer| self.schema.pointer(pointer))
+    }
+
+    /// Check `value` at `path` against `schema`; `refs` counts the `$ref`s
+    /// followed since the last descent into the document.
+    fn check(&self, schema: &Value, value: &Value, path: &str, refs: usize) -> Check {
+        let obj = match schema {
+            Value::Bool(false) => return Err(Violation::new(path, "no value is allowed here")),
+            Value::Object(obj) => obj,
+            _ => return Ok(()),
+        };
+
+        if let Some(Value::String(reference)) = obj.get("$ref") {
+            if refs >= MAX_REF_DEPTH {
+                return Err(Violation::new(
+                    path,
+                    format!("$ref {} recurses without end", reference),
+                ));
+            }
+            if let Some(target) = self.resolve(reference) {
+                self.check(target, value, path, refs + 1)?;
+            }
+        }
+
+        if let Some(types) = obj.get("type") {
+            let names: Vec<&str> = match types {
+                Value::String(name) => vec![name],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+                return Err(Violation::new(
+                    path,
+                    format!("expected {}, got {}", names.join(" or "), type_name(value)),
+                ));
+            }
+        }
+        if let Some(Value::Array(options)) = obj.get("enum") {
+            if !options.iter().any(|option| json_eq(option, value)) {
+                return Err(Violation::new(
+                    path,
+                    format!("{} is not one of {}", value, Value::Array(options.clone())),
+                ));
+            }
+        }
+        if let Some(expected) = obj.get("const") {
+            if !json_eq(expected, value) {
+                return Err(Violation::new(
+                    path,
+                    format!("{} is not {}", value, expected),
+                ));
+            }
+        }
+
+        match value {
+            Value::Number(n) => check_number(obj, n, value, path)?,
+            Value::String(s) => self.check_string(obj, s, value, path)?,
+            Value::Array(items) => self.check_array(obj, items, path)?,
+            Value::Object(map) => self.check_object(obj, map, value, path, refs)?,
+            _ => {}
+        }
+
+        if let Some(Value::Array(schemas)) = obj.get("allOf") {
+            for s in schemas {
+                self.check(s, value, path, refs)?;
+            }
+        }
+        if let Some(Value::Array(schemas)) = obj.get("anyOf") {
+            if !schemas
+                .iter()
+                .any(|s| self.check(s, value, path, refs).is_ok())
+            {
+                return Err(Violation::new(path, "value matches no schema in anyOf"));
+            }
+        }
+        if let Some(Value::Array(schemas)) = obj.get("oneOf") {
+            let matches = schemas
+                .iter()
+                .filter(|s| self.check(s, value, path, refs).is_ok())
+                .count();
+            if matches != 1 {
+                return Err(Violation::new(
+                    path,
+                    format!("value matches {} schemas in oneOf, expected one", matches),
+                ));
+            }
+        }
+        if let Some(s) = obj.get("not") {
+            if self.check(s, value, path, refs).is_ok() {
+                return Err(Violation::new(path, "value matches the schema in not"));
+            }
+        }
+        if let Some(condition) = obj.get("if") {
+            let branch = match self.check(condition, value, path, refs) {
+                Ok(()) => obj.get("then"),
+                Err(_) => obj.get("else"),
+            };
+            if let Some(s) = branch {
+                self.check(s, value, path, refs)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_string(&self, obj: &Map<String, Value>, s: &str, value: &Value, path: &str) -> Check {
+        let len = s.chars().count() as u64;
+        if let Some(min) = obj.get("minLength").and_then(Value::as_u64) {
+            if len < min {
+                return Err(Violation::new(
+                    path,
+                    format!("string is shorter than {} characters", min),
+                ));
+            }
+        }
+        if let Some(max) = obj.get("maxLength").and_then(Value::as_u64) {
+            if len > max {
+                return Err(Violation::new(
+                    path,
+                    format!("string is longer than {} characters", max),
+                ));
+            }
+        }
+        if let Some(Value::String(pattern)) = obj.get("pattern") {
+            if !self.patterns[pattern].is_match(s) {
+                return Err(Violation::new(
+                    path,
+                    format!("{} does not match pattern {:?}", value, pattern),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_array(&self, obj: &Map<String, Value>, items: &[Value], path: &str) -> Check {
+        let len = items.len() as u64;
+        if let Some(min) = obj.get("minItems").and_then(Value::as_u64) {
+            if len < min {
+                return Err(Violation::new(
+                    path,
+                    format!("array has fewer than {} items", min),
+                ));
+            }
+        }
+        if let Some(max) = obj.get("maxItems").and_then(Value::as_u64) {
+            if len > max {
+                return Err(Violation::new(
+                    path,
+                    format!("array has more than {} items", max),
+                ));
+            }
+        }
+        if obj.get("uniqueItems") == Some(&Value::Bool(true)) {
+            for (i, a) in items.iter().enumerate() {
+                if let Some(j) = items[i + 1..].iter().position(|b| json_eq(a, b)) {
+                    return Err(Violation::new(
+                        path,
+                        format!("items {} and {} are equal", i, i + 1 + j),
+                    ));
+                }
+            }
+        }
+
+        let mut evaluated = 0;
+        if let Some(Value::Array(prefix)) = obj.get("prefixItems") {
+            for (i, (item, s)) in items.iter().zip(prefix).enumerate() {
+                self.check(s, item, &child_path(path, &i.to_string()), 0)?;
+            }
+            evaluated = prefix.len();
+        }
+        if let Some(s) = obj.get("items") {
+            for (i, item) in items.iter().enumerate().skip(evaluated) {
+                self.check(s, item, &child_path(path, &i.to_string()), 0)?;
+            }
+        }
+        if let Some(s) = obj.get("contains") {
+            let matches = items
+                .iter()
+                .filter(|item| self.check(s, item, path, 0).is_ok())
+                .count() as u64;
+            let min = obj.get("minContains").and_then(Value::as_u64).unwrap_or(1);
+            if matches < min {
+                return Err(Violation::new(
+                    path,
+                    format!("array has fewer than {} items matching contains", min),
+                ));
+            }
+            if let Some(max) = obj.get("maxContains").and_then(Value::as_u64) {
+                if matches > max {
+                    return Err(Violation::new(
+                        path,
+                        format!("array has more than {} items matching contains", max),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_object(
+        &self,
+        obj: &Map<String, Value>,
+        map: &Map<String, Value>,
+        value: &Value,
+        path: &str,
+        refs: usize,
+    ) -> Check {
+        if let Some(Value::Array(required)) = obj.get("required") {
+            if let Some(missing) = required
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|name| !map.contains_key(*name))
+            {
+                return Err(Violation::new(
+                    path,
+                    format!("missing required property {:?}", missing),
+                ));
+            }
+        }
+        let len = map.len() as u64;
+        if let Some(min) = obj.get("minProperties").and_then(Value::as_u64) {
+            if len < min {
+                return Err(Violation::new(
+                    path,
+                    format!("object has fewer than {} properties", min),
+                ));
+            }
+        }
+        if let Some(max) = obj.get("maxProperties").and_then(Value::as_u64) {
+            if len > max {
+                return Err(Violation::new(
+                    path,
+                    format!("object has more than {} properties", max),
+                ));
+            }
+        }
+        if let Some(Value::Object(dependencies)) = obj.get("dependentRequired") {
+            for (name, needs) in dependencies.iter().filter(|(k, _)| map.contains_key(*k)) {
+                let Value::Array(needs) = needs else { continue };
+                if let Some(missing) = needs
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .find(|need| !map.contains_key(*need))
+                {
+                    return Err(Violation::new(
+                        path,
+                        format!("property {:?} requires property {:?}", name, missing),
+                    ));
+                }
+            }
+        }
+        if let Some(Value::Object(dependencies)) = obj.get("dependentSchemas") {
+            for (_, s) in dependencies.iter().filter(|(k, _)| map.contains_key(*k)) {
+                self.check(s, value, path, refs)?;
+            }
+        }
+        if let Some(s) = obj.get("propertyNames") {
+            for key in map.keys() {
+                self.check(s, &Value::String(key.clone()), &child_path(path, key), 0)?;
+            }
+        }
+
+        let properties = obj.get("properties").and_then(Value::as_object);
+        let pattern_properties = obj.get("patternProperties").and_then(Value::as_object);
+        let additional = obj.get("additionalProperties");
+        for (key, v) in map {
+            let key_path = child_path(path, key);
+            let mut matched = false;
+            if let Some(s) = properties.and_then(|p| p.get(key)) {
+                matched = true;
+                self.check(s, v, &key_path, 0)?;
+            }
+            for (pattern, s) in pattern_properties.into_iter().flatten() {
+                if self.patterns[pattern].is_match(key) {
+                    matched = true;
+                    self.check(s, v, &key_path, 0)?;
+                }
+            }
+            match additional {
+                Some(Value::Bool(false)) if !matched => {
+                    return Err(Violation::new(
+                        path,
+                        format!("property {:?} is not allowed", key),
+                    ));
+                }
+                Some(s) if !matched => self.check(s, v, &key_path, 0)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_number(
+    obj: &Map<String, Value>,
+    n: &serde_json::Number,
+    value: &Value,
+    path: &str,
+) -> Check {
+    let x = n.as_f64().unwrap_or(f64::NAN);
+    let bound = |key: &str| obj.get(key).and_then(|b| Some((b.as_f64()?, b)));
+
+    if let Some((min, b)) = bound("minimum") {
+        if x < min {
+            return Err(Violation::new(
+                path,
+                format!("{} is less than the minimum of {}", value, b),
+            ));
+        }
+    }
+    if let Some((min, b)) = bound("exclusiveMinimum") {
+        if x <= min {
+            return Err(Violation::new(
+                path,
+                format!("{} is not greater than {}", value, b),
+            ));
+        }
+    }
+    if let Some((max, b)) = bound("maximum") {
+        if x > max {
+            return Err(Violation::new(
+                path,
+                format!("{} is greater than the maximum of {}", value, b),
+            ));
+        }
+    }
+    if let Some((max, b)) = bound("exclusiveMaximum") {
+        if x >= max {
+            return Err(Violation::new(
+                path,
+                format!("{} is not less than {}", value, b),
+            ));
+        }
+    }
+    if let Some((step, b)) = bound("multipleOf").filter(|(step, _)| *step > 0.0) {
+        let multiple = match (n.as_i64(), b.as_i64()) {
+            (Some(a), Some(m)) => a % m == 0,
+            _ => {
+                let q = x / step;
+                (q - q.round()).abs() <= 1e-9 * q.abs().max(1.0)
+            }
+        };
+        if !multiple {
+            return Err(Violation::new(
+                path,
+                format!("{} is not a multiple of {}", value, b),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn compile_pattern(pattern: &str, patterns: &mut HashMap<String, Regex>) -> Result<()> {
+    if !patterns.contains_key(pattern) {
+        let regex = Regex::new(pattern)
+            .map_err(|e| Error::Validation(format!("invalid pattern {:?}: {}", pattern, e)))?;
+        patterns.insert(pattern.to_string(), regex);
+    }
+    Ok(())
+}
+
+/// JSON pointer to the member `key` of the value at `path`.
+fn child_path(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        // Integers are numbers without a fractional part, so 1.0 counts
+        "integer" => match value {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// JSON equality, where numbers compare by value (`1` equals `1.0`).
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x == y,
+            _ => x.as_f64() == y.as_f64(),
+        },
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(a, b)| json_eq(a, b))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, a)| y.get(k).is_some_and(|b| json_eq(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn error(schema: &JsonSchema, doc: Value) -> String {
+        match schema.validate(&doc) {
+            Err(Error::SchemaViolation(message)) => message,
+            other => panic!("expected a violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_schema_validate() {
+        let schema = JsonSchema::new(json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1, "pattern": "^[a-z]+$"},
+                "age": {"type": "integer", "minimum": 0, "maximum": 150},
+                "label": {"enum": ["pos", "neg"]},
+                "tags": {"type": "array", "items": {"$ref": "#/$defs/tag"}, "uniqueItems": true}
+            },
+            "additionalProperties": false,
+            "$defs": {"tag": {"type": "string", "maxLength": 8}}
+        }))
+        .unwrap();
+
+        schema
+            .validate(&json!({"name": "alice", "age": 30.0, "label": "pos", "tags": ["a", "b"]}))
+            .unwrap();
+        assert_eq!(
+            error(&schema, json!({"name": "alice"})),
+            "missing required property \"age\""
+        );
+        assert_eq!(
+            error(&schema, json!({"name": "alice", "age": -1})),
+            "/age: -1 is less than the minimum of 0"
+        );
+        assert_eq!(
+            error(&schema, json!({"name": "alice", "age": 1.5})),
+            "/age: expected integer, got number"
+        );
+        assert_eq!(
+            error(&schema, json!({"name": "Alice", "age": 1})),
+            "/name: \"Alice\" does not match pattern \"^[a-z]+$\""
+        );
+        assert_eq!(
+            error(&schema, json!({"name": "a", "age": 1, "label": "x"})),
+            "/label: \"x\" is not one of [\"pos\",\"neg\"]"
+        );
+        assert_eq!(
+            error(
+                &schema,
+                json!({"name": "a", "age": 1, "tags": ["toolongtag"]})
+            ),
+            "/tags/0: string is longer than 8 characters"
+        );
+        assert_eq!(
+            error(&schema, json!({"name": "a", "age": 1, "tags": ["x", "x"]})),
+            "/tags: items 0 and 1 are equal"
+        );
+        assert_eq!(
+            error(&schema, json!({"name": "a", "age": 1, "extra": true})),
+            "property \"extra\" is not allowed"
+        );
+    }
+
+    #[test]
+    fn test_json_schema_applicators() {
+        let schema = JsonSchema::new(json!({
+            "oneOf": [
+                {"properties": {"kind": {"const": "text"}}, "required": ["text"]},
+                {"properties": {"kind": {"const": "image"}}, "required": ["url"]}
+            ],
+            "if": {"properties": {"kind": {"const": "image"}}},
+            "then": {"properties": {"url": {"type": "string", "pattern": "^https://"}}}
+        }))
+        .unwrap();
+
+        schema
+            .validate(&json!({"kind": "text", "text": "hi"}))
+            .unwrap();
+        schema
+            .validate(&json!({"kind": "image", "url": "https://x"}))
+            .unwrap();
+        assert!(schema
+            .validate(&json!({"kind": "image", "url": "ftp://x"}))
+            .is_err());
+        assert_eq!(
+            error(&schema, json!({"kind": "text"})),
+            "value matches 0 schemas in oneOf, expected one"
+        );
+
+        assert!(JsonSchema::new(json!({"pattern": "("})).is_err());
+        assert!(JsonSchema::new(json!({"$ref": "other.json"})).is_err());
+        assert!(JsonSchema::new(json!(3)).is_err());
+
+        // A cycle of $refs that never descends fails instead of overflowing
+        let cyclic = JsonSchema::new(json!({"$ref": "#"})).unwrap();
+        assert!(cyclic.validate(&json!({})).is_err());
+    }
+}
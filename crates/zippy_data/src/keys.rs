@@ -0,0 +1,95 @@
+//! Sorted document IDs for prefix scans.
+//!
+//! Hierarchical IDs such as `"user/123/event/456"` group related documents
+//! under a common prefix. [`SortedIds`] keeps a collection's IDs in order so
+//! the documents under a prefix are one range of the set, found without
+//! looking at the other IDs. ID ranges (`"a".."m"`) are read the same way.
+//! Stores build it on the first prefix or range scan and keep it up to date
+//! from then on, or when opened if the collection's
+//! [`KeyOrder`](crate::config::KeyOrder) is `Sorted`.
+
+use std::{
+    collections::BTreeSet,
+    ops::{Bound, RangeBounds},
+};
+
+/// The bounds of an ID range such as `"a".."m"`.
+pub(crate) fn id_bounds<'a>(range: &impl RangeBounds<&'a str>) -> (Bound<&'a str>, Bound<&'a str>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+/// Document IDs in sorted order.
+#[derive(Debug, Default)]
+pub(crate) struct SortedIds(BTreeSet<String>);
+
+impl SortedIds {
+    pub(crate) fn new<'a>(ids: impl Iterator<Item = &'a str>) -> Self {
+        SortedIds(ids.map(str::to_string).collect())
+    }
+
+    pub(crate) fn insert(&mut self, doc_id: &str) {
+        if !self.0.contains(doc_id) {
+            self.0.insert(doc_id.to_string());
+        }
+    }
+
+    pub(crate) fn remove(&mut self, doc_id: &str) {
+        self.0.remove(doc_id);
+    }
+
+    /// IDs starting with `prefix`, in order.
+    pub(crate) fn with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(String::as_str)
+            .take_while(move |id| id.starts_with(prefix))
+    }
+
+    /// IDs within `(start, end)`, in order.
+    pub(crate) fn range<'a>(
+        &'a self,
+        (start, end): (Bound<&str>, Bound<&str>),
+    ) -> impl Iterator<Item = &'a str> {
+        // `BTreeSet::range` panics on a reversed or empty exclusive range
+        let empty = match (start, end) {
+            (Bound::Included(s), Bound::Included(e)) => s > e,
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Excluded(e))
+            | (Bound::Excluded(s), Bound::Included(e)) => s >= e,
+            _ => false,
+        };
+        (!empty)
+            .then(|| self.0.range::<str, _>((start, end)))
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted_ids_prefix() {
+        let mut ids = SortedIds::new(["user/2/a", "user/1/b", "user/10", "user/1/a"].into_iter());
+        ids.insert("user/1/c");
+        ids.remove("user/1/b");
+        assert_eq!(
+            ids.with_prefix("user/1/").collect::<Vec<_>>(),
+            ["user/1/a", "user/1/c"]
+        );
+        assert_eq!(ids.with_prefix("user/1").count(), 3);
+        assert_eq!(ids.with_prefix("").count(), 4);
+        assert_eq!(ids.with_prefix("zzz").count(), 0);
+
+        assert_eq!(
+            ids.range(id_bounds(&("user/1/c".."user/2/z")))
+                .collect::<Vec<_>>(),
+            ["user/1/c", "user/10", "user/2/a"]
+        );
+        assert_eq!(ids.range(id_bounds(&("user/10"..))).count(), 2);
+        assert_eq!(ids.range(id_bounds(&(.."user/10"))).count(), 2);
+        assert_eq!(ids.range(id_bounds(&("user/2".."user/1"))).count(), 0);
+        assert_eq!(ids.range(id_bounds(&("user/10".."user/10"))).count(), 0);
+    }
+}
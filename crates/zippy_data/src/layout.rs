@@ -1,6 +1,9 @@
 //! ZDS directory layout and path utilities.
 
-use std::path::{Path, PathBuf};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
 
 use crate::Result;
 
@@ -15,17 +18,40 @@ impl Layout {
     // Collection subdirectories
     pub const DOCS_DIR: &'static str = "docs";
     pub const META_DIR: &'static str = "meta";
+    pub const FIELD_INDEX_DIR: &'static str = "indexes";
+    pub const TEXT_INDEX_DIR: &'static str = "text_indexes";
+    pub const VECTOR_DIR: &'static str = "vectors";
+    pub const SNAPSHOTS_DIR: &'static str = "snapshots";
+    pub const BLOBS_DIR: &'static str = "blobs";
 
     // Metadata files
     pub const SCHEMA_REGISTRY_FILE: &'static str = "schemas.jsonl";
     pub const DOC_INDEX_FILE: &'static str = "doc_index.jsonl";
+    pub const DOC_INDEX_DELTA_FILE: &'static str = "doc_index.delta.jsonl";
     pub const ORDER_FILE: &'static str = "order.ids";
     pub const JOURNAL_FILE: &'static str = "journal.log";
     pub const MANIFEST_FILE: &'static str = "manifest.json";
+    pub const DATA_FILE: &'static str = "data.jsonl";
+    pub const COMPRESSED_DATA_FILE: &'static str = "data.jsonl.zst";
+    pub const TOMBSTONE_FILE: &'static str = "tombstones.jsonl";
+    pub const INDEX_DELTA_FILE: &'static str = "index.delta";
+    pub const VERSIONS_FILE: &'static str = "versions.jsonl";
+    pub const DEDUP_FILE: &'static str = "dedup.jsonl";
+    pub const ID_SEQUENCE_FILE: &'static str = "id_sequence.json";
+    pub const MIGRATION_FILE: &'static str = "migration.json";
+    pub const JSON_SCHEMA_FILE: &'static str = "schema.json";
+    pub const COLUMN_CACHE_FILE: &'static str = "columns.json";
     pub const ROOT_MANIFEST_FILE: &'static str = "root_manifest.json";
+    pub const BLOB_DATA_FILE: &'static str = "blobs.bin";
+    pub const BLOB_INDEX_FILE: &'static str = "index.jsonl";
 
     pub const VERSION: &'static str = "0.1.0";
 
+    /// Separator of hierarchical document IDs (`"user/123/event/456"`)
+    pub const ID_SEPARATOR: char = '/';
+    /// The separator in document file names
+    const ESCAPED_SEPARATOR: &'static str = "%2F";
+
     // Path builders for root-level directories
     pub fn collections_dir(root: &Path) -> PathBuf {
         root.join(Self::COLLECTIONS_DIR)
@@ -52,9 +78,41 @@ impl Layout {
         Self::collection_dir(root, collection).join(Self::META_DIR)
     }
 
+    /// Snapshots of a fast store collection, one directory each.
+    pub fn snapshots_dir(root: &Path, collection: &str) -> PathBuf {
+        Self::collection_dir(root, collection).join(Self::SNAPSHOTS_DIR)
+    }
+
+    /// Binary attachments of fast store documents.
+    pub fn blobs_dir(root: &Path, collection: &str) -> PathBuf {
+        Self::collection_dir(root, collection).join(Self::BLOBS_DIR)
+    }
+
     // Path builders for specific files
     pub fn doc_file(root: &Path, collection: &str, doc_id: &str) -> PathBuf {
-        Self::docs_dir(root, collection).join(format!("{}.json", doc_id))
+        Self::docs_dir(root, collection).join(format!("{}.json", Self::doc_file_stem(doc_id)))
+    }
+
+    /// Temporary file a writer fills before renaming it to the document's
+    /// file.
+    pub fn doc_tmp_file(root: &Path, collection: &str, doc_id: &str) -> PathBuf {
+        Self::docs_dir(root, collection).join(format!(".{}.tmp", Self::doc_file_stem(doc_id)))
+    }
+
+    /// File name of a document without its extension: the separators of
+    /// hierarchical IDs are written as `%2F`, so all documents stay in
+    /// `docs/`.
+    pub fn doc_file_stem(doc_id: &str) -> Cow<'_, str> {
+        if doc_id.contains(Self::ID_SEPARATOR) {
+            Cow::Owned(doc_id.replace(Self::ID_SEPARATOR, Self::ESCAPED_SEPARATOR))
+        } else {
+            Cow::Borrowed(doc_id)
+        }
+    }
+
+    /// Document ID of a file name stem (see [`doc_file_stem`](Self::doc_file_stem)).
+    pub fn doc_id_from_stem(stem: &str) -> String {
+        stem.replace(Self::ESCAPED_SEPARATOR, "/")
     }
 
     pub fn schema_registry(root: &Path, collection: &str) -> PathBuf {
@@ -65,6 +123,16 @@ impl Layout {
         Self::meta_dir(root, collection).join(Self::DOC_INDEX_FILE)
     }
 
+    /// Index changes appended since `doc_index.jsonl` was last saved.
+    pub fn doc_index_delta(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::DOC_INDEX_DELTA_FILE)
+    }
+
+    /// Fast store index changes appended since `index.bin` was last saved.
+    pub fn index_delta(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::INDEX_DELTA_FILE)
+    }
+
     pub fn order_file(root: &Path, collection: &str) -> PathBuf {
         Self::meta_dir(root, collection).join(Self::ORDER_FILE)
     }
@@ -77,6 +145,144 @@ impl Layout {
         Self::meta_dir(root, collection).join(Self::MANIFEST_FILE)
     }
 
+    /// Fast store (JSONL) data file.
+    pub fn data_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::DATA_FILE)
+    }
+
+    /// Fast store data file in zstd block format.
+    pub fn compressed_data_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::COMPRESSED_DATA_FILE)
+    }
+
+    /// Fast store data segment: the data file for segment 0, then
+    /// `data.00001.jsonl`, `data.00002.jsonl`, ... (`.zst` when compressed).
+    pub fn data_segment_file(
+        root: &Path,
+        collection: &str,
+        segment: u32,
+        compressed: bool,
+    ) -> PathBuf {
+        let name = match segment {
+            0 => Self::DATA_FILE.to_string(),
+            n => format!("data.{:05}.jsonl", n),
+        };
+        let name = if compressed { name + ".zst" } else { name };
+        Self::meta_dir(root, collection).join(name)
+    }
+
+    /// Parse a data segment file name into `(segment, compressed)`.
+    pub fn parse_data_segment(name: &str) -> Option<(u32, bool)> {
+        let (name, compressed) = match name.strip_suffix(".zst") {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        if name == Self::DATA_FILE {
+            return Some((0, compressed));
+        }
+        let digits = name.strip_prefix("data.")?.strip_suffix(".jsonl")?;
+        if digits.len() != 5 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some((digits.parse().ok()?, compressed))
+    }
+
+    /// Check if a collection holds fast store data (any segment, plain or
+    /// compressed).
+    pub fn has_fast_data(root: &Path, collection: &str) -> bool {
+        std::fs::read_dir(Self::meta_dir(root, collection)).is_ok_and(|entries| {
+            entries.flatten().any(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| Self::parse_data_segment(name).is_some())
+            })
+        })
+    }
+
+    /// Check if a collection holds documents as files (`docs/*.json`).
+    pub fn has_file_docs(root: &Path, collection: &str) -> bool {
+        std::fs::read_dir(Self::docs_dir(root, collection)).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        })
+    }
+
+    /// Fast store deletion records.
+    pub fn tombstone_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::TOMBSTONE_FILE)
+    }
+
+    /// Fast store previous document versions.
+    pub fn versions_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::VERSIONS_FILE)
+    }
+
+    /// Fast store documents sharing another document's line.
+    pub fn dedup_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::DEDUP_FILE)
+    }
+
+    /// Next number of sequential generated document IDs.
+    pub fn id_sequence_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::ID_SEQUENCE_FILE)
+    }
+
+    /// Progress of an unfinished migration.
+    pub fn migration_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::MIGRATION_FILE)
+    }
+
+    /// JSON Schema attached to the collection.
+    pub fn json_schema_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::JSON_SCHEMA_FILE)
+    }
+
+    /// Columnar projection cache (see [`ColumnCache`](crate::ColumnCache)).
+    pub fn column_cache_file(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::COLUMN_CACHE_FILE)
+    }
+
+    pub fn field_index_dir(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::FIELD_INDEX_DIR)
+    }
+
+    pub fn field_index_file(root: &Path, collection: &str, field: &str) -> PathBuf {
+        Self::field_index_dir(root, collection).join(format!("{}.jsonl", field))
+    }
+
+    pub fn text_index_dir(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::TEXT_INDEX_DIR)
+    }
+
+    pub fn text_index_file(root: &Path, collection: &str, field: &str) -> PathBuf {
+        Self::text_index_dir(root, collection).join(format!("{}.jsonl", field))
+    }
+
+    pub fn vector_dir(root: &Path, collection: &str) -> PathBuf {
+        Self::meta_dir(root, collection).join(Self::VECTOR_DIR)
+    }
+
+    pub fn vector_file(root: &Path, collection: &str, name: &str) -> PathBuf {
+        Self::vector_dir(root, collection).join(format!("{}.vec", name))
+    }
+
+    /// Blob data file: `blobs.bin`, then `blobs.00001.bin`, ... as
+    /// compactions rewrite it.
+    pub fn blob_data_file(root: &Path, collection: &str, generation: u32) -> PathBuf {
+        let name = match generation {
+            0 => Self::BLOB_DATA_FILE.to_string(),
+            n => format!("blobs.{:05}.bin", n),
+        };
+        Self::blobs_dir(root, collection).join(name)
+    }
+
+    /// Append-only log of the blobs added and removed.
+    pub fn blob_index_file(root: &Path, collection: &str) -> PathBuf {
+        Self::blobs_dir(root, collection).join(Self::BLOB_INDEX_FILE)
+    }
+
     /// Validate that a path is a valid ZDS root.
     pub fn validate(root: &Path) -> Result<()> {
         if !root.exists() {
@@ -138,16 +344,18 @@ impl Layout {
     }
 
     /// Check if a document ID is valid.
+    ///
+    /// IDs may be hierarchical, with non-empty segments joined by
+    /// [`ID_SEPARATOR`](Self::ID_SEPARATOR).
     pub fn validate_doc_id(doc_id: &str) -> Result<()> {
         if doc_id.is_empty() {
             return Err(crate::Error::InvalidDocId("empty document ID".to_string()));
         }
 
-        // Only allow alphanumeric, underscore, hyphen, dot
-        if !doc_id
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
-        {
+        // Only allow alphanumeric, underscore, hyphen, dot and the separator
+        if !doc_id.chars().all(|c| {
+            c.is_alphanumeric() || c == '_' || c == '-' || c == '.' || c == Self::ID_SEPARATOR
+        }) {
             return Err(crate::Error::InvalidDocId(format!(
                 "invalid characters in document ID: {}",
                 doc_id
@@ -155,7 +363,11 @@ impl Layout {
         }
 
         // Prevent path traversal
-        if doc_id.contains("..") || doc_id.starts_with('.') {
+        if doc_id.contains("..")
+            || doc_id
+                .split(Self::ID_SEPARATOR)
+                .any(|segment| segment.is_empty() || segment.starts_with('.'))
+        {
             return Err(crate::Error::InvalidDocId(format!(
                 "potentially unsafe document ID: {}",
                 doc_id
@@ -164,6 +376,23 @@ impl Layout {
 
         Ok(())
     }
+
+    /// Check if a collection name is valid (same characters as document IDs).
+    pub fn validate_collection_name(name: &str) -> Result<()> {
+        if name.is_empty()
+            || name.contains("..")
+            || name.starts_with('.')
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            return Err(crate::Error::Validation(format!(
+                "invalid collection name: {}",
+                name
+            )));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -183,6 +412,17 @@ mod tests {
             Layout::doc_file(root, "train", "doc001"),
             PathBuf::from("/data/my_dataset/collections/train/docs/doc001.json")
         );
+        assert_eq!(
+            Layout::data_segment_file(root, "train", 0, false),
+            Layout::data_file(root, "train")
+        );
+        let segment = Layout::data_segment_file(root, "train", 12, true);
+        assert!(segment.ends_with("meta/data.00012.jsonl.zst"));
+        let name = segment.file_name().unwrap().to_str().unwrap();
+        assert_eq!(Layout::parse_data_segment(name), Some((12, true)));
+        assert_eq!(Layout::parse_data_segment("data.jsonl"), Some((0, false)));
+        assert_eq!(Layout::parse_data_segment("data.tmp"), None);
+        assert_eq!(Layout::parse_data_segment("data.1.jsonl"), None);
     }
 
     #[test]
@@ -209,5 +449,21 @@ mod tests {
         assert!(Layout::validate_doc_id("").is_err());
         assert!(Layout::validate_doc_id("../evil").is_err());
         assert!(Layout::validate_doc_id(".hidden").is_err());
+        assert!(Layout::validate_doc_id("user/123/event/456").is_ok());
+        assert!(Layout::validate_doc_id("/user").is_err());
+        assert!(Layout::validate_doc_id("user//1").is_err());
+        assert!(Layout::validate_doc_id("user/").is_err());
+        assert!(Layout::validate_doc_id("user/.hidden").is_err());
+
+        let path = Layout::doc_file(Path::new("/data"), "train", "user/1");
+        assert_eq!(
+            path,
+            PathBuf::from("/data/collections/train/docs/user%2F1.json")
+        );
+        assert_eq!(Layout::doc_id_from_stem("user%2F1"), "user/1");
+
+        assert!(Layout::validate_collection_name("train-v2").is_ok());
+        assert!(Layout::validate_collection_name("a/b").is_err());
+        assert!(Layout::validate_collection_name("..").is_err());
     }
 }
@@ -2,30 +2,94 @@
 //!
 //! High-performance, multi-language dataset storage format.
 
+pub mod aggregate;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod async_writer;
+mod blob;
 pub mod codec;
+pub mod columns;
+pub mod compression;
+pub mod config;
 pub mod container;
+mod dedup;
+pub mod docref;
+pub mod durability;
+pub mod encryption;
 pub mod engine;
 pub mod error;
 pub mod fast_writer;
 pub mod ffi;
+pub mod field_index;
+mod id_map;
+pub mod ids;
 pub mod index;
+pub mod infer;
+mod json_scan;
+pub mod json_schema;
+mod keys;
 pub mod layout;
 pub mod lock;
+pub mod manifest;
+pub mod migrate;
+mod query;
+#[cfg(feature = "remote")]
+pub mod remote;
+mod sample;
 pub mod schema;
+pub mod segment;
+pub mod sharded;
+pub mod shared;
+pub mod snapshot;
+pub mod store;
+pub mod sync;
+pub mod text_index;
 pub mod txlog;
+#[cfg(all(target_os = "linux", feature = "vectored-io"))]
+mod vectored;
+pub mod vectors;
+pub mod versions;
+pub mod watch;
 pub mod writer;
 
+pub use aggregate::{Aggregate, GroupBy};
+pub use async_writer::{AsyncWriter, AsyncWriterConfig};
 pub use codec::{Codec, Predicate};
+pub use columns::ColumnCache;
+pub use compression::Compression;
+pub use config::{CollectionConfig, KeyOrder};
 pub use container::ContainerFS;
-pub use engine::{Engine, Scanner};
+pub use docref::DocRef;
+pub use durability::Durability;
+pub use encryption::EncryptionKey;
+pub use engine::{Engine, Scanner, ScannerChunks, StorageMode};
 pub use error::{Error, Result};
-pub use fast_writer::{FastStore, OpenMode, ZDSRoot};
+pub use fast_writer::{
+    ChunkSize, CompactionPolicy, ConflictPolicy, CorruptEntry, CorruptionKind, FastScanner,
+    FastStore, FastStoreStats, OpenMode, ScanBatches, ScanChunks, VerifyReport, ZDSRoot,
+};
+pub use field_index::{FieldIndex, FieldIndexes};
+pub use ids::IdStrategy;
 pub use index::{DocIndexEntry, IndexRegistry};
+pub use infer::{FieldType, InferredField, InferredSchema};
+pub use json_schema::JsonSchema;
 pub use layout::Layout;
-pub use lock::WriteLock;
+pub use lock::{ReadLock, WriteLock};
+pub use manifest::{CollectionSummary, RootManifest};
+pub use migrate::{MigrationRecord, MigrationReport, SchemaMapping};
 pub use schema::{SchemaEntry, SchemaRegistry};
+pub use segment::MmapHints;
+pub use sharded::ShardedWriter;
+pub use shared::{SharedStore, StoreReadGuard, StoreWriteGuard};
+pub use snapshot::Snapshot;
+pub use store::{open_read_store, open_store, ReadStore, Store};
+pub use sync::{SyncOptions, SyncProgress, SyncReport};
+pub use text_index::TextIndex;
 pub use txlog::{JournalEntry, TransactionLog};
-pub use writer::{BufferedWriter, WriteConfig};
+pub use vectors::{Metric, Neighbor, VectorIndex};
+pub use versions::DocVersion;
+pub use watch::{ChangeEvent, Watcher};
+pub use writer::{BufferedWriter, RecoveryReport, WriteConfig};
 
 /// ZDS format version
 pub const ZDS_VERSION: &str = "0.1.2";
@@ -1,7 +1,13 @@
 //! File locking for ZDS stores.
 //!
 //! Provides cross-platform write locking using flock (via fs2) plus an
-//! explicit lock file for systems where flock is unreliable.
+//! explicit lock file for systems where flock is unreliable. A lock file
+//! whose flock is free but which names a live process is still honored;
+//! one left by a crashed process on this host is detected as stale and
+//! taken over.
+//!
+//! Read-only handles take a shared [`ReadLock`] on a separate file, so any
+//! number of readers coexist with the writer while remaining visible.
 
 use std::{
     fs::{File, OpenOptions},
@@ -16,6 +22,9 @@ use crate::{Error, Result};
 /// Lock file name within the ZDS metadata directory.
 const LOCK_FILE_NAME: &str = ".zds_write.lock";
 
+/// Reader lock file name within the ZDS metadata directory.
+const READ_LOCK_FILE_NAME: &str = ".zds_read.lock";
+
 /// Metadata written to the lock file for debugging.
 #[derive(Debug)]
 pub struct LockInfo {
@@ -28,13 +37,19 @@ impl LockInfo {
     fn current() -> Self {
         LockInfo {
             pid: std::process::id(),
-            hostname: hostname::get()
-                .map(|h| h.to_string_lossy().to_string())
-                .unwrap_or_else(|_| "unknown".to_string()),
+            hostname: current_hostname(),
             timestamp: chrono::Utc::now().to_rfc3339(),
         }
     }
 
+    /// Whether the holder is known to be gone: it ran on this host and its
+    /// process no longer exists (or is this one). Holders on other hosts
+    /// can't be checked and are never stale.
+    pub fn is_stale(&self) -> bool {
+        self.hostname == current_hostname()
+            && (self.pid == std::process::id() || !process_alive(self.pid))
+    }
+
     fn serialize(&self) -> String {
         format!(
             "pid={}\nhostname={}\ntimestamp={}\n",
@@ -65,6 +80,34 @@ impl LockInfo {
     }
 }
 
+fn current_hostname() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Check whether a process exists on this host.
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks for the process; EPERM means it exists but
+    // belongs to another user
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a liveness check every holder is assumed alive.
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+fn lock_dir(root: &Path) -> PathBuf {
+    root.join(".zds")
+}
+
 /// A write lock on a ZDS root directory.
 ///
 /// Uses both flock (via fs2) and an explicit lock file for maximum compatibility.
@@ -82,7 +125,7 @@ impl WriteLock {
     ///
     /// Returns an error if another process already holds the lock.
     pub fn acquire(root: &Path) -> Result<Self> {
-        let metadata_dir = root.join(".zds");
+        let metadata_dir = lock_dir(root);
         std::fs::create_dir_all(&metadata_dir)?;
 
         let lock_path = metadata_dir.join(LOCK_FILE_NAME);
@@ -98,6 +141,18 @@ impl WriteLock {
         // Try to acquire exclusive flock (non-blocking)
         match file.try_lock_exclusive() {
             Ok(()) => {
+                // The flock is free, but info left by a live holder means
+                // flock isn't reliable here (e.g. NFS); info left by a
+                // crashed process is stale and overwritten
+                if let Some(info) = Self::read_lock_info(&lock_path) {
+                    if !info.is_stale() {
+                        return Err(Error::WriteLock(format!(
+                            "ZDS store is locked by another process (pid={}, host={}, since={}); \
+                             run `zippy unlock --force` if it is gone",
+                            info.pid, info.hostname, info.timestamp
+                        )));
+                    }
+                }
                 // Got the flock - now write our metadata
                 Self::write_lock_info(&lock_path)?;
                 Ok(WriteLock { file, lock_path })
@@ -118,6 +173,49 @@ impl WriteLock {
         }
     }
 
+    /// Info about the current holder of a root's write lock, if it is
+    /// locked and the lock file is readable.
+    pub fn holder(root: &Path) -> Option<LockInfo> {
+        Self::read_lock_info(&lock_dir(root).join(LOCK_FILE_NAME))
+    }
+
+    /// Remove a write lock whose holder is gone. Returns false if the root
+    /// isn't locked.
+    ///
+    /// Fails if the holder may still be running: another process holds the
+    /// flock, or the lock file names a live process on this host or any
+    /// process on another host. `force` removes the lock anyway; a holder
+    /// that is in fact running keeps writing without a lock.
+    pub fn break_lock(root: &Path, force: bool) -> Result<bool> {
+        let lock_path = lock_dir(root).join(LOCK_FILE_NAME);
+        if !lock_path.exists() {
+            return Ok(false);
+        }
+        if !force {
+            let file = OpenOptions::new().read(true).write(true).open(&lock_path)?;
+            let held = file.try_lock_exclusive().is_err();
+            // Closing the file releases the flock taken to check
+            drop(file);
+            let info = Self::read_lock_info(&lock_path);
+            let running = match &info {
+                _ if held => true,
+                Some(info) => !info.is_stale(),
+                None => false,
+            };
+            if running {
+                let holder = info.map_or_else(String::new, |info| {
+                    format!(" (pid={}, host={})", info.pid, info.hostname)
+                });
+                return Err(Error::WriteLock(format!(
+                    "lock holder{} may still be running; use force to remove the lock anyway",
+                    holder
+                )));
+            }
+        }
+        std::fs::remove_file(&lock_path)?;
+        Ok(true)
+    }
+
     /// Release the lock explicitly (also happens on drop).
     pub fn release(self) {
         // Drop will handle cleanup
@@ -151,6 +249,53 @@ impl Drop for WriteLock {
     }
 }
 
+/// A shared lock held by a read-only handle on a ZDS root.
+///
+/// Any number of readers hold it at once and it never conflicts with the
+/// [`WriteLock`]; it lets tools tell whether readers are still open (see
+/// [`ReadLock::readers_active`]). The lock is released when dropped.
+pub struct ReadLock {
+    /// The lock file handle (keeps the shared flock active)
+    file: File,
+}
+
+impl ReadLock {
+    /// Take a shared lock on the given ZDS root.
+    ///
+    /// Fails if the root has no metadata directory or it isn't writable,
+    /// e.g. on read-only media; callers treat the lock as best effort.
+    pub fn acquire(root: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_dir(root).join(READ_LOCK_FILE_NAME))?;
+        // Blocks only for the instant `readers_active` holds it exclusively
+        FileExt::lock_shared(&file)?;
+        Ok(ReadLock { file })
+    }
+
+    /// Whether any process holds a read lock on the root.
+    pub fn readers_active(root: &Path) -> bool {
+        let Ok(file) = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(lock_dir(root).join(READ_LOCK_FILE_NAME))
+        else {
+            return false;
+        };
+        // The file is left in place: readers may still open it
+        FileExt::try_lock_exclusive(&file).is_err()
+    }
+}
+
+impl Drop for ReadLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tempfile::TempDir;
@@ -203,4 +348,62 @@ mod tests {
         assert_eq!(deserialized.hostname, "testhost");
         assert_eq!(deserialized.timestamp, "2025-01-01T00:00:00Z");
     }
+
+    #[test]
+    fn test_stale_lock_detection() {
+        let tmp = TempDir::new().unwrap();
+        let lock_path = tmp.path().join(".zds").join(LOCK_FILE_NAME);
+        std::fs::create_dir_all(lock_path.parent().unwrap()).unwrap();
+        let write_info = |pid: u32, hostname: &str| {
+            let info = LockInfo {
+                pid,
+                hostname: hostname.to_string(),
+                timestamp: "2025-01-01T00:00:00Z".to_string(),
+            };
+            std::fs::write(&lock_path, info.serialize()).unwrap();
+        };
+
+        // A crashed process on this host: taken over
+        write_info(u32::MAX, &current_hostname());
+        assert!(WriteLock::holder(tmp.path()).unwrap().is_stale());
+        let lock = WriteLock::acquire(tmp.path()).unwrap();
+        assert_eq!(
+            WriteLock::holder(tmp.path()).unwrap().pid,
+            std::process::id()
+        );
+        drop(lock);
+
+        // A holder on another host can't be checked
+        write_info(1, "elsewhere.example");
+        let err = WriteLock::acquire(tmp.path()).err().unwrap();
+        assert!(err.to_string().contains("zippy unlock"));
+        assert!(WriteLock::break_lock(tmp.path(), false).is_err());
+        assert!(WriteLock::break_lock(tmp.path(), true).unwrap());
+        assert!(!WriteLock::break_lock(tmp.path(), true).unwrap());
+        WriteLock::acquire(tmp.path()).unwrap();
+
+        // Stale locks break without force; held ones don't
+        write_info(u32::MAX, &current_hostname());
+        assert!(WriteLock::break_lock(tmp.path(), false).unwrap());
+        let _lock = WriteLock::acquire(tmp.path()).unwrap();
+        assert!(WriteLock::break_lock(tmp.path(), false).is_err());
+    }
+
+    #[test]
+    fn test_read_lock() {
+        let tmp = TempDir::new().unwrap();
+        // No metadata directory: nothing to lock
+        assert!(ReadLock::acquire(tmp.path()).is_err());
+        assert!(!ReadLock::readers_active(tmp.path()));
+
+        let writer = WriteLock::acquire(tmp.path()).unwrap();
+        let a = ReadLock::acquire(tmp.path()).unwrap();
+        let b = ReadLock::acquire(tmp.path()).unwrap();
+        assert!(ReadLock::readers_active(tmp.path()));
+        drop(a);
+        assert!(ReadLock::readers_active(tmp.path()));
+        drop(b);
+        assert!(!ReadLock::readers_active(tmp.path()));
+        drop(writer);
+    }
 }
@@ -0,0 +1,207 @@
+//! Document and schema counts in the manifests.
+//!
+//! Writers record their collection's counts in its `manifest.json` when
+//! they flush, and in the root's `metadata/root_manifest.json`, which lists
+//! every collection. Both are read-modify-write updates; the root manifest
+//! is replaced atomically, but writers of two collections flushing at once
+//! can lose one's entry until its next flush. A root manifest that is
+//! missing or unreadable is rebuilt from the collection manifests.
+
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::CollectionConfig, Layout, Result};
+
+/// Counts of a collection, as listed in the root manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionSummary {
+    pub doc_count: u64,
+    pub schema_count: u64,
+    /// Time of the last recorded flush (RFC 3339, empty if unknown)
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// The root manifest (`metadata/root_manifest.json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootManifest {
+    pub version: String,
+    pub updated_at: String,
+    /// Collections by name
+    #[serde(default)]
+    pub collections: BTreeMap<String, CollectionSummary>,
+}
+
+impl Default for RootManifest {
+    fn default() -> Self {
+        RootManifest {
+            version: crate::ZDS_VERSION.to_string(),
+            updated_at: chrono::Utc::now().to_rfc3339(),
+            collections: BTreeMap::new(),
+        }
+    }
+}
+
+impl RootManifest {
+    /// Read a root's manifest, or rebuild it if it is missing or unreadable.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = Layout::root_manifest(root);
+        let loaded = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+        match loaded {
+            Some(manifest) => Ok(manifest),
+            None => Self::rebuild(root),
+        }
+    }
+
+    /// Build a root's manifest from the counts in its collection manifests.
+    pub fn rebuild(root: &Path) -> Result<Self> {
+        let mut manifest = Self::default();
+        let collections_dir = Layout::collections_dir(root);
+        if !collections_dir.exists() {
+            return Ok(manifest);
+        }
+        for entry in std::fs::read_dir(collections_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str() {
+                let summary = read_summary(root, name).unwrap_or(CollectionSummary {
+                    doc_count: 0,
+                    schema_count: 0,
+                    updated_at: String::new(),
+                });
+                manifest.collections.insert(name.to_string(), summary);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest through a temporary file, so readers never see
+    /// a partial one.
+    pub fn save(&mut self, root: &Path) -> Result<()> {
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+        let path = Layout::root_manifest(root);
+        std::fs::create_dir_all(Layout::metadata_dir(root))?;
+        let tmp_file = path.with_extension("tmp");
+        std::fs::write(&tmp_file, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp_file, &path)?;
+        Ok(())
+    }
+
+    /// Apply `change` to a root's manifest and save it.
+    pub(crate) fn update(root: &Path, change: impl FnOnce(&mut Self)) -> Result<()> {
+        let mut manifest = Self::load(root)?;
+        change(&mut manifest);
+        manifest.save(root)
+    }
+}
+
+/// Counts and update time recorded in a collection's manifest.
+fn read_summary(root: &Path, collection: &str) -> Option<CollectionSummary> {
+    let content = std::fs::read_to_string(Layout::manifest_file(root, collection)).ok()?;
+    let manifest: Value = serde_json::from_str(&content).ok()?;
+    let field = |key: &str| manifest.get(key).and_then(Value::as_u64).unwrap_or(0);
+    Some(CollectionSummary {
+        doc_count: field("doc_count"),
+        schema_count: field("schema_count"),
+        updated_at: manifest
+            .get("updated_at")
+            .or_else(|| manifest.get("created_at"))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// Record a collection's counts in its manifest, keeping other fields,
+/// and in the root manifest.
+pub(crate) fn record_counts(
+    root: &Path,
+    collection: &str,
+    doc_count: usize,
+    schema_count: usize,
+) -> Result<()> {
+    let updated_at = chrono::Utc::now().to_rfc3339();
+    CollectionConfig::update(root, collection, |obj| {
+        obj.insert("doc_count".to_string(), Value::from(doc_count));
+        obj.insert("schema_count".to_string(), Value::from(schema_count));
+        obj.insert("updated_at".to_string(), Value::from(updated_at.clone()));
+    })?;
+
+    let summary = CollectionSummary {
+        doc_count: doc_count as u64,
+        schema_count: schema_count as u64,
+        updated_at,
+    };
+    RootManifest::update(root, |root_manifest| {
+        root_manifest
+            .collections
+            .insert(collection.to_string(), summary);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{writer::SyncWriter, FastStore, OpenMode, ZDSRoot};
+
+    #[test]
+    fn test_manifest_counts() {
+        let tmp = TempDir::new().unwrap();
+        let root = ZDSRoot::open_rw(tmp.path(), 100).unwrap();
+        let mut store = root.collection("fast").unwrap();
+        store.put("a", json!({"x": 1})).unwrap();
+        store.put("b", json!({"y": "two"})).unwrap();
+        store.put("c", json!({"x": 3})).unwrap();
+        store.flush().unwrap();
+
+        let manifest: Value = serde_json::from_str(
+            &std::fs::read_to_string(Layout::manifest_file(tmp.path(), "fast")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["doc_count"], 3);
+        assert_eq!(manifest["schema_count"], 2);
+        assert!(manifest["updated_at"].is_string());
+
+        store.delete("b").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        let mut writer = SyncWriter::new(tmp.path(), "files").unwrap();
+        writer.put("a", &json!({"x": 1})).unwrap();
+        writer.put("b", &json!({"x": 2})).unwrap();
+        drop(writer);
+
+        let listed = RootManifest::load(tmp.path()).unwrap();
+        assert_eq!(listed.collections.len(), 2);
+        assert_eq!(listed.collections["fast"].doc_count, 2);
+        assert_eq!(listed.collections["fast"].schema_count, 1);
+        assert_eq!(listed.collections["files"].doc_count, 2);
+        assert_eq!(listed.collections["files"].schema_count, 1);
+
+        // A lost root manifest is rebuilt from the collections'
+        std::fs::remove_file(Layout::root_manifest(tmp.path())).unwrap();
+        let rebuilt = RootManifest::load(tmp.path()).unwrap();
+        assert_eq!(rebuilt.collections, listed.collections);
+
+        root.drop_collection("files").unwrap();
+        let listed = RootManifest::load(tmp.path()).unwrap();
+        assert!(!listed.collections.contains_key("files"));
+
+        // Readers don't record counts
+        let reader = FastStore::open_with_mode(tmp.path(), "fast", 100, OpenMode::Read).unwrap();
+        drop(reader);
+        assert_eq!(
+            RootManifest::load(tmp.path()).unwrap().collections["fast"].doc_count,
+            2
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Collection migrations: rewrite every document through a transform.
+//!
+//! Documents are migrated in `order.ids` order, a batch at a time. A batch's
+//! rewritten documents are first written next to the originals as
+//! `.{doc_id}.migrate` files, then the progress file (`meta/migration.json`)
+//! marks the batch as staged, then the staged files replace the originals and
+//! the batch is marked done. Running the migration again after a crash
+//! resumes from the progress file, so no document is transformed twice.
+//!
+//! A finished migration is recorded in the collection manifest with the
+//! schema IDs each old schema was mapped to.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::CollectionConfig, index::DocIndexEntry, Codec, Error, FieldIndexes, IndexRegistry,
+    Layout, Result, SchemaEntry, SchemaRegistry, WriteLock,
+};
+
+/// Documents rewritten per batch by [`Engine::migrate`](crate::Engine::migrate).
+pub const MIGRATION_BATCH_SIZE: usize = 1000;
+
+/// A schema that documents moved from, and one they moved to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SchemaMapping {
+    pub from: String,
+    pub to: String,
+}
+
+/// A finished migration, as recorded in the manifest's `migrations` list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationRecord {
+    pub completed_at: String,
+    pub documents: u64,
+    pub mappings: Vec<SchemaMapping>,
+}
+
+/// Outcome of [`Engine::migrate`](crate::Engine::migrate).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// Number of documents in the collection, all migrated
+    pub documents: usize,
+    /// Documents already migrated by an interrupted run (0 for a fresh run)
+    pub resumed_from: usize,
+    /// Old → new schema IDs, sorted
+    pub mappings: Vec<SchemaMapping>,
+}
+
+/// Progress of an unfinished migration.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MigrationState {
+    /// Collection size when the migration started
+    total: usize,
+    /// Documents migrated so far
+    done: usize,
+    /// Index entries of the staged batch following `done`, if any
+    #[serde(default)]
+    staged: Vec<DocIndexEntry>,
+    mappings: BTreeSet<SchemaMapping>,
+    /// Schemas of the migrated documents
+    schemas: BTreeMap<String, SchemaEntry>,
+}
+
+impl MigrationState {
+    fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&std::fs::read_to_string(path)?)?))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp_file = path.with_extension("tmp");
+        std::fs::write(&tmp_file, serde_json::to_vec(self)?)?;
+        std::fs::rename(&tmp_file, path)?;
+        Ok(())
+    }
+}
+
+/// Check whether a collection has an interrupted migration to resume.
+pub fn is_pending(root: &Path, collection: &str) -> bool {
+    Layout::migration_file(root, collection).exists()
+}
+
+fn staged_file(root: &Path, collection: &str, doc_id: &str) -> PathBuf {
+    Layout::docs_dir(root, collection).join(format!(".{}.migrate", Layout::doc_file_stem(doc_id)))
+}
+
+/// Migrate a file-per-document collection (see the module docs).
+pub(crate) fn run<F>(
+    root: &Path,
+    collection: &str,
+    batch_size: usize,
+    mut transform: F,
+) -> Result<MigrationReport>
+where
+    F: FnMut(Value) -> Result<Value>,
+{
+    let _lock = WriteLock::acquire(root)?;
+    let mut index = IndexRegistry::load(root, collection)?;
+    let mut field_indexes = FieldIndexes::load(root, collection)?;
+    let strict = SchemaRegistry::load(root, collection)?.is_strict();
+
+    let state_path = Layout::migration_file(root, collection);
+    let mut state = match MigrationState::load(&state_path)? {
+        Some(state) if state.total != index.len() => {
+            return Err(Error::Validation(format!(
+                "collection '{}' changed since its migration was interrupted \
+                 ({} documents, {} expected)",
+                collection,
+                index.len(),
+                state.total
+            )));
+        }
+        Some(state) => state,
+        None => MigrationState {
+            total: index.len(),
+            ..Default::default()
+        },
+    };
+    let resumed_from = state.done + state.staged.len();
+    let order: Vec<String> = index.all_doc_ids().to_vec();
+
+    // A batch staged before a crash only has to be moved into place
+    if !state.staged.is_empty() {
+        apply_batch(
+            root,
+            collection,
+            &mut state,
+            &mut index,
+            &mut field_indexes,
+            None,
+        )?;
+        state.save(&state_path)?;
+    }
+
+    while state.done < order.len() {
+        let batch = &order[state.done..order.len().min(state.done + batch_size)];
+        let mut docs = Vec::with_capacity(batch.len());
+        for doc_id in batch {
+            let path = Layout::doc_file(root, collection, doc_id);
+            let doc = transform(Codec::decode(&std::fs::read_to_string(&path)?)?)?;
+            let schema_id = SchemaRegistry::compute_schema_id(&doc);
+            if strict {
+                if let Some(expected) = state.schemas.keys().find(|id| **id != schema_id) {
+                    return Err(Error::SchemaMismatch {
+                        expected: expected.clone(),
+                        actual: schema_id,
+                    });
+                }
+            }
+
+            let content = serde_json::to_string_pretty(&doc)?;
+            std::fs::write(staged_file(root, collection, doc_id), &content)?;
+            if let Some(old) = index.get(doc_id) {
+                state.mappings.insert(SchemaMapping {
+                    from: old.schema_id.clone(),
+                    to: schema_id.clone(),
+                });
+            }
+            state
+                .schemas
+                .entry(schema_id.clone())
+                .or_insert_with(|| SchemaEntry {
+                    schema_id: schema_id.clone(),
+                    schema: SchemaRegistry::extract_schema(&doc),
+                    count: 0,
+                })
+                .count += 1;
+            state.staged.push(DocIndexEntry {
+                doc_id: doc_id.clone(),
+                schema_id,
+                size: content.len() as u64,
+                mtime: 0,
+            });
+            docs.push(doc);
+        }
+        state.save(&state_path)?;
+
+        apply_batch(
+            root,
+            collection,
+            &mut state,
+            &mut index,
+            &mut field_indexes,
+            Some(docs),
+        )?;
+        state.save(&state_path)?;
+    }
+
+    // Every document now has one of the new schemas
+    let registry = SchemaRegistry::from_entries(strict, state.schemas.values().cloned());
+    registry.save(root, collection)?;
+    let mappings: Vec<SchemaMapping> = state.mappings.into_iter().collect();
+    record(
+        root,
+        collection,
+        &MigrationRecord {
+            completed_at: chrono::Utc::now().to_rfc3339(),
+            documents: order.len() as u64,
+            mappings: mappings.clone(),
+        },
+        registry.schema_count(),
+    )?;
+    std::fs::remove_file(&state_path)?;
+
+    Ok(MigrationReport {
+        documents: order.len(),
+        resumed_from,
+        mappings,
+    })
+}
+
+/// Move the staged batch into place and mark it done. Without `docs`
+/// (resuming), the documents are read back for the field indexes.
+fn apply_batch(
+    root: &Path,
+    collection: &str,
+    state: &mut MigrationState,
+    index: &mut IndexRegistry,
+    field_indexes: &mut FieldIndexes,
+    docs: Option<Vec<Value>>,
+) -> Result<()> {
+    let staged = std::mem::take(&mut state.staged);
+    for (i, mut entry) in staged.iter().cloned().enumerate() {
+        let path = Layout::doc_file(root, collection, &entry.doc_id);
+        let staged_path = staged_file(root, collection, &entry.doc_id);
+        // Already moved if the crash came later in the batch
+        if staged_path.exists() {
+            std::fs::rename(&staged_path, &path)?;
+        }
+        entry.mtime = std::fs::metadata(&path)
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        match &docs {
+            Some(docs) => field_indexes.insert(&entry.doc_id, &docs[i]),
+            None => {
+                let doc = Codec::decode(&std::fs::read_to_string(&path)?)?;
+                field_indexes.insert(&entry.doc_id, &doc);
+            }
+        }
+        index.put(entry);
+    }
+    index.save(root, collection)?;
+    field_indexes.save(root, collection)?;
+    state.done += staged.len();
+    Ok(())
+}
+
+/// Append a finished migration to the manifest, keeping other fields.
+fn record(
+    root: &Path,
+    collection: &str,
+    migration: &MigrationRecord,
+    schema_count: usize,
+) -> Result<()> {
+    let migration = serde_json::to_value(migration)?;
+    CollectionConfig::update(root, collection, |obj| {
+        let migrations = obj
+            .entry("migrations")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(list) = migrations {
+            list.push(migration);
+        }
+        obj.insert("schema_count".to_string(), Value::from(schema_count));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{writer::SyncWriter, Engine};
+
+    fn rename_name(mut doc: Value) -> Value {
+        if let Value::Object(obj) = &mut doc {
+            if let Some(name) = obj.remove("name") {
+                obj.insert("full_name".to_string(), name);
+            }
+        }
+        doc
+    }
+
+    #[test]
+    fn test_migrate_resume() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        for i in 0..5 {
+            writer
+                .put(&format!("doc{}", i), &json!({"name": format!("n{}", i)}))
+                .unwrap();
+        }
+        drop(writer);
+        let old_schema = SchemaRegistry::compute_schema_id(&json!({"name": ""}));
+        let new_schema = SchemaRegistry::compute_schema_id(&json!({"full_name": ""}));
+
+        // Fail in the second batch: the first one stays migrated
+        let mut seen = 0;
+        let err = run(root, "test", 2, |doc| {
+            seen += 1;
+            if seen == 4 {
+                return Err(Error::Validation("boom".to_string()));
+            }
+            Ok(rename_name(doc))
+        });
+        assert!(err.is_err());
+        assert!(is_pending(root, "test"));
+        let engine = Engine::open(root, "test").unwrap();
+        assert_eq!(
+            engine.get_document("doc1").unwrap(),
+            json!({"full_name": "n1"})
+        );
+        assert_eq!(engine.get_document("doc2").unwrap(), json!({"name": "n2"}));
+
+        // Resuming only transforms the rest
+        let mut seen = Vec::new();
+        let report = run(root, "test", 2, |doc| {
+            seen.push(doc.clone());
+            Ok(rename_name(doc))
+        })
+        .unwrap();
+        assert_eq!(seen.len(), 3);
+        assert_eq!(report.documents, 5);
+        assert_eq!(report.resumed_from, 2);
+        assert_eq!(
+            report.mappings,
+            [SchemaMapping {
+                from: old_schema,
+                to: new_schema.clone(),
+            }]
+        );
+        assert!(!is_pending(root, "test"));
+
+        let engine = Engine::open(root, "test").unwrap();
+        assert_eq!(
+            engine.get_document("doc4").unwrap(),
+            json!({"full_name": "n4"})
+        );
+        let registry = SchemaRegistry::load(root, "test").unwrap();
+        assert_eq!(registry.schema_count(), 1);
+        assert_eq!(registry.get(&new_schema).unwrap().count, 5);
+        let manifest: Value = serde_json::from_str(
+            &std::fs::read_to_string(Layout::manifest_file(root, "test")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["migrations"][0]["documents"], 5);
+    }
+
+    #[test]
+    fn test_migrate_staged_batch() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        writer.put("a", &json!({"name": "x"})).unwrap();
+        drop(writer);
+
+        // Crash after staging, before the staged file was moved into place
+        let doc = rename_name(json!({"name": "x"}));
+        let content = serde_json::to_string_pretty(&doc).unwrap();
+        std::fs::write(staged_file(root, "test", "a"), &content).unwrap();
+        let schema_id = SchemaRegistry::compute_schema_id(&doc);
+        let state = MigrationState {
+            total: 1,
+            staged: vec![DocIndexEntry {
+                doc_id: "a".to_string(),
+                schema_id: schema_id.clone(),
+                size: content.len() as u64,
+                mtime: 0,
+            }],
+            schemas: [(
+                schema_id.clone(),
+                SchemaEntry {
+                    schema_id,
+                    schema: SchemaRegistry::extract_schema(&doc),
+                    count: 1,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        state.save(&Layout::migration_file(root, "test")).unwrap();
+
+        let report = run(root, "test", 2, |_| panic!("already transformed")).unwrap();
+        assert_eq!(report.resumed_from, 1);
+        let engine = Engine::open(root, "test").unwrap();
+        assert_eq!(engine.get_document("a").unwrap(), doc);
+    }
+}
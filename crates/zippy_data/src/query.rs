@@ -0,0 +1,372 @@
+//! Parser for human-writable filter expressions.
+//!
+//! ```text
+//! expr      := and ("OR" and)*
+//! and       := term ("AND" term)*
+//! term      := "(" expr ")" | condition
+//! condition := field ("=" | "==" | ">" | ">=" | "<" | "<=") value
+//!            | field "IN" "(" value ("," value)* ")"
+//!            | field "STARTSWITH" string
+//!            | field "CONTAINS" value
+//!            | field "MATCH" string
+//!            | field "EXISTS" | field "NOT" "EXISTS"
+//! value     := 'string' | "string" | number | true | false | null
+//! ```
+//!
+//! Keywords are case-insensitive. Fields use dot notation for nested values
+//! and may be wrapped in backticks when they clash with a keyword.
+
+use serde_json::Value;
+
+use crate::{Error, Predicate, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Quoted(String),
+    Str(String),
+    Num(Value),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Ident(s) if s.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn error(pos: usize, msg: impl std::fmt::Display) -> Error {
+    Error::Codec(format!("Invalid filter at position {}: {}", pos, msg))
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' | ')' | ',' => {
+                tokens.push((
+                    pos,
+                    match c {
+                        '(' => Token::LParen,
+                        ')' => Token::RParen,
+                        _ => Token::Comma,
+                    },
+                ));
+                i += 1;
+            }
+            '=' | '>' | '<' | '!' => {
+                let next = chars.get(i + 1).map(|&(_, c)| c);
+                let (op, len) = match (c, next) {
+                    ('=', Some('=')) => ("=", 2),
+                    ('=', _) => ("=", 1),
+                    ('>', Some('=')) => (">=", 2),
+                    ('>', _) => (">", 1),
+                    ('<', Some('=')) => ("<=", 2),
+                    ('<', _) => ("<", 1),
+                    _ => return Err(error(pos, format!("unsupported operator '{}'", c))),
+                };
+                tokens.push((pos, Token::Op(op)));
+                i += len;
+            }
+            '\'' | '"' | '`' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(error(pos, "unterminated string")),
+                        Some(&(_, '\\')) => {
+                            let (_, escaped) = *chars
+                                .get(i + 1)
+                                .ok_or_else(|| error(pos, "unterminated string"))?;
+                            text.push(escaped);
+                            i += 2;
+                        }
+                        Some(&(_, q)) if q == c => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&(_, other)) => {
+                            text.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push((
+                    pos,
+                    if c == '`' {
+                        Token::Quoted(text)
+                    } else {
+                        Token::Str(text)
+                    },
+                ));
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                while i < chars.len()
+                    && (chars[i].1.is_ascii_alphanumeric() || "+-.".contains(chars[i].1))
+                {
+                    i += 1;
+                }
+                let end = chars.get(i).map_or(input.len(), |&(p, _)| p);
+                let text = &input[pos..end];
+                let value = text
+                    .parse::<i64>()
+                    .map(Value::from)
+                    .ok()
+                    .or_else(|| {
+                        text.parse::<f64>()
+                            .ok()
+                            .and_then(serde_json::Number::from_f64)
+                            .map(Value::Number)
+                    })
+                    .ok_or_else(|| error(pos, format!("invalid number '{}'", text)))?;
+                tokens.push((pos, Token::Num(value)));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while i < chars.len()
+                    && (chars[i].1.is_alphanumeric() || chars[i].1 == '_' || chars[i].1 == '.')
+                {
+                    i += 1;
+                }
+                let end = chars.get(i).map_or(input.len(), |&(p, _)| p);
+                tokens.push((pos, Token::Ident(input[pos..end].to_string())));
+            }
+            other => return Err(error(pos, format!("unexpected character '{}'", other))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.len, |&(p, _)| p)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek().is_some_and(|t| t.is_keyword(keyword)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<()> {
+        let offset = self.offset();
+        match self.next() {
+            Some(t) if t == expected => Ok(()),
+            _ => Err(error(offset, format!("expected {}", what))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate> {
+        let mut preds = vec![self.parse_and()?];
+        while self.eat_keyword("OR") {
+            preds.push(self.parse_and()?);
+        }
+        Ok(if preds.len() == 1 {
+            preds.pop().unwrap()
+        } else {
+            Predicate::Or(preds)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate> {
+        let mut preds = vec![self.parse_term()?];
+        while self.eat_keyword("AND") {
+            preds.push(self.parse_term()?);
+        }
+        Ok(if preds.len() == 1 {
+            preds.pop().unwrap()
+        } else {
+            Predicate::And(preds)
+        })
+    }
+
+    fn parse_term(&mut self) -> Result<Predicate> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let pred = self.parse_or()?;
+            self.expect(Token::RParen, "')'")?;
+            return Ok(pred);
+        }
+        self.parse_condition()
+    }
+
+    fn parse_condition(&mut self) -> Result<Predicate> {
+        let offset = self.offset();
+        let field = match self.next() {
+            Some(Token::Ident(name)) | Some(Token::Quoted(name)) => name,
+            _ => return Err(error(offset, "expected field name")),
+        };
+
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Op(op)) => {
+                let value = self.parse_value()?;
+                Ok(match op {
+                    "=" => Predicate::eq(field, value),
+                    ">" => Predicate::gt(field, value),
+                    ">=" => Predicate::gte(field, value),
+                    "<" => Predicate::lt(field, value),
+                    _ => Predicate::lte(field, value),
+                })
+            }
+            Some(t) if t.is_keyword("IN") => {
+                self.expect(Token::LParen, "'(' after IN")?;
+                let mut values = vec![self.parse_value()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    values.push(self.parse_value()?);
+                }
+                self.expect(Token::RParen, "')'")?;
+                Ok(Predicate::is_in(field, values))
+            }
+            Some(t) if t.is_keyword("STARTSWITH") => {
+                let offset = self.offset();
+                match self.next() {
+                    Some(Token::Str(prefix)) => Ok(Predicate::starts_with(field, prefix)),
+                    _ => Err(error(offset, "STARTSWITH expects a string")),
+                }
+            }
+            Some(t) if t.is_keyword("CONTAINS") => {
+                Ok(Predicate::contains(field, self.parse_value()?))
+            }
+            Some(t) if t.is_keyword("MATCH") => {
+                let offset = self.offset();
+                match self.next() {
+                    Some(Token::Str(query)) => Ok(Predicate::text_match(field, query)),
+                    _ => Err(error(offset, "MATCH expects a string")),
+                }
+            }
+            Some(t) if t.is_keyword("EXISTS") => Ok(Predicate::Exists(field)),
+            Some(t) if t.is_keyword("NOT") => {
+                if self.eat_keyword("EXISTS") {
+                    Ok(Predicate::NotExists(field))
+                } else {
+                    Err(error(self.offset(), "expected EXISTS after NOT"))
+                }
+            }
+            _ => Err(error(
+                offset,
+                format!("expected operator after '{}'", field),
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        let offset = self.offset();
+        match self.next() {
+            Some(Token::Str(s)) => Ok(Value::String(s)),
+            Some(Token::Num(n)) => Ok(n),
+            Some(t) if t.is_keyword("true") => Ok(Value::Bool(true)),
+            Some(t) if t.is_keyword("false") => Ok(Value::Bool(false)),
+            Some(t) if t.is_keyword("null") => Ok(Value::Null),
+            Some(Token::Ident(name)) => Err(error(
+                offset,
+                format!("expected value, got '{}' (quote string values)", name),
+            )),
+            _ => Err(error(offset, "expected value")),
+        }
+    }
+}
+
+/// Parse a filter expression into a predicate.
+pub(crate) fn parse(input: &str) -> Result<Predicate> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(error(0, "empty filter"));
+    }
+
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        len: input.len(),
+    };
+    let pred = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(error(parser.offset(), "unexpected trailing input"));
+    }
+    Ok(pred)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::Codec;
+
+    fn check(filter: &str, doc: &Value) -> bool {
+        Codec::apply_predicate(doc, &parse(filter).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        let doc = json!({
+            "age": 42, "score": 0.5, "status": "active",
+            "meta": {"lang": "en"}, "tags": ["a", "b"], "flag": true
+        });
+
+        assert!(check("age > 30 AND status = 'active'", &doc));
+        assert!(check("age >= 42 and age <= 42", &doc));
+        assert!(!check("age < 30 OR status == \"inactive\"", &doc));
+        assert!(check(
+            "(age < 30 OR score = 0.5) AND meta.lang = 'en'",
+            &doc
+        ));
+        assert!(check("status IN ('active', 'pending')", &doc));
+        assert!(check("status STARTSWITH 'act' AND tags CONTAINS 'b'", &doc));
+        assert!(check("meta EXISTS AND missing NOT EXISTS", &doc));
+        assert!(check("flag = true AND `age` > -1", &doc));
+        assert!(check("status = 'it\\'s' OR age = 42", &doc));
+        assert!(check("status MATCH 'ACTIVE' AND tags match 'a'", &doc));
+
+        assert!(matches!(
+            parse("a = 1 OR b = 2 AND c = 3").unwrap(),
+            Predicate::Or(ref preds) if matches!(preds[1], Predicate::And(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "",
+            "age >",
+            "age 30",
+            "status = active",
+            "age != 3",
+            "(age > 1",
+            "age > 1 age < 2",
+            "name = 'open",
+            "x IN 1",
+            "body MATCH 1",
+        ] {
+            assert!(parse(bad).is_err(), "expected error for {:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,653 @@
+//! Read-only containers in object storage (S3, GCS) and over HTTP.
+//!
+//! A remote container is a folder container uploaded under a bucket prefix
+//! and opened by URL: `s3://bucket/path/to/store` or `gs://bucket/path`.
+//! Credentials, region and endpoint come from the usual environment
+//! variables (`AWS_ACCESS_KEY_ID`, `AWS_REGION`, `AWS_ENDPOINT`,
+//! `GOOGLE_SERVICE_ACCOUNT`, ...).
+//!
+//! Nothing is downloaded up front: metadata files are fetched when a
+//! collection is opened, and documents when they are read. JSONL data
+//! segments are read with one range request per document, using the
+//! store's saved `index.bin` and `index.delta`.
+//!
+//! `.zds` archives can also be read by URL, including `http://` and
+//! `https://` ones, through a [`RangeReader`]: only the ZIP central
+//! directory and the entries actually read are fetched.
+
+use std::{
+    future::Future,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use object_store::{
+    aws::AmazonS3Builder, gcp::GoogleCloudStorageBuilder, http::HttpBuilder,
+    path::Path as ObjectPath, ClientOptions, ObjectStore,
+};
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+
+use crate::{
+    container::ZipEntry,
+    fast_writer::{self, FastStore, IndexEntry},
+    id_map::IdMap,
+    index::{DocIndexEntry, IndexRegistry},
+    segment, Error, Layout, Result,
+};
+
+/// A container stored in an object store.
+///
+/// Cheap to clone; clones share the client and its runtime.
+#[derive(Clone)]
+pub struct RemoteStore {
+    url: PathBuf,
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    runtime: Arc<tokio::runtime::Runtime>,
+}
+
+impl std::fmt::Debug for RemoteStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteStore")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RemoteStore {
+    /// Connect to the container at an `s3://`, `gs://`, `http://` or
+    /// `https://` URL.
+    ///
+    /// HTTP servers can't list directories, so HTTP containers can only
+    /// serve files by name, such as a `.zds` archive to a [`RangeReader`].
+    pub fn open(url: &str) -> Result<Self> {
+        let invalid = || Error::InvalidContainer(format!("Invalid remote URL: {}", url));
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(invalid());
+        }
+
+        let (store, prefix): (Arc<dyn ObjectStore>, _) = match scheme {
+            "s3" => (
+                Arc::new(
+                    AmazonS3Builder::from_env()
+                        .with_bucket_name(bucket)
+                        .build()
+                        .map_err(remote_error)?,
+                ),
+                prefix,
+            ),
+            "gs" => (
+                Arc::new(
+                    GoogleCloudStorageBuilder::from_env()
+                        .with_bucket_name(bucket)
+                        .build()
+                        .map_err(remote_error)?,
+                ),
+                prefix,
+            ),
+            // Paths are resolved against the URL itself
+            "http" | "https" => (
+                Arc::new(
+                    HttpBuilder::new()
+                        .with_url(url)
+                        .with_client_options(ClientOptions::new().with_allow_http(scheme == "http"))
+                        .build()
+                        .map_err(remote_error)?,
+                ),
+                "",
+            ),
+            other => {
+                return Err(Error::InvalidContainer(format!(
+                    "Unsupported remote scheme '{}' (expected s3, gs, http or https)",
+                    other
+                )))
+            }
+        };
+        Self::with_store(store, prefix, url)
+    }
+
+    /// Read the container under `prefix` of any object store; `url` is
+    /// only used to name it.
+    pub fn with_store(store: Arc<dyn ObjectStore>, prefix: &str, url: &str) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()?;
+        Ok(RemoteStore {
+            url: PathBuf::from(url),
+            store,
+            prefix: ObjectPath::from(prefix),
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The container's URL.
+    pub fn url(&self) -> &Path {
+        &self.url
+    }
+
+    /// Object location of a path relative to the container root.
+    fn location(&self, relative: &Path) -> ObjectPath {
+        relative
+            .components()
+            .fold(self.prefix.clone(), |location, part| {
+                location.child(part.as_os_str().to_string_lossy().as_ref())
+            })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Read a file, or `None` if the container doesn't hold it.
+    pub fn read(&self, relative_path: &Path) -> Result<Option<Vec<u8>>> {
+        let location = self.location(relative_path);
+        self.block_on(async {
+            match self.store.get(&location).await {
+                Ok(result) => Ok(Some(result.bytes().await.map_err(remote_error)?.to_vec())),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(remote_error(e)),
+            }
+        })
+    }
+
+    /// Read a file, failing with a not-found I/O error if the container
+    /// doesn't hold it.
+    pub fn read_file(&self, relative_path: &Path) -> Result<Vec<u8>> {
+        self.read(relative_path)?.ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("File not found: {}", relative_path.display()),
+            ))
+        })
+    }
+
+    /// Read a byte range of a file.
+    pub fn read_range(&self, relative_path: &Path, range: Range<u64>) -> Result<Vec<u8>> {
+        let location = self.location(relative_path);
+        let range = range.start as usize..range.end as usize;
+        let bytes = self
+            .block_on(self.store.get_range(&location, range))
+            .map_err(remote_error)?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Size of a file in bytes.
+    pub fn size(&self, relative_path: &Path) -> Result<u64> {
+        let location = self.location(relative_path);
+        let meta = self
+            .block_on(self.store.head(&location))
+            .map_err(remote_error)?;
+        Ok(meta.size as u64)
+    }
+
+    /// Check if the container holds a file.
+    pub fn exists(&self, relative_path: &Path) -> Result<bool> {
+        let location = self.location(relative_path);
+        match self.block_on(self.store.head(&location)) {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(remote_error(e)),
+        }
+    }
+
+    /// Files directly inside a directory, without reading them.
+    pub fn list_dir(&self, dir: &Path) -> Result<Vec<ZipEntry>> {
+        let location = self.location(dir);
+        let listing = self
+            .block_on(self.store.list_with_delimiter(Some(&location)))
+            .map_err(remote_error)?;
+        Ok(listing
+            .objects
+            .into_iter()
+            .filter_map(|meta| {
+                Some(ZipEntry {
+                    name: meta.location.filename()?.to_string(),
+                    size: meta.size as u64,
+                    mtime: meta.last_modified.timestamp().max(0) as u64,
+                })
+            })
+            .collect())
+    }
+
+    /// Names of the directories directly inside a directory.
+    pub fn list_subdirs(&self, dir: &Path) -> Result<Vec<String>> {
+        let location = self.location(dir);
+        let listing = self
+            .block_on(self.store.list_with_delimiter(Some(&location)))
+            .map_err(remote_error)?;
+        Ok(listing
+            .common_prefixes
+            .iter()
+            .filter_map(|prefix| prefix.filename().map(str::to_string))
+            .collect())
+    }
+}
+
+/// Object store errors as I/O errors (not found, permission denied, ...).
+fn remote_error(e: object_store::Error) -> Error {
+    Error::Io(e.into())
+}
+
+/// Size of the blocks a [`RangeReader`] fetches.
+const RANGE_BLOCK: u64 = 256 * 1024;
+
+/// A remote file read with range requests, as `Read + Seek`.
+///
+/// Reads are served from one cached block aligned to [`RANGE_BLOCK`], so
+/// the many small, mostly nearby reads of a ZIP reader (including its
+/// backwards search for the end of the central directory) cost one
+/// request per block.
+pub struct RangeReader {
+    store: RemoteStore,
+    path: PathBuf,
+    len: u64,
+    pos: u64,
+    block_start: u64,
+    block: Vec<u8>,
+}
+
+impl RangeReader {
+    /// Open the file at an `http://`, `https://`, `s3://` or `gs://` URL.
+    pub fn open(url: &str) -> Result<Self> {
+        let (dir, name) = url
+            .rsplit_once('/')
+            .filter(|(dir, name)| !name.is_empty() && !dir.ends_with('/'))
+            .ok_or_else(|| Error::InvalidContainer(format!("Invalid remote URL: {}", url)))?;
+        Self::new(RemoteStore::open(dir)?, Path::new(name))
+    }
+
+    /// Open a file of a remote container.
+    pub fn new(store: RemoteStore, relative_path: &Path) -> Result<Self> {
+        let len = store.size(relative_path)?;
+        Ok(RangeReader {
+            store,
+            path: relative_path.to_path_buf(),
+            len,
+            pos: 0,
+            block_start: 0,
+            block: Vec::new(),
+        })
+    }
+
+    /// Size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl std::io::Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let block_end = self.block_start + self.block.len() as u64;
+        if !(self.block_start..block_end).contains(&self.pos) {
+            let start = self.pos - self.pos % RANGE_BLOCK;
+            let end = (start + RANGE_BLOCK).min(self.len);
+            self.block = self
+                .store
+                .read_range(&self.path, start..end)
+                .map_err(|e| match e {
+                    Error::Io(e) => e,
+                    other => std::io::Error::other(other.to_string()),
+                })?;
+            self.block_start = start;
+        }
+        let offset = (self.pos - self.block_start) as usize;
+        let n = buf.len().min(self.block.len() - offset);
+        buf[..n].copy_from_slice(&self.block[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl std::io::Seek for RangeReader {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let pos = match pos {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            std::io::SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek before the start of the file",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// A JSONL collection in a remote container, read with one range request
+/// per document.
+///
+/// Only documents covered by the store's saved index are visible, and
+/// zstd-compressed data segments can't be read.
+pub(crate) struct RemoteJsonl {
+    store: RemoteStore,
+    collection: String,
+    index: IdMap<IndexEntry>,
+    /// Expiry times (Unix ms) of documents written with a TTL
+    expires: FxHashMap<String, u64>,
+}
+
+impl RemoteJsonl {
+    /// Load the collection's index, returning the reader and the documents
+    /// in insertion order.
+    pub(crate) fn open(store: &RemoteStore, collection: &str) -> Result<(Self, IndexRegistry)> {
+        // Layout paths relative to the container root
+        let root = Path::new("");
+        let meta_dir = Layout::meta_dir(root, collection);
+        let compressed = store
+            .list_dir(&meta_dir)?
+            .iter()
+            .any(|e| Layout::parse_data_segment(&e.name).is_some_and(|(_, zstd)| zstd));
+        if compressed {
+            return Err(Error::InvalidContainer(format!(
+                "collection '{}' has zstd-compressed data segments, which can't be read remotely",
+                collection
+            )));
+        }
+
+        let buf = store.read(&meta_dir.join("index.bin"))?.ok_or_else(|| {
+            Error::InvalidContainer(format!(
+                "collection '{}' has no saved index; flush the store before uploading it",
+                collection
+            ))
+        })?;
+        let mut index = IdMap::default();
+        let mut expires = FxHashMap::default();
+        let checksum = FastStore::parse_index_binary(&buf, &mut index, &mut expires)?;
+        if let Some(delta) = store.read(&Layout::index_delta(root, collection))? {
+            FastStore::parse_index_delta(&delta, checksum, None, &mut index, &mut expires)?;
+        }
+
+        if let Some(content) = store.read(&Layout::tombstone_file(root, collection))? {
+            for tombstone in FastStore::parse_tombstones(&content)? {
+                if index
+                    .get(&tombstone.id)
+                    .is_some_and(|e| e.offset < tombstone.offset)
+                {
+                    index.remove(&tombstone.id);
+                }
+            }
+        }
+        let now = fast_writer::now_ms();
+        index.retain(|id, _| expires.get(id).map_or(true, |&t| t > now));
+        expires.retain(|id, _| index.contains_key(id));
+
+        // Saved insertion order first, then documents written after it in
+        // file order
+        let read_string = |path: &Path| -> Result<Option<String>> {
+            store
+                .read(path)?
+                .map(|bytes| {
+                    String::from_utf8(bytes)
+                        .map_err(|e| Error::Codec(format!("Invalid UTF-8 in file: {}", e)))
+                })
+                .transpose()
+        };
+        let saved = IndexRegistry::parse(
+            read_string(&Layout::doc_index(root, collection))?.as_deref(),
+            read_string(&Layout::order_file(root, collection))?.as_deref(),
+        )
+        .unwrap_or_default();
+        let mut registry = IndexRegistry::new();
+        for entry in saved.iter() {
+            if let Some(indexed) = index.get(&entry.doc_id) {
+                registry.put(DocIndexEntry {
+                    size: indexed.length as u64,
+                    ..entry.clone()
+                });
+            }
+        }
+        let mut rest: Vec<_> = index
+            .iter()
+            .filter(|(id, _)| !registry.contains(id))
+            .collect();
+        rest.sort_unstable_by_key(|(_, e)| e.offset);
+        for (doc_id, entry) in rest {
+            registry.put(DocIndexEntry {
+                doc_id: doc_id.to_string(),
+                schema_id: String::new(),
+                size: entry.length as u64,
+                mtime: 0,
+            });
+        }
+
+        let reader = RemoteJsonl {
+            store: store.clone(),
+            collection: collection.to_string(),
+            index,
+            expires,
+        };
+        Ok((reader, registry))
+    }
+
+    /// Read a document with a range request.
+    pub(crate) fn get(&self, doc_id: &str) -> Result<Value> {
+        fast_writer::parse_raw_doc(&self.raw_line(doc_id)?)
+    }
+
+    /// Documents with their index entries and expiry times (Unix ms), as
+    /// of when the collection was opened.
+    pub(crate) fn indexed(&self) -> impl Iterator<Item = (&str, &IndexEntry, Option<u64>)> {
+        self.index
+            .iter()
+            .map(|(id, entry)| (id, entry, self.expires.get(id).copied()))
+    }
+
+    /// A document's stored line, `_id` included and without its newline.
+    pub(crate) fn raw_line(&self, doc_id: &str) -> Result<Vec<u8>> {
+        let entry = self
+            .index
+            .get(doc_id)
+            .ok_or_else(|| Error::DocumentNotFound(doc_id.to_string()))?;
+        let (no, offset) = segment::split(entry.offset);
+        let path = Layout::data_segment_file(Path::new(""), &self.collection, no, false);
+        let mut line = self
+            .store
+            .read_range(&path, offset..offset + entry.length as u64)?;
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::local::LocalFileSystem;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{writer::SyncWriter, ContainerFS, Engine, Layout, StorageMode};
+
+    fn local_store(root: &Path) -> RemoteStore {
+        let store = LocalFileSystem::new_with_prefix(root).unwrap();
+        RemoteStore::with_store(Arc::new(store), "", "file:///test").unwrap()
+    }
+
+    #[test]
+    fn test_remote_files_collection() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        ContainerFS::create_folder(root).unwrap();
+        let mut writer = SyncWriter::new(root, "train").unwrap();
+        writer.put("doc1", &json!({"label": 1})).unwrap();
+        writer.put("doc2", &json!({"label": 2})).unwrap();
+        drop(writer);
+
+        let container = ContainerFS::Remote(local_store(root));
+        assert_eq!(container.list_collections().unwrap(), vec!["train"]);
+
+        let engine = Engine::open_remote(local_store(root), "train").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Files);
+        assert_eq!(engine.len(), 2);
+        assert_eq!(engine.get_document("doc2").unwrap()["label"], 2);
+        let docs: Vec<_> = engine.scan(None, None).unwrap().collect();
+        assert_eq!(docs.len(), 2);
+    }
+
+    /// Serve `data` at every path over HTTP, answering HEAD and range
+    /// requests, and return the server's base URL.
+    fn serve(data: Vec<u8>) -> String {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let data = Arc::new(data);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let data = data.clone();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request = String::new();
+                    reader.read_line(&mut request).unwrap();
+                    let mut range = None;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() {
+                            break;
+                        }
+                        if let Some(value) = header.to_ascii_lowercase().strip_prefix("range:") {
+                            let (start, end) = value
+                                .trim()
+                                .strip_prefix("bytes=")
+                                .unwrap()
+                                .split_once('-')
+                                .unwrap();
+                            range = Some((
+                                start.parse::<usize>().unwrap(),
+                                end.parse::<usize>().unwrap(),
+                            ));
+                        }
+                    }
+
+                    let (status, body, content_range) = match range {
+                        Some((start, end)) => {
+                            let end = (end + 1).min(data.len());
+                            let content_range = format!(
+                                "Content-Range: bytes {}-{}/{}\r\n",
+                                start,
+                                end - 1,
+                                data.len()
+                            );
+                            ("206 Partial Content", &data[start..end], content_range)
+                        }
+                        None => ("200 OK", &data[..], String::new()),
+                    };
+                    let head = format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                        status,
+                        body.len(),
+                        content_range
+                    );
+                    stream.write_all(head.as_bytes()).unwrap();
+                    if !request.starts_with("HEAD") {
+                        stream.write_all(body).unwrap();
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_archive_over_http() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().join("store");
+        ContainerFS::create_folder(&root).unwrap();
+        let mut writer = SyncWriter::new(&root, "train").unwrap();
+        for i in 0..50 {
+            writer
+                .put(
+                    &format!("doc{:02}", i),
+                    &json!({"n": i, "text": "x".repeat(10_000)}),
+                )
+                .unwrap();
+        }
+        drop(writer);
+        let archive = tmp.path().join("data.zds");
+        crate::container::pack(&root, &archive).unwrap();
+
+        let url = format!("{}/data/data.zds", serve(std::fs::read(&archive).unwrap()));
+        let container = ContainerFS::open(&url).unwrap();
+        assert!(container.is_zip());
+        assert_eq!(container.list_collections().unwrap(), vec!["train"]);
+
+        let engine = Engine::open(&url, "train").unwrap();
+        assert_eq!(engine.len(), 50);
+        assert_eq!(engine.get_document("doc42").unwrap()["n"], 42);
+        let docs: Vec<_> = engine.scan(None, None).unwrap().collect();
+        assert_eq!(docs.len(), 50);
+
+        assert!(ContainerFS::open(url.trim_end_matches(".zds")).is_err());
+    }
+
+    #[test]
+    fn test_range_reader() {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let tmp = TempDir::new().unwrap();
+        let data: Vec<u8> = (0..RANGE_BLOCK * 2 + 10).map(|i| i as u8).collect();
+        std::fs::write(tmp.path().join("file.bin"), &data).unwrap();
+
+        let mut reader = RangeReader::new(local_store(tmp.path()), Path::new("file.bin")).unwrap();
+        assert_eq!(reader.len(), data.len() as u64);
+        let mut buf = [0u8; 8];
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(buf[..4], data[data.len() - 4..]);
+
+        // Reads stop at block boundaries
+        reader.seek(SeekFrom::Start(RANGE_BLOCK - 2)).unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, data[RANGE_BLOCK as usize..]);
+        assert!(reader
+            .seek(SeekFrom::Current(-(data.len() as i64) - 1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_remote_jsonl_collection() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        let mut store = FastStore::open(root, "train", 10).unwrap();
+        for i in 0..5 {
+            store.put(format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        store.delete("doc3").unwrap();
+        store.flush().unwrap();
+        drop(store);
+        assert!(Layout::data_file(root, "train").exists());
+
+        let engine = Engine::open_remote(local_store(root), "train").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Jsonl);
+        assert_eq!(engine.doc_ids(), ["doc0", "doc1", "doc2", "doc4"]);
+        assert_eq!(engine.get_document("doc4").unwrap(), json!({"n": 4}));
+        assert!(engine.get_document("doc3").is_err());
+
+        let docs: Vec<_> = engine.scan(None, None).unwrap().collect();
+        assert_eq!(docs.len(), 4);
+        assert_eq!(docs[1].as_ref().unwrap(), &json!({"n": 1}));
+    }
+}
@@ -0,0 +1,74 @@
+//! Reproducible random sampling of document positions.
+//!
+//! Stores pick positions in their document order and read only those
+//! documents, so a sample never loads the whole collection.
+
+use std::collections::HashSet;
+
+/// Seeded splitmix64 generator: the same seed gives the same sequence on
+/// every platform and release.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound` (`bound` > 0).
+    fn below(&mut self, bound: u64) -> u64 {
+        // Reject the low values that would bias the modulo
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let x = self.next_u64();
+            if x >= threshold {
+                return x % bound;
+            }
+        }
+    }
+}
+
+/// `n` distinct positions out of `0..len` (all of them if `n >= len`),
+/// sorted, chosen by `seed`.
+///
+/// Uses Floyd's algorithm, so only `n` random draws are made however large
+/// `len` is.
+pub(crate) fn sample_positions(len: usize, n: usize, seed: u64) -> Vec<usize> {
+    if n >= len {
+        return (0..len).collect();
+    }
+    let mut rng = SplitMix64(seed);
+    let mut picked = HashSet::with_capacity(n);
+    for j in len - n..len {
+        let t = rng.below(j as u64 + 1) as usize;
+        if !picked.insert(t) {
+            picked.insert(j);
+        }
+    }
+    let mut positions: Vec<usize> = picked.into_iter().collect();
+    positions.sort_unstable();
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_positions() {
+        let sample = sample_positions(1000, 10, 42);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+        assert!(sample.iter().all(|&p| p < 1000));
+
+        // Reproducible per seed
+        assert_eq!(sample, sample_positions(1000, 10, 42));
+        assert_ne!(sample, sample_positions(1000, 10, 7));
+
+        assert_eq!(sample_positions(3, 10, 0), [0, 1, 2]);
+        assert!(sample_positions(0, 5, 0).is_empty());
+    }
+}
@@ -1,10 +1,6 @@
 //! Schema registry and schema identity computation.
 
-use std::{
-    collections::HashMap,
-    io::{BufRead, BufReader, Write},
-    path::Path,
-};
+use std::{collections::HashMap, io::Write, path::Path};
 
 use blake3;
 use serde::{Deserialize, Serialize};
@@ -58,26 +54,40 @@ impl SchemaRegistry {
             false
         };
 
-        let mut registry = SchemaRegistry::new(strict);
-
-        if path.exists() {
-            let file = std::fs::File::open(&path)?;
-            let reader = BufReader::new(file);
+        let content = if path.exists() {
+            std::fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+        Self::parse(strict, &content)
+    }
 
-            for line in reader.lines() {
-                let line = line?;
-                if line.trim().is_empty() {
-                    continue;
-                }
-                let entry: SchemaEntry = serde_json::from_str(&line)?;
-                if registry.strict && registry.strict_schema_id.is_none() {
-                    registry.strict_schema_id = Some(entry.schema_id.clone());
-                }
-                registry.schemas.insert(entry.schema_id.clone(), entry);
+    /// Build a registry from the contents of `schemas.jsonl`.
+    pub fn parse(strict: bool, content: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for line in content.lines() {
+            if line.trim().is_empty() {
+                continue;
             }
+            entries.push(serde_json::from_str::<SchemaEntry>(line)?);
         }
+        Ok(Self::from_entries(strict, entries))
+    }
 
-        Ok(registry)
+    /// Build a registry from existing entries; in strict mode the first
+    /// entry is the required schema.
+    pub(crate) fn from_entries(
+        strict: bool,
+        entries: impl IntoIterator<Item = SchemaEntry>,
+    ) -> Self {
+        let mut registry = SchemaRegistry::new(strict);
+        for entry in entries {
+            if strict && registry.strict_schema_id.is_none() {
+                registry.strict_schema_id = Some(entry.schema_id.clone());
+            }
+            registry.schemas.insert(entry.schema_id.clone(), entry);
+        }
+        registry
     }
 
     /// Save schema registry to disk.
@@ -0,0 +1,413 @@
+//! Segmented data files for the fast store.
+//!
+//! Documents are appended to the active (last) segment until it reaches the
+//! store's segment size, then a new segment is started. Segment 0 is the
+//! classic `meta/data.jsonl`, so stores smaller than one segment keep the
+//! single-file layout; later segments are `data.00001.jsonl`, ... Each segment
+//! has its own format (plain or zstd blocks), given by its file extension.
+//!
+//! Index offsets are segment-qualified: the segment number sits above bit
+//! [`SEGMENT_SHIFT`] and the logical offset within the segment below it.
+//! Offsets therefore still sort in write order, and segment 0 offsets are
+//! plain byte offsets.
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    compression::{BlockCache, Compression, DataView},
+    encryption::Cipher,
+    fast_writer::IndexEntry,
+    Layout, OpenMode, Result,
+};
+
+/// Bits of an index offset that address bytes within a segment (1 TiB).
+pub const SEGMENT_SHIFT: u32 = 40;
+
+/// Default maximum (uncompressed) size of a segment.
+pub const DEFAULT_SEGMENT_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Default bytes read ahead of parallel scans (see [`MmapHints`]).
+pub const DEFAULT_READAHEAD: u64 = 8 * 1024 * 1024;
+
+/// Paging hints for the memory maps of a store's data segments.
+///
+/// A cold scan otherwise faults its pages in one at a time. The hints are
+/// passed to the OS where it supports them (`MAP_POPULATE` on Linux,
+/// `madvise` on Unix) and ignored elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmapHints {
+    /// Read the whole data into the page cache when it is mapped
+    /// (`MAP_POPULATE`)
+    pub populate: bool,
+    /// Advise sequential access (`MADV_SEQUENTIAL`): more readahead, and
+    /// pages are dropped sooner once read. Suits stores that are mostly
+    /// scanned rather than read by ID.
+    pub sequential: bool,
+    /// Bytes parallel scans ask the OS to read (`MADV_WILLNEED`) ahead of
+    /// the lines being parsed; 0 disables it
+    pub readahead: u64,
+}
+
+impl Default for MmapHints {
+    fn default() -> Self {
+        MmapHints {
+            populate: false,
+            sequential: false,
+            readahead: DEFAULT_READAHEAD,
+        }
+    }
+}
+
+impl MmapHints {
+    /// Whether maps made with `self` and `other` are interchangeable.
+    fn maps_like(&self, other: &MmapHints) -> bool {
+        self.populate == other.populate && self.sequential == other.sequential
+    }
+}
+
+/// Index offset of `offset` within `segment`.
+pub(crate) fn pack(segment: u32, offset: u64) -> u64 {
+    ((segment as u64) << SEGMENT_SHIFT) | offset
+}
+
+/// Split an index offset into segment number and offset within the segment.
+pub(crate) fn split(offset: u64) -> (u32, u64) {
+    (
+        (offset >> SEGMENT_SHIFT) as u32,
+        offset & ((1 << SEGMENT_SHIFT) - 1),
+    )
+}
+
+/// One data file of a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Segment {
+    pub(crate) no: u32,
+    pub(crate) compression: Compression,
+    /// Logical (uncompressed) length
+    pub(crate) len: u64,
+    /// Whether its blocks are sealed (empty segments follow the collection)
+    pub(crate) encrypted: bool,
+}
+
+impl Segment {
+    pub(crate) fn path(&self, root: &Path, collection: &str) -> PathBuf {
+        Layout::data_segment_file(
+            root,
+            collection,
+            self.no,
+            self.compression == Compression::Zstd,
+        )
+    }
+
+    /// Index offset just past the segment's data.
+    pub(crate) fn end(&self) -> u64 {
+        pack(self.no, self.len)
+    }
+}
+
+/// Existing segment files and their formats, sorted by segment number.
+///
+/// A segment present in both formats is left over from a crash while
+/// compaction switched formats: the copy in the `configured` format is the
+/// finished rewrite, and the other one is removed in read-write mode.
+pub(crate) fn list(
+    root: &Path,
+    collection: &str,
+    configured: Compression,
+    mode: OpenMode,
+) -> Result<Vec<(u32, Compression)>> {
+    let dir = Layout::meta_dir(root, collection);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut found: BTreeMap<u32, Vec<Compression>> = BTreeMap::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if let Some((no, compressed)) = name.to_str().and_then(Layout::parse_data_segment) {
+            let compression = if compressed {
+                Compression::Zstd
+            } else {
+                Compression::None
+            };
+            found.entry(no).or_default().push(compression);
+        }
+    }
+
+    let mut segments = Vec::with_capacity(found.len());
+    for (no, formats) in found {
+        if formats.len() == 1 {
+            segments.push((no, formats[0]));
+            continue;
+        }
+        for &stale in formats.iter().filter(|&&c| c != configured) {
+            if mode == OpenMode::ReadWrite {
+                std::fs::remove_file(Layout::data_segment_file(
+                    root,
+                    collection,
+                    no,
+                    stale == Compression::Zstd,
+                ))?;
+            }
+        }
+        segments.push((no, configured));
+    }
+    Ok(segments)
+}
+
+/// Decoded-block cache for [`SegmentView`] reads.
+#[derive(Default)]
+pub(crate) struct ReadCache {
+    segment: Option<u32>,
+    blocks: BlockCache,
+}
+
+/// Read-only view of all segments of a store, addressed by index offsets.
+pub(crate) struct SegmentView {
+    /// Non-empty segments, sorted by number
+    segments: Vec<(u32, Arc<DataView>)>,
+    /// Index offset just past the mapped data of the last segment
+    end: u64,
+    hints: MmapHints,
+}
+
+impl SegmentView {
+    /// Map the segments of a store (`None` if they are all empty).
+    ///
+    /// Mappings in `previous` that still cover a whole segment, and were
+    /// made with the same `hints`, are reused. Encrypted blocks are opened
+    /// with `cipher`.
+    pub(crate) fn open(
+        root: &Path,
+        collection: &str,
+        segments: &[Segment],
+        previous: Option<&SegmentView>,
+        cipher: Option<&Arc<Cipher>>,
+        hints: MmapHints,
+    ) -> Result<Option<Self>> {
+        let previous = previous.filter(|p| p.hints.maps_like(&hints));
+        let mut views = Vec::with_capacity(segments.len());
+        let mut end = 0;
+        for segment in segments {
+            let reused = previous.and_then(|p| p.get(segment.no)).filter(|view| {
+                view.len() == segment.len
+                    && view.compression() == segment.compression
+                    && view.encrypted() == (segment.encrypted && segment.len > 0)
+            });
+            let view = match reused {
+                Some(view) => Some(view.clone()),
+                None => DataView::open(
+                    &segment.path(root, collection),
+                    segment.compression,
+                    cipher,
+                    &hints,
+                )?
+                .map(Arc::new),
+            };
+            end = pack(segment.no, view.as_ref().map_or(0, |v| v.len()));
+            if let Some(view) = view {
+                views.push((segment.no, view));
+            }
+        }
+        if views.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(SegmentView {
+            segments: views,
+            end,
+            hints,
+        }))
+    }
+
+    /// Index offset just past the mapped data.
+    pub(crate) fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Total logical length of the mapped data.
+    pub(crate) fn len(&self) -> u64 {
+        self.segments.iter().map(|(_, view)| view.len()).sum()
+    }
+
+    fn get(&self, no: u32) -> Option<&Arc<DataView>> {
+        self.segments
+            .binary_search_by_key(&no, |(n, _)| *n)
+            .ok()
+            .map(|i| &self.segments[i].1)
+    }
+
+    /// Read the line of an index entry (`None` if out of bounds).
+    pub(crate) fn line<'a>(
+        &'a self,
+        entry: &IndexEntry,
+        cache: &'a mut ReadCache,
+    ) -> Result<Option<&'a [u8]>> {
+        let (no, offset) = split(entry.offset);
+        let Some(view) = self.get(no) else {
+            return Ok(None);
+        };
+        if cache.segment != Some(no) {
+            *cache = ReadCache {
+                segment: Some(no),
+                blocks: BlockCache::default(),
+            };
+        }
+        view.read(offset, entry.length, &mut cache.blocks)
+    }
+
+    /// Ask the OS to read the data between index offsets `start..end` into
+    /// the page cache, without waiting for it.
+    pub(crate) fn prefetch(&self, start: u64, end: u64) {
+        for (no, view) in &self.segments {
+            let (seg_start, seg_end) = (pack(*no, 0), pack(*no, view.len()));
+            if seg_end > start && seg_start < end {
+                view.prefetch(
+                    start.saturating_sub(seg_start),
+                    end.min(seg_end) - seg_start,
+                );
+            }
+        }
+    }
+
+    /// Map the lines of `entries` (sorted by offset) in parallel, keeping order.
+    ///
+    /// The lines are mapped a readahead window at a time (see
+    /// [`MmapHints::readahead`]), prefetching the next window meanwhile.
+    /// Lines that can't be read are skipped, as are `None` results.
+    pub(crate) fn par_map_lines<T, F>(&self, entries: &[IndexEntry], f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&[u8]) -> Option<T> + Sync + Send,
+    {
+        let mut out = Vec::with_capacity(entries.len());
+        let mut rest = entries;
+        // Sorted entries of one segment are contiguous
+        while let Some(first) = rest.first() {
+            let (no, _) = split(first.offset);
+            let (run, tail) = rest.split_at(rest.partition_point(|e| split(e.offset).0 == no));
+            rest = tail;
+            let Some(view) = self.get(no) else {
+                continue;
+            };
+            let local: Cow<'_, [IndexEntry]> = if no == 0 {
+                Cow::Borrowed(run)
+            } else {
+                run.iter()
+                    .map(|e| IndexEntry {
+                        offset: split(e.offset).1,
+                        ..*e
+                    })
+                    .collect()
+            };
+            if self.hints.readahead == 0 {
+                out.extend(view.par_map_lines(&local, &f));
+                continue;
+            }
+            let windows = readahead_windows(&local, self.hints.readahead);
+            if let Some(first) = windows.first() {
+                let (start, end) = span(&local[first.clone()]);
+                view.prefetch(start, end);
+            }
+            for (i, window) in windows.iter().enumerate() {
+                if let Some(next) = windows.get(i + 1) {
+                    let (start, end) = span(&local[next.clone()]);
+                    view.prefetch(start, end);
+                }
+                out.extend(view.par_map_lines(&local[window.clone()], &f));
+            }
+        }
+        out
+    }
+
+    /// Visit the data between index offsets `start..end` as
+    /// `(index offset, bytes)` chunks that begin and end on line boundaries.
+    pub(crate) fn for_each_chunk(
+        &self,
+        start: u64,
+        end: u64,
+        mut f: impl FnMut(u64, &[u8]),
+    ) -> Result<()> {
+        for (no, view) in &self.segments {
+            let (seg_start, seg_end) = (pack(*no, 0), pack(*no, view.len()));
+            if seg_end <= start || seg_start >= end {
+                continue;
+            }
+            let from = start.saturating_sub(seg_start);
+            let to = end.min(seg_end) - seg_start;
+            view.for_each_chunk(from, to, |offset, chunk| f(seg_start + offset, chunk))?;
+        }
+        Ok(())
+    }
+
+    /// All data in segment order (decompressed for compressed segments).
+    pub(crate) fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+        if let [(_, view)] = self.segments.as_slice() {
+            return view.bytes();
+        }
+        let mut data = Vec::with_capacity(self.len() as usize);
+        self.for_each_chunk(0, self.end, |_, chunk| data.extend_from_slice(chunk))?;
+        Ok(Cow::Owned(data))
+    }
+}
+
+/// Split `entries` (sorted by offset) into consecutive runs spanning at
+/// most `bytes` each (and at least one entry).
+fn readahead_windows(entries: &[IndexEntry], bytes: u64) -> Vec<Range<usize>> {
+    let mut windows = Vec::new();
+    let mut start = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if i > start && entry.offset + entry.length as u64 - entries[start].offset > bytes {
+            windows.push(start..i);
+            start = i;
+        }
+    }
+    if start < entries.len() {
+        windows.push(start..entries.len());
+    }
+    windows
+}
+
+/// Offsets `start..end` covered by the lines of `entries` (sorted by
+/// offset, non-empty).
+fn span(entries: &[IndexEntry]) -> (u64, u64) {
+    let last = entries[entries.len() - 1];
+    (entries[0].offset, last.offset + last.length as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets() {
+        assert_eq!(pack(0, 1234), 1234);
+        assert_eq!(split(pack(7, 99)), (7, 99));
+        assert!(pack(1, 0) > pack(0, (1 << SEGMENT_SHIFT) - 1));
+    }
+
+    #[test]
+    fn test_readahead_windows() {
+        let entries: Vec<IndexEntry> = (0..10)
+            .map(|i| IndexEntry {
+                offset: i * 100,
+                length: 100,
+                checksum: 0,
+            })
+            .collect();
+        assert_eq!(
+            readahead_windows(&entries, 250),
+            [0..2, 2..4, 4..6, 6..8, 8..10]
+        );
+        assert_eq!(readahead_windows(&entries, 50).len(), 10);
+        assert_eq!(readahead_windows(&entries, u64::MAX), vec![0..10]);
+        assert_eq!(span(&entries[2..4]), (200, 400));
+        assert!(readahead_windows(&[], 250).is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+//! Parallel ingestion through sharded data segments.
+//!
+//! A [`FastStore`](crate::FastStore) appends every document to its one
+//! active segment, so ingestion runs on a single core. A [`ShardedWriter`]
+//! opens `N` new segments at once and routes each document to one of them
+//! by a hash of its ID. Producer threads serialize documents in parallel
+//! and only contend when they write to the same shard.
+//! [`FastStore::merge_shards`](crate::FastStore::merge_shards) then adds the
+//! segments to the store and indexes their lines, after which they are
+//! ordinary sealed segments.
+//!
+//! Routing by ID keeps every write of a document in one shard, in the order
+//! it was made, so the last write still wins. A writer dropped without
+//! being merged deletes its segments; after a crash, the lines its shards
+//! had flushed are recovered by the next open like any unindexed lines.
+
+use std::{
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHasher;
+use serde_json::Value;
+
+use crate::{
+    compression::{Compression, DataWriter},
+    encryption::Cipher,
+    fast_writer::{line_checksum, parse_raw_doc},
+    json_schema::JsonSchema,
+    schema::SchemaRegistry,
+    segment::Segment,
+    Layout, Result,
+};
+
+/// Concurrent writer into new data segments of a store, created by
+/// [`FastStore::sharded_writer`](crate::FastStore::sharded_writer).
+///
+/// All methods take `&self`, so the writer can be shared by reference with
+/// scoped threads or a rayon pool.
+pub struct ShardedWriter {
+    root: PathBuf,
+    collection: String,
+    shards: Vec<Mutex<Shard>>,
+    /// JSON Schema of the collection, checked on every write
+    json_schema: Option<JsonSchema>,
+    /// Pauses the store's own appends while alive
+    handle: Arc<()>,
+}
+
+/// One segment being written by a [`ShardedWriter`].
+pub(crate) struct Shard {
+    pub(crate) segment: Segment,
+    writer: DataWriter,
+    /// Lines written, in order
+    pub(crate) lines: Vec<ShardLine>,
+}
+
+/// A line written to a shard, to be indexed on merge.
+pub(crate) struct ShardLine {
+    pub(crate) doc_id: String,
+    /// Offset within the shard's segment
+    pub(crate) offset: u64,
+    /// Length including the newline
+    pub(crate) length: u32,
+    pub(crate) checksum: u32,
+    pub(crate) schema_id: Option<String>,
+}
+
+impl ShardedWriter {
+    /// Create the segments `first..first + shards` of a collection.
+    pub(crate) fn create(
+        root: &Path,
+        collection: &str,
+        first: u32,
+        shards: usize,
+        compression: Compression,
+        cipher: Option<&Arc<Cipher>>,
+        json_schema: Option<JsonSchema>,
+    ) -> Result<Self> {
+        let mut writer = ShardedWriter {
+            root: root.to_path_buf(),
+            collection: collection.to_string(),
+            shards: Vec::with_capacity(shards),
+            json_schema,
+            handle: Arc::new(()),
+        };
+        for no in (first..).take(shards) {
+            let segment = Segment {
+                no,
+                compression,
+                len: 0,
+                encrypted: cipher.is_some(),
+            };
+            let file = DataWriter::append(&segment.path(root, collection), compression, cipher)?;
+            writer.shards.push(Mutex::new(Shard {
+                segment,
+                writer: file,
+                lines: Vec::new(),
+            }));
+        }
+        Ok(writer)
+    }
+
+    /// Marker the store holds weakly to know the writer is alive.
+    pub(crate) fn handle(&self) -> Weak<()> {
+        Arc::downgrade(&self.handle)
+    }
+
+    /// Number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Number of lines written so far.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().lines.len())
+            .sum()
+    }
+
+    /// Check if nothing was written yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Put a document, like [`FastStore::put`](crate::FastStore::put).
+    pub fn put(&self, doc_id: impl Into<String>, mut doc: Value) -> Result<()> {
+        let doc_id = doc_id.into();
+        Layout::validate_doc_id(&doc_id)?;
+        if let Value::Object(obj) = &mut doc {
+            obj.remove("_id");
+        }
+        if let Some(json_schema) = &self.json_schema {
+            json_schema.validate(&doc)?;
+        }
+        let schema_id = SchemaRegistry::compute_schema_id(&doc);
+
+        let mut doc_with_id = serde_json::Map::new();
+        doc_with_id.insert("_id".to_string(), Value::String(doc_id.clone()));
+        if let Value::Object(obj) = doc {
+            doc_with_id.extend(obj);
+        }
+        let line = serde_json::to_vec(&Value::Object(doc_with_id))?;
+        self.append(doc_id, &line, Some(schema_id))
+    }
+
+    /// Put a document as a raw JSON line with its `_id` included, like
+    /// [`FastStore::put_raw_line`](crate::FastStore::put_raw_line).
+    pub fn put_raw_line(&self, doc_id: impl Into<String>, line_bytes: &[u8]) -> Result<()> {
+        if let Some(json_schema) = &self.json_schema {
+            json_schema.validate(&parse_raw_doc(line_bytes)?)?;
+        }
+        self.append(doc_id.into(), line_bytes, None)
+    }
+
+    /// Write a line to the shard of its document.
+    fn append(&self, doc_id: String, line: &[u8], schema_id: Option<String>) -> Result<()> {
+        let mut hasher = FxHasher::default();
+        doc_id.hash(&mut hasher);
+        let mut shard = self.shards[hasher.finish() as usize % self.shards.len()].lock();
+
+        shard.writer.write_all(line)?;
+        shard.writer.write_all(b"\n")?;
+        let offset = shard.segment.len;
+        let length = line.len() as u32 + 1;
+        shard.segment.len += length as u64;
+        shard.lines.push(ShardLine {
+            doc_id,
+            offset,
+            length,
+            checksum: line_checksum(line),
+            schema_id,
+        });
+        Ok(())
+    }
+
+    /// Flush and sync the shards and hand them over for merging.
+    pub(crate) fn finish(mut self) -> Result<Vec<Shard>> {
+        for shard in &self.shards {
+            let mut shard = shard.lock();
+            shard.writer.flush()?;
+            shard.writer.sync_data()?;
+        }
+        Ok(std::mem::take(&mut self.shards)
+            .into_iter()
+            .map(Mutex::into_inner)
+            .collect())
+    }
+}
+
+impl Drop for ShardedWriter {
+    // Deletes the segments of a writer that wasn't merged
+    fn drop(&mut self) {
+        for shard in self.shards.drain(..) {
+            let shard = shard.into_inner();
+            drop(shard.writer);
+            let _ = std::fs::remove_file(shard.segment.path(&self.root, &self.collection));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::{Compression, Error, FastStore, Predicate};
+
+    #[test]
+    fn test_sharded_writer() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"n": -1})).unwrap();
+        store.put("b", json!({"n": -2})).unwrap();
+        store.create_index("n").unwrap();
+
+        let writer = store.sharded_writer(4).unwrap();
+        assert!(matches!(
+            store.put("c", json!({})),
+            Err(Error::Validation(_))
+        ));
+        assert!(store.compact().is_err());
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let writer = &writer;
+                s.spawn(move || {
+                    for i in (t..1000).step_by(4) {
+                        writer.put(format!("doc{:04}", i), json!({"n": i})).unwrap();
+                    }
+                });
+            }
+        });
+        writer
+            .put_raw_line("a", br#"{"_id":"a","n":1000}"#)
+            .unwrap();
+        assert_eq!(writer.len(), 1001);
+        assert!(!store.exists("doc0000"));
+
+        assert_eq!(store.merge_shards(writer).unwrap(), 1001);
+        assert_eq!(store.len(), 1002);
+        assert_eq!(store.get("doc0500").unwrap()["n"], 500);
+        assert_eq!(store.get("a").unwrap()["n"], 1000);
+        assert_eq!(store.find(&Predicate::eq("n", 1000)).unwrap().len(), 1);
+        assert_eq!(store.segment_count(), 5);
+        store.put("c", json!({"n": 7})).unwrap();
+        drop(store);
+
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 1003);
+        assert_eq!(store.get("doc0999").unwrap()["n"], 999);
+        assert_eq!(store.get("c").unwrap()["n"], 7);
+
+        // A writer dropped without merging discards its writes
+        let writer = store.sharded_writer(2).unwrap();
+        writer.put("x", json!({})).unwrap();
+        drop(writer);
+        assert_eq!(store.segment_count(), 5);
+        store.put("y", json!({})).unwrap();
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert!(!store.exists("x") && store.exists("y"));
+    }
+
+    #[test]
+    fn test_sharded_writer_compressed() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.set_compression(Compression::Zstd).unwrap();
+        let writer = store.sharded_writer(3).unwrap();
+        for i in 0..300 {
+            writer.put(format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        // The key wins over an `_id` in the document
+        writer.put("doc300", json!({"_id": "x", "n": 300})).unwrap();
+
+        // Writers of another store are refused
+        let mut other = FastStore::open(tmp.path(), "other", 100).unwrap();
+        let foreign = other.sharded_writer(1).unwrap();
+        assert!(store.merge_shards(foreign).is_err());
+
+        assert_eq!(store.merge_shards(writer).unwrap(), 301);
+        drop(store);
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 301);
+        assert_eq!(store.get("doc123").unwrap()["n"], 123);
+        assert_eq!(store.scan().unwrap().len(), 301);
+        assert!(!store.exists("x"));
+        assert!(store.verify().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+//! Thread-safe handle to a [`FastStore`].
+//!
+//! [`SharedStore`] wraps a store in a reader-writer lock behind an `Arc`,
+//! so clones can be handed to any number of threads. Reads (`get`, `scan`,
+//! ...) take `&self` on the store and run concurrently with each other;
+//! writes take the lock exclusively, but only for as long as it takes to
+//! buffer the line, so readers wait at most for one append (or a flush).
+//!
+//! A handle can be closed from any clone: the store is flushed and
+//! released, and further access fails instead of blocking on a store that
+//! no longer exists.
+
+use std::{path::Path, sync::Arc};
+
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+use serde_json::Value;
+
+use crate::{Error, FastStore, Result};
+
+/// Read access to the store of a [`SharedStore`].
+pub type StoreReadGuard<'a> = MappedRwLockReadGuard<'a, FastStore>;
+
+/// Write access to the store of a [`SharedStore`].
+pub type StoreWriteGuard<'a> = MappedRwLockWriteGuard<'a, FastStore>;
+
+/// A [`FastStore`] that can be shared between threads.
+#[derive(Clone)]
+pub struct SharedStore {
+    /// `None` once closed
+    inner: Arc<RwLock<Option<FastStore>>>,
+}
+
+impl SharedStore {
+    /// Share an open store.
+    pub fn new(store: FastStore) -> Self {
+        SharedStore {
+            inner: Arc::new(RwLock::new(Some(store))),
+        }
+    }
+
+    /// Open a collection read-write and share it (see [`FastStore::open`]).
+    pub fn open(root: impl AsRef<Path>, collection: &str, batch_size: usize) -> Result<Self> {
+        FastStore::open(root, collection, batch_size).map(Self::new)
+    }
+
+    /// Lock the store for reading. Any number of threads can hold a read
+    /// guard at once.
+    pub fn read(&self) -> Result<StoreReadGuard<'_>> {
+        RwLockReadGuard::try_map(self.inner.read(), Option::as_ref).map_err(|_| closed())
+    }
+
+    /// Lock the store for writing, waiting for the readers to finish.
+    pub fn write(&self) -> Result<StoreWriteGuard<'_>> {
+        RwLockWriteGuard::try_map(self.inner.write(), Option::as_mut).map_err(|_| closed())
+    }
+
+    /// Flush pending writes and release the store for every clone of this
+    /// handle. Closing twice is a no-op.
+    pub fn close(&self) -> Result<()> {
+        let mut slot = self.inner.write();
+        if let Some(store) = slot.as_mut() {
+            store.flush()?;
+        }
+        *slot = None;
+        Ok(())
+    }
+
+    /// Whether the store was closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.read().is_none()
+    }
+
+    /// Take the store back, if this is its last handle and it is open.
+    pub fn into_inner(self) -> Option<FastStore> {
+        Arc::try_unwrap(self.inner).ok()?.into_inner()
+    }
+
+    /// Get a document by ID (see [`FastStore::get`]).
+    pub fn get(&self, doc_id: &str) -> Result<Value> {
+        self.read()?.get(doc_id)
+    }
+
+    /// Get several documents at once (see [`FastStore::get_many`]).
+    pub fn get_many<S: AsRef<str>>(&self, doc_ids: &[S]) -> Result<Vec<Option<Value>>> {
+        self.read()?.get_many(doc_ids)
+    }
+
+    /// Whether a document exists; `false` once closed.
+    pub fn exists(&self, doc_id: &str) -> bool {
+        self.read().is_ok_and(|store| store.exists(doc_id))
+    }
+
+    /// Number of documents; 0 once closed.
+    pub fn len(&self) -> usize {
+        self.read().map_or(0, |store| store.len())
+    }
+
+    /// Whether the store has no documents (or is closed).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All documents (see [`FastStore::scan`]).
+    pub fn scan(&self) -> Result<Vec<Value>> {
+        self.read()?.scan()
+    }
+
+    /// Put a document (see [`FastStore::put`]).
+    pub fn put(&self, doc_id: impl Into<String>, doc: Value) -> Result<()> {
+        self.write()?.put(doc_id, doc)
+    }
+
+    /// Delete a document (see [`FastStore::delete`]).
+    pub fn delete(&self, doc_id: &str) -> Result<()> {
+        self.write()?.delete(doc_id)
+    }
+
+    /// Flush pending writes (see [`FastStore::flush`]).
+    pub fn flush(&self) -> Result<()> {
+        self.write()?.flush()
+    }
+}
+
+impl From<FastStore> for SharedStore {
+    fn from(store: FastStore) -> Self {
+        SharedStore::new(store)
+    }
+}
+
+fn closed() -> Error {
+    Error::Validation("store is closed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::thread;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_shared_store_threads() {
+        let tmp = TempDir::new().unwrap();
+        let store = SharedStore::open(tmp.path(), "test", 100).unwrap();
+        for i in 0..100 {
+            store.put(format!("doc{:03}", i), json!({"n": i})).unwrap();
+        }
+
+        // Readers run alongside a writer appending to the same store
+        let writer = {
+            let store = store.clone();
+            thread::spawn(move || {
+                for i in 100..400 {
+                    store.put(format!("doc{:03}", i), json!({"n": i})).unwrap();
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let doc = store.get(&format!("doc{:03}", i)).unwrap();
+                        assert_eq!(doc["n"], i);
+                    }
+                    assert!(store.scan().unwrap().len() >= 100);
+                })
+            })
+            .collect();
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(store.len(), 400);
+
+        // Closing through one clone closes them all
+        let other = store.clone();
+        store.close().unwrap();
+        assert!(other.is_closed());
+        assert!(matches!(other.get("doc000"), Err(Error::Validation(_))));
+        assert!(other.put("x", json!({})).is_err());
+        assert_eq!(other.len(), 0);
+        store.close().unwrap();
+        drop(store);
+        assert!(other.into_inner().is_none());
+
+        let store = SharedStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.len(), 400);
+        assert_eq!(store.into_inner().unwrap().len(), 400);
+    }
+}
@@ -0,0 +1,436 @@
+//! Point-in-time snapshots of fast store collections.
+//!
+//! Data segments and the tombstone file are append-only between
+//! compactions, so a snapshot only records their lengths and copies the
+//! small metadata files (index, order, schemas, field and text indexes) to
+//! `collections/<name>/snapshots/<snapshot>/`. Restoring truncates the data
+//! back to those lengths and copies the metadata back, without copying any
+//! data.
+//!
+//! A compaction (explicit, automatic on flush, or after a merge) rewrites
+//! segments, so a snapshot taken before it can no longer be restored. The
+//! compaction marks the snapshots pointing into the segments it rewrites as
+//! stale before replacing them, and each recorded file also keeps a
+//! checksum of its last bytes to catch rewrites by older versions
+//! ([`Snapshot::is_valid`]).
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{lock::WriteLock, Error, Layout, Result, TransactionLog};
+
+/// Metadata file of a snapshot directory.
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// Bytes at the end of a recorded file covered by its checksum.
+const TAIL_BYTES: u64 = 4096;
+
+/// A saved point-in-time view of a collection.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Number of documents when the snapshot was taken
+    pub documents: usize,
+    /// Data segments and tombstone file, with their lengths at the time
+    files: Vec<FileMark>,
+    /// Time a compaction rewrote files the snapshot points into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_since: Option<DateTime<Utc>>,
+}
+
+/// Length of an append-only file and a checksum of the bytes before it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct FileMark {
+    name: String,
+    len: u64,
+    tail_crc: u32,
+}
+
+impl FileMark {
+    fn new(path: &Path, name: String) -> Result<Self> {
+        let len = std::fs::metadata(path)?.len();
+        Ok(FileMark {
+            tail_crc: tail_crc(path, len)?,
+            name,
+            len,
+        })
+    }
+
+    /// Whether the file still starts with the recorded bytes.
+    fn matches(&self, meta_dir: &Path) -> Result<bool> {
+        let path = meta_dir.join(&self.name);
+        match std::fs::metadata(&path) {
+            Ok(m) if m.len() >= self.len => Ok(tail_crc(&path, self.len)? == self.tail_crc),
+            Ok(_) => Ok(false),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// CRC32 of the last [`TAIL_BYTES`] of `path` before `len`.
+fn tail_crc(path: &Path, len: u64) -> Result<u32> {
+    let start = len.saturating_sub(TAIL_BYTES);
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut buf = Vec::with_capacity((len - start) as usize);
+    file.take(len - start).read_to_end(&mut buf)?;
+    Ok(crc32fast::hash(&buf))
+}
+
+/// Whether a meta directory file is append-only data tracked by length.
+fn is_log_file(name: &str) -> bool {
+    name == Layout::TOMBSTONE_FILE || Layout::parse_data_segment(name).is_some()
+}
+
+/// Whether a meta directory file is copied into snapshots.
+fn is_snapshotted(name: &str) -> bool {
+    !is_log_file(name)
+        && name != Layout::JOURNAL_FILE
+        && !name.ends_with(".lock")
+        && !name.ends_with(".tmp")
+}
+
+impl Snapshot {
+    /// Record the current state of a collection as snapshot `name`.
+    ///
+    /// The collection's writes must be flushed, and no other process may
+    /// write to it meanwhile; use [`FastStore::snapshot`](crate::FastStore::snapshot),
+    /// which flushes first.
+    pub(crate) fn create(
+        root: &Path,
+        collection: &str,
+        name: &str,
+        documents: usize,
+    ) -> Result<Self> {
+        if Layout::validate_collection_name(name).is_err() || name.ends_with(".tmp") {
+            return Err(Error::Validation(format!(
+                "invalid snapshot name: {}",
+                name
+            )));
+        }
+        if !Layout::has_fast_data(root, collection) {
+            return Err(Error::Validation(format!(
+                "collection '{}' has no fast store data to snapshot",
+                collection
+            )));
+        }
+        let dir = Self::dir(root, collection, name);
+        if dir.exists() {
+            return Err(Error::Validation(format!(
+                "snapshot '{}' already exists",
+                name
+            )));
+        }
+
+        // Built next to its final place, so a failed snapshot never lists
+        let tmp_dir = Layout::snapshots_dir(root, collection).join(format!("{}.tmp", name));
+        if tmp_dir.exists() {
+            std::fs::remove_dir_all(&tmp_dir)?;
+        }
+        std::fs::create_dir_all(&tmp_dir)?;
+
+        let meta_dir = Layout::meta_dir(root, collection);
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&meta_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if is_log_file(&file_name) {
+                files.push(FileMark::new(&entry.path(), file_name)?);
+            } else if is_snapshotted(&file_name) {
+                copy_recursive(&entry.path(), &tmp_dir.join(&file_name))?;
+            }
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let snapshot = Snapshot {
+            name: name.to_string(),
+            created_at: Utc::now(),
+            documents,
+            files,
+            stale_since: None,
+        };
+        std::fs::write(
+            tmp_dir.join(SNAPSHOT_FILE),
+            serde_json::to_string_pretty(&snapshot)?,
+        )?;
+        std::fs::rename(&tmp_dir, &dir)?;
+        Ok(snapshot)
+    }
+
+    /// Mark the snapshots recording any of the meta directory files
+    /// `rewritten` as stale, before a compaction replaces them.
+    pub(crate) fn invalidate(root: &Path, collection: &str, rewritten: &[String]) -> Result<()> {
+        for mut snapshot in Self::list(root, collection)? {
+            if snapshot.stale_since.is_some()
+                || !snapshot.files.iter().any(|m| rewritten.contains(&m.name))
+            {
+                continue;
+            }
+            snapshot.stale_since = Some(Utc::now());
+            let path = Self::dir(root, collection, &snapshot.name).join(SNAPSHOT_FILE);
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, serde_json::to_string_pretty(&snapshot)?)?;
+            std::fs::rename(&tmp, &path)?;
+        }
+        Ok(())
+    }
+
+    /// Load snapshot `name` of a collection.
+    pub fn load(root: &Path, collection: &str, name: &str) -> Result<Self> {
+        let path = Self::dir(root, collection, name).join(SNAPSHOT_FILE);
+        let content = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                Error::Validation(format!("no snapshot named '{}'", name))
+            }
+            _ => e.into(),
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Snapshots of a collection, oldest first.
+    pub fn list(root: &Path, collection: &str) -> Result<Vec<Self>> {
+        let dir = Layout::snapshots_dir(root, collection);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut snapshots = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".tmp") && entry.path().join(SNAPSHOT_FILE).exists() {
+                snapshots.push(Self::load(root, collection, &name)?);
+            }
+        }
+        snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.name.cmp(&b.name)));
+        Ok(snapshots)
+    }
+
+    /// Delete snapshot `name` of a collection.
+    pub fn delete(root: &Path, collection: &str, name: &str) -> Result<()> {
+        Self::load(root, collection, name)?;
+        std::fs::remove_dir_all(Self::dir(root, collection, name))?;
+        Ok(())
+    }
+
+    /// Whether the collection's data still holds this snapshot's data
+    /// (false once a compaction has rewritten it).
+    pub fn is_valid(&self, root: &Path, collection: &str) -> Result<bool> {
+        if self.stale_since.is_some() {
+            return Ok(false);
+        }
+        let meta_dir = Layout::meta_dir(root, collection);
+        for mark in &self.files {
+            if !mark.matches(&meta_dir)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Roll a collection back to snapshot `name`.
+    ///
+    /// Writes made since are discarded: data segments and the tombstone
+    /// file are cut back to their recorded lengths and the metadata files
+    /// are replaced by the snapshot's copies. The collection must not be
+    /// open; the store's write lock is held while restoring. Fails with
+    /// [`Error::Validation`] if a compaction has rewritten the data since.
+    pub fn restore(root: &Path, collection: &str, name: &str) -> Result<Self> {
+        let snapshot = Self::load(root, collection, name)?;
+        let _lock = WriteLock::acquire(root)?;
+        if !snapshot.is_valid(root, collection)? {
+            return Err(Error::Validation(format!(
+                "snapshot '{}' can no longer be restored: the collection was compacted since",
+                name
+            )));
+        }
+
+        let meta_dir = Layout::meta_dir(root, collection);
+        let snapshot_dir = Self::dir(root, collection, name);
+        for entry in std::fs::read_dir(&meta_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if is_log_file(&file_name) {
+                match snapshot.files.iter().find(|m| m.name == file_name) {
+                    Some(mark) => OpenOptions::new()
+                        .write(true)
+                        .open(entry.path())?
+                        .set_len(mark.len)?,
+                    // Started after the snapshot
+                    None => std::fs::remove_file(entry.path())?,
+                }
+            } else if is_snapshotted(&file_name) {
+                remove_recursive(&entry.path())?;
+            }
+        }
+        for entry in std::fs::read_dir(&snapshot_dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            if file_name != SNAPSHOT_FILE {
+                copy_recursive(&entry.path(), &meta_dir.join(&file_name))?;
+            }
+        }
+
+        // An interrupted transaction must not be rolled back over the
+        // restored files
+        TransactionLog::open(root, collection)?.checkpoint()?;
+        Ok(snapshot)
+    }
+
+    fn dir(root: &Path, collection: &str, name: &str) -> PathBuf {
+        Layout::snapshots_dir(root, collection).join(name)
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+fn remove_recursive(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{fast_writer::CompactionPolicy, FastStore};
+
+    #[test]
+    fn test_snapshot_restore() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        store.put("a", json!({"n": 1})).unwrap();
+        store.put("b", json!({"n": 2})).unwrap();
+        store.create_index("n").unwrap();
+        let snapshot = store.snapshot("v1").unwrap();
+        assert_eq!(snapshot.documents, 2);
+
+        store.put("a", json!({"n": 10})).unwrap();
+        store.put("c", json!({"n": 3})).unwrap();
+        store.delete("b").unwrap();
+        store.drop_index("n").unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        assert!(matches!(
+            store_snapshot(tmp.path(), "v1"),
+            Err(Error::Validation(_))
+        ));
+        let listed = Snapshot::list(tmp.path(), "train").unwrap();
+        assert_eq!(listed, [snapshot]);
+        assert!(listed[0].is_valid(tmp.path(), "train").unwrap());
+
+        Snapshot::restore(tmp.path(), "train", "v1").unwrap();
+        let store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get("a").unwrap()["n"], 1);
+        assert_eq!(store.get("b").unwrap()["n"], 2);
+        assert!(!store.exists("c"));
+        assert!(store.field_indexes().get("n").is_some());
+        drop(store);
+
+        assert!(Snapshot::restore(tmp.path(), "train", "missing").is_err());
+        Snapshot::delete(tmp.path(), "train", "v1").unwrap();
+        assert!(Snapshot::list(tmp.path(), "train").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_invalidated_by_compaction() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        store.put("a", json!({"n": 1})).unwrap();
+        store.put("b", json!({"n": 2})).unwrap();
+        store.snapshot("before").unwrap();
+
+        store.delete("a").unwrap();
+        store.compact().unwrap();
+        drop(store);
+
+        let snapshot = Snapshot::load(tmp.path(), "train", "before").unwrap();
+        assert!(snapshot.stale_since.is_some());
+        assert!(!snapshot.is_valid(tmp.path(), "train").unwrap());
+        assert!(matches!(
+            Snapshot::restore(tmp.path(), "train", "before"),
+            Err(Error::Validation(_))
+        ));
+        let store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get("b").unwrap()["n"], 2);
+    }
+
+    #[test]
+    fn test_snapshot_invalidated_by_auto_compaction() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        store.set_compaction_policy(Some(CompactionPolicy {
+            min_dead_ratio: 0.3,
+            min_dead_bytes: 0,
+        }));
+        for i in 0..10 {
+            store.put(format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        store.snapshot("before").unwrap();
+
+        // Deleting most documents makes the next flush compact
+        for i in 0..8 {
+            store.delete(&format!("doc{}", i)).unwrap();
+        }
+        store.flush().unwrap();
+        assert_eq!(store.stats().dead_bytes, 0);
+        drop(store);
+
+        let snapshot = Snapshot::load(tmp.path(), "train", "before").unwrap();
+        assert!(snapshot.stale_since.is_some());
+        assert!(matches!(
+            Snapshot::restore(tmp.path(), "train", "before"),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_kept_by_compaction_elsewhere() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        store.put("a", json!({"n": 1})).unwrap();
+        store.snapshot("before").unwrap();
+
+        // Nothing dead to compact: the data stays as the snapshot saw it
+        store.put("b", json!({"n": 2})).unwrap();
+        store.compact().unwrap();
+        drop(store);
+
+        let snapshot = Snapshot::load(tmp.path(), "train", "before").unwrap();
+        assert_eq!(snapshot.stale_since, None);
+        Snapshot::restore(tmp.path(), "train", "before").unwrap();
+        let store = FastStore::open(tmp.path(), "train", 100).unwrap();
+        assert_eq!(store.doc_ids(), ["a"]);
+    }
+
+    fn store_snapshot(root: &Path, name: &str) -> Result<Snapshot> {
+        FastStore::open(root, "train", 100)?.snapshot(name)
+    }
+}
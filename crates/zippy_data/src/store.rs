@@ -0,0 +1,188 @@
+//! A common interface over the storage modes.
+//!
+//! [`Store`] is implemented by [`FastStore`] for JSONL collections (on disk
+//! or [in memory](FastStore::open_in_memory)) and by [`SyncWriter`] for
+//! file-per-document collections, so code that only reads and writes
+//! documents can work with either. [`open_store`] opens a collection with
+//! the implementation its storage mode calls for.
+//!
+//! Its read half, [`ReadStore`], is also implemented by the
+//! [`Engine`](crate::Engine), which reads every kind of container,
+//! archives and remote stores included; [`open_read_store`] opens one.
+//!
+//! The Python and Node.js bindings wrap a [`FastStore`] directly: their
+//! stores are JSONL collections, with raw-line batches, compaction and
+//! vector search that the trait doesn't cover.
+
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{
+    engine::{CollectionStats, StorageMode},
+    writer::SyncWriter,
+    Engine, FastStore, IdStrategy, Result,
+};
+
+/// Batch size of the fast stores opened by [`open_store`].
+const STORE_BATCH_SIZE: usize = 1000;
+
+/// Reads and writes of a collection's documents, whatever its storage
+/// mode.
+///
+/// Writes are readable once [flushed](Store::flush); file-per-document
+/// stores write through, JSONL stores buffer.
+pub trait Store: ReadStore {
+    /// Write a document, replacing any document with the same ID.
+    fn put(&mut self, doc_id: &str, doc: Value) -> Result<()>;
+
+    /// Write a document given as a raw JSON line whose `_id` is `doc_id`
+    /// (see [`FastStore::put_raw_line`]).
+    fn put_raw_line(&mut self, doc_id: &str, line: &[u8]) -> Result<()>;
+
+    /// Write a document under a generated ID, and return the ID.
+    fn put_auto(&mut self, doc: Value, strategy: IdStrategy) -> Result<String>;
+
+    /// Apply a JSON merge patch (RFC 7386) to a document and return the
+    /// patched document.
+    fn patch(&mut self, doc_id: &str, patch: &Value) -> Result<Value>;
+
+    /// Delete a document.
+    fn delete(&mut self, doc_id: &str) -> Result<()>;
+
+    /// Make writes durable and readable, and save the indexes.
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// Reads of a collection's documents, whatever its storage mode or
+/// container.
+pub trait ReadStore: Send {
+    /// Storage mode of the collection.
+    fn storage(&self) -> StorageMode;
+
+    /// Get a document by ID.
+    fn get(&self, doc_id: &str) -> Result<Value>;
+
+    /// Whether a document exists.
+    fn exists(&self, doc_id: &str) -> bool;
+
+    /// IDs of all documents.
+    fn doc_ids(&self) -> Vec<String>;
+
+    /// All documents.
+    fn scan(&self) -> Result<Vec<Value>>;
+
+    /// Number of documents.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Collection statistics.
+    fn stats(&self) -> CollectionStats;
+}
+
+/// Open a local collection for reading and writing with the store its
+/// storage mode calls for: a [`FastStore`] for JSONL collections, a
+/// [`SyncWriter`] for file-per-document ones (see [`StorageMode::detect`]).
+pub fn open_store(root: impl AsRef<Path>, collection: &str) -> Result<Box<dyn Store>> {
+    let root = root.as_ref();
+    Ok(match StorageMode::detect(root, collection)? {
+        StorageMode::Jsonl => Box::new(FastStore::open(root, collection, STORE_BATCH_SIZE)?),
+        StorageMode::Files => Box::new(SyncWriter::new(root, collection)?),
+    })
+}
+
+/// Open a collection for reading, in a folder, an archive or (with the
+/// `remote` feature) a remote container, through the [`Engine`].
+pub fn open_read_store(
+    container_path: impl AsRef<Path>,
+    collection: &str,
+) -> Result<Box<dyn ReadStore>> {
+    Ok(Box::new(Engine::open(container_path, collection)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::config::CollectionConfig;
+
+    /// The same writes and reads through any store.
+    fn exercise(store: &mut dyn Store) {
+        store.put("a", json!({"n": 1})).unwrap();
+        let id = store
+            .put_auto(json!({"n": 2}), IdStrategy::Sequence)
+            .unwrap();
+        store.put("c", json!({"n": 3})).unwrap();
+        store.put_raw_line("r", br#"{"_id":"r","n":4}"#).unwrap();
+        assert!(store.put_raw_line("s", br#"{"_id":"r"}"#).is_err());
+        store.delete("c").unwrap();
+        store.flush().unwrap();
+        let patched = store.patch("a", &json!({"m": true})).unwrap();
+        assert_eq!(patched, json!({"n": 1, "m": true}));
+        store.flush().unwrap();
+
+        assert_eq!(store.get("a").unwrap(), patched);
+        assert!(store.exists(&id) && !store.exists("c"));
+        let mut ids = store.doc_ids();
+        ids.sort();
+        assert_eq!(ids, ["1", "a", "r"]);
+        assert_eq!(store.get("r").unwrap(), json!({"n": 4}));
+        assert_eq!(store.scan().unwrap().len(), 3);
+        assert_eq!(store.stats().doc_count, 3);
+        assert!(store.get("c").is_err());
+    }
+
+    #[test]
+    fn test_stores() {
+        let tmp = TempDir::new().unwrap();
+        let mut files = open_store(tmp.path(), "files").unwrap();
+        assert_eq!(files.storage(), StorageMode::Files);
+        exercise(files.as_mut());
+
+        let config = CollectionConfig {
+            storage: Some(StorageMode::Jsonl),
+            ..Default::default()
+        };
+        config.save(tmp.path(), "jsonl").unwrap();
+        let mut jsonl = open_store(tmp.path(), "jsonl").unwrap();
+        assert_eq!(jsonl.storage(), StorageMode::Jsonl);
+        exercise(jsonl.as_mut());
+
+        let mut memory = FastStore::open_in_memory("test", 100).unwrap();
+        exercise(&mut memory);
+    }
+
+    #[test]
+    fn test_read_stores() {
+        let tmp = TempDir::new().unwrap();
+        let mut files = open_store(tmp.path(), "files").unwrap();
+        exercise(files.as_mut());
+        drop(files);
+        let mut jsonl = FastStore::open(tmp.path(), "jsonl", 100).unwrap();
+        exercise(&mut jsonl);
+        drop(jsonl);
+
+        let out = TempDir::new().unwrap();
+        let archive = out.path().join("data.zds");
+        crate::container::pack(tmp.path(), &archive).unwrap();
+        for (collection, storage) in [("files", StorageMode::Files), ("jsonl", StorageMode::Jsonl)]
+        {
+            for path in [tmp.path(), archive.as_path()] {
+                let store = open_read_store(path, collection).unwrap();
+                assert_eq!(store.storage(), storage);
+                let mut ids = store.doc_ids();
+                ids.sort();
+                assert_eq!(ids, ["1", "a", "r"]);
+                assert_eq!(store.get("r").unwrap(), json!({"n": 4}));
+                assert!(store.exists("a") && !store.exists("c"));
+                assert_eq!(store.scan().unwrap().len(), 3);
+                assert_eq!(store.stats().doc_count, 3);
+            }
+        }
+    }
+}
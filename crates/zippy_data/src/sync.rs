@@ -0,0 +1,389 @@
+//! One-way sync of fast store collections between containers.
+//!
+//! [`sync_with`] makes each destination collection hold the same documents
+//! as the source. Documents are compared by the length and CRC32 of their
+//! stored lines, straight from the two indexes, so nothing is parsed and
+//! only new or changed lines are read from the source and appended to the
+//! destination. Lines are copied as they are, with their expiry times.
+//!
+//! The destination flushes every [`SYNC_BATCH`] documents, so an
+//! interrupted sync keeps what it copied and running it again picks up
+//! where it stopped.
+//!
+//! The source can be a local folder container or, with the `remote`
+//! feature, an `s3://` or `gs://` one; the destination is always a local
+//! folder, created if missing.
+
+use std::path::Path;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    container::is_remote_url,
+    fast_writer::{FastStore, IndexEntry, OpenMode},
+    ContainerFS, Error, Layout, Result, StorageMode,
+};
+
+/// Documents written to the destination between flushes.
+pub const SYNC_BATCH: usize = 1000;
+
+/// Options for [`sync_with`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncOptions {
+    /// Collections to sync; empty means every collection in the source
+    pub collections: Vec<String>,
+    /// Remove destination documents the source doesn't have
+    pub delete: bool,
+    /// Only count what would change
+    pub dry_run: bool,
+}
+
+/// What [`sync_with`] changed, summed over collections.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Bytes of document lines copied
+    pub bytes: u64,
+}
+
+/// Progress of [`sync_with`], reported after every document copied or
+/// removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncProgress {
+    /// Collection being synced
+    pub collection: String,
+    /// Documents copied or removed so far in this collection
+    pub docs_done: usize,
+    /// Documents to copy or remove in this collection
+    pub docs_total: usize,
+}
+
+/// A source collection's documents: a local store or a remote reader.
+enum SourceCollection {
+    Local(Box<FastStore>),
+    #[cfg(feature = "remote")]
+    Remote(Box<crate::remote::RemoteJsonl>),
+}
+
+impl SourceCollection {
+    fn open(container: &ContainerFS, collection: &str) -> Result<Self> {
+        match container {
+            ContainerFS::Folder(root) => {
+                if StorageMode::detect(root, collection)? != StorageMode::Jsonl {
+                    return Err(Error::Validation(format!(
+                        "collection '{}' isn't a fast store collection and can't be synced",
+                        collection
+                    )));
+                }
+                let store = FastStore::open_with_mode(root, collection, 1, OpenMode::Read)?;
+                Ok(SourceCollection::Local(Box::new(store)))
+            }
+            #[cfg(feature = "remote")]
+            ContainerFS::Remote(store) => {
+                let (reader, _) = crate::remote::RemoteJsonl::open(store, collection)?;
+                Ok(SourceCollection::Remote(Box::new(reader)))
+            }
+            ContainerFS::Zip(path) => Err(Error::Validation(format!(
+                "can't sync from archive {}; unpack it first",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Documents with their index entries and expiry times, in file order.
+    fn indexed(&self) -> Vec<(String, IndexEntry, Option<u64>)> {
+        let mut docs: Vec<_> = match self {
+            SourceCollection::Local(store) => store
+                .indexed()
+                .map(|(id, entry, expires_at)| (id.to_string(), *entry, expires_at))
+                .collect(),
+            #[cfg(feature = "remote")]
+            SourceCollection::Remote(reader) => reader
+                .indexed()
+                .map(|(id, entry, expires_at)| (id.to_string(), *entry, expires_at))
+                .collect(),
+        };
+        docs.sort_unstable_by_key(|(_, entry, _)| entry.offset);
+        docs
+    }
+
+    fn raw_line(&self, doc_id: &str) -> Result<Vec<u8>> {
+        match self {
+            SourceCollection::Local(store) => store.raw_line(doc_id),
+            #[cfg(feature = "remote")]
+            SourceCollection::Remote(reader) => reader.raw_line(doc_id),
+        }
+    }
+}
+
+/// Sync every collection of `source` into `dest`, copying only new and
+/// changed documents.
+pub fn sync(source: impl AsRef<Path>, dest: impl AsRef<Path>) -> Result<SyncReport> {
+    sync_with(source, dest, &SyncOptions::default(), |_| {})
+}
+
+/// Sync collections of `source` into `dest`, calling `progress` as it goes.
+///
+/// `source` is a local path or, with the `remote` feature, an `s3://` or
+/// `gs://` URL. Only fast store (JSONL) collections can be synced.
+pub fn sync_with(
+    source: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    options: &SyncOptions,
+    mut progress: impl FnMut(&SyncProgress),
+) -> Result<SyncReport> {
+    let (source, dest) = (source.as_ref(), dest.as_ref());
+    if is_remote_url(dest) {
+        return Err(Error::ReadOnly(format!(
+            "can't sync into {}: remote containers are read-only",
+            dest.display()
+        )));
+    }
+    let container = ContainerFS::open(source)?;
+    if let ContainerFS::Folder(root) = &container {
+        if dest.exists() && root.canonicalize()? == dest.canonicalize()? {
+            return Err(Error::Validation(
+                "source and destination are the same container".to_string(),
+            ));
+        }
+    }
+
+    let collections = if options.collections.is_empty() {
+        container.list_collections()?
+    } else {
+        options.collections.clone()
+    };
+    if !options.dry_run {
+        Layout::init_root(dest)?;
+    }
+
+    let mut report = SyncReport::default();
+    for collection in &collections {
+        let source = SourceCollection::open(&container, collection)?;
+        sync_collection(
+            &source,
+            dest,
+            collection,
+            options,
+            &mut report,
+            &mut progress,
+        )?;
+    }
+    Ok(report)
+}
+
+/// A pending change to a destination collection.
+enum Change {
+    Copy { doc_id: String, added: bool },
+    Remove(String),
+}
+
+fn sync_collection(
+    source: &SourceCollection,
+    dest: &Path,
+    collection: &str,
+    options: &SyncOptions,
+    report: &mut SyncReport,
+    progress: &mut impl FnMut(&SyncProgress),
+) -> Result<()> {
+    let store = open_dest(dest, collection, options.dry_run)?;
+    let existing: FxHashMap<String, (u32, u32, Option<u64>)> = store
+        .as_ref()
+        .map(|store| {
+            store
+                .indexed()
+                .map(|(id, e, expires_at)| (id.to_string(), (e.checksum, e.length, expires_at)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let docs = source.indexed();
+    let mut changes = Vec::new();
+    for (doc_id, entry, expires_at) in &docs {
+        match existing.get(doc_id) {
+            Some(&mark) if mark == (entry.checksum, entry.length, *expires_at) => {
+                report.unchanged += 1;
+            }
+            found => {
+                let added = found.is_none();
+                changes.push(Change::Copy {
+                    doc_id: doc_id.clone(),
+                    added,
+                });
+            }
+        }
+    }
+    if options.delete {
+        let live: FxHashSet<&str> = docs.iter().map(|(id, _, _)| id.as_str()).collect();
+        let mut gone: Vec<&String> = existing
+            .keys()
+            .filter(|id| !live.contains(id.as_str()))
+            .collect();
+        gone.sort_unstable();
+        changes.extend(gone.into_iter().map(|id| Change::Remove(id.clone())));
+    }
+
+    // A dry run only reads the destination
+    let mut store = store.filter(|_| !options.dry_run);
+    let lengths: FxHashMap<&str, (u32, Option<u64>)> = docs
+        .iter()
+        .map(|(id, e, expires_at)| (id.as_str(), (e.length, *expires_at)))
+        .collect();
+    let mut state = SyncProgress {
+        collection: collection.to_string(),
+        docs_done: 0,
+        docs_total: changes.len(),
+    };
+    for change in changes {
+        match change {
+            Change::Copy { doc_id, added } => {
+                let (length, expires_at) = lengths[doc_id.as_str()];
+                if let Some(store) = &mut store {
+                    let line = source.raw_line(&doc_id)?;
+                    store.put_raw_expiring(doc_id, &line, expires_at)?;
+                }
+                if added {
+                    report.added += 1;
+                } else {
+                    report.updated += 1;
+                }
+                // Line lengths include the newline
+                report.bytes += length.saturating_sub(1) as u64;
+            }
+            Change::Remove(doc_id) => {
+                if let Some(store) = &mut store {
+                    store.delete(&doc_id)?;
+                }
+                report.removed += 1;
+            }
+        }
+        state.docs_done += 1;
+        progress(&state);
+    }
+
+    if let Some(mut store) = store {
+        store.flush()?;
+    }
+    Ok(())
+}
+
+/// Open the destination collection for writing; in a dry run, open it
+/// read-only if it exists.
+fn open_dest(dest: &Path, collection: &str, dry_run: bool) -> Result<Option<FastStore>> {
+    let exists = Layout::collection_dir(dest, collection).exists();
+    if exists && StorageMode::detect(dest, collection)? != StorageMode::Jsonl {
+        return Err(Error::Validation(format!(
+            "destination collection '{}' isn't a fast store collection",
+            collection
+        )));
+    }
+    match (exists, dry_run) {
+        (_, false) => Ok(Some(FastStore::open(dest, collection, SYNC_BATCH)?)),
+        (true, true) => Ok(Some(FastStore::open_with_mode(
+            dest,
+            collection,
+            1,
+            OpenMode::Read,
+        )?)),
+        (false, true) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn docs(root: &Path, collection: &str) -> Vec<(String, serde_json::Value)> {
+        let store = FastStore::open_with_mode(root, collection, 1, OpenMode::Read).unwrap();
+        let mut docs: Vec<_> = store
+            .doc_ids()
+            .into_iter()
+            .map(|id| {
+                let doc = store.get(&id).unwrap();
+                (id, doc)
+            })
+            .collect();
+        docs.sort_by(|a, b| a.0.cmp(&b.0));
+        docs
+    }
+
+    #[test]
+    fn test_sync_copies_changes() {
+        let tmp = TempDir::new().unwrap();
+        let (src, dst) = (tmp.path().join("src"), tmp.path().join("dst"));
+        let mut store = FastStore::open(&src, "train", 10).unwrap();
+        for i in 0..5 {
+            store.put(format!("doc{}", i), json!({"n": i})).unwrap();
+        }
+        store
+            .put_with_ttl("session", json!({"n": -1}), Duration::from_secs(3600))
+            .unwrap();
+        store.flush().unwrap();
+
+        let report = sync(&src, &dst).unwrap();
+        assert_eq!(report.added, 6);
+        assert_eq!(docs(&src, "train"), docs(&dst, "train"));
+        let copied = FastStore::open_with_mode(&dst, "train", 1, OpenMode::Read).unwrap();
+        assert_eq!(copied.expires_at("session"), store.expires_at("session"));
+        drop(copied);
+
+        store.put("doc1", json!({"n": 10})).unwrap();
+        store.put("doc5", json!({"n": 5})).unwrap();
+        store.delete("doc2").unwrap();
+        store.flush().unwrap();
+
+        let options = SyncOptions {
+            delete: true,
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let report = sync_with(&src, &dst, &options, |p| {
+            calls += 1;
+            assert_eq!(p.docs_total, 3);
+        })
+        .unwrap();
+        assert_eq!(
+            (
+                report.added,
+                report.updated,
+                report.removed,
+                report.unchanged
+            ),
+            (1, 1, 1, 4)
+        );
+        assert_eq!(calls, 3);
+        assert_eq!(docs(&src, "train"), docs(&dst, "train"));
+
+        // Nothing left to copy
+        let report = sync_with(&src, &dst, &options, |_| {}).unwrap();
+        assert_eq!((report.unchanged, report.bytes), (6, 0));
+    }
+
+    #[test]
+    fn test_sync_dry_run() {
+        let tmp = TempDir::new().unwrap();
+        let (src, dst) = (tmp.path().join("src"), tmp.path().join("dst"));
+        let mut store = FastStore::open(&src, "train", 10).unwrap();
+        store.put("doc1", json!({"n": 1})).unwrap();
+        store.flush().unwrap();
+
+        let options = SyncOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let report = sync_with(&src, &dst, &options, |_| {}).unwrap();
+        assert_eq!(report.added, 1);
+        assert!(report.bytes > 0);
+        assert!(!dst.exists());
+
+        assert!(matches!(sync(&src, &src), Err(Error::Validation(_))));
+    }
+}
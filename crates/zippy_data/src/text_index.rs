@@ -0,0 +1,232 @@
+//! Full-text inverted indexes for string fields.
+//!
+//! Text is split on non-alphanumeric characters and lowercased; each token
+//! maps to the IDs of the documents containing it. Indexes live under
+//! `meta/text_indexes/<field>.jsonl` and are queried through
+//! [`Predicate::TextMatch`](crate::Predicate::TextMatch), which requires every
+//! query token to be present.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{encryption, Codec, Layout, Result};
+
+/// Split text into lowercase alphanumeric tokens.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Unique tokens of a field value (strings, or arrays of strings).
+pub(crate) fn value_tokens(value: &Value) -> HashSet<String> {
+    match value {
+        Value::String(s) => tokenize(s).into_iter().collect(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .flat_map(tokenize)
+            .collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// On-disk posting list for one token.
+#[derive(Debug, Serialize, Deserialize)]
+struct Posting {
+    token: String,
+    ids: Vec<String>,
+}
+
+/// Inverted index over the tokens of a single string field.
+#[derive(Debug, Clone)]
+pub struct TextIndex {
+    field: String,
+    /// Token -> doc IDs containing it
+    postings: HashMap<String, BTreeSet<String>>,
+    /// Doc ID -> tokens (reverse map for updates)
+    tokens: HashMap<String, Vec<String>>,
+}
+
+impl TextIndex {
+    /// Create an empty text index for `field` (dot notation for nested fields).
+    pub fn new(field: impl Into<String>) -> Self {
+        TextIndex {
+            field: field.into(),
+            postings: HashMap::new(),
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// The indexed field.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Number of indexed documents.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Check if no documents are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Number of distinct tokens.
+    pub fn distinct_tokens(&self) -> usize {
+        self.postings.len()
+    }
+
+    /// Index (or re-index) a document. Documents without text are skipped.
+    pub fn insert(&mut self, doc_id: &str, doc: &Value) {
+        self.remove(doc_id);
+        let tokens = Codec::get_nested(doc, &self.field)
+            .map(value_tokens)
+            .unwrap_or_default();
+        if tokens.is_empty() {
+            return;
+        }
+        for token in &tokens {
+            self.postings
+                .entry(token.clone())
+                .or_default()
+                .insert(doc_id.to_string());
+        }
+        self.tokens
+            .insert(doc_id.to_string(), tokens.into_iter().collect());
+    }
+
+    /// Remove a document from the index.
+    pub fn remove(&mut self, doc_id: &str) {
+        if let Some(tokens) = self.tokens.remove(doc_id) {
+            for token in tokens {
+                if let Some(ids) = self.postings.get_mut(&token) {
+                    ids.remove(doc_id);
+                    if ids.is_empty() {
+                        self.postings.remove(&token);
+                    }
+                }
+            }
+        }
+    }
+
+    /// IDs of documents containing every token of `query`.
+    pub fn search(&self, query: &str) -> Vec<String> {
+        self.search_set(query).into_iter().collect()
+    }
+
+    pub(crate) fn search_set(&self, query: &str) -> HashSet<String> {
+        let mut result: Option<HashSet<String>> = None;
+        for token in tokenize(query) {
+            let ids: HashSet<String> = self
+                .postings
+                .get(&token)
+                .map(|ids| ids.iter().cloned().collect())
+                .unwrap_or_default();
+            result = Some(match result {
+                Some(acc) => acc.intersection(&ids).cloned().collect(),
+                None => ids,
+            });
+        }
+        result.unwrap_or_default()
+    }
+
+    /// Load the text index for `field` from disk.
+    pub fn load(root: &Path, collection: &str, field: &str) -> Result<Self> {
+        let path = Layout::text_index_file(root, collection, field);
+        let content = encryption::read_file(root, collection, &path)?;
+
+        let mut index = TextIndex::new(field);
+        for line in content.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let posting: Posting = serde_json::from_str(&line)?;
+            for doc_id in &posting.ids {
+                index
+                    .tokens
+                    .entry(doc_id.clone())
+                    .or_default()
+                    .push(posting.token.clone());
+            }
+            index
+                .postings
+                .insert(posting.token, posting.ids.into_iter().collect());
+        }
+
+        Ok(index)
+    }
+
+    /// Save the text index to disk.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        std::fs::create_dir_all(Layout::text_index_dir(root, collection))?;
+        let path = Layout::text_index_file(root, collection, &self.field);
+        let mut file = Vec::new();
+
+        let mut tokens: Vec<_> = self.postings.keys().collect();
+        tokens.sort();
+        for token in tokens {
+            let posting = Posting {
+                token: token.clone(),
+                ids: self.postings[token].iter().cloned().collect(),
+            };
+            writeln!(file, "{}", serde_json::to_string(&posting)?)?;
+        }
+
+        encryption::write_file(root, collection, &path, file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Hello, World! rust-lang 2024"),
+            ["hello", "world", "rust", "lang", "2024"]
+        );
+        assert!(tokenize(" ,.; ").is_empty());
+    }
+
+    #[test]
+    fn test_text_index_search_and_persistence() {
+        let tmp = TempDir::new().unwrap();
+        let mut index = TextIndex::new("body");
+        index.insert("a", &json!({"body": "The quick brown fox"}));
+        index.insert("b", &json!({"body": "A quick red fox"}));
+        index.insert("c", &json!({"body": ["lazy", "Brown dog"]}));
+        index.insert("d", &json!({"title": "no body"}));
+        assert_eq!(index.len(), 3);
+
+        let mut ids = index.search("Quick FOX");
+        ids.sort();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(index.search("brown dog"), ["c"]);
+        assert!(index.search("cat").is_empty());
+        assert!(index.search("").is_empty());
+
+        index.insert("a", &json!({"body": "slow turtle"}));
+        index.remove("b");
+        assert!(index.search("fox").is_empty());
+
+        index.save(tmp.path(), "test").unwrap();
+        let loaded = TextIndex::load(tmp.path(), "test", "body").unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.distinct_tokens(), index.distinct_tokens());
+        assert_eq!(loaded.search("turtle"), ["a"]);
+    }
+}
@@ -1,14 +1,19 @@
 //! Transaction log for crash-safe writes.
+//!
+//! Entries are synced as the log's [`Durability`] says: by default once per
+//! batch, when its COMMIT is appended, so the entries of a batch share one
+//! sync.
 
 use std::{
-    io::{BufRead, BufReader, Write},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, Layout, Result};
+use crate::{Durability, Error, Layout, Result};
 
 /// Journal entry types.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -26,6 +31,20 @@ pub enum JournalEntry {
         timestamp: DateTime<Utc>,
         doc_id: String,
     },
+    /// Start of a fast store transaction: the data and tombstone file
+    /// lengths to roll back to if no COMMIT follows.
+    #[serde(rename = "BEGIN")]
+    Begin {
+        timestamp: DateTime<Utc>,
+        /// Active data segment when the transaction started
+        segment: u32,
+        /// Length of the segment file on disk
+        segment_len: u64,
+        /// Index offset of the end of the data
+        offset: u64,
+        /// Length of the tombstone file
+        tombstones_len: u64,
+    },
     #[serde(rename = "COMMIT")]
     Commit {
         timestamp: DateTime<Utc>,
@@ -54,6 +73,17 @@ impl JournalEntry {
         }
     }
 
+    /// Create a BEGIN entry.
+    pub fn begin(segment: u32, segment_len: u64, offset: u64, tombstones_len: u64) -> Self {
+        JournalEntry::Begin {
+            timestamp: Utc::now(),
+            segment,
+            segment_len,
+            offset,
+            tombstones_len,
+        }
+    }
+
     /// Create a COMMIT entry.
     pub fn commit(batch_id: u64) -> Self {
         JournalEntry::Commit {
@@ -74,17 +104,27 @@ impl JournalEntry {
         match self {
             JournalEntry::Put { timestamp, .. } => timestamp,
             JournalEntry::Delete { timestamp, .. } => timestamp,
+            JournalEntry::Begin { timestamp, .. } => timestamp,
             JournalEntry::Commit { timestamp, .. } => timestamp,
             JournalEntry::Checkpoint { timestamp } => timestamp,
         }
     }
+
+    /// Get the document a PUT or DELETE entry is about.
+    pub fn doc_id(&self) -> Option<&str> {
+        match self {
+            JournalEntry::Put { doc_id, .. } | JournalEntry::Delete { doc_id, .. } => Some(doc_id),
+            _ => None,
+        }
+    }
 }
 
 /// Transaction log for crash recovery.
 pub struct TransactionLog {
     path: std::path::PathBuf,
-    file: std::fs::File,
+    file: BufWriter<File>,
     next_batch_id: u64,
+    durability: Durability,
 }
 
 impl TransactionLog {
@@ -108,11 +148,25 @@ impl TransactionLog {
 
         Ok(TransactionLog {
             path,
-            file,
+            file: BufWriter::new(file),
             next_batch_id,
+            durability: Durability::default(),
         })
     }
 
+    /// When entries are synced.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Set when entries are synced; entries buffered so far are written
+    /// out first.
+    pub fn set_durability(&mut self, durability: Durability) -> Result<()> {
+        self.file.flush()?;
+        self.durability = durability;
+        Ok(())
+    }
+
     fn find_next_batch_id(path: &Path) -> Result<u64> {
         if !path.exists() {
             return Ok(1);
@@ -138,24 +192,52 @@ impl TransactionLog {
     }
 
     /// Append an entry to the journal.
+    ///
+    /// Unless the durability is [`Durability::None`], the entry is written
+    /// through to the operating system; with [`Durability::FsyncOp`] it is
+    /// also synced.
     pub fn append(&mut self, entry: &JournalEntry) -> Result<()> {
-        let line = serde_json::to_string(entry)?;
-        writeln!(self.file, "{}", line)?;
-        self.file.sync_data()?;
+        self.write_entry(entry)?;
+        if self.durability != Durability::None {
+            self.file.flush()?;
+        }
+        if self.durability.syncs_ops() {
+            self.file.get_ref().sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Write out the entries appended so far, and sync them unless the
+    /// durability is [`Durability::None`] or [`Durability::Flush`].
+    ///
+    /// Entries that must reach the log before what follows them (a BEGIN
+    /// record before the transaction's data) are synced with this.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.flush()?;
+        if self.durability.syncs_batches() {
+            self.file.get_ref().sync_data()?;
+        }
         Ok(())
     }
 
     /// Commit the current batch.
     pub fn commit(&mut self) -> Result<u64> {
         let batch_id = self.next_batch_id;
-        self.append(&JournalEntry::commit(batch_id))?;
+        self.write_entry(&JournalEntry::commit(batch_id))?;
+        self.sync()?;
         self.next_batch_id += 1;
         Ok(batch_id)
     }
 
     /// Write a checkpoint.
     pub fn checkpoint(&mut self) -> Result<()> {
-        self.append(&JournalEntry::checkpoint())?;
+        self.write_entry(&JournalEntry::checkpoint())?;
+        self.sync()
+    }
+
+    fn write_entry(&mut self, entry: &JournalEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)?;
+        writeln!(self.file, "{}", line)?;
         Ok(())
     }
 
@@ -188,6 +270,63 @@ impl TransactionLog {
         Ok(uncommitted)
     }
 
+    /// Read the uncommitted entries of a collection's journal without
+    /// opening it for writing (none if there is no journal).
+    ///
+    /// An unterminated final entry is a torn write and is ignored.
+    pub fn load_uncommitted(root: &Path, collection: &str) -> Result<Vec<JournalEntry>> {
+        let path = Layout::journal_file(root, collection);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let mut uncommitted = Vec::new();
+        for line in content.split_inclusive('\n') {
+            if !line.ends_with('\n') || line.trim().is_empty() {
+                continue;
+            }
+            let entry: JournalEntry = serde_json::from_str(line).map_err(|e| {
+                Error::JournalCorrupted(format!("Invalid entry: {} ({})", line.trim_end(), e))
+            })?;
+            match entry {
+                JournalEntry::Commit { .. } | JournalEntry::Checkpoint { .. } => {
+                    uncommitted.clear();
+                }
+                _ => uncommitted.push(entry),
+            }
+        }
+        Ok(uncommitted)
+    }
+
+    /// Read the PUT and DELETE entries of a collection's journal that were
+    /// committed (or recovered at a checkpoint), oldest first; none if
+    /// there is no journal.
+    pub fn load_history(root: &Path, collection: &str) -> Result<Vec<JournalEntry>> {
+        let path = Layout::journal_file(root, collection);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let mut history = Vec::new();
+        let mut pending = Vec::new();
+        for line in content.split_inclusive('\n') {
+            if !line.ends_with('\n') || line.trim().is_empty() {
+                continue;
+            }
+            let entry: JournalEntry = serde_json::from_str(line).map_err(|e| {
+                Error::JournalCorrupted(format!("Invalid entry: {} ({})", line.trim_end(), e))
+            })?;
+            match entry {
+                JournalEntry::Commit { .. } | JournalEntry::Checkpoint { .. } => {
+                    history.append(&mut pending);
+                }
+                JournalEntry::Put { .. } | JournalEntry::Delete { .. } => pending.push(entry),
+                JournalEntry::Begin { .. } => {}
+            }
+        }
+        Ok(history)
+    }
+
     /// Replay uncommitted entries (for crash recovery).
     pub fn replay<F>(&self, mut handler: F) -> Result<()>
     where
@@ -201,18 +340,17 @@ impl TransactionLog {
 
     /// Truncate the journal (after successful checkpoint).
     pub fn truncate(&mut self) -> Result<()> {
-        // Close current file
-        drop(std::mem::replace(
-            &mut self.file,
-            std::fs::File::open("/dev/null")?,
-        ));
+        // Write out buffered entries before the file is cut
+        self.file.flush()?;
 
         // Truncate and reopen
-        self.file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&self.path)?;
+        self.file = BufWriter::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)?,
+        );
 
         // Write a fresh checkpoint
         self.checkpoint()?;
@@ -277,5 +415,77 @@ mod tests {
         let log = TransactionLog::open(root, "test").unwrap();
         let uncommitted = log.get_uncommitted().unwrap();
         assert_eq!(uncommitted.len(), 2);
+
+        // A torn final entry is ignored when loading without a handle
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(Layout::journal_file(root, "test"))
+            .unwrap();
+        file.write_all(br#"{"op":"COMM"#).unwrap();
+        let uncommitted = TransactionLog::load_uncommitted(root, "test").unwrap();
+        assert_eq!(uncommitted.len(), 2);
+    }
+
+    #[test]
+    fn test_journal_history() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        Layout::init_collection(root, "test").unwrap();
+        assert!(TransactionLog::load_history(root, "test")
+            .unwrap()
+            .is_empty());
+
+        let mut log = TransactionLog::open(root, "test").unwrap();
+        log.append(&JournalEntry::put("doc1", "schema1", 100))
+            .unwrap();
+        log.commit().unwrap();
+        log.append(&JournalEntry::delete("doc1")).unwrap();
+        log.checkpoint().unwrap();
+        // Not committed yet
+        log.append(&JournalEntry::put("doc2", "schema1", 10))
+            .unwrap();
+
+        let history = TransactionLog::load_history(root, "test").unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(matches!(history[0], JournalEntry::Put { size: 100, .. }));
+        assert!(matches!(history[1], JournalEntry::Delete { .. }));
+        assert_eq!(history[1].doc_id(), Some("doc1"));
+    }
+
+    #[test]
+    fn test_journal_durability() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        Layout::init_collection(root, "test").unwrap();
+
+        let mut log = TransactionLog::open(root, "test").unwrap();
+        assert_eq!(log.durability(), Durability::FsyncBatch);
+        log.set_durability("none".parse().unwrap()).unwrap();
+        assert_eq!(log.durability(), Durability::None);
+
+        // Entries are buffered until the batch commits
+        log.append(&JournalEntry::put("doc1", "schema1", 100))
+            .unwrap();
+        assert!(TransactionLog::load_history(root, "test")
+            .unwrap()
+            .is_empty());
+        log.commit().unwrap();
+        assert_eq!(TransactionLog::load_history(root, "test").unwrap().len(), 1);
+
+        for durability in [Durability::Flush, Durability::FsyncOp] {
+            log.set_durability(durability).unwrap();
+            log.append(&JournalEntry::delete("doc1")).unwrap();
+            assert_eq!(log.get_uncommitted().unwrap().len(), 1);
+            log.commit().unwrap();
+        }
+        assert_eq!(TransactionLog::load_history(root, "test").unwrap().len(), 3);
+
+        assert_eq!(
+            "FSYNC_OP".parse::<Durability>().unwrap(),
+            Durability::FsyncOp
+        );
+        assert!("always".parse::<Durability>().is_err());
     }
 }
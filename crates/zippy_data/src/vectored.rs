@@ -0,0 +1,167 @@
+//! Vectored writes for plain data segments (Linux, `vectored-io` feature).
+//!
+//! A `BufWriter` issues one `write` per buffer it fills, so bulk ingestion
+//! makes a system call every 256 KiB. [`VectoredWriter`] instead fills a
+//! list of chunks and hands them all to a single `writev` when it flushes
+//! or holds [`MAX_CHUNKS`] of them, and writes large slices (such as the
+//! blob of [`FastStore::write_jsonl_blob`](crate::FastStore::write_jsonl_blob))
+//! straight from the caller's memory behind the buffered chunks. Syncs stay
+//! once per flush, as the store's [`Durability`](crate::Durability) says.
+
+use std::{
+    fs::File,
+    io::{self, IoSlice, Write},
+};
+
+/// Capacity of a chunk.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Chunks buffered before they are written out.
+const MAX_CHUNKS: usize = 16;
+
+/// Slices at least this long are written without being copied.
+const DIRECT_MIN: usize = 64 * 1024;
+
+/// Appending writer that gathers writes into large `writev` calls.
+pub(crate) struct VectoredWriter {
+    file: File,
+    /// Filled chunks, then the one being filled
+    chunks: Vec<Vec<u8>>,
+    /// Written chunks, kept for reuse
+    spare: Vec<Vec<u8>>,
+}
+
+impl VectoredWriter {
+    pub(crate) fn new(file: File) -> Self {
+        VectoredWriter {
+            file,
+            chunks: Vec::with_capacity(MAX_CHUNKS),
+            spare: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Write the buffered chunks, then `tail`, in as few calls as the
+    /// kernel allows.
+    fn write_out(&mut self, tail: &[u8]) -> io::Result<()> {
+        let parts: Vec<&[u8]> = self
+            .chunks
+            .iter()
+            .map(Vec::as_slice)
+            .chain(std::iter::once(tail))
+            .filter(|part| !part.is_empty())
+            .collect();
+        // Position of the first unwritten byte: part index, then offset
+        let (mut part, mut offset) = (0, 0);
+        while part < parts.len() {
+            let slices: Vec<IoSlice<'_>> = std::iter::once(&parts[part][offset..])
+                .chain(parts[part + 1..].iter().copied())
+                .map(IoSlice::new)
+                .collect();
+            let mut written = match self.file.write_vectored(&slices) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            while written > 0 {
+                let left = parts[part].len() - offset;
+                if written < left {
+                    offset += written;
+                    break;
+                }
+                written -= left;
+                part += 1;
+                offset = 0;
+            }
+        }
+        for mut chunk in self.chunks.drain(..) {
+            chunk.clear();
+            if self.spare.len() < MAX_CHUNKS {
+                self.spare.push(chunk);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for VectoredWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() >= DIRECT_MIN {
+            self.write_out(data)?;
+            return Ok(data.len());
+        }
+        let full = self
+            .chunks
+            .last()
+            .map_or(true, |chunk| chunk.len() + data.len() > CHUNK_SIZE);
+        if full {
+            if self.chunks.len() == MAX_CHUNKS {
+                self.write_out(&[])?;
+            }
+            let chunk = self
+                .spare
+                .pop()
+                .unwrap_or_else(|| Vec::with_capacity(CHUNK_SIZE));
+            self.chunks.push(chunk);
+        }
+        if let Some(chunk) = self.chunks.last_mut() {
+            chunk.extend_from_slice(data);
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out(&[])
+    }
+}
+
+impl Drop for VectoredWriter {
+    fn drop(&mut self) {
+        let _ = self.write_out(&[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_vectored_writer() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("data.jsonl");
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .unwrap();
+        let mut writer = VectoredWriter::new(file);
+
+        // Enough small lines to fill every chunk, then a direct write
+        let mut expected = Vec::new();
+        for i in 0..100_000 {
+            let line = format!("{{\"_id\":\"doc{}\",\"n\":{}}}\n", i, i);
+            writer.write_all(line.as_bytes()).unwrap();
+            expected.extend_from_slice(line.as_bytes());
+        }
+        let blob = vec![b'x'; DIRECT_MIN * 3];
+        writer.write_all(&blob).unwrap();
+        expected.extend_from_slice(&blob);
+        writer.write_all(b"\n").unwrap();
+        expected.push(b'\n');
+        writer.flush().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+        writer.write_all(b"tail\n").unwrap();
+        drop(writer);
+        expected.extend_from_slice(b"tail\n");
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+    }
+}
@@ -0,0 +1,626 @@
+//! Vector embeddings and nearest-neighbor search.
+//!
+//! A vector index holds one fixed-dimension `f32` embedding per document
+//! and finds the documents nearest to a query vector by exact (brute force)
+//! search, which is fast enough for small and medium corpora without a
+//! separate vector database. Indexes are maintained by
+//! [`FastStore`](crate::FastStore) once vectors are put into them, and read
+//! through [`Engine::knn`](crate::Engine::knn).
+//!
+//! Each index lives in `meta/vectors/<name>.vec`:
+//!
+//! ```text
+//! "ZDSV" | version: u32 | dim: u32 | reserved: u32 | count: u64
+//! count * dim f32 values, one row per document
+//! count * [id_len: u16, id_bytes], the documents of the rows in order
+//! ```
+//!
+//! All integers and floats are little-endian. Rows start 24 bytes in, so a
+//! memory-mapped file is searched in place; the first write copies the rows
+//! into memory. Index files of an encrypted collection are sealed and read
+//! into memory instead.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    fs::File,
+    path::Path,
+    str::FromStr,
+};
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::{encryption, Error, FieldIndexes, Layout, Result};
+
+/// Magic at the start of a vector index file ("ZDSV").
+const VECTOR_MAGIC: &[u8; 4] = b"ZDSV";
+const VECTOR_VERSION: u32 = 1;
+
+/// Length of the header before the rows.
+const HEADER_LEN: usize = 24;
+
+/// Accumulators of the distance kernels, wide enough for AVX.
+const LANES: usize = 8;
+
+/// Rows searched per parallel task.
+const SEARCH_CHUNK: usize = 4096;
+
+/// How [`VectorIndex::search`] scores vectors (higher is nearer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Metric {
+    /// Cosine similarity, in `[-1, 1]`; zero vectors score 0
+    #[default]
+    Cosine,
+    /// Dot product, for embeddings normalized at creation
+    Dot,
+}
+
+impl Metric {
+    /// Short machine-readable name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Cosine => "cosine",
+            Metric::Dot => "dot",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "cosine" => Ok(Metric::Cosine),
+            "dot" => Ok(Metric::Dot),
+            other => Err(Error::Validation(format!(
+                "unknown metric '{}' (expected cosine or dot)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A search result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbor {
+    pub doc_id: String,
+    pub score: f32,
+}
+
+/// Row storage: a mapped index file until the first write.
+enum Rows {
+    Mapped { mmap: Mmap, len: usize },
+    Owned(Vec<f32>),
+}
+
+impl Rows {
+    fn as_slice(&self) -> &[f32] {
+        match self {
+            // SAFETY: checked on load that the rows are aligned, in bounds and
+            // little-endian; the file is replaced by rename, never rewritten
+            Rows::Mapped { mmap, len } => unsafe {
+                std::slice::from_raw_parts(mmap[HEADER_LEN..].as_ptr() as *const f32, *len)
+            },
+            Rows::Owned(rows) => rows,
+        }
+    }
+
+    fn to_mut(&mut self) -> &mut Vec<f32> {
+        if let Rows::Mapped { .. } = self {
+            *self = Rows::Owned(self.as_slice().to_vec());
+        }
+        match self {
+            Rows::Owned(rows) => rows,
+            Rows::Mapped { .. } => unreachable!(),
+        }
+    }
+}
+
+/// Fixed-dimension embeddings of a collection's documents.
+pub struct VectorIndex {
+    name: String,
+    dim: usize,
+    /// Doc ID of each row
+    ids: Vec<String>,
+    /// Doc ID -> row
+    rows_by_id: FxHashMap<String, usize>,
+    rows: Rows,
+}
+
+impl std::fmt::Debug for VectorIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VectorIndex")
+            .field("name", &self.name)
+            .field("dim", &self.dim)
+            .field("len", &self.ids.len())
+            .finish()
+    }
+}
+
+impl VectorIndex {
+    /// Create an empty index of `dim`-dimensional vectors.
+    pub fn new(name: impl Into<String>, dim: usize) -> Result<Self> {
+        let name = name.into();
+        FieldIndexes::validate_field(&name)?;
+        if dim == 0 || dim > u32::MAX as usize {
+            return Err(Error::Validation(format!(
+                "invalid vector dimension {} for index '{}'",
+                dim, name
+            )));
+        }
+        Ok(VectorIndex {
+            name,
+            dim,
+            ids: Vec::new(),
+            rows_by_id: FxHashMap::default(),
+            rows: Rows::Owned(Vec::new()),
+        })
+    }
+
+    /// Index name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Vector dimension.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of documents with a vector.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if no document has a vector.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Documents with a vector, in row order.
+    pub fn doc_ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// Check that `vector` fits the index.
+    fn check(&self, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dim {
+            return Err(Error::Validation(format!(
+                "vector index '{}' holds {}-dimensional vectors, got {}",
+                self.name,
+                self.dim,
+                vector.len()
+            )));
+        }
+        if !vector.iter().all(|x| x.is_finite()) {
+            return Err(Error::Validation(format!(
+                "vector for index '{}' has non-finite values",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Set the vector of a document, replacing any previous one.
+    pub fn insert(&mut self, doc_id: &str, vector: &[f32]) -> Result<()> {
+        self.check(vector)?;
+        let dim = self.dim;
+        match self.rows_by_id.get(doc_id) {
+            Some(&row) => {
+                self.rows.to_mut()[row * dim..(row + 1) * dim].copy_from_slice(vector);
+            }
+            None => {
+                self.rows.to_mut().extend_from_slice(vector);
+                self.rows_by_id.insert(doc_id.to_string(), self.ids.len());
+                self.ids.push(doc_id.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a document's vector. Returns whether it had one.
+    pub fn remove(&mut self, doc_id: &str) -> bool {
+        let Some(row) = self.rows_by_id.remove(doc_id) else {
+            return false;
+        };
+        // The last row takes the removed row's place
+        let dim = self.dim;
+        let last = self.ids.len() - 1;
+        let rows = self.rows.to_mut();
+        if row != last {
+            rows.copy_within(last * dim..(last + 1) * dim, row * dim);
+            self.rows_by_id.insert(self.ids[last].clone(), row);
+        }
+        rows.truncate(last * dim);
+        self.ids.swap_remove(row);
+        true
+    }
+
+    /// The vector of a document.
+    pub fn get(&self, doc_id: &str) -> Option<&[f32]> {
+        let row = *self.rows_by_id.get(doc_id)?;
+        Some(&self.rows.as_slice()[row * self.dim..(row + 1) * self.dim])
+    }
+
+    /// The `k` documents nearest to `query`, nearest first (ties by ID).
+    pub fn search(&self, query: &[f32], k: usize, metric: Metric) -> Result<Vec<Neighbor>> {
+        self.search_where(query, k, metric, |_| true)
+    }
+
+    /// Like [`search`](Self::search), but only over the documents for
+    /// which `keep` is true; the others don't take up any of the `k`
+    /// places.
+    pub fn search_where(
+        &self,
+        query: &[f32],
+        k: usize,
+        metric: Metric,
+        keep: impl Fn(&str) -> bool + Sync,
+    ) -> Result<Vec<Neighbor>> {
+        self.check(query)?;
+        if k == 0 || self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = match metric {
+            Metric::Cosine => normalized(query),
+            Metric::Dot => query.to_vec(),
+        };
+        let score = |row: &[f32]| match metric {
+            Metric::Cosine => {
+                let norm = dot(row, row).sqrt();
+                if norm > 0.0 {
+                    dot(&query, row) / norm
+                } else {
+                    0.0
+                }
+            }
+            Metric::Dot => dot(&query, row),
+        };
+
+        let chunk = SEARCH_CHUNK * self.dim;
+        let best = self
+            .rows
+            .as_slice()
+            .par_chunks(chunk)
+            .enumerate()
+            .map(|(i, rows)| {
+                let mut heap = TopK::new(k);
+                for (j, row) in rows.chunks_exact(self.dim).enumerate() {
+                    let row_no = i * SEARCH_CHUNK + j;
+                    if keep(&self.ids[row_no]) {
+                        heap.push(score(row), row_no);
+                    }
+                }
+                heap
+            })
+            .reduce(|| TopK::new(k), TopK::merge);
+
+        let mut neighbors: Vec<Neighbor> = best
+            .0
+            .into_iter()
+            .map(|Reverse(scored)| Neighbor {
+                doc_id: self.ids[scored.row].clone(),
+                score: scored.score,
+            })
+            .collect();
+        neighbors.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.doc_id.cmp(&b.doc_id))
+        });
+        Ok(neighbors)
+    }
+
+    /// Save the index to `meta/vectors/<name>.vec`, replacing it atomically.
+    pub fn save(&self, root: &Path, collection: &str) -> Result<()> {
+        let rows = self.rows.as_slice();
+        let mut buf = Vec::with_capacity(HEADER_LEN + rows.len() * 4);
+        buf.extend_from_slice(VECTOR_MAGIC);
+        buf.extend_from_slice(&VECTOR_VERSION.to_le_bytes());
+        buf.extend_from_slice(&(self.dim as u32).to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        buf.extend_from_slice(&(self.ids.len() as u64).to_le_bytes());
+        for value in rows {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        for id in &self.ids {
+            buf.extend_from_slice(&(id.len() as u16).to_le_bytes());
+            buf.extend_from_slice(id.as_bytes());
+        }
+
+        std::fs::create_dir_all(Layout::vector_dir(root, collection))?;
+        let path = Layout::vector_file(root, collection, &self.name);
+        encryption::write_file(root, collection, &path, buf)
+    }
+
+    /// Load the index `name` of a collection.
+    pub fn load(root: &Path, collection: &str, name: &str) -> Result<Self> {
+        let path = Layout::vector_file(root, collection, name);
+        if !path.exists() {
+            return Err(Error::Validation(format!(
+                "No vector index '{}' in collection '{}'",
+                name, collection
+            )));
+        }
+        let file = File::open(&path)?;
+        // SAFETY: index files are replaced by rename, never modified in place
+        let mmap = unsafe { Mmap::map(&file)? };
+        if !mmap.starts_with(VECTOR_MAGIC) || encryption::key_id(root, collection)?.is_some() {
+            // Sealed file of an encrypted collection (and rejected if it
+            // isn't sealed)
+            let bytes = encryption::read_file(root, collection, &path)?;
+            let (dim, count, ids) = Self::parse(name, &bytes)?;
+            let rows = bytes[HEADER_LEN..HEADER_LEN + count * dim * 4]
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            return Ok(Self::from_parts(name, dim, ids, Rows::Owned(rows)));
+        }
+
+        let (dim, count, ids) = Self::parse(name, &mmap)?;
+        let aligned = mmap[HEADER_LEN..].as_ptr().align_offset(4) == 0;
+        let rows = if aligned && cfg!(target_endian = "little") {
+            Rows::Mapped {
+                mmap,
+                len: count * dim,
+            }
+        } else {
+            Rows::Owned(
+                mmap[HEADER_LEN..HEADER_LEN + count * dim * 4]
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                    .collect(),
+            )
+        };
+        Ok(Self::from_parts(name, dim, ids, rows))
+    }
+
+    fn from_parts(name: &str, dim: usize, ids: Vec<String>, rows: Rows) -> Self {
+        let rows_by_id = ids
+            .iter()
+            .enumerate()
+            .map(|(row, id)| (id.clone(), row))
+            .collect();
+        VectorIndex {
+            name: name.to_string(),
+            dim,
+            ids,
+            rows_by_id,
+            rows,
+        }
+    }
+
+    /// Parse the header and document IDs of an index file, returning
+    /// `(dim, count, ids)`.
+    fn parse(name: &str, bytes: &[u8]) -> Result<(usize, usize, Vec<String>)> {
+        let corrupt = || Error::InvalidContainer(format!("vector index '{}' is corrupted", name));
+        if bytes.len() < HEADER_LEN || &bytes[..4] != VECTOR_MAGIC {
+            return Err(corrupt());
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != VECTOR_VERSION {
+            return Err(Error::InvalidContainer(format!(
+                "vector index '{}' has unsupported version {}",
+                name, version
+            )));
+        }
+        let dim = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let count = u64::from_le_bytes(bytes[16..24].try_into().unwrap()) as usize;
+        let rows_end = count
+            .checked_mul(dim)
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|n| n.checked_add(HEADER_LEN))
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(corrupt)?;
+
+        let mut ids = Vec::with_capacity(count);
+        let mut pos = rows_end;
+        for _ in 0..count {
+            let len_bytes = bytes.get(pos..pos + 2).ok_or_else(corrupt)?;
+            let len = u16::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            let id = bytes.get(pos + 2..pos + 2 + len).ok_or_else(corrupt)?;
+            ids.push(String::from_utf8(id.to_vec()).map_err(|_| corrupt())?);
+            pos += 2 + len;
+        }
+        Ok((dim, count, ids))
+    }
+
+    /// Names of a collection's vector indexes, sorted.
+    pub fn list(root: &Path, collection: &str) -> Result<Vec<String>> {
+        let dir = Layout::vector_dir(root, collection);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "vec") {
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Load every vector index of a collection.
+    pub fn load_all(root: &Path, collection: &str) -> Result<Vec<Self>> {
+        Self::list(root, collection)?
+            .iter()
+            .map(|name| Self::load(root, collection, name))
+            .collect()
+    }
+
+    /// Delete the index `name` of a collection.
+    pub fn drop_index(root: &Path, collection: &str, name: &str) -> Result<()> {
+        let path = Layout::vector_file(root, collection, name);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Dot product, with independent accumulators the compiler vectorizes.
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let mut acc = [0f32; LANES];
+    let (a_chunks, b_chunks) = (a.chunks_exact(LANES), b.chunks_exact(LANES));
+    let tail: f32 = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for i in 0..LANES {
+            acc[i] += x[i] * y[i];
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// `v` scaled to unit length (unchanged if zero).
+fn normalized(v: &[f32]) -> Vec<f32> {
+    let norm = dot(v, v).sqrt();
+    if norm > 0.0 {
+        v.iter().map(|x| x / norm).collect()
+    } else {
+        v.to_vec()
+    }
+}
+
+/// A scored row, ordered by score, then preferring lower rows.
+#[derive(Debug, Clone, Copy)]
+struct Scored {
+    score: f32,
+    row: usize,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.row.cmp(&self.row))
+    }
+}
+
+/// The `k` best scored rows, worst on top.
+struct TopK(BinaryHeap<Reverse<Scored>>, usize);
+
+impl TopK {
+    fn new(k: usize) -> Self {
+        TopK(BinaryHeap::with_capacity(k + 1), k)
+    }
+
+    fn push(&mut self, score: f32, row: usize) {
+        let scored = Scored { score, row };
+        if self.0.len() < self.1 {
+            self.0.push(Reverse(scored));
+        } else if self.0.peek().is_some_and(|Reverse(worst)| scored > *worst) {
+            self.0.pop();
+            self.0.push(Reverse(scored));
+        }
+    }
+
+    fn merge(mut self, other: TopK) -> TopK {
+        for Reverse(scored) in other.0 {
+            self.push(scored.score, scored.row);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_vector_search() {
+        let mut index = VectorIndex::new("emb", 3).unwrap();
+        index.insert("x", &[1.0, 0.0, 0.0]).unwrap();
+        index.insert("y", &[0.0, 2.0, 0.0]).unwrap();
+        index.insert("xy", &[1.0, 1.0, 0.0]).unwrap();
+        index.insert("zero", &[0.0, 0.0, 0.0]).unwrap();
+        assert!(index.insert("bad", &[1.0, 2.0]).is_err());
+        assert!(index.insert("nan", &[f32::NAN, 0.0, 0.0]).is_err());
+
+        let hits = index.search(&[2.0, 0.1, 0.0], 2, Metric::Cosine).unwrap();
+        let ids: Vec<&str> = hits.iter().map(|n| n.doc_id.as_str()).collect();
+        assert_eq!(ids, ["x", "xy"]);
+        assert!((hits[0].score - 0.99875).abs() < 1e-4);
+
+        let hits = index.search(&[0.0, 1.0, 0.0], 1, Metric::Dot).unwrap();
+        assert_eq!(hits[0].doc_id, "y");
+        assert_eq!(hits[0].score, 2.0);
+        assert_eq!(
+            index
+                .search(&[1.0, 0.0, 0.0], 10, Metric::Dot)
+                .unwrap()
+                .len(),
+            4
+        );
+
+        // Removing moves the last row into the gap
+        assert!(index.remove("x"));
+        assert!(!index.remove("x"));
+        assert_eq!(index.get("zero"), Some(&[0.0, 0.0, 0.0][..]));
+        assert_eq!(index.get("xy"), Some(&[1.0, 1.0, 0.0][..]));
+        let hits = index.search(&[1.0, 0.0, 0.0], 1, Metric::Cosine).unwrap();
+        assert_eq!(hits[0].doc_id, "xy");
+        assert_eq!("dot".parse::<Metric>().unwrap(), Metric::Dot);
+        assert!("l2".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn test_vector_index_save_load() {
+        let tmp = TempDir::new().unwrap();
+        let mut index = VectorIndex::new("emb", 20).unwrap();
+        for i in 0..10_000 {
+            let vector: Vec<f32> = (0..20).map(|j| ((i * 7 + j) % 13) as f32 - 6.0).collect();
+            index.insert(&format!("doc{:05}", i), &vector).unwrap();
+        }
+        index.save(tmp.path(), "test").unwrap();
+        assert_eq!(VectorIndex::list(tmp.path(), "test").unwrap(), ["emb"]);
+
+        let mut loaded = VectorIndex::load(tmp.path(), "test", "emb").unwrap();
+        assert!(matches!(loaded.rows, Rows::Mapped { .. }));
+        assert_eq!(loaded.len(), 10_000);
+        assert_eq!(loaded.get("doc00042"), index.get("doc00042"));
+        let query = index.get("doc01234").unwrap().to_vec();
+        assert_eq!(
+            loaded.search(&query, 5, Metric::Cosine).unwrap(),
+            index.search(&query, 5, Metric::Cosine).unwrap()
+        );
+        loaded.insert("new", &query).unwrap();
+        assert!(matches!(loaded.rows, Rows::Owned(_)));
+        assert_eq!(loaded.get("doc01234"), Some(&query[..]));
+
+        VectorIndex::drop_index(tmp.path(), "test", "emb").unwrap();
+        assert!(VectorIndex::load(tmp.path(), "test", "emb").is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! Previous versions of the documents of a fast store.
+//!
+//! Overwriting a document leaves its old line in the data segments until
+//! compaction drops it. With a version retention of `n` (the manifest's
+//! `"versions"` field), a [`FastStore`](crate::FastStore) keeps the last `n`
+//! previous lines of every document instead: compaction copies them like
+//! live lines, and `meta/versions.jsonl` records where they are.
+//!
+//! Records are appended as documents are overwritten and the file is only
+//! rewritten by compaction, so on load the records of deleted documents,
+//! of lines rolled back with a transaction and beyond the retention are
+//! skipped. Versions are numbered per document from 1 (the version current
+//! when retention was enabled), and keep their number when older ones are
+//! dropped. Deleting a document drops its
+//! versions.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::CollectionConfig, fast_writer::IndexEntry, id_map::IdMap, Layout, Result};
+
+/// A version of a document, from
+/// [`FastStore::list_versions`](crate::FastStore::list_versions).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DocVersion {
+    /// Version number, from 1
+    pub version: u64,
+    /// Time the version was written (Unix seconds, 0 if unknown)
+    pub mtime: u64,
+    /// Length of the stored line
+    pub size: u64,
+    /// Whether this is the document as it is now
+    pub current: bool,
+}
+
+/// A retained previous line of a document.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Version {
+    pub(crate) version: u64,
+    pub(crate) entry: IndexEntry,
+    pub(crate) mtime: u64,
+}
+
+/// A line of `versions.jsonl`.
+#[derive(Debug, Serialize, Deserialize)]
+struct VersionRecord {
+    id: String,
+    version: u64,
+    offset: u64,
+    length: u32,
+    checksum: u32,
+    mtime: u64,
+}
+
+impl VersionRecord {
+    fn new(id: &str, version: &Version) -> Self {
+        VersionRecord {
+            id: id.to_string(),
+            version: version.version,
+            offset: version.entry.offset,
+            length: version.entry.length,
+            checksum: version.entry.checksum,
+            mtime: version.mtime,
+        }
+    }
+}
+
+/// Retained versions of a collection's documents.
+#[derive(Debug, Default)]
+pub(crate) struct Versions {
+    /// Previous versions kept per document (0 disables versioning)
+    retention: usize,
+    /// Doc ID -> retained versions, oldest first
+    docs: FxHashMap<String, Vec<Version>>,
+    /// Records not yet appended to the file
+    pending: Vec<VersionRecord>,
+    /// Total length of the retained lines
+    bytes: u64,
+}
+
+impl Versions {
+    /// Read a collection's version retention from its manifest (0 if unset).
+    pub(crate) fn load_retention(root: &Path, collection: &str) -> Result<usize> {
+        let path = Layout::manifest_file(root, collection);
+        if !path.exists() {
+            return Ok(0);
+        }
+        let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        Ok(manifest
+            .get("versions")
+            .and_then(Value::as_u64)
+            .unwrap_or(0) as usize)
+    }
+
+    /// Store a version retention in a collection's manifest, keeping other
+    /// fields.
+    pub(crate) fn save_retention(root: &Path, collection: &str, retention: usize) -> Result<()> {
+        CollectionConfig::update(root, collection, |obj| {
+            obj.insert("versions".to_string(), Value::from(retention));
+        })
+    }
+
+    /// Load the retained versions of the documents in `index`. Versions
+    /// older than a document's deletion (`tombstones`) are skipped, and so
+    /// are versions newer than its line unless it points at an older line
+    /// with the same content (`shared`, see [`dedup`](crate::dedup)).
+    pub(crate) fn load(
+        root: &Path,
+        collection: &str,
+        index: &IdMap<IndexEntry>,
+        tombstones: &FxHashMap<String, u64>,
+        shared: impl Fn(&str) -> bool,
+    ) -> Result<Self> {
+        let mut versions = Versions {
+            retention: Self::load_retention(root, collection)?,
+            ..Default::default()
+        };
+        let path = Layout::versions_file(root, collection);
+        if versions.retention == 0 || !path.exists() {
+            return Ok(versions);
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        for line in content.split_inclusive('\n') {
+            // A torn last line is ignored
+            let Ok(record) = serde_json::from_str::<VersionRecord>(line) else {
+                continue;
+            };
+            let Some(current) = index.get(&record.id) else {
+                continue;
+            };
+            let deleted = tombstones.get(&record.id).copied().unwrap_or(0);
+            let newer = record.offset >= current.offset && !shared(&record.id);
+            if newer || record.offset < deleted {
+                continue;
+            }
+            let docs = versions.docs.entry(record.id).or_default();
+            docs.retain(|v| v.version != record.version);
+            docs.push(Version {
+                version: record.version,
+                entry: IndexEntry {
+                    offset: record.offset,
+                    length: record.length,
+                    checksum: record.checksum,
+                },
+                mtime: record.mtime,
+            });
+        }
+        let retention = versions.retention;
+        for docs in versions.docs.values_mut() {
+            docs.sort_unstable_by_key(|v| v.version);
+            if docs.len() > retention {
+                docs.drain(..docs.len() - retention);
+            }
+        }
+        versions.bytes = versions.entries().map(|(_, v)| v.entry.length as u64).sum();
+        Ok(versions)
+    }
+
+    /// Previous versions kept per document.
+    pub(crate) fn retention(&self) -> usize {
+        self.retention
+    }
+
+    /// Change the retention, dropping versions beyond it. Returns whether
+    /// any were dropped.
+    pub(crate) fn set_retention(&mut self, retention: usize) -> bool {
+        self.retention = retention;
+        let mut dropped = 0;
+        self.docs.retain(|_, docs| {
+            if docs.len() > retention {
+                for version in docs.drain(..docs.len() - retention) {
+                    dropped += version.entry.length as u64;
+                }
+            }
+            !docs.is_empty()
+        });
+        self.bytes -= dropped;
+        dropped > 0
+    }
+
+    /// Number of the current version of a document.
+    pub(crate) fn current(&self, doc_id: &str) -> u64 {
+        self.get(doc_id).last().map_or(1, |v| v.version + 1)
+    }
+
+    /// Keep the line a document was just overwritten from, written at
+    /// `mtime`, dropping the oldest version beyond the retention.
+    pub(crate) fn retain(&mut self, doc_id: &str, entry: IndexEntry, mtime: u64) {
+        if self.retention == 0 {
+            return;
+        }
+        let version = Version {
+            version: self.current(doc_id),
+            entry,
+            mtime,
+        };
+        self.pending.push(VersionRecord::new(doc_id, &version));
+        self.bytes += entry.length as u64;
+        let docs = self.docs.entry(doc_id.to_string()).or_default();
+        docs.push(version);
+        if docs.len() > self.retention {
+            let dropped = docs.remove(0);
+            self.bytes -= dropped.entry.length as u64;
+        }
+    }
+
+    /// Drop the versions of a deleted document.
+    pub(crate) fn remove(&mut self, doc_id: &str) {
+        if let Some(docs) = self.docs.remove(doc_id) {
+            self.bytes -= docs.iter().map(|v| v.entry.length as u64).sum::<u64>();
+        }
+    }
+
+    /// Retained versions of a document, oldest first.
+    pub(crate) fn get(&self, doc_id: &str) -> &[Version] {
+        self.docs.get(doc_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// All retained versions.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (&str, &Version)> {
+        self.docs
+            .iter()
+            .flat_map(|(id, docs)| docs.iter().map(move |v| (id.as_str(), v)))
+    }
+
+    /// Total length of the retained lines.
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Point a version at its line's new place after compaction.
+    pub(crate) fn relocate(&mut self, doc_id: &str, version: u64, entry: IndexEntry) {
+        if let Some(v) = self
+            .docs
+            .get_mut(doc_id)
+            .and_then(|docs| docs.iter_mut().find(|v| v.version == version))
+        {
+            v.entry = entry;
+        }
+    }
+
+    /// Append the records of versions retained since the last sync.
+    pub(crate) fn sync(&mut self, root: &Path, collection: &str) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Layout::versions_file(root, collection))?;
+        let mut writer = BufWriter::new(file);
+        for record in self.pending.drain(..) {
+            writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Replace the file with the records of the retained versions (or
+    /// remove it if there are none).
+    pub(crate) fn save(&mut self, root: &Path, collection: &str) -> Result<()> {
+        self.pending.clear();
+        let path = Layout::versions_file(root, collection);
+        if self.docs.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+        let tmp_file = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_file)?);
+        for (id, version) in self.entries() {
+            writeln!(
+                writer,
+                "{}",
+                serde_json::to_string(&VersionRecord::new(id, version))?
+            )?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        drop(writer);
+        std::fs::rename(&tmp_file, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::{Error, FastStore, OpenMode};
+
+    #[test]
+    fn test_versions() {
+        let tmp = TempDir::new().unwrap();
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.put("a", json!({"n": 0})).unwrap();
+        assert_eq!(store.version_retention(), 0);
+        store.put("a", json!({"n": 1})).unwrap();
+        assert_eq!(store.list_versions("a").len(), 1);
+
+        store.set_version_retention(2).unwrap();
+        for n in 2..5 {
+            store.put("a", json!({"n": n})).unwrap();
+        }
+        store.put("b", json!({"n": 10})).unwrap();
+        store.flush().unwrap();
+        let versions = store.list_versions("a");
+        let numbers: Vec<u64> = versions.iter().map(|v| v.version).collect();
+        assert_eq!(numbers, [2, 3, 4]);
+        assert!(versions[2].current && !versions[1].current);
+        assert_eq!(store.get_version("a", 3).unwrap()["n"], 3);
+        assert_eq!(store.get_version("a", 4).unwrap()["n"], 4);
+        assert!(matches!(
+            store.get_version("a", 1),
+            Err(Error::DocumentNotFound(_))
+        ));
+        assert!(store.list_versions("x").is_empty());
+
+        // Compaction copies the retained versions
+        store.compact().unwrap();
+        assert_eq!(store.dead_bytes(), 0);
+        assert_eq!(store.get_version("a", 2).unwrap()["n"], 2);
+        store.put("a", json!({"n": 5})).unwrap();
+        drop(store);
+
+        let store = FastStore::open_with_mode(tmp.path(), "test", 100, OpenMode::Read).unwrap();
+        assert_eq!(store.version_retention(), 2);
+        assert_eq!(store.list_versions("a").len(), 3);
+        assert_eq!(store.get_version("a", 3).unwrap()["n"], 3);
+        assert!(store.get_version("a", 2).is_err());
+        drop(store);
+
+        // Deleting a document drops its versions, even after a reopen
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        store.delete("a").unwrap();
+        store.put("a", json!({"n": 0})).unwrap();
+        assert_eq!(store.list_versions("a").len(), 1);
+        drop(store);
+        let mut store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.list_versions("a").len(), 1);
+        assert_eq!(store.get("a").unwrap()["n"], 0);
+
+        store.put("b", json!({"n": 11})).unwrap();
+        store.set_version_retention(0).unwrap();
+        assert_eq!(store.list_versions("b").len(), 1);
+        store.compact().unwrap();
+        drop(store);
+        let store = FastStore::open(tmp.path(), "test", 100).unwrap();
+        assert_eq!(store.list_versions("b").len(), 1);
+        assert_eq!(store.get("b").unwrap()["n"], 11);
+    }
+}
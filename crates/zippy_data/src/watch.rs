@@ -0,0 +1,403 @@
+//! Change notifications for collections.
+//!
+//! [`FastStore::subscribe`](crate::FastStore::subscribe) delivers the writes
+//! of one store handle as they are applied. A [`Watcher`] (from
+//! [`ZDSRoot::watch`](crate::ZDSRoot::watch)) polls a collection's files and
+//! reports changes made by any process:
+//!
+//! - fast store collections are watched through their saved `index.bin`
+//!   and `index.delta`: each time either changes, the watcher diffs the documents' checksums
+//!   against the previous index, so changes show up once they are flushed
+//!   and compaction (which moves lines without changing them) reports
+//!   nothing;
+//! - file-per-document collections are watched through the PUT and DELETE
+//!   entries appended to their journal.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    encryption,
+    fast_writer::{now_ms, IndexEntry},
+    id_map::IdMap,
+    Error, FastStore, JournalEntry, Layout, Result,
+};
+
+/// How often [`Watcher::wait`] polls by default.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A change to one document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    /// The document was written (created or overwritten).
+    Put { doc_id: String },
+    /// The document was deleted (or its TTL ran out).
+    Delete { doc_id: String },
+}
+
+impl ChangeEvent {
+    /// ID of the changed document.
+    pub fn doc_id(&self) -> &str {
+        match self {
+            ChangeEvent::Put { doc_id } | ChangeEvent::Delete { doc_id } => doc_id,
+        }
+    }
+
+    /// Whether the document was deleted.
+    pub fn is_delete(&self) -> bool {
+        matches!(self, ChangeEvent::Delete { .. })
+    }
+}
+
+/// Polls a collection's files for changes made by any process.
+///
+/// Only changes after the watcher is created are reported.
+#[derive(Debug)]
+pub struct Watcher {
+    root: PathBuf,
+    collection: String,
+    index_file: PathBuf,
+    index_delta_file: PathBuf,
+    /// Length and modification time of `index.bin` and `index.delta` when
+    /// last read
+    index_stamp: [Option<(u64, SystemTime)>; 2],
+    /// Checksum and length of each live document in the last index read
+    docs: FxHashMap<String, (u32, u32)>,
+    /// Expiry times (Unix ms) of documents written with a TTL
+    expires: FxHashMap<String, u64>,
+    /// Bytes of the journal already read
+    journal_pos: u64,
+    poll_interval: Duration,
+}
+
+impl Watcher {
+    /// Watch `collection` of the store at `root`, starting from its current
+    /// state.
+    pub fn new(root: impl AsRef<Path>, collection: impl AsRef<str>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let collection = collection.as_ref().to_string();
+        Layout::validate_collection_name(&collection)?;
+        if !Layout::collection_dir(&root, &collection).exists() {
+            return Err(Error::CollectionNotFound(collection));
+        }
+
+        let journal_pos =
+            std::fs::metadata(Layout::journal_file(&root, &collection)).map_or(0, |m| m.len());
+        let mut watcher = Watcher {
+            index_file: Layout::meta_dir(&root, &collection).join("index.bin"),
+            index_delta_file: Layout::index_delta(&root, &collection),
+            root,
+            collection,
+            index_stamp: [None; 2],
+            docs: FxHashMap::default(),
+            expires: FxHashMap::default(),
+            journal_pos,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        };
+        watcher.poll_index()?;
+        Ok(watcher)
+    }
+
+    /// Set how often [`wait`](Self::wait) polls.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Name of the watched collection.
+    pub fn collection(&self) -> &str {
+        &self.collection
+    }
+
+    /// Changes since the last poll (or since the watcher was created).
+    ///
+    /// Journal entries come first, in write order; index changes follow,
+    /// deletions first and then writes in data file order.
+    pub fn poll(&mut self) -> Result<Vec<ChangeEvent>> {
+        let mut events = self.poll_journal()?;
+        events.extend(self.poll_index()?);
+        Ok(events)
+    }
+
+    /// Poll until there are changes or `timeout` passes (then the result is
+    /// empty).
+    pub fn wait(&mut self, timeout: Duration) -> Result<Vec<ChangeEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let events = self.poll()?;
+            let now = Instant::now();
+            if !events.is_empty() || now >= deadline {
+                return Ok(events);
+            }
+            std::thread::sleep(self.poll_interval.min(deadline - now));
+        }
+    }
+
+    /// PUT and DELETE entries appended to the journal since the last poll.
+    fn poll_journal(&mut self) -> Result<Vec<ChangeEvent>> {
+        let path = Layout::journal_file(&self.root, &self.collection);
+        let Ok(mut file) = File::open(&path) else {
+            self.journal_pos = 0;
+            return Ok(Vec::new());
+        };
+        let len = file.metadata()?.len();
+        if len < self.journal_pos {
+            // Replaced: everything in it is new
+            self.journal_pos = 0;
+        }
+        if len == self.journal_pos {
+            return Ok(Vec::new());
+        }
+
+        file.seek(SeekFrom::Start(self.journal_pos))?;
+        let mut buf = Vec::with_capacity((len - self.journal_pos) as usize);
+        file.take(len - self.journal_pos).read_to_end(&mut buf)?;
+
+        // An unterminated final entry is still being written
+        let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let mut events = Vec::new();
+        for line in buf[..complete].split(|&b| b == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let entry: JournalEntry = serde_json::from_slice(line).map_err(|e| {
+                Error::JournalCorrupted(format!(
+                    "Invalid entry: {} ({})",
+                    String::from_utf8_lossy(line),
+                    e
+                ))
+            })?;
+            match entry {
+                JournalEntry::Put { doc_id, .. } => events.push(ChangeEvent::Put { doc_id }),
+                JournalEntry::Delete { doc_id, .. } => events.push(ChangeEvent::Delete { doc_id }),
+                _ => {}
+            }
+        }
+        self.journal_pos += complete as u64;
+        Ok(events)
+    }
+
+    /// Differences between the saved index and the last one read, plus
+    /// documents whose TTL ran out since.
+    fn poll_index(&mut self) -> Result<Vec<ChangeEvent>> {
+        let stamp = [&self.index_file, &self.index_delta_file].map(|path| {
+            std::fs::metadata(path)
+                .ok()
+                .map(|m| (m.len(), m.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+        });
+        let mut events = Vec::new();
+
+        if stamp != self.index_stamp {
+            let mut index = IdMap::default();
+            let mut expires = FxHashMap::default();
+            if stamp[0].is_some() {
+                let buf =
+                    match encryption::read_file(&self.root, &self.collection, &self.index_file) {
+                        Ok(buf) => buf,
+                        // Removed between the stat and the read; catch up next poll
+                        Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                            return Ok(events)
+                        }
+                        Err(e) => return Err(e),
+                    };
+                let checksum = FastStore::parse_index_binary(&buf, &mut index, &mut expires)?;
+                match std::fs::read(&self.index_delta_file) {
+                    Ok(delta) => {
+                        let cipher = encryption::load_cipher(&self.root, &self.collection)?;
+                        FastStore::parse_index_delta(
+                            &delta,
+                            checksum,
+                            cipher.as_deref(),
+                            &mut index,
+                            &mut expires,
+                        )?;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            events = self.diff(index, expires);
+            self.index_stamp = stamp;
+        }
+
+        let now = now_ms();
+        let mut expired: Vec<String> = self
+            .expires
+            .iter()
+            .filter(|(_, &expires_at)| expires_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        expired.sort_unstable();
+        for doc_id in expired {
+            self.expires.remove(&doc_id);
+            if self.docs.remove(&doc_id).is_some() {
+                events.push(ChangeEvent::Delete { doc_id });
+            }
+        }
+        Ok(events)
+    }
+
+    /// Replace the known documents with `index`, returning what changed.
+    fn diff(
+        &mut self,
+        index: IdMap<IndexEntry>,
+        mut expires: FxHashMap<String, u64>,
+    ) -> Vec<ChangeEvent> {
+        let now = now_ms();
+        let live = |doc_id: &str| expires.get(doc_id).map_or(true, |&at| at > now);
+
+        let mut deleted: Vec<String> = self
+            .docs
+            .keys()
+            .filter(|id| !index.contains_key(id) || !live(id))
+            .cloned()
+            .collect();
+        deleted.sort_unstable();
+
+        let mut written: Vec<(u64, &str)> = index
+            .iter()
+            .filter(|(id, _)| live(id))
+            .filter(|(id, entry)| self.docs.get(*id) != Some(&(entry.checksum, entry.length)))
+            .map(|(id, entry)| (entry.offset, id))
+            .collect();
+        written.sort_unstable();
+
+        let mut events: Vec<ChangeEvent> = deleted
+            .into_iter()
+            .map(|doc_id| ChangeEvent::Delete { doc_id })
+            .collect();
+        events.extend(written.into_iter().map(|(_, doc_id)| ChangeEvent::Put {
+            doc_id: doc_id.to_string(),
+        }));
+
+        self.docs = index
+            .iter()
+            .filter(|(id, _)| live(id))
+            .map(|(id, entry)| (id.to_string(), (entry.checksum, entry.length)))
+            .collect();
+        expires.retain(|_, &mut at| at > now);
+        self.expires = expires;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{writer::SyncWriter, OpenMode, ZDSRoot};
+
+    #[test]
+    fn test_subscribe() {
+        let tmp = TempDir::new().unwrap();
+        let root = ZDSRoot::open(tmp.path(), 100, OpenMode::ReadWrite).unwrap();
+        let mut store = root.collection("events").unwrap();
+        let events = store.subscribe();
+
+        store.put("a", serde_json::json!({"n": 1})).unwrap();
+        store.put("b", serde_json::json!({"n": 2})).unwrap();
+        store.delete("a").unwrap();
+
+        store.begin().unwrap();
+        store.put("c", serde_json::json!({"n": 3})).unwrap();
+        assert_eq!(events.try_iter().count(), 3);
+        store.commit().unwrap();
+
+        let received: Vec<_> = events.try_iter().collect();
+        assert_eq!(
+            received,
+            [ChangeEvent::Put {
+                doc_id: "c".to_string()
+            }]
+        );
+
+        // A dropped receiver is forgotten on the next write
+        drop(events);
+        store.put("d", serde_json::json!({"n": 4})).unwrap();
+    }
+
+    #[test]
+    fn test_watch_fast_store() {
+        let tmp = TempDir::new().unwrap();
+        let root = ZDSRoot::open(tmp.path(), 100, OpenMode::ReadWrite).unwrap();
+        let mut store = root.collection("events").unwrap();
+        store.put("old", serde_json::json!({"n": 0})).unwrap();
+        store.put("gone", serde_json::json!({"n": 0})).unwrap();
+        store.flush().unwrap();
+
+        let mut watcher = root.watch("events").unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        store.put("new", serde_json::json!({"n": 1})).unwrap();
+        store.put("old", serde_json::json!({"n": 2})).unwrap();
+        store.delete("gone").unwrap();
+        // Unflushed writes aren't visible yet
+        assert!(watcher.poll().unwrap().is_empty());
+        store.flush().unwrap();
+
+        let events = watcher.wait(Duration::from_secs(5)).unwrap();
+        let ids: Vec<_> = events.iter().map(|e| (e.is_delete(), e.doc_id())).collect();
+        assert_eq!(ids, [(true, "gone"), (false, "new"), (false, "old")]);
+
+        // Compaction moves lines without changing documents
+        store.compact().unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // Expiry is reported as a deletion
+        store
+            .put_with_ttl("tmp", serde_json::json!({}), Duration::from_millis(500))
+            .unwrap();
+        store.flush().unwrap();
+        assert_eq!(
+            watcher.poll().unwrap(),
+            [ChangeEvent::Put {
+                doc_id: "tmp".to_string()
+            }]
+        );
+        std::thread::sleep(Duration::from_millis(600));
+        assert_eq!(
+            watcher.poll().unwrap(),
+            [ChangeEvent::Delete {
+                doc_id: "tmp".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_watch_journal() {
+        let tmp = TempDir::new().unwrap();
+        Layout::init_root(tmp.path()).unwrap();
+        Layout::init_collection(tmp.path(), "docs").unwrap();
+        let mut writer = SyncWriter::new(tmp.path(), "docs").unwrap();
+        writer.put("before", &serde_json::json!({"n": 0})).unwrap();
+
+        let mut watcher = Watcher::new(tmp.path(), "docs").unwrap();
+        writer.put("a", &serde_json::json!({"n": 1})).unwrap();
+        writer.delete("before").unwrap();
+
+        assert_eq!(
+            watcher.poll().unwrap(),
+            [
+                ChangeEvent::Put {
+                    doc_id: "a".to_string()
+                },
+                ChangeEvent::Delete {
+                    doc_id: "before".to_string()
+                },
+            ]
+        );
+        assert!(watcher.poll().unwrap().is_empty());
+
+        assert!(matches!(
+            Watcher::new(tmp.path(), "missing"),
+            Err(Error::CollectionNotFound(_))
+        ));
+    }
+}
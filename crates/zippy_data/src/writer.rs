@@ -1,17 +1,43 @@
 //! Buffered writer with crash-safe commits.
+//!
+//! Writers append the index changes of each commit to the collection's
+//! index delta file rather than rewriting `doc_index.jsonl`, so a commit
+//! costs the same however large the collection is. The index is saved in
+//! full on [`checkpoint`](SyncWriter::checkpoint), when the writer is
+//! dropped, and whenever the delta grows as large as the index.
+//!
+//! A writer that crashes mid-commit leaves PUT and DELETE entries without a
+//! COMMIT in the journal. Opening a writer (or an [`Engine`](crate::Engine))
+//! [recovers](recover) them: each document was fully written before its
+//! entry was logged, so the writes are completed and indexed, and temp
+//! files never logged are removed.
+//!
+//! The writer's [`Durability`] decides when documents and journal entries
+//! are synced: by default once per flush, with every document of the batch
+//! written before the journal's COMMIT is synced.
 
 use std::{
+    fs::File,
+    io::Write,
     path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
+use serde::Serialize;
 use serde_json::Value;
 
 use crate::{
-    index::DocIndexEntry,
+    engine::{CollectionStats, StorageMode},
+    fast_writer::{check_line_id, parse_raw_doc},
+    field_index::FieldIndexes,
+    ids::{IdSequence, IdStrategy},
+    index::{DocIndexEntry, IndexChange},
+    json_schema::JsonSchema,
+    manifest,
     schema::SchemaRegistry,
+    store::{ReadStore, Store},
     txlog::{JournalEntry, TransactionLog},
-    Error, IndexRegistry, Layout, Result,
+    Codec, Durability, Error, IndexRegistry, Layout, Result,
 };
 
 /// Write operation.
@@ -30,6 +56,8 @@ pub struct WriteConfig {
     pub max_pending_bytes: usize,
     /// Flush interval in milliseconds
     pub flush_interval_ms: u64,
+    /// When documents and journal entries are synced
+    pub durability: Durability,
 }
 
 impl Default for WriteConfig {
@@ -38,10 +66,176 @@ impl Default for WriteConfig {
             max_pending_ops: 1000,
             max_pending_bytes: 10 * 1024 * 1024, // 10MB
             flush_interval_ms: 1000,
+            durability: Durability::default(),
         }
     }
 }
 
+/// What [`recover`] did with the writes of a crashed writer.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RecoveryReport {
+    /// Uncommitted puts completed and indexed
+    pub puts_replayed: usize,
+    /// Uncommitted deletes completed
+    pub deletes_replayed: usize,
+    /// Uncommitted puts whose document is no longer on disk, dropped
+    pub rolled_back: usize,
+    /// Temp files of writes that never reached the journal, removed
+    pub tmp_files_removed: usize,
+}
+
+impl RecoveryReport {
+    /// Whether there was nothing to recover.
+    pub fn is_empty(&self) -> bool {
+        *self == RecoveryReport::default()
+    }
+}
+
+/// Complete the uncommitted writes in a file-per-document collection's
+/// journal, update its indexes, and remove orphaned temp files.
+///
+/// Must not run while a writer of the collection is active.
+pub fn recover(root: &Path, collection: &str) -> Result<RecoveryReport> {
+    let mut report = RecoveryReport::default();
+    let docs_dir = Layout::docs_dir(root, collection);
+    let uncommitted: Vec<_> = TransactionLog::load_uncommitted(root, collection)?
+        .into_iter()
+        .filter(|entry| {
+            matches!(
+                entry,
+                JournalEntry::Put { .. } | JournalEntry::Delete { .. }
+            )
+        })
+        .collect();
+
+    if !uncommitted.is_empty() {
+        let mut index = IndexRegistry::load(root, collection).unwrap_or_default();
+        let mut schema_registry =
+            SchemaRegistry::load(root, collection).unwrap_or_else(|_| SchemaRegistry::new(false));
+        let mut field_indexes = FieldIndexes::load(root, collection)?;
+
+        for entry in &uncommitted {
+            match entry {
+                JournalEntry::Put { doc_id, .. } => {
+                    let final_path = Layout::doc_file(root, collection, doc_id);
+                    let tmp_path = Layout::doc_tmp_file(root, collection, doc_id);
+                    if tmp_path.exists() {
+                        std::fs::rename(&tmp_path, &final_path)?;
+                    }
+                    if !final_path.exists() {
+                        report.rolled_back += 1;
+                        continue;
+                    }
+
+                    let content = std::fs::read_to_string(&final_path)?;
+                    let doc = Codec::decode(&content)?;
+                    if let Some(old) = index.get(doc_id) {
+                        schema_registry.unregister(&old.schema_id);
+                    }
+                    let schema_id = schema_registry.register(&doc)?;
+                    index.put(DocIndexEntry {
+                        doc_id: doc_id.clone(),
+                        schema_id,
+                        size: content.len() as u64,
+                        mtime: file_mtime(&final_path),
+                    });
+                    field_indexes.insert(doc_id, &doc);
+                    report.puts_replayed += 1;
+                }
+                JournalEntry::Delete { doc_id, .. } => {
+                    let path = Layout::doc_file(root, collection, doc_id);
+                    if path.exists() {
+                        std::fs::remove_file(&path)?;
+                    }
+                    if let Some(old) = index.remove(doc_id) {
+                        schema_registry.unregister(&old.schema_id);
+                    }
+                    field_indexes.remove(doc_id);
+                    report.deletes_replayed += 1;
+                }
+                _ => {}
+            }
+        }
+
+        index.checkpoint(root, collection)?;
+        schema_registry.save(root, collection)?;
+        field_indexes.save(root, collection)?;
+    }
+
+    if docs_dir.exists() {
+        for entry in std::fs::read_dir(&docs_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') && name.ends_with(".tmp") {
+                std::fs::remove_file(entry.path())?;
+                report.tmp_files_removed += 1;
+            }
+        }
+    }
+
+    if !uncommitted.is_empty() {
+        TransactionLog::open(root, collection)?.checkpoint()?;
+    }
+    Ok(report)
+}
+
+/// Write a document's temp file, synced with [`Durability::FsyncOp`] so
+/// the document is on disk before its journal entry.
+fn write_tmp(path: &Path, content: &[u8], durability: Durability) -> Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content)?;
+    if durability.syncs_ops() {
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+/// Create a file-per-document collection and record its storage mode, so
+/// a [`FastStore`](crate::FastStore) doesn't claim it later. Refuses a
+/// JSONL collection, whose readers would never see the files.
+fn init_files_collection(root: &Path, collection: &str) -> Result<()> {
+    if StorageMode::detect(root, collection)? == StorageMode::Jsonl {
+        return Err(Error::Validation(format!(
+            "collection '{}' stores JSONL; write to it with FastStore",
+            collection
+        )));
+    }
+    Layout::init_collection(root, collection)?;
+    if StorageMode::load(root, collection)?.is_none() {
+        StorageMode::Files.save(root, collection)?;
+    }
+    Ok(())
+}
+
+/// Modification time of a file in Unix seconds, 0 if unknown.
+fn file_mtime(path: &Path) -> u64 {
+    std::fs::metadata(path)
+        .ok()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A generated document ID, loading the collection's ID sequence on first
+/// use.
+fn generate_id(
+    root: &Path,
+    collection: &str,
+    sequence: &mut Option<IdSequence>,
+    strategy: IdStrategy,
+    taken: impl Fn(&str) -> bool,
+) -> Result<String> {
+    if let Some(doc_id) = strategy.generate() {
+        return Ok(doc_id);
+    }
+    if sequence.is_none() {
+        *sequence = Some(IdSequence::load(root, collection)?);
+    }
+    Ok(sequence.as_mut().unwrap().allocate(taken))
+}
+
 /// Buffered writer for high-throughput ingestion.
 pub struct BufferedWriter {
     root: PathBuf,
@@ -50,13 +244,27 @@ pub struct BufferedWriter {
     pending_ops: Vec<WriteOp>,
     pending_bytes: usize,
     last_flush: Instant,
+    /// Whether a transaction is holding back flushes
+    in_txn: bool,
     journal: TransactionLog,
     index: IndexRegistry,
+    /// Index changes since the last flush
+    index_changes: Vec<IndexChange>,
     schema_registry: SchemaRegistry,
+    json_schema: Option<JsonSchema>,
+    field_indexes: FieldIndexes,
+    /// Counter of sequential generated IDs (loaded on first use)
+    id_sequence: Option<IdSequence>,
+    /// What opening the writer recovered
+    recovery: RecoveryReport,
 }
 
 impl BufferedWriter {
     /// Create a new buffered writer.
+    ///
+    /// Fails with [`Error::Validation`] for a collection in JSONL storage
+    /// mode (see [`StorageMode::detect`]), which is written by a
+    /// [`FastStore`](crate::FastStore).
     pub fn new(
         root: impl AsRef<Path>,
         collection: impl AsRef<str>,
@@ -66,15 +274,19 @@ impl BufferedWriter {
         let collection = collection.as_ref().to_string();
 
         // Ensure collection exists
-        Layout::init_collection(&root, &collection)?;
+        init_files_collection(&root, &collection)?;
+        let recovery = recover(&root, &collection)?;
 
         // Load or create indexes
         let index = IndexRegistry::load(&root, &collection).unwrap_or_default();
         let schema_registry =
             SchemaRegistry::load(&root, &collection).unwrap_or_else(|_| SchemaRegistry::new(false));
+        let json_schema = JsonSchema::load(&root, &collection)?;
+        let field_indexes = FieldIndexes::load(&root, &collection)?;
 
         // Open transaction log
-        let journal = TransactionLog::open(&root, &collection)?;
+        let mut journal = TransactionLog::open(&root, &collection)?;
+        journal.set_durability(config.durability)?;
 
         Ok(BufferedWriter {
             root,
@@ -83,9 +295,15 @@ impl BufferedWriter {
             pending_ops: Vec::new(),
             pending_bytes: 0,
             last_flush: Instant::now(),
+            in_txn: false,
             journal,
             index,
+            index_changes: Vec::new(),
             schema_registry,
+            json_schema,
+            field_indexes,
+            id_sequence: None,
+            recovery,
         })
     }
 
@@ -93,6 +311,9 @@ impl BufferedWriter {
     pub fn put(&mut self, doc_id: impl Into<String>, doc: Value) -> Result<()> {
         let doc_id = doc_id.into();
         Layout::validate_doc_id(&doc_id)?;
+        if let Some(json_schema) = &self.json_schema {
+            json_schema.validate(&doc)?;
+        }
 
         let doc_size = serde_json::to_string(&doc)?.len();
         self.pending_bytes += doc_size;
@@ -102,6 +323,28 @@ impl BufferedWriter {
         Ok(())
     }
 
+    /// Queue a document under a generated ID, and return the ID.
+    ///
+    /// Sequence numbers already used by documents, written or queued, are
+    /// skipped; the sequence is saved on flush.
+    pub fn put_auto(&mut self, doc: Value, strategy: IdStrategy) -> Result<String> {
+        let pending = &self.pending_ops;
+        let doc_id = generate_id(
+            &self.root,
+            &self.collection,
+            &mut self.id_sequence,
+            strategy,
+            |id| {
+                self.index.get(id).is_some()
+                    || pending
+                        .iter()
+                        .any(|op| matches!(op, WriteOp::Put { doc_id, .. } if doc_id == id))
+            },
+        )?;
+        self.put(doc_id.clone(), doc)?;
+        Ok(doc_id)
+    }
+
     /// Queue a document deletion.
     pub fn delete(&mut self, doc_id: impl Into<String>) -> Result<()> {
         let doc_id = doc_id.into();
@@ -111,8 +354,56 @@ impl BufferedWriter {
         Ok(())
     }
 
+    /// Start a transaction: operations queued until [`commit`](Self::commit)
+    /// are written as one journal batch, and never auto-flushed on their own.
+    ///
+    /// Operations queued before are flushed first. A transaction still open
+    /// when the writer is dropped is discarded.
+    pub fn begin(&mut self) -> Result<()> {
+        if self.in_txn {
+            return Err(Error::TransactionFailed(
+                "a transaction is already in progress".to_string(),
+            ));
+        }
+        self.flush()?;
+        self.in_txn = true;
+        Ok(())
+    }
+
+    /// Check whether a transaction is open.
+    pub fn in_transaction(&self) -> bool {
+        self.in_txn
+    }
+
+    /// Write the transaction's operations, followed by their COMMIT marker.
+    pub fn commit(&mut self) -> Result<()> {
+        if !self.in_txn {
+            return Err(Error::TransactionFailed(
+                "no transaction in progress".to_string(),
+            ));
+        }
+        self.in_txn = false;
+        self.flush()
+    }
+
+    /// Discard the transaction's operations.
+    pub fn rollback(&mut self) -> Result<()> {
+        if !self.in_txn {
+            return Err(Error::TransactionFailed(
+                "no transaction in progress".to_string(),
+            ));
+        }
+        self.in_txn = false;
+        self.pending_ops.clear();
+        self.pending_bytes = 0;
+        Ok(())
+    }
+
     /// Check if we should auto-flush.
     fn maybe_flush(&mut self) -> Result<()> {
+        if self.in_txn {
+            return Ok(());
+        }
         let should_flush = self.pending_ops.len() >= self.config.max_pending_ops
             || self.pending_bytes >= self.config.max_pending_bytes
             || self.last_flush.elapsed() >= Duration::from_millis(self.config.flush_interval_ms);
@@ -123,9 +414,9 @@ impl BufferedWriter {
         Ok(())
     }
 
-    /// Flush all pending operations.
+    /// Flush all pending operations (held back while a transaction is open).
     pub fn flush(&mut self) -> Result<()> {
-        if self.pending_ops.is_empty() {
+        if self.in_txn || self.pending_ops.is_empty() {
             return Ok(());
         }
 
@@ -144,13 +435,39 @@ impl BufferedWriter {
             }
         }
 
-        // Commit the batch
+        // Save indexes, then commit the batch: a crash in between replays
+        // it on the next open
+        let changes = std::mem::take(&mut self.index_changes);
+        self.index
+            .save_changes(&self.root, &self.collection, &changes)?;
+        self.schema_registry.save(&self.root, &self.collection)?;
+        self.field_indexes.save(&self.root, &self.collection)?;
         self.journal.commit()?;
+        if let Some(sequence) = &mut self.id_sequence {
+            sequence.save(&self.root, &self.collection)?;
+        }
+        manifest::record_counts(
+            &self.root,
+            &self.collection,
+            self.index.len(),
+            self.schema_registry.schema_count(),
+        )?;
 
-        // Save indexes
-        self.index.save(&self.root, &self.collection)?;
-        self.schema_registry.save(&self.root, &self.collection)?;
+        Ok(())
+    }
+
+    /// What opening the writer recovered from a crashed writer's journal.
+    pub fn recovery(&self) -> &RecoveryReport {
+        &self.recovery
+    }
 
+    /// Flush pending operations and save the document index in full,
+    /// folding in its delta file.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        self.flush()?;
+        if self.index.delta_len() > 0 {
+            self.index.checkpoint(&self.root, &self.collection)?;
+        }
         Ok(())
     }
 
@@ -162,14 +479,14 @@ impl BufferedWriter {
         // Write to temp file first
         let docs_dir = Layout::docs_dir(&self.root, &self.collection);
         let final_path = Layout::doc_file(&self.root, &self.collection, doc_id);
-        let tmp_path = docs_dir.join(format!(".{}.tmp", doc_id));
+        let tmp_path = Layout::doc_tmp_file(&self.root, &self.collection, doc_id);
 
         std::fs::create_dir_all(&docs_dir)?;
 
         let content = serde_json::to_string_pretty(doc)?;
         let size = content.len() as u64;
 
-        std::fs::write(&tmp_path, &content)?;
+        write_tmp(&tmp_path, content.as_bytes(), self.config.durability)?;
 
         // Log the PUT
         self.journal
@@ -179,19 +496,17 @@ impl BufferedWriter {
         std::fs::rename(&tmp_path, &final_path)?;
 
         // Update index
-        let mtime = std::fs::metadata(&final_path)
-            .ok()
-            .and_then(|m| m.modified().ok())
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-
-        self.index.put(DocIndexEntry {
+        let mtime = file_mtime(&final_path);
+
+        let entry = DocIndexEntry {
             doc_id: doc_id.to_string(),
             schema_id,
             size,
             mtime,
-        });
+        };
+        self.index.put(entry.clone());
+        self.index_changes.push(IndexChange::Put(entry));
+        self.field_indexes.insert(doc_id, doc);
 
         Ok(())
     }
@@ -215,12 +530,21 @@ impl BufferedWriter {
         // Delete file
         std::fs::remove_file(&path)?;
 
-        // Update index
+        // Update indexes
         self.index.remove(doc_id);
+        self.index_changes.push(IndexChange::Remove {
+            doc_id: doc_id.to_string(),
+        });
+        self.field_indexes.remove(doc_id);
 
         Ok(())
     }
 
+    /// Get when documents and journal entries are synced.
+    pub fn durability(&self) -> Durability {
+        self.config.durability
+    }
+
     /// Get current document count.
     pub fn len(&self) -> usize {
         self.index.len()
@@ -235,7 +559,7 @@ impl BufferedWriter {
 impl Drop for BufferedWriter {
     fn drop(&mut self) {
         // Flush any remaining operations
-        let _ = self.flush();
+        let _ = self.checkpoint();
     }
 }
 
@@ -246,19 +570,38 @@ pub struct SyncWriter {
     journal: TransactionLog,
     index: IndexRegistry,
     schema_registry: SchemaRegistry,
+    /// Whether schema counts changed since the registry was saved
+    schemas_dirty: bool,
+    /// Whether the manifest's counts are behind
+    counts_dirty: bool,
+    json_schema: Option<JsonSchema>,
+    field_indexes: FieldIndexes,
+    /// Whether the field indexes changed since they were saved
+    field_indexes_dirty: bool,
+    /// Counter of sequential generated IDs (loaded on first use)
+    id_sequence: Option<IdSequence>,
+    /// What opening the writer recovered
+    recovery: RecoveryReport,
 }
 
 impl SyncWriter {
     /// Create a new synchronous writer.
+    ///
+    /// Fails with [`Error::Validation`] for a collection in JSONL storage
+    /// mode (see [`StorageMode::detect`]), which is written by a
+    /// [`FastStore`](crate::FastStore).
     pub fn new(root: impl AsRef<Path>, collection: impl AsRef<str>) -> Result<Self> {
         let root = root.as_ref().to_path_buf();
         let collection = collection.as_ref().to_string();
 
-        Layout::init_collection(&root, &collection)?;
+        init_files_collection(&root, &collection)?;
+        let recovery = recover(&root, &collection)?;
 
         let index = IndexRegistry::load(&root, &collection).unwrap_or_default();
         let schema_registry =
             SchemaRegistry::load(&root, &collection).unwrap_or_else(|_| SchemaRegistry::new(false));
+        let json_schema = JsonSchema::load(&root, &collection)?;
+        let field_indexes = FieldIndexes::load(&root, &collection)?;
         let journal = TransactionLog::open(&root, &collection)?;
 
         Ok(SyncWriter {
@@ -267,50 +610,107 @@ impl SyncWriter {
             journal,
             index,
             schema_registry,
+            schemas_dirty: false,
+            counts_dirty: false,
+            json_schema,
+            field_indexes,
+            field_indexes_dirty: false,
+            id_sequence: None,
+            recovery,
         })
     }
 
     /// Write a document synchronously.
     pub fn put(&mut self, doc_id: &str, doc: &Value) -> Result<()> {
         Layout::validate_doc_id(doc_id)?;
+        if let Some(json_schema) = &self.json_schema {
+            json_schema.validate(doc)?;
+        }
 
+        let schema_count = self.schema_registry.schema_count();
         let schema_id = self.schema_registry.register(doc)?;
+        let new_schema = self.schema_registry.schema_count() > schema_count;
 
         let docs_dir = Layout::docs_dir(&self.root, &self.collection);
         let final_path = Layout::doc_file(&self.root, &self.collection, doc_id);
-        let tmp_path = docs_dir.join(format!(".{}.tmp", doc_id));
+        let tmp_path = Layout::doc_tmp_file(&self.root, &self.collection, doc_id);
 
         std::fs::create_dir_all(&docs_dir)?;
 
         let content = serde_json::to_string_pretty(doc)?;
         let size = content.len() as u64;
 
-        std::fs::write(&tmp_path, &content)?;
+        write_tmp(&tmp_path, content.as_bytes(), self.journal.durability())?;
         self.journal
             .append(&JournalEntry::put(doc_id, &schema_id, size))?;
         std::fs::rename(&tmp_path, &final_path)?;
 
-        let mtime = std::fs::metadata(&final_path)
-            .ok()
-            .and_then(|m| m.modified().ok())
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
+        let mtime = file_mtime(&final_path);
 
-        self.index.put(DocIndexEntry {
+        let entry = DocIndexEntry {
             doc_id: doc_id.to_string(),
             schema_id,
             size,
             mtime,
-        });
+        };
+        self.index.put(entry.clone());
+        if !self.field_indexes.is_empty() {
+            self.field_indexes.insert(doc_id, doc);
+            self.field_indexes_dirty = true;
+        }
 
+        self.index
+            .save_changes(&self.root, &self.collection, &[IndexChange::Put(entry)])?;
+        // Counts of known schemas, the manifest's and the field indexes wait
+        // for the next checkpoint
+        self.schemas_dirty = true;
+        self.counts_dirty = true;
+        if new_schema {
+            self.save_schemas()?;
+        }
         self.journal.commit()?;
-        self.index.save(&self.root, &self.collection)?;
-        self.schema_registry.save(&self.root, &self.collection)?;
 
         Ok(())
     }
 
+    /// Write a document under a generated ID synchronously, and return the
+    /// ID. Sequence numbers already used by documents are skipped.
+    pub fn put_auto(&mut self, doc: &Value, strategy: IdStrategy) -> Result<String> {
+        let index = &self.index;
+        let doc_id = generate_id(
+            &self.root,
+            &self.collection,
+            &mut self.id_sequence,
+            strategy,
+            |id| index.get(id).is_some(),
+        )?;
+        self.put(&doc_id, doc)?;
+        if let Some(sequence) = &mut self.id_sequence {
+            sequence.save(&self.root, &self.collection)?;
+        }
+        Ok(doc_id)
+    }
+
+    /// Apply a JSON merge patch (RFC 7386) to a document synchronously and
+    /// return the patched document.
+    pub fn patch(&mut self, doc_id: &str, patch: &Value) -> Result<Value> {
+        Layout::validate_doc_id(doc_id)?;
+        if !patch.is_object() {
+            return Err(Error::Validation(
+                "merge patch must be a JSON object".to_string(),
+            ));
+        }
+        let path = Layout::doc_file(&self.root, &self.collection, doc_id);
+        if !path.exists() {
+            return Err(Error::DocumentNotFound(doc_id.to_string()));
+        }
+
+        let mut doc = Codec::decode(&std::fs::read_to_string(&path)?)?;
+        Codec::merge_patch(&mut doc, patch);
+        self.put(doc_id, &doc)?;
+        Ok(doc)
+    }
+
     /// Delete a document synchronously.
     pub fn delete(&mut self, doc_id: &str) -> Result<()> {
         let path = Layout::doc_file(&self.root, &self.collection, doc_id);
@@ -326,12 +726,154 @@ impl SyncWriter {
         self.journal.append(&JournalEntry::delete(doc_id))?;
         std::fs::remove_file(&path)?;
         self.index.remove(doc_id);
+        if !self.field_indexes.is_empty() {
+            self.field_indexes.remove(doc_id);
+            self.field_indexes_dirty = true;
+        }
+        let change = IndexChange::Remove {
+            doc_id: doc_id.to_string(),
+        };
+        self.index
+            .save_changes(&self.root, &self.collection, &[change])?;
+        self.schemas_dirty = true;
+        self.counts_dirty = true;
         self.journal.commit()?;
-        self.index.save(&self.root, &self.collection)?;
-        self.schema_registry.save(&self.root, &self.collection)?;
 
         Ok(())
     }
+
+    /// What opening the writer recovered from a crashed writer's journal.
+    pub fn recovery(&self) -> &RecoveryReport {
+        &self.recovery
+    }
+
+    /// Get when documents and journal entries are synced
+    /// ([`Durability::FsyncBatch`] by default).
+    pub fn durability(&self) -> Durability {
+        self.journal.durability()
+    }
+
+    /// Set when documents and journal entries are synced.
+    pub fn set_durability(&mut self, durability: Durability) -> Result<()> {
+        self.journal.set_durability(durability)
+    }
+
+    /// Save the document index in full, folding in its delta file, along
+    /// with the schema counts, the field indexes and the manifest's document
+    /// and schema counts.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        if self.index.delta_len() > 0 {
+            self.index.checkpoint(&self.root, &self.collection)?;
+        }
+        if self.schemas_dirty {
+            self.save_schemas()?;
+        }
+        if self.field_indexes_dirty {
+            self.field_indexes.save(&self.root, &self.collection)?;
+            self.field_indexes_dirty = false;
+        }
+        if self.counts_dirty {
+            manifest::record_counts(
+                &self.root,
+                &self.collection,
+                self.index.len(),
+                self.schema_registry.schema_count(),
+            )?;
+            self.counts_dirty = false;
+        }
+        Ok(())
+    }
+
+    fn save_schemas(&mut self) -> Result<()> {
+        self.schema_registry.save(&self.root, &self.collection)?;
+        self.schemas_dirty = false;
+        Ok(())
+    }
+}
+
+/// A file-per-document collection as a [`Store`]; documents are read from
+/// their files.
+impl Store for SyncWriter {
+    fn put(&mut self, doc_id: &str, doc: Value) -> Result<()> {
+        SyncWriter::put(self, doc_id, &doc)
+    }
+
+    fn put_raw_line(&mut self, doc_id: &str, line: &[u8]) -> Result<()> {
+        check_line_id(doc_id, line)?;
+        SyncWriter::put(self, doc_id, &parse_raw_doc(line)?)
+    }
+
+    fn put_auto(&mut self, doc: Value, strategy: IdStrategy) -> Result<String> {
+        SyncWriter::put_auto(self, &doc, strategy)
+    }
+
+    fn patch(&mut self, doc_id: &str, patch: &Value) -> Result<Value> {
+        SyncWriter::patch(self, doc_id, patch)
+    }
+
+    fn delete(&mut self, doc_id: &str) -> Result<()> {
+        SyncWriter::delete(self, doc_id)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.checkpoint()
+    }
+}
+
+impl ReadStore for SyncWriter {
+    fn storage(&self) -> StorageMode {
+        StorageMode::Files
+    }
+
+    fn get(&self, doc_id: &str) -> Result<Value> {
+        let path = Layout::doc_file(&self.root, &self.collection, doc_id);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Codec::decode(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(Error::DocumentNotFound(doc_id.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn exists(&self, doc_id: &str) -> bool {
+        self.index.get(doc_id).is_some()
+    }
+
+    fn doc_ids(&self) -> Vec<String> {
+        self.index.all_doc_ids().to_vec()
+    }
+
+    fn scan(&self) -> Result<Vec<Value>> {
+        self.index
+            .all_doc_ids()
+            .iter()
+            .map(|doc_id| self.get(doc_id))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn stats(&self) -> CollectionStats {
+        CollectionStats {
+            collection: self.collection.clone(),
+            doc_count: self.index.len(),
+            schema_count: self.schema_registry.schema_count(),
+            total_size: self.index.total_size(),
+            strict_mode: self.schema_registry.is_strict(),
+            storage: StorageMode::Files,
+            fast: None,
+            recovery: (!self.recovery.is_empty()).then(|| self.recovery.clone()),
+        }
+    }
+}
+
+impl Drop for SyncWriter {
+    fn drop(&mut self) {
+        let _ = self.checkpoint();
+    }
 }
 
 #[cfg(test)]
@@ -349,10 +891,12 @@ mod tests {
 
         let config = WriteConfig {
             max_pending_ops: 2,
+            durability: Durability::FsyncOp,
             ..Default::default()
         };
 
         let mut writer = BufferedWriter::new(root, "test", config).unwrap();
+        assert_eq!(writer.durability(), Durability::FsyncOp);
 
         writer.put("doc1", json!({"name": "alice"})).unwrap();
         writer.put("doc2", json!({"name": "bob"})).unwrap();
@@ -363,6 +907,37 @@ mod tests {
         assert!(Layout::doc_file(root, "test", "doc2").exists());
     }
 
+    #[test]
+    fn test_buffered_writer_transaction() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+
+        let config = WriteConfig {
+            max_pending_ops: 1,
+            ..Default::default()
+        };
+        let mut writer = BufferedWriter::new(root, "test", config).unwrap();
+
+        // No auto-flush inside a transaction
+        writer.begin().unwrap();
+        writer.put("doc1", json!({"name": "alice"})).unwrap();
+        writer.put("doc2", json!({"name": "bob"})).unwrap();
+        writer.flush().unwrap();
+        assert!(!Layout::doc_file(root, "test", "doc1").exists());
+        writer.rollback().unwrap();
+        assert!(writer.commit().is_err());
+
+        writer.begin().unwrap();
+        writer.put("doc1", json!({"name": "alice"})).unwrap();
+        writer.put("doc2", json!({"name": "bob"})).unwrap();
+        writer.commit().unwrap();
+        assert!(Layout::doc_file(root, "test", "doc1").exists());
+        assert!(Layout::doc_file(root, "test", "doc2").exists());
+        let journal = TransactionLog::open(root, "test").unwrap();
+        assert!(journal.get_uncommitted().unwrap().is_empty());
+    }
+
     #[test]
     fn test_sync_writer() {
         let tmp = TempDir::new().unwrap();
@@ -374,7 +949,201 @@ mod tests {
         writer.put("doc1", &json!({"name": "alice"})).unwrap();
         assert!(Layout::doc_file(root, "test", "doc1").exists());
 
+        let patched = writer
+            .patch("doc1", &json!({"age": 30, "name": null}))
+            .unwrap();
+        assert_eq!(patched, json!({"age": 30}));
+        let content = std::fs::read_to_string(Layout::doc_file(root, "test", "doc1")).unwrap();
+        assert_eq!(Codec::decode(&content).unwrap(), patched);
+        assert!(writer.patch("doc2", &json!({"age": 1})).is_err());
+
         writer.delete("doc1").unwrap();
         assert!(!Layout::doc_file(root, "test", "doc1").exists());
     }
+
+    #[test]
+    fn test_writer_storage_mode() {
+        use crate::{Engine, FastStore};
+
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+
+        // Writers record file storage, so a fast store doesn't claim it
+        let mut writer = SyncWriter::new(root, "files").unwrap();
+        writer.put("doc1", &json!({"n": 1})).unwrap();
+        drop(writer);
+        assert_eq!(
+            StorageMode::load(root, "files").unwrap(),
+            Some(StorageMode::Files)
+        );
+        assert!(FastStore::open(root, "files", 100).is_err());
+
+        // Nor does it claim files of a collection that never recorded its
+        // storage mode
+        Layout::init_collection(root, "legacy").unwrap();
+        std::fs::write(Layout::doc_file(root, "legacy", "doc1"), r#"{"n":1}"#).unwrap();
+        assert!(FastStore::open(root, "legacy", 100).is_err());
+        assert_eq!(StorageMode::load(root, "legacy").unwrap(), None);
+        let engine = Engine::open(root, "legacy").unwrap();
+        assert_eq!(engine.storage_mode(), StorageMode::Files);
+        assert_eq!(engine.get_document("doc1").unwrap(), json!({"n": 1}));
+
+        // And writers refuse JSONL collections
+        let mut store = FastStore::open(root, "jsonl", 100).unwrap();
+        store.put("doc1", json!({"n": 1})).unwrap();
+        store.flush().unwrap();
+        assert!(SyncWriter::new(root, "jsonl").is_err());
+        assert!(BufferedWriter::new(root, "jsonl", WriteConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_sync_writer_index_delta() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        for i in 0..10 {
+            writer.put(&format!("doc{}", i), &json!({"n": i})).unwrap();
+        }
+        writer.delete("doc3").unwrap();
+
+        // Commits only append to the delta file
+        assert!(!Layout::doc_index(root, "test").exists());
+        let delta = std::fs::read_to_string(Layout::doc_index_delta(root, "test")).unwrap();
+        assert_eq!(delta.lines().count(), 11);
+        let index = IndexRegistry::load(root, "test").unwrap();
+        assert_eq!(index.len(), 9);
+        assert!(!index.contains("doc3"));
+        let schemas = SchemaRegistry::load(root, "test").unwrap();
+        assert_eq!(schemas.schema_count(), 1);
+
+        // Dropping the writer checkpoints the index and schema counts
+        drop(writer);
+        assert!(!Layout::doc_index_delta(root, "test").exists());
+        let index = IndexRegistry::load(root, "test").unwrap();
+        assert_eq!(index.len(), 9);
+        assert_eq!(index.get_doc_id_at(8), Some("doc9"));
+        let schemas = SchemaRegistry::load(root, "test").unwrap();
+        assert_eq!(schemas.total_doc_count(), 9);
+
+        let mut writer = BufferedWriter::new(root, "test", WriteConfig::default()).unwrap();
+        writer.put("doc10", json!({"n": 10})).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(IndexRegistry::load(root, "test").unwrap().len(), 10);
+        writer.checkpoint().unwrap();
+        assert!(!Layout::doc_index_delta(root, "test").exists());
+    }
+
+    #[test]
+    fn test_sync_writer_field_indexes() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        Layout::init_collection(root, "test").unwrap();
+        crate::FieldIndex::new("tag").save(root, "test").unwrap();
+        let index_file = Layout::field_index_file(root, "test", "tag");
+
+        // Puts and deletes leave the saved index alone until a checkpoint
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        for i in 0..4 {
+            let tag = if i % 2 == 0 { "even" } else { "odd" };
+            writer
+                .put(&format!("doc{}", i), &json!({"tag": tag}))
+                .unwrap();
+        }
+        writer.delete("doc2").unwrap();
+        assert_eq!(std::fs::read_to_string(&index_file).unwrap(), "");
+        writer.checkpoint().unwrap();
+        let indexes = FieldIndexes::load(root, "test").unwrap();
+        let index = indexes.get("tag").unwrap();
+        assert_eq!(index.lookup(&json!("even")), vec!["doc0".to_string()]);
+        assert_eq!(index.lookup(&json!("odd")).len(), 2);
+        drop(writer);
+
+        // A corrupt index is an error, not an empty index
+        std::fs::write(&index_file, "{not json\n").unwrap();
+        assert!(SyncWriter::new(root, "test").is_err());
+        assert!(BufferedWriter::new(root, "test", WriteConfig::default()).is_err());
+    }
+
+    /// Leave the journal of a writer that crashed in the middle of a batch
+    /// putting `doc2` and deleting `doc1`.
+    fn simulate_crash(root: &Path) {
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        writer.put("doc1", &json!({"name": "alice"})).unwrap();
+        drop(writer);
+
+        let docs_dir = Layout::docs_dir(root, "test");
+        std::fs::write(docs_dir.join(".doc2.tmp"), r#"{"name": "bob"}"#).unwrap();
+        std::fs::write(docs_dir.join(".doc9.tmp"), "{").unwrap();
+        let mut journal = TransactionLog::open(root, "test").unwrap();
+        journal
+            .append(&JournalEntry::put("doc2", "schema", 15))
+            .unwrap();
+        journal.append(&JournalEntry::delete("doc1")).unwrap();
+        journal
+            .append(&JournalEntry::put("doc3", "schema", 2))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_writer_recovery() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        simulate_crash(root);
+
+        let writer = BufferedWriter::new(root, "test", WriteConfig::default()).unwrap();
+        assert_eq!(
+            writer.recovery(),
+            &RecoveryReport {
+                puts_replayed: 1,
+                deletes_replayed: 1,
+                rolled_back: 1,
+                tmp_files_removed: 1,
+            }
+        );
+        assert_eq!(writer.len(), 1);
+        assert!(Layout::doc_file(root, "test", "doc2").exists());
+        assert!(!Layout::doc_file(root, "test", "doc1").exists());
+        let docs: Vec<_> = std::fs::read_dir(Layout::docs_dir(root, "test"))
+            .unwrap()
+            .collect();
+        assert_eq!(docs.len(), 1);
+        drop(writer);
+
+        // Recovered writes are committed, so the next open has nothing to do
+        let writer = SyncWriter::new(root, "test").unwrap();
+        assert!(writer.recovery().is_empty());
+        let index = IndexRegistry::load(root, "test").unwrap();
+        assert_eq!(index.all_doc_ids(), ["doc2"]);
+        let schemas = SchemaRegistry::load(root, "test").unwrap();
+        assert_eq!(schemas.total_doc_count(), 1);
+    }
+
+    #[test]
+    fn test_writer_json_schema() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        Layout::init_collection(root, "test").unwrap();
+        JsonSchema::new(json!({"required": ["name"]}))
+            .unwrap()
+            .save(root, "test")
+            .unwrap();
+
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        writer.put("doc1", &json!({"name": "alice"})).unwrap();
+        let err = writer.put("doc2", &json!({"age": 1})).unwrap_err();
+        assert!(matches!(err, Error::SchemaViolation(_)));
+        assert!(writer.patch("doc1", &json!({"name": null})).is_err());
+        assert!(!Layout::doc_file(root, "test", "doc2").exists());
+
+        let mut writer = BufferedWriter::new(root, "test", WriteConfig::default()).unwrap();
+        assert!(writer.put("doc3", json!({"age": 1})).is_err());
+        writer.flush().unwrap();
+        assert!(!Layout::doc_file(root, "test", "doc3").exists());
+    }
 }
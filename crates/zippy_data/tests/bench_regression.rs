@@ -0,0 +1,72 @@
+//! Tests for the scan regression bench (`benches/regression.rs`).
+//!
+//! Benches without the test harness can't hold `#[cfg(test)]` tests, so
+//! the bench is compiled in here as a module.
+
+#[allow(dead_code)]
+#[path = "../benches/regression.rs"]
+mod regression;
+
+use serde_json::{json, Map, Value};
+use tempfile::TempDir;
+use zippy_data::Layout;
+
+use regression::{generate, measure, regressions, COLLECTION};
+
+fn metrics(values: Value) -> Map<String, Value> {
+    values.as_object().unwrap().clone()
+}
+
+#[test]
+fn test_regressions_beyond_threshold() {
+    let baseline = json!({"metrics": {
+        "warm_scan_ms": 100.0,
+        "filtered_scan_ms": 100.0,
+        "random_get_us": 0.0,
+    }});
+    let now = metrics(json!({
+        "warm_scan_ms": 125.0,
+        "filtered_scan_ms": 115.0,
+        "random_get_us": 3.0,
+        "cold_open_ms": 50.0,
+    }));
+
+    // Only the scan that slowed down by more than 20% counts; metrics the
+    // baseline lacks or measured as zero can't regress
+    assert_eq!(
+        regressions(&now, &baseline, 0.2),
+        ["warm_scan_ms: 100 -> 125 (+25%)"]
+    );
+    assert_eq!(regressions(&now, &baseline, 0.1).len(), 2);
+    assert!(regressions(&now, &baseline, 0.3).is_empty());
+    assert!(regressions(&now, &json!({}), 0.0).is_empty());
+}
+
+#[test]
+fn test_generate_and_measure() {
+    let tmp = TempDir::new().unwrap();
+    let docs = generate(tmp.path(), 1);
+    assert!(docs > 0);
+
+    // A second run of the same size reuses the data
+    let data_file = Layout::data_file(tmp.path(), COLLECTION);
+    let modified = std::fs::metadata(&data_file).unwrap().modified().unwrap();
+    assert_eq!(generate(tmp.path(), 1), docs);
+    assert_eq!(
+        std::fs::metadata(&data_file).unwrap().modified().unwrap(),
+        modified
+    );
+
+    let (metrics, counts) = measure(tmp.path(), docs, 1);
+    for name in [
+        "cold_open_ms",
+        "warm_scan_ms",
+        "filtered_scan_ms",
+        "random_get_us",
+    ] {
+        assert!(metrics[name].as_f64().unwrap() >= 0.0, "{}", name);
+    }
+    assert_eq!(counts["scanned"], docs);
+    let matched = counts["matched"].as_u64().unwrap() as usize;
+    assert!(matched > 0 && matched < docs / 4);
+}
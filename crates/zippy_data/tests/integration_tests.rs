@@ -8,7 +8,7 @@ use serde_json::json;
 use tempfile::TempDir;
 use zippy_data::{
     writer::{BufferedWriter, SyncWriter, WriteConfig},
-    Engine, FastStore, Layout, Result,
+    Engine, FastStore, Layout, Result, StorageMode,
 };
 
 /// Create a temporary test directory with proper layout.
@@ -315,6 +315,7 @@ mod writers {
             max_pending_ops: 1000,
             max_pending_bytes: 10 * 1024 * 1024,
             flush_interval_ms: 60000,
+            ..Default::default()
         };
 
         let mut writer = BufferedWriter::new(&root, "buffered", config)?;
@@ -351,15 +352,19 @@ mod cross_component {
             store.flush()?;
         }
 
-        // Also write with SyncWriter (different storage mode)
-        {
-            let mut writer = SyncWriter::new(&root, "cross")?;
-            writer.put("sync_doc1", &json!({"source": "syncwriter"}))?;
-        }
+        // The FastStore wrote first, so the manifest marks the collection
+        // JSONL-backed: a file-per-document writer would write documents
+        // no reader sees, and is refused
+        assert!(matches!(
+            SyncWriter::new(&root, "cross"),
+            Err(zippy_data::Error::Validation(_))
+        ));
+        assert!(!Layout::docs_dir(&root, "cross").exists());
 
-        // Read with Engine (should see SyncWriter docs)
         let engine = Engine::open(&root, "cross")?;
-        assert!(!engine.is_empty());
+        assert_eq!(engine.storage_mode(), StorageMode::Jsonl);
+        assert_eq!(engine.len(), 2);
+        assert_eq!(engine.get_document("fast_doc1")?["source"], "faststore");
 
         Ok(())
     }
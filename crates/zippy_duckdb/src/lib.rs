@@ -1,6 +1,9 @@
 //! DuckDB extension for ZDS (Zippy Data System)
 //!
-//! Provides `read_zds(path, collection, ...)` table function.
+//! Provides `read_zds(path, collection, ...)` table function, the
+//! `zds_collections(path)` and `zds_stats(path, collection)` metadata table
+//! functions and a `zds` COPY format
+//! (`COPY (SELECT ...) TO 'store' (FORMAT zds, COLLECTION 'name')`).
 //!
 //! # Implementation Notes
 //!
@@ -10,19 +13,50 @@
 //!
 //! Table functions require:
 //! - `bind`: Parse parameters and determine output schema
-//! - `init`: Initialize scan state
+//! - `init`: Initialize global scan state (partition plan, max threads)
+//! - `local_init`: Initialize per-thread scan state
 //! - `main`: Produce output tuples
 //!
+//! Scans are partitioned: the global state splits the collection's doc order
+//! into contiguous ranges and each DuckDB thread claims ranges until none are
+//! left, driving one `Scanner` per partition.
+//!
+//! Copy functions require:
+//! - `bind`: Parse options (COLLECTION, ID column)
+//! - `initialize_global`: Open the destination store
+//! - `sink`: Convert each DataChunk to JSONL and append it
+//! - `finalize`: Flush data and index
+//!
 //! This is a stub implementation. Full implementation requires linking
 //! against DuckDB's C API headers.
 
-use std::ffi::{c_char, c_void};
+use std::{
+    ffi::{c_char, c_void},
+    fmt,
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
 
-use zippy_data::{Engine, Scanner};
+use serde_json::{json, Map, Value};
+use zippy_data::{
+    engine::CollectionStats,
+    infer::{self, FieldType, DEFAULT_SAMPLE_SIZE},
+    ContainerFS, Engine, Error, FastStore, Layout, Result, Scanner,
+};
 
 /// Extension version (must match DuckDB version for compatibility).
 pub const EXTENSION_VERSION: &str = "0.1.0";
 
+/// DuckDB's standard vector size (rows per output chunk).
+pub const STANDARD_VECTOR_SIZE: usize = 2048;
+
+/// Target number of documents per scan partition.
+pub const PARTITION_SIZE: usize = STANDARD_VECTOR_SIZE * 8;
+
 /// Bind data for read_zds table function.
 #[repr(C)]
 pub struct ReadZdsBindData {
@@ -40,16 +74,92 @@ pub struct ReadZdsInitData {
 }
 
 /// Global state for the extension.
+///
+/// Shared by all scan threads. Holds the partition plan and hands out
+/// partitions to threads in order.
 pub struct ReadZdsGlobalState {
     pub engine: Option<Engine>,
+    partitions: Vec<Range<usize>>,
+    next_partition: AtomicUsize,
+}
+
+impl ReadZdsGlobalState {
+    /// Plan a partitioned scan over `engine`, using at most `max_threads` threads.
+    pub fn new(engine: Engine, max_threads: usize) -> Self {
+        let count = engine
+            .len()
+            .div_ceil(PARTITION_SIZE)
+            .max(max_threads.max(1));
+        let partitions = engine.partitions(count);
+        ReadZdsGlobalState {
+            engine: Some(engine),
+            partitions,
+            next_partition: AtomicUsize::new(0),
+        }
+    }
+
+    /// Maximum useful scan parallelism (reported to DuckDB from `init`).
+    pub fn max_threads(&self) -> usize {
+        self.partitions.len().max(1)
+    }
+
+    /// Planned partitions, as ranges of document positions.
+    pub fn partitions(&self) -> &[Range<usize>] {
+        &self.partitions
+    }
+
+    /// Claim the next unscanned partition, if any.
+    pub fn next_partition(&self) -> Option<Range<usize>> {
+        let idx = self.next_partition.fetch_add(1, Ordering::Relaxed);
+        self.partitions.get(idx).cloned()
+    }
 }
 
 /// Local state per thread/partition.
+#[derive(Default)]
 pub struct ReadZdsLocalState {
     pub scanner: Option<Scanner>,
     pub done: bool,
 }
 
+impl ReadZdsLocalState {
+    /// Create an idle local state; partitions are claimed lazily.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch up to `max_rows` documents, claiming new partitions from the
+    /// global state as the current one is exhausted.
+    pub fn next_batch(
+        &mut self,
+        global: &ReadZdsGlobalState,
+        max_rows: usize,
+    ) -> Result<Vec<serde_json::Value>> {
+        let mut batch = Vec::with_capacity(max_rows.min(STANDARD_VECTOR_SIZE));
+        while !self.done && batch.len() < max_rows {
+            if self.scanner.is_none() {
+                match (global.next_partition(), global.engine.as_ref()) {
+                    (Some(range), Some(engine)) => {
+                        self.scanner = Some(engine.scan_range(range, None, None)?);
+                    }
+                    _ => {
+                        self.done = true;
+                        break;
+                    }
+                }
+            }
+
+            if let Some(scanner) = self.scanner.as_mut() {
+                match scanner.next_doc()? {
+                    Some(doc) => batch.push(doc),
+                    None => self.scanner = None,
+                }
+            }
+        }
+        Ok(batch)
+    }
+}
+
 // ============================================================================
 // DuckDB Extension Entry Points (stubs)
 // ============================================================================
@@ -59,7 +169,7 @@ pub struct ReadZdsLocalState {
 /// Called when the extension is loaded. Should register table functions.
 #[no_mangle]
 pub extern "C" fn duckdb_init(_db: *mut c_void) {
-    // TODO: Register read_zds table function
+    // TODO: Register read_zds, zds_collections and zds_stats table functions
     // duckdb_register_table_function(db, create_read_zds_function());
 }
 
@@ -90,20 +200,35 @@ pub extern "C" fn duckdb_version() -> *const c_char {
 pub extern "C" fn read_zds_bind(_info: *mut c_void) {
     // TODO: Implement bind logic
     // 1. Parse path and collection parameters
-    // 2. Open Engine to get schema info
-    // 3. Set output column names and types based on schema
-    // 4. Store bind data for init/main
+    // 2. Open Engine and infer_schema() (STRUCT/LIST for nested fields),
+    //    raising a bind error if it fails
+    // 3. Add a result column per InferredSchema column, creating each
+    //    duckdb_logical_type from its LogicalType
+    // 4. Store the InferredSchema in the bind data for init/main; it owns
+    //    the column names and is dropped with the bind data, so repeated
+    //    binds in a long-lived session don't leak
 }
 
-/// Init function: initialize scan state.
+/// Init function: initialize global scan state.
 ///
-/// Called once per thread/partition before scanning.
+/// Called once per scan before any thread starts.
 #[no_mangle]
 pub extern "C" fn read_zds_init(_info: *mut c_void) {
     // TODO: Implement init logic
-    // 1. Get bind data (path, collection, projection)
-    // 2. Create Engine and Scanner
-    // 3. Store in init data
+    // 1. Get bind data (path, collection, and the projected columns'
+    //    InferredSchema::names as fields)
+    // 2. Create Engine and ReadZdsGlobalState::new(engine, threads)
+    // 3. Report global_state.max_threads() to DuckDB
+}
+
+/// Local init function: initialize per-thread scan state.
+///
+/// Called once per thread; the thread then claims partitions from the global state.
+#[no_mangle]
+pub extern "C" fn read_zds_local_init(_info: *mut c_void) {
+    // TODO: Implement local init logic
+    // 1. Create ReadZdsLocalState::new()
+    // 2. Store in local init data
 }
 
 /// Main function: produce output tuples.
@@ -112,65 +237,589 @@ pub extern "C" fn read_zds_init(_info: *mut c_void) {
 #[no_mangle]
 pub extern "C" fn read_zds_main(_info: *mut c_void, _output: *mut c_void) {
     // TODO: Implement main logic
-    // 1. Get scanner from local state
-    // 2. Fetch batch of documents
-    // 3. Convert to DuckDB vectors
+    // 1. Get global and local state
+    // 2. local.next_batch(&global, STANDARD_VECTOR_SIZE)
+    // 3. Convert to DuckDB vectors: STRUCT/LIST children recursively, JSON
+    //    columns as serialized text, mismatched values as NULL
     // 4. Set cardinality
 }
 
+// ============================================================================
+// Metadata Table Functions
+// ============================================================================
+
+/// Output columns of `zds_collections(path)`.
+pub const ZDS_COLLECTIONS_COLUMNS: &[(&str, &str)] = &[
+    ("collection", "VARCHAR"),
+    ("storage", "VARCHAR"),
+    ("doc_count", "BIGINT"),
+];
+
+/// Output columns of `zds_stats(path, collection)`. The storage columns
+/// after `strict_mode` are NULL for file-per-document collections.
+pub const ZDS_STATS_COLUMNS: &[(&str, &str)] = &[
+    ("collection", "VARCHAR"),
+    ("storage", "VARCHAR"),
+    ("doc_count", "BIGINT"),
+    ("schema_count", "BIGINT"),
+    ("total_size", "BIGINT"),
+    ("strict_mode", "BOOLEAN"),
+    ("data_bytes", "BIGINT"),
+    ("live_bytes", "BIGINT"),
+    ("dead_bytes", "BIGINT"),
+    ("index_bytes", "BIGINT"),
+    ("index_memory_bytes", "BIGINT"),
+    ("segments", "BIGINT"),
+    ("tombstones", "BIGINT"),
+    ("compression", "VARCHAR"),
+];
+
+/// Rows of `zds_collections(path)`, one per collection in name order,
+/// matching [`ZDS_COLLECTIONS_COLUMNS`].
+pub fn zds_collections(path: impl AsRef<Path>) -> Result<Vec<Vec<Value>>> {
+    let path = path.as_ref();
+    let mut names = ContainerFS::open(path)?.list_collections()?;
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let engine = Engine::open(path, &name)?;
+            Ok(vec![
+                json!(name),
+                json!(engine.storage_mode().as_str()),
+                json!(engine.len()),
+            ])
+        })
+        .collect()
+}
+
+/// The row of `zds_stats(path, collection)`, matching
+/// [`ZDS_STATS_COLUMNS`].
+pub fn zds_stats(path: impl AsRef<Path>, collection: &str) -> Result<Vec<Value>> {
+    let path = path.as_ref();
+    if !ContainerFS::open(path)?
+        .list_collections()?
+        .iter()
+        .any(|name| name == collection)
+    {
+        return Err(Error::CollectionNotFound(collection.to_string()));
+    }
+    Ok(stats_row(&Engine::open(path, collection)?.stats()))
+}
+
+fn stats_row(stats: &CollectionStats) -> Vec<Value> {
+    let fast = stats.fast.as_ref();
+    vec![
+        json!(stats.collection),
+        json!(stats.storage.as_str()),
+        json!(stats.doc_count),
+        json!(stats.schema_count),
+        json!(stats.total_size),
+        json!(stats.strict_mode),
+        json!(fast.map(|f| f.data_bytes)),
+        json!(fast.map(|f| f.live_bytes)),
+        json!(fast.map(|f| f.dead_bytes)),
+        json!(fast.map(|f| f.index_bytes)),
+        json!(fast.map(|f| f.index_memory_bytes)),
+        json!(fast.map(|f| f.segments)),
+        json!(fast.map(|f| f.tombstones)),
+        json!(fast.map(|f| f.compression.as_str())),
+    ]
+}
+
+/// Bind function for the metadata table functions: parse parameters and
+/// declare [`ZDS_COLLECTIONS_COLUMNS`] or [`ZDS_STATS_COLUMNS`].
+///
+/// # Expected SQL
+/// ```sql
+/// SELECT * FROM zds_collections('path/to/store')
+/// SELECT data_bytes, dead_bytes FROM zds_stats('path/to/store', 'train')
+/// ```
+#[no_mangle]
+pub extern "C" fn zds_metadata_bind(_info: *mut c_void) {
+    // TODO: Implement bind logic
+    // 1. Parse path (and collection for zds_stats)
+    // 2. Add the output columns from the matching *_COLUMNS table
+}
+
+/// Main function for the metadata table functions: produce all rows in a
+/// single chunk.
+#[no_mangle]
+pub extern "C" fn zds_metadata_main(_info: *mut c_void, _output: *mut c_void) {
+    // TODO: Implement main logic
+    // 1. zds_collections(path) or vec![zds_stats(path, collection)]
+    // 2. Convert to DuckDB vectors (NULL for null values)
+    // 3. Set cardinality, then 0 on the next call
+}
+
+// ============================================================================
+// COPY ... TO (FORMAT zds) Implementation (stubs)
+// ============================================================================
+
+/// Default collection used when `COLLECTION` is not given to COPY.
+pub const DEFAULT_COPY_COLLECTION: &str = "default";
+
+/// Batch size used for the destination FastStore.
+const COPY_BATCH_SIZE: usize = 10_000;
+
+/// Global state for a `COPY ... TO (FORMAT zds)` statement.
+///
+/// Shared by all sink threads; writes are serialized on the store.
+pub struct WriteZdsGlobalState {
+    store: Mutex<FastStore>,
+    id_column: Option<String>,
+    next_row: AtomicU64,
+}
+
+impl WriteZdsGlobalState {
+    /// Open (or create) the destination collection.
+    ///
+    /// If `id_column` is given, its value becomes the document `_id`;
+    /// otherwise rows are numbered in sink order.
+    pub fn open(
+        path: impl AsRef<Path>,
+        collection: Option<&str>,
+        id_column: Option<&str>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        Layout::init_root(path)?;
+        let collection = collection.unwrap_or(DEFAULT_COPY_COLLECTION);
+        let store = FastStore::open(path, collection, COPY_BATCH_SIZE)?;
+        let next_row = AtomicU64::new(store.len() as u64);
+        Ok(WriteZdsGlobalState {
+            store: Mutex::new(store),
+            id_column: id_column.map(|s| s.to_string()),
+            next_row,
+        })
+    }
+
+    /// Append one chunk of rows. Returns the number of rows written.
+    pub fn sink(&self, columns: &[String], rows: &[Vec<Value>]) -> Result<usize> {
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let id_idx = match &self.id_column {
+            Some(name) => Some(
+                columns
+                    .iter()
+                    .position(|c| c == name)
+                    .ok_or_else(|| Error::Validation(format!("ID column not found: {}", name)))?,
+            ),
+            None => None,
+        };
+
+        let first_row = self
+            .next_row
+            .fetch_add(rows.len() as u64, Ordering::Relaxed);
+        let mut blob = Vec::with_capacity(rows.len() * 64);
+        let mut doc_ids = Vec::with_capacity(rows.len());
+
+        for (i, row) in rows.iter().enumerate() {
+            let doc_id = match id_idx {
+                Some(idx) => match row.get(idx) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Number(n)) => n.to_string(),
+                    other => {
+                        return Err(Error::InvalidDocId(format!(
+                            "unsupported ID value: {:?}",
+                            other
+                        )))
+                    }
+                },
+                None => format!("row_{:012}", first_row + i as u64),
+            };
+            Layout::validate_doc_id(&doc_id)?;
+
+            let mut doc = Map::with_capacity(columns.len() + 1);
+            doc.insert("_id".to_string(), Value::String(doc_id.clone()));
+            for (name, value) in columns.iter().zip(row) {
+                doc.insert(name.clone(), value.clone());
+            }
+
+            serde_json::to_writer(&mut blob, &Value::Object(doc))?;
+            blob.push(b'\n');
+            doc_ids.push(doc_id);
+        }
+
+        let mut store = self
+            .store
+            .lock()
+            .map_err(|e| Error::TransactionFailed(format!("Lock error: {}", e)))?;
+        store.write_jsonl_blob(&blob, &doc_ids)
+    }
+
+    /// Flush data and index. Returns the collection's document count.
+    pub fn finalize(&self) -> Result<usize> {
+        let mut store = self
+            .store
+            .lock()
+            .map_err(|e| Error::TransactionFailed(format!("Lock error: {}", e)))?;
+        store.flush()?;
+        Ok(store.len())
+    }
+}
+
+/// Copy bind function: parse `COLLECTION` and `ID` options.
+///
+/// # Expected SQL
+/// ```sql
+/// COPY (SELECT * FROM tbl) TO 'path/to/store' (FORMAT zds, COLLECTION 'train')
+/// COPY tbl TO 'path/to/store' (FORMAT zds, COLLECTION 'train', ID 'doc_id')
+/// ```
+#[no_mangle]
+pub extern "C" fn write_zds_bind(_info: *mut c_void) {
+    // TODO: Implement bind logic
+    // 1. Parse file path and COLLECTION / ID options
+    // 2. Capture column names from the bound SELECT
+}
+
+/// Copy global init: open the destination store.
+#[no_mangle]
+pub extern "C" fn write_zds_initialize_global(_info: *mut c_void) {
+    // TODO: WriteZdsGlobalState::open(path, collection, id_column)
+}
+
+/// Copy sink: append one DataChunk.
+#[no_mangle]
+pub extern "C" fn write_zds_sink(_info: *mut c_void, _input: *mut c_void) {
+    // TODO: Implement sink logic
+    // 1. Convert DuckDB vectors to rows of serde_json::Value
+    // 2. global_state.sink(&columns, &rows)
+}
+
+/// Copy finalize: flush pending writes and index.
+#[no_mangle]
+pub extern "C" fn write_zds_finalize(_info: *mut c_void) {
+    // TODO: global_state.finalize()
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
-/// Convert JSON value to appropriate DuckDB type.
-fn json_to_duckdb_type(value: &serde_json::Value) -> &'static str {
-    match value {
-        serde_json::Value::Null => "VARCHAR",
-        serde_json::Value::Bool(_) => "BOOLEAN",
-        serde_json::Value::Number(n) => {
-            if n.is_i64() {
-                "BIGINT"
-            } else {
-                "DOUBLE"
+/// Maximum nesting of STRUCT and LIST column types; values nested deeper
+/// are exposed as JSON.
+pub const MAX_NESTED_DEPTH: usize = 3;
+
+/// DuckDB logical type of a column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogicalType {
+    Boolean,
+    BigInt,
+    Double,
+    Varchar,
+    Json,
+    List(Box<LogicalType>),
+    Struct(Vec<(String, LogicalType)>),
+}
+
+impl LogicalType {
+    /// Logical type of an inferred type, at nesting `depth` (0 for a
+    /// top-level column).
+    fn of_field_type(field_type: &FieldType, depth: usize) -> Self {
+        match field_type {
+            FieldType::Null | FieldType::Str => LogicalType::Varchar,
+            FieldType::Bool => LogicalType::Boolean,
+            FieldType::Int => LogicalType::BigInt,
+            FieldType::Float => LogicalType::Double,
+            FieldType::Mixed => LogicalType::Json,
+            // Lists whose elements were never seen have no element type
+            FieldType::List(item) if depth < MAX_NESTED_DEPTH && **item != FieldType::Null => {
+                LogicalType::List(Box::new(Self::of_field_type(item, depth + 1)))
+            }
+            FieldType::Struct(fields) if depth < MAX_NESTED_DEPTH && !fields.is_empty() => {
+                LogicalType::Struct(
+                    fields
+                        .iter()
+                        .map(|field| {
+                            (
+                                field.name.clone(),
+                                Self::of_field_type(&field.field_type, depth + 1),
+                            )
+                        })
+                        .collect(),
+                )
             }
+            FieldType::List(_) | FieldType::Struct(_) => LogicalType::Json,
         }
-        serde_json::Value::String(_) => "VARCHAR",
-        serde_json::Value::Array(_) => "JSON",
-        serde_json::Value::Object(_) => "JSON",
-    }
-}
-
-/// Infer DuckDB schema from ZDS schema.
-pub fn infer_schema(engine: &Engine) -> Vec<(&'static str, &'static str)> {
-    // Get first document to infer types
-    if let Ok(doc) = engine.get_document_at(0) {
-        if let Some(obj) = doc.as_object() {
-            return obj
-                .iter()
-                .map(|(k, v)| {
-                    let key: &'static str = Box::leak(k.clone().into_boxed_str());
-                    (key, json_to_duckdb_type(v))
-                })
-                .collect();
+    }
+}
+
+/// The SQL spelling of the type, e.g. `STRUCT("a" BIGINT)[]`.
+impl fmt::Display for LogicalType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogicalType::Boolean => f.write_str("BOOLEAN"),
+            LogicalType::BigInt => f.write_str("BIGINT"),
+            LogicalType::Double => f.write_str("DOUBLE"),
+            LogicalType::Varchar => f.write_str("VARCHAR"),
+            LogicalType::Json => f.write_str("JSON"),
+            LogicalType::List(item) => write!(f, "{}[]", item),
+            LogicalType::Struct(fields) => {
+                f.write_str("STRUCT(")?;
+                for (i, (name, field_type)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "\"{}\" {}", name.replace('"', "\"\""), field_type)?;
+                }
+                f.write_str(")")
+            }
         }
     }
-    Vec::new()
+}
+
+/// Output columns of a `read_zds` scan, owned by its bind data.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InferredSchema {
+    /// Column names and types, in name order
+    pub columns: Vec<(String, LogicalType)>,
+}
+
+impl InferredSchema {
+    /// Column names, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+/// DuckDB type of a single JSON value, with nested values as STRUCT and
+/// LIST types.
+pub fn json_to_duckdb_type(value: &Value) -> LogicalType {
+    LogicalType::of_field_type(&FieldType::of_value(value), 0)
+}
+
+/// Infer DuckDB columns from a collection's schemas, in name order.
+///
+/// Uses [`infer::InferredSchema::for_engine`]: the collection's schema
+/// registry, or a sample of [`DEFAULT_SAMPLE_SIZE`] documents when the
+/// registry is empty. Nested objects and arrays with a stable shape become
+/// STRUCT and LIST types up to [`MAX_NESTED_DEPTH`]; conflicting or deeper
+/// values become JSON.
+pub fn infer_schema(engine: &Engine) -> Result<InferredSchema> {
+    let mut schema = infer::InferredSchema::for_engine(engine, DEFAULT_SAMPLE_SIZE)?;
+    schema.sort();
+    Ok(InferredSchema {
+        columns: schema
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.name.clone(),
+                    LogicalType::of_field_type(&field.field_type, 0),
+                )
+            })
+            .collect(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use std::f64::consts::PI;
 
+    use serde_json::json;
+    use tempfile::TempDir;
+    use zippy_data::{writer::SyncWriter, Layout};
+
     use super::*;
 
     #[test]
     fn test_json_type_mapping() {
-        assert_eq!(json_to_duckdb_type(&serde_json::json!(null)), "VARCHAR");
-        assert_eq!(json_to_duckdb_type(&serde_json::json!(true)), "BOOLEAN");
-        assert_eq!(json_to_duckdb_type(&serde_json::json!(42)), "BIGINT");
-        assert_eq!(json_to_duckdb_type(&serde_json::json!(PI)), "DOUBLE");
-        assert_eq!(json_to_duckdb_type(&serde_json::json!("hello")), "VARCHAR");
-        assert_eq!(json_to_duckdb_type(&serde_json::json!([1, 2, 3])), "JSON");
-        assert_eq!(json_to_duckdb_type(&serde_json::json!({"a": 1})), "JSON");
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!(null)).to_string(),
+            "VARCHAR"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!(true)).to_string(),
+            "BOOLEAN"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!(42)).to_string(),
+            "BIGINT"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!(PI)).to_string(),
+            "DOUBLE"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!("hello")).to_string(),
+            "VARCHAR"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!([1, 2, 3])).to_string(),
+            "BIGINT[]"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!({"a": 1})).to_string(),
+            "STRUCT(\"a\" BIGINT)"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!([])).to_string(),
+            "JSON"
+        );
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!({})).to_string(),
+            "JSON"
+        );
+        // Nested deeper than MAX_NESTED_DEPTH falls back to JSON
+        assert_eq!(
+            json_to_duckdb_type(&serde_json::json!({"a": {"b": [{"c": 1}]}})).to_string(),
+            "STRUCT(\"a\" STRUCT(\"b\" JSON[]))"
+        );
+    }
+
+    #[test]
+    fn test_infer_nested_schema() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+
+        // File-per-document collections use the schema registry
+        let mut writer = SyncWriter::new(root, "files").unwrap();
+        writer
+            .put("a", &json!({"meta": {"lang": "en"}, "tags": ["x"], "n": 1}))
+            .unwrap();
+        writer
+            .put(
+                "b",
+                &json!({"meta": {"lang": "fr", "score": 0.5}, "tags": [], "n": 2.5}),
+            )
+            .unwrap();
+        writer
+            .put("c", &json!({"meta": "none", "n": null}))
+            .unwrap();
+        let engine = Engine::open(root, "files").unwrap();
+        let schema = infer_schema(&engine).unwrap();
+        assert_eq!(
+            schema.columns,
+            [
+                ("meta".to_string(), LogicalType::Json),
+                ("n".to_string(), LogicalType::Double),
+                (
+                    "tags".to_string(),
+                    LogicalType::List(Box::new(LogicalType::Varchar))
+                ),
+            ]
+        );
+        assert_eq!(schema.names().collect::<Vec<_>>(), ["meta", "n", "tags"]);
+
+        // Fast store collections are sampled
+        let mut store = FastStore::open(root, "fast", 100).unwrap();
+        store
+            .put("x", json!({"meta": {"lang": "en"}, "points": [[1, 2]]}))
+            .unwrap();
+        store
+            .put("y", json!({"meta": {"score": 1}, "points": []}))
+            .unwrap();
+        drop(store);
+        let engine = Engine::open(root, "fast").unwrap();
+        let schema = infer_schema(&engine).unwrap();
+        let types: Vec<String> = schema.columns.iter().map(|(_, t)| t.to_string()).collect();
+        assert_eq!(
+            types,
+            ["STRUCT(\"lang\" VARCHAR, \"score\" BIGINT)", "BIGINT[][]"]
+        );
+    }
+
+    #[test]
+    fn test_partitioned_scan_covers_all_docs() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "test").unwrap();
+        for i in 0..10 {
+            writer.put(&format!("doc{}", i), &json!({"i": i})).unwrap();
+        }
+
+        let engine = Engine::open(root, "test").unwrap();
+        let global = ReadZdsGlobalState::new(engine, 4);
+        assert_eq!(global.max_threads(), 4);
+
+        let mut locals = [ReadZdsLocalState::new(), ReadZdsLocalState::new()];
+        let mut seen = Vec::new();
+        loop {
+            let mut progressed = false;
+            for local in locals.iter_mut() {
+                let batch = local.next_batch(&global, 3).unwrap();
+                progressed |= !batch.is_empty();
+                seen.extend(batch.into_iter().map(|d| d["i"].as_i64().unwrap()));
+            }
+            if !progressed {
+                break;
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, (0..10).collect::<Vec<_>>());
+        assert!(locals.iter().all(|l| l.done));
+    }
+
+    #[test]
+    fn test_metadata_functions() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        Layout::init_root(root).unwrap();
+        let mut writer = SyncWriter::new(root, "files").unwrap();
+        writer.put("a", &json!({"i": 1})).unwrap();
+        let mut store = FastStore::open(root, "fast", 100).unwrap();
+        store.put("x", json!({"i": 1})).unwrap();
+        store.put("y", json!({"i": 2})).unwrap();
+        drop(store);
+
+        assert_eq!(
+            zds_collections(root).unwrap(),
+            [
+                vec![json!("fast"), json!("jsonl"), json!(2)],
+                vec![json!("files"), json!("files"), json!(1)],
+            ]
+        );
+
+        let row = zds_stats(root, "fast").unwrap();
+        assert_eq!(row.len(), ZDS_STATS_COLUMNS.len());
+        assert_eq!(row[2], json!(2));
+        assert!(row[6].as_u64().unwrap() > 0);
+        assert_eq!(row[13], json!("none"));
+
+        let row = zds_stats(root, "files").unwrap();
+        assert_eq!(row[1], json!("files"));
+        assert!(row[6..].iter().all(Value::is_null));
+        assert!(zds_stats(root, "missing").is_err());
+    }
+
+    #[test]
+    fn test_copy_to_zds() {
+        let tmp = TempDir::new().unwrap();
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let rows = vec![
+            vec![json!("a1"), json!("alice")],
+            vec![json!(2), json!("bob")],
+        ];
+
+        let state = WriteZdsGlobalState::open(tmp.path(), Some("people"), Some("id")).unwrap();
+        assert_eq!(state.sink(&columns, &rows).unwrap(), 2);
+        assert_eq!(state.finalize().unwrap(), 2);
+        drop(state);
+
+        let store = FastStore::open(tmp.path(), "people", 100).unwrap();
+        assert_eq!(store.get("a1").unwrap()["name"], "alice");
+        assert_eq!(store.get("2").unwrap()["name"], "bob");
+    }
+
+    #[test]
+    fn test_copy_to_zds_generated_ids() {
+        let tmp = TempDir::new().unwrap();
+        let columns = vec!["v".to_string()];
+        let rows = vec![vec![json!(1)], vec![json!(2)]];
+
+        let state = WriteZdsGlobalState::open(tmp.path(), None, None).unwrap();
+        state.sink(&columns, &rows).unwrap();
+        state.finalize().unwrap();
+        drop(state);
+
+        let store = FastStore::open(tmp.path(), DEFAULT_COPY_COLLECTION, 100).unwrap();
+        assert_eq!(store.get("row_000000000001").unwrap()["v"], 2);
+
+        let state = WriteZdsGlobalState::open(tmp.path(), None, Some("missing")).unwrap();
+        assert!(state.sink(&columns, &rows).is_err());
     }
 }
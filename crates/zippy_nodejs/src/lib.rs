@@ -1,15 +1,22 @@
 //! Node.js bindings for ZDS using napi-rs.
 
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
-use zippy_data::{FastStore, OpenMode, ZDSRoot as RustZDSRoot};
+use zippy_data::{
+    container, AsyncWriter, AsyncWriterConfig, Codec, CompactionPolicy, ConflictPolicy, Durability,
+    FastScanner, FastStore, IdStrategy, OpenMode, Predicate, SharedStore, StoreReadGuard,
+    StoreWriteGuard, ZDSRoot as RustZDSRoot,
+};
 
 /// High-performance ZDS Store backed by Rust FastStore (JSONL-based).
+///
+/// Reads share the store's lock, so `*Async` reads on the thread pool run
+/// in parallel with each other and wait only for a write in progress.
 #[napi]
 pub struct ZDSStore {
-    store: Mutex<FastStore>,
+    store: SharedStore,
     root: String,
     collection: String,
 }
@@ -26,55 +33,178 @@ impl ZDSStore {
             .map_err(|e| Error::from_reason(format!("Failed to open store: {}", e)))?;
 
         Ok(ZDSStore {
-            store: Mutex::new(store),
+            store: SharedStore::new(store),
             root,
             collection,
         })
     }
 
+    /// Create an empty store kept in memory and deleted when closed, for
+    /// tests and caches.
+    #[napi(factory)]
+    pub fn in_memory(collection: Option<String>, batch_size: Option<u32>) -> Result<Self> {
+        let collection = collection.unwrap_or_else(|| "default".to_string());
+        let batch_size = batch_size.unwrap_or(5000) as usize;
+
+        let store = FastStore::open_in_memory(&collection, batch_size)
+            .map_err(|e| Error::from_reason(format!("Failed to open store: {}", e)))?;
+        Ok(Self::in_memory_store(store, collection))
+    }
+
+    /// Copy a collection into memory, pinning a hot dataset in RAM. Writes
+    /// go to the copy only.
+    #[napi(factory)]
+    pub fn load_in_memory(
+        root: String,
+        collection: Option<String>,
+        batch_size: Option<u32>,
+    ) -> Result<Self> {
+        let collection = collection.unwrap_or_else(|| "default".to_string());
+        let batch_size = batch_size.unwrap_or(5000) as usize;
+
+        let store = FastStore::load_in_memory(&root, &collection, batch_size)
+            .map_err(|e| Error::from_reason(format!("Failed to open store: {}", e)))?;
+        Ok(Self::in_memory_store(store, collection))
+    }
+
+    /// Whether the store is kept in memory.
+    #[napi(getter)]
+    pub fn is_in_memory(&self) -> Result<bool> {
+        Ok(self.read()?.is_in_memory())
+    }
+
     /// Get document by ID.
     #[napi]
     pub fn get(&self, doc_id: String) -> Result<serde_json::Value> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
+        let store = self.read()?;
+        get_doc(&store, &doc_id)
+    }
+
+    /// Get document by ID without blocking the event loop.
+    #[napi(ts_return_type = "Promise<any>")]
+    pub fn get_async(&self, doc_id: String) -> AsyncTask<StoreTask<serde_json::Value>> {
+        self.task(move |store| get_doc(store, &doc_id))
+    }
+
+    /// Get several documents at once, in the order of `doc_ids`, with `null`
+    /// for missing ones. The lines are read in one pass and parsed in
+    /// parallel.
+    #[napi]
+    pub fn get_many(&self, doc_ids: Vec<String>) -> Result<Vec<Option<serde_json::Value>>> {
+        let store = self.read()?;
         store
-            .get(&doc_id)
-            .map_err(|_| Error::from_reason(format!("Document not found: {}", doc_id)))
+            .get_many(&doc_ids)
+            .map_err(|e| Error::from_reason(format!("Read failed: {}", e)))
     }
 
     /// Put a document.
     #[napi]
     pub fn put(&self, doc_id: String, doc: serde_json::Value) -> Result<()> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
+        let mut store = self.write()?;
         store
             .put(doc_id, doc)
             .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))
     }
 
+    /// Put a document under a generated ID and return the ID. `strategy`
+    /// is `ulid` (the default, sortable by creation time), `uuid` or `seq`
+    /// (a per-collection counter).
+    #[napi]
+    pub fn put_auto(&self, doc: serde_json::Value, strategy: Option<String>) -> Result<String> {
+        let strategy = match strategy {
+            Some(strategy) => strategy
+                .parse()
+                .map_err(|e| Error::from_reason(format!("{}", e)))?,
+            None => IdStrategy::default(),
+        };
+        let mut store = self.write()?;
+        store
+            .put_auto(doc, strategy)
+            .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))
+    }
+
+    /// Put a batch of documents, resolving existing IDs with `policy`
+    /// (`overwrite`, `skip_existing` or `fail_on_conflict`). Returns the
+    /// number of documents written.
+    #[napi]
+    pub fn put_batch_with_policy(
+        &self,
+        items: Vec<BatchItem>,
+        policy: Option<String>,
+    ) -> Result<u32> {
+        let policy = match policy {
+            Some(policy) => policy
+                .parse()
+                .map_err(|e| Error::from_reason(format!("{}", e)))?,
+            None => ConflictPolicy::default(),
+        };
+        let batch = items.into_iter().map(|item| (item.id, item.doc)).collect();
+        let mut store = self.write()?;
+        store
+            .put_batch_with_policy(batch, policy)
+            .map(|written| written as u32)
+            .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))
+    }
+
+    /// Apply a JSON merge patch (RFC 7386) to a document and return the
+    /// patched document. `null` values remove fields.
+    #[napi]
+    pub fn patch(&self, doc_id: String, patch: serde_json::Value) -> Result<serde_json::Value> {
+        let mut store = self.write()?;
+        store
+            .patch(&doc_id, &patch)
+            .map_err(|e| Error::from_reason(format!("Patch failed: {}", e)))
+    }
+
     /// Delete a document.
     #[napi]
     pub fn delete(&self, doc_id: String) -> Result<()> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
+        let mut store = self.write()?;
         store
             .delete(&doc_id)
             .map_err(|e| Error::from_reason(format!("Delete failed: {}", e)))
     }
 
+    /// Attach binary data (an image, audio clip, ...) to a document under
+    /// `name`, replacing any blob of that name.
+    #[napi]
+    pub fn put_blob(&self, doc_id: String, name: String, data: Buffer) -> Result<()> {
+        let mut store = self.write()?;
+        store
+            .put_blob(&doc_id, &name, &data)
+            .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))
+    }
+
+    /// Read a document's blob.
+    #[napi]
+    pub fn get_blob(&self, doc_id: String, name: String) -> Result<Buffer> {
+        let store = self.read()?;
+        store
+            .get_blob(&doc_id, &name)
+            .map(Buffer::from)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    /// Names of a document's blobs, sorted.
+    #[napi]
+    pub fn blob_names(&self, doc_id: String) -> Result<Vec<String>> {
+        let store = self.read()?;
+        Ok(store.blob_names(&doc_id))
+    }
+
+    /// Remove a document's blob.
+    #[napi]
+    pub fn delete_blob(&self, doc_id: String, name: String) -> Result<()> {
+        let mut store = self.write()?;
+        store
+            .delete_blob(&doc_id, &name)
+            .map_err(|e| Error::from_reason(format!("Delete failed: {}", e)))
+    }
+
     /// Flush pending writes and refresh mmap.
     #[napi]
     pub fn flush(&self) -> Result<()> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
+        let mut store = self.write()?;
         store
             .flush()
             .map_err(|e| Error::from_reason(format!("Flush failed: {}", e)))?;
@@ -83,6 +213,121 @@ impl ZDSStore {
             .map_err(|e| Error::from_reason(format!("Mmap refresh failed: {}", e)))
     }
 
+    /// Rewrite the data file without deleted and overwritten documents.
+    #[napi]
+    pub fn compact(&self) -> Result<()> {
+        let mut store = self.write()?;
+        compact_store(&mut store)
+    }
+
+    /// Compact on the thread pool, without blocking the event loop.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn compact_async(&self) -> AsyncTask<StoreTask<()>> {
+        self.task_mut(compact_store)
+    }
+
+    /// Compact automatically on flush/close once `minDeadRatio` of the data
+    /// file is garbage. Call without arguments to disable.
+    #[napi]
+    pub fn set_auto_compact(&self, min_dead_ratio: Option<f64>) -> Result<()> {
+        let mut store = self.write()?;
+        store.set_compaction_policy(min_dead_ratio.map(CompactionPolicy::with_ratio));
+        Ok(())
+    }
+
+    /// When writes are synced: "none", "flush", "fsync_batch" (once per
+    /// flush, the default) or "fsync_op" (after every write).
+    #[napi(getter)]
+    pub fn durability(&self) -> Result<String> {
+        let store = self.read()?;
+        Ok(store.durability().to_string())
+    }
+
+    /// Set when writes are synced (see `durability`).
+    #[napi]
+    pub fn set_durability(&self, durability: String) -> Result<()> {
+        let durability: Durability = durability
+            .parse()
+            .map_err(|e| Error::from_reason(format!("{}", e)))?;
+        let mut store = self.write()?;
+        store.set_durability(durability);
+        Ok(())
+    }
+
+    /// Garbage statistics for the data file.
+    #[napi(getter)]
+    pub fn compaction_stats(&self) -> Result<CompactionStats> {
+        let store = self.read()?;
+        Ok(CompactionStats {
+            dead_bytes: store.dead_bytes() as f64,
+            dead_ratio: store.dead_ratio(),
+            tombstones: store.tombstone_count() as u32,
+        })
+    }
+
+    /// Storage statistics: data, live, dead and index sizes.
+    #[napi(getter)]
+    pub fn storage_stats(&self) -> Result<StorageStats> {
+        let store = self.read()?;
+        let stats = store.stats();
+        Ok(StorageStats {
+            count: stats.doc_count as u32,
+            data_bytes: stats.data_bytes as f64,
+            live_bytes: stats.live_bytes as f64,
+            dead_bytes: stats.dead_bytes as f64,
+            dead_ratio: stats.dead_ratio,
+            index_bytes: stats.index_bytes as f64,
+            index_memory_bytes: stats.index_memory_bytes as f64,
+            min_offset: stats.min_offset.map(|o| o as f64),
+            max_offset: stats.max_offset.map(|o| o as f64),
+            last_flush: stats.last_flush.map(|t| t as f64),
+            segments: stats.segments as u32,
+            tombstones: stats.tombstones as u32,
+            compression: stats.compression.to_string(),
+        })
+    }
+
+    /// IDs deleted since the last compaction, sorted.
+    #[napi]
+    pub fn deleted_ids(&self) -> Result<Vec<String>> {
+        let store = self.read()?;
+        Ok(store.deleted_ids())
+    }
+
+    /// Data file compression ("none" or "zstd").
+    #[napi(getter)]
+    pub fn compression(&self) -> Result<String> {
+        let store = self.read()?;
+        Ok(store.compression().to_string())
+    }
+
+    /// Set the data file compression; applies on the next compact() unless
+    /// the store is empty.
+    #[napi]
+    pub fn set_compression(&self, compression: String) -> Result<()> {
+        let compression = compression
+            .parse()
+            .map_err(|e| Error::from_reason(format!("{}", e)))?;
+        let mut store = self.write()?;
+        store
+            .set_compression(compression)
+            .map_err(|e| Error::from_reason(format!("Set compression failed: {}", e)))
+    }
+
+    /// Check the data file against its checksums.
+    #[napi]
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let mut store = self.write()?;
+        verify_store(&mut store)
+    }
+
+    /// Check the data file on the thread pool, without blocking the event
+    /// loop.
+    #[napi(ts_return_type = "Promise<VerifyReport>")]
+    pub fn verify_async(&self) -> AsyncTask<StoreTask<VerifyReport>> {
+        self.task_mut(verify_store)
+    }
+
     /// Close the store and flush pending writes.
     #[napi]
     pub fn close(&self) -> Result<()> {
@@ -94,83 +339,212 @@ impl ZDSStore {
     /// doc_ids: List of document IDs in order matching the lines.
     #[napi]
     pub fn write_jsonl(&self, jsonl_data: Buffer, doc_ids: Vec<String>) -> Result<u32> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
-        let count = store
-            .write_jsonl_blob(&jsonl_data, &doc_ids)
-            .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))?;
-        Ok(count as u32)
+        let mut store = self.write()?;
+        write_jsonl_blob(&mut store, &jsonl_data, &doc_ids)
+    }
+
+    /// Write a complete JSONL blob on the thread pool, without blocking the
+    /// event loop.
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn write_jsonl_async(
+        &self,
+        jsonl_data: Buffer,
+        doc_ids: Vec<String>,
+    ) -> AsyncTask<StoreTask<u32>> {
+        self.task_mut(move |store| write_jsonl_blob(store, &jsonl_data, &doc_ids))
     }
 
     /// Scan and return raw JSON bytes (fastest read path).
     #[napi]
     pub fn scan_raw(&self) -> Result<Vec<Buffer>> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
-        let raw = store
-            .scan_raw()
-            .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))?;
-        Ok(raw.into_iter().map(Buffer::from).collect())
+        let store = self.read()?;
+        scan_raw_buffers(&store)
+    }
+
+    /// Scan raw JSON bytes on the thread pool, without blocking the event
+    /// loop.
+    #[napi(ts_return_type = "Promise<Array<Buffer>>")]
+    pub fn scan_raw_async(&self) -> AsyncTask<StoreTask<Vec<Buffer>>> {
+        self.task(scan_raw_buffers)
     }
 
     /// Read entire JSONL file as a single buffer (fastest bulk read).
     /// Returns the raw JSONL content - caller splits and parses.
     #[napi]
     pub fn read_jsonl_blob(&self) -> Result<Buffer> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
-
-        // Get mmap data directly via the public method
-        if let Some(data) = store.get_raw_data() {
-            Ok(Buffer::from(data.to_vec()))
-        } else {
-            Ok(Buffer::from(Vec::new()))
-        }
+        let store = self.read()?;
+        Ok(jsonl_blob(&store))
+    }
+
+    /// Read entire JSONL file as a single buffer on the thread pool, without
+    /// blocking the event loop.
+    #[napi(ts_return_type = "Promise<Buffer>")]
+    pub fn read_jsonl_blob_async(&self) -> AsyncTask<StoreTask<Buffer>> {
+        self.task(|store| Ok(jsonl_blob(store)))
     }
 
     /// Get document count.
     #[napi(getter)]
     pub fn count(&self) -> u32 {
-        self.store.lock().map(|s| s.len() as u32).unwrap_or(0)
+        self.store.len() as u32
     }
 
     /// Check if document exists.
     #[napi]
     pub fn exists(&self, doc_id: String) -> bool {
-        self.store
-            .lock()
-            .map(|s| s.exists(&doc_id))
-            .unwrap_or(false)
+        self.store.exists(&doc_id)
+    }
+
+    /// Scan all documents, optionally keeping only those matching `filter`
+    /// and projecting them onto `fields`.
+    ///
+    /// `filter` uses the same descriptor as Python, e.g.
+    /// `{ category: "A", age: { $gte: 18 } }`, or an expression string like
+    /// `"category = 'A' AND age >= 18"`, and is evaluated in Rust together
+    /// with the projection, inside the parallel scan.
+    #[napi]
+    pub fn scan(
+        &self,
+        filter: Option<serde_json::Value>,
+        fields: Option<Vec<String>>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let predicate = parse_filter(filter)?;
+        let store = self.read()?;
+        scan_docs(&store, predicate.as_ref(), fields.as_deref())
     }
 
-    /// Scan all documents.
+    /// Like `scan`, but runs on the thread pool so scanning a large
+    /// collection doesn't block the event loop. Invalid filters reject the
+    /// promise.
+    #[napi(ts_return_type = "Promise<Array<any>>")]
+    pub fn scan_async(
+        &self,
+        filter: Option<serde_json::Value>,
+        fields: Option<Vec<String>>,
+    ) -> AsyncTask<StoreTask<Vec<serde_json::Value>>> {
+        self.task(move |store| {
+            let predicate = parse_filter(filter)?;
+            scan_docs(store, predicate.as_ref(), fields.as_deref())
+        })
+    }
+
+    /// Lazily iterate over all documents in file order.
+    ///
+    /// Call `nextBatch()` on the returned scanner until it returns an empty
+    /// array; only one batch is decoded at a time.
+    #[napi]
+    pub fn scan_iter(&self) -> Result<ZDSScanner> {
+        let store = self.read()?;
+        let scanner = store
+            .iter()
+            .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))?;
+        Ok(ZDSScanner { scanner })
+    }
+
+    /// Start a background writer for this store.
+    ///
+    /// Its puts and deletes are queued and applied on a thread of their
+    /// own, which also flushes every `flushIntervalMs`, so the event loop
+    /// never waits for a flush. Once `queueSize` writes are queued, further
+    /// ones block until the thread catches up.
     #[napi]
-    pub fn scan(&self) -> Result<Vec<serde_json::Value>> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?;
+    pub fn async_writer(&self, options: Option<AsyncWriterOptions>) -> Result<ZDSAsyncWriter> {
+        let options = options.unwrap_or_default();
+        let defaults = AsyncWriterConfig::default();
+        let config = AsyncWriterConfig {
+            queue_size: options
+                .queue_size
+                .map_or(defaults.queue_size, |n| n as usize),
+            max_batch: options.max_batch.map_or(defaults.max_batch, |n| n as usize),
+            flush_interval_ms: options
+                .flush_interval_ms
+                .map_or(defaults.flush_interval_ms, u64::from),
+        };
+        let writer = AsyncWriter::new(self.store.clone(), config).map_err(async_write_error)?;
+        Ok(ZDSAsyncWriter {
+            writer: Arc::new(writer),
+        })
+    }
+
+    /// Stream documents as an async iterable, pulling `batchSize` documents
+    /// at a time from Rust on the thread pool.
+    ///
+    /// Only one batch is held in memory, so large collections can be piped
+    /// with `for await` or `Readable.from(store.stream())` without
+    /// materializing them. `fields` projects and `filter` (see `scan`)
+    /// selects documents in Rust. The scan sees the data as of this call;
+    /// documents deleted later are skipped.
+    #[napi(ts_return_type = "ZdsStream")]
+    pub fn stream(&self, env: Env, options: Option<StreamOptions>) -> Result<Object> {
+        let options = options.unwrap_or_default();
+        let batch_size = options.batch_size.unwrap_or(1000);
+        if batch_size == 0 {
+            return Err(Error::from_reason("batchSize must be positive"));
+        }
+        let predicate = parse_filter(options.filter)?;
+        let scanner = {
+            let store = self.read()?;
+            store
+                .iter()
+                .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))?
+        };
+        let stream = ZDSStream {
+            store: self.store.clone(),
+            state: Arc::new(Mutex::new(StreamState {
+                scanner,
+                batch_size: batch_size as usize,
+                fields: options.fields,
+                predicate,
+            })),
+        }
+        .into_instance(env)?
+        .as_object(env);
+
+        // Make the stream async iterable, yielding documents one by one
+        let make_iterable: JsFunction = env.run_script(
+            "(function (stream) {
+                Object.getPrototypeOf(stream)[Symbol.asyncIterator] ??= async function* () {
+                    for (let batch; (batch = await this.nextBatch()).length > 0; ) {
+                        yield* batch;
+                    }
+                };
+            })",
+        )?;
+        make_iterable.call(None, &[&stream])?;
+        Ok(stream)
+    }
+
+    /// Read `limit` documents starting at position `offset`, in the order
+    /// they were last written (stable across calls and reopens).
+    #[napi]
+    pub fn scan_range(&self, offset: u32, limit: u32) -> Result<Vec<serde_json::Value>> {
+        let store = self.read()?;
         store
-            .scan()
+            .scan_range(offset as usize, limit as usize)
             .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))
     }
 
-    /// List all document IDs.
+    /// Read a reproducible random sample of `n` documents (all of them if
+    /// the store holds fewer), in file order. The same `seed` gives the same
+    /// sample while the store is unchanged.
+    #[napi]
+    pub fn sample(&self, n: u32, seed: Option<u32>) -> Result<Vec<serde_json::Value>> {
+        let store = self.read()?;
+        store
+            .sample(n as usize, seed.unwrap_or(0) as u64)
+            .map_err(|e| Error::from_reason(format!("Sample failed: {}", e)))
+    }
+
+    /// List all document IDs in file order.
     #[napi]
     pub fn list_doc_ids(&self) -> Vec<String> {
-        self.store.lock().map(|s| s.doc_ids()).unwrap_or_default()
+        self.read().map(|s| s.doc_ids()).unwrap_or_default()
     }
 
     /// Get store info.
     #[napi(getter)]
     pub fn info(&self) -> StoreInfo {
-        let count = self.store.lock().map(|s| s.len() as u32).unwrap_or(0);
+        let count = self.store.len() as u32;
         StoreInfo {
             root: self.root.clone(),
             collection: self.collection.clone(),
@@ -179,6 +553,441 @@ impl ZDSStore {
     }
 }
 
+impl ZDSStore {
+    /// Wrap a store kept in memory, rooted at its temporary directory.
+    fn in_memory_store(store: FastStore, collection: String) -> Self {
+        ZDSStore {
+            root: store.root_path().to_string_lossy().to_string(),
+            store: SharedStore::new(store),
+            collection,
+        }
+    }
+
+    fn read(&self) -> Result<StoreReadGuard<'_>> {
+        read_store(&self.store)
+    }
+
+    fn write(&self) -> Result<StoreWriteGuard<'_>> {
+        write_store(&self.store)
+    }
+
+    /// Run the read-only `op` against the store on the libuv thread pool.
+    fn task<T, F>(&self, op: F) -> AsyncTask<StoreTask<T>>
+    where
+        T: ToNapiValue + Send + 'static,
+        F: FnOnce(&FastStore) -> Result<T> + Send + 'static,
+    {
+        AsyncTask::new(StoreTask {
+            store: self.store.clone(),
+            op: Some(StoreOp::Read(Box::new(op))),
+        })
+    }
+
+    /// Run `op`, which modifies the store, on the libuv thread pool.
+    fn task_mut<T, F>(&self, op: F) -> AsyncTask<StoreTask<T>>
+    where
+        T: ToNapiValue + Send + 'static,
+        F: FnOnce(&mut FastStore) -> Result<T> + Send + 'static,
+    {
+        AsyncTask::new(StoreTask {
+            store: self.store.clone(),
+            op: Some(StoreOp::Write(Box::new(op))),
+        })
+    }
+}
+
+fn read_store(store: &SharedStore) -> Result<StoreReadGuard<'_>> {
+    store
+        .read()
+        .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))
+}
+
+fn write_store(store: &SharedStore) -> Result<StoreWriteGuard<'_>> {
+    store
+        .write()
+        .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))
+}
+
+type ReadOp<T> = Box<dyn FnOnce(&FastStore) -> Result<T> + Send>;
+type WriteOp<T> = Box<dyn FnOnce(&mut FastStore) -> Result<T> + Send>;
+
+/// An operation of a [`StoreTask`], run under a read or a write lock.
+enum StoreOp<T> {
+    Read(ReadOp<T>),
+    Write(WriteOp<T>),
+}
+
+/// A store operation run off the event loop by the `*Async` methods.
+pub struct StoreTask<T> {
+    store: SharedStore,
+    op: Option<StoreOp<T>>,
+}
+
+impl<T: ToNapiValue + Send + 'static> Task for StoreTask<T> {
+    type Output = T;
+    type JsValue = Unknown;
+
+    fn compute(&mut self) -> Result<T> {
+        let op = self
+            .op
+            .take()
+            .ok_or_else(|| Error::from_reason("Task already ran"))?;
+        match op {
+            StoreOp::Read(op) => op(&*read_store(&self.store)?),
+            StoreOp::Write(op) => op(&mut *write_store(&self.store)?),
+        }
+    }
+
+    fn resolve(&mut self, env: Env, output: T) -> Result<Unknown> {
+        // SAFETY: `env` is the live environment of the resolving callback and
+        // the value is created in it
+        unsafe {
+            let value = T::to_napi_value(env.raw(), output)?;
+            Unknown::from_napi_value(env.raw(), value)
+        }
+    }
+}
+
+/// Parse a filter descriptor or expression string.
+fn parse_filter(filter: Option<serde_json::Value>) -> Result<Option<Predicate>> {
+    filter
+        .map(|f| match f {
+            serde_json::Value::String(expr) => Predicate::parse(&expr),
+            other => Predicate::from_json(&other),
+        })
+        .transpose()
+        .map_err(|e| Error::from_reason(format!("Invalid filter: {}", e)))
+}
+
+fn get_doc(store: &FastStore, doc_id: &str) -> Result<serde_json::Value> {
+    store
+        .get(doc_id)
+        .map_err(|_| Error::from_reason(format!("Document not found: {}", doc_id)))
+}
+
+fn scan_docs(
+    store: &FastStore,
+    predicate: Option<&Predicate>,
+    fields: Option<&[String]>,
+) -> Result<Vec<serde_json::Value>> {
+    let fields: Option<Vec<&str>> = fields.map(|f| f.iter().map(String::as_str).collect());
+    store
+        .scan_filtered(predicate, fields.as_deref())
+        .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))
+}
+
+fn scan_raw_buffers(store: &FastStore) -> Result<Vec<Buffer>> {
+    let raw = store
+        .scan_raw()
+        .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))?;
+    Ok(raw.into_iter().map(Buffer::from).collect())
+}
+
+fn write_jsonl_blob(store: &mut FastStore, jsonl_data: &[u8], doc_ids: &[String]) -> Result<u32> {
+    let count = store
+        .write_jsonl_blob(jsonl_data, doc_ids)
+        .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))?;
+    Ok(count as u32)
+}
+
+fn compact_store(store: &mut FastStore) -> Result<()> {
+    store
+        .compact()
+        .map_err(|e| Error::from_reason(format!("Compact failed: {}", e)))
+}
+
+fn verify_store(store: &mut FastStore) -> Result<VerifyReport> {
+    let report = store
+        .verify()
+        .map_err(|e| Error::from_reason(format!("Verify failed: {}", e)))?;
+    Ok(VerifyReport {
+        checked: report.checked as u32,
+        index_ok: report.index_ok,
+        corrupt: report
+            .corrupt
+            .into_iter()
+            .map(|entry| CorruptEntry {
+                id: entry.doc_id,
+                segment: entry.segment,
+                offset: entry.offset as f64,
+                kind: entry.kind.as_str().to_string(),
+            })
+            .collect(),
+    })
+}
+
+fn flush_store(store: &mut FastStore) -> Result<()> {
+    store
+        .flush()
+        .map_err(|e| Error::from_reason(format!("Flush failed: {}", e)))
+}
+
+fn jsonl_blob(store: &FastStore) -> Buffer {
+    // Get mmap data directly via the public method
+    match store.get_raw_data() {
+        Some(data) => Buffer::from(data.to_vec()),
+        None => Buffer::from(Vec::new()),
+    }
+}
+
+/// Lazy document scanner returned by `ZdsStore.scanIter()`.
+#[napi(js_name = "ZdsScanner")]
+pub struct ZDSScanner {
+    scanner: FastScanner,
+}
+
+#[napi]
+impl ZDSScanner {
+    /// Get the next batch of up to `size` documents (default 1000).
+    /// Returns an empty array when the scan is exhausted.
+    #[napi]
+    pub fn next_batch(&mut self, size: Option<u32>) -> Result<Vec<serde_json::Value>> {
+        self.scanner
+            .next_batch(size.unwrap_or(1000).max(1) as usize)
+            .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))
+    }
+
+    /// Upper bound on the number of remaining documents.
+    #[napi(getter)]
+    pub fn remaining(&self) -> u32 {
+        self.scanner.remaining() as u32
+    }
+}
+
+/// Options for `ZdsStore.stream()`.
+#[napi(object)]
+#[derive(Default)]
+pub struct StreamOptions {
+    /// Documents fetched from Rust per batch (default 1000)
+    pub batch_size: Option<u32>,
+    /// Fields to project (dot notation allowed)
+    pub fields: Option<Vec<String>>,
+    /// Filter descriptor or expression, as for `scan()`
+    pub filter: Option<serde_json::Value>,
+}
+
+/// Async iterable document stream returned by `ZdsStore.stream()`.
+#[napi(js_name = "ZdsStream")]
+pub struct ZDSStream {
+    store: SharedStore,
+    state: Arc<Mutex<StreamState>>,
+}
+
+struct StreamState {
+    scanner: FastScanner,
+    batch_size: usize,
+    fields: Option<Vec<String>>,
+    predicate: Option<Predicate>,
+}
+
+impl StreamState {
+    /// Read up to `batch_size` matching documents, none at the end.
+    fn next_batch(&mut self, store: &FastStore) -> Result<Vec<serde_json::Value>> {
+        let fields: Option<Vec<&str>> = self
+            .fields
+            .as_ref()
+            .map(|f| f.iter().map(String::as_str).collect());
+        let mut docs = Vec::with_capacity(self.batch_size);
+        while docs.len() < self.batch_size {
+            let Some((doc_id, doc)) = self
+                .scanner
+                .next_entry()
+                .map_err(|e| Error::from_reason(format!("Scan failed: {}", e)))?
+            else {
+                break;
+            };
+            if !store.exists(&doc_id) {
+                continue;
+            }
+            if let Some(predicate) = &self.predicate {
+                if !Codec::apply_predicate(&doc, predicate).unwrap_or(false) {
+                    continue;
+                }
+            }
+            let doc = match &fields {
+                Some(fields) => Codec::extract_fields(&doc, fields)
+                    .map_err(|e| Error::from_reason(format!("Projection failed: {}", e)))?,
+                None => doc,
+            };
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+}
+
+#[napi]
+impl ZDSStream {
+    /// Fetch the next batch of documents on the thread pool. Resolves to an
+    /// empty array when the stream is exhausted.
+    #[napi(ts_return_type = "Promise<Array<any>>")]
+    pub fn next_batch(&self) -> AsyncTask<StoreTask<Vec<serde_json::Value>>> {
+        let state = Arc::clone(&self.state);
+        AsyncTask::new(StoreTask {
+            store: self.store.clone(),
+            op: Some(StoreOp::Read(Box::new(move |store| {
+                state
+                    .lock()
+                    .map_err(|e| Error::from_reason(format!("Lock error: {}", e)))?
+                    .next_batch(store)
+            }))),
+        })
+    }
+}
+
+/// Options for `ZdsStore.asyncWriter()`.
+#[napi(object)]
+#[derive(Default)]
+pub struct AsyncWriterOptions {
+    /// Writes queued before `put` and `delete` block (default 10000)
+    pub queue_size: Option<u32>,
+    /// Most writes applied under one hold of the store's lock (default 1000)
+    pub max_batch: Option<u32>,
+    /// Flush interval in milliseconds (default 1000)
+    pub flush_interval_ms: Option<u32>,
+}
+
+/// Background writer returned by `ZdsStore.asyncWriter()`.
+///
+/// A write that fails on the writer thread stops it; the error is thrown
+/// by the next call.
+#[napi(js_name = "ZdsAsyncWriter")]
+pub struct ZDSAsyncWriter {
+    writer: Arc<AsyncWriter>,
+}
+
+#[napi]
+impl ZDSAsyncWriter {
+    /// Queue a document put.
+    #[napi]
+    pub fn put(&self, doc_id: String, doc: serde_json::Value) -> Result<()> {
+        self.writer.put(doc_id, doc).map_err(async_write_error)
+    }
+
+    /// Queue a document delete.
+    #[napi]
+    pub fn delete(&self, doc_id: String) -> Result<()> {
+        self.writer.delete(doc_id).map_err(async_write_error)
+    }
+
+    /// Resolve once the writes queued so far are applied and flushed.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn flush(&self) -> AsyncTask<WriterTask> {
+        AsyncTask::new(WriterTask {
+            writer: Arc::clone(&self.writer),
+            close: false,
+        })
+    }
+
+    /// Apply and flush the queued writes and stop the writer thread.
+    /// Closing twice is a no-op.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn close(&self) -> AsyncTask<WriterTask> {
+        AsyncTask::new(WriterTask {
+            writer: Arc::clone(&self.writer),
+            close: true,
+        })
+    }
+
+    /// Number of queued writes not yet applied.
+    #[napi(getter)]
+    pub fn pending(&self) -> u32 {
+        self.writer.pending() as u32
+    }
+
+    /// Whether the writer was closed.
+    #[napi(getter)]
+    pub fn closed(&self) -> bool {
+        self.writer.is_closed()
+    }
+}
+
+/// A flush or close of a [`ZDSAsyncWriter`], waited for off the event loop.
+pub struct WriterTask {
+    writer: Arc<AsyncWriter>,
+    close: bool,
+}
+
+impl Task for WriterTask {
+    type Output = ();
+    type JsValue = ();
+
+    fn compute(&mut self) -> Result<()> {
+        if self.close {
+            self.writer.close()
+        } else {
+            self.writer.flush()
+        }
+        .map_err(async_write_error)
+    }
+
+    fn resolve(&mut self, _env: Env, _output: ()) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn async_write_error(e: zippy_data::Error) -> Error {
+    Error::from_reason(format!("Write failed: {}", e))
+}
+
+/// Data file garbage statistics.
+#[napi(object)]
+pub struct CompactionStats {
+    pub dead_bytes: f64,
+    pub dead_ratio: f64,
+    pub tombstones: u32,
+}
+
+/// Storage statistics from `storageStats`.
+#[napi(object)]
+pub struct StorageStats {
+    pub count: u32,
+    /// Logical (uncompressed) size of the data segments
+    pub data_bytes: f64,
+    pub live_bytes: f64,
+    pub dead_bytes: f64,
+    pub dead_ratio: f64,
+    /// Size of the document index file
+    pub index_bytes: f64,
+    /// Memory held by the in-memory document index
+    pub index_memory_bytes: f64,
+    /// Offset of the first live line (segment-qualified)
+    pub min_offset: Option<f64>,
+    /// Offset of the last live line (segment-qualified)
+    pub max_offset: Option<f64>,
+    /// Time of the last flush in ms since the epoch
+    pub last_flush: Option<f64>,
+    pub segments: u32,
+    pub tombstones: u32,
+    pub compression: String,
+}
+
+/// A document for `putBatchWithPolicy()`.
+#[napi(object)]
+pub struct BatchItem {
+    pub id: String,
+    pub doc: serde_json::Value,
+}
+
+/// A damaged document line found by `verify()`.
+#[napi(object)]
+pub struct CorruptEntry {
+    pub id: String,
+    /// Data segment holding the line (0 for `data.jsonl`)
+    pub segment: u32,
+    /// Offset of the line within its segment
+    pub offset: f64,
+    /// One of `truncated`, `checksum_mismatch`, `invalid_json`, `id_mismatch`
+    pub kind: String,
+}
+
+/// Corruption report from `verify()`.
+#[napi(object)]
+pub struct VerifyReport {
+    pub checked: u32,
+    pub index_ok: bool,
+    pub corrupt: Vec<CorruptEntry>,
+}
+
 /// Store information.
 #[napi(object)]
 pub struct StoreInfo {
@@ -193,10 +1002,98 @@ pub fn version() -> &'static str {
     zippy_data::ZDS_VERSION
 }
 
+/// Options for `pack()`.
+#[napi(object)]
+pub struct PackOptions {
+    /// `deflate` (default) or `stored`
+    pub compression: Option<String>,
+    /// DEFLATE level (0-9)
+    pub level: Option<u32>,
+}
+
+/// Report pack/unpack progress to a JS callback, keeping the first error
+/// it throws (later reports are skipped).
+fn progress_callback<'a>(
+    env: &'a Env,
+    progress: Option<&'a JsFunction>,
+    error: &'a mut Option<Error>,
+) -> impl FnMut(&container::PackProgress) + 'a {
+    move |p| {
+        let Some(callback) = progress.filter(|_| error.is_none()) else {
+            return;
+        };
+        let result = (|| {
+            let mut obj = env.create_object()?;
+            obj.set_named_property("path", env.create_string(&p.path)?)?;
+            obj.set_named_property("filesDone", env.create_uint32(p.files_done as u32)?)?;
+            obj.set_named_property("filesTotal", env.create_uint32(p.files_total as u32)?)?;
+            obj.set_named_property("bytesDone", env.create_double(p.bytes_done as f64)?)?;
+            obj.set_named_property("bytesTotal", env.create_double(p.bytes_total as f64)?)?;
+            callback.call(None, &[obj])
+        })();
+        if let Err(e) = result {
+            *error = Some(e);
+        }
+    }
+}
+
+/// Pack a store folder into a .zds archive. `progress`, if given, is called
+/// with `{path, filesDone, filesTotal, bytesDone, bytesTotal}` as files are
+/// copied.
+#[napi]
+pub fn pack(
+    env: Env,
+    source: String,
+    dest: String,
+    options: Option<PackOptions>,
+    progress: Option<JsFunction>,
+) -> Result<()> {
+    let mut pack_options = container::PackOptions::default();
+    if let Some(options) = options {
+        if let Some(compression) = options.compression {
+            pack_options.compression = compression
+                .parse()
+                .map_err(|e: zippy_data::Error| Error::from_reason(e.to_string()))?;
+        }
+        if let Some(level) = options.level {
+            if level > 9 {
+                return Err(Error::from_reason("level must be between 0 and 9"));
+            }
+            pack_options.level = Some(level as i32);
+        }
+    }
+    let mut error = None;
+    let result = container::pack_with(
+        source.as_ref(),
+        dest.as_ref(),
+        &pack_options,
+        progress_callback(&env, progress.as_ref(), &mut error),
+    );
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result.map_err(|e| Error::from_reason(format!("Pack failed: {}", e)))
+}
+
+/// Unpack a .zds archive into a folder, calling `progress` like `pack()`.
+#[napi]
+pub fn unpack(env: Env, source: String, dest: String, progress: Option<JsFunction>) -> Result<()> {
+    let mut error = None;
+    let result = container::unpack_with(
+        source.as_ref(),
+        dest.as_ref(),
+        progress_callback(&env, progress.as_ref(), &mut error),
+    );
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result.map_err(|e| Error::from_reason(format!("Unpack failed: {}", e)))
+}
+
 /// Bulk write helper for high-throughput ingestion.
 #[napi]
 pub struct BulkWriter {
-    store: FastStore,
+    store: SharedStore,
 }
 
 #[napi]
@@ -214,22 +1111,68 @@ impl BulkWriter {
         let store = FastStore::open(&root, &collection, batch_size)
             .map_err(|e| Error::from_reason(format!("Failed to create store: {}", e)))?;
 
-        Ok(BulkWriter { store })
+        Ok(BulkWriter {
+            store: SharedStore::new(store),
+        })
     }
 
     /// Put a document.
     #[napi]
     pub fn put(&mut self, doc_id: String, doc: serde_json::Value) -> Result<()> {
-        self.store
+        self.lock()?
             .put(doc_id, doc)
             .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))
     }
 
+    /// Put a document as a raw JSON line (fastest path - no parsing).
+    /// The line must be valid JSON with the `_id` field included.
+    #[napi]
+    pub fn put_raw_line(&mut self, doc_id: String, line: Buffer) -> Result<()> {
+        self.lock()?
+            .put_raw_line(doc_id, &line)
+            .map_err(|e| Error::from_reason(format!("Write failed: {}", e)))
+    }
+
+    /// Write a complete JSONL blob, see `ZdsStore.writeJsonl()`.
+    #[napi]
+    pub fn write_jsonl(&mut self, jsonl_data: Buffer, doc_ids: Vec<String>) -> Result<u32> {
+        write_jsonl_blob(&mut *self.lock()?, &jsonl_data, &doc_ids)
+    }
+
+    /// Delete a document.
+    #[napi]
+    pub fn delete(&mut self, doc_id: String) -> Result<()> {
+        self.lock()?
+            .delete(&doc_id)
+            .map_err(|e| Error::from_reason(format!("Delete failed: {}", e)))
+    }
+
     /// Flush pending writes.
     #[napi]
     pub fn flush(&mut self) -> Result<()> {
+        flush_store(&mut *self.lock()?)
+    }
+
+    /// Flush pending writes on the thread pool, without blocking the event
+    /// loop. Await it before writing again: a write issued while the flush
+    /// runs waits for it to finish.
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn flush_async(&self) -> Result<AsyncTask<StoreTask<()>>> {
+        if self.store.is_closed() {
+            return Err(closed_writer());
+        }
+        Ok(AsyncTask::new(StoreTask {
+            store: self.store.clone(),
+            op: Some(StoreOp::Write(Box::new(flush_store))),
+        }))
+    }
+
+    /// Flush pending writes, write the index and release the store. Further
+    /// calls fail; closing twice is a no-op.
+    #[napi]
+    pub fn close(&mut self) -> Result<()> {
         self.store
-            .flush()
+            .close()
             .map_err(|e| Error::from_reason(format!("Flush failed: {}", e)))
     }
 
@@ -240,6 +1183,17 @@ impl BulkWriter {
     }
 }
 
+impl BulkWriter {
+    /// The store, unless the writer is closed.
+    fn lock(&self) -> Result<StoreWriteGuard<'_>> {
+        self.store.write().map_err(|_| closed_writer())
+    }
+}
+
+fn closed_writer() -> Error {
+    Error::from_reason("BulkWriter is closed")
+}
+
 /// Root handle for a ZDS store directory.
 ///
 /// This class represents a ZDS root directory without binding to a specific collection.
@@ -324,7 +1278,7 @@ impl ZDSRoot {
         .map_err(|e| Error::from_reason(format!("Failed to open collection: {}", e)))?;
 
         Ok(ZDSStore {
-            store: Mutex::new(store),
+            store: SharedStore::new(store),
             root: self.root.root_path().to_string_lossy().to_string(),
             collection: name,
         })
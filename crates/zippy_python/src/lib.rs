@@ -2,14 +2,21 @@
 
 //! Python bindings for ZDS using PyO3.
 
-use std::sync::Mutex;
+use std::{borrow::Cow, collections::VecDeque, path::Path, sync::RwLock};
 
+use arrow::pyarrow::ToPyArrow;
 use pyo3::{
+    buffer::PyBuffer,
     exceptions::{PyIOError, PyKeyError, PyValueError},
     prelude::*,
-    types::{PyDict, PyList, PyTuple},
+    types::{PyBytes, PyDict, PyList, PyTuple},
+};
+use zippy_data::{
+    container::{self, PackOptions, PackProgress},
+    AsyncWriter, AsyncWriterConfig, Codec, CompactionPolicy, ConflictPolicy, Durability,
+    FastScanner, FastStore, IdStrategy, OpenMode, Predicate, SharedStore, StoreReadGuard,
+    StoreWriteGuard, ZDSRoot,
 };
-use zippy_data::{FastStore, OpenMode, ZDSRoot};
 
 /// Convert serde_json::Value to Python object
 fn json_to_py(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
@@ -90,100 +97,506 @@ fn py_to_json(obj: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
     )))
 }
 
+/// Document filter compiled to a Rust predicate.
+///
+/// Filters are evaluated in Rust before documents cross into Python. Combine
+/// them with `&` and `|`, or pass a plain dict such as `{"category": "A"}` to
+/// the `filter` argument of the scan methods instead.
+///
+/// Example:
+///     >>> f = Filter.eq("category", "A") & Filter.exists("label")
+///     >>> store.scan(filter=f)
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub struct Filter {
+    predicate: Predicate,
+}
+
+#[pymethods]
+impl Filter {
+    /// Field equals value (dot notation for nested fields).
+    #[staticmethod]
+    fn eq(field: String, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: Predicate::eq(field, py_to_json(value)?),
+        })
+    }
+
+    /// Field exists.
+    #[staticmethod]
+    fn exists(field: String) -> Self {
+        Filter {
+            predicate: Predicate::exists(field),
+        }
+    }
+
+    /// Field does not exist.
+    #[staticmethod]
+    fn not_exists(field: String) -> Self {
+        Filter {
+            predicate: Predicate::NotExists(field),
+        }
+    }
+
+    /// Field is greater than value.
+    #[staticmethod]
+    fn gt(field: String, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: Predicate::gt(field, py_to_json(value)?),
+        })
+    }
+
+    /// Field is greater than or equal to value.
+    #[staticmethod]
+    fn gte(field: String, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: Predicate::gte(field, py_to_json(value)?),
+        })
+    }
+
+    /// Field is less than value.
+    #[staticmethod]
+    fn lt(field: String, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: Predicate::lt(field, py_to_json(value)?),
+        })
+    }
+
+    /// Field is less than or equal to value.
+    #[staticmethod]
+    fn lte(field: String, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: Predicate::lte(field, py_to_json(value)?),
+        })
+    }
+
+    /// Field equals one of the values.
+    #[staticmethod]
+    fn isin(field: String, values: Vec<Bound<'_, PyAny>>) -> PyResult<Self> {
+        let values = values
+            .iter()
+            .map(|v| py_to_json(v))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(Filter {
+            predicate: Predicate::is_in(field, values),
+        })
+    }
+
+    /// String field starts with prefix.
+    #[staticmethod]
+    fn startswith(field: String, prefix: String) -> Self {
+        Filter {
+            predicate: Predicate::starts_with(field, prefix),
+        }
+    }
+
+    /// String field contains substring, or list field contains element.
+    #[staticmethod]
+    fn contains(field: String, value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: Predicate::contains(field, py_to_json(value)?),
+        })
+    }
+
+    /// All filters must match.
+    #[staticmethod]
+    fn all_of(filters: Vec<Filter>) -> Self {
+        Filter {
+            predicate: Predicate::and(filters.into_iter().map(|f| f.predicate).collect()),
+        }
+    }
+
+    /// At least one filter must match.
+    #[staticmethod]
+    fn any_of(filters: Vec<Filter>) -> Self {
+        Filter {
+            predicate: Predicate::or(filters.into_iter().map(|f| f.predicate).collect()),
+        }
+    }
+
+    /// Build a filter from a dict descriptor (see `scan`).
+    #[staticmethod]
+    fn from_dict(filter: &Bound<'_, PyDict>) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: predicate_from_dict(filter.as_any())?,
+        })
+    }
+
+    /// Text field contains every word of `query` (case-insensitive).
+    #[staticmethod]
+    fn text_match(field: String, query: String) -> Self {
+        Filter {
+            predicate: Predicate::text_match(field, query),
+        }
+    }
+
+    /// Parse a filter expression, e.g. `"age > 30 AND status = 'active'"`.
+    #[staticmethod]
+    fn parse(expr: &str) -> PyResult<Self> {
+        Ok(Filter {
+            predicate: predicate_from_str(expr)?,
+        })
+    }
+
+    fn __and__(&self, other: &Filter) -> Self {
+        Filter {
+            predicate: Predicate::and(vec![self.predicate.clone(), other.predicate.clone()]),
+        }
+    }
+
+    fn __or__(&self, other: &Filter) -> Self {
+        Filter {
+            predicate: Predicate::or(vec![self.predicate.clone(), other.predicate.clone()]),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Filter({:?})", self.predicate)
+    }
+}
+
+fn predicate_from_dict(obj: &Bound<'_, PyAny>) -> PyResult<Predicate> {
+    let value = py_to_json(obj)?;
+    Predicate::from_json(&value)
+        .map_err(|e| PyValueError::new_err(format!("Invalid filter: {}", e)))
+}
+
+fn predicate_from_str(filter: &str) -> PyResult<Predicate> {
+    Predicate::parse(filter).map_err(|e| PyValueError::new_err(format!("Invalid filter: {}", e)))
+}
+
+/// Compile a `filter` argument (dict, str, Filter or None) into a predicate.
+fn extract_predicate(filter: Option<&Bound<'_, PyAny>>) -> PyResult<Option<Predicate>> {
+    match filter {
+        None => Ok(None),
+        Some(obj) if obj.is_none() => Ok(None),
+        Some(obj) => {
+            if let Ok(f) = obj.downcast::<Filter>() {
+                Ok(Some(f.get().predicate.clone()))
+            } else if obj.downcast::<PyDict>().is_ok() {
+                predicate_from_dict(obj).map(Some)
+            } else if let Ok(expr) = obj.extract::<String>() {
+                predicate_from_str(&expr).map(Some)
+            } else {
+                Err(PyValueError::new_err(
+                    "filter must be a dict, str or Filter",
+                ))
+            }
+        }
+    }
+}
+
+fn matches(doc: &serde_json::Value, predicate: Option<&Predicate>) -> bool {
+    predicate.map_or(true, |p| Codec::apply_predicate(doc, p).unwrap_or(false))
+}
+
+/// Bytes of a `bytes` object (borrowed) or any other object supporting the
+/// buffer protocol, such as `bytearray` or `memoryview` (copied once).
+fn buffer_bytes<'a>(obj: &'a Bound<'_, PyAny>) -> PyResult<Cow<'a, [u8]>> {
+    if let Ok(bytes) = obj.downcast::<PyBytes>() {
+        return Ok(Cow::Borrowed(bytes.as_bytes()));
+    }
+    let buffer = PyBuffer::<u8>::get_bound(obj)
+        .map_err(|_| PyValueError::new_err("Expected bytes or a bytes-like buffer"))?;
+    Ok(Cow::Owned(buffer.to_vec(obj.py())?))
+}
+
+/// Convert a list of `(doc_id, doc)` tuples for a batch put.
+fn batch_items(items: &Bound<'_, PyList>) -> PyResult<Vec<(String, serde_json::Value)>> {
+    let mut batch = Vec::with_capacity(items.len());
+    for item in items.iter() {
+        let tuple = item
+            .downcast::<PyTuple>()
+            .map_err(|_| PyValueError::new_err("Expected list of (doc_id, doc) tuples"))?;
+        if tuple.len() != 2 {
+            return Err(PyValueError::new_err(
+                "Each item must be a (doc_id, doc) tuple",
+            ));
+        }
+        let doc_id: String = tuple.get_item(0)?.extract()?;
+        let doc = py_to_json(&tuple.get_item(1)?)?;
+        batch.push((doc_id, doc));
+    }
+    Ok(batch)
+}
+
+/// Convert a write error; ID conflicts and schema mismatches or violations
+/// raise ValueError.
+fn write_error(e: zippy_data::Error) -> PyErr {
+    match e {
+        zippy_data::Error::DocumentExists(_)
+        | zippy_data::Error::SchemaMismatch { .. }
+        | zippy_data::Error::SchemaViolation(_) => PyValueError::new_err(e.to_string()),
+        e => PyIOError::new_err(format!("Write failed: {}", e)),
+    }
+}
+
+/// Convert a blob error; a missing document or blob raises KeyError.
+fn blob_error(e: zippy_data::Error) -> PyErr {
+    match e {
+        zippy_data::Error::DocumentNotFound(what) => {
+            PyKeyError::new_err(format!("Not found: {}", what))
+        }
+        zippy_data::Error::Validation(_) => PyValueError::new_err(e.to_string()),
+        e => PyIOError::new_err(format!("Blob operation failed: {}", e)),
+    }
+}
+
+/// Convert a vector error; a missing document raises KeyError.
+fn vector_error(e: zippy_data::Error) -> PyErr {
+    match e {
+        zippy_data::Error::DocumentNotFound(what) => {
+            PyKeyError::new_err(format!("Not found: {}", what))
+        }
+        zippy_data::Error::Validation(_) => PyValueError::new_err(e.to_string()),
+        e => PyIOError::new_err(format!("Vector operation failed: {}", e)),
+    }
+}
+
+/// Default number of documents fetched per batch by `scan_iter`.
+const SCAN_ITER_BATCH_SIZE: usize = 1000;
+
+/// Parse an open mode name.
+fn parse_mode(mode: &str) -> PyResult<OpenMode> {
+    match mode {
+        "r" | "read" => Ok(OpenMode::Read),
+        "rw" | "read-write" | "readwrite" => Ok(OpenMode::ReadWrite),
+        _ => Err(PyValueError::new_err(format!(
+            "Invalid mode '{}'. Use 'r' for read-only or 'rw' for read-write",
+            mode
+        ))),
+    }
+}
+
 /// High-performance ZDS Store backed by Rust FastStore (JSONL-based).
-#[pyclass]
+///
+/// Reads share the store's lock, so threads calling `get`/`scan` with the
+/// GIL released run in parallel, alongside a writer.
+#[pyclass(frozen)]
 pub struct NativeStore {
-    store: Mutex<FastStore>,
+    store: SharedStore,
     root: String,
     collection: String,
 }
 
+impl NativeStore {
+    fn new(store: FastStore, root: String, collection: String) -> Self {
+        NativeStore {
+            store: SharedStore::new(store),
+            root,
+            collection,
+        }
+    }
+
+    /// Lock the store for reading, failing if it was closed.
+    fn read(&self) -> PyResult<StoreReadGuard<'_>> {
+        self.store.read().map_err(|_| closed_error())
+    }
+
+    /// Lock the store for writing, failing if it was closed.
+    fn write(&self) -> PyResult<StoreWriteGuard<'_>> {
+        self.store.write().map_err(|_| closed_error())
+    }
+}
+
+fn closed_error() -> PyErr {
+    PyValueError::new_err("I/O operation on closed store")
+}
+
 #[allow(clippy::useless_conversion)]
 #[pymethods]
 impl NativeStore {
     /// Open a ZDS store.
+    ///
+    /// With `mode="r"` the collection is opened read-only through a
+    /// read-only root: it must exist, writes fail, and no write lock is
+    /// taken, so notebooks can inspect a store another process writes to.
+    #[staticmethod]
+    #[pyo3(signature = (root, collection = "default", batch_size = 5000, mode = "rw"))]
+    fn open(root: String, collection: &str, batch_size: usize, mode: &str) -> PyResult<Self> {
+        let store = match parse_mode(mode)? {
+            OpenMode::ReadWrite => FastStore::open(&root, collection, batch_size),
+            OpenMode::Read => ZDSRoot::open(&root, batch_size, OpenMode::Read)
+                .and_then(|zds_root| zds_root.collection(collection)),
+        }
+        .map_err(|e| PyIOError::new_err(format!("Failed to open store: {}", e)))?;
+
+        Ok(NativeStore::new(store, root, collection.to_string()))
+    }
+
+    /// Create an empty store kept in memory and deleted when released,
+    /// for tests and caches.
+    #[staticmethod]
+    #[pyo3(signature = (collection = "default", batch_size = 5000))]
+    fn in_memory(collection: &str, batch_size: usize) -> PyResult<Self> {
+        let store = FastStore::open_in_memory(collection, batch_size)
+            .map_err(|e| PyIOError::new_err(format!("Failed to open store: {}", e)))?;
+        let root = store.root_path().to_string_lossy().to_string();
+        Ok(NativeStore::new(store, root, collection.to_string()))
+    }
+
+    /// Copy a collection into memory, pinning a hot dataset in RAM. Writes
+    /// go to the copy only.
     #[staticmethod]
     #[pyo3(signature = (root, collection = "default", batch_size = 5000))]
-    fn open(root: String, collection: &str, batch_size: usize) -> PyResult<Self> {
-        let store = FastStore::open(&root, collection, batch_size)
+    fn load_in_memory(root: String, collection: &str, batch_size: usize) -> PyResult<Self> {
+        let store = FastStore::load_in_memory(&root, collection, batch_size)
             .map_err(|e| PyIOError::new_err(format!("Failed to open store: {}", e)))?;
+        let root = store.root_path().to_string_lossy().to_string();
+        Ok(NativeStore::new(store, root, collection.to_string()))
+    }
 
-        Ok(NativeStore {
-            store: Mutex::new(store),
-            root,
-            collection: collection.to_string(),
+    /// Whether the store is kept in memory.
+    #[getter]
+    fn is_in_memory(&self) -> PyResult<bool> {
+        Ok(self.read()?.is_in_memory())
+    }
+
+    /// Flush pending writes and release the store. Further calls raise
+    /// ValueError; closing twice is a no-op.
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| {
+            self.store
+                .close()
+                .map_err(|e| PyIOError::new_err(format!("Flush failed: {}", e)))
         })
     }
 
+    /// Whether the store was closed.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.store.is_closed()
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&self, py: Python<'_>, _args: &Bound<'_, PyTuple>) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+
     /// Get document by ID.
     fn get(&self, py: Python<'_>, doc_id: &str) -> PyResult<PyObject> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let store = self.read()?;
         let value = store
             .get(doc_id)
             .map_err(|_| PyKeyError::new_err(format!("Document not found: {}", doc_id)))?;
         json_to_py(py, &value)
     }
 
+    /// Get several documents at once, in the order of `doc_ids`, with None
+    /// for missing ones. The lines are read in one pass and parsed in
+    /// parallel with the GIL released, which is much faster than calling
+    /// `get` in a loop.
+    fn get_batch(&self, py: Python<'_>, doc_ids: Vec<String>) -> PyResult<PyObject> {
+        let docs = py.allow_threads(|| {
+            let store = self.read()?;
+            store
+                .get_many(&doc_ids)
+                .map_err(|e| PyIOError::new_err(format!("Read failed: {}", e)))
+        })?;
+
+        let list = PyList::empty_bound(py);
+        for doc in &docs {
+            match doc {
+                Some(doc) => list.append(json_to_py(py, doc)?)?,
+                None => list.append(py.None())?,
+            }
+        }
+        Ok(list.into())
+    }
+
     /// Put a document.
     fn put(&self, doc_id: &str, doc: &Bound<'_, PyDict>) -> PyResult<()> {
         let value = py_to_json(doc.as_any())?;
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
-        store
-            .put(doc_id, value)
-            .map_err(|e| PyIOError::new_err(format!("Write failed: {}", e)))?;
+        let mut store = self.write()?;
+        store.put(doc_id, value).map_err(write_error)?;
         Ok(())
     }
 
+    /// Put a document under a generated ID and return the ID. `strategy`
+    /// is `"ulid"` (sortable by creation time), `"uuid"` or `"seq"` (a
+    /// per-collection counter).
+    #[pyo3(signature = (doc, strategy = "ulid"))]
+    fn put_auto(&self, doc: &Bound<'_, PyDict>, strategy: &str) -> PyResult<String> {
+        let strategy: IdStrategy = strategy
+            .parse()
+            .map_err(|e: zippy_data::Error| PyValueError::new_err(e.to_string()))?;
+        let value = py_to_json(doc.as_any())?;
+        let mut store = self.write()?;
+        store.put_auto(value, strategy).map_err(write_error)
+    }
+
+    /// Apply a JSON merge patch (RFC 7386) to a document and return the
+    /// patched document. `None` values remove fields.
+    fn patch(&self, py: Python<'_>, doc_id: &str, patch: &Bound<'_, PyDict>) -> PyResult<PyObject> {
+        let patch = py_to_json(patch.as_any())?;
+        let mut store = self.write()?;
+        let value = store.patch(doc_id, &patch).map_err(|e| match e {
+            zippy_data::Error::DocumentNotFound(_) => {
+                PyKeyError::new_err(format!("Document not found: {}", doc_id))
+            }
+            e @ (zippy_data::Error::SchemaMismatch { .. }
+            | zippy_data::Error::SchemaViolation(_)) => PyValueError::new_err(e.to_string()),
+            e => PyIOError::new_err(format!("Patch failed: {}", e)),
+        })?;
+        json_to_py(py, &value)
+    }
+
     /// Put multiple documents in a single batch (much faster than individual puts).
-    fn put_batch(&self, items: &Bound<'_, PyList>) -> PyResult<usize> {
+    /// The GIL is released while writing.
+    fn put_batch(&self, py: Python<'_>, items: &Bound<'_, PyList>) -> PyResult<usize> {
         // Convert all items first (outside the lock)
-        let mut batch: Vec<(String, serde_json::Value)> = Vec::with_capacity(items.len());
-        for item in items.iter() {
-            let tuple = item
-                .downcast::<PyTuple>()
-                .map_err(|_| PyValueError::new_err("Expected list of (doc_id, doc) tuples"))?;
-            if tuple.len() != 2 {
-                return Err(PyValueError::new_err(
-                    "Each item must be a (doc_id, doc) tuple",
-                ));
+        let batch = batch_items(items)?;
+
+        // Now acquire lock once and write all, without holding the GIL
+        py.allow_threads(|| {
+            let mut store = self.write()?;
+
+            let count = batch.len();
+            for (doc_id, doc) in batch {
+                store.put(doc_id, doc).map_err(write_error)?;
             }
-            let doc_id: String = tuple.get_item(0)?.extract()?;
-            let doc = py_to_json(&tuple.get_item(1)?)?;
-            batch.push((doc_id, doc));
-        }
 
-        // Now acquire lock once and write all
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+            Ok(count)
+        })
+    }
 
-        let count = batch.len();
-        for (doc_id, doc) in batch {
-            store
-                .put(doc_id, doc)
-                .map_err(|e| PyIOError::new_err(format!("Write failed: {}", e)))?;
-        }
+    /// Put a batch of documents, resolving existing IDs with `policy`:
+    /// "overwrite", "skip_existing" or "fail_on_conflict" (raises
+    /// ValueError and writes nothing if any ID exists). Returns the number
+    /// of documents written. The GIL is released while writing.
+    #[pyo3(signature = (items, policy = "overwrite"))]
+    fn put_batch_with_policy(
+        &self,
+        py: Python<'_>,
+        items: &Bound<'_, PyList>,
+        policy: &str,
+    ) -> PyResult<usize> {
+        let policy: ConflictPolicy = policy
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+        let batch = batch_items(items)?;
 
-        Ok(count)
+        py.allow_threads(|| {
+            let mut store = self.write()?;
+            store
+                .put_batch_with_policy(batch, policy)
+                .map_err(write_error)
+        })
     }
 
     /// Put multiple documents as raw JSONL bytes (fastest path - zero parsing).
-    /// Each line must be valid JSON with "_id" field included.
+    /// Each line must be valid JSON with "_id" field included, given as
+    /// bytes or any bytes-like buffer (bytearray, memoryview slices, ...).
     /// Use with orjson:
     ///   lines = [orjson.dumps({"_id": id, **doc}) for id, doc in items]
     ///   store.put_raw_batch([(id, line) for (id, _), line in zip(items, lines)])
     fn put_raw_batch(&self, items: &Bound<'_, PyList>) -> PyResult<usize> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let mut store = self.write()?;
 
         let mut count = 0;
         for item in items.iter() {
@@ -196,50 +609,175 @@ impl NativeStore {
                 ));
             }
             let doc_id: String = tuple.get_item(0)?.extract()?;
-            let json_bytes: Vec<u8> = tuple.get_item(1)?.extract()?;
+            let item = tuple.get_item(1)?;
+            let json_bytes = buffer_bytes(&item)?;
 
             // Write raw bytes directly - no parsing!
             store
                 .put_raw_line(&doc_id, &json_bytes)
-                .map_err(|e| PyIOError::new_err(format!("Write failed: {}", e)))?;
+                .map_err(write_error)?;
             count += 1;
         }
 
         Ok(count)
     }
 
+    /// Start parallel ingestion into `shards` new data files (by default
+    /// the collection's configured shard count, or 8).
+    ///
+    /// The returned writer can be shared by threads (its writes release the
+    /// GIL) and makes its documents visible on `merge()`. Until then, puts
+    /// on this store fail. Use as a context manager to merge on success and
+    /// discard on error.
+    #[pyo3(signature = (shards = None))]
+    fn sharded_writer(
+        slf: Py<Self>,
+        py: Python<'_>,
+        shards: Option<usize>,
+    ) -> PyResult<NativeShardedWriter> {
+        let mut store = slf.get().write()?;
+        let shards = match shards {
+            Some(shards) => shards,
+            None => store.config().map_err(write_error)?.shards.unwrap_or(8),
+        };
+        let writer = store.sharded_writer(shards).map_err(write_error)?;
+        drop(store);
+        Ok(NativeShardedWriter {
+            store: slf.clone_ref(py),
+            writer: RwLock::new(Some(writer)),
+        })
+    }
+
+    /// Start a background writer for this store.
+    ///
+    /// Its puts and deletes are queued and applied on a thread of their
+    /// own, which also flushes every `flush_interval_ms`, so callers never
+    /// wait for a flush. Once `queue_size` writes are queued, further ones
+    /// block (with the GIL released) until the thread catches up. Use as a
+    /// context manager to close it on exit.
+    #[pyo3(signature = (queue_size = 10000, max_batch = 1000, flush_interval_ms = 1000))]
+    fn async_writer(
+        &self,
+        queue_size: usize,
+        max_batch: usize,
+        flush_interval_ms: u64,
+    ) -> PyResult<NativeAsyncWriter> {
+        let config = AsyncWriterConfig {
+            queue_size,
+            max_batch,
+            flush_interval_ms,
+        };
+        let writer = AsyncWriter::new(self.store.clone(), config).map_err(async_write_error)?;
+        Ok(NativeAsyncWriter { writer })
+    }
+
     /// Write complete JSONL blob (fastest bulk write - single FFI call, single buffer copy).
-    /// jsonl_blob: Pre-serialized JSONL bytes (newline-separated JSON objects with "_id" field).
+    /// jsonl_blob: Pre-serialized JSONL bytes (newline-separated JSON objects with "_id" field),
+    ///   or any bytes-like buffer such as a memoryview.
     /// doc_ids: List of document IDs in order matching the lines.
-    fn write_jsonl(&self, jsonl_blob: &[u8], doc_ids: Vec<String>) -> PyResult<usize> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+    /// The GIL is released while writing.
+    fn write_jsonl(
+        &self,
+        py: Python<'_>,
+        jsonl_blob: &Bound<'_, PyAny>,
+        doc_ids: Vec<String>,
+    ) -> PyResult<usize> {
+        let jsonl_blob = buffer_bytes(jsonl_blob)?;
+        py.allow_threads(|| {
+            let mut store = self.write()?;
 
-        store
-            .write_jsonl_blob(jsonl_blob, &doc_ids)
-            .map_err(|e| PyIOError::new_err(format!("Write failed: {}", e)))
+            store
+                .write_jsonl_blob(&jsonl_blob, &doc_ids)
+                .map_err(write_error)
+        })
     }
 
     /// Delete a document.
     fn delete(&self, doc_id: &str) -> PyResult<()> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let mut store = self.write()?;
         store
             .delete(doc_id)
             .map_err(|e| PyKeyError::new_err(format!("Delete failed: {}", e)))?;
         Ok(())
     }
 
+    /// Attach binary data (an image, audio clip, ...) to a document under
+    /// `name`, replacing any blob of that name. The GIL is released while
+    /// writing.
+    fn put_blob(&self, py: Python<'_>, doc_id: &str, name: &str, data: &[u8]) -> PyResult<()> {
+        py.allow_threads(|| {
+            let mut store = self.write()?;
+            store.put_blob(doc_id, name, data).map_err(blob_error)
+        })
+    }
+
+    /// Read a document's blob as bytes.
+    fn get_blob(&self, py: Python<'_>, doc_id: &str, name: &str) -> PyResult<PyObject> {
+        let data = py.allow_threads(|| {
+            let store = self.read()?;
+            store.get_blob(doc_id, name).map_err(blob_error)
+        })?;
+        Ok(PyBytes::new_bound(py, &data).into())
+    }
+
+    /// Names of a document's blobs, sorted.
+    fn blob_names(&self, doc_id: &str) -> PyResult<Vec<String>> {
+        let store = self.read()?;
+        Ok(store.blob_names(doc_id))
+    }
+
+    /// Remove a document's blob.
+    fn delete_blob(&self, doc_id: &str, name: &str) -> PyResult<()> {
+        let mut store = self.write()?;
+        store.delete_blob(doc_id, name).map_err(blob_error)
+    }
+
+    /// Store a document's embedding in the vector index `name`, creating
+    /// the index with the vector's dimension on first use.
+    fn put_vector(&self, name: &str, doc_id: &str, vector: Vec<f32>) -> PyResult<()> {
+        let mut store = self.write()?;
+        store
+            .put_vector(name, doc_id, &vector)
+            .map_err(vector_error)
+    }
+
+    /// A document's embedding in the vector index `name`, or None.
+    fn get_vector(&self, name: &str, doc_id: &str) -> PyResult<Option<Vec<f32>>> {
+        let store = self.read()?;
+        Ok(store.get_vector(name, doc_id).map(<[f32]>::to_vec))
+    }
+
+    /// Names of the collection's vector indexes.
+    fn vector_indexes(&self) -> PyResult<Vec<String>> {
+        let store = self.read()?;
+        Ok(store.vector_indexes().map(str::to_string).collect())
+    }
+
+    /// The `k` documents nearest to `query` in the vector index `name`, as
+    /// `(doc_id, score)` tuples, best first. `metric` is "cosine" or "dot".
+    /// The GIL is released while searching.
+    #[pyo3(signature = (name, query, k = 10, metric = "cosine"))]
+    fn knn(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        query: Vec<f32>,
+        k: usize,
+        metric: &str,
+    ) -> PyResult<Vec<(String, f32)>> {
+        let metric: zippy_data::Metric = metric
+            .parse()
+            .map_err(|e: zippy_data::Error| PyValueError::new_err(e.to_string()))?;
+        let hits = py.allow_threads(|| {
+            let store = self.read()?;
+            store.knn(name, &query, k, metric).map_err(vector_error)
+        })?;
+        Ok(hits.into_iter().map(|n| (n.doc_id, n.score)).collect())
+    }
+
     /// Flush pending writes and refresh mmap for reads.
     fn flush(&self) -> PyResult<()> {
-        let mut store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let mut store = self.write()?;
         store
             .flush()
             .map_err(|e| PyIOError::new_err(format!("Flush failed: {}", e)))?;
@@ -252,31 +790,200 @@ impl NativeStore {
 
     /// Get document count.
     fn count(&self) -> PyResult<usize> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let store = self.read()?;
         Ok(store.len())
     }
 
+    /// Rewrite the data file without deleted and overwritten documents.
+    fn compact(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| {
+            let mut store = self.write()?;
+            store
+                .compact()
+                .map_err(|e| PyIOError::new_err(format!("Compact failed: {}", e)))
+        })
+    }
+
+    /// Compact automatically on flush/close once `min_dead_ratio` of the data
+    /// file is garbage (and at least `min_dead_bytes`). `None` disables it.
+    #[pyo3(signature = (min_dead_ratio = 0.3, min_dead_bytes = None))]
+    fn set_auto_compact(
+        &self,
+        min_dead_ratio: Option<f64>,
+        min_dead_bytes: Option<u64>,
+    ) -> PyResult<()> {
+        let mut store = self.write()?;
+        store.set_compaction_policy(min_dead_ratio.map(|ratio| {
+            let mut policy = CompactionPolicy::with_ratio(ratio);
+            if let Some(bytes) = min_dead_bytes {
+                policy.min_dead_bytes = bytes;
+            }
+            policy
+        }));
+        Ok(())
+    }
+
+    /// When writes are synced: `"none"`, `"flush"`, `"fsync_batch"` (once
+    /// per flush, the default) or `"fsync_op"` (after every write).
+    #[getter]
+    fn durability(&self) -> PyResult<String> {
+        Ok(self.read()?.durability().to_string())
+    }
+
+    /// Set when writes are synced (see `durability`).
+    fn set_durability(&self, durability: &str) -> PyResult<()> {
+        let durability: Durability = durability
+            .parse()
+            .map_err(|e: zippy_data::Error| PyValueError::new_err(e.to_string()))?;
+        self.write()?.set_durability(durability);
+        Ok(())
+    }
+
+    /// Garbage statistics: `dead_bytes`, `dead_ratio` and `tombstones`.
+    fn compaction_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let store = self.read()?;
+        let dict = PyDict::new_bound(py);
+        dict.set_item("dead_bytes", store.dead_bytes())?;
+        dict.set_item("dead_ratio", store.dead_ratio())?;
+        dict.set_item("tombstones", store.tombstone_count())?;
+        Ok(dict.into())
+    }
+
+    /// Storage statistics: `count`, `data_bytes` (logical size of the data
+    /// segments), `live_bytes`, `dead_bytes`, `dead_ratio`, `index_bytes`
+    /// (size of `index.bin`), `index_memory_bytes` (memory held by the
+    /// in-memory index), `min_offset`/`max_offset` (offsets of the
+    /// first and last live lines, `None` when empty), `last_flush` (ms
+    /// since the epoch, `None` before the first flush), `segments`,
+    /// `tombstones` and `compression`.
+    fn storage_stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = self.read()?.stats();
+        let dict = PyDict::new_bound(py);
+        dict.set_item("count", stats.doc_count)?;
+        dict.set_item("data_bytes", stats.data_bytes)?;
+        dict.set_item("live_bytes", stats.live_bytes)?;
+        dict.set_item("dead_bytes", stats.dead_bytes)?;
+        dict.set_item("dead_ratio", stats.dead_ratio)?;
+        dict.set_item("index_bytes", stats.index_bytes)?;
+        dict.set_item("index_memory_bytes", stats.index_memory_bytes)?;
+        dict.set_item("min_offset", stats.min_offset)?;
+        dict.set_item("max_offset", stats.max_offset)?;
+        dict.set_item("last_flush", stats.last_flush)?;
+        dict.set_item("segments", stats.segments)?;
+        dict.set_item("tombstones", stats.tombstones)?;
+        dict.set_item("compression", stats.compression.as_str())?;
+        Ok(dict.into())
+    }
+
+    /// IDs deleted since the last compaction, sorted.
+    fn deleted_ids(&self) -> PyResult<Vec<String>> {
+        let store = self.read()?;
+        Ok(store.deleted_ids())
+    }
+
+    /// Data file compression (`"none"` or `"zstd"`).
+    #[getter]
+    fn compression(&self) -> PyResult<String> {
+        let store = self.read()?;
+        Ok(store.compression().to_string())
+    }
+
+    /// Set the data file compression (`"none"` or `"zstd"`).
+    ///
+    /// Applies immediately to an empty store, otherwise on the next `compact()`.
+    fn set_compression(&self, compression: &str) -> PyResult<()> {
+        let compression = compression
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+        let mut store = self.write()?;
+        store
+            .set_compression(compression)
+            .map_err(|e| PyIOError::new_err(format!("Set compression failed: {}", e)))
+    }
+
+    /// Check data against its checksums.
+    ///
+    /// Returns a dict with `checked`, `index_ok` and `corrupt` (a list of
+    /// `{"_id", "segment", "offset", "kind"}` dicts).
+    fn verify(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let report = py.allow_threads(|| {
+            let mut store = self.write()?;
+            store
+                .verify()
+                .map_err(|e| PyIOError::new_err(format!("Verify failed: {}", e)))
+        })?;
+        let corrupt = PyList::empty_bound(py);
+        for entry in &report.corrupt {
+            let item = PyDict::new_bound(py);
+            item.set_item("_id", &entry.doc_id)?;
+            item.set_item("segment", entry.segment)?;
+            item.set_item("offset", entry.offset)?;
+            item.set_item("kind", entry.kind.as_str())?;
+            corrupt.append(item)?;
+        }
+        let dict = PyDict::new_bound(py);
+        dict.set_item("checked", report.checked)?;
+        dict.set_item("index_ok", report.index_ok)?;
+        dict.set_item("corrupt", corrupt)?;
+        Ok(dict.into())
+    }
+
     /// Check if document exists.
     fn exists(&self, doc_id: &str) -> PyResult<bool> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let store = self.read()?;
         Ok(store.exists(doc_id))
     }
 
     /// Scan all documents (mmap + parallel SIMD parsing).
-    fn scan(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
-        let docs = store
-            .scan()
-            .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))?;
+    /// The GIL is released while reading and parsing.
+    ///
+    /// `filter` is a `Filter` or a dict descriptor evaluated in Rust:
+    /// `{"field": value}` for equality, `{"field": {"$exists": True}}`,
+    /// comparisons such as `{"age": {"$gte": 18, "$lt": 65}}` (also `$in`,
+    /// `$startswith`, `$contains`), and `{"$and": [...]}` / `{"$or": [...]}`.
+    /// A string is parsed as an expression such as `"age >= 18 AND tier IN (1, 2)"`. Equality on
+    /// fields with an index (see `create_index`) only reads matching documents.
+    ///
+    /// `fields` (dot notation allowed) projects each document onto those
+    /// fields. Filtering and projection run inside the parallel scan, so
+    /// only matching documents are converted to Python objects.
+    #[pyo3(signature = (filter = None, fields = None))]
+    fn scan(
+        &self,
+        py: Python<'_>,
+        filter: Option<&Bound<'_, PyAny>>,
+        fields: Option<Vec<String>>,
+    ) -> PyResult<PyObject> {
+        let predicate = extract_predicate(filter)?;
+        let docs = py.allow_threads(|| {
+            let store = self.read()?;
+            let fields: Option<Vec<&str>> = fields
+                .as_ref()
+                .map(|f| f.iter().map(String::as_str).collect());
+            store
+                .scan_filtered(predicate.as_ref(), fields.as_deref())
+                .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))
+        })?;
+
+        let list = PyList::empty_bound(py);
+        for doc in docs {
+            list.append(json_to_py(py, &doc)?)?;
+        }
+        Ok(list.into())
+    }
+
+    /// Return a reproducible random sample of `n` documents (all of them if
+    /// the store holds fewer), in storage order. The same `seed` gives the
+    /// same sample while the store is unchanged; only the sampled documents
+    /// are read.
+    #[pyo3(signature = (n, seed = 0))]
+    fn sample(&self, py: Python<'_>, n: usize, seed: u64) -> PyResult<PyObject> {
+        let docs = py.allow_threads(|| {
+            let store = self.read()?;
+            store
+                .sample(n, seed)
+                .map_err(|e| PyIOError::new_err(format!("Sample failed: {}", e)))
+        })?;
 
         let list = PyList::empty_bound(py);
         for doc in docs {
@@ -286,69 +993,521 @@ impl NativeStore {
     }
 
     /// Scan and return raw JSON bytes (fastest - zero parsing, use with orjson).
-    fn scan_raw(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
-        let raw_docs = store
-            .scan_raw()
-            .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))?;
+    /// The GIL is released while reading.
+    ///
+    /// With a `filter` (see `scan`), each line is parsed in Rust to evaluate it
+    /// and only matching lines are returned.
+    #[pyo3(signature = (filter = None))]
+    fn scan_raw(&self, py: Python<'_>, filter: Option<&Bound<'_, PyAny>>) -> PyResult<PyObject> {
+        let predicate = extract_predicate(filter)?;
+        let raw_docs = py.allow_threads(|| {
+            let store = self.read()?;
+            let mut raw_docs = store
+                .scan_raw()
+                .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))?;
+            if predicate.is_some() {
+                raw_docs.retain(|line| {
+                    serde_json::from_slice::<serde_json::Value>(line)
+                        .map(|doc| matches(&doc, predicate.as_ref()))
+                        .unwrap_or(false)
+                });
+            }
+            Ok::<_, PyErr>(raw_docs)
+        })?;
 
         // Convert to list of Python bytes objects
         let list = PyList::empty_bound(py);
         for doc in raw_docs {
-            let bytes = pyo3::types::PyBytes::new_bound(py, &doc);
+            let bytes = PyBytes::new_bound(py, &doc);
             list.append(bytes)?;
         }
         Ok(list.into())
     }
 
-    /// List all document IDs.
+    /// Scan raw JSON lines into one contiguous buffer, avoiding a bytes
+    /// object per document. The GIL is released while reading.
+    ///
+    /// Returns `(data, offsets)` where `offsets` is a NumPy int64 array of
+    /// `len + 1` entries and document `i` is `data[offsets[i]:offsets[i + 1]]`;
+    /// slice a `memoryview(data)` for zero-copy views. `filter` is as for
+    /// `scan_raw`. Requires NumPy.
+    #[pyo3(signature = (filter = None))]
+    fn scan_raw_buffer(
+        &self,
+        py: Python<'_>,
+        filter: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<(PyObject, PyObject)> {
+        let predicate = extract_predicate(filter)?;
+        let (data, offsets) = py.allow_threads(|| {
+            let store = self.read()?;
+            store
+                .scan_raw_packed(predicate.as_ref())
+                .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))
+        })?;
+
+        let offsets: Vec<u8> = offsets
+            .iter()
+            .flat_map(|&offset| (offset as i64).to_le_bytes())
+            .collect();
+        let offsets = py
+            .import_bound("numpy")?
+            .call_method1("frombuffer", (PyBytes::new_bound(py, &offsets), "<i8"))?;
+        Ok((PyBytes::new_bound(py, &data).into(), offsets.into()))
+    }
+
+    /// Build a secondary index on `field` (dot notation allowed).
+    /// Returns the number of indexed documents.
+    fn create_index(&self, py: Python<'_>, field: String) -> PyResult<usize> {
+        py.allow_threads(|| {
+            let mut store = self.write()?;
+            store
+                .create_index(&field)
+                .map_err(|e| PyIOError::new_err(format!("Create index failed: {}", e)))
+        })
+    }
+
+    /// Drop the secondary index on `field`.
+    fn drop_index(&self, field: &str) -> PyResult<()> {
+        let mut store = self.write()?;
+        store
+            .drop_index(field)
+            .map_err(|e| PyIOError::new_err(format!("Drop index failed: {}", e)))
+    }
+
+    /// Indexed field names.
+    fn indexes(&self) -> PyResult<Vec<String>> {
+        let store = self.read()?;
+        let mut fields: Vec<String> = store.field_indexes().fields().map(String::from).collect();
+        fields.sort();
+        Ok(fields)
+    }
+
+    /// Build a full-text index on string `field` (dot notation allowed).
+    /// Returns the number of documents with indexed text.
+    fn create_text_index(&self, py: Python<'_>, field: String) -> PyResult<usize> {
+        py.allow_threads(|| {
+            let mut store = self.write()?;
+            store
+                .create_text_index(&field)
+                .map_err(|e| PyIOError::new_err(format!("Create text index failed: {}", e)))
+        })
+    }
+
+    /// Drop the full-text index on `field`.
+    fn drop_text_index(&self, field: &str) -> PyResult<()> {
+        let mut store = self.write()?;
+        store
+            .drop_text_index(field)
+            .map_err(|e| PyIOError::new_err(format!("Drop text index failed: {}", e)))
+    }
+
+    /// Text-indexed field names.
+    fn text_indexes(&self) -> PyResult<Vec<String>> {
+        let store = self.read()?;
+        let mut fields: Vec<String> = store
+            .field_indexes()
+            .text_fields()
+            .map(String::from)
+            .collect();
+        fields.sort();
+        Ok(fields)
+    }
+
+    /// Documents whose text-indexed fields contain every word of `query`.
+    fn search(&self, py: Python<'_>, query: &str) -> PyResult<PyObject> {
+        let docs = py.allow_threads(|| {
+            let store = self.read()?;
+            store
+                .search(query)
+                .map_err(|e| PyValueError::new_err(format!("Search failed: {}", e)))
+        })?;
+
+        let list = PyList::empty_bound(py);
+        for doc in &docs {
+            list.append(json_to_py(py, doc)?)?;
+        }
+        Ok(list.into())
+    }
+
+    /// Scan into a `pyarrow.RecordBatch` built in Rust.
+    ///
+    /// `fields` (dot notation allowed) become typed columns; by default every
+    /// top-level field is exported. The batch is handed to pyarrow through the
+    /// Arrow C data interface, so no per-document Python objects are created.
+    /// Use `.to_pandas()` or `pyarrow.Table.from_batches` on the result.
+    /// Requires `pyarrow` to be installed.
+    #[pyo3(signature = (fields = None, filter = None))]
+    fn scan_arrow(
+        &self,
+        py: Python<'_>,
+        fields: Option<Vec<String>>,
+        filter: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyObject> {
+        let predicate = extract_predicate(filter)?;
+        let batch = py.allow_threads(|| {
+            let store = self.read()?;
+            let docs = store
+                .scan_filtered(predicate.as_ref(), None)
+                .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))?;
+            drop(store);
+            let fields: Option<Vec<&str>> = fields
+                .as_ref()
+                .map(|f| f.iter().map(String::as_str).collect());
+            let schema = zippy_data::arrow::infer_schema(&docs, fields.as_deref());
+            zippy_data::arrow::to_record_batch(&docs, schema)
+                .map_err(|e| PyValueError::new_err(format!("Arrow conversion failed: {}", e)))
+        })?;
+
+        batch.to_pyarrow(py)
+    }
+
+    /// Lazily iterate over all documents, fetching `batch_size` at a time.
+    ///
+    /// Only one batch is materialized at a time, so this works for collections
+    /// that don't fit in memory. `fields` projects and `filter` (see `scan`)
+    /// selects documents in Rust.
+    #[pyo3(signature = (batch_size = SCAN_ITER_BATCH_SIZE, fields = None, filter = None))]
+    fn scan_iter(
+        slf: Py<Self>,
+        batch_size: usize,
+        fields: Option<Vec<String>>,
+        filter: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<ScanIterator> {
+        let predicate = extract_predicate(filter)?;
+        if batch_size == 0 {
+            return Err(PyValueError::new_err("batch_size must be positive"));
+        }
+        let scanner = {
+            let store = slf.get().read()?;
+            store
+                .iter()
+                .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))?
+        };
+        Ok(ScanIterator {
+            store: slf,
+            scanner,
+            batch_size,
+            fields,
+            predicate,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    /// Read `limit` documents starting at position `offset`.
+    ///
+    /// Documents are ordered by position in the data file (the order they
+    /// were last written), so pages are stable across calls and reopens.
+    fn scan_range(&self, py: Python<'_>, offset: usize, limit: usize) -> PyResult<PyObject> {
+        let docs = py.allow_threads(|| {
+            let store = self.read()?;
+            store
+                .scan_range(offset, limit)
+                .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))
+        })?;
+
+        let list = PyList::empty_bound(py);
+        for doc in &docs {
+            list.append(json_to_py(py, doc)?)?;
+        }
+        Ok(list.into())
+    }
+
+    /// List all document IDs in file order.
     fn list_doc_ids(&self) -> PyResult<Vec<String>> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let store = self.read()?;
         Ok(store.doc_ids())
     }
 
     /// Read entire JSONL file as bytes (fastest bulk read).
     /// Returns raw JSONL content - caller splits and parses.
     fn read_jsonl_blob(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let store = self
-            .store
-            .lock()
-            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        let store = self.read()?;
 
         if let Some(data) = store.get_raw_data() {
-            Ok(pyo3::types::PyBytes::new_bound(py, data).into())
+            Ok(PyBytes::new_bound(py, &data).into())
         } else {
-            Ok(pyo3::types::PyBytes::new_bound(py, b"").into())
+            Ok(PyBytes::new_bound(py, b"").into())
         }
     }
 
+    /// Asyncio facade over this store (`zippy.aio.AsyncNativeStore`).
+    ///
+    /// Each method runs in a worker thread; the heavy methods release the GIL,
+    /// so awaiting them does not block the event loop.
+    #[getter]
+    fn aio(slf: Py<Self>, py: Python<'_>) -> PyResult<PyObject> {
+        let module = py.import_bound("zippy.aio")?;
+        Ok(module.getattr("AsyncNativeStore")?.call1((slf,))?.unbind())
+    }
+
     fn __len__(&self) -> PyResult<usize> {
         self.count()
     }
 
+    fn __iter__(slf: Py<Self>) -> PyResult<ScanIterator> {
+        Self::scan_iter(slf, SCAN_ITER_BATCH_SIZE, None, None)
+    }
+
     fn __contains__(&self, doc_id: &str) -> PyResult<bool> {
         self.exists(doc_id)
     }
 
     fn __repr__(&self) -> String {
-        let count = self.store.lock().map(|s| s.len()).unwrap_or(0);
-        format!(
-            "NativeStore(root={:?}, collection={:?}, count={})",
-            self.root, self.collection, count
-        )
+        match self.read() {
+            Ok(store) => format!(
+                "NativeStore(root={:?}, collection={:?}, count={})",
+                self.root,
+                self.collection,
+                store.len()
+            ),
+            Err(_) => format!(
+                "NativeStore(root={:?}, collection={:?}, closed)",
+                self.root, self.collection
+            ),
+        }
+    }
+}
+
+/// Parallel writer returned by `NativeStore.sharded_writer()`.
+#[pyclass(frozen)]
+pub struct NativeShardedWriter {
+    store: Py<NativeStore>,
+    /// `None` once merged or discarded
+    writer: RwLock<Option<zippy_data::ShardedWriter>>,
+}
+
+impl NativeShardedWriter {
+    /// Run `op` on the writer with the GIL released.
+    fn with_writer<T: Send>(
+        &self,
+        py: Python<'_>,
+        op: impl FnOnce(&zippy_data::ShardedWriter) -> PyResult<T> + Send,
+    ) -> PyResult<T> {
+        py.allow_threads(|| {
+            let slot = self
+                .writer
+                .read()
+                .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+            let writer = slot
+                .as_ref()
+                .ok_or_else(|| PyValueError::new_err("Sharded writer is finished"))?;
+            op(writer)
+        })
+    }
+
+    fn merge_writer(&self, py: Python<'_>, writer: zippy_data::ShardedWriter) -> PyResult<usize> {
+        let store = self.store.get();
+        py.allow_threads(|| store.write()?.merge_shards(writer).map_err(write_error))
+    }
+
+    fn take(&self) -> PyResult<Option<zippy_data::ShardedWriter>> {
+        Ok(self
+            .writer
+            .write()
+            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?
+            .take())
+    }
+}
+
+#[pymethods]
+impl NativeShardedWriter {
+    /// Put a document.
+    fn put(&self, py: Python<'_>, doc_id: String, doc: &Bound<'_, PyDict>) -> PyResult<()> {
+        let value = py_to_json(doc.as_any())?;
+        self.with_writer(py, |writer| writer.put(doc_id, value).map_err(write_error))
+    }
+
+    /// Put `(doc_id, json_bytes)` tuples, as `NativeStore.put_raw_batch()`.
+    fn put_raw_batch(&self, py: Python<'_>, items: &Bound<'_, PyList>) -> PyResult<usize> {
+        let mut lines = Vec::with_capacity(items.len());
+        for item in items.iter() {
+            let (doc_id, line): (String, Bound<'_, PyAny>) = item.extract().map_err(|_| {
+                PyValueError::new_err("Expected list of (doc_id, json_bytes) tuples")
+            })?;
+            lines.push((doc_id, buffer_bytes(&line)?.into_owned()));
+        }
+        self.with_writer(py, |writer| {
+            for (doc_id, line) in &lines {
+                writer
+                    .put_raw_line(doc_id.as_str(), line)
+                    .map_err(write_error)?;
+            }
+            Ok(lines.len())
+        })
+    }
+
+    /// Make the written documents visible in the store. Returns how many
+    /// lines were merged.
+    fn merge(&self, py: Python<'_>) -> PyResult<usize> {
+        let Some(writer) = self.take()? else {
+            return Err(PyValueError::new_err("Sharded writer is finished"));
+        };
+        self.merge_writer(py, writer)
+    }
+
+    /// Drop the written documents.
+    fn discard(&self) -> PyResult<()> {
+        self.take()?;
+        Ok(())
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        let slot = self
+            .writer
+            .read()
+            .map_err(|e| PyValueError::new_err(format!("Lock error: {}", e)))?;
+        Ok(slot.as_ref().map_or(0, |writer| writer.len()))
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        // On error the taken writer is dropped, discarding its documents
+        if let (None, Some(writer)) = (exc_type, self.take()?) {
+            self.merge_writer(py, writer)?;
+        }
+        Ok(false)
+    }
+}
+
+/// Background writer returned by `NativeStore.async_writer()`.
+///
+/// A write that fails on the writer thread stops it; the error is raised
+/// by the next call.
+#[pyclass(frozen)]
+pub struct NativeAsyncWriter {
+    writer: AsyncWriter,
+}
+
+#[pymethods]
+impl NativeAsyncWriter {
+    /// Queue a document put.
+    fn put(&self, py: Python<'_>, doc_id: String, doc: &Bound<'_, PyDict>) -> PyResult<()> {
+        let value = py_to_json(doc.as_any())?;
+        py.allow_threads(|| self.writer.put(doc_id, value))
+            .map_err(async_write_error)
+    }
+
+    /// Queue a document delete.
+    fn delete(&self, py: Python<'_>, doc_id: String) -> PyResult<()> {
+        py.allow_threads(|| self.writer.delete(doc_id))
+            .map_err(async_write_error)
+    }
+
+    /// Wait until the writes queued so far are applied and flushed.
+    fn flush(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| self.writer.flush())
+            .map_err(async_write_error)
+    }
+
+    /// Apply and flush the queued writes and stop the writer thread.
+    /// Closing twice is a no-op.
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        py.allow_threads(|| self.writer.close())
+            .map_err(async_write_error)
+    }
+
+    /// Number of queued writes not yet applied.
+    #[getter]
+    fn pending(&self) -> usize {
+        self.writer.pending()
+    }
+
+    /// Whether the writer was closed.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.writer.is_closed()
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&self, py: Python<'_>, _args: &Bound<'_, PyTuple>) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+/// Convert an async writer error; a closed writer raises ValueError.
+fn async_write_error(e: zippy_data::Error) -> PyErr {
+    match e {
+        zippy_data::Error::Validation(_) => PyValueError::new_err(e.to_string()),
+        e => write_error(e),
     }
 }
 
 /// Iterator for scanning documents.
+///
+/// Streams documents from a snapshot of the store taken when the scan
+/// starts, decoding one batch at a time from the memory-mapped data file
+/// (GIL released). Documents deleted after the snapshot are skipped; newer
+/// writes are not visible.
 #[pyclass]
 pub struct ScanIterator {
-    scanner: std::vec::IntoIter<serde_json::Value>,
+    store: Py<NativeStore>,
+    scanner: FastScanner,
+    batch_size: usize,
+    fields: Option<Vec<String>>,
+    predicate: Option<Predicate>,
+    buffer: VecDeque<serde_json::Value>,
+}
+
+impl ScanIterator {
+    /// Refill the buffer with the next non-empty batch (if any).
+    fn fill(&mut self, py: Python<'_>) -> PyResult<()> {
+        while self.buffer.is_empty() {
+            let store = self.store.get();
+            let scanner = &mut self.scanner;
+            let batch_size = self.batch_size;
+            let fields = self.fields.as_deref();
+            let predicate = self.predicate.as_ref();
+            let docs = py.allow_threads(|| {
+                let store = store.read()?;
+                let field_refs: Option<Vec<&str>> =
+                    fields.map(|f| f.iter().map(|s| s.as_str()).collect());
+
+                let mut docs = Vec::with_capacity(batch_size);
+                for _ in 0..batch_size {
+                    let Some((doc_id, doc)) = scanner
+                        .next_entry()
+                        .map_err(|e| PyIOError::new_err(format!("Scan failed: {}", e)))?
+                    else {
+                        if docs.is_empty() {
+                            return Ok::<_, PyErr>(None);
+                        }
+                        break;
+                    };
+                    if !store.exists(&doc_id) || !matches(&doc, predicate) {
+                        continue;
+                    }
+                    let doc = match &field_refs {
+                        Some(refs) => Codec::extract_fields(&doc, refs).map_err(|e| {
+                            PyValueError::new_err(format!("Projection failed: {}", e))
+                        })?,
+                        None => doc,
+                    };
+                    docs.push(doc);
+                }
+                Ok(Some(docs))
+            })?;
+            match docs {
+                Some(docs) => self.buffer.extend(docs),
+                None => return Ok(()),
+            }
+        }
+        Ok(())
+    }
 }
 
 #[pymethods]
@@ -357,8 +1516,14 @@ impl ScanIterator {
         slf
     }
 
-    fn __next__(&mut self, py: Python<'_>) -> Option<PyObject> {
-        self.scanner.next().and_then(|v| json_to_py(py, &v).ok())
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        if self.buffer.is_empty() {
+            self.fill(py)?;
+        }
+        match self.buffer.pop_front() {
+            Some(doc) => Ok(Some(json_to_py(py, &doc)?)),
+            None => Ok(None),
+        }
     }
 }
 
@@ -389,18 +1554,7 @@ impl NativeRoot {
     #[staticmethod]
     #[pyo3(signature = (root, batch_size = 5000, mode = "rw"))]
     fn open(root: String, batch_size: usize, mode: &str) -> PyResult<Self> {
-        let open_mode = match mode {
-            "r" | "read" => OpenMode::Read,
-            "rw" | "read-write" | "readwrite" => OpenMode::ReadWrite,
-            _ => {
-                return Err(PyValueError::new_err(format!(
-                    "Invalid mode '{}'. Use 'r' for read-only or 'rw' for read-write",
-                    mode
-                )))
-            }
-        };
-
-        let zds_root = ZDSRoot::open(&root, batch_size, open_mode)
+        let zds_root = ZDSRoot::open(&root, batch_size, parse_mode(mode)?)
             .map_err(|e| PyIOError::new_err(format!("Failed to open root: {}", e)))?;
 
         Ok(NativeRoot { root: zds_root })
@@ -443,11 +1597,11 @@ impl NativeRoot {
         }
         .map_err(|e| PyIOError::new_err(format!("Failed to open collection: {}", e)))?;
 
-        Ok(NativeStore {
-            store: Mutex::new(store),
-            root: self.root.root_path().to_string_lossy().to_string(),
-            collection: name.to_string(),
-        })
+        Ok(NativeStore::new(
+            store,
+            self.root.root_path().to_string_lossy().to_string(),
+            name.to_string(),
+        ))
     }
 
     /// List all collections in this ZDS root.
@@ -484,12 +1638,99 @@ fn version() -> &'static str {
     zippy_data::ZDS_VERSION
 }
 
+/// Report pack/unpack progress to a Python callable, keeping the first
+/// error it raises (later reports are skipped).
+fn progress_callback<'a>(
+    progress: Option<&'a PyObject>,
+    error: &'a mut Option<PyErr>,
+) -> impl FnMut(&PackProgress) + 'a {
+    move |p| {
+        let Some(callback) = progress.filter(|_| error.is_none()) else {
+            return;
+        };
+        Python::with_gil(|py| {
+            let dict = PyDict::new_bound(py);
+            let result = dict
+                .set_item("path", &p.path)
+                .and_then(|_| dict.set_item("files_done", p.files_done))
+                .and_then(|_| dict.set_item("files_total", p.files_total))
+                .and_then(|_| dict.set_item("bytes_done", p.bytes_done))
+                .and_then(|_| dict.set_item("bytes_total", p.bytes_total))
+                .and_then(|_| callback.call1(py, (dict,)));
+            if let Err(e) = result {
+                *error = Some(e);
+            }
+        });
+    }
+}
+
+/// Pack a store folder into a .zds archive.
+///
+/// `compression` is "deflate" or "stored" and `level` the DEFLATE level
+/// (0-9). `progress`, if given, is called with a dict of `path`,
+/// `files_done`, `files_total`, `bytes_done` and `bytes_total` as files are
+/// copied.
+#[pyfunction]
+#[pyo3(signature = (source, dest, compression = "deflate", level = None, progress = None))]
+fn pack(
+    py: Python<'_>,
+    source: &str,
+    dest: &str,
+    compression: &str,
+    level: Option<i32>,
+    progress: Option<PyObject>,
+) -> PyResult<()> {
+    let compression = compression
+        .parse()
+        .map_err(|e: zippy_data::Error| PyValueError::new_err(e.to_string()))?;
+    if level.is_some_and(|level| !(0..=9).contains(&level)) {
+        return Err(PyValueError::new_err("level must be between 0 and 9"));
+    }
+    let options = PackOptions { compression, level };
+    let mut error = None;
+    let result = py.allow_threads(|| {
+        container::pack_with(
+            Path::new(source),
+            Path::new(dest),
+            &options,
+            progress_callback(progress.as_ref(), &mut error),
+        )
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result.map_err(|e| PyIOError::new_err(format!("Pack failed: {}", e)))
+}
+
+/// Unpack a .zds archive into a folder, calling `progress` like `pack`.
+#[pyfunction]
+#[pyo3(signature = (source, dest, progress = None))]
+fn unpack(py: Python<'_>, source: &str, dest: &str, progress: Option<PyObject>) -> PyResult<()> {
+    let mut error = None;
+    let result = py.allow_threads(|| {
+        container::unpack_with(
+            Path::new(source),
+            Path::new(dest),
+            progress_callback(progress.as_ref(), &mut error),
+        )
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result.map_err(|e| PyIOError::new_err(format!("Unpack failed: {}", e)))
+}
+
 /// Python module definition.
 #[pymodule]
 fn _zippy_data(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<NativeStore>()?;
     m.add_class::<NativeRoot>()?;
     m.add_class::<ScanIterator>()?;
+    m.add_class::<NativeShardedWriter>()?;
+    m.add_class::<NativeAsyncWriter>()?;
+    m.add_class::<Filter>()?;
     m.add_function(wrap_pyfunction!(version, m)?)?;
+    m.add_function(wrap_pyfunction!(pack, m)?)?;
+    m.add_function(wrap_pyfunction!(unpack, m)?)?;
     Ok(())
 }
@@ -0,0 +1,568 @@
+//! Read-only access to `.zds` archives held in memory.
+//!
+//! Mirrors the read path of `zippy_data` without its native dependencies
+//! (mmap, file locks, zstd), so it builds for `wasm32-unknown-unknown`.
+//! JSONL collections are read through their saved `index.bin`, like remote
+//! containers; file-per-document collections through `order.ids` and
+//! `docs/`. Compressed and encrypted collections can't be read.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{Cursor, Read},
+    rc::Rc,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Binary index magic ("ZDSI") and newest version, as written by
+/// `FastStore`.
+const INDEX_MAGIC: u32 = 0x5A445349;
+const INDEX_VERSION: u32 = 3;
+
+/// Bits of an index offset holding the offset within a segment.
+const SEGMENT_SHIFT: u32 = 40;
+
+#[derive(Error, Debug)]
+pub enum ReadError {
+    #[error("Archive error: {0}")]
+    Archive(String),
+
+    #[error("Collection not found: {0}")]
+    CollectionNotFound(String),
+
+    #[error("Document not found: {0}")]
+    DocumentNotFound(String),
+
+    #[error("Unsupported collection: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid container: {0}")]
+    InvalidContainer(String),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ReadError>;
+
+type Zip = zip::ZipArchive<Cursor<Vec<u8>>>;
+
+/// A `.zds` archive opened from its bytes.
+pub struct Archive {
+    zip: Rc<RefCell<Zip>>,
+}
+
+impl Archive {
+    /// Open an archive and read its file listing.
+    pub fn open(bytes: Vec<u8>) -> Result<Self> {
+        let zip = zip::ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| ReadError::Archive(format!("Failed to open archive: {}", e)))?;
+        Ok(Archive {
+            zip: Rc::new(RefCell::new(zip)),
+        })
+    }
+
+    /// Names of the collections in the archive, sorted.
+    pub fn collections(&self) -> Vec<String> {
+        let zip = self.zip.borrow();
+        let mut names: Vec<String> = zip
+            .file_names()
+            .filter_map(|name| {
+                let rest = name.strip_prefix("collections/")?;
+                let (collection, _) = rest.split_once('/')?;
+                (!collection.is_empty()).then(|| collection.to_string())
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Open a collection, loading its index. `now_ms` (Unix ms) hides
+    /// documents whose TTL has passed.
+    pub fn collection(&self, name: &str, now_ms: u64) -> Result<Collection> {
+        if !self.collections().iter().any(|c| c == name) {
+            return Err(ReadError::CollectionNotFound(name.to_string()));
+        }
+        let meta_dir = format!("collections/{}/meta/", name);
+        let read = |file: &str| read_entry(&self.zip, &format!("{}{}", meta_dir, file));
+
+        if let Some(manifest) = read("manifest.json")? {
+            let manifest: Value = serde_json::from_slice(&manifest)?;
+            if manifest.get("encryption").is_some() {
+                return Err(ReadError::Unsupported(format!(
+                    "collection '{}' is encrypted",
+                    name
+                )));
+            }
+        }
+
+        let segments: Vec<(String, u32, bool)> = self
+            .zip
+            .borrow()
+            .file_names()
+            .filter_map(|file| {
+                let (segment, compressed) = parse_data_segment(file.strip_prefix(&meta_dir)?)?;
+                Some((file.to_string(), segment, compressed))
+            })
+            .collect();
+        if segments.iter().any(|&(_, _, compressed)| compressed) {
+            return Err(ReadError::Unsupported(format!(
+                "collection '{}' has zstd-compressed data segments",
+                name
+            )));
+        }
+
+        let saved_order = read("order.ids")?
+            .map(|bytes| {
+                String::from_utf8_lossy(&bytes)
+                    .lines()
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if segments.is_empty() {
+            return Ok(Collection {
+                zip: self.zip.clone(),
+                name: name.to_string(),
+                ids: saved_order,
+                source: Source::Files,
+            });
+        }
+
+        let buf = read("index.bin")?.ok_or_else(|| {
+            ReadError::Unsupported(format!(
+                "collection '{}' has no saved index; flush the store before packing it",
+                name
+            ))
+        })?;
+        let (mut index, expires) = parse_index(&buf)?;
+        if let Some(content) = read("tombstones.jsonl")? {
+            for line in content.split(|&b| b == b'\n') {
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                let tombstone: Tombstone = serde_json::from_slice(line)?;
+                if index
+                    .get(&tombstone.id)
+                    .is_some_and(|e| e.offset < tombstone.offset)
+                {
+                    index.remove(&tombstone.id);
+                }
+            }
+        }
+        index.retain(|id, _| expires.get(id).map_or(true, |&t| t > now_ms));
+
+        // Saved insertion order first, then documents written after it in
+        // file order
+        let mut ids: Vec<String> = saved_order
+            .into_iter()
+            .filter(|id| index.contains_key(id))
+            .collect();
+        let listed: HashSet<&String> = ids.iter().collect();
+        let mut rest: Vec<(&String, u64)> = index
+            .iter()
+            .filter(|(id, _)| !listed.contains(id))
+            .map(|(id, e)| (id, e.offset))
+            .collect();
+        rest.sort_unstable_by_key(|&(_, offset)| offset);
+        let rest: Vec<String> = rest.into_iter().map(|(id, _)| id.clone()).collect();
+        ids.extend(rest);
+
+        let files = segments
+            .into_iter()
+            .map(|(file, segment, _)| (segment, file))
+            .collect();
+        Ok(Collection {
+            zip: self.zip.clone(),
+            name: name.to_string(),
+            ids,
+            source: Source::Jsonl {
+                index,
+                files,
+                loaded: RefCell::new(HashMap::new()),
+            },
+        })
+    }
+}
+
+/// Where a collection's documents are stored.
+enum Source {
+    /// One `docs/<id>.json` file per document
+    Files,
+    /// JSONL data segments, read whole on first use
+    Jsonl {
+        index: HashMap<String, IndexEntry>,
+        /// Segment number -> archive entry
+        files: HashMap<u32, String>,
+        loaded: RefCell<HashMap<u32, Rc<Vec<u8>>>>,
+    },
+}
+
+/// Location of a document's line (segment-qualified offset and length).
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    offset: u64,
+    length: u32,
+}
+
+/// A deletion record; it only applies to lines written before `offset`.
+#[derive(Deserialize)]
+struct Tombstone {
+    #[serde(rename = "_id")]
+    id: String,
+    offset: u64,
+}
+
+/// A collection of an [`Archive`].
+pub struct Collection {
+    zip: Rc<RefCell<Zip>>,
+    name: String,
+    /// Document IDs in insertion order
+    ids: Vec<String>,
+    source: Source,
+}
+
+impl Collection {
+    /// Collection name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Document IDs in insertion order.
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// Number of documents.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if the collection is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Storage mode: `"jsonl"` or `"files"`.
+    pub fn storage(&self) -> &'static str {
+        match self.source {
+            Source::Files => "files",
+            Source::Jsonl { .. } => "jsonl",
+        }
+    }
+
+    /// A document's stored JSON; for JSONL collections the line includes
+    /// `_id`.
+    pub fn raw(&self, doc_id: &str) -> Result<Vec<u8>> {
+        let not_found = || ReadError::DocumentNotFound(doc_id.to_string());
+        match &self.source {
+            Source::Files => {
+                // Hierarchical IDs are stored with '/' escaped, as `zippy_data`'s
+                // `Layout::doc_file_stem` does
+                let path = format!(
+                    "collections/{}/docs/{}.json",
+                    self.name,
+                    doc_id.replace('/', "%2F")
+                );
+                read_entry(&self.zip, &path)?.ok_or_else(not_found)
+            }
+            Source::Jsonl {
+                index,
+                files,
+                loaded,
+            } => {
+                let entry = index.get(doc_id).ok_or_else(not_found)?;
+                let segment = (entry.offset >> SEGMENT_SHIFT) as u32;
+                let offset = (entry.offset & ((1 << SEGMENT_SHIFT) - 1)) as usize;
+                let cached = loaded.borrow().get(&segment).cloned();
+                let data = match cached {
+                    Some(data) => data,
+                    None => {
+                        let file = files.get(&segment).ok_or_else(|| {
+                            ReadError::InvalidContainer(format!(
+                                "data segment {} of collection '{}' is missing",
+                                segment, self.name
+                            ))
+                        })?;
+                        let data = Rc::new(read_entry(&self.zip, file)?.unwrap_or_default());
+                        loaded.borrow_mut().insert(segment, data.clone());
+                        data
+                    }
+                };
+                let line = data
+                    .get(offset..offset + entry.length as usize)
+                    .ok_or_else(|| {
+                        ReadError::InvalidContainer(format!(
+                            "document '{}' points past the end of its data segment",
+                            doc_id
+                        ))
+                    })?;
+                Ok(line.strip_suffix(b"\n").unwrap_or(line).to_vec())
+            }
+        }
+    }
+
+    /// Get a document (without `_id`).
+    pub fn get(&self, doc_id: &str) -> Result<Value> {
+        let mut doc: Value = serde_json::from_slice(&self.raw(doc_id)?)?;
+        if let Value::Object(obj) = &mut doc {
+            obj.remove("_id");
+        }
+        Ok(doc)
+    }
+
+    /// Read `limit` documents (all by default) starting at position
+    /// `offset` in insertion order, each with its `_id`.
+    pub fn scan(&self, offset: usize, limit: Option<usize>) -> Result<Vec<Value>> {
+        let start = offset.min(self.ids.len());
+        let end = limit.map_or(self.ids.len(), |n| {
+            start.saturating_add(n).min(self.ids.len())
+        });
+        self.ids[start..end]
+            .iter()
+            .map(|doc_id| {
+                let mut doc = self.get(doc_id)?;
+                if let Value::Object(obj) = &mut doc {
+                    obj.insert("_id".to_string(), Value::String(doc_id.clone()));
+                }
+                Ok(doc)
+            })
+            .collect()
+    }
+}
+
+/// Read an archive entry, or `None` if the archive doesn't hold it.
+fn read_entry(zip: &RefCell<Zip>, path: &str) -> Result<Option<Vec<u8>>> {
+    let mut zip = zip.borrow_mut();
+    let mut entry = match zip.by_name(path) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => {
+            return Err(ReadError::Archive(format!(
+                "Failed to read {} from archive: {}",
+                path, e
+            )))
+        }
+    };
+    let mut buf = Vec::with_capacity(entry.size() as usize);
+    entry
+        .read_to_end(&mut buf)
+        .map_err(|e| ReadError::Archive(format!("Failed to read {}: {}", path, e)))?;
+    Ok(Some(buf))
+}
+
+/// Parse a data segment file name into `(segment, compressed)`:
+/// `data.jsonl` for segment 0, then `data.00001.jsonl`, ... (`.zst` when
+/// compressed).
+fn parse_data_segment(name: &str) -> Option<(u32, bool)> {
+    let (name, compressed) = match name.strip_suffix(".zst") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if name == "data.jsonl" {
+        return Some((0, compressed));
+    }
+    let digits = name.strip_prefix("data.")?.strip_suffix(".jsonl")?;
+    if digits.len() != 5 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, compressed))
+}
+
+/// Parse a binary index into document locations and expiry times.
+///
+/// Format: [magic:u32][version:u32][count:u64], then per document
+/// [id_len:u16, id_bytes, offset:u64, length:u32, checksum:u32]; version 3
+/// adds [expiry_count:u64] + [id_len:u16, id_bytes, expires_at:u64]...;
+/// a crc32 of everything before ends the file.
+#[allow(clippy::type_complexity)]
+fn parse_index(buf: &[u8]) -> Result<(HashMap<String, IndexEntry>, HashMap<String, u64>)> {
+    let invalid = || ReadError::InvalidContainer("Invalid index format".to_string());
+    if buf.len() < 20 {
+        return Err(invalid());
+    }
+    let (body, trailer) = buf.split_at(buf.len() - 4);
+    let u16_at = |pos: usize| -> Result<u16> {
+        Ok(u16::from_le_bytes(
+            body.get(pos..pos + 2)
+                .ok_or_else(invalid)?
+                .try_into()
+                .unwrap(),
+        ))
+    };
+    let u64_at = |pos: usize| -> Result<u64> {
+        Ok(u64::from_le_bytes(
+            body.get(pos..pos + 8)
+                .ok_or_else(invalid)?
+                .try_into()
+                .unwrap(),
+        ))
+    };
+
+    let magic = u32::from_le_bytes(body[0..4].try_into().unwrap());
+    let version = u32::from_le_bytes(body[4..8].try_into().unwrap());
+    if magic != INDEX_MAGIC || !(2..=INDEX_VERSION).contains(&version) {
+        return Err(invalid());
+    }
+    if crc32fast::hash(body) != u32::from_le_bytes(trailer.try_into().unwrap()) {
+        return Err(ReadError::InvalidContainer(
+            "Index checksum mismatch".to_string(),
+        ));
+    }
+
+    let mut index = HashMap::new();
+    let mut pos = 16;
+    for _ in 0..u64_at(8)? {
+        let id_len = u16_at(pos)? as usize;
+        pos += 2;
+        let record = body.get(pos..pos + id_len + 16).ok_or_else(invalid)?;
+        pos += id_len + 16;
+        let (id, record) = record.split_at(id_len);
+        index.insert(
+            String::from_utf8_lossy(id).into_owned(),
+            IndexEntry {
+                offset: u64::from_le_bytes(record[0..8].try_into().unwrap()),
+                length: u32::from_le_bytes(record[8..12].try_into().unwrap()),
+            },
+        );
+    }
+
+    let mut expires = HashMap::new();
+    if version >= 3 {
+        let count = u64_at(pos)?;
+        pos += 8;
+        for _ in 0..count {
+            let id_len = u16_at(pos)? as usize;
+            pos += 2;
+            let record = body.get(pos..pos + id_len + 8).ok_or_else(invalid)?;
+            pos += id_len + 8;
+            let (id, expires_at) = record.split_at(id_len);
+            expires.insert(
+                String::from_utf8_lossy(id).into_owned(),
+                u64::from_le_bytes(expires_at.try_into().unwrap()),
+            );
+        }
+    }
+    Ok((index, expires))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use serde_json::json;
+    use zip::write::FileOptions;
+
+    use super::*;
+
+    /// Binary index (version 3) over `(id, offset, length)` entries.
+    fn index_bin(entries: &[(&str, u64, u32)], expires: &[(&str, u64)]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(INDEX_MAGIC.to_le_bytes());
+        buf.extend(INDEX_VERSION.to_le_bytes());
+        buf.extend((entries.len() as u64).to_le_bytes());
+        for &(id, offset, length) in entries {
+            buf.extend((id.len() as u16).to_le_bytes());
+            buf.extend(id.as_bytes());
+            buf.extend(offset.to_le_bytes());
+            buf.extend(length.to_le_bytes());
+            buf.extend(0u32.to_le_bytes());
+        }
+        buf.extend((expires.len() as u64).to_le_bytes());
+        for &(id, at) in expires {
+            buf.extend((id.len() as u16).to_le_bytes());
+            buf.extend(id.as_bytes());
+            buf.extend(at.to_le_bytes());
+        }
+        buf.extend(crc32fast::hash(&buf).to_le_bytes());
+        buf
+    }
+
+    fn archive(files: &[(&str, &[u8])]) -> Archive {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        Archive::open(zip.finish().unwrap().into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_jsonl_collection() {
+        let data = b"{\"_id\":\"a\",\"v\":1}\n{\"_id\":\"b\",\"v\":2}\n{\"_id\":\"a\",\"v\":3}\n{\"_id\":\"c\",\"v\":4}\n{\"_id\":\"d\",\"v\":5}\n";
+        let index = index_bin(
+            &[("b", 18, 18), ("a", 36, 18), ("c", 54, 18), ("d", 72, 18)],
+            &[("d", 1_000)],
+        );
+        let archive = archive(&[
+            ("collections/train/meta/data.jsonl", data),
+            ("collections/train/meta/index.bin", &index),
+            (
+                "collections/train/meta/tombstones.jsonl",
+                b"{\"_id\":\"c\",\"offset\":90}\n",
+            ),
+            ("collections/train/meta/order.ids", b"a\nb\n"),
+        ]);
+        assert_eq!(archive.collections(), ["train"]);
+
+        let train = archive.collection("train", 2_000).unwrap();
+        assert_eq!(train.storage(), "jsonl");
+        // Deleted and expired documents are hidden
+        assert_eq!(train.ids(), ["a", "b"]);
+        assert_eq!(train.get("a").unwrap(), json!({"v": 3}));
+        assert!(matches!(
+            train.get("c"),
+            Err(ReadError::DocumentNotFound(_))
+        ));
+        assert_eq!(train.scan(1, None).unwrap(), [json!({"_id": "b", "v": 2})]);
+
+        // Before the TTL passes the document is visible, after the saved
+        // order
+        let train = archive.collection("train", 500).unwrap();
+        assert_eq!(train.ids(), ["a", "b", "d"]);
+        assert!(archive.collection("missing", 0).is_err());
+    }
+
+    #[test]
+    fn test_files_collection() {
+        let archive = archive(&[
+            ("collections/users/meta/order.ids", b"u2\nu1\n"),
+            ("collections/users/docs/u1.json", b"{\"name\":\"alice\"}"),
+            ("collections/users/docs/u2.json", b"{\"name\":\"bob\"}"),
+        ]);
+        let users = archive.collection("users", 0).unwrap();
+        assert_eq!(users.storage(), "files");
+        assert_eq!(users.len(), 2);
+        assert_eq!(
+            users.scan(0, Some(1)).unwrap(),
+            [json!({"_id": "u2", "name": "bob"})]
+        );
+        assert_eq!(users.get("u1").unwrap(), json!({"name": "alice"}));
+    }
+
+    #[test]
+    fn test_unsupported_collections() {
+        let archive = archive(&[
+            ("collections/zst/meta/data.jsonl.zst", b""),
+            ("collections/sealed/meta/data.jsonl", b""),
+            (
+                "collections/sealed/meta/manifest.json",
+                b"{\"encryption\":{\"algorithm\":\"aes-256-gcm\"}}",
+            ),
+            ("collections/unflushed/meta/data.jsonl", b""),
+        ]);
+        for name in ["zst", "sealed", "unflushed"] {
+            assert!(matches!(
+                archive.collection(name, 0),
+                Err(ReadError::Unsupported(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! WebAssembly bindings for ZDS (Zippy Data System)
+//!
+//! Read-only access to `.zds` archives from the browser: open an archive
+//! from its bytes or fetch it by URL, then list collections and read
+//! documents. Built with `wasm-pack build --target web`.
+//!
+//! ```javascript
+//! import init, { ZdsArchive } from './pkg/zippy_wasm.js';
+//!
+//! await init();
+//! const archive = await ZdsArchive.fetch('/data/train.zds');
+//! const train = archive.collection('train');
+//! console.log(train.length, train.scan(0, 10));
+//! ```
+
+mod archive;
+
+use js_sys::{Array, Promise, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+pub use archive::{Archive, Collection, ReadError, Result};
+
+fn to_js(e: ReadError) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+/// Convert a JSON document to a JS object.
+fn to_js_value(doc: &serde_json::Value) -> std::result::Result<JsValue, JsError> {
+    js_sys::JSON::parse(&doc.to_string()).map_err(|_| JsError::new("Failed to convert document"))
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// The global `fetch`, available in windows and workers.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_url(url: &str) -> Promise;
+
+    type Response;
+
+    #[wasm_bindgen(method, getter)]
+    fn ok(this: &Response) -> bool;
+
+    #[wasm_bindgen(method, getter)]
+    fn status(this: &Response) -> u16;
+
+    #[wasm_bindgen(method, js_name = arrayBuffer)]
+    fn array_buffer(this: &Response) -> Promise;
+}
+
+/// A read-only `.zds` archive.
+#[wasm_bindgen(js_name = ZdsArchive)]
+pub struct ZdsArchive {
+    archive: Archive,
+}
+
+#[wasm_bindgen(js_class = ZdsArchive)]
+impl ZdsArchive {
+    /// Open an archive from its bytes.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<ZdsArchive, JsError> {
+        let archive = Archive::open(bytes.to_vec()).map_err(to_js)?;
+        Ok(ZdsArchive { archive })
+    }
+
+    /// Download an archive with `fetch` and open it.
+    ///
+    /// The whole archive is downloaded; the server needs to allow the
+    /// page's origin (CORS) when it is another host.
+    pub async fn fetch(url: String) -> std::result::Result<ZdsArchive, JsError> {
+        let fetch_error = |e: JsValue| {
+            JsError::new(&format!(
+                "Failed to fetch {}: {}",
+                url,
+                e.as_string().unwrap_or_else(|| format!("{:?}", e))
+            ))
+        };
+        let response: Response = JsFuture::from(fetch_url(&url))
+            .await
+            .map_err(fetch_error)?
+            .unchecked_into();
+        if !response.ok() {
+            return Err(JsError::new(&format!(
+                "Failed to fetch {}: HTTP {}",
+                url,
+                response.status()
+            )));
+        }
+        let buffer = JsFuture::from(response.array_buffer())
+            .await
+            .map_err(fetch_error)?;
+        let archive = Archive::open(Uint8Array::new(&buffer).to_vec()).map_err(to_js)?;
+        Ok(ZdsArchive { archive })
+    }
+
+    /// Names of the collections in the archive, sorted.
+    pub fn collections(&self) -> Vec<String> {
+        self.archive.collections()
+    }
+
+    /// Open a collection.
+    pub fn collection(&self, name: &str) -> std::result::Result<ZdsCollection, JsError> {
+        let now = js_sys::Date::now() as u64;
+        let collection = self.archive.collection(name, now).map_err(to_js)?;
+        Ok(ZdsCollection { collection })
+    }
+}
+
+/// A collection of a [`ZdsArchive`].
+#[wasm_bindgen(js_name = ZdsCollection)]
+pub struct ZdsCollection {
+    collection: Collection,
+}
+
+#[wasm_bindgen(js_class = ZdsCollection)]
+impl ZdsCollection {
+    /// Collection name.
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.collection.name().to_string()
+    }
+
+    /// Number of documents.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.collection.len()
+    }
+
+    /// Storage mode: "jsonl" or "files".
+    #[wasm_bindgen(getter)]
+    pub fn storage(&self) -> String {
+        self.collection.storage().to_string()
+    }
+
+    /// Document IDs in insertion order.
+    #[wasm_bindgen(js_name = docIds)]
+    pub fn doc_ids(&self) -> Vec<String> {
+        self.collection.ids().to_vec()
+    }
+
+    /// Check if a document exists.
+    pub fn exists(&self, doc_id: &str) -> bool {
+        self.collection.raw(doc_id).is_ok()
+    }
+
+    /// Get a document by ID (without `_id`).
+    pub fn get(&self, doc_id: &str) -> std::result::Result<JsValue, JsError> {
+        to_js_value(&self.collection.get(doc_id).map_err(to_js)?)
+    }
+
+    /// Read `limit` documents (all by default) starting at position
+    /// `offset`, each with its `_id`.
+    pub fn scan(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> std::result::Result<Array, JsError> {
+        let docs = self
+            .collection
+            .scan(offset.unwrap_or(0), limit)
+            .map_err(to_js)?;
+        docs.iter().map(to_js_value).collect()
+    }
+}
@@ -4,10 +4,11 @@
  * Comprehensive tests for the ZdsStore class.
  */
 
-import { ZdsStore, ZdsRoot, BulkWriter, version } from '../index';
+import { ZdsStore, ZdsRoot, BulkWriter, version, pack, unpack } from '../index';
 import * as fs from 'fs';
 import * as path from 'path';
 import * as os from 'os';
+import { Readable } from 'stream';
 
 describe('ZdsStore', () => {
     let testDir: string;
@@ -108,6 +109,20 @@ describe('ZdsStore', () => {
             store.close();
         });
         
+        it('should get many documents at once', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            store.put('doc2', { value: 2 });
+            store.flush();
+
+            expect(store.getMany(['doc2', 'missing', 'doc1'])).toEqual([
+                { value: 2 },
+                null,
+                { value: 1 },
+            ]);
+            store.close();
+        });
+
         it('should handle nested objects', () => {
             const store = ZdsStore.open(testDir, 'test');
             const nested = {
@@ -180,6 +195,31 @@ describe('ZdsStore', () => {
             store.close();
         });
         
+        it('should filter documents', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1, name: 'alpha' });
+            store.put('doc2', { value: 2, name: 'beta' });
+            store.put('doc3', { value: 3, name: 'alpine' });
+            store.flush();
+            
+            expect(store.scan({ value: { $gte: 2 } }).length).toBe(2);
+            expect(store.scan({ name: { $startswith: 'al' } }).length).toBe(2);
+            expect(store.scan({ value: { $in: [1, 3] } }).length).toBe(2);
+            expect(store.scan("value > 1 AND name STARTSWITH 'al'").length).toBe(1);
+            store.close();
+        });
+
+        it('should project scanned documents', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1, name: 'alpha' });
+            store.put('doc2', { value: 2, name: 'beta' });
+            store.flush();
+
+            expect(store.scan({ value: { $gte: 2 } }, ['name'])).toEqual([{ name: 'beta' }]);
+            expect(store.scan(null, ['value'])).toEqual([{ value: 1 }, { value: 2 }]);
+            store.close();
+        });
+        
         it('should return empty array for empty store', () => {
             const store = ZdsStore.open(testDir, 'test');
             
@@ -189,6 +229,320 @@ describe('ZdsStore', () => {
         });
     });
     
+    describe('scanIter', () => {
+        it('should return documents in batches', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            for (let i = 0; i < 5; i++) {
+                store.put(`doc${i}`, { value: i });
+            }
+            store.flush();
+            
+            const scanner = store.scanIter();
+            expect(scanner.nextBatch(2).length).toBe(2);
+            expect(scanner.nextBatch(2).length).toBe(2);
+            expect(scanner.nextBatch(2).length).toBe(1);
+            expect(scanner.nextBatch(2)).toEqual([]);
+            store.close();
+        });
+    });
+    
+    describe('stream', () => {
+        it('should iterate documents asynchronously', async () => {
+            const store = ZdsStore.open(testDir, 'test');
+            for (let i = 0; i < 10; i++) {
+                store.put(`doc${i}`, { value: i, parity: i % 2 ? 'odd' : 'even' });
+            }
+            store.flush();
+
+            const values: number[] = [];
+            for await (const doc of store.stream({ batchSize: 3 })) {
+                values.push(doc.value);
+            }
+            expect(values).toEqual([0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+            const stream = store.stream({ filter: { parity: 'odd' }, fields: ['value'] });
+            store.delete('doc1');
+            const docs: any[] = [];
+            for await (const doc of stream) {
+                docs.push(doc);
+            }
+            expect(docs).toEqual([{ value: 3 }, { value: 5 }, { value: 7 }, { value: 9 }]);
+            store.close();
+        });
+
+        it('should pipe through a Readable', async () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            store.put('doc2', { value: 2 });
+            store.flush();
+
+            const docs: any[] = [];
+            for await (const doc of Readable.from(store.stream())) {
+                docs.push(doc);
+            }
+            expect(docs).toEqual([{ value: 1 }, { value: 2 }]);
+            expect(() => store.stream({ batchSize: 0 })).toThrow('batchSize must be positive');
+            store.close();
+        });
+    });
+
+    describe('scanRange', () => {
+        it('should page through documents in write order', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            for (let i = 0; i < 5; i++) {
+                store.put(`doc${i}`, { value: i });
+            }
+            store.flush();
+            
+            expect(store.scanRange(0, 2)).toEqual([{ value: 0 }, { value: 1 }]);
+            expect(store.scanRange(4, 2)).toEqual([{ value: 4 }]);
+            expect(store.listDocIds()).toEqual(['doc0', 'doc1', 'doc2', 'doc3', 'doc4']);
+            store.close();
+        });
+    });
+    
+    describe('sample', () => {
+        it('should return a reproducible subset', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            for (let i = 0; i < 20; i++) {
+                store.put(`doc${i}`, { value: i });
+            }
+            store.flush();
+            
+            const sample = store.sample(5, 7);
+            expect(sample).toHaveLength(5);
+            expect(store.sample(5, 7)).toEqual(sample);
+            expect(store.sample(50)).toHaveLength(20);
+            store.close();
+        });
+    });
+
+    describe('strict mode', () => {
+        it('should reject documents of another schema', () => {
+            const meta = path.join(testDir, 'collections', 'test', 'meta');
+            fs.mkdirSync(meta, { recursive: true });
+            fs.writeFileSync(path.join(meta, 'manifest.json'), '{"strict": true}');
+
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { name: 'alice', age: 30 });
+            expect(() => store.put('doc2', { name: 'bob' })).toThrow(/Schema mismatch/);
+            store.put('doc2', { name: 'bob', age: 25 });
+            expect(store.count).toBe(2);
+            store.close();
+        });
+    });
+    
+    describe('compact', () => {
+        it('should drop deleted documents from the data file', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            store.put('doc2', { value: 2 });
+            store.flush();
+            store.delete('doc1');
+            store.flush();
+            
+            expect(store.compactionStats.tombstones).toBe(1);
+            expect(store.compactionStats.deadRatio).toBeGreaterThan(0);
+            store.compact();
+            expect(store.compactionStats).toEqual({ deadBytes: 0, deadRatio: 0, tombstones: 0 });
+            expect(store.scan()).toEqual([{ value: 2 }]);
+            store.close();
+        });
+
+        it('should report storage stats and compact asynchronously', async () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            store.put('doc2', { value: 2 });
+            store.flush();
+            store.delete('doc1');
+            store.flush();
+
+            const stats = store.storageStats;
+            expect(stats.count).toBe(1);
+            expect(stats.liveBytes + stats.deadBytes).toBe(stats.dataBytes);
+            expect(stats.indexBytes).toBeGreaterThan(0);
+            expect(stats.indexMemoryBytes).toBeGreaterThan(0);
+            expect(stats.segments).toBe(1);
+            expect(stats.minOffset).toBe(stats.maxOffset);
+            expect(stats.lastFlush).toBeLessThanOrEqual(Date.now());
+
+            await store.compactAsync();
+            expect(store.storageStats.dataBytes).toBe(stats.liveBytes);
+            expect(await store.verifyAsync()).toEqual({ checked: 1, indexOk: true, corrupt: [] });
+            store.close();
+        });
+        
+        it('should keep deletions when the index is rebuilt', () => {
+            let store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            store.put('doc2', { value: 2 });
+            store.delete('doc1');
+            store.close();
+            fs.rmSync(path.join(testDir, 'collections', 'test', 'meta', 'index.bin'));
+            
+            store = ZdsStore.open(testDir, 'test');
+            expect(store.exists('doc1')).toBe(false);
+            expect(store.deletedIds()).toEqual(['doc1']);
+            store.close();
+        });
+    });
+    
+    describe('verify', () => {
+        it('should report lines that fail their checksum', () => {
+            let store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            store.put('doc2', { value: 2 });
+            expect(store.verify()).toEqual({ checked: 2, indexOk: true, corrupt: [] });
+            store.close();
+            
+            const dataFile = path.join(testDir, 'collections', 'test', 'meta', 'data.jsonl');
+            fs.writeFileSync(dataFile, fs.readFileSync(dataFile, 'utf8').replace('"value":2', '"value":9'));
+            store = ZdsStore.open(testDir, 'test');
+            const report = store.verify();
+            expect(report.corrupt.map(c => [c.id, c.kind])).toEqual([['doc2', 'checksum_mismatch']]);
+            store.close();
+        });
+    });
+    
+    describe('compression', () => {
+        it('should read back a zstd-compressed store', () => {
+            let store = ZdsStore.open(testDir, 'test');
+            store.setCompression('zstd');
+            store.put('doc1', { text: 'abc '.repeat(50) });
+            store.put('doc2', { value: 2 });
+            store.close();
+            expect(fs.existsSync(path.join(testDir, 'collections', 'test', 'meta', 'data.jsonl.zst'))).toBe(true);
+            
+            store = ZdsStore.open(testDir, 'test');
+            expect(store.compression).toBe('zstd');
+            expect(store.get('doc2')).toEqual({ value: 2 });
+            expect(store.scan()).toHaveLength(2);
+            expect(() => store.setCompression('lz4')).toThrow();
+            store.close();
+        });
+    });
+    
+    describe('asyncWriter', () => {
+        it('should apply queued writes on a background thread', async () => {
+            const store = ZdsStore.open(testDir, 'test');
+            const writer = store.asyncWriter({ queueSize: 4, maxBatch: 2 });
+            for (let i = 0; i < 20; i++) {
+                writer.put(`doc${i}`, { value: i });
+            }
+            writer.delete('doc0');
+            await writer.flush();
+            expect(writer.pending).toBe(0);
+            expect(store.count).toBe(19);
+            expect(store.get('doc19')).toEqual({ value: 19 });
+            
+            writer.delete('missing');
+            await expect(writer.flush()).rejects.toThrow(/missing/);
+            await writer.close();
+            expect(writer.closed).toBe(true);
+            expect(() => writer.put('late', {})).toThrow();
+            store.close();
+        });
+    });
+    
+    describe('durability', () => {
+        it('should sync every write with fsync_op', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            expect(store.durability).toBe('fsync_batch');
+            store.setDurability('fsync_op');
+            expect(store.durability).toBe('fsync_op');
+            store.put('doc1', { value: 1 });
+            expect(() => store.setDurability('always')).toThrow();
+            store.close();
+        });
+    });
+    
+    describe('putBatchWithPolicy', () => {
+        it('should resolve existing IDs with the policy', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { value: 1 });
+            const items = [
+                { id: 'doc1', doc: { value: 2 } },
+                { id: 'doc2', doc: { value: 2 } },
+            ];
+            expect(() => store.putBatchWithPolicy(items, 'fail_on_conflict')).toThrow();
+            expect(store.putBatchWithPolicy(items, 'skip_existing')).toBe(1);
+            store.flush();
+            expect(store.get('doc1')).toEqual({ value: 1 });
+            expect(store.putBatchWithPolicy(items)).toBe(2);
+            store.flush();
+            expect(store.get('doc1')).toEqual({ value: 2 });
+            store.close();
+        });
+    });
+    
+    describe('putAuto', () => {
+        it('should put documents under generated IDs', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            const ulid = store.putAuto({ value: 1 });
+            const uuid = store.putAuto({ value: 2 }, 'uuid');
+            const seq = store.putAuto({ value: 3 }, 'seq');
+            expect(ulid).toHaveLength(26);
+            expect(uuid).toMatch(/^[0-9a-f]{8}-[0-9a-f]{4}-4/);
+            expect(seq).toBe('1');
+            store.flush();
+            expect(store.get(seq)).toEqual({ value: 3 });
+            expect(() => store.putAuto({ value: 4 }, 'snowflake')).toThrow();
+            store.close();
+        });
+    });
+    
+    describe('inMemory', () => {
+        it('should keep a store in memory', () => {
+            const store = ZdsStore.inMemory('test');
+            expect(store.isInMemory).toBe(true);
+            store.put('doc1', { value: 1 });
+            store.flush();
+            expect(store.get('doc1')).toEqual({ value: 1 });
+            store.close();
+        });
+
+        it('should copy a collection into memory', () => {
+            const disk = ZdsStore.open(testDir, 'test');
+            disk.put('doc1', { value: 1 });
+            disk.flush();
+            const copy = ZdsStore.loadInMemory(testDir, 'test');
+            copy.put('doc2', { value: 2 });
+            expect(copy.count).toBe(2);
+            copy.close();
+            expect(disk.exists('doc2')).toBe(false);
+            disk.close();
+        });
+    });
+    
+    describe('patch', () => {
+        it('should merge a patch into a document', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('doc1', { name: 'widget', price: 10, tags: { sale: true } });
+            const patched = store.patch('doc1', { price: 12, tags: { sale: null } });
+            expect(patched).toEqual({ name: 'widget', price: 12, tags: {} });
+            store.flush();
+            expect(store.get('doc1')).toEqual(patched);
+            expect(() => store.patch('missing', { price: 1 })).toThrow();
+            store.close();
+        });
+    });
+    
+    describe('blobs', () => {
+        it('should store binary attachments next to a document', () => {
+            const store = ZdsStore.open(testDir, 'test');
+            store.put('img1', { label: 'cat' });
+            const pixels = Buffer.from([0, 255, 128, 7]);
+            store.putBlob('img1', 'image', pixels);
+            store.flush();
+            expect(store.getBlob('img1', 'image').equals(pixels)).toBe(true);
+            expect(store.blobNames('img1')).toEqual(['image']);
+            expect(() => store.putBlob('missing', 'image', pixels)).toThrow();
+            store.deleteBlob('img1', 'image');
+            expect(() => store.getBlob('img1', 'image')).toThrow();
+            store.close();
+        });
+    });
+    
     describe('listDocIds', () => {
         it('should return all document IDs', () => {
             const store = ZdsStore.open(testDir, 'test');
@@ -265,6 +619,29 @@ describe('BulkWriter', () => {
         
         expect(writer.count).toBe(25);
     });
+
+    it('should write raw lines, delete and close', async () => {
+        const writer = BulkWriter.create(testDir, 'bulk', 2);
+        writer.put('doc1', { value: 1 });
+        writer.putRawLine('doc2', Buffer.from(JSON.stringify({ _id: 'doc2', value: 2 })));
+        const lines = [
+            JSON.stringify({ _id: 'doc3', value: 3 }),
+            JSON.stringify({ _id: 'doc4', value: 4 }),
+        ].join('\n');
+        expect(writer.writeJsonl(Buffer.from(lines), ['doc3', 'doc4'])).toBe(2);
+        writer.delete('doc1');
+        await writer.flushAsync();
+        expect(writer.count).toBe(3);
+
+        writer.close();
+        writer.close();
+        expect(() => writer.put('doc5', {})).toThrow('BulkWriter is closed');
+
+        const store = ZdsStore.open(testDir, 'bulk');
+        expect(store.listDocIds()).toEqual(['doc2', 'doc3', 'doc4']);
+        expect(store.get('doc2')).toEqual({ value: 2 });
+        store.close();
+    });
 });
 
 describe('Raw JSONL Operations', () => {
@@ -329,6 +706,42 @@ describe('Raw JSONL Operations', () => {
     });
 });
 
+describe('Async Operations', () => {
+    let testDir: string;
+
+    beforeEach(() => {
+        testDir = fs.mkdtempSync(path.join(os.tmpdir(), 'zds-test-'));
+    });
+
+    afterEach(() => {
+        fs.rmSync(testDir, { recursive: true, force: true });
+    });
+
+    it('should read and write without blocking', async () => {
+        const store = ZdsStore.open(testDir, 'test');
+        const lines = [
+            JSON.stringify({ _id: 'doc1', value: 1 }),
+            JSON.stringify({ _id: 'doc2', value: 2 }),
+        ].join('\n');
+
+        expect(await store.writeJsonlAsync(Buffer.from(lines), ['doc1', 'doc2'])).toBe(2);
+        store.flush();
+
+        expect(await store.getAsync('doc2')).toEqual({ value: 2 });
+        expect(await store.scanAsync({ value: { $gte: 2 } }, ['value'])).toEqual([{ value: 2 }]);
+        expect((await store.scanRawAsync()).length).toBe(2);
+        expect((await store.readJsonlBlobAsync()).length).toBeGreaterThan(0);
+        store.close();
+    });
+
+    it('should reject on errors', async () => {
+        const store = ZdsStore.open(testDir, 'test');
+        await expect(store.getAsync('missing')).rejects.toThrow('Document not found');
+        await expect(store.scanAsync('value >')).rejects.toThrow('Invalid filter');
+        store.close();
+    });
+});
+
 describe('Persistence', () => {
     let testDir: string;
     
@@ -566,3 +979,36 @@ describe('ZdsRoot vs ZdsStore.open compatibility', () => {
         train.close();
     });
 });
+
+describe('pack/unpack', () => {
+    let testDir: string;
+
+    beforeEach(() => {
+        testDir = fs.mkdtempSync(path.join(os.tmpdir(), 'zds-test-'));
+    });
+
+    afterEach(() => {
+        fs.rmSync(testDir, { recursive: true, force: true });
+    });
+
+    it('should round-trip a store and report progress', () => {
+        const source = path.join(testDir, 'source');
+        const store = ZdsStore.open(source, 'train');
+        store.put('doc1', { text: 'hello' });
+        store.close();
+
+        const archive = path.join(testDir, 'data.zds');
+        const reports: any[] = [];
+        pack(source, archive, { compression: 'stored' }, (p) => reports.push(p));
+        const last = reports[reports.length - 1];
+        expect(last.filesDone).toBe(last.filesTotal);
+        expect(last.bytesDone).toBe(last.bytesTotal);
+        expect(() => pack(source, archive, { level: 12 })).toThrow();
+
+        const dest = path.join(testDir, 'dest');
+        unpack(archive, dest);
+        const unpacked = ZdsStore.open(dest, 'train');
+        expect(unpacked.get('doc1')).toEqual({ text: 'hello' });
+        unpacked.close();
+    });
+});
@@ -3,14 +3,96 @@
 
 /* auto-generated by NAPI-RS */
 
+/** Data file garbage statistics. */
+export interface CompactionStats {
+  deadBytes: number
+  deadRatio: number
+  tombstones: number
+}
+/** Storage statistics from `storageStats`. */
+export interface StorageStats {
+  count: number
+  /** Logical (uncompressed) size of the data segments */
+  dataBytes: number
+  liveBytes: number
+  deadBytes: number
+  deadRatio: number
+  /** Size of the document index file */
+  indexBytes: number
+  /** Memory held by the in-memory document index */
+  indexMemoryBytes: number
+  /** Offset of the first live line (segment-qualified) */
+  minOffset?: number
+  /** Offset of the last live line (segment-qualified) */
+  maxOffset?: number
+  /** Time of the last flush in ms since the epoch */
+  lastFlush?: number
+  segments: number
+  tombstones: number
+  compression: string
+}
+/** A document for `putBatchWithPolicy()`. */
+export interface BatchItem {
+  id: string
+  doc: any
+}
+/** A damaged document line found by `verify()`. */
+export interface CorruptEntry {
+  id: string
+  /** Data segment holding the line (0 for `data.jsonl`) */
+  segment: number
+  /** Offset of the line within its segment */
+  offset: number
+  /** One of `truncated`, `checksum_mismatch`, `invalid_json`, `id_mismatch` */
+  kind: string
+}
+/** Corruption report from `verify()`. */
+export interface VerifyReport {
+  checked: number
+  indexOk: boolean
+  corrupt: Array<CorruptEntry>
+}
 /** Store information. */
 export interface StoreInfo {
   root: string
   collection: string
   count: number
 }
+/** Options for `ZdsStore.stream()`. */
+export interface StreamOptions {
+  /** Documents fetched from Rust per batch (default 1000) */
+  batchSize?: number
+  /** Fields to project (dot notation allowed) */
+  fields?: Array<string>
+  /** Filter descriptor or expression, as for `scan()` */
+  filter?: any
+}
+/** Options for `ZdsStore.asyncWriter()`. */
+export interface AsyncWriterOptions {
+  /** Writes queued before `put` and `delete` block (default 10000) */
+  queueSize?: number
+  /** Most writes applied under one hold of the store's lock (default 1000) */
+  maxBatch?: number
+  /** Flush interval in milliseconds (default 1000) */
+  flushIntervalMs?: number
+}
 /** Get the ZDS version. */
 export declare function version(): string
+/** Options for `pack()`. */
+export interface PackOptions {
+  /** `deflate` (default) or `stored` */
+  compression?: string
+  /** DEFLATE level (0-9) */
+  level?: number
+}
+/**
+ * Pack a store folder into a .zds archive. `progress`, if given, is called
+ * with `{path, filesDone, filesTotal, bytesDone, bytesTotal}` as files are
+ * copied.
+ */
+export declare function pack(source: string, dest: string, options?: PackOptions | undefined | null, progress?: (...args: any[]) => any | undefined | null): void
+/** Unpack a .zds archive into a folder, calling `progress` like `pack()`. */
+export declare function unpack(source: string, dest: string, progress?: (...args: any[]) => any | undefined | null): void
 /** Root information. */
 export interface RootInfo {
   root: string
@@ -24,14 +106,98 @@ export type ZDSStore = ZdsStore
 export declare class ZdsStore {
   /** Open a ZDS store. */
   static open(root: string, collection?: string | undefined | null, batchSize?: number | undefined | null): ZdsStore
+  /**
+   * Create an empty store kept in memory and deleted when closed, for
+   * tests and caches.
+   */
+  static inMemory(collection?: string | undefined | null, batchSize?: number | undefined | null): ZdsStore
+  /**
+   * Copy a collection into memory, pinning a hot dataset in RAM. Writes
+   * go to the copy only.
+   */
+  static loadInMemory(root: string, collection?: string | undefined | null, batchSize?: number | undefined | null): ZdsStore
+  /** Whether the store is kept in memory. */
+  get isInMemory(): boolean
   /** Get document by ID. */
   get(docId: string): any
+  /** Get document by ID without blocking the event loop. */
+  getAsync(docId: string): Promise<any>
+  /**
+   * Get several documents at once, in the order of `doc_ids`, with `null`
+   * for missing ones. The lines are read in one pass and parsed in
+   * parallel.
+   */
+  getMany(docIds: Array<string>): Array<any | undefined | null>
   /** Put a document. */
   put(docId: string, doc: any): void
+  /**
+   * Put a document under a generated ID and return the ID. `strategy`
+   * is `ulid` (the default, sortable by creation time), `uuid` or `seq`
+   * (a per-collection counter).
+   */
+  putAuto(doc: any, strategy?: string | undefined | null): string
+  /**
+   * Put a batch of documents, resolving existing IDs with `policy`
+   * (`overwrite`, `skip_existing` or `fail_on_conflict`). Returns the
+   * number of documents written.
+   */
+  putBatchWithPolicy(items: Array<BatchItem>, policy?: string | undefined | null): number
+  /**
+   * Apply a JSON merge patch (RFC 7386) to a document and return the
+   * patched document. `null` values remove fields.
+   */
+  patch(docId: string, patch: any): any
   /** Delete a document. */
   delete(docId: string): void
+  /**
+   * Attach binary data (an image, audio clip, ...) to a document under
+   * `name`, replacing any blob of that name.
+   */
+  putBlob(docId: string, name: string, data: Buffer): void
+  /** Read a document's blob. */
+  getBlob(docId: string, name: string): Buffer
+  /** Names of a document's blobs, sorted. */
+  blobNames(docId: string): Array<string>
+  /** Remove a document's blob. */
+  deleteBlob(docId: string, name: string): void
   /** Flush pending writes and refresh mmap. */
   flush(): void
+  /** Rewrite the data file without deleted and overwritten documents. */
+  compact(): void
+  /** Compact on the thread pool, without blocking the event loop. */
+  compactAsync(): Promise<void>
+  /**
+   * Compact automatically on flush/close once `minDeadRatio` of the data
+   * file is garbage. Call without arguments to disable.
+   */
+  setAutoCompact(minDeadRatio?: number | undefined | null): void
+  /**
+   * When writes are synced: "none", "flush", "fsync_batch" (once per
+   * flush, the default) or "fsync_op" (after every write).
+   */
+  get durability(): string
+  /** Set when writes are synced (see `durability`). */
+  setDurability(durability: string): void
+  /** Garbage statistics for the data file. */
+  get compactionStats(): CompactionStats
+  /** Storage statistics: data, live, dead and index sizes. */
+  get storageStats(): StorageStats
+  /** IDs deleted since the last compaction, sorted. */
+  deletedIds(): Array<string>
+  /** Data file compression ("none" or "zstd"). */
+  get compression(): string
+  /**
+   * Set the data file compression; applies on the next compact() unless
+   * the store is empty.
+   */
+  setCompression(compression: string): void
+  /** Check the data file against its checksums. */
+  verify(): VerifyReport
+  /**
+   * Check the data file on the thread pool, without blocking the event
+   * loop.
+   */
+  verifyAsync(): Promise<VerifyReport>
   /** Close the store and flush pending writes. */
   close(): void
   /**
@@ -40,32 +206,162 @@ export declare class ZdsStore {
    * doc_ids: List of document IDs in order matching the lines.
    */
   writeJsonl(jsonlData: Buffer, docIds: Array<string>): number
+  /**
+   * Write a complete JSONL blob on the thread pool, without blocking the
+   * event loop.
+   */
+  writeJsonlAsync(jsonlData: Buffer, docIds: Array<string>): Promise<number>
   /** Scan and return raw JSON bytes (fastest read path). */
   scanRaw(): Array<Buffer>
+  /**
+   * Scan raw JSON bytes on the thread pool, without blocking the event
+   * loop.
+   */
+  scanRawAsync(): Promise<Array<Buffer>>
   /**
    * Read entire JSONL file as a single buffer (fastest bulk read).
    * Returns the raw JSONL content - caller splits and parses.
    */
   readJsonlBlob(): Buffer
+  /**
+   * Read entire JSONL file as a single buffer on the thread pool, without
+   * blocking the event loop.
+   */
+  readJsonlBlobAsync(): Promise<Buffer>
   /** Get document count. */
   get count(): number
   /** Check if document exists. */
   exists(docId: string): boolean
-  /** Scan all documents. */
-  scan(): Array<any>
-  /** List all document IDs. */
+  /**
+   * Scan all documents, optionally keeping only those matching `filter`.
+   *
+   * `filter` uses the same descriptor as Python, e.g.
+   * `{ category: "A", age: { $gte: 18 } }`, or an expression string like
+   * `"category = 'A' AND age >= 18"`, and is evaluated in Rust.
+   */
+  scan(filter?: any | undefined | null, fields?: Array<string> | undefined | null): Array<any>
+  /**
+   * Like `scan`, but runs on the thread pool so scanning a large
+   * collection doesn't block the event loop. Invalid filters reject the
+   * promise.
+   */
+  scanAsync(filter?: any | undefined | null, fields?: Array<string> | undefined | null): Promise<Array<any>>
+  /**
+   * Lazily iterate over all documents in file order.
+   *
+   * Call `nextBatch()` on the returned scanner until it returns an empty
+   * array; only one batch is decoded at a time.
+   */
+  scanIter(): ZdsScanner
+  /**
+   * Start a background writer for this store.
+   *
+   * Its puts and deletes are queued and applied on a thread of their
+   * own, which also flushes every `flushIntervalMs`, so the event loop
+   * never waits for a flush. Once `queueSize` writes are queued, further
+   * ones block until the thread catches up.
+   */
+  asyncWriter(options?: AsyncWriterOptions | undefined | null): ZdsAsyncWriter
+  /**
+   * Stream documents as an async iterable, pulling `batchSize` documents
+   * at a time from Rust on the thread pool.
+   *
+   * Only one batch is held in memory, so large collections can be piped
+   * with `for await` or `Readable.from(store.stream())` without
+   * materializing them. `fields` projects and `filter` (see `scan`)
+   * selects documents in Rust. The scan sees the data as of this call;
+   * documents deleted later are skipped.
+   */
+  stream(options?: StreamOptions | undefined | null): ZdsStream
+  /**
+   * Read `limit` documents starting at position `offset`, in the order
+   * they were last written (stable across calls and reopens).
+   */
+  scanRange(offset: number, limit: number): Array<any>
+  /**
+   * Read a reproducible random sample of `n` documents (all of them if
+   * the store holds fewer), in file order. The same `seed` gives the same
+   * sample while the store is unchanged.
+   */
+  sample(n: number, seed?: number | undefined | null): Array<any>
+  /** List all document IDs in file order. */
   listDocIds(): Array<string>
   /** Get store info. */
   get info(): StoreInfo
 }
+export type ZDSScanner = ZdsScanner
+/** Lazy document scanner returned by `ZdsStore.scanIter()`. */
+export declare class ZdsScanner {
+  /**
+   * Get the next batch of up to `size` documents (default 1000).
+   * Returns an empty array when the scan is exhausted.
+   */
+  nextBatch(size?: number | undefined | null): Array<any>
+  /** Upper bound on the number of remaining documents. */
+  get remaining(): number
+}
+export type ZDSStream = ZdsStream
+/** Async iterable document stream returned by `ZdsStore.stream()`. */
+export declare class ZdsStream {
+  /**
+   * Fetch the next batch of documents on the thread pool. Resolves to an
+   * empty array when the stream is exhausted.
+   */
+  nextBatch(): Promise<Array<any>>
+  [Symbol.asyncIterator](): AsyncIterator<any>
+}
+export type ZDSAsyncWriter = ZdsAsyncWriter
+/**
+ * Background writer returned by `ZdsStore.asyncWriter()`.
+ *
+ * A write that fails on the writer thread stops it; the error is thrown
+ * by the next call.
+ */
+export declare class ZdsAsyncWriter {
+  /** Queue a document put. */
+  put(docId: string, doc: any): void
+  /** Queue a document delete. */
+  delete(docId: string): void
+  /** Resolve once the writes queued so far are applied and flushed. */
+  flush(): Promise<void>
+  /**
+   * Apply and flush the queued writes and stop the writer thread.
+   * Closing twice is a no-op.
+   */
+  close(): Promise<void>
+  /** Number of queued writes not yet applied. */
+  get pending(): number
+  /** Whether the writer was closed. */
+  get closed(): boolean
+}
 /** Bulk write helper for high-throughput ingestion. */
 export declare class BulkWriter {
   /** Create a new bulk writer. */
   static create(root: string, collection?: string | undefined | null, batchSize?: number | undefined | null): BulkWriter
   /** Put a document. */
   put(docId: string, doc: any): void
+  /**
+   * Put a document as a raw JSON line (fastest path - no parsing).
+   * The line must be valid JSON with the `_id` field included.
+   */
+  putRawLine(docId: string, line: Buffer): void
+  /** Write a complete JSONL blob, see `ZdsStore.writeJsonl()`. */
+  writeJsonl(jsonlData: Buffer, docIds: Array<string>): number
+  /** Delete a document. */
+  delete(docId: string): void
   /** Flush pending writes. */
   flush(): void
+  /**
+   * Flush pending writes on the thread pool, without blocking the event
+   * loop. Await it before writing again: a write issued while the flush
+   * runs waits for it to finish.
+   */
+  flushAsync(): Promise<void>
+  /**
+   * Flush pending writes, write the index and release the store. Further
+   * calls fail; closing twice is a no-op.
+   */
+  close(): void
   /** Get current document count. */
   get count(): number
 }
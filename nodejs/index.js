@@ -310,9 +310,12 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { ZdsStore, version, BulkWriter, ZdsRoot } = nativeBinding
+const { ZdsStore, ZdsScanner, version, pack, unpack, BulkWriter, ZdsRoot } = nativeBinding
 
 module.exports.ZdsStore = ZdsStore
+module.exports.ZdsScanner = ZdsScanner
 module.exports.version = version
+module.exports.pack = pack
+module.exports.unpack = unpack
 module.exports.BulkWriter = BulkWriter
 module.exports.ZdsRoot = ZdsRoot
@@ -9,26 +9,159 @@ export interface StoreInfo {
   count: number;
 }
 
+export interface CompactionStats {
+  deadBytes: number;
+  deadRatio: number;
+  tombstones: number;
+}
+
+export interface StorageStats {
+  count: number;
+  dataBytes: number;
+  liveBytes: number;
+  deadBytes: number;
+  deadRatio: number;
+  indexBytes: number;
+  indexMemoryBytes: number;
+  minOffset?: number;
+  maxOffset?: number;
+  lastFlush?: number;
+  segments: number;
+  tombstones: number;
+  compression: string;
+}
+
+export interface BatchItem {
+  id: string;
+  doc: unknown;
+}
+
+export interface CorruptEntry {
+  id: string;
+  segment: number;
+  offset: number;
+  kind: string;
+}
+
+export interface VerifyReport {
+  checked: number;
+  indexOk: boolean;
+  corrupt: CorruptEntry[];
+}
+
 export class ZDSStore {
   static open(root: string, collection?: string, batchSize?: number): ZDSStore;
+  static inMemory(collection?: string, batchSize?: number): ZDSStore;
+  static loadInMemory(root: string, collection?: string, batchSize?: number): ZDSStore;
+  get isInMemory(): boolean;
   get(docId: string): unknown;
+  getAsync(docId: string): Promise<unknown>;
+  getMany(docIds: string[]): (unknown | null)[];
   put(docId: string, doc: unknown): void;
+  putAuto(doc: unknown, strategy?: 'ulid' | 'uuid' | 'seq'): string;
+  putBatchWithPolicy(items: BatchItem[], policy?: 'overwrite' | 'skip_existing' | 'fail_on_conflict'): number;
+  patch(docId: string, patch: unknown): unknown;
   delete(docId: string): void;
+  putBlob(docId: string, name: string, data: Buffer): void;
+  getBlob(docId: string, name: string): Buffer;
+  blobNames(docId: string): string[];
+  deleteBlob(docId: string, name: string): void;
   exists(docId: string): boolean;
   flush(): void;
+  compact(): void;
+  compactAsync(): Promise<void>;
+  setAutoCompact(minDeadRatio?: number): void;
+  get durability(): string;
+  setDurability(durability: 'none' | 'flush' | 'fsync_batch' | 'fsync_op'): void;
+  get compactionStats(): CompactionStats;
+  get storageStats(): StorageStats;
+  deletedIds(): string[];
+  get compression(): string;
+  setCompression(compression: string): void;
+  verify(): VerifyReport;
+  verifyAsync(): Promise<VerifyReport>;
   close(): void;
-  scan(): unknown[];
+  scan(filter?: unknown, fields?: string[]): unknown[];
+  scanAsync(filter?: unknown, fields?: string[]): Promise<unknown[]>;
+  scanRaw(): Buffer[];
+  scanRawAsync(): Promise<Buffer[]>;
+  writeJsonl(jsonlData: Buffer, docIds: string[]): number;
+  writeJsonlAsync(jsonlData: Buffer, docIds: string[]): Promise<number>;
+  readJsonlBlob(): Buffer;
+  readJsonlBlobAsync(): Promise<Buffer>;
+  scanIter(): ZDSScanner;
+  stream(options?: StreamOptions): ZDSStream;
+  asyncWriter(options?: AsyncWriterOptions): ZDSAsyncWriter;
+  scanRange(offset: number, limit: number): unknown[];
+  sample(n: number, seed?: number): unknown[];
   listDocIds(): string[];
   getAt(index: number): unknown;
   get count(): number;
   get info(): StoreInfo;
 }
 
+export class ZDSScanner {
+  nextBatch(size?: number): unknown[];
+  get remaining(): number;
+}
+
+export interface StreamOptions {
+  batchSize?: number;
+  fields?: string[];
+  filter?: unknown;
+}
+
+export class ZDSStream implements AsyncIterable<unknown> {
+  nextBatch(): Promise<unknown[]>;
+  [Symbol.asyncIterator](): AsyncIterator<unknown>;
+}
+
+export interface AsyncWriterOptions {
+  queueSize?: number;
+  maxBatch?: number;
+  flushIntervalMs?: number;
+}
+
+export class ZDSAsyncWriter {
+  put(docId: string, doc: unknown): void;
+  delete(docId: string): void;
+  flush(): Promise<void>;
+  close(): Promise<void>;
+  get pending(): number;
+  get closed(): boolean;
+}
+
 export class BulkWriter {
   static create(root: string, collection?: string, batchSize?: number): BulkWriter;
   put(docId: string, doc: unknown): void;
+  putRawLine(docId: string, line: Buffer): void;
+  writeJsonl(jsonlData: Buffer, docIds: string[]): number;
+  delete(docId: string): void;
   flush(): void;
+  flushAsync(): Promise<void>;
+  close(): void;
   get count(): number;
 }
 
 export function version(): string;
+
+export interface PackOptions {
+  compression?: 'deflate' | 'stored';
+  level?: number;
+}
+
+export interface PackProgress {
+  path: string;
+  filesDone: number;
+  filesTotal: number;
+  bytesDone: number;
+  bytesTotal: number;
+}
+
+export function pack(
+  source: string,
+  dest: string,
+  options?: PackOptions,
+  progress?: (progress: PackProgress) => void,
+): void;
+export function unpack(source: string, dest: string, progress?: (progress: PackProgress) => void): void;
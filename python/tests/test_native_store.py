@@ -0,0 +1,657 @@
+"""Tests for the native (Rust) NativeStore bindings."""
+
+import asyncio
+import os
+import tempfile
+import time
+
+import pytest
+
+from zippy import Filter, NativeStore, pack, unpack
+
+pytestmark = pytest.mark.skipif(NativeStore is None, reason="Native backend not available")
+
+
+class TestNativeStoreAio:
+    """Test the asyncio facade (NativeStore.aio)."""
+
+    def test_put_batch_and_scan(self):
+        """Test awaiting batch writes and scans."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+
+            async def run():
+                written = await store.aio.put_batch([("a", {"x": 1}), ("b", {"x": 2})])
+                await store.aio.flush()
+                docs = await store.aio.scan()
+                raw = await store.aio.scan_raw()
+                return written, docs, raw
+
+            written, docs, raw = asyncio.run(run())
+            assert written == 2
+            assert sorted(d["x"] for d in docs) == [1, 2]
+            assert len(raw) == 2
+
+    def test_get_batch(self):
+        """Test fetching a batch of documents in one call."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"i": i}) for i in range(10)])
+            store.flush()
+
+            docs = store.get_batch(["d7", "missing", "d2"])
+            assert docs == [{"i": 7}, None, {"i": 2}]
+            assert asyncio.run(store.aio.get_batch(["d1"])) == [{"i": 1}]
+
+    def test_write_jsonl(self):
+        """Test awaiting a raw JSONL blob write."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            blob = b'{"_id":"a","v":1}\n{"_id":"b","v":2}\n'
+
+            async def run():
+                count = await store.aio.write_jsonl(blob, ["a", "b"])
+                await store.aio.flush()
+                return count, await store.aio.get("b")
+
+            count, doc = asyncio.run(run())
+            assert count == 2
+            assert doc["v"] == 2
+
+
+class TestNativeStoreBuffers:
+    """Test buffer-protocol batch reads and writes."""
+
+    def test_scan_raw_buffer(self):
+        """Test slicing documents out of one contiguous buffer."""
+        pytest.importorskip("numpy")
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"i": i}) for i in range(5)])
+            store.flush()
+
+            data, offsets = store.scan_raw_buffer()
+            assert offsets.dtype == "int64"
+            assert len(offsets) == 6 and offsets[0] == 0
+            view = memoryview(data)
+            lines = [bytes(view[offsets[i] : offsets[i + 1]]) for i in range(5)]
+            assert lines == store.scan_raw()
+
+            data, offsets = store.scan_raw_buffer(filter={"i": 3})
+            assert data[offsets[0] : offsets[1]] == b'{"_id":"d3","i":3}'
+
+    def test_buffer_writes(self):
+        """Test writing raw lines from memoryview and bytearray inputs."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            blob = bytearray(b'{"_id":"a","v":1}\n{"_id":"b","v":2}\n')
+            view = memoryview(blob)
+            assert store.put_raw_batch([("a", view[:17]), ("b", view[18:35])]) == 2
+            assert store.write_jsonl(view, ["a", "b"]) == 2
+            assert store.write_jsonl(bytearray(b'{"_id":"c","v":3}\n'), ["c"]) == 1
+            store.flush()
+            assert [store.get(k)["v"] for k in "abc"] == [1, 2, 3]
+
+            with pytest.raises(ValueError):
+                store.put_raw_batch([("x", "not bytes")])
+
+    def test_put_auto(self):
+        """Test putting documents under generated IDs."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            ulid = store.put_auto({"v": 1})
+            uuid = store.put_auto({"v": 2}, "uuid")
+            seq = asyncio.run(store.aio.put_auto({"v": 3}, "seq"))
+            store.flush()
+            assert len(ulid) == 26 and len(uuid) == 36 and seq == "1"
+            assert [store.get(k)["v"] for k in (ulid, uuid, seq)] == [1, 2, 3]
+
+            with pytest.raises(ValueError):
+                store.put_auto({"v": 4}, "snowflake")
+
+    def test_in_memory(self):
+        """Test stores kept in memory."""
+        store = NativeStore.in_memory("train")
+        assert store.is_in_memory
+        store.put("a", {"v": 1})
+        store.flush()
+        assert store.get("a") == {"v": 1}
+        store.close()
+
+        with tempfile.TemporaryDirectory() as tmp:
+            disk = NativeStore.open(tmp, "train")
+            disk.put("a", {"v": 1})
+            disk.flush()
+            copy = NativeStore.load_in_memory(tmp, "train")
+            copy.put("b", {"v": 2})
+            copy.flush()
+            assert copy.count() == 2
+            copy.close()
+            disk.close()
+            assert NativeStore.open(tmp, "train").count() == 1
+
+    def test_sharded_writer(self):
+        """Test parallel raw writes through a sharded writer."""
+        from concurrent.futures import ThreadPoolExecutor
+
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            batches = [
+                [(f"d{i}", b'{"_id":"d%d","v":%d}' % (i, i)) for i in range(j, j + 100)]
+                for j in range(0, 800, 100)
+            ]
+            with store.sharded_writer(4) as writer:
+                with ThreadPoolExecutor(4) as pool:
+                    assert sum(pool.map(writer.put_raw_batch, batches)) == 800
+                writer.put("extra", {"v": -1})
+                with pytest.raises(OSError):
+                    store.put("x", {})
+                assert len(writer) == 801
+            assert store.count() == 801
+            assert store.get("d123") == {"v": 123}
+
+            try:
+                with store.sharded_writer(2) as writer:
+                    writer.put("lost", {})
+                    raise RuntimeError("abort")
+            except RuntimeError:
+                pass
+            assert not store.exists("lost")
+            store.put("x", {})
+
+
+class TestNativeStoreLifecycle:
+    """Test closing stores and read-only opens."""
+
+    def test_context_manager(self):
+        """Test that leaving the block flushes and closes the store."""
+        with tempfile.TemporaryDirectory() as tmp:
+            with NativeStore.open(tmp, "train") as store:
+                store.put("a", {"x": 1})
+                assert not store.closed
+            assert store.closed
+            assert "closed" in repr(store)
+            with pytest.raises(ValueError, match="closed store"):
+                store.get("a")
+            store.close()
+
+            reopened = NativeStore.open(tmp, "train")
+            assert reopened.get("a") == {"x": 1}
+            asyncio.run(reopened.aio.close())
+            assert reopened.closed
+
+    def test_read_only_open(self):
+        """Test opening an existing collection read-only."""
+        with tempfile.TemporaryDirectory() as tmp:
+            with NativeStore.open(tmp, "train") as store:
+                store.put("a", {"x": 1})
+
+            reader = NativeStore.open(tmp, "train", mode="r")
+            assert reader.get("a") == {"x": 1}
+            with pytest.raises(IOError):
+                reader.put("b", {"x": 2})
+            with pytest.raises(IOError):
+                NativeStore.open(tmp, "missing", mode="r")
+            with pytest.raises(ValueError):
+                NativeStore.open(tmp, "train", mode="x")
+
+
+class TestNativeStoreScanIter:
+    """Test lazy batched iteration (NativeStore.scan_iter)."""
+
+    def test_scan_iter_batches(self):
+        """Test iterating across several batches."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"doc{i}", {"i": i, "extra": "x"}) for i in range(25)])
+            store.flush()
+
+            values = sorted(doc["i"] for doc in store.scan_iter(batch_size=10))
+            assert values == list(range(25))
+            assert len(list(store)) == 25
+
+    def test_scan_iter_projection(self):
+        """Test field projection inside the iterator."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put("a", {"i": 1, "extra": "x"})
+            store.flush()
+
+            docs = list(store.scan_iter(fields=["i"]))
+            assert docs == [{"i": 1}]
+
+    def test_scan_range_paging(self):
+        """Test deterministic offset/limit paging in write order."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"i": i}) for i in range(7)])
+            store.flush()
+
+            pages = [store.scan_range(offset, 3) for offset in range(0, 7, 3)]
+            assert [[d["i"] for d in page] for page in pages] == [[0, 1, 2], [3, 4, 5], [6]]
+            assert store.list_doc_ids() == [f"d{i}" for i in range(7)]
+            assert store.scan_range(10, 3) == []
+
+    def test_sample(self):
+        """Test reproducible random samples."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"i": i}) for i in range(50)])
+            store.flush()
+
+            sample = store.sample(5, seed=3)
+            assert len(sample) == 5
+            assert len({d["i"] for d in sample}) == 5
+            assert store.sample(5, seed=3) == sample
+            assert len(store.sample(100)) == 50
+
+    def test_strict_schema(self):
+        """Test that strict collections reject documents of another schema."""
+        with tempfile.TemporaryDirectory() as tmp:
+            meta = os.path.join(tmp, "collections", "train", "meta")
+            os.makedirs(meta)
+            with open(os.path.join(meta, "manifest.json"), "w") as f:
+                f.write('{"strict": true}')
+
+            store = NativeStore.open(tmp, "train")
+            store.put("a", {"text": "hello", "label": 1})
+            with pytest.raises(ValueError, match="Schema mismatch"):
+                store.put("b", {"text": "hello"})
+            with pytest.raises(ValueError, match="Schema mismatch"):
+                store.write_jsonl(b'{"_id":"c","label":"x","text":"y"}\n', ["c"])
+            store.put_batch([("b", {"text": "bye", "label": 0})])
+            assert store.count() == 2
+
+    def test_json_schema(self):
+        """Test that writes are validated against an attached JSON Schema."""
+        with tempfile.TemporaryDirectory() as tmp:
+            meta = os.path.join(tmp, "collections", "train", "meta")
+            os.makedirs(meta)
+            with open(os.path.join(meta, "schema.json"), "w") as f:
+                f.write('{"properties": {"label": {"enum": [0, 1]}}}')
+
+            store = NativeStore.open(tmp, "train")
+            store.put("a", {"label": 1})
+            with pytest.raises(ValueError, match="Schema violation: /label"):
+                store.put("b", {"label": 2})
+            assert store.count() == 1
+
+    def test_auto_compaction(self):
+        """Test garbage statistics and automatic compaction on flush."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"i": i}) for i in range(10)])
+            store.flush()
+            for i in range(4):
+                store.delete(f"d{i}")
+            store.flush()
+
+            stats = store.compaction_stats()
+            assert stats["tombstones"] == 4
+            assert stats["dead_ratio"] > 0.3
+
+            store.set_auto_compact(0.3, min_dead_bytes=0)
+            store.put("d9", {"i": 9})
+            store.flush()
+            assert store.compaction_stats() == {"dead_bytes": 0, "dead_ratio": 0.0, "tombstones": 0}
+            assert [d["i"] for d in store.scan()] == [4, 5, 6, 7, 8, 9]
+
+    def test_async_writer(self):
+        """Test writes applied by a background writer thread."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            with store.async_writer(queue_size=4, max_batch=2) as writer:
+                for i in range(20):
+                    writer.put(f"d{i}", {"i": i})
+                writer.delete("d0")
+                writer.flush()
+                assert writer.pending == 0
+                assert store.count() == 19
+                writer.put("d20", {"i": 20})
+            assert writer.closed
+            assert store.get("d20") == {"i": 20}
+            with pytest.raises(ValueError):
+                writer.put("late", {})
+
+            writer = store.async_writer()
+            writer.delete("missing")
+            with pytest.raises(OSError, match="missing"):
+                writer.flush()
+            writer.close()
+            store.close()
+
+    def test_durability(self):
+        """Test choosing when writes are synced."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            assert store.durability == "fsync_batch"
+            store.set_durability("none")
+            assert store.durability == "none"
+            store.put("d1", {"i": 1})
+            store.flush()
+            with pytest.raises(ValueError):
+                store.set_durability("always")
+            store.close()
+
+    def test_storage_stats(self):
+        """Test storage statistics around a compaction."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"i": i}) for i in range(4)])
+            store.flush()
+            store.delete("d0")
+            store.flush()
+
+            stats = store.storage_stats()
+            assert stats["count"] == 3
+            assert stats["live_bytes"] + stats["dead_bytes"] == stats["data_bytes"]
+            assert stats["index_bytes"] > 0
+            assert stats["index_memory_bytes"] > 0
+            assert stats["segments"] == 1
+            assert stats["compression"] == "none"
+            assert 0 < stats["min_offset"] < stats["max_offset"]
+            assert stats["last_flush"] <= time.time() * 1000
+
+            store.compact()
+            assert store.storage_stats()["data_bytes"] == stats["live_bytes"]
+            assert asyncio.run(store.aio.verify())["corrupt"] == []
+
+    def test_deletes_survive_index_rebuild(self):
+        """Test that deletions persist when the binary index is rebuilt."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([("a", {"i": 1}), ("b", {"i": 2})])
+            store.delete("a")
+            store.flush()
+            del store
+            os.remove(os.path.join(tmp, "collections", "train", "meta", "index.bin"))
+
+            store = NativeStore.open(tmp, "train")
+            assert not store.exists("a")
+            assert store.deleted_ids() == ["a"]
+            assert [d["i"] for d in store.scan()] == [2]
+
+    def test_verify_checksums(self):
+        """Test that verify reports lines that fail their checksum."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([("a", {"i": 1}), ("b", {"i": 2})])
+            assert store.verify() == {"checked": 2, "index_ok": True, "corrupt": []}
+            del store
+
+            data_file = os.path.join(tmp, "collections", "train", "meta", "data.jsonl")
+            with open(data_file) as f:
+                data = f.read()
+            with open(data_file, "w") as f:
+                f.write(data.replace('"i":1', '"i":5'))
+
+            report = NativeStore.open(tmp, "train").verify()
+            assert [(c["_id"], c["kind"]) for c in report["corrupt"]] == [("a", "checksum_mismatch")]
+
+    def test_zstd_compression(self):
+        """Test that a zstd-compressed store reads back like a plain one."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.set_compression("zstd")
+            store.put_batch([(f"d{i}", {"i": i, "text": "abc " * 50}) for i in range(100)])
+            store.flush()
+            assert store.compression == "zstd"
+            assert store.get("d42")["i"] == 42
+            assert len(store.scan()) == 100
+            del store
+
+            meta = os.path.join(tmp, "collections", "train", "meta")
+            assert os.path.exists(os.path.join(meta, "data.jsonl.zst"))
+            assert not os.path.exists(os.path.join(meta, "data.jsonl"))
+            assert NativeStore.open(tmp, "train").read_jsonl_blob().count(b"\n") == 100
+
+    def test_put_batch_with_policy(self):
+        """Test that batch puts resolve existing IDs with the policy."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put("a", {"v": 1})
+            items = [("a", {"v": 2}), ("b", {"v": 2})]
+            with pytest.raises(ValueError):
+                store.put_batch_with_policy(items, "fail_on_conflict")
+            assert len(store) == 1
+            assert store.put_batch_with_policy(items, "skip_existing") == 1
+            store.flush()
+            assert store.get("a") == {"v": 1}
+            assert store.put_batch_with_policy(items) == 2
+            store.flush()
+            assert store.get("a") == {"v": 2}
+            with pytest.raises(ValueError):
+                store.put_batch_with_policy(items, "ignore")
+
+    def test_patch(self):
+        """Test that patch merges into a document and None removes fields."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put("a", {"name": "alice", "meta": {"age": 30, "city": "Oslo"}})
+            patched = store.patch("a", {"meta": {"city": None}, "tags": ["x"]})
+            assert patched == {"name": "alice", "meta": {"age": 30}, "tags": ["x"]}
+            store.flush()
+            assert store.get("a") == patched
+            with pytest.raises(KeyError):
+                store.patch("missing", {"name": "bob"})
+
+    def test_blobs(self):
+        """Test that blobs are stored next to documents and dropped with them."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put("img1", {"label": "cat"})
+            store.put_blob("img1", "image", b"\x89PNG\x00\x01")
+            store.put_blob("img1", "audio", b"RIFF")
+            store.flush()
+            assert store.get_blob("img1", "image") == b"\x89PNG\x00\x01"
+            assert store.blob_names("img1") == ["audio", "image"]
+            with pytest.raises(KeyError):
+                store.put_blob("missing", "image", b"")
+            store.delete("img1")
+            with pytest.raises(KeyError):
+                store.get_blob("img1", "audio")
+
+    def test_vectors(self):
+        """Test storing embeddings and nearest-neighbor search."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            for doc_id, vec in [("a", [1.0, 0.0]), ("b", [0.0, 1.0]), ("c", [0.7, 0.7])]:
+                store.put(doc_id, {"id": doc_id})
+                store.put_vector("emb", doc_id, vec)
+            assert store.vector_indexes() == ["emb"]
+            assert store.get_vector("emb", "b") == [0.0, 1.0]
+            hits = store.knn("emb", [1.0, 0.1], k=2)
+            assert [doc_id for doc_id, _ in hits] == ["a", "c"]
+            assert store.knn("emb", [1.0, 0.1], k=1, metric="dot")[0][0] == "a"
+            with pytest.raises(KeyError):
+                store.put_vector("emb", "missing", [1.0, 0.0])
+            with pytest.raises(ValueError):
+                store.put_vector("emb", "a", [1.0])
+            with pytest.raises(ValueError):
+                store.knn("emb", [1.0, 0.0], metric="l2")
+
+    def test_scan_iter_skips_deleted(self):
+        """Test that documents deleted mid-iteration are skipped."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([("a", {"i": 1}), ("b", {"i": 2})])
+            store.flush()
+
+            it = store.scan_iter(batch_size=1)
+            first = next(it)
+            store.delete("b" if first["i"] == 1 else "a")
+            assert list(it) == []
+
+
+class TestNativeStoreFilter:
+    """Test predicate filtering evaluated in Rust."""
+
+    def _store(self, tmp):
+        store = NativeStore.open(tmp, "train")
+        store.put_batch([
+            ("a", {"cat": "A", "meta": {"lang": "en"}, "label": 1}),
+            ("b", {"cat": "B", "meta": {"lang": "fr"}}),
+            ("c", {"cat": "A", "meta": {"lang": "fr"}}),
+        ])
+        store.flush()
+        return store
+
+    def test_scan_dict_filter(self):
+        """Test dict descriptors on scan and scan_raw."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = self._store(tmp)
+
+            assert len(store.scan(filter={"cat": "A"})) == 2
+            assert len(store.scan(filter={"cat": "A", "meta.lang": "fr"})) == 1
+            assert len(store.scan(filter={"label": {"$exists": True}})) == 1
+            assert len(store.scan(filter={"$or": [{"cat": "B"}, {"label": 1}]})) == 2
+            assert len(store.scan_raw(filter={"meta.lang": "fr"})) == 2
+
+    def test_scan_fields(self):
+        """Test projecting scanned documents onto fields."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = self._store(tmp)
+
+            docs = store.scan(filter={"cat": "A"}, fields=["meta.lang", "label"])
+            assert docs == [{"lang": "en", "label": 1}, {"lang": "fr"}]
+            assert store.scan(fields=["cat"]) == [{"cat": "A"}, {"cat": "B"}, {"cat": "A"}]
+
+    def test_filter_objects(self):
+        """Test Filter expressions and their use in scan_iter."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = self._store(tmp)
+
+            f = Filter.eq("cat", "A") & Filter.not_exists("label")
+            assert [d["meta"]["lang"] for d in store.scan(filter=f)] == ["fr"]
+            f = Filter.eq("cat", "B") | Filter.exists("label")
+            assert len(list(store.scan_iter(batch_size=1, filter=f))) == 2
+
+    def test_comparison_filters(self):
+        """Test range, membership and string filters."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"n": i, "name": f"item{i}", "tags": ["x"] if i % 2 else []}) for i in range(10)])
+            store.flush()
+
+            assert len(store.scan(filter={"n": {"$gte": 3, "$lt": 6}})) == 3
+            assert len(store.scan(filter=Filter.gt("n", 7))) == 2
+            assert len(store.scan(filter=Filter.isin("n", [1, 2, 42]))) == 2
+            assert len(store.scan(filter=Filter.startswith("name", "item1"))) == 1
+            assert len(store.scan(filter=Filter.contains("tags", "x"))) == 5
+            assert len(store.scan(filter=Filter.lte("n", 1) | Filter.gte("n", 9))) == 3
+
+    def test_string_filter(self):
+        """Test filter expressions given as strings."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([(f"d{i}", {"n": i, "cat": "A" if i < 3 else "B"}) for i in range(6)])
+            store.flush()
+
+            assert len(store.scan(filter="n >= 2 AND cat = 'A'")) == 1
+            assert len(store.scan(filter="cat = 'B' OR n IN (0, 1)")) == 5
+            assert len(store.scan(filter=Filter.parse("n < 2") | Filter.eq("n", 5))) == 3
+
+    def test_indexed_filter(self):
+        """Test that indexed equality filters agree with full scans."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = self._store(tmp)
+
+            assert store.create_index("meta.lang") == 3
+            assert store.indexes() == ["meta.lang"]
+            store.put("d", {"cat": "B", "meta": {"lang": "fr"}})
+            store.delete("c")
+            store.flush()
+
+            docs = store.scan(filter={"meta.lang": "fr"})
+            assert sorted(d["cat"] for d in docs) == ["B", "B"]
+            store.drop_index("meta.lang")
+            assert store.indexes() == []
+
+    def test_text_search(self):
+        """Test full-text indexes and search."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put("a", {"body": "The quick brown fox"})
+            store.put("b", {"body": "A lazy brown dog"})
+            store.flush()
+
+            assert store.create_text_index("body") == 2
+            store.put("c", {"body": "Quick thinking"})
+            store.flush()
+            assert store.text_indexes() == ["body"]
+            assert len(store.search("QUICK")) == 2
+            assert [d["body"] for d in store.search("brown fox")] == ["The quick brown fox"]
+            assert len(store.scan(filter="body MATCH 'brown'")) == 2
+            assert len(store.scan(filter=Filter.text_match("body", "dog"))) == 1
+
+            store.drop_text_index("body")
+            with pytest.raises(ValueError):
+                store.search("fox")
+
+    def test_invalid_filter(self):
+        """Test that malformed filters are rejected."""
+        with tempfile.TemporaryDirectory() as tmp:
+            store = self._store(tmp)
+
+            with pytest.raises(ValueError):
+                store.scan(filter={"cat": {"$regex": "A.*"}})
+            with pytest.raises(ValueError):
+                store.scan(filter="cat == A")
+            with pytest.raises(ValueError):
+                store.scan(filter=42)
+
+
+class TestNativeStoreArrow:
+    """Test Arrow export (NativeStore.scan_arrow)."""
+
+    def test_scan_arrow_columns(self):
+        """Test typed columns, projection and filtering."""
+        pa = pytest.importorskip("pyarrow")
+        with tempfile.TemporaryDirectory() as tmp:
+            store = NativeStore.open(tmp, "train")
+            store.put_batch([
+                ("a", {"text": "hello", "n": 1, "score": 0.5, "meta": {"lang": "en"}}),
+                ("b", {"text": "world", "n": 2, "score": 1}),
+            ])
+            store.flush()
+
+            batch = store.scan_arrow()
+            assert batch.num_rows == 2
+            assert batch.schema.field("n").type == pa.int64()
+            assert batch.schema.field("score").type == pa.float64()
+            assert batch.schema.field("text").type == pa.string()
+
+            batch = store.scan_arrow(fields=["meta.lang", "n"], filter={"text": "hello"})
+            assert batch.num_rows == 1
+            assert batch.column(0).to_pylist() == ["en"]
+            assert batch.column(1).to_pylist() == [1]
+
+
+class TestArchives:
+    """Test pack/unpack of .zds archives."""
+
+    def test_pack_unpack_progress(self):
+        """Test compression options and progress reports."""
+        with tempfile.TemporaryDirectory() as tmp:
+            source = os.path.join(tmp, "source")
+            store = NativeStore.open(source, "train")
+            store.put("a", {"text": "hello"})
+            store.flush()
+
+            archive = os.path.join(tmp, "data.zds")
+            reports = []
+            pack(source, archive, compression="stored", progress=reports.append)
+            assert reports[-1]["files_done"] == reports[-1]["files_total"]
+            assert reports[-1]["bytes_done"] == reports[-1]["bytes_total"]
+            with pytest.raises(ValueError):
+                pack(source, archive, compression="bzip2")
+
+            dest = os.path.join(tmp, "dest")
+            unpack(archive, dest)
+            assert NativeStore.open(dest, "train").get("a") == {"text": "hello"}
+
+            def fail(_):
+                raise RuntimeError("stop")
+
+            with pytest.raises(RuntimeError):
+                unpack(archive, os.path.join(tmp, "other"), progress=fail)
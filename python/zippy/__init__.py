@@ -39,11 +39,14 @@ __author__ = "Omar Kamali"
 # Try to import native bindings
 _HAS_NATIVE = False
 try:
-    from ._zippy_data import NativeStore, NativeRoot, version as native_version
+    from ._zippy_data import NativeStore, NativeRoot, Filter, pack, unpack, version as native_version
     _HAS_NATIVE = True
 except ImportError:
     NativeStore = None
     NativeRoot = None
+    Filter = None
+    pack = None
+    unpack = None
     native_version = None
 
 from .store import ZDSStore
@@ -95,6 +98,9 @@ __all__ = [
     "compute_schema_id",
     "validate_doc_id",
     "list_collections",
+    # Archives (native bindings)
+    "pack",
+    "unpack",
     # DuckDB
     "query_zds",
     "register_zds",
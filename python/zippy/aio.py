@@ -0,0 +1,127 @@
+"""Asyncio wrappers for the native store.
+
+The native ``put_batch``, ``write_jsonl``, ``scan`` and ``scan_raw`` methods
+release the GIL while doing disk I/O and parsing, so running them in a worker
+thread lets asyncio apps and threaded loaders make progress concurrently.
+
+Example:
+    >>> store = NativeStore.open("./data", "train")
+    >>> docs = await store.aio.scan()
+    >>> await store.aio.put_batch([("doc1", {"text": "hello"})])
+"""
+
+import asyncio
+from typing import Any, Dict, List, Optional, Tuple
+
+
+class AsyncNativeStore:
+    """Awaitable facade over a ``NativeStore``.
+
+    Obtain one via ``NativeStore.aio``. All calls are forwarded to the wrapped
+    store in a worker thread via ``asyncio.to_thread``.
+    """
+
+    __slots__ = ("_store",)
+
+    def __init__(self, store: Any):
+        self._store = store
+
+    @property
+    def store(self) -> Any:
+        """The wrapped synchronous store."""
+        return self._store
+
+    async def get(self, doc_id: str) -> Dict[str, Any]:
+        """Get document by ID."""
+        return await asyncio.to_thread(self._store.get, doc_id)
+
+    async def get_batch(self, doc_ids: List[str]) -> List[Optional[Dict[str, Any]]]:
+        """Get several documents, with ``None`` for missing ones."""
+        return await asyncio.to_thread(self._store.get_batch, doc_ids)
+
+    async def put(self, doc_id: str, doc: Dict[str, Any]) -> None:
+        """Put a document."""
+        await asyncio.to_thread(self._store.put, doc_id, doc)
+
+    async def put_auto(self, doc: Dict[str, Any], strategy: str = "ulid") -> str:
+        """Put a document under a generated ID; returns the ID."""
+        return await asyncio.to_thread(self._store.put_auto, doc, strategy)
+
+    async def put_batch(self, items: List[Tuple[str, Dict[str, Any]]]) -> int:
+        """Put multiple ``(doc_id, doc)`` tuples; returns the count written."""
+        return await asyncio.to_thread(self._store.put_batch, items)
+
+    async def write_jsonl(self, jsonl_blob: bytes, doc_ids: List[str]) -> int:
+        """Write a pre-serialized JSONL blob; returns the count written."""
+        return await asyncio.to_thread(self._store.write_jsonl, jsonl_blob, doc_ids)
+
+    async def delete(self, doc_id: str) -> None:
+        """Delete a document."""
+        await asyncio.to_thread(self._store.delete, doc_id)
+
+    async def put_blob(self, doc_id: str, name: str, data: bytes) -> None:
+        """Attach binary data to a document under ``name``."""
+        await asyncio.to_thread(self._store.put_blob, doc_id, name, data)
+
+    async def get_blob(self, doc_id: str, name: str) -> bytes:
+        """Read a document's blob."""
+        return await asyncio.to_thread(self._store.get_blob, doc_id, name)
+
+    async def knn(
+        self, name: str, query: List[float], k: int = 10, metric: str = "cosine"
+    ) -> List[Tuple[str, float]]:
+        """Find the ``k`` documents nearest to ``query`` in a vector index."""
+        return await asyncio.to_thread(self._store.knn, name, query, k, metric)
+
+    async def flush(self) -> None:
+        """Flush pending writes."""
+        await asyncio.to_thread(self._store.flush)
+
+    async def close(self) -> None:
+        """Flush pending writes and close the store."""
+        await asyncio.to_thread(self._store.close)
+
+    async def scan(
+        self, filter: Any = None, fields: Optional[List[str]] = None
+    ) -> List[Dict[str, Any]]:
+        """Scan all documents, optionally filtered and projected."""
+        return await asyncio.to_thread(self._store.scan, filter, fields)
+
+    async def scan_raw(self, filter: Any = None) -> List[bytes]:
+        """Scan all documents as raw JSON bytes."""
+        return await asyncio.to_thread(self._store.scan_raw, filter)
+
+    async def scan_raw_buffer(self, filter: Any = None) -> Tuple[bytes, Any]:
+        """Scan raw JSON lines into one buffer plus a NumPy offsets array."""
+        return await asyncio.to_thread(self._store.scan_raw_buffer, filter)
+
+    async def scan_arrow(self, fields: Optional[List[str]] = None, filter: Any = None) -> Any:
+        """Scan into a ``pyarrow.RecordBatch``."""
+        return await asyncio.to_thread(self._store.scan_arrow, fields, filter)
+
+    async def scan_range(self, offset: int, limit: int) -> List[Dict[str, Any]]:
+        """Read ``limit`` documents starting at position ``offset``."""
+        return await asyncio.to_thread(self._store.scan_range, offset, limit)
+
+    async def sample(self, n: int, seed: int = 0) -> List[Dict[str, Any]]:
+        """Read a reproducible random sample of ``n`` documents."""
+        return await asyncio.to_thread(self._store.sample, n, seed)
+
+    async def search(self, query: str) -> List[Dict[str, Any]]:
+        """Full-text search over text-indexed fields."""
+        return await asyncio.to_thread(self._store.search, query)
+
+    async def compact(self) -> None:
+        """Rewrite the data file without deleted and overwritten documents."""
+        await asyncio.to_thread(self._store.compact)
+
+    async def verify(self) -> Dict[str, Any]:
+        """Check the data against its checksums."""
+        return await asyncio.to_thread(self._store.verify)
+
+    async def count(self) -> int:
+        """Get document count."""
+        return await asyncio.to_thread(self._store.count)
+
+    def __repr__(self) -> str:
+        return f"AsyncNativeStore({self._store!r})"
@@ -0,0 +1,128 @@
+//! Development tasks, run with `cargo xtask <task>`.
+
+use std::{
+    env,
+    path::Path,
+    process::{exit, Command},
+};
+
+const USAGE: &str = "\
+Usage: cargo xtask <task>
+
+Tasks:
+  bench-regression [options]   Scan regression suite on a synthetic collection
+      --size-mb <N>            Size of the generated data (default 256)
+      --runs <N>               Runs per measurement, median kept (default 3)
+      --dir <PATH>             Generate into PATH and reuse it on later runs
+      --out <FILE>             Write the JSON results to FILE
+      --baseline <FILE>        Fail on regressions against earlier results
+      --threshold <F>          Slowdown that counts as a regression (default 0.2)";
+
+/// Options of `bench-regression` and the variables the bench reads them from.
+const BENCH_OPTIONS: &[(&str, &str)] = &[
+    ("--size-mb", "ZDS_BENCH_SIZE_MB"),
+    ("--runs", "ZDS_BENCH_RUNS"),
+    ("--dir", "ZDS_BENCH_DIR"),
+    ("--out", "ZDS_BENCH_OUT"),
+    ("--baseline", "ZDS_BENCH_BASELINE"),
+    ("--threshold", "ZDS_BENCH_THRESHOLD"),
+];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("bench-regression") => bench_regression(&args[1..]),
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(task) => Err(format!("unknown task '{}'", task)),
+        None => Err("no task given".to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}\n\n{}", e, USAGE);
+        exit(2);
+    }
+}
+
+fn bench_regression(args: &[String]) -> Result<(), String> {
+    let mut command = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()));
+    command.args(["bench", "-p", "zippy_data", "--bench", "regression"]);
+    let cwd = env::current_dir().map_err(|e| e.to_string())?;
+    command.envs(bench_env(args, &cwd)?);
+
+    let status = command
+        .status()
+        .map_err(|e| format!("failed to run cargo: {}", e))?;
+    if !status.success() {
+        exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// The environment variables that pass `bench-regression` options to the
+/// bench, with relative paths resolved against `cwd`.
+fn bench_env(args: &[String], cwd: &Path) -> Result<Vec<(&'static str, String)>, String> {
+    let mut vars = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let (_, var) = BENCH_OPTIONS
+            .iter()
+            .find(|(name, _)| *name == flag)
+            .ok_or_else(|| format!("unknown option '{}'", flag))?;
+        let value = inline
+            .or_else(|| args.next().cloned())
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        // Relative paths are the caller's, not the bench's working directory
+        let value = if matches!(flag, "--dir" | "--out" | "--baseline") {
+            cwd.join(value).display().to_string()
+        } else {
+            value
+        };
+        vars.push((*var, value));
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_bench_env() {
+        let cwd = Path::new("/work");
+        let vars = bench_env(
+            &args(&[
+                "--size-mb",
+                "64",
+                "--out=new.json",
+                "--baseline",
+                "/tmp/old.json",
+            ]),
+            cwd,
+        )
+        .unwrap();
+        assert_eq!(
+            vars,
+            [
+                ("ZDS_BENCH_SIZE_MB", "64".to_string()),
+                ("ZDS_BENCH_OUT", cwd.join("new.json").display().to_string()),
+                ("ZDS_BENCH_BASELINE", "/tmp/old.json".to_string()),
+            ]
+        );
+        assert!(bench_env(&[], cwd).unwrap().is_empty());
+
+        let err = bench_env(&args(&["--size", "64"]), cwd).unwrap_err();
+        assert_eq!(err, "unknown option '--size'");
+        let err = bench_env(&args(&["--runs"]), cwd).unwrap_err();
+        assert_eq!(err, "--runs needs a value");
+    }
+}